@@ -0,0 +1,70 @@
+use std::path::{Path, PathBuf};
+
+use bril_bench::cli::ConformCli;
+use bril_bench::conform::{find_suites, run_suites};
+use clap::Parser;
+
+// The `test` and `benchmarks` directories of the closest directory above the current one that has both
+fn default_roots() -> Option<Vec<PathBuf>> {
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors()
+        .find(|dir| dir.join("test").is_dir() && dir.join("benchmarks").is_dir())
+        .map(|dir| vec![dir.join("test"), dir.join("benchmarks")])
+}
+
+// Paths are shown relative to the current directory when they are under it
+fn show(path: &Path) -> String {
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+fn main() {
+    let args = ConformCli::parse();
+    let roots = if args.roots.is_empty() {
+        default_roots().unwrap_or_else(|| {
+            eprintln!("error: could not find the `test` and `benchmarks` directories of the repository, so give the directories to search");
+            std::process::exit(2)
+        })
+    } else {
+        args.roots
+    };
+    let suites = find_suites(&roots, &args.config).unwrap_or_else(|(path, e)| {
+        eprintln!("error: {}: {e}", path.display());
+        std::process::exit(2)
+    });
+    let jobs = args.jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+    });
+    let results = run_suites(&suites, jobs);
+
+    let names: Vec<String> = suites.iter().map(|suite| show(&suite.dir)).collect();
+    let width = names.iter().map(String::len).max().unwrap_or_default();
+    let (mut passed, mut total) = (0, 0);
+    for (name, results) in names.iter().zip(&results) {
+        let ok = results.iter().filter(|r| r.passed()).count();
+        let marks: String = results
+            .iter()
+            .map(|r| if r.passed() { '.' } else { 'F' })
+            .collect();
+        println!("{name:<width$}  {ok:>4}/{:<4} {marks}", results.len());
+        passed += ok;
+        total += results.len();
+    }
+
+    if !args.quiet {
+        for result in results.iter().flatten().filter(|r| !r.passed()) {
+            println!("\n{}", show(&result.test));
+            for problem in &result.problems {
+                println!("  {problem}");
+            }
+        }
+    }
+    println!("\n{passed} of {total} tests passed");
+    if passed != total {
+        std::process::exit(1)
+    }
+}
@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(about, version, author)] // keeps the cli synced with Cargo.toml
+pub struct Cli {
+    /// How many pipelines to run at once, which is one for each core by default
+    #[clap(short, long)]
+    pub jobs: Option<usize>,
+
+    /// The TOML config listing the runs to compare
+    pub config: PathBuf,
+
+    /// The benchmarks to run, in place of the `benchmarks` of the config
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(Parser)]
+#[clap(
+    name = "bril-conform",
+    about = "Runs the turnt tests of the Bril repository and reports which of them pass",
+    version,
+    author
+)]
+pub struct ConformCli {
+    /// How many tests to run at once, which is one for each core by default
+    #[clap(short, long)]
+    pub jobs: Option<usize>,
+
+    /// The name of the turnt configs to run the tests of
+    #[clap(short, long, default_value = crate::conform::DEFAULT_CONFIG)]
+    pub config: String,
+
+    /// Only print the matrix of results, and not the diffs of the tests which failed
+    #[clap(short, long)]
+    pub quiet: bool,
+
+    /// The directories to look for configs in, which are the `test` and `benchmarks` directories of the repository by default
+    pub roots: Vec<PathBuf>,
+}
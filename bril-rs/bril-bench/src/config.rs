@@ -0,0 +1,232 @@
+use std::time::Duration;
+
+use regex::Regex;
+use thiserror::Error;
+
+use crate::toml::{self, TomlError, Value};
+
+/// The default ```extract```, which finds the instruction count that `brili -p` and `brilirs -p` print
+pub const DEFAULT_EXTRACT: &str = r"total_dyn_inst: (\d+)";
+
+/// How long a pipeline can run for, in seconds, before it is stopped when the config doesn't say
+pub const DEFAULT_TIMEOUT: f64 = 5.0;
+
+/// What to benchmark and how, as read from a TOML config like
+/// ```toml
+/// extract = 'total_dyn_inst: (\d+)'
+/// benchmarks = '../benchmarks/core/*.bril'
+/// timeout = 5
+///
+/// [runs.baseline]
+/// pipeline = ["bril2json", "brilirs -p {args}"]
+///
+/// [runs.dce]
+/// pipeline = ["bril2json", "bril-opt -p dce", "brilirs -p {args}"]
+///
+/// [inputs]
+/// file = { from_file = true }
+/// small = "10"
+/// random = { seed = 1, min = 0, max = 100 }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// A regex whose first group, or the whole match if it has no groups, is the result of a run. It is looked for in the output of the last command of the pipeline and then in what it printed to stderr
+    pub extract: Regex,
+    /// Globs of the benchmarks to run when none are given on the command line, where `*` and `?` match within a file name and `**` matches any number of directories
+    pub benchmarks: Vec<String>,
+    /// How long each pipeline can run for before it is stopped
+    pub timeout: Duration,
+    /// The pipelines to compare, in the order they were written. The first is the one the others have to print the same thing as
+    pub runs: Vec<Run>,
+    /// The arguments to run each benchmark with, in the order they were written. Without any, each benchmark is run once with the arguments from its `ARGS:` comment
+    pub inputs: Vec<Input>,
+}
+
+/// A pipeline of shell commands, where the benchmark is given to the first on stdin and each command's output is given to the next
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    /// The name of the run in `[runs.<name>]`
+    pub name: String,
+    /// The commands of the pipeline, in which `{args}` is replaced by the arguments of the benchmark
+    pub pipeline: Vec<String>,
+}
+
+/// A set of arguments that every benchmark is run with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Input {
+    /// The name of the input in `[inputs]`
+    pub name: String,
+    /// Where the arguments come from
+    pub args: Arguments,
+}
+
+/// Where the arguments of a benchmark come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Arguments {
+    /// The `ARGS:` comment of the benchmark, written `{ from_file = true }`
+    FromFile,
+    /// The same arguments for every benchmark, written as a string
+    Given(String),
+    /// Random arguments of the types the `main` function of each benchmark takes, written `{ seed = 1, min = 0, max = 100 }`. The same seed always gives a benchmark the same arguments
+    Random {
+        /// The seed that the arguments of each benchmark are made from, along with its name
+        seed: u64,
+        /// The smallest `int` or `float` argument, which is 0 by default
+        min: i64,
+        /// The largest `int` or `float` argument, which is 100 by default
+        max: i64,
+    },
+}
+
+/// What is wrong with a config
+#[derive(Error, Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub enum ConfigError {
+    /// The config is not TOML that can be read
+    #[error(transparent)]
+    Toml(#[from] TomlError),
+    /// Something has the wrong type or is missing
+    #[error("`{0}` should be {1}")]
+    Invalid(String, &'static str),
+    /// A key which doesn't mean anything
+    #[error("unknown key `{0}`")]
+    Unknown(String),
+    /// The ```extract``` regex is not valid
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
+}
+
+impl Config {
+    /// Reads a config from its TOML
+    /// # Errors
+    /// Will return an error if ```src``` is not TOML, or it doesn't describe at least one run
+    pub fn from_toml(src: &str) -> Result<Self, ConfigError> {
+        let mut config = Self {
+            extract: Regex::new(DEFAULT_EXTRACT)?,
+            benchmarks: Vec::new(),
+            timeout: Duration::from_secs_f64(DEFAULT_TIMEOUT),
+            runs: Vec::new(),
+            inputs: Vec::new(),
+        };
+        for (key, value) in toml::parse(src)? {
+            match (key.as_str(), value) {
+                ("extract", Value::String(s)) => config.extract = Regex::new(&s)?,
+                ("benchmarks", Value::String(s)) => config.benchmarks = vec![s],
+                ("benchmarks", Value::Array(globs)) => {
+                    config.benchmarks = globs
+                        .into_iter()
+                        .map(|glob| match glob {
+                            Value::String(s) => Ok(s),
+                            _ => Err(invalid("benchmarks", "a string or an array of strings")),
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                #[allow(clippy::cast_precision_loss)]
+                ("timeout", Value::Integer(i)) if i > 0 => {
+                    config.timeout = Duration::from_secs_f64(i as f64);
+                }
+                ("timeout", Value::Float(f)) if f > 0.0 && f.is_finite() => {
+                    config.timeout = Duration::from_secs_f64(f);
+                }
+                ("runs", Value::Table(runs)) => {
+                    config.runs = runs
+                        .into_iter()
+                        .map(|(name, run)| Run::from_toml(name, run))
+                        .collect::<Result<_, _>>()?;
+                }
+                ("inputs", Value::Table(inputs)) => {
+                    config.inputs = inputs
+                        .into_iter()
+                        .map(|(name, input)| Input::from_toml(name, input))
+                        .collect::<Result<_, _>>()?;
+                }
+                ("extract", _) => return Err(invalid("extract", "a string")),
+                ("benchmarks", _) => {
+                    return Err(invalid("benchmarks", "a string or an array of strings"))
+                }
+                ("timeout", _) => return Err(invalid("timeout", "a positive number of seconds")),
+                ("runs" | "inputs", _) => return Err(invalid(&key, "a table")),
+                _ => return Err(ConfigError::Unknown(key)),
+            }
+        }
+        if config.runs.is_empty() {
+            return Err(invalid("runs", "a table of at least one run"));
+        }
+        Ok(config)
+    }
+}
+
+fn invalid(key: &str, expected: &'static str) -> ConfigError {
+    ConfigError::Invalid(key.to_string(), expected)
+}
+
+impl Run {
+    fn from_toml(name: String, run: Value) -> Result<Self, ConfigError> {
+        let key = format!("runs.{name}.pipeline");
+        let Value::Table(run) = run else {
+            return Err(invalid(&format!("runs.{name}"), "a table"));
+        };
+        let mut pipeline = None;
+        for (k, value) in run {
+            match (k.as_str(), value) {
+                ("pipeline", Value::Array(commands)) if !commands.is_empty() => {
+                    pipeline = Some(
+                        commands
+                            .into_iter()
+                            .map(|command| match command {
+                                Value::String(s) => Ok(s),
+                                _ => Err(invalid(&key, "an array of commands")),
+                            })
+                            .collect::<Result<_, _>>()?,
+                    );
+                }
+                ("pipeline", _) => return Err(invalid(&key, "an array of commands")),
+                _ => return Err(ConfigError::Unknown(format!("runs.{name}.{k}"))),
+            }
+        }
+        Ok(Self {
+            pipeline: pipeline.ok_or_else(|| invalid(&key, "an array of commands"))?,
+            name,
+        })
+    }
+}
+
+impl Input {
+    fn from_toml(name: String, input: Value) -> Result<Self, ConfigError> {
+        let key = format!("inputs.{name}");
+        let expected = "a string of arguments, `{ from_file = true }`, or `{ seed = N }`";
+        let args = match input {
+            Value::String(s) => Arguments::Given(s),
+            Value::Table(table) => {
+                let get = |k: &str| table.iter().find(|(key, _)| key == k).map(|(_, v)| v);
+                let int = |k: &str, default: i64| match get(k) {
+                    None => Ok(default),
+                    Some(Value::Integer(i)) => Ok(*i),
+                    Some(_) => Err(invalid(&format!("{key}.{k}"), "an integer")),
+                };
+                if let Some((k, _)) = table
+                    .iter()
+                    .find(|(k, _)| !matches!(k.as_str(), "from_file" | "seed" | "min" | "max"))
+                {
+                    return Err(ConfigError::Unknown(format!("{key}.{k}")));
+                }
+                match (get("from_file"), get("seed")) {
+                    (Some(Value::Boolean(true)), None) if table.len() == 1 => Arguments::FromFile,
+                    (None, Some(Value::Integer(seed))) => {
+                        let seed = u64::try_from(*seed).map_err(|_| {
+                            invalid(&format!("{key}.seed"), "a non-negative integer")
+                        })?;
+                        let (min, max) = (int("min", 0)?, int("max", 100)?);
+                        if min > max {
+                            return Err(invalid(&format!("{key}.min"), "at most `max`"));
+                        }
+                        Arguments::Random { seed, min, max }
+                    }
+                    _ => return Err(invalid(&key, expected)),
+                }
+            }
+            _ => return Err(invalid(&key, expected)),
+        };
+        Ok(Self { name, args })
+    }
+}
@@ -0,0 +1,388 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use regex::Regex;
+
+use crate::config::ConfigError;
+use crate::run::arguments;
+use crate::toml::{self, Value};
+
+/// The name of the turnt configs that `bril-conform` looks for by default, which are the ones that run `brilirs`
+pub const DEFAULT_CONFIG: &str = "turnt_brilirs.toml";
+
+/// The extensions of the files in a directory with a config which are tests, rather than their expected outputs or files they use
+pub const TEST_EXTENSIONS: [&str; 2] = ["bril", "repl"];
+
+/// The parts of a [turnt](https://github.com/cucapra/turnt) config that say how to run the tests next to it and what to check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TurntConfig {
+    /// The shell command which runs a test from the directory it is in, where `{filename}` is the name of the test's file, `{base}` is that name without its extension, and `{args}` is its arguments
+    pub command: String,
+    /// The outputs to check, each as the extension of the file holding what it should be along with where it comes from: `-` for stdout, `2` for stderr, and anything else for a file the command writes
+    pub outputs: Vec<(String, String)>,
+    /// The status the command should exit with, unless a test says otherwise with a `RETURN:` comment
+    pub return_code: i32,
+    /// The arguments of the tests which don't have an `ARGS:` comment
+    pub args: String,
+}
+
+impl TurntConfig {
+    /// Reads a config from its TOML
+    /// # Errors
+    /// Will return an error if ```src``` is not TOML, doesn't have a `command`, or has keys that aren't understood
+    pub fn from_toml(src: &str) -> Result<Self, ConfigError> {
+        let mut command = None;
+        let mut config = Self {
+            command: String::new(),
+            outputs: Vec::new(),
+            return_code: 0,
+            args: String::new(),
+        };
+        for (key, value) in toml::parse(src)? {
+            match (key.as_str(), value) {
+                ("command", Value::String(s)) => command = Some(s),
+                ("args", Value::String(s)) => config.args = s,
+                ("return_code", Value::Integer(i)) => {
+                    config.return_code =
+                        i32::try_from(i).map_err(|_| invalid("return_code", "an exit status"))?;
+                }
+                ("output", Value::Table(outputs)) => {
+                    config.outputs = outputs
+                        .into_iter()
+                        .map(|(ext, from)| match from {
+                            Value::String(from) => Ok((ext, from)),
+                            _ => Err(invalid(&format!("output.{ext}"), "a string")),
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                ("command" | "args", _) => return Err(invalid(&key, "a string")),
+                ("return_code", _) => return Err(invalid(&key, "an exit status")),
+                ("output", _) => return Err(invalid(&key, "a table")),
+                _ => return Err(ConfigError::Unknown(key)),
+            }
+        }
+        config.command = command.ok_or_else(|| invalid("command", "a string"))?;
+        // turnt checks stdout when a config doesn't say what to check
+        if config.outputs.is_empty() {
+            config.outputs.push(("out".to_string(), "-".to_string()));
+        }
+        Ok(config)
+    }
+}
+
+fn invalid(key: &str, expected: &'static str) -> ConfigError {
+    ConfigError::Invalid(key.to_string(), expected)
+}
+
+/// A directory of tests along with the config they are run with
+#[derive(Debug, Clone)]
+pub struct Suite {
+    /// The directory the config and tests are in
+    pub dir: PathBuf,
+    /// How to run the tests
+    pub config: TurntConfig,
+    /// The tests, in sorted order
+    pub tests: Vec<PathBuf>,
+}
+
+/// Finds every directory in or under ```roots``` with a turnt config called ```config```, along with the tests in it, in sorted order
+/// # Errors
+/// Will return the path of a config, with what is wrong with it, if it can't be read
+pub fn find_suites(roots: &[PathBuf], config: &str) -> Result<Vec<Suite>, (PathBuf, String)> {
+    let mut suites = Vec::new();
+    for root in roots {
+        for path in crate::glob(&format!("{}/**/{config}", root.display())) {
+            let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            let parsed = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|src| TurntConfig::from_toml(&src).map_err(|e| e.to_string()))
+                .map_err(|e| (path.clone(), e))?;
+            let mut tests: Vec<PathBuf> = std::fs::read_dir(&dir)
+                .map_err(|e| (dir.clone(), e.to_string()))?
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|p| {
+                    p.is_file()
+                        && p.extension()
+                            .is_some_and(|ext| TEST_EXTENSIONS.iter().any(|t| ext == *t))
+                })
+                .collect();
+            tests.sort();
+            suites.push(Suite {
+                dir,
+                config: parsed,
+                tests,
+            });
+        }
+    }
+    suites.sort_by(|a, b| a.dir.cmp(&b.dir));
+    suites.dedup_by(|a, b| a.dir == b.dir);
+    Ok(suites)
+}
+
+/// Something a test did which it shouldn't have
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The test or its command couldn't be read or started
+    Run(String),
+    /// The command exited with the wrong status, where [`None`] means it was killed by a signal
+    ReturnCode {
+        /// The status in the config or the test's `RETURN:` comment
+        expected: i32,
+        /// The status it exited with
+        found: Option<i32>,
+    },
+    /// There is no file saying what an output should be
+    Missing(PathBuf),
+    /// An output is different from what it should be
+    Mismatch {
+        /// The file holding what the output should be
+        expected: PathBuf,
+        /// The lines which are different, as made by [`diff`]
+        diff: String,
+    },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Run(e) => write!(f, "could not run: {e}"),
+            Self::ReturnCode {
+                expected,
+                found: Some(found),
+            } => write!(f, "exited with status {found} instead of {expected}"),
+            Self::ReturnCode {
+                expected,
+                found: None,
+            } => write!(f, "was killed instead of exiting with status {expected}"),
+            Self::Missing(path) => write!(f, "there is no `{}` to compare with", path.display()),
+            Self::Mismatch { expected, diff } => {
+                write!(f, "differs from `{}`:\n{diff}", expected.display())
+            }
+        }
+    }
+}
+
+/// How one test went, which passed if there were no problems
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestResult {
+    /// The file of the test
+    pub test: PathBuf,
+    /// Everything that was wrong, in the order the config lists the outputs
+    pub problems: Vec<Problem>,
+}
+
+impl TestResult {
+    /// Whether the test did everything it should have
+    #[must_use]
+    pub const fn passed(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Runs every test of ```suites```, up to ```jobs``` at once, and compares what each does with what it should. The results of each suite are in the order of its tests, no matter which finishes first.
+/// # Panics
+/// Will panic if running a test panics, which shouldn't happen
+#[must_use]
+pub fn run_suites(suites: &[Suite], jobs: usize) -> Vec<Vec<TestResult>> {
+    let work: Vec<(usize, usize)> = suites
+        .iter()
+        .enumerate()
+        .flat_map(|(s, suite)| (0..suite.tests.len()).map(move |t| (s, t)))
+        .collect();
+    let next = AtomicUsize::new(0);
+    let finished = Mutex::new(vec![None; work.len()]);
+    thread::scope(|s| {
+        for _ in 0..jobs.clamp(1, work.len().max(1)) {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&(suite, test)) = work.get(i) else {
+                    return;
+                };
+                let suite = &suites[suite];
+                let result = run_test(suite, &suite.tests[test]);
+                finished.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+
+    let mut finished = finished.into_inner().unwrap().into_iter().flatten();
+    suites
+        .iter()
+        .map(|suite| finished.by_ref().take(suite.tests.len()).collect())
+        .collect()
+}
+
+fn run_test(suite: &Suite, test: &Path) -> TestResult {
+    let problems = check_test(suite, test).unwrap_or_else(|e| vec![Problem::Run(e)]);
+    TestResult {
+        test: test.to_path_buf(),
+        problems,
+    }
+}
+
+fn check_test(suite: &Suite, test: &Path) -> Result<Vec<Problem>, String> {
+    let source = std::fs::read_to_string(test).map_err(|e| e.to_string())?;
+    let name = test
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let stem = test
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let test_args = match arguments(&crate::config::Arguments::FromFile, &stem, &source)? {
+        args if args.is_empty() && !source.contains("ARGS:") => suite.config.args.clone(),
+        args => args,
+    };
+    let expected_code = Regex::new(r"RETURN: (-?\d+)")
+        .unwrap()
+        .captures(&source)
+        .and_then(|c| c[1].parse().ok())
+        .unwrap_or(suite.config.return_code);
+    let fill = |template: &str| {
+        template
+            .replace("{filename}", &name)
+            .replace("{base}", &stem)
+            .replace("{args}", &test_args)
+    };
+
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(fill(&suite.config.command))
+        .current_dir(&suite.dir)
+        // Otherwise the `cargo run` in most configs adds how it built the interpreter to the stderr being checked
+        .env("CARGO_TERM_QUIET", "true")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    let mut problems = Vec::new();
+    if output.status.code() != Some(expected_code) {
+        problems.push(Problem::ReturnCode {
+            expected: expected_code,
+            found: output.status.code(),
+        });
+    }
+    for (ext, from) in &suite.config.outputs {
+        let actual = match from.as_str() {
+            "-" => String::from_utf8_lossy(&output.stdout).into_owned(),
+            "2" => String::from_utf8_lossy(&output.stderr).into_owned(),
+            path => std::fs::read_to_string(suite.dir.join(fill(path))).unwrap_or_default(),
+        };
+        let expected_path = suite.dir.join(format!("{stem}.{ext}"));
+        match std::fs::read_to_string(&expected_path) {
+            Err(_) => problems.push(Problem::Missing(expected_path)),
+            Ok(expected) if expected != actual => problems.push(Problem::Mismatch {
+                expected: expected_path,
+                diff: diff(&expected, &actual),
+            }),
+            Ok(_) => {}
+        }
+    }
+    Ok(problems)
+}
+
+// How many lines of a diff to show on each side of a change
+const CONTEXT: usize = 2;
+
+// Beyond this many pairs of lines, a diff just shows everything that isn't the same at the start and end as changed
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// The lines of ```expected``` and ```actual``` which are different, marked `-` when only ```expected``` has them and `+` when only ```actual``` does, along with a couple of the lines around them which are the same
+#[must_use]
+pub fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_mid, new_mid) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut lines: Vec<Line> = old[..prefix].iter().map(|l| Line::Same(l)).collect();
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_DIFF_CELLS {
+        lines.extend(old_mid.iter().map(|l| Line::Removed(l)));
+        lines.extend(new_mid.iter().map(|l| Line::Added(l)));
+    } else {
+        lines.extend(longest_common(old_mid, new_mid));
+    }
+    lines.extend(old[old.len() - suffix..].iter().map(|l| Line::Same(l)));
+    if lines.iter().all(|l| matches!(l, Line::Same(_))) {
+        // The only difference is in the line endings
+        return "  (the last line ends differently)".to_string();
+    }
+
+    let changed: Vec<usize> = (0..lines.len())
+        .filter(|i| !matches!(lines[*i], Line::Same(_)))
+        .collect();
+    let mut text = Vec::new();
+    let mut shown_to = 0;
+    for &i in &changed {
+        let from = i.saturating_sub(CONTEXT).max(shown_to);
+        if from > shown_to {
+            text.push("  ...".to_string());
+        }
+        let to = (i + CONTEXT + 1).min(lines.len());
+        for line in &lines[from.max(shown_to)..to] {
+            text.push(match line {
+                Line::Same(l) => format!("  {l}"),
+                Line::Removed(l) => format!("- {l}"),
+                Line::Added(l) => format!("+ {l}"),
+            });
+        }
+        shown_to = shown_to.max(to);
+    }
+    if shown_to < lines.len() {
+        text.push("  ...".to_string());
+    }
+    text.join("\n")
+}
+
+// The lines of ```old``` and ```new``` with as many as possible kept the same
+fn longest_common<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    // lengths[i][j] is the length of the longest common sequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0_usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
@@ -0,0 +1,73 @@
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+/// The files matching ```pattern```, in sorted order. `*` matches any part of a file name and `?` any one character, neither of them matching a leading `.`, and a `**` component matches any number of directories.
+#[must_use]
+pub fn glob(pattern: &str) -> Vec<PathBuf> {
+    let (start, rest) = pattern
+        .strip_prefix('/')
+        .map_or((PathBuf::new(), pattern), |rest| (PathBuf::from("/"), rest));
+    let parts: Vec<_> = rest.split('/').filter(|p| !p.is_empty()).collect();
+    let mut found = Vec::new();
+    expand(start, &parts, &mut found);
+    found.sort();
+    found.dedup();
+    found
+}
+
+fn expand(dir: PathBuf, parts: &[&str], found: &mut Vec<PathBuf>) {
+    let Some((part, rest)) = parts.split_first() else {
+        if dir.is_file() {
+            found.push(dir);
+        }
+        return;
+    };
+    if *part == "**" {
+        for entry in entries(&dir).into_iter().filter(|e| e.is_dir()) {
+            expand(entry, parts, found);
+        }
+        expand(dir, rest, found);
+    } else if part.contains(['*', '?']) {
+        let regex = to_regex(part);
+        for entry in entries(&dir) {
+            if entry
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| regex.is_match(name))
+            {
+                expand(entry, rest, found);
+            }
+        }
+    } else {
+        expand(dir.join(part), rest, found);
+    }
+}
+
+// The entries of ```dir``` which aren't hidden, where an empty path is the current directory
+fn entries(dir: &Path) -> Vec<PathBuf> {
+    let read = if dir.as_os_str().is_empty() {
+        std::fs::read_dir(".")
+    } else {
+        std::fs::read_dir(dir)
+    };
+    read.into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .map(|entry| dir.join(entry.file_name()))
+        .collect()
+}
+
+fn to_regex(part: &str) -> Regex {
+    let mut regex = String::from("^");
+    for c in part.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).unwrap()
+}
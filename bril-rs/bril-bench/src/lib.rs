@@ -0,0 +1,59 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
+
+use std::io::Write;
+
+#[doc(hidden)]
+pub mod cli;
+/// Provides ```config::Config```, which says what to benchmark and how
+pub mod config;
+/// Provides ```conform::run_suites```, which runs the turnt tests of the repository with the expected outputs next to them
+pub mod conform;
+mod glob;
+/// Provides ```run::run_benchmarks```, which runs every benchmark through every pipeline in parallel
+pub mod run;
+/// Provides the subset of TOML that configs are written in
+pub mod toml;
+
+pub use glob::glob;
+
+/// Writes ```rows``` to ```out``` as a CSV table with one row for each benchmark and input, and one column for each run of ```config```. The input column is left out when the config doesn't have any.
+/// # Errors
+/// Will return an error if writing to ```out``` fails
+pub fn write_csv(
+    mut out: impl Write,
+    config: &config::Config,
+    rows: &[run::Row],
+) -> std::io::Result<()> {
+    let mut header = vec!["benchmark".to_string()];
+    if !config.inputs.is_empty() {
+        header.push("input".to_string());
+    }
+    header.extend(config.runs.iter().map(|run| run.name.clone()));
+    write_csv_row(&mut out, &header)?;
+    for row in rows {
+        let cells = std::iter::once(row.benchmark.clone())
+            .chain(row.input.clone())
+            .chain(row.outcomes.iter().map(ToString::to_string))
+            .collect::<Vec<_>>();
+        write_csv_row(&mut out, &cells)?;
+    }
+    Ok(())
+}
+
+fn write_csv_row(out: &mut impl Write, cells: &[String]) -> std::io::Result<()> {
+    let cells: Vec<_> = cells
+        .iter()
+        .map(|cell| {
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.clone()
+            }
+        })
+        .collect();
+    writeln!(out, "{}", cells.join(","))
+}
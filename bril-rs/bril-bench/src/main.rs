@@ -0,0 +1,31 @@
+use bril_bench::cli::Cli;
+use bril_bench::config::Config;
+use clap::Parser;
+
+fn main() {
+    let args = Cli::parse();
+    let config = std::fs::read_to_string(&args.config)
+        .map_err(|e| e.to_string())
+        .and_then(|src| Config::from_toml(&src).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}: {e}", args.config.display());
+            std::process::exit(1)
+        });
+    let files = if args.files.is_empty() {
+        config
+            .benchmarks
+            .iter()
+            .flat_map(|pattern| bril_bench::glob(pattern))
+            .collect()
+    } else {
+        args.files
+    };
+    let jobs = args.jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+    });
+    let rows = bril_bench::run::run_benchmarks(&config, &files, jobs).unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        std::process::exit(1)
+    });
+    bril_bench::write_csv(std::io::stdout().lock(), &config, &rows).unwrap();
+}
@@ -0,0 +1,309 @@
+use std::fmt::Display;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bril_rs::{AbstractProgram, AbstractType};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use regex::Regex;
+use thiserror::Error;
+
+use crate::config::{Arguments, Config};
+
+/// What one run of a benchmark came to, which is what goes in its cell of the table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// What ```extract``` found, like the number of instructions that ran
+    Result(String),
+    /// The pipeline was still running when it ran out of time
+    Timeout,
+    /// The pipeline printed something different from the first run of the benchmark
+    Incorrect,
+    /// ```extract``` didn't find anything
+    Missing,
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Result(result) => write!(f, "{result}"),
+            Self::Timeout => write!(f, "timeout"),
+            Self::Incorrect => write!(f, "incorrect"),
+            Self::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+/// One benchmark with one set of arguments, along with what each run of it came to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    /// The name of the benchmark's file without its extension
+    pub benchmark: String,
+    /// The name of the input the benchmark was run with, if the config has any
+    pub input: Option<String>,
+    /// What each run came to, in the order of the runs of the config
+    pub outcomes: Vec<Outcome>,
+}
+
+/// What can stop the benchmarks from being run at all
+#[derive(Error, Debug)]
+pub enum BenchError {
+    /// A benchmark could not be read
+    #[error("could not read `{0}`: {1}")]
+    Read(String, std::io::Error),
+    /// Random arguments could not be made for a benchmark
+    #[error("could not make arguments for `{0}`: {1}")]
+    Arguments(String, String),
+    /// A pipeline could not be started
+    #[error("could not run `{0}`: {1}")]
+    Spawn(String, std::io::Error),
+}
+
+// A benchmark with the arguments of one of the inputs
+struct Task {
+    benchmark: String,
+    input: Option<String>,
+    source: usize,
+    args: String,
+}
+
+// How a pipeline finished, with what its last command printed
+enum Finished {
+    Done { stdout: String, stderr: String },
+    TimedOut,
+}
+
+/// Runs every benchmark in ```files``` with each input of ```config``` through each of its runs.
+///
+/// Up to ```jobs``` pipelines are run alongside each other. The rows are in the order of ```files``` and then of the inputs, no matter which finishes first.
+/// # Errors
+/// Will return an error if a benchmark can't be read, random arguments can't be made for it, or a pipeline can't be started
+/// # Panics
+/// Will panic if running a pipeline panics, which shouldn't happen
+pub fn run_benchmarks(
+    config: &Config,
+    files: &[PathBuf],
+    jobs: usize,
+) -> Result<Vec<Row>, BenchError> {
+    let mut sources = Vec::new();
+    let mut tasks = Vec::new();
+    for file in files {
+        let name = file.display().to_string();
+        let source =
+            std::fs::read_to_string(file).map_err(|e| BenchError::Read(name.clone(), e))?;
+        let benchmark = file
+            .file_stem()
+            .map_or_else(|| name.clone(), |stem| stem.to_string_lossy().into_owned());
+        if config.inputs.is_empty() {
+            tasks.push(Task {
+                args: arguments(&Arguments::FromFile, &benchmark, &source)
+                    .map_err(|e| BenchError::Arguments(name.clone(), e))?,
+                benchmark: benchmark.clone(),
+                input: None,
+                source: sources.len(),
+            });
+        }
+        for input in &config.inputs {
+            tasks.push(Task {
+                benchmark: benchmark.clone(),
+                input: Some(input.name.clone()),
+                source: sources.len(),
+                args: arguments(&input.args, &benchmark, &source)
+                    .map_err(|e| BenchError::Arguments(name.clone(), e))?,
+            });
+        }
+        sources.push(source);
+    }
+
+    let work: Vec<_> = (0..tasks.len())
+        .flat_map(|task| (0..config.runs.len()).map(move |run| (task, run)))
+        .collect();
+    let next = AtomicUsize::new(0);
+    let finished = Mutex::new((0..work.len()).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|s| {
+        for _ in 0..jobs.clamp(1, work.len().max(1)) {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&(task, run)) = work.get(i) else {
+                    return;
+                };
+                let task = &tasks[task];
+                let pipeline: Vec<_> = config.runs[run]
+                    .pipeline
+                    .iter()
+                    .map(|command| command.replace("{args}", &task.args))
+                    .collect();
+                let result = run_pipeline(&pipeline, &sources[task.source], config.timeout);
+                finished.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+
+    let mut finished = finished.into_inner().unwrap().into_iter().flatten();
+    tasks
+        .into_iter()
+        .map(|task| {
+            let mut expected = None;
+            let outcomes = (0..config.runs.len())
+                .map(|_| {
+                    Ok(match finished.next().unwrap()? {
+                        Finished::TimedOut => {
+                            expected.get_or_insert_with(String::new);
+                            Outcome::Timeout
+                        }
+                        Finished::Done { stdout, stderr } => {
+                            let expected = expected.get_or_insert_with(|| stdout.clone());
+                            if *expected == stdout {
+                                extract(&config.extract, &stdout)
+                                    .or_else(|| extract(&config.extract, &stderr))
+                                    .map_or(Outcome::Missing, Outcome::Result)
+                            } else {
+                                Outcome::Incorrect
+                            }
+                        }
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Row {
+                benchmark: task.benchmark,
+                input: task.input,
+                outcomes,
+            })
+        })
+        .collect()
+}
+
+fn extract(regex: &Regex, text: &str) -> Option<String> {
+    let captures = regex.captures(text)?;
+    captures
+        .get(1)
+        .or_else(|| captures.get(0))
+        .map(|m| m.as_str().to_string())
+}
+
+/// The arguments that ```args``` gives the benchmark called ```name``` whose text or JSON is ```source```
+/// # Errors
+/// Will return an error if random arguments are asked for but the benchmark can't be parsed, or its `main` takes an argument which can't be made at random
+/// # Panics
+/// Will not panic, since the regex for `ARGS:` comments is valid
+pub fn arguments(args: &Arguments, name: &str, source: &str) -> Result<String, String> {
+    match args {
+        Arguments::FromFile => Ok(Regex::new(r"ARGS: (.*)")
+            .unwrap()
+            .captures(source)
+            .map_or_else(String::new, |c| c[1].to_string())),
+        Arguments::Given(args) => Ok(args.clone()),
+        Arguments::Random { seed, min, max } => {
+            let prog: AbstractProgram = if source.trim_start().starts_with('{') {
+                serde_json::from_str(source).map_err(|e| e.to_string())?
+            } else {
+                bril2json::parse_abstract_program_from_str(source, false)
+                    .map_err(|e| e.to_string())?
+            };
+            let Some(main) = prog.functions.iter().find(|f| f.name == "main") else {
+                return Ok(String::new());
+            };
+            // Mixing in the name gives each benchmark different arguments, while staying the same from one run to the next
+            let mut rng = StdRng::seed_from_u64(seed ^ fnv1a(name));
+            let args = main
+                .args
+                .iter()
+                .map(|arg| match &arg.arg_type {
+                    AbstractType::Primitive(t) if t == "int" => {
+                        Ok(rng.gen_range(*min..=*max).to_string())
+                    }
+                    AbstractType::Primitive(t) if t == "bool" => Ok(rng.gen::<bool>().to_string()),
+                    #[allow(clippy::cast_precision_loss)]
+                    AbstractType::Primitive(t) if t == "float" => {
+                        Ok(format!("{:.3}", rng.gen_range(*min as f64..=*max as f64)))
+                    }
+                    AbstractType::Primitive(t) if t == "char" => {
+                        Ok(char::from(rng.gen_range(b'a'..=b'z')).to_string())
+                    }
+                    t => Err(format!(
+                        "`main` takes `{}: {t}`, which can't be made at random",
+                        arg.name
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(args.join(" "))
+        }
+    }
+}
+
+// A hash which, unlike those of the standard library, is the same wherever and whenever it is run
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// Kills every command of a pipeline which is still running once it is no longer needed
+struct Pipeline(Vec<Child>);
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        for child in &mut self.0 {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+// Runs ```commands``` with `sh`, piping ```input``` into the first and each command's output into the next. Only what the last command prints to stderr is kept
+fn run_pipeline(
+    commands: &[String],
+    input: &str,
+    timeout: Duration,
+) -> Result<Finished, BenchError> {
+    let deadline = Instant::now() + timeout;
+    let mut pipeline = Pipeline(Vec::new());
+    let mut stdin = Some(Stdio::piped());
+    for (i, command) in commands.iter().enumerate() {
+        let last = i + 1 == commands.len();
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(stdin.take().unwrap())
+            .stdout(Stdio::piped())
+            .stderr(if last { Stdio::piped() } else { Stdio::null() })
+            .spawn()
+            .map_err(|e| BenchError::Spawn(command.clone(), e))?;
+        if !last {
+            stdin = Some(child.stdout.take().unwrap().into());
+        }
+        pipeline.0.push(child);
+    }
+
+    let mut first = pipeline.0[0].stdin.take().unwrap();
+    let input = input.to_string();
+    // The command might stop reading before it is given everything, which isn't a problem
+    thread::spawn(move || first.write_all(input.as_bytes()));
+    let last = pipeline.0.last_mut().unwrap();
+    // These are read from on their own threads so that neither pipe can fill up and stall the command, and are left behind on a timeout in case something the command started keeps them open
+    let stdout = read_in_background(last.stdout.take().unwrap());
+    let stderr = read_in_background(last.stderr.take().unwrap());
+    loop {
+        match last.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(5)),
+            _ => return Ok(Finished::TimedOut),
+        }
+    }
+    let stdout = stdout.join().unwrap();
+    let stderr = stderr.join().unwrap();
+    Ok(Finished::Done { stdout, stderr })
+}
+
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        String::from_utf8_lossy(&bytes).into_owned()
+    })
+}
@@ -0,0 +1,339 @@
+use thiserror::Error;
+
+/// A value in a TOML document
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// `"a"` or `'a'`
+    String(String),
+    /// `1`
+    Integer(i64),
+    /// `1.5`
+    Float(f64),
+    /// `true`
+    Boolean(bool),
+    /// `[1, 2]`
+    Array(Vec<Self>),
+    /// `[table]` or `{ a = 1 }`
+    Table(Table),
+}
+
+/// The keys of a table along with their values, in the order they were written
+pub type Table = Vec<(String, Value)>;
+
+/// What is wrong with a TOML document, and on which line
+#[derive(Error, Debug)]
+#[error("line {line}: {message}")]
+pub struct TomlError {
+    /// The line the problem was found on, starting from 1
+    pub line: usize,
+    /// What the problem is
+    pub message: String,
+}
+
+/// Parses the subset of TOML that configs need: comments, `[table]` headers, dotted and quoted keys, strings without the multi-line forms, integers, floats, booleans, arrays, and inline tables.
+/// # Errors
+/// Will return an error if ```src``` is not TOML, or uses parts of it which aren't supported like dates and arrays of tables
+pub fn parse(src: &str) -> Result<Table, TomlError> {
+    let mut parser = Parser {
+        chars: src.chars().collect(),
+        at: 0,
+        line: 1,
+    };
+    let mut root = Table::new();
+    let mut current = Vec::new();
+    loop {
+        parser.skip_blank_lines();
+        match parser.peek() {
+            None => return Ok(root),
+            Some('[') => {
+                parser.at += 1;
+                if parser.peek() == Some('[') {
+                    return Err(parser.error("arrays of tables are not supported"));
+                }
+                parser.skip_spaces();
+                current = parser.key()?;
+                parser.skip_spaces();
+                parser.expect(']')?;
+                // Make sure the table exists even if nothing is ever put in it
+                table_at(&mut root, &current).map_err(|message| parser.error(message))?;
+            }
+            Some(_) => {
+                let (key, value) = parser.key_value()?;
+                insert(&mut root, &current, &key, value)
+                    .map_err(|message| parser.error(message))?;
+            }
+        }
+        parser.end_of_line()?;
+    }
+}
+
+// Finds the table at ```path``` in ```root```, making any that don't exist yet
+fn table_at<'a>(root: &'a mut Table, path: &[String]) -> Result<&'a mut Table, String> {
+    let mut table = root;
+    for key in path {
+        let index = table.iter().position(|(k, _)| k == key).unwrap_or_else(|| {
+            table.push((key.clone(), Value::Table(Table::new())));
+            table.len() - 1
+        });
+        table = match &mut table[index].1 {
+            Value::Table(t) => t,
+            _ => return Err(format!("`{key}` is not a table")),
+        };
+    }
+    Ok(table)
+}
+
+fn insert(root: &mut Table, table: &[String], key: &[String], value: Value) -> Result<(), String> {
+    let (last, parents) = key.split_last().unwrap();
+    let path: Vec<_> = table.iter().chain(parents).cloned().collect();
+    let table = table_at(root, &path)?;
+    if table.iter().any(|(k, _)| k == last) {
+        return Err(format!("`{last}` is defined twice"));
+    }
+    table.push((last.clone(), value));
+    Ok(())
+}
+
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.at += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: impl Into<String>) -> TomlError {
+        TomlError {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), TomlError> {
+        if self.peek() == Some(c) {
+            self.next();
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{c}`")))
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.next();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.next();
+            }
+        }
+    }
+
+    // Skips whitespace, newlines, and comments, which can go between the values of arrays as well as between lines
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n' | '\r') => {
+                    self.next();
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), TomlError> {
+        self.skip_spaces();
+        self.skip_comment();
+        if self.peek() == Some('\r') {
+            self.next();
+        }
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.next();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("unexpected `{c}` at the end of the line"))),
+        }
+    }
+
+    // A key, which is made up of bare or quoted parts separated by dots
+    fn key(&mut self) -> Result<Vec<String>, TomlError> {
+        let mut parts = Vec::new();
+        loop {
+            parts.push(match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.at;
+                    while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    {
+                        self.next();
+                    }
+                    if self.at == start {
+                        return Err(self.error("expected a key"));
+                    }
+                    self.chars[start..self.at].iter().collect()
+                }
+            });
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                return Ok(parts);
+            }
+            self.next();
+            self.skip_spaces();
+        }
+    }
+
+    fn key_value(&mut self) -> Result<(Vec<String>, Value), TomlError> {
+        let key = self.key()?;
+        self.skip_spaces();
+        self.expect('=')?;
+        self.skip_spaces();
+        Ok((key, self.value()?))
+    }
+
+    fn value(&mut self) -> Result<Value, TomlError> {
+        match self.peek() {
+            Some('"') => self.basic_string().map(Value::String),
+            Some('\'') => self.literal_string().map(Value::String),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some('t' | 'f') => {
+                for (word, b) in [("true", true), ("false", false)] {
+                    if self.chars[self.at..].starts_with(&word.chars().collect::<Vec<_>>()) {
+                        self.at += word.len();
+                        return Ok(Value::Boolean(b));
+                    }
+                }
+                Err(self.error("expected a value"))
+            }
+            Some(c) if c.is_ascii_digit() || c == '+' || c == '-' => self.number(),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, TomlError> {
+        self.expect('"')?;
+        if self.peek() == Some('"') && self.chars.get(self.at + 1) == Some(&'"') {
+            return Err(self.error("multi-line strings are not supported"));
+        }
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(s),
+                Some('\\') => s.push(match self.next() {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some(c @ ('u' | 'U')) => {
+                        let len = if c == 'u' { 4 } else { 8 };
+                        let digits: String = self.chars.iter().skip(self.at).take(len).collect();
+                        self.at += len;
+                        u32::from_str_radix(&digits, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| self.error(format!("invalid escape `\\{c}{digits}`")))?
+                    }
+                    _ => return Err(self.error("invalid escape")),
+                }),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, TomlError> {
+        self.expect('\'')?;
+        if self.peek() == Some('\'') && self.chars.get(self.at + 1) == Some(&'\'') {
+            return Err(self.error("multi-line strings are not supported"));
+        }
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('\'') => return Ok(s),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, TomlError> {
+        let start = self.at;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_'))
+        {
+            self.next();
+        }
+        let text: String = self.chars[start..self.at]
+            .iter()
+            .filter(|c| **c != '_')
+            .collect();
+        text.parse()
+            .map(Value::Integer)
+            .or_else(|_| text.parse().map(Value::Float))
+            .map_err(|_| self.error(format!("`{text}` is not a number")))
+    }
+
+    fn array(&mut self) -> Result<Value, TomlError> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        loop {
+            self.skip_blank_lines();
+            if self.peek() == Some(']') {
+                self.next();
+                return Ok(Value::Array(values));
+            }
+            values.push(self.value()?);
+            self.skip_blank_lines();
+            match self.peek() {
+                Some(',') => {
+                    self.next();
+                }
+                Some(']') => {}
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value, TomlError> {
+        self.expect('{')?;
+        let mut table = Table::new();
+        self.skip_spaces();
+        if self.peek() == Some('}') {
+            self.next();
+            return Ok(Value::Table(table));
+        }
+        loop {
+            self.skip_spaces();
+            let (key, value) = self.key_value()?;
+            insert(&mut table, &[], &key, value).map_err(|message| self.error(message))?;
+            self.skip_spaces();
+            match self.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Table(table)),
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(about, version, author)] // keeps the cli synced with Cargo.toml
+pub struct Cli {
+    /// The program before the change, in JSON or as text if its name ends in `.bril`. `-` reads it from stdin
+    pub old: PathBuf,
+
+    /// The program after the change, read like the first
+    pub new: PathBuf,
+
+    /// Arguments to run `main` with, separated by spaces. Can be given more than once
+    #[clap(long = "args", allow_hyphen_values(true))]
+    pub args: Vec<String>,
+
+    /// A file of inputs to run `main` with, one line of arguments per input, like `bril-symex --inputs` prints. `-` reads them from stdin
+    #[clap(long)]
+    pub inputs: Option<PathBuf>,
+
+    /// Also run `main` on this many random inputs. When no inputs are given, 10 are used if `main` takes arguments
+    #[clap(long)]
+    pub random: Option<usize>,
+
+    /// The seed for the random inputs
+    #[clap(long, default_value = "0")]
+    pub seed: u64,
+
+    /// The smallest random integer or float
+    #[clap(long, default_value = "-100", allow_hyphen_values(true))]
+    pub min: i64,
+
+    /// The largest random integer or float
+    #[clap(long, default_value = "100", allow_hyphen_values(true))]
+    pub max: i64,
+
+    /// How many instructions each version can run on an input before it counts as not terminating
+    #[clap(long, default_value_t = crate::DEFAULT_BUDGET)]
+    pub budget: u64,
+}
@@ -0,0 +1,280 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
+
+use std::fmt::{self, Display, Formatter};
+use std::panic::{self, AssertUnwindSafe};
+
+use bril_rs::{Program, Type};
+use brilirs::basic_block::BBProgram;
+use brilirs::error::{InterpError, Limit};
+use brilirs::interp::{self, Options};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use thiserror::Error;
+
+#[doc(hidden)]
+pub mod cli;
+
+/// How many instructions each version of a program can run on an input by default before it counts as not terminating
+pub const DEFAULT_BUDGET: u64 = 1_000_000;
+
+/// How a run of one version of a program ended
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    /// `main` returned
+    Returned,
+    /// The program stopped with an error, or couldn't be run at all because it doesn't type check
+    Error,
+    /// The program ran out of instructions before finishing, so it may never have terminated
+    OutOfBudget,
+}
+
+/// What running one version of a program on an input did
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    /// How the run ended
+    pub status: Status,
+    /// Everything the program printed, up to where it stopped
+    pub output: String,
+    /// The message of the error the program stopped with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// How many instructions ran, if the program returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<u64>,
+}
+
+/// Whether two versions of a program behaved the same on an input
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Verdict {
+    /// Both versions printed the same thing and ended the same way. Two errors count as the same even if their messages differ, since those mention variables and positions which transformations change
+    Equivalent,
+    /// The versions printed different things
+    OutputDiffers,
+    /// One version returned and the other stopped with an error, whatever they printed
+    StatusDiffers,
+    /// One version finished within the budget and the other didn't
+    TerminationDiffers,
+    /// Neither version finished within the budget, and what one printed is the start of what the other did, so they may still agree
+    Inconclusive,
+}
+
+impl Verdict {
+    /// Whether the versions were seen to behave differently
+    #[must_use]
+    pub const fn diverges(self) -> bool {
+        !matches!(self, Self::Equivalent | Self::Inconclusive)
+    }
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Equivalent => "equivalent",
+            Self::OutputDiffers => "output-differs",
+            Self::StatusDiffers => "status-differs",
+            Self::TerminationDiffers => "termination-differs",
+            Self::Inconclusive => "inconclusive",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The verdict for one input, along with what each version did on it
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    /// The arguments `main` was given
+    pub args: Vec<String>,
+    /// Whether the versions behaved the same
+    pub verdict: Verdict,
+    /// What the old version did
+    pub old: Run,
+    /// What the new version did
+    pub new: Run,
+}
+
+/// How ```old``` and ```new``` compare, which are the runs of two versions of a program on the same input
+#[must_use]
+pub fn verdict(old: &Run, new: &Run) -> Verdict {
+    let finished = |r: &Run| r.status != Status::OutOfBudget;
+    match (finished(old), finished(new)) {
+        (true, true) if old.status != new.status => Verdict::StatusDiffers,
+        (true, true) if old.output != new.output => Verdict::OutputDiffers,
+        (true, true) => Verdict::Equivalent,
+        (false, false) => {
+            if old.output.starts_with(&new.output) || new.output.starts_with(&old.output) {
+                Verdict::Inconclusive
+            } else {
+                Verdict::OutputDiffers
+            }
+        }
+        _ => Verdict::TerminationDiffers,
+    }
+}
+
+/// A version of a program, ready to be run on inputs
+pub struct Version {
+    // The program, or why brilirs can't run it
+    prog: Result<BBProgram, String>,
+}
+
+impl Version {
+    /// Prepares ```prog``` for `brilirs`, including type checking it. A program which doesn't type check stops with that error on every input
+    #[must_use]
+    pub fn new(prog: Program) -> Self {
+        let prog = BBProgram::new(prog)
+            .map_err(|e| e.to_string())
+            .and_then(|bbprog| {
+                brilirs::check::type_check(&bbprog)
+                    .map(|()| bbprog)
+                    .map_err(|e| e.to_string())
+            });
+        Self { prog }
+    }
+
+    /// Runs `main` with ```args```, stopping after ```budget``` instructions. A panic in the interpreter counts as an error
+    #[must_use]
+    pub fn run(&self, args: &[String], budget: u64) -> Run {
+        let bbprog = match &self.prog {
+            Ok(bbprog) => bbprog,
+            Err(e) => {
+                return Run {
+                    status: Status::Error,
+                    output: String::new(),
+                    error: Some(e.clone()),
+                    instructions: None,
+                }
+            }
+        };
+        let mut output = Vec::new();
+        let options = Options {
+            collect_stats: true,
+            max_instructions: Some(budget),
+            ..Options::default()
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            interp::execute_main_with_options(bbprog, &mut output, args, options)
+        }));
+        let output = String::from_utf8_lossy(&output).into_owned();
+        match result {
+            Ok(Ok(report)) => Run {
+                status: Status::Returned,
+                output,
+                error: None,
+                instructions: report.stats.map(|s| s.total_dyn_inst),
+            },
+            Ok(Err(e)) => Run {
+                status: if matches!(
+                    e.error(),
+                    InterpError::ResourceLimit(Limit::Instructions(_), _)
+                ) {
+                    Status::OutOfBudget
+                } else {
+                    Status::Error
+                },
+                output,
+                error: Some(e.to_string()),
+                instructions: None,
+            },
+            Err(_) => Run {
+                status: Status::Error,
+                output,
+                error: Some("brilirs panicked".to_string()),
+                instructions: None,
+            },
+        }
+    }
+}
+
+/// Runs ```old``` and ```new``` on ```args``` with a budget of ```budget``` instructions each, and compares what they did
+/// ```
+/// use bril_equiv::{compare, Verdict, Version};
+/// use bril_rs::Program;
+///
+/// let version = |text: &str| {
+///     let prog = bril2json::parse_abstract_program_from_str(text, false).unwrap();
+///     Version::new(Program::try_from(prog).unwrap())
+/// };
+/// let old = version("@main(x: int) { two: int = const 2; y: int = mul x two; print y; }");
+/// let new = version("@main(x: int) { y: int = add x x; print y; }");
+/// let comparison = compare(&old, &new, &["21".to_string()], 100);
+/// assert_eq!(comparison.verdict, Verdict::Equivalent);
+/// assert_eq!(comparison.new.output, "42\n");
+/// ```
+#[must_use]
+pub fn compare(old: &Version, new: &Version, args: &[String], budget: u64) -> Comparison {
+    let old = old.run(args, budget);
+    let new = new.run(args, budget);
+    Comparison {
+        args: args.to_vec(),
+        verdict: verdict(&old, &new),
+        old,
+        new,
+    }
+}
+
+/// Why inputs couldn't be made for a program
+#[derive(Error, Debug)]
+pub enum InputError {
+    /// Inputs are for `main`, which the program doesn't have
+    #[error("no function of name `@main` found")]
+    MissingMain,
+    /// `main` takes an argument which can't be made at random
+    #[error("`main` takes `{0}: {1}`, which can't be made at random")]
+    Unsupported(String, Type),
+}
+
+/// ```count``` sets of random arguments for the `main` function of ```prog```, which are always the same for the same ```seed```
+///
+/// Integers and floats are between ```min``` and ```max```, except that now and then an integer is one of the values which are most likely to find mistakes, like 0, -1, and the largest and smallest integers
+/// # Errors
+/// When ```prog``` has no `main` function, or it takes an argument which isn't an `int`, `bool`, `float`, or `char`
+pub fn random_inputs(
+    prog: &Program,
+    count: usize,
+    seed: u64,
+    min: i64,
+    max: i64,
+) -> Result<Vec<Vec<String>>, InputError> {
+    const EDGES: [i64; 5] = [0, 1, -1, i64::MAX, i64::MIN];
+    let func = prog
+        .functions
+        .iter()
+        .find(|f| f.name == "main")
+        .ok_or(InputError::MissingMain)?;
+    if let Some(arg) = func.args.iter().find(|a| {
+        !matches!(
+            a.arg_type,
+            Type::Int | Type::Bool | Type::Float | Type::Char
+        )
+    }) {
+        return Err(InputError::Unsupported(
+            arg.name.clone(),
+            arg.arg_type.clone(),
+        ));
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    Ok((0..count)
+        .map(|_| {
+            func.args
+                .iter()
+                .map(|arg| match arg.arg_type {
+                    Type::Int if rng.gen_ratio(1, 8) => {
+                        EDGES[rng.gen_range(0..EDGES.len())].to_string()
+                    }
+                    Type::Int => rng.gen_range(min..=max).to_string(),
+                    Type::Bool => rng.gen::<bool>().to_string(),
+                    #[allow(clippy::cast_precision_loss)]
+                    Type::Float => format!("{:.3}", rng.gen_range(min as f64..=max as f64)),
+                    _ => char::from(rng.gen_range(b'a'..=b'z')).to_string(),
+                })
+                .collect()
+        })
+        .collect())
+}
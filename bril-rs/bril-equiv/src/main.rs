@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+
+use bril_equiv::cli::Cli;
+use bril_equiv::Version;
+use bril_rs::Program;
+use clap::Parser;
+
+// Reads all of ```path```, or stdin if it is `-`
+fn read_to_string(path: &Path) -> Result<String, String> {
+    let mut input = String::new();
+    if path == Path::new("-") {
+        std::io::stdin().read_to_string(&mut input)
+    } else {
+        std::fs::File::open(path).and_then(|mut f| f.read_to_string(&mut input))
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(input)
+}
+
+// Reads the program at ```path```, as text if the name ends in `.bril` and as JSON otherwise
+fn read_program(path: &Path) -> Result<Program, String> {
+    let input = read_to_string(path)?;
+    if path.extension().is_some_and(|e| e == "bril") {
+        let prog =
+            bril2json::parse_abstract_program_from_str(&input, true).map_err(|e| e.to_string())?;
+        Program::try_from(prog).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str(&input).map_err(|e| e.to_string())
+    }
+}
+
+// The arguments of one input, which are separated by whitespace
+fn split(args: &str) -> Vec<String> {
+    args.split_whitespace().map(ToString::to_string).collect()
+}
+
+// Exits with 2, like unreadable programs do
+fn fail(context: &str, e: &str) -> ! {
+    eprintln!("error: {context}: {e}");
+    std::process::exit(2)
+}
+
+fn main() {
+    let args = Cli::parse();
+    let [old, new] = [&args.old, &args.new].map(|path| {
+        read_program(path).unwrap_or_else(|e| {
+            // 1 means the versions diverged and 2 that they couldn't be compared
+            fail(&path.display().to_string(), &e)
+        })
+    });
+
+    let mut inputs: Vec<Vec<String>> = args.args.iter().map(|a| split(a)).collect();
+    if let Some(path) = &args.inputs {
+        let file = read_to_string(path).unwrap_or_else(|e| fail(&path.display().to_string(), &e));
+        inputs.extend(file.lines().map(split));
+    }
+    let takes_args = old
+        .functions
+        .iter()
+        .find(|f| f.name == "main")
+        .is_some_and(|main| !main.args.is_empty());
+    let random = match args.random {
+        Some(count) => count,
+        None if inputs.is_empty() && takes_args => 10,
+        None => 0,
+    };
+    if random > 0 {
+        let generated = bril_equiv::random_inputs(&old, random, args.seed, args.min, args.max)
+            .unwrap_or_else(|e| fail("random inputs", &e.to_string()));
+        inputs.extend(generated);
+    }
+    if inputs.is_empty() {
+        inputs.push(Vec::new());
+    }
+
+    let [old, new] = [old, new].map(Version::new);
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    let mut diverged = false;
+    for input in &inputs {
+        let comparison = bril_equiv::compare(&old, &new, input, args.budget);
+        println!("{}", serde_json::to_string(&comparison).unwrap());
+        *counts.entry(comparison.verdict.to_string()).or_default() += 1;
+        diverged |= comparison.verdict.diverges();
+    }
+    let counts: Vec<String> = counts
+        .iter()
+        .map(|(verdict, count)| format!("{count} {verdict}"))
+        .collect();
+    eprintln!("{} inputs: {}", inputs.len(), counts.join(", "));
+    if diverged {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,17 @@
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(about, version, author)] // keeps the cli synced with Cargo.toml
+pub struct Cli {
+    /// The seed of the first program to generate. Each program after it uses the next seed
+    #[clap(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// How many programs to generate and test
+    #[clap(short = 'n', long, default_value_t = 1000)]
+    pub count: u64,
+
+    /// About how many instructions to generate in each function
+    #[clap(long, default_value_t = 40)]
+    pub size: usize,
+}
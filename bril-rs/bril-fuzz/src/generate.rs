@@ -0,0 +1,316 @@
+use std::collections::HashSet;
+
+use bril_rs::builder::{FunctionBuilder, ProgramBuilder};
+use bril_rs::{Literal, Program, Type, ValueOps};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+/// Generates random Bril programs which pass the type checker and always terminate.
+///
+/// Programs use `int` and `bool` variables, arithmetic, comparisons, logic, `print`, branches, loops, and calls. Every program has a `main` without arguments along with a few other functions, each of which only calls the functions before it so that there is no recursion. Every loop counts down a counter which nothing else assigns to, and division by zero is left in on purpose since both interpreters should report it.
+pub struct Generator {
+    rng: StdRng,
+    size: usize,
+}
+
+// A function that can be called from the one being generated
+struct Signature {
+    name: String,
+    args: Vec<Type>,
+    return_type: Option<Type>,
+}
+
+// The state of the function being generated
+struct Body<'a> {
+    builder: FunctionBuilder,
+    // The variables which are defined at this point on every path, along with their types
+    scope: Vec<(String, Type)>,
+    // Loop counters and the constants they are compared with, which must not be assigned to
+    protected: HashSet<String>,
+    // How many more statements can be generated
+    budget: usize,
+    callees: &'a [Signature],
+}
+
+impl Body<'_> {
+    // A new variable of type ```ty``` which is added to the scope
+    fn fresh(&mut self, ty: &Type) -> String {
+        let name = self.builder.fresh_var("v");
+        self.scope.push((name.clone(), ty.clone()));
+        name
+    }
+}
+
+const TYPES: [Type; 2] = [Type::Int, Type::Bool];
+const MAX_DEPTH: usize = 3;
+const MAX_TRIPS: i64 = 4;
+
+impl Generator {
+    /// A generator which always makes the same programs for the same ```seed```, with at most about ```size``` instructions in each function
+    #[must_use]
+    pub fn new(seed: u64, size: usize) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            size,
+        }
+    }
+
+    /// Generates the next program
+    /// # Panics
+    /// Only if the generator builds an ill-formed function, which is a bug
+    pub fn program(&mut self) -> Program {
+        let mut builder = ProgramBuilder::new();
+        let mut callees = Vec::new();
+        for i in 0..self.rng.gen_range(0..=3) {
+            let sig = Signature {
+                name: format!("f{i}"),
+                args: (0..self.rng.gen_range(0..=3)).map(|_| self.ty()).collect(),
+                return_type: self.rng.gen_bool(0.7).then(|| self.ty()),
+            };
+            builder = builder.function(self.function(&sig, &callees));
+            callees.push(sig);
+        }
+        let main = Signature {
+            name: "main".to_string(),
+            args: Vec::new(),
+            return_type: None,
+        };
+        builder
+            .function(self.function(&main, &callees))
+            .finish()
+            .unwrap()
+    }
+
+    fn function(&mut self, sig: &Signature, callees: &[Signature]) -> FunctionBuilder {
+        let mut builder = FunctionBuilder::new(sig.name.clone());
+        let mut scope = Vec::new();
+        for (i, ty) in sig.args.iter().enumerate() {
+            let name = format!("a{i}");
+            builder.arg(name.clone(), ty.clone());
+            scope.push((name, ty.clone()));
+        }
+        if let Some(ty) = &sig.return_type {
+            builder.return_type(ty.clone());
+        }
+        let mut body = Body {
+            builder,
+            scope,
+            protected: HashSet::new(),
+            budget: self.size,
+            callees,
+        };
+        self.block(&mut body, 0);
+        if let Some(ty) = &sig.return_type {
+            let result = self.var(&mut body, ty);
+            body.builder.ret(Some(&result));
+        } else {
+            // Print some of what was computed, since nothing is returned
+            let shown: Vec<String> = body
+                .scope
+                .choose_multiple(&mut self.rng, 3)
+                .map(|(name, _)| name.clone())
+                .collect();
+            if !shown.is_empty() {
+                body.builder
+                    .print(&shown.iter().map(String::as_str).collect::<Vec<_>>());
+            }
+        }
+        body.builder
+    }
+
+    fn ty(&mut self) -> Type {
+        TYPES.choose(&mut self.rng).unwrap().clone()
+    }
+
+    // A run of statements nested ```depth``` branches or loops deep
+    fn block(&mut self, body: &mut Body, depth: usize) {
+        while body.budget > 0 && self.rng.gen_bool(0.9) {
+            body.budget -= 1;
+            self.statement(body, depth);
+        }
+    }
+
+    fn statement(&mut self, body: &mut Body, depth: usize) {
+        match self.rng.gen_range(0..20) {
+            0..=3 => {
+                let ty = self.ty();
+                let value = self.literal(&ty);
+                let dest = self.dest(body, &ty);
+                body.builder.constant_into(dest, ty, value);
+            }
+            4..=7 => {
+                let op = *[
+                    ValueOps::Add,
+                    ValueOps::Sub,
+                    ValueOps::Mul,
+                    ValueOps::Div,
+                    ValueOps::Rem,
+                ]
+                .choose(&mut self.rng)
+                .unwrap();
+                self.value(body, op, &[Type::Int, Type::Int], &Type::Int);
+            }
+            8..=10 => {
+                let op = *[
+                    ValueOps::Eq,
+                    ValueOps::Lt,
+                    ValueOps::Gt,
+                    ValueOps::Le,
+                    ValueOps::Ge,
+                ]
+                .choose(&mut self.rng)
+                .unwrap();
+                self.value(body, op, &[Type::Int, Type::Int], &Type::Bool);
+            }
+            11 | 12 => match self.rng.gen_range(0..3) {
+                0 => self.value(body, ValueOps::Not, &[Type::Bool], &Type::Bool),
+                1 => self.value(body, ValueOps::And, &[Type::Bool, Type::Bool], &Type::Bool),
+                _ => self.value(body, ValueOps::Or, &[Type::Bool, Type::Bool], &Type::Bool),
+            },
+            13 => {
+                let ty = self.ty();
+                self.value(body, ValueOps::Id, std::slice::from_ref(&ty), &ty);
+            }
+            15 | 16 if !body.callees.is_empty() => self.call(body),
+            17 | 18 if depth < MAX_DEPTH => self.branch(body, depth),
+            19 if depth < MAX_DEPTH => self.repeat(body, depth),
+            _ => {
+                let args: Vec<String> = (0..self.rng.gen_range(1..=3))
+                    .map(|_| {
+                        let ty = self.ty();
+                        self.var(body, &ty)
+                    })
+                    .collect();
+                body.builder
+                    .print(&args.iter().map(String::as_str).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    fn literal(&mut self, ty: &Type) -> Literal {
+        match ty {
+            Type::Bool => Literal::Bool(self.rng.gen()),
+            // Mostly small numbers, with the occasional extreme one to overflow
+            _ => Literal::Int(match self.rng.gen_range(0..20) {
+                0 => i64::MAX,
+                1 => i64::MIN,
+                2 => self.rng.gen(),
+                _ => self.rng.gen_range(-10..=10),
+            }),
+        }
+    }
+
+    // A variable of type ```ty``` that is in scope, which is defined as a constant first if there aren't any
+    fn var(&mut self, body: &mut Body, ty: &Type) -> String {
+        let vars: Vec<&String> = body
+            .scope
+            .iter()
+            .filter(|(_, t)| t == ty)
+            .map(|(name, _)| name)
+            .collect();
+        if let Some(name) = vars.choose(&mut self.rng) {
+            return (*name).clone();
+        }
+        let value = self.literal(ty);
+        let dest = body.fresh(ty);
+        body.builder.constant_into(dest.clone(), ty.clone(), value);
+        dest
+    }
+
+    // Somewhere to store a value of type ```ty```, which is sometimes a variable already in scope
+    fn dest(&mut self, body: &mut Body, ty: &Type) -> String {
+        let vars: Vec<&String> = body
+            .scope
+            .iter()
+            .filter(|(name, t)| t == ty && !body.protected.contains(name))
+            .map(|(name, _)| name)
+            .collect();
+        if self.rng.gen_bool(0.3) {
+            if let Some(name) = vars.choose(&mut self.rng) {
+                return (*name).clone();
+            }
+        }
+        body.fresh(ty)
+    }
+
+    fn value(&mut self, body: &mut Body, op: ValueOps, args: &[Type], op_type: &Type) {
+        let args: Vec<String> = args.iter().map(|ty| self.var(body, ty)).collect();
+        let dest = self.dest(body, op_type);
+        body.builder.value_into(
+            dest,
+            op,
+            op_type.clone(),
+            &args.iter().map(String::as_str).collect::<Vec<_>>(),
+        );
+    }
+
+    fn call(&mut self, body: &mut Body) {
+        let sig = body.callees.choose(&mut self.rng).unwrap();
+        let args: Vec<String> = sig.args.iter().map(|ty| self.var(body, ty)).collect();
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        if let Some(ty) = &sig.return_type {
+            let dest = self.dest(body, ty);
+            body.builder
+                .call_into(dest, sig.name.clone(), ty.clone(), &args);
+        } else {
+            body.builder.call_effect(sig.name.clone(), &args);
+        }
+    }
+
+    // An if with an else, after which only the variables defined before it are in scope
+    fn branch(&mut self, body: &mut Body, depth: usize) {
+        let cond = self.var(body, &Type::Bool);
+        let then_label = body.builder.fresh_label("then");
+        let else_label = body.builder.fresh_label("else");
+        let end_label = body.builder.fresh_label("endif");
+        body.builder
+            .br(&cond, then_label.clone(), else_label.clone());
+        let defined = body.scope.len();
+        body.builder.label(then_label);
+        self.block(body, depth + 1);
+        body.builder.jmp(end_label.clone());
+        body.scope.truncate(defined);
+        body.builder.label(else_label);
+        self.block(body, depth + 1);
+        body.scope.truncate(defined);
+        body.builder.label(end_label);
+    }
+
+    // A loop which runs its body a constant number of times
+    fn repeat(&mut self, body: &mut Body, depth: usize) {
+        let trips = self.rng.gen_range(0..=MAX_TRIPS);
+        let counter = body.fresh(&Type::Int);
+        body.builder
+            .constant_into(counter.clone(), Type::Int, Literal::Int(trips));
+        let zero = body.fresh(&Type::Int);
+        body.builder
+            .constant_into(zero.clone(), Type::Int, Literal::Int(0));
+        let one = body.fresh(&Type::Int);
+        body.builder
+            .constant_into(one.clone(), Type::Int, Literal::Int(1));
+        let protected = [counter.clone(), zero.clone(), one.clone()];
+        body.protected.extend(protected.iter().cloned());
+
+        let head_label = body.builder.fresh_label("loop");
+        let body_label = body.builder.fresh_label("body");
+        let exit_label = body.builder.fresh_label("exit");
+        body.builder.label(head_label.clone());
+        let cond = body.fresh(&Type::Bool);
+        body.builder
+            .value_into(cond.clone(), ValueOps::Gt, Type::Bool, &[&counter, &zero]);
+        body.builder
+            .br(&cond, body_label.clone(), exit_label.clone());
+        let defined = body.scope.len();
+        body.builder.label(body_label);
+        self.block(body, depth + 1);
+        body.scope.truncate(defined);
+        body.builder
+            .value_into(counter.clone(), ValueOps::Sub, Type::Int, &[&counter, &one]);
+        body.builder.jmp(head_label);
+        body.builder.label(exit_label);
+        for name in &protected {
+            body.protected.remove(name);
+        }
+    }
+}
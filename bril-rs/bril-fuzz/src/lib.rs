@@ -0,0 +1,116 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
+
+use std::panic::{self, AssertUnwindSafe};
+
+use bril_rs::{Code, Program};
+use brilirs::basic_block::BBProgram;
+use brilirs::{check, interp};
+
+#[doc(hidden)]
+pub mod cli;
+/// Provides ```generate::Generator```, which makes random programs that pass the type checker
+pub mod generate;
+/// Provides ```reference::run```, a simple interpreter to check `brilirs` against
+pub mod reference;
+
+/// What running a program printed, and the error it stopped with if there was one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    /// Everything the program printed
+    pub output: String,
+    /// The error the program stopped with, if it didn't finish normally
+    pub error: Option<String>,
+}
+
+impl Outcome {
+    /// Whether ```self``` and ```other``` printed the same thing and either both finished normally or both stopped with an error. Error messages don't have to match.
+    #[must_use]
+    pub fn agrees_with(&self, other: &Self) -> bool {
+        self.output == other.output && self.error.is_some() == other.error.is_some()
+    }
+}
+
+/// How many instructions [`reference::run`] is allowed to run before giving up on a program
+pub const FUEL: u64 = 1_000_000;
+
+/// Runs ```prog``` with `brilirs`, including its type checker. A panic counts as stopping with an error.
+#[must_use]
+pub fn run_brilirs(prog: &Program) -> Outcome {
+    let mut output = Vec::new();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let bbprog = BBProgram::new(prog.clone()).map_err(|e| e.to_string())?;
+        check::type_check(&bbprog).map_err(|e| e.to_string())?;
+        interp::execute_main(&bbprog, &mut output, &[], false).map_err(|e| e.to_string())
+    }))
+    .unwrap_or_else(|_| Err("brilirs panicked".to_string()));
+    Outcome {
+        output: String::from_utf8_lossy(&output).into_owned(),
+        error: result.err(),
+    }
+}
+
+/// Runs ```prog``` with both `brilirs` and [`reference::run`], returning both outcomes if they disagree.
+///
+/// Programs that run out of fuel in the reference interpreter are never run with `brilirs`, since they might not terminate, and count as agreeing.
+#[must_use]
+pub fn differ(prog: &Program) -> Option<(Outcome, Outcome)> {
+    let expected = reference::run(prog, FUEL)?;
+    let actual = run_brilirs(prog);
+    (!actual.agrees_with(&expected)).then_some((expected, actual))
+}
+
+/// Makes ```prog``` smaller while `brilirs` and [`reference::run`] still disagree on it by removing instructions and functions one at a time.
+///
+/// Removals which would leave a program that `brilirs` doesn't accept are skipped, as are those that change how the reference interpreter stops. This keeps the program type checking and keeps it from using variables that were never defined or returning nothing from a function that should return something, neither of which `brilirs` checks for.
+#[must_use]
+pub fn shrink(mut prog: Program) -> Program {
+    let Some((expected, _)) = differ(&prog) else {
+        return prog;
+    };
+    loop {
+        let mut smaller = false;
+        let mut f = 0;
+        while f < prog.functions.len() {
+            let mut i = 0;
+            while i < prog.functions[f].instrs.len() {
+                if matches!(prog.functions[f].instrs[i], Code::Label { .. }) {
+                    i += 1;
+                    continue;
+                }
+                let mut candidate = prog.clone();
+                candidate.functions[f].instrs.remove(i);
+                if still_differs(&candidate, expected.error.as_deref()) {
+                    prog = candidate;
+                    smaller = true;
+                } else {
+                    i += 1;
+                }
+            }
+            if prog.functions[f].name != "main" {
+                let mut candidate = prog.clone();
+                candidate.functions.remove(f);
+                if still_differs(&candidate, expected.error.as_deref()) {
+                    prog = candidate;
+                    smaller = true;
+                    continue;
+                }
+            }
+            f += 1;
+        }
+        if !smaller {
+            return prog;
+        }
+    }
+}
+
+// Whether ```prog``` is still one that `brilirs` accepts and disagrees with the reference on, with the reference stopping with ```error```
+fn still_differs(prog: &Program, error: Option<&str>) -> bool {
+    BBProgram::new(prog.clone()).is_ok_and(|bbprog| check::type_check(&bbprog).is_ok())
+        && reference::run(prog, FUEL)
+            .filter(|expected| expected.error.as_deref() == error)
+            .is_some_and(|expected| !run_brilirs(prog).agrees_with(&expected))
+}
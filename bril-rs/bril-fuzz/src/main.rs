@@ -0,0 +1,31 @@
+use bril_fuzz::cli::Cli;
+use bril_fuzz::generate::Generator;
+use bril_fuzz::{differ, shrink};
+use clap::Parser;
+
+fn main() {
+    let args = Cli::parse();
+    for seed in args.seed..args.seed + args.count {
+        let prog = Generator::new(seed, args.size).program();
+        if differ(&prog).is_none() {
+            continue;
+        }
+        let prog = shrink(prog);
+        let (expected, actual) = differ(&prog).unwrap();
+        eprintln!("brilirs disagrees with the reference interpreter on seed {seed}:\n");
+        eprintln!("{prog}");
+        eprintln!("reference output:\n{}", expected.output);
+        if let Some(e) = expected.error {
+            eprintln!("reference error: {e}");
+        }
+        eprintln!("\nbrilirs output:\n{}", actual.output);
+        if let Some(e) = actual.error {
+            eprintln!("brilirs error: {e}");
+        }
+        std::process::exit(1);
+    }
+    println!(
+        "brilirs agreed with the reference interpreter on {} programs",
+        args.count
+    );
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use bril_rs::{Code, EffectOps, Function, Instruction, Literal, Program, ValueOps};
+
+use crate::Outcome;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Int(i64),
+    Bool(bool),
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(i) => write!(f, "{i}"),
+            Self::Bool(b) => write!(f, "{b}"),
+        }
+    }
+}
+
+// Why the program stopped early
+enum Stop {
+    Error(String),
+    OutOfFuel,
+}
+
+struct Interp<'a> {
+    functions: HashMap<&'a str, &'a Function>,
+    output: String,
+    fuel: u64,
+}
+
+/// Runs the `main` function of ```prog``` by walking over its instructions one at a time, with none of the checking or preprocessing that `brilirs` does first.
+///
+/// This only supports the subset of Bril made by [`crate::generate::Generator`]: `int` and `bool` values, arithmetic, comparisons, logic, `print`, control flow, and calls. Integer arithmetic wraps on overflow and dividing by zero is an error. Returns [`None`] if the program runs more than ```fuel``` instructions, which can happen once a program has been shrunk.
+#[must_use]
+pub fn run(prog: &Program, fuel: u64) -> Option<Outcome> {
+    let mut interp = Interp {
+        functions: prog
+            .functions
+            .iter()
+            .map(|f| (f.name.as_str(), f))
+            .collect(),
+        output: String::new(),
+        fuel,
+    };
+    let error = match interp.functions.get("main") {
+        None => Some("no main function".to_string()),
+        Some(main) => match interp.call(main, Vec::new()) {
+            Ok(_) => None,
+            Err(Stop::Error(e)) => Some(e),
+            Err(Stop::OutOfFuel) => return None,
+        },
+    };
+    Some(Outcome {
+        output: interp.output,
+        error,
+    })
+}
+
+impl<'a> Interp<'a> {
+    fn call(&mut self, func: &'a Function, args: Vec<Value>) -> Result<Option<Value>, Stop> {
+        let mut env: HashMap<&str, Value> = func
+            .args
+            .iter()
+            .map(|a| a.name.as_str())
+            .zip(args)
+            .collect();
+        let labels: HashMap<&str, usize> = func
+            .instrs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, code)| match code {
+                Code::Label { label, .. } => Some((label.as_str(), i)),
+                Code::Instruction(_) => None,
+            })
+            .collect();
+        let jump = |label: &str| {
+            labels
+                .get(label)
+                .copied()
+                .ok_or_else(|| Stop::Error(format!("undefined label .{label}")))
+        };
+
+        let mut pc = 0;
+        while let Some(code) = func.instrs.get(pc) {
+            pc += 1;
+            let Code::Instruction(instr) = code else {
+                continue;
+            };
+            if self.fuel == 0 {
+                return Err(Stop::OutOfFuel);
+            }
+            self.fuel -= 1;
+            match instr {
+                Instruction::Constant { dest, value, .. } => {
+                    let value = match value {
+                        Literal::Int(i) => Value::Int(*i),
+                        Literal::Bool(b) => Value::Bool(*b),
+                        _ => return Err(unsupported("constant")),
+                    };
+                    env.insert(dest, value);
+                }
+                Instruction::Value {
+                    dest,
+                    op,
+                    args,
+                    funcs,
+                    ..
+                } => {
+                    let args = lookup(&env, args)?;
+                    let value = if *op == ValueOps::Call {
+                        self.call_function(&funcs[0], args)?
+                            .ok_or_else(|| Stop::Error(format!("@{} returned nothing", funcs[0])))?
+                    } else {
+                        apply(*op, &args)?
+                    };
+                    env.insert(dest, value);
+                }
+                Instruction::Effect {
+                    op,
+                    args,
+                    funcs,
+                    labels,
+                    ..
+                } => {
+                    let args = lookup(&env, args)?;
+                    match op {
+                        EffectOps::Print => {
+                            let line: Vec<String> = args.iter().map(ToString::to_string).collect();
+                            writeln!(self.output, "{}", line.join(" ")).unwrap();
+                        }
+                        EffectOps::Jump => pc = jump(&labels[0])?,
+                        EffectOps::Branch => {
+                            let Value::Bool(cond) = args[0] else {
+                                return Err(Stop::Error("branch on an int".to_string()));
+                            };
+                            pc = jump(&labels[usize::from(!cond)])?;
+                        }
+                        EffectOps::Return => return Ok(args.first().copied()),
+                        EffectOps::Call => {
+                            self.call_function(&funcs[0], args)?;
+                        }
+                        EffectOps::Nop => {}
+                        _ => return Err(unsupported(&op.to_string())),
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn call_function(&mut self, name: &str, args: Vec<Value>) -> Result<Option<Value>, Stop> {
+        let func = *self
+            .functions
+            .get(name)
+            .ok_or_else(|| Stop::Error(format!("undefined function @{name}")))?;
+        self.call(func, args)
+    }
+}
+
+fn unsupported(what: &str) -> Stop {
+    Stop::Error(format!("unsupported {what}"))
+}
+
+fn lookup(env: &HashMap<&str, Value>, args: &[String]) -> Result<Vec<Value>, Stop> {
+    args.iter()
+        .map(|a| {
+            env.get(a.as_str())
+                .copied()
+                .ok_or_else(|| Stop::Error(format!("undefined variable {a}")))
+        })
+        .collect()
+}
+
+fn apply(op: ValueOps, args: &[Value]) -> Result<Value, Stop> {
+    use Value::{Bool, Int};
+    Ok(match (op, args) {
+        (ValueOps::Id, [v]) => *v,
+        (ValueOps::Not, [Bool(a)]) => Bool(!a),
+        (ValueOps::And, [Bool(a), Bool(b)]) => Bool(*a && *b),
+        (ValueOps::Or, [Bool(a), Bool(b)]) => Bool(*a || *b),
+        (ValueOps::Add, [Int(a), Int(b)]) => Int(a.wrapping_add(*b)),
+        (ValueOps::Sub, [Int(a), Int(b)]) => Int(a.wrapping_sub(*b)),
+        (ValueOps::Mul, [Int(a), Int(b)]) => Int(a.wrapping_mul(*b)),
+        (ValueOps::Div | ValueOps::Rem, [Int(_), Int(0)]) => {
+            return Err(Stop::Error("division by zero".to_string()))
+        }
+        (ValueOps::Div, [Int(a), Int(b)]) => Int(a.wrapping_div(*b)),
+        (ValueOps::Rem, [Int(a), Int(b)]) => Int(a.wrapping_rem(*b)),
+        (ValueOps::Eq, [Int(a), Int(b)]) => Bool(a == b),
+        (ValueOps::Lt, [Int(a), Int(b)]) => Bool(a < b),
+        (ValueOps::Gt, [Int(a), Int(b)]) => Bool(a > b),
+        (ValueOps::Le, [Int(a), Int(b)]) => Bool(a <= b),
+        (ValueOps::Ge, [Int(a), Int(b)]) => Bool(a >= b),
+        _ => return Err(Stop::Error(format!("bad arguments to {op}"))),
+    })
+}
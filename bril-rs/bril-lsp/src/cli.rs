@@ -0,0 +1,13 @@
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(about, version, author)] // keeps the cli synced with Cargo.toml
+pub struct Cli {
+    /// Read and write one JSON-RPC message per line instead of with `Content-Length` headers, which is handy for scripting and testing
+    #[clap(long)]
+    pub lines: bool,
+
+    /// A directory to search for the files a document imports from when they aren't next to it. Can be repeated
+    #[clap(short = 'L', long)]
+    pub lib_path: Vec<std::path::PathBuf>,
+}
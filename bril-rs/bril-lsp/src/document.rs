@@ -0,0 +1,556 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Write};
+use std::path::{Path, PathBuf};
+
+use bril_rs::link::{LinkError, Linker};
+use bril_rs::{AbstractCode, AbstractFunction, AbstractInstruction, AbstractProgram, Program};
+use brilirs::basic_block::BBProgram;
+use brilirs::check::{type_check_all, Severity};
+use serde::{Deserialize, Serialize};
+
+/// A place in a document as LSP counts it, with lines from 0 and characters in UTF-16 code units from 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    /// The line, from 0
+    pub line: u32,
+    /// The character within the line, from 0
+    pub character: u32,
+}
+
+/// The text between two [`Position`]s, including ```start``` but not ```end```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Range {
+    /// Where the range starts
+    pub start: Position,
+    /// Where the range ends
+    pub end: Position,
+}
+
+/// A problem with a document, as it is sent to the client
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    /// The text with the problem
+    pub range: Range,
+    /// 1 for errors and 2 for warnings
+    pub severity: u8,
+    /// What found the problem
+    pub source: &'static str,
+    /// A description of the problem
+    pub message: String,
+}
+
+/// A function or label of a document, as it is sent to the client
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentSymbol {
+    /// What the symbol is called, like `@main` or `.loop`
+    pub name: String,
+    /// The signature of a function
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// 12 for functions and 20 for labels, since LSP has no kind for labels
+    pub kind: u8,
+    /// All of the text of the symbol, which is the whole body of a function
+    pub range: Range,
+    /// Just the name of the symbol
+    pub selection_range: Range,
+    /// The labels of a function
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Self>,
+}
+
+// Something which a name in a document can refer to
+enum Word<'a> {
+    Function(&'a str),
+    Label(&'a str),
+    Variable(&'a str),
+}
+
+/// An open Bril text file along with everything found out about it. Everything is worked out again from scratch whenever the text changes, since Bril files are small.
+pub struct Document {
+    /// The version the client gave the text, which goes along with the diagnostics published for it
+    pub version: i64,
+    text: String,
+    // The byte offset of the start of every line
+    line_starts: Vec<usize>,
+    // None if the text couldn't be parsed
+    program: Option<AbstractProgram>,
+    // The first and last line of each function in ```program``` with a position, in order
+    spans: Vec<(usize, usize, usize)>,
+    // The signatures of the functions linked in from other files, by the names they are called here
+    imported: HashMap<String, String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    /// Parses and checks ```text```, which is the contents of the file at ```uri```. Imports are looked for next to the file first and then in ```lib_paths```.
+    #[must_use]
+    pub fn new(uri: &str, text: String, version: i64, lib_paths: &[PathBuf]) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        let mut doc = Self {
+            version,
+            text,
+            line_starts,
+            program: None,
+            spans: Vec::new(),
+            imported: HashMap::new(),
+            diagnostics: Vec::new(),
+        };
+        match bril2json::parse_abstract_program_from_str(&doc.text, true) {
+            Ok(program) => {
+                doc.spans = doc.function_spans(&program);
+                doc.diagnostics = doc.check(program.clone(), &uri_dir(uri), lib_paths);
+                doc.program = Some(program);
+            }
+            Err(e) => {
+                let diagnostic = doc.diagnostic(e.pos, Severity::Error, e.message);
+                doc.diagnostics = vec![diagnostic];
+            }
+        }
+        doc
+    }
+
+    /// The problems found in the document, in order
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    // Everything the brilck type checker finds wrong with the functions of this document, after linking in its imports
+    fn check(
+        &mut self,
+        abstract_prog: AbstractProgram,
+        dir: &Path,
+        lib_paths: &[PathBuf],
+    ) -> Vec<Diagnostic> {
+        let defined: Vec<String> = abstract_prog
+            .functions
+            .iter()
+            .map(|f| f.name.clone())
+            .collect();
+        let imports = abstract_prog.imports.clone();
+        let mut prog = match Program::try_from(abstract_prog) {
+            Ok(prog) => prog,
+            Err(e) => {
+                return vec![self.diagnostic(e.pos(), Severity::Error, e.error().to_string())]
+            }
+        };
+        if let Err(e) = Linker::new(lib_paths.to_vec(), load_import).link(&mut prog, dir) {
+            let path = match &e {
+                LinkError::NotFound(path)
+                | LinkError::Load(path, _)
+                | LinkError::UndefinedImport { path, .. }
+                | LinkError::ConflictingImport { path, .. } => path,
+            };
+            // The error is put on the import of the file it is about when that can be told
+            let line = imports
+                .iter()
+                .find(|i| i.path.file_name().is_some() && i.path.file_name() == path.file_name())
+                .and_then(|i| self.import_line(&i.path));
+            let pos = line.map(|l| bril_rs::Position {
+                row: l as u64 + 1,
+                col: 1,
+                derived: false,
+                source: None,
+            });
+            return vec![self.diagnostic(pos, Severity::Error, e.to_string())];
+        }
+        self.imported = prog
+            .functions
+            .iter()
+            .filter(|f| !defined.contains(&f.name))
+            .map(|f| {
+                let signature = signature(&f.name, &f.args, f.return_type.as_ref());
+                (f.name.clone(), signature)
+            })
+            .collect();
+        let bbprog = match BBProgram::new(prog) {
+            Ok(bbprog) => bbprog,
+            Err(e) => {
+                let e = e.add_pos(None);
+                return vec![self.diagnostic(e.pos(), Severity::Error, e.error().to_string())];
+            }
+        };
+        // Problems with imported functions belong to the files they come from
+        type_check_all(&bbprog)
+            .into_iter()
+            .filter(|d| d.function.as_ref().is_none_or(|f| defined.contains(f)))
+            .map(|d| {
+                let pos = d.line.zip(d.column).map(|(row, col)| bril_rs::Position {
+                    row,
+                    col,
+                    derived: false,
+                    source: None,
+                });
+                self.diagnostic(pos, d.severity, d.message)
+            })
+            .collect()
+    }
+
+    // A problem at ```pos```, which covers the rest of its line. Problems without a position go at the start of the document
+    fn diagnostic(
+        &self,
+        pos: Option<bril_rs::Position>,
+        severity: Severity,
+        message: String,
+    ) -> Diagnostic {
+        let start = pos.map_or(
+            Position {
+                line: 0,
+                character: 0,
+            },
+            |p| self.position(p),
+        );
+        let line = self.line(start.line as usize);
+        let end = Position {
+            line: start.line,
+            character: utf16_len(line.trim_end()).max(start.character),
+        };
+        Diagnostic {
+            range: Range { start, end },
+            severity: match severity {
+                Severity::Error => 1,
+                Severity::Warning => 2,
+            },
+            source: "bril",
+            message,
+        }
+    }
+
+    /// The signature of the function or the type of the variable at ```pos```, along with the text it is for
+    #[must_use]
+    pub fn hover(&self, pos: Position) -> Option<(String, Range)> {
+        let program = self.program.as_ref()?;
+        let (word, range) = self.word_at(pos)?;
+        let text = match word {
+            Word::Function(name) => {
+                if let Some(func) = program.functions.iter().find(|f| f.name == name) {
+                    signature(&func.name, &func.args, func.return_type.as_ref())
+                } else {
+                    let import = program.imports.iter().find(|i| {
+                        i.functions
+                            .iter()
+                            .any(|f| f.alias.as_ref().unwrap_or(&f.name) == name)
+                    })?;
+                    // Only the name is known if the imports couldn't be linked in
+                    let signature = self
+                        .imported
+                        .get(name)
+                        .map_or_else(|| format!("@{name}"), Clone::clone);
+                    format!("{signature} from \"{}\"", import.path.display())
+                }
+            }
+            Word::Variable(name) => {
+                let func = self.function_at(pos.line as usize)?;
+                let ty = func
+                    .args
+                    .iter()
+                    .find(|a| a.name == name)
+                    .map(|a| a.arg_type.to_string())
+                    .or_else(|| {
+                        func.instrs.iter().find_map(|code| match code {
+                            AbstractCode::Instruction(
+                                AbstractInstruction::Constant {
+                                    dest,
+                                    const_type: Some(ty),
+                                    ..
+                                }
+                                | AbstractInstruction::Value {
+                                    dest,
+                                    op_type: Some(ty),
+                                    ..
+                                },
+                            ) if dest == name => Some(ty.to_string()),
+                            _ => None,
+                        })
+                    })?;
+                format!("{name}: {ty}")
+            }
+            Word::Label(_) => return None,
+        };
+        Some((text, range))
+    }
+
+    /// Where the function or label named at ```pos``` is defined in this document
+    #[must_use]
+    pub fn definition(&self, pos: Position) -> Option<Range> {
+        let program = self.program.as_ref()?;
+        let (word, _) = self.word_at(pos)?;
+        match word {
+            Word::Function(name) => {
+                let func = program.functions.iter().find(|f| f.name == name)?;
+                Some(self.name_range(func.pos?, &func.name))
+            }
+            // Labels are local to the function they are in
+            Word::Label(name) => {
+                self.function_at(pos.line as usize)?
+                    .instrs
+                    .iter()
+                    .find_map(|code| match code {
+                        AbstractCode::Label {
+                            label,
+                            pos: Some(pos),
+                        } if label == name => Some(self.name_range(*pos, label)),
+                        _ => None,
+                    })
+            }
+            Word::Variable(_) => None,
+        }
+    }
+
+    /// The functions of the document with their labels, in order
+    #[must_use]
+    pub fn symbols(&self) -> Vec<DocumentSymbol> {
+        let Some(program) = &self.program else {
+            return Vec::new();
+        };
+        self.spans
+            .iter()
+            .filter_map(|&(f, _, last)| {
+                let func = &program.functions[f];
+                let selection_range = self.name_range(func.pos?, &func.name);
+                let children = func
+                    .instrs
+                    .iter()
+                    .filter_map(|code| match code {
+                        AbstractCode::Label {
+                            label,
+                            pos: Some(pos),
+                        } => {
+                            let range = self.name_range(*pos, label);
+                            Some(DocumentSymbol {
+                                name: format!(".{label}"),
+                                detail: None,
+                                kind: 20,
+                                range,
+                                selection_range: range,
+                                children: Vec::new(),
+                            })
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                Some(DocumentSymbol {
+                    name: format!("@{}", func.name),
+                    detail: Some(signature(&func.name, &func.args, func.return_type.as_ref())),
+                    kind: 12,
+                    range: Range {
+                        start: selection_range.start,
+                        end: Position {
+                            line: to_u32(last),
+                            character: utf16_len(self.line(last)),
+                        },
+                    },
+                    selection_range,
+                    children,
+                })
+            })
+            .collect()
+    }
+
+    // The index of each function with a position in ```program``` along with its first and last line. A function ends at the last closing brace before the next function starts
+    fn function_spans(&self, program: &AbstractProgram) -> Vec<(usize, usize, usize)> {
+        let mut starts: Vec<(usize, usize)> = program
+            .functions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, f)| Some((i, to_usize(f.pos?.row).saturating_sub(1))))
+            .collect();
+        starts.sort_by_key(|(_, line)| *line);
+        (0..starts.len())
+            .map(|i| {
+                let (f, first) = starts[i];
+                let next = starts
+                    .get(i + 1)
+                    .map_or(self.line_starts.len(), |(_, l)| *l);
+                let last = (first..next)
+                    .rev()
+                    .find(|l| self.line(*l).split('#').next().unwrap_or("").contains('}'))
+                    .unwrap_or(first);
+                (f, first, last)
+            })
+            .collect()
+    }
+
+    // The function whose body ```line``` is in
+    fn function_at(&self, line: usize) -> Option<&AbstractFunction> {
+        let (f, _, _) = self
+            .spans
+            .iter()
+            .find(|(_, first, last)| (*first..=*last).contains(&line))?;
+        Some(&self.program.as_ref()?.functions[*f])
+    }
+
+    // The line of the import of ```path```
+    fn import_line(&self, path: &Path) -> Option<usize> {
+        let quoted = format!("\"{}\"", path.display());
+        (0..self.line_starts.len()).find(|l| {
+            let line = self.line(*l).trim_start();
+            line.starts_with("from") && line.contains(&quoted)
+        })
+    }
+
+    // The text of ```line``` without its line break
+    fn line(&self, line: usize) -> &str {
+        let Some(&start) = self.line_starts.get(line) else {
+            return "";
+        };
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .map_or(self.text.len(), |e| e - 1);
+        self.text[start..end].trim_end_matches('\r')
+    }
+
+    // Converts a position from the parser, with lines and byte columns from 1, into an LSP one
+    fn position(&self, pos: bril_rs::Position) -> Position {
+        let line = to_usize(pos.row).saturating_sub(1);
+        let text = self.line(line);
+        let mut col = to_usize(pos.col).saturating_sub(1).min(text.len());
+        while !text.is_char_boundary(col) {
+            col -= 1;
+        }
+        Position {
+            line: to_u32(line),
+            character: utf16_len(&text[..col]),
+        }
+    }
+
+    // The range of the name of a function or label which is defined at ```pos```, including its `@` or `.`
+    fn name_range(&self, pos: bril_rs::Position, name: &str) -> Range {
+        let start = self.position(pos);
+        Range {
+            start,
+            end: Position {
+                line: start.line,
+                character: start.character + 1 + utf16_len(name),
+            },
+        }
+    }
+
+    // The function, label, or variable name which ```pos``` is on, along with where it is
+    fn word_at(&self, pos: Position) -> Option<(Word<'_>, Range)> {
+        let line = self.line(pos.line as usize);
+        // Find the byte offset of the UTF-16 character
+        let mut offset = line.len();
+        let mut units = 0;
+        for (i, c) in line.char_indices() {
+            if units >= pos.character as usize {
+                offset = i;
+                break;
+            }
+            units += c.len_utf16();
+        }
+        let bytes = line.as_bytes();
+        let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b == b'%' || b == b'.';
+        let mut start = offset;
+        while start > 0 && is_ident(bytes[start - 1]) {
+            start -= 1;
+        }
+        if start > 0 && bytes[start - 1] == b'@' {
+            start -= 1;
+        }
+        let mut end = offset;
+        if end < bytes.len() && bytes[end] == b'@' && end == start {
+            end += 1;
+        }
+        while end < bytes.len() && is_ident(bytes[end]) {
+            end += 1;
+        }
+        // Nothing in a comment refers to anything
+        if line[..start].contains('#') {
+            return None;
+        }
+        let text = &line[start..end];
+        let word = match text.as_bytes().first() {
+            Some(b'@') => Word::Function(&text[1..]),
+            Some(b'.') => Word::Label(&text[1..]),
+            _ => Word::Variable(text),
+        };
+        match word {
+            Word::Function(name) | Word::Label(name) | Word::Variable(name)
+                if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '%') =>
+            {
+                return None;
+            }
+            _ => {}
+        }
+        let range = Range {
+            start: Position {
+                line: pos.line,
+                character: utf16_len(&line[..start]),
+            },
+            end: Position {
+                line: pos.line,
+                character: utf16_len(&line[..end]),
+            },
+        };
+        Some((word, range))
+    }
+}
+
+// The function's name, arguments, and return type as they are written in Bril text
+fn signature(name: &str, args: &[impl Display], return_type: Option<&impl Display>) -> String {
+    let mut signature = format!("@{name}");
+    if !args.is_empty() {
+        let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+        write!(signature, "({})", args.join(", ")).unwrap();
+    }
+    if let Some(ty) = return_type {
+        write!(signature, ": {ty}").unwrap();
+    }
+    signature
+}
+
+fn utf16_len(s: &str) -> u32 {
+    to_u32(s.encode_utf16().count())
+}
+
+// Lines and columns past what fits are clamped, since no document that big can be open
+fn to_u32(n: usize) -> u32 {
+    u32::try_from(n).unwrap_or(u32::MAX)
+}
+
+fn to_usize(n: u64) -> usize {
+    usize::try_from(n).unwrap_or(usize::MAX)
+}
+
+// The directory that the imports of the file at ```uri``` are relative to
+fn uri_dir(uri: &str) -> PathBuf {
+    uri.strip_prefix("file://")
+        .map(|path| PathBuf::from(percent_decode(path)))
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+// Turns escapes like `%20` back into the bytes they stand for
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| s.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(b) = escaped {
+            decoded.push(b);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn load_import(path: &Path) -> Result<Program, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let abstract_prog: AbstractProgram = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&text).map_err(|e| e.to_string())?
+    } else {
+        bril2json::parse_abstract_program_from_str(&text, true).map_err(|e| e.to_string())?
+    };
+    Program::try_from(abstract_prog).map_err(|e| e.to_string())
+}
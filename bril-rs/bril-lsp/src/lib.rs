@@ -0,0 +1,302 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+#[doc(hidden)]
+pub mod cli;
+/// Provides [`document::Document`], which is what the server knows about an open file
+pub mod document;
+/// Provides ```protocol::read_message``` and ```protocol::write_message```, which move messages to and from the client
+pub mod protocol;
+
+use document::{Document, Position};
+
+/// The errors which a request can be answered with
+#[derive(Error, Debug)]
+pub enum ServerError {
+    /// The message is not JSON
+    #[error("message is not valid JSON: {0}")]
+    ParseError(serde_json::Error),
+    /// The message is JSON but not a JSON-RPC request or notification
+    #[error("message is not a request or notification")]
+    InvalidRequest,
+    /// A request was made before `initialize`
+    #[error("the server has not been initialized")]
+    NotInitialized,
+    /// A request was made after `shutdown`
+    #[error("the server is shutting down")]
+    ShutDown,
+    /// The server doesn't know how to answer a request
+    #[error("unknown method {0}")]
+    MethodNotFound(String),
+    /// The parameters of a request aren't what its method takes
+    #[error("invalid parameters: {0}")]
+    InvalidParams(serde_json::Error),
+    /// A request is about a document which the client hasn't opened
+    #[error("{0} is not open")]
+    UnknownDocument(String),
+}
+
+impl ServerError {
+    /// The JSON-RPC error code of the error
+    #[must_use]
+    pub const fn code(&self) -> i64 {
+        match self {
+            Self::ParseError(_) => -32700,
+            Self::InvalidRequest | Self::ShutDown => -32600,
+            Self::MethodNotFound(_) => -32601,
+            Self::InvalidParams(_) | Self::UnknownDocument(_) => -32602,
+            Self::NotInitialized => -32002,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextDocumentItem {
+    uri: String,
+    version: i64,
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextDocumentIdentifier {
+    uri: String,
+    #[serde(default)]
+    version: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidOpenParams {
+    text_document: TextDocumentItem,
+}
+
+#[derive(Deserialize)]
+struct ContentChange {
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidChangeParams {
+    text_document: TextDocumentIdentifier,
+    content_changes: Vec<ContentChange>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentParams {
+    text_document: TextDocumentIdentifier,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PositionParams {
+    text_document: TextDocumentIdentifier,
+    position: Position,
+}
+
+/// A language server for Bril text, which checks files as they are edited and answers questions about them.
+///
+/// The client sends the whole text of a file whenever it changes and gets back the problems that `brilck` finds in it. The server can also go to where functions and labels are defined, show the types of variables and the signatures of functions, and list the functions and labels of a file.
+pub struct Server {
+    lib_paths: Vec<PathBuf>,
+    documents: HashMap<String, Document>,
+    initialized: bool,
+    shut_down: bool,
+    exit_code: Option<i32>,
+}
+
+impl Server {
+    /// A server whose documents import from ```lib_paths``` as well as from the files next to them
+    #[must_use]
+    pub fn new(lib_paths: Vec<PathBuf>) -> Self {
+        Self {
+            lib_paths,
+            documents: HashMap::new(),
+            initialized: false,
+            shut_down: false,
+            exit_code: None,
+        }
+    }
+
+    /// Once the client has asked the server to exit, what the server's exit code should be: 0 if it was shut down first and 1 otherwise
+    #[must_use]
+    pub const fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Handles the text of one message from the client, returning the messages to send back. A request always gets exactly one response, which comes first
+    pub fn handle(&mut self, message: &str) -> Vec<Value> {
+        let message: Value = match serde_json::from_str(message) {
+            Ok(message) => message,
+            Err(e) => return vec![error_response(&Value::Null, &ServerError::ParseError(e))],
+        };
+        let id = message.get("id").cloned();
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            // Responses to requests from the server are ignored since it never makes any
+            return match id {
+                Some(_) if message.get("result").is_some() || message.get("error").is_some() => {
+                    Vec::new()
+                }
+                id => vec![error_response(
+                    &id.unwrap_or(Value::Null),
+                    &ServerError::InvalidRequest,
+                )],
+            };
+        };
+        match id {
+            Some(id) => vec![match self.request(method, params) {
+                Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                Err(e) => error_response(&id, &e),
+            }],
+            None => self.notify(method, params),
+        }
+    }
+
+    // Answers a request
+    fn request(&mut self, method: &str, params: Value) -> Result<Value, ServerError> {
+        if method == "initialize" {
+            self.initialized = true;
+            return Ok(json!({
+                "capabilities": {
+                    // The whole text is sent on every change
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            }));
+        }
+        if !self.initialized {
+            return Err(ServerError::NotInitialized);
+        }
+        if self.shut_down {
+            return Err(ServerError::ShutDown);
+        }
+        match method {
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => {
+                let params: PositionParams = parse_params(params)?;
+                let doc = self.document(&params.text_document.uri)?;
+                Ok(doc.hover(params.position).map_or(Value::Null, |(text, range)| {
+                    json!({"contents": {"kind": "plaintext", "value": text}, "range": range})
+                }))
+            }
+            "textDocument/definition" => {
+                let params: PositionParams = parse_params(params)?;
+                let uri = params.text_document.uri;
+                let doc = self.document(&uri)?;
+                Ok(doc
+                    .definition(params.position)
+                    .map_or(Value::Null, |range| json!({"uri": uri, "range": range})))
+            }
+            "textDocument/documentSymbol" => {
+                let params: DocumentParams = parse_params(params)?;
+                let doc = self.document(&params.text_document.uri)?;
+                Ok(json!(doc.symbols()))
+            }
+            _ => Err(ServerError::MethodNotFound(method.to_string())),
+        }
+    }
+
+    // Handles a notification, which never gets a response. Problems with notifications can't be reported so they are ignored
+    fn notify(&mut self, method: &str, params: Value) -> Vec<Value> {
+        match method {
+            "exit" => {
+                self.exit_code = Some(i32::from(!self.shut_down));
+                Vec::new()
+            }
+            "textDocument/didOpen" => match parse_params::<DidOpenParams>(params) {
+                Ok(DidOpenParams { text_document: doc }) => {
+                    self.update(doc.uri, doc.text, doc.version)
+                }
+                Err(_) => Vec::new(),
+            },
+            "textDocument/didChange" => match parse_params::<DidChangeParams>(params) {
+                // With full syncing the last change has all of the text
+                Ok(DidChangeParams {
+                    text_document,
+                    mut content_changes,
+                }) if !content_changes.is_empty() => self.update(
+                    text_document.uri,
+                    content_changes.pop().unwrap().text,
+                    text_document.version,
+                ),
+                _ => Vec::new(),
+            },
+            "textDocument/didClose" => match parse_params::<DocumentParams>(params) {
+                Ok(params) => {
+                    let uri = params.text_document.uri;
+                    self.documents.remove(&uri);
+                    // Problems with closed files are cleared from the editor
+                    vec![publish_diagnostics(&uri, None, &[])]
+                }
+                Err(_) => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    // Checks the new text of a document and publishes what was found
+    fn update(&mut self, uri: String, text: String, version: i64) -> Vec<Value> {
+        let doc = Document::new(&uri, text, version, &self.lib_paths);
+        let notification = publish_diagnostics(&uri, Some(doc.version), doc.diagnostics());
+        self.documents.insert(uri, doc);
+        vec![notification]
+    }
+
+    fn document(&self, uri: &str) -> Result<&Document, ServerError> {
+        self.documents
+            .get(uri)
+            .ok_or_else(|| ServerError::UnknownDocument(uri.to_string()))
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, ServerError> {
+    serde_json::from_value(params).map_err(ServerError::InvalidParams)
+}
+
+fn error_response(id: &Value, e: &ServerError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": e.code(), "message": e.to_string()},
+    })
+}
+
+fn publish_diagnostics(
+    uri: &str,
+    version: Option<i64>,
+    diagnostics: &[document::Diagnostic],
+) -> Value {
+    let mut params = json!({"uri": uri, "diagnostics": diagnostics});
+    if let Some(version) = version {
+        params["version"] = json!(version);
+    }
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": params,
+    })
+}
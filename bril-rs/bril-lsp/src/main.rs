@@ -0,0 +1,37 @@
+use bril_lsp::cli::Cli;
+use bril_lsp::protocol::{read_message, write_message, Framing};
+use bril_lsp::Server;
+use clap::Parser;
+
+fn main() {
+    let args = Cli::parse();
+    let framing = if args.lines {
+        Framing::Lines
+    } else {
+        Framing::Headers
+    };
+    let mut server = Server::new(args.lib_path);
+    let mut input = std::io::stdin().lock();
+    let mut output = std::io::stdout().lock();
+    loop {
+        let message = match read_message(&mut input, framing) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("error: {e}");
+                std::process::exit(1)
+            }
+        };
+        for reply in server.handle(&message) {
+            if let Err(e) = write_message(&mut output, &reply, framing) {
+                eprintln!("error: {e}");
+                std::process::exit(1)
+            }
+        }
+        if let Some(code) = server.exit_code() {
+            std::process::exit(code)
+        }
+    }
+    // The client went away without asking the server to exit
+    std::process::exit(1)
+}
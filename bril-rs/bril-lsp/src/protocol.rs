@@ -0,0 +1,108 @@
+use std::io::{BufRead, Write};
+
+use thiserror::Error;
+
+/// How messages are separated from each other on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// Each message is preceded by a `Content-Length` header and a blank line, as the Language Server Protocol specifies
+    Headers,
+    /// Each message is on a line of its own, and lines starting with `#` are ignored
+    Lines,
+}
+
+/// The errors from reading or writing messages, after which the connection can't be used any more
+#[derive(Error, Debug)]
+pub enum ProtocolError {
+    /// Reading or writing failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// A message was sent without saying how long it is
+    #[error("message has no Content-Length header")]
+    MissingLength,
+    /// A header of a message isn't of the form `Name: value`
+    #[error("malformed header {0:?}")]
+    BadHeader(String),
+    /// The input ended part of the way through a message
+    #[error("input ended in the middle of a message")]
+    Truncated,
+}
+
+/// Reads the text of the next message from ```input```, or [`None`] once there are no more. The text isn't parsed, so that a message which isn't valid JSON can still be answered with an error.
+/// # Errors
+/// Will return an error if ```input``` can't be read or doesn't hold a well-formed message
+pub fn read_message<R: BufRead>(
+    input: &mut R,
+    framing: Framing,
+) -> Result<Option<String>, ProtocolError> {
+    match framing {
+        Framing::Lines => loop {
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            // Blank lines and comments are skipped so that scripts can space out and explain their messages
+            let trimmed = line.trim();
+            if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                return Ok(Some(line));
+            }
+        },
+        Framing::Headers => {
+            let mut length = None;
+            let mut read_any = false;
+            loop {
+                let mut line = String::new();
+                if input.read_line(&mut line)? == 0 {
+                    return if read_any {
+                        Err(ProtocolError::Truncated)
+                    } else {
+                        Ok(None)
+                    };
+                }
+                read_any = true;
+                let line = line.trim_end_matches(['\r', '\n']);
+                if line.is_empty() {
+                    break;
+                }
+                let (name, value) = line
+                    .split_once(':')
+                    .ok_or_else(|| ProtocolError::BadHeader(line.to_string()))?;
+                // Content-Type is the only other header and there is only one kind of content
+                if name.trim().eq_ignore_ascii_case("Content-Length") {
+                    length = Some(
+                        value
+                            .trim()
+                            .parse::<usize>()
+                            .map_err(|_| ProtocolError::BadHeader(line.to_string()))?,
+                    );
+                }
+            }
+            let mut content = vec![0; length.ok_or(ProtocolError::MissingLength)?];
+            input.read_exact(&mut content).map_err(|e| {
+                if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                    ProtocolError::Truncated
+                } else {
+                    ProtocolError::Io(e)
+                }
+            })?;
+            Ok(Some(String::from_utf8_lossy(&content).into_owned()))
+        }
+    }
+}
+
+/// Writes ```message``` to ```output``` and flushes it so that the client sees it straight away
+/// # Errors
+/// Will return an error if ```output``` can't be written to
+pub fn write_message<W: Write>(
+    output: &mut W,
+    message: &serde_json::Value,
+    framing: Framing,
+) -> Result<(), ProtocolError> {
+    let content = message.to_string();
+    match framing {
+        Framing::Lines => writeln!(output, "{content}")?,
+        Framing::Headers => write!(output, "Content-Length: {}\r\n\r\n{content}", content.len())?,
+    }
+    output.flush()?;
+    Ok(())
+}
@@ -0,0 +1,177 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
+
+use bril_rs::Program;
+use brilirs::basic_block::BBProgram;
+use brilirs::check::{self, Severity};
+use proc_macro::{Delimiter, Span, TokenStream, TokenTree};
+use quote::{quote, quote_spanned};
+
+/// Writes a Bril program in the same syntax as Bril text, which is parsed and type checked while the Rust around it compiles and becomes a [`bril_rs::Program`].
+///
+/// Problems with the program, like a syntax error, a call with the wrong number of arguments, or an argument of the wrong type, are compile errors pointing at the Bril which has them. Comments are written like they are in Rust, since a comment starting with `#` would have to be made of Rust tokens. The crate using the macro has to depend on `bril-rs` with the `std` feature and whichever extensions the program uses.
+/// ```
+/// use bril_macro::bril;
+/// use bril_rs::{Code, Instruction};
+///
+/// let prog = bril! {
+///     @main {
+///         v: int = const 4;
+///         // Labels and calls are written as they are in Bril text
+///         r: int = call @double v;
+///         print r;
+///     }
+///     @double(x: int): int {
+///         r: int = add x x;
+///         ret r;
+///     }
+/// };
+/// assert_eq!(prog.functions.len(), 2);
+/// assert!(matches!(prog.functions[1].instrs[0], Code::Instruction(Instruction::Value { .. })));
+/// ```
+///
+/// A program which doesn't type check doesn't compile:
+/// ```compile_fail
+/// let prog = bril_macro::bril! {
+///     @main {
+///         b: bool = const true;
+///         v: int = add b b;
+///     }
+/// };
+/// ```
+#[proc_macro]
+pub fn bril(input: TokenStream) -> TokenStream {
+    let mut source = Source::default();
+    source.extend(input);
+    match compile(&source.text) {
+        Ok(json) => quote!(::bril_rs::load_program_from_read(#json.as_bytes())).into(),
+        Err(problems) => {
+            let errors = problems.iter().map(|(pos, message)| {
+                let span = pos
+                    .map_or_else(Span::call_site, |pos| source.span(pos))
+                    .into();
+                quote_spanned!(span=> ::core::compile_error!(#message);)
+            });
+            // The program is never built, but something of its type stops anything else being reported about the code using it
+            quote!({ #(#errors)* ::bril_rs::load_program_from_read(b"".as_slice()) }).into()
+        }
+    }
+}
+
+// A line and column of the text, which both start at 1
+type Pos = (usize, usize);
+
+// The Bril text of the tokens given to the macro, laid out on the same lines and columns as in the Rust source so that positions in the text are positions in the source
+struct Source {
+    text: String,
+    // Where each token starts, in the order they appear
+    tokens: Vec<(Pos, Span)>,
+    // Where the end of the text is
+    end: Pos,
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            tokens: Vec::new(),
+            end: (1, 1),
+        }
+    }
+}
+
+impl Source {
+    fn extend(&mut self, input: TokenStream) {
+        for token in input {
+            match token {
+                TokenTree::Group(group) => {
+                    let delimiters = match group.delimiter() {
+                        Delimiter::Parenthesis => Some(("(", ")")),
+                        Delimiter::Brace => Some(("{", "}")),
+                        Delimiter::Bracket => Some(("[", "]")),
+                        Delimiter::None => None,
+                    };
+                    match delimiters {
+                        Some((open, close)) => {
+                            self.push(group.span_open(), open);
+                            self.extend(group.stream());
+                            self.push(group.span_close(), close);
+                        }
+                        None => self.extend(group.stream()),
+                    }
+                }
+                token => self.push(token.span(), &token.to_string()),
+            }
+        }
+    }
+
+    // Adds ```text``` at the position of ```span```, or right after the last token if the span is somewhere before it, like when it comes from another macro
+    fn push(&mut self, span: Span, text: &str) {
+        let start = (span.line(), span.column());
+        if start.0 > self.end.0 {
+            self.text.push_str(&"\n".repeat(start.0 - self.end.0));
+            self.end = (start.0, 1);
+        }
+        if start.0 == self.end.0 && start.1 > self.end.1 {
+            self.text.push_str(&" ".repeat(start.1 - self.end.1));
+        } else if start < self.end && !self.text.is_empty() {
+            self.text.push(' ');
+        }
+        self.tokens.push((start.max(self.end), span));
+        self.text.push_str(text);
+        let end = span.end();
+        self.end = (end.line(), end.column()).max(self.end);
+    }
+
+    // The span of the token at ```pos```, or of the last one before it
+    fn span(&self, pos: Pos) -> Span {
+        let after = self.tokens.partition_point(|(start, _)| *start <= pos);
+        self.tokens
+            .get(after.saturating_sub(1))
+            .map_or_else(Span::call_site, |(_, span)| *span)
+    }
+}
+
+// Every problem with a program, which is where it is if that is known and what it is
+type Problems = Vec<(Option<Pos>, String)>;
+
+fn problem(pos: Option<bril_rs::Position>, message: &impl ToString) -> Problems {
+    let pos = pos.and_then(|p| Some((usize::try_from(p.row).ok()?, usize::try_from(p.col).ok()?)));
+    vec![(pos, message.to_string())]
+}
+
+// The program in ```text``` as JSON without source positions, once it has been parsed and type checked
+fn compile(text: &str) -> Result<String, Problems> {
+    let abstract_prog = bril2json::parse_abstract_program_from_str(text, true)
+        .map_err(|e| problem(e.pos, &e.message))?;
+    let prog = Program::try_from(abstract_prog).map_err(|e| problem(e.pos(), &e.error()))?;
+    let bbprog = BBProgram::new(prog).map_err(|e| {
+        let e = e.add_pos(None);
+        problem(e.pos(), &e.error())
+    })?;
+    let problems: Problems = check::type_check_all(&bbprog)
+        .into_iter()
+        .filter(|d| d.severity == Severity::Error)
+        .flat_map(|d| {
+            let pos = d.line.zip(d.column).map(|(row, col)| bril_rs::Position {
+                row,
+                col,
+                derived: false,
+                source: None,
+            });
+            problem(pos, &d.message)
+        })
+        .collect();
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+
+    // The positions are of the Rust source, which don't mean anything to the program once it is built
+    let abstract_prog = bril2json::parse_abstract_program_from_str(text, false)
+        .map_err(|e| problem(e.pos, &e.message))?;
+    let prog = Program::try_from(abstract_prog).map_err(|e| problem(e.pos(), &e.error()))?;
+    Ok(serde_json::to_string(&prog).unwrap())
+}
@@ -0,0 +1,15 @@
+use clap::Parser;
+
+use crate::Transform;
+
+#[derive(Parser)]
+#[clap(about, version, author)] // keeps the cli synced with Cargo.toml
+pub struct Cli {
+    /// The seed that every random choice is made from. The same seed always mangles a program the same way
+    #[clap(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// A transformation to apply. Transformations run in the order they are given and can be repeated. Every transformation runs once if none are given
+    #[clap(short, long = "transform", arg_enum)]
+    pub transforms: Vec<Transform>,
+}
@@ -0,0 +1,385 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
+
+use std::collections::{HashMap, HashSet};
+
+use bril_rs::cfg::{check_labels, BasicBlock, Cfg, CfgError};
+use bril_rs::ssa::UNDEFINED;
+use bril_rs::{Attrs, Code, ConstOps, EffectOps, Instruction, Literal, Program, Type, ValueOps};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+#[doc(hidden)]
+pub mod cli;
+
+/// The transformations which can be applied with [`mangle`]. None of them change what a program prints or returns, only how it is laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum Transform {
+    /// Splits about half of the basic blocks in two at a random instruction, joining the halves with a `jmp`
+    Split,
+    /// Moves the terminator of about half of the basic blocks into a block of its own, so that the block ends with a `jmp` to it
+    Jump,
+    /// Adds instructions whose results are never used to about half of the basic blocks, and a block which can never run to about half of the functions
+    Dead,
+    /// Renames every variable, label, and function other than `main` and the functions which are imported
+    Rename,
+    /// Puts the functions, and the blocks of each function other than the first, in a random order
+    Shuffle,
+}
+
+impl Transform {
+    /// Every transformation, in the order that [`mangle`] runs them by default. Renaming comes last so that the labels and variables the others add are renamed too
+    pub const ALL: [Self; 5] = [
+        Self::Split,
+        Self::Jump,
+        Self::Dead,
+        Self::Shuffle,
+        Self::Rename,
+    ];
+
+    /// Applies the transformation to ```prog``` with the random choices made by ```rng```
+    ///
+    /// # Errors
+    /// Will return [`MangleError`] if a `jmp` or `br` targets a label which its function doesn't have, in which case ```prog``` is left as it was
+    pub fn run(self, prog: &mut Program, rng: &mut StdRng) -> Result<(), MangleError> {
+        for func in &prog.functions {
+            check_labels(&func.instrs).map_err(|error| MangleError {
+                func: func.name.clone(),
+                error,
+            })?;
+        }
+        match self {
+            Self::Rename => rename(prog, rng),
+            Self::Shuffle => {
+                prog.functions.shuffle(rng);
+                for_each_cfg(prog, |cfg| {
+                    // Every block ends with a terminator, so only the entry has to stay where it is
+                    cfg.blocks[1..].shuffle(rng);
+                });
+            }
+            Self::Split => for_each_cfg(prog, |cfg| {
+                for index in (0..cfg.blocks.len()).rev() {
+                    if rng.gen_bool(0.5) {
+                        let instrs = &cfg.blocks[index].instrs;
+                        let at = rng.gen_range(leading_phis(instrs)..instrs.len());
+                        cfg.split_block(index, at);
+                    }
+                }
+            }),
+            Self::Jump => for_each_cfg(prog, |cfg| {
+                for index in (0..cfg.blocks.len()).rev() {
+                    if rng.gen_bool(0.5) {
+                        cfg.split_block(index, cfg.blocks[index].instrs.len() - 1);
+                    }
+                }
+            }),
+            Self::Dead => {
+                for func in &mut prog.functions {
+                    let mut names = variables(&func.instrs);
+                    names.extend(func.args.iter().map(|a| a.name.clone()));
+                    let mut dead = Dead {
+                        rng,
+                        names,
+                        next: 0,
+                    };
+                    let mut cfg = blocks(std::mem::take(&mut func.instrs));
+                    dead.add(&mut cfg);
+                    func.instrs = cfg.into_code();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The error for a function which can't be split into basic blocks, because a `jmp` or `br` in it targets a label which it doesn't have
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MangleError {
+    /// The name of the function
+    pub func: String,
+    /// What is wrong with its labels
+    pub error: CfgError,
+}
+
+impl std::fmt::Display for MangleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "@{}: {}", self.func, self.error)
+    }
+}
+
+impl std::error::Error for MangleError {}
+
+/// Applies each of ```transforms``` in order to ```prog```, making every random choice from ```seed``` so that the same seed always gives the same program
+///
+/// # Errors
+/// Will return [`MangleError`] if a `jmp` or `br` targets a label which its function doesn't have
+pub fn mangle(prog: &mut Program, transforms: &[Transform], seed: u64) -> Result<(), MangleError> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    transforms
+        .iter()
+        .try_for_each(|transform| transform.run(prog, &mut rng))
+}
+
+// The blocks of ```instrs```, whose labels [`Transform::run`] has checked
+fn blocks(instrs: Vec<Code>) -> Cfg {
+    Cfg::new(instrs).expect("every jump goes to a label of its function")
+}
+
+fn for_each_cfg(prog: &mut Program, mut f: impl FnMut(&mut Cfg)) {
+    for func in &mut prog.functions {
+        let mut cfg = blocks(std::mem::take(&mut func.instrs));
+        f(&mut cfg);
+        func.instrs = cfg.into_code();
+    }
+}
+
+// The number of phi nodes at the start of ```instrs```. A phi takes its value from the block that ran before the one it is in, so these have to stay at the start of their block
+fn leading_phis(instrs: &[Instruction]) -> usize {
+    instrs
+        .iter()
+        .take_while(|i| {
+            matches!(
+                i,
+                Instruction::Value {
+                    op: ValueOps::Phi,
+                    ..
+                }
+            )
+        })
+        .count()
+}
+
+// Every variable assigned or used in ```code```
+fn variables(code: &[Code]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for code in code {
+        match code {
+            Code::Instruction(Instruction::Constant { dest, .. }) => {
+                names.insert(dest.clone());
+            }
+            Code::Instruction(Instruction::Value { dest, args, .. }) => {
+                names.insert(dest.clone());
+                names.extend(args.iter().cloned());
+            }
+            Code::Instruction(Instruction::Effect { args, .. }) => {
+                names.extend(args.iter().cloned());
+            }
+            Code::Label { .. } => {}
+        }
+    }
+    names
+}
+
+// Makes dead code out of variables which aren't used anywhere else in the function
+struct Dead<'a> {
+    rng: &'a mut StdRng,
+    names: HashSet<String>,
+    next: usize,
+}
+
+impl Dead<'_> {
+    fn fresh(&mut self) -> String {
+        loop {
+            let name = format!("dead.{}", self.next);
+            self.next += 1;
+            if self.names.insert(name.clone()) {
+                return name;
+            }
+        }
+    }
+
+    // One to three instructions which only use each other's results
+    fn instrs(&mut self) -> Vec<Instruction> {
+        let (ty, value, op) = if self.rng.gen_bool(0.5) {
+            let ops = [ValueOps::Add, ValueOps::Sub, ValueOps::Mul];
+            (
+                Type::Int,
+                Literal::Int(self.rng.gen_range(-100..100)),
+                *ops.choose(self.rng).unwrap(),
+            )
+        } else {
+            let ops = [ValueOps::And, ValueOps::Or];
+            (
+                Type::Bool,
+                Literal::Bool(self.rng.gen_bool(0.5)),
+                *ops.choose(self.rng).unwrap(),
+            )
+        };
+        let first = self.fresh();
+        let mut instrs = vec![Instruction::Constant {
+            dest: first.clone(),
+            op: ConstOps::Const,
+            const_type: ty.clone(),
+            value,
+            pos: None,
+            attrs: Attrs::new(),
+        }];
+        let mut previous = first.clone();
+        for _ in 0..self.rng.gen_range(0..3) {
+            let dest = self.fresh();
+            instrs.push(Instruction::Value {
+                args: vec![
+                    first.clone(),
+                    std::mem::replace(&mut previous, dest.clone()),
+                ],
+                dest,
+                funcs: Vec::new(),
+                labels: Vec::new(),
+                op,
+                op_type: ty.clone(),
+                pos: None,
+                extra_dests: Vec::new(),
+                attrs: Attrs::new(),
+            });
+        }
+        instrs
+    }
+
+    fn add(&mut self, cfg: &mut Cfg) {
+        for block in &mut cfg.blocks {
+            if self.rng.gen_bool(0.5) {
+                // Dead code goes after any phis and before the terminator
+                let at = self
+                    .rng
+                    .gen_range(leading_phis(&block.instrs)..block.instrs.len());
+                let instrs = self.instrs();
+                block.instrs.splice(at..at, instrs);
+            }
+        }
+        if self.rng.gen_bool(0.5) {
+            // Only jumping to itself means the block adds no edges to the rest of the function, so no phi needs a value from it
+            let label = cfg.fresh_label("dead");
+            let mut instrs = self.instrs();
+            instrs.push(Instruction::Effect {
+                args: Vec::new(),
+                funcs: Vec::new(),
+                labels: vec![label.clone()],
+                op: EffectOps::Jump,
+                pos: None,
+                attrs: Attrs::new(),
+            });
+            let index = self.rng.gen_range(1..=cfg.blocks.len());
+            cfg.insert_block(
+                index,
+                BasicBlock {
+                    label,
+                    pos: None,
+                    instrs,
+                },
+            )
+            .expect("the block only jumps to itself");
+        }
+    }
+}
+
+// Gives each of ```names``` a new name made of ```prefix``` and a number, in a random order so that the numbers say nothing about where the names were. Names in ```keep``` stay as they are and aren't given to anything else
+fn shuffled_names(
+    names: HashSet<String>,
+    keep: &HashSet<String>,
+    prefix: &str,
+    rng: &mut StdRng,
+) -> HashMap<String, String> {
+    let mut names: Vec<String> = names.into_iter().filter(|n| !keep.contains(n)).collect();
+    // Sorting first makes the order only depend on the seed and not on how the set was hashed
+    names.sort();
+    names.shuffle(rng);
+    let fresh = (0..)
+        .map(|i| format!("{prefix}{i}"))
+        .filter(|n| !keep.contains(n));
+    names.into_iter().zip(fresh).collect()
+}
+
+fn rename_all(names: &mut [String], map: &HashMap<String, String>) {
+    for name in names {
+        if let Some(new) = map.get(name) {
+            new.clone_into(name);
+        }
+    }
+}
+
+fn rename(prog: &mut Program, rng: &mut StdRng) {
+    let mut keep: HashSet<String> = prog
+        .imports
+        .iter()
+        .flat_map(|i| &i.functions)
+        .map(|f| f.alias.clone().unwrap_or_else(|| f.name.clone()))
+        .collect();
+    keep.insert("main".to_string());
+    let funcs = shuffled_names(
+        prog.functions.iter().map(|f| f.name.clone()).collect(),
+        &keep,
+        "f",
+        rng,
+    );
+
+    let undefined = HashSet::from([UNDEFINED.to_string()]);
+    for func in &mut prog.functions {
+        if let Some(new) = funcs.get(&func.name) {
+            new.clone_into(&mut func.name);
+        }
+
+        let mut names = variables(&func.instrs);
+        names.extend(func.args.iter().map(|a| a.name.clone()));
+        let vars = shuffled_names(names, &undefined, "v", rng);
+        let labels = shuffled_names(
+            func.instrs
+                .iter()
+                .filter_map(|c| match c {
+                    Code::Label { label, .. } => Some(label.clone()),
+                    Code::Instruction(_) => None,
+                })
+                .collect(),
+            &HashSet::new(),
+            "l",
+            rng,
+        );
+
+        for arg in &mut func.args {
+            if let Some(new) = vars.get(&arg.name) {
+                new.clone_into(&mut arg.name);
+            }
+        }
+        for code in &mut func.instrs {
+            match code {
+                Code::Label { label, .. } => {
+                    if let Some(new) = labels.get(label) {
+                        new.clone_into(label);
+                    }
+                }
+                Code::Instruction(Instruction::Constant { dest, .. }) => {
+                    if let Some(new) = vars.get(dest) {
+                        new.clone_into(dest);
+                    }
+                }
+                Code::Instruction(Instruction::Value {
+                    args,
+                    dest,
+                    funcs: called,
+                    labels: targets,
+                    ..
+                }) => {
+                    if let Some(new) = vars.get(dest) {
+                        new.clone_into(dest);
+                    }
+                    rename_all(args, &vars);
+                    rename_all(called, &funcs);
+                    rename_all(targets, &labels);
+                }
+                Code::Instruction(Instruction::Effect {
+                    args,
+                    funcs: called,
+                    labels: targets,
+                    ..
+                }) => {
+                    rename_all(args, &vars);
+                    rename_all(called, &funcs);
+                    rename_all(targets, &labels);
+                }
+            }
+        }
+    }
+}
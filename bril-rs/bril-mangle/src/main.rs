@@ -0,0 +1,19 @@
+use bril_mangle::cli::Cli;
+use bril_mangle::{mangle, Transform};
+use bril_rs::{load_program, output_program};
+use clap::Parser;
+
+fn main() {
+    let args = Cli::parse();
+    let mut prog = load_program();
+    let transforms = if args.transforms.is_empty() {
+        Transform::ALL.to_vec()
+    } else {
+        args.transforms
+    };
+    if let Err(e) = mangle(&mut prog, &transforms, args.seed) {
+        eprintln!("error: {e}");
+        std::process::exit(1)
+    }
+    output_program(&prog);
+}
@@ -0,0 +1,603 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::{self, Display, Formatter};
+use std::io::Write;
+
+use bril_rs::cfg::{BasicBlock, Cfg};
+use bril_rs::{EffectOps, Function, Instruction, Literal, Position, Program, Type, ValueOps};
+use serde::{Serialize, Serializer};
+
+use crate::{function_blocks, FunctionError};
+
+/// The abstract value of every integer variable which has been defined at a program point. Variables of other types are not tracked.
+pub type State<D> = BTreeMap<String, D>;
+
+/// A comparison between two integers which a branch has found to be true, for [`Domain::assume`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    /// `a < b`
+    Lt,
+    /// `a <= b`
+    Le,
+    /// `a == b`
+    Eq,
+    /// `a != b`
+    Ne,
+}
+
+/// An abstraction of sets of integers which [`analyze`] can compute over. Every value stands for a non-empty set of integers.
+pub trait Domain: Clone + PartialEq + Serialize {
+    /// Any integer at all
+    fn top() -> Self;
+
+    /// Exactly ```n```
+    fn constant(n: i64) -> Self;
+
+    /// The smallest value standing for every integer of both ```self``` and ```other```
+    #[must_use]
+    fn join(&self, other: &Self) -> Self;
+
+    /// Joins ```self``` with the ```next``` value reached at the head of a loop, going far enough up that the loop can only go around a finite number of times before nothing changes. Domains with no infinite ascending chains can leave this as [`Domain::join`].
+    #[must_use]
+    fn widen(&self, next: &Self) -> Self {
+        self.join(next)
+    }
+
+    /// The result of the integer operation ```op``` on ```a``` and ```b```, taking into account that arithmetic wraps on overflow
+    fn arith(op: ValueOps, a: &Self, b: &Self) -> Self;
+
+    /// Narrows ```a``` and ```b``` down to the values which satisfy ```relation```, or [`None`] if none of them do. Leaving both as they are is always correct.
+    fn assume(relation: Relation, a: &Self, b: &Self) -> Option<(Self, Self)> {
+        let _ = relation;
+        Some((a.clone(), b.clone()))
+    }
+}
+
+/// An integer range for interval analysis. A missing bound is unbounded, which is the same as the smallest or largest `int`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Interval {
+    /// The smallest value
+    pub min: Option<i64>,
+    /// The largest value
+    pub max: Option<i64>,
+}
+
+impl Interval {
+    fn lo(self) -> i128 {
+        self.min.unwrap_or(i64::MIN).into()
+    }
+
+    fn hi(self) -> i128 {
+        self.max.unwrap_or(i64::MAX).into()
+    }
+
+    // The range between ```lo``` and ```hi```, or everything if either is outside of an `int` since the arithmetic wrapped
+    fn wrapping(lo: i128, hi: i128) -> Self {
+        let min = i64::try_from(lo);
+        let max = i64::try_from(hi);
+        match (min, max) {
+            (Ok(min), Ok(max)) => Self::new(min.into(), max.into()).unwrap_or_else(Self::top),
+            _ => Self::top(),
+        }
+    }
+
+    // The range between ```lo``` and ```hi```, which must be within an `int`, if it isn't empty
+    fn new(lo: i128, hi: i128) -> Option<Self> {
+        (lo <= hi).then(|| Self {
+            min: (lo > i64::MIN.into()).then(|| i64::try_from(lo).unwrap()),
+            max: (hi < i64::MAX.into()).then(|| i64::try_from(hi).unwrap()),
+        })
+    }
+
+    // The smallest range holding every one of ```values```
+    fn hull(values: impl Iterator<Item = i128>) -> Self {
+        let (lo, hi) = values.fold((i128::MAX, i128::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)));
+        Self::wrapping(lo, hi)
+    }
+
+    fn divide(a: Self, b: Self) -> Option<Self> {
+        // Dividing by zero is an error, so only the non-zero divisors produce a result
+        let negative = Self::new(b.lo(), b.hi().min(-1));
+        let positive = Self::new(b.lo().max(1), b.hi());
+        negative
+            .into_iter()
+            .chain(positive)
+            .map(|b| {
+                Self::hull(
+                    [
+                        (a.lo(), b.lo()),
+                        (a.lo(), b.hi()),
+                        (a.hi(), b.lo()),
+                        (a.hi(), b.hi()),
+                    ]
+                    .into_iter()
+                    .map(|(x, y)| x / y),
+                )
+            })
+            .reduce(|x, y| x.join(&y))
+    }
+}
+
+impl Domain for Interval {
+    fn top() -> Self {
+        Self {
+            min: None,
+            max: None,
+        }
+    }
+
+    fn constant(n: i64) -> Self {
+        Self::new(n.into(), n.into()).unwrap()
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        Self::new(self.lo().min(other.lo()), self.hi().max(other.hi())).unwrap()
+    }
+
+    fn widen(&self, next: &Self) -> Self {
+        Self {
+            min: self.min.filter(|_| next.lo() >= self.lo()),
+            max: self.max.filter(|_| next.hi() <= self.hi()),
+        }
+    }
+
+    fn arith(op: ValueOps, a: &Self, b: &Self) -> Self {
+        match op {
+            ValueOps::Add => Self::wrapping(a.lo() + b.lo(), a.hi() + b.hi()),
+            ValueOps::Sub => Self::wrapping(a.lo() - b.hi(), a.hi() - b.lo()),
+            ValueOps::Mul => Self::hull(
+                [
+                    a.lo() * b.lo(),
+                    a.lo() * b.hi(),
+                    a.hi() * b.lo(),
+                    a.hi() * b.hi(),
+                ]
+                .into_iter(),
+            ),
+            // A division which always fails never produces a value, but anything is still correct
+            ValueOps::Div => Self::divide(*a, *b).unwrap_or_else(Self::top),
+            _ => Self::top(),
+        }
+    }
+
+    fn assume(relation: Relation, a: &Self, b: &Self) -> Option<(Self, Self)> {
+        match relation {
+            Relation::Lt => Some((
+                Self::new(a.lo(), a.hi().min(b.hi() - 1))?,
+                Self::new(b.lo().max(a.lo() + 1), b.hi())?,
+            )),
+            Relation::Le => Some((
+                Self::new(a.lo(), a.hi().min(b.hi()))?,
+                Self::new(b.lo().max(a.lo()), b.hi())?,
+            )),
+            Relation::Eq => {
+                let both = Self::new(a.lo().max(b.lo()), a.hi().min(b.hi()))?;
+                Some((both, both))
+            }
+            // Only a bound equal to a single value on the other side can be excluded
+            Relation::Ne => {
+                let exclude = |x: Self, y: Self| match (y.lo() == y.hi(), y.lo()) {
+                    (true, v) if x.lo() == v => Self::new(v + 1, x.hi()),
+                    (true, v) if x.hi() == v => Self::new(x.lo(), v - 1),
+                    _ => Some(x),
+                };
+                Some((exclude(*a, *b)?, exclude(*b, *a)?))
+            }
+        }
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.min {
+            Some(n) => write!(f, "[{n}, ")?,
+            None => write!(f, "[-inf, ")?,
+        }
+        match self.max {
+            Some(n) => write!(f, "{n}]"),
+            None => write!(f, "+inf]"),
+        }
+    }
+}
+
+/// Which signs an integer can have for sign analysis. At least one of them is always possible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sign {
+    /// Whether the integer can be less than zero
+    pub negative: bool,
+    /// Whether the integer can be zero
+    pub zero: bool,
+    /// Whether the integer can be greater than zero
+    pub positive: bool,
+}
+
+impl Sign {
+    // The ranges of integers with each possible sign
+    fn parts(self) -> impl Iterator<Item = Interval> {
+        [
+            (self.negative, Interval::new(i64::MIN.into(), -1)),
+            (self.zero, Interval::new(0, 0)),
+            (self.positive, Interval::new(1, i64::MAX.into())),
+        ]
+        .into_iter()
+        .filter_map(|(possible, part)| possible.then_some(part).flatten())
+    }
+
+    fn of(interval: Interval) -> Self {
+        Self {
+            negative: interval.lo() < 0,
+            zero: interval.lo() <= 0 && interval.hi() >= 0,
+            positive: interval.hi() > 0,
+        }
+    }
+}
+
+// Each operation on signs is worked out by doing it on the ranges of integers with those signs
+impl Domain for Sign {
+    fn top() -> Self {
+        Self {
+            negative: true,
+            zero: true,
+            positive: true,
+        }
+    }
+
+    fn constant(n: i64) -> Self {
+        Self::of(Interval::constant(n))
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        Self {
+            negative: self.negative || other.negative,
+            zero: self.zero || other.zero,
+            positive: self.positive || other.positive,
+        }
+    }
+
+    fn arith(op: ValueOps, a: &Self, b: &Self) -> Self {
+        a.parts()
+            .flat_map(|x| {
+                b.parts()
+                    .map(move |y| Self::of(Interval::arith(op, &x, &y)))
+            })
+            .reduce(|x, y| x.join(&y))
+            .unwrap_or_else(Self::top)
+    }
+
+    fn assume(relation: Relation, a: &Self, b: &Self) -> Option<(Self, Self)> {
+        a.parts()
+            .flat_map(|x| b.parts().map(move |y| (x, y)))
+            .filter_map(|(x, y)| Interval::assume(relation, &x, &y))
+            .map(|(x, y)| (Self::of(x), Self::of(y)))
+            .reduce(|(a1, b1), (a2, b2)| (a1.join(&a2), b1.join(&b2)))
+    }
+}
+
+impl Display for Sign {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match (self.negative, self.zero, self.positive) {
+            (true, false, false) => "negative",
+            (false, true, false) => "zero",
+            (false, false, true) => "positive",
+            (true, true, false) => "nonpositive",
+            (false, true, true) => "nonnegative",
+            (true, false, true) => "nonzero",
+            _ => "any",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl Serialize for Sign {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// The domains that `bril-opt --absint` can analyze programs with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum Analysis {
+    /// [`Interval`]
+    Interval,
+    /// [`Sign`]
+    Sign,
+}
+
+impl Analysis {
+    /// Analyzes every function of ```prog``` and writes the facts to ```out``` as JSON
+    /// # Errors
+    /// Will return [`FunctionError`] if a `jmp` or `br` targets a label which its function doesn't have, in which case nothing is written, or an error if writing to ```out``` fails
+    pub fn run<W: Write>(self, prog: &Program, out: W) -> Result<(), Box<dyn std::error::Error>> {
+        let written = match self {
+            Self::Interval => {
+                serde_json::to_writer_pretty(out, &analyze_program::<Interval>(prog)?)
+            }
+            Self::Sign => serde_json::to_writer_pretty(out, &analyze_program::<Sign>(prog)?),
+        };
+        Ok(written?)
+    }
+}
+
+/// The results of [`analyze_program`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgramFacts<D> {
+    /// The facts of each function, in the order they appear in the program
+    pub functions: Vec<FunctionFacts<D>>,
+}
+
+/// The results of [`analyze`] for one function
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionFacts<D> {
+    /// The name of the function
+    pub name: String,
+    /// The facts of each basic block, in the order of [`Cfg::blocks`]
+    pub blocks: Vec<BlockFacts<D>>,
+}
+
+/// The facts of a basic block
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockFacts<D> {
+    /// The label of the block, which is made up for blocks which were not labelled
+    pub label: String,
+    /// The state on entry to the block, or [`None`] if the block can never run
+    pub entry: Option<State<D>>,
+    /// The state after each instruction of the block
+    pub instrs: Vec<InstrFacts<D>>,
+}
+
+/// The state after an instruction
+#[derive(Debug, Clone, Serialize)]
+pub struct InstrFacts<D> {
+    /// The instruction as Bril text
+    pub instr: String,
+    /// Where the instruction is located in source code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pos: Option<Position>,
+    /// The state after the instruction runs, or [`None`] if it can never run
+    pub state: Option<State<D>>,
+}
+
+/// Runs [`analyze`] over every function of ```prog```
+/// # Errors
+/// Will return [`FunctionError`] for the first function with a `jmp` or `br` to a label which it doesn't have
+pub fn analyze_program<D: Domain>(prog: &Program) -> Result<ProgramFacts<D>, FunctionError> {
+    Ok(ProgramFacts {
+        functions: prog
+            .functions
+            .iter()
+            .map(analyze)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// Abstractly interprets ```func``` over its control flow graph with the domain ```D```, starting with every integer argument unknown.
+///
+/// Branches on a comparison narrow the values of its arguments along each side, and the blocks at the head of a loop are widened so that the analysis always finishes. Once nothing changes, the blocks are gone over a couple more times without widening to narrow the facts back down.
+/// # Errors
+/// Will return [`FunctionError`] if a `jmp` or `br` targets a label which ```func``` doesn't have
+pub fn analyze<D: Domain>(func: &Function) -> Result<FunctionFacts<D>, FunctionError> {
+    let cfg = function_blocks(func)?;
+    let num_blocks = cfg.blocks.len();
+    // Blocks which are jumped back to from themselves or a later block
+    let loop_heads: Vec<bool> = (0..num_blocks)
+        .map(|b| cfg.predecessors[b].iter().any(|p| *p >= b))
+        .collect();
+
+    let mut entries: Vec<Option<State<D>>> = vec![None; num_blocks];
+    entries[0] = Some(
+        func.args
+            .iter()
+            .filter(|a| a.arg_type == Type::Int)
+            .map(|a| (a.name.clone(), D::top()))
+            .collect(),
+    );
+    let mut work_list: VecDeque<usize> = VecDeque::from([0]);
+    let mut queued = vec![false; num_blocks];
+    queued[0] = true;
+    while let Some(b) = work_list.pop_front() {
+        queued[b] = false;
+        let Some(entry) = &entries[b] else {
+            continue;
+        };
+        for (succ, state) in edges(&cfg, b, &run_block(&cfg.blocks[b], entry)) {
+            let new = match &entries[succ] {
+                None => state,
+                Some(old) if loop_heads[succ] => combine(old, &join(old, &state), D::widen),
+                Some(old) => join(old, &state),
+            };
+            if entries[succ].as_ref() != Some(&new) {
+                entries[succ] = Some(new);
+                if !queued[succ] {
+                    queued[succ] = true;
+                    work_list.push_back(succ);
+                }
+            }
+        }
+    }
+
+    // Widening can overshoot, so a few more rounds without it take back what can't actually be reached
+    for _ in 0..NARROWING_ROUNDS {
+        // The entry block never has any predecessors so it keeps the state of the arguments
+        for b in 1..num_blocks {
+            entries[b] = cfg.predecessors[b]
+                .iter()
+                .filter_map(|&p| {
+                    let exit = run_block(&cfg.blocks[p], entries[p].as_ref()?);
+                    edges(&cfg, p, &exit)
+                        .into_iter()
+                        .find(|(succ, _)| *succ == b)
+                        .map(|(_, state)| state)
+                })
+                .reduce(|a, b| join(&a, &b));
+        }
+    }
+
+    let blocks = cfg
+        .blocks
+        .iter()
+        .zip(entries)
+        .map(|(block, entry)| {
+            let mut state = entry.clone();
+            let instrs = block
+                .instrs
+                .iter()
+                .map(|instr| {
+                    if let Some(state) = &mut state {
+                        transfer(state, instr);
+                    }
+                    InstrFacts {
+                        instr: instr.to_string().trim().to_string(),
+                        pos: instr.get_pos(),
+                        state: state.clone(),
+                    }
+                })
+                .collect();
+            BlockFacts {
+                label: block.label.clone(),
+                entry,
+                instrs,
+            }
+        })
+        .collect();
+    Ok(FunctionFacts {
+        name: func.name.clone(),
+        blocks,
+    })
+}
+
+// How many times [`analyze`] goes back over every block after reaching a fixpoint
+const NARROWING_ROUNDS: usize = 2;
+
+fn run_block<D: Domain>(block: &BasicBlock, entry: &State<D>) -> State<D> {
+    let mut state = entry.clone();
+    for instr in &block.instrs {
+        transfer(&mut state, instr);
+    }
+    state
+}
+
+// Combines the values of variables defined in both states with ```f```, keeping the rest as they are
+fn combine<D: Domain>(a: &State<D>, b: &State<D>, f: impl Fn(&D, &D) -> D) -> State<D> {
+    let mut state = a.clone();
+    for (var, value) in b {
+        state
+            .entry(var.clone())
+            .and_modify(|v| *v = f(v, value))
+            .or_insert_with(|| value.clone());
+    }
+    state
+}
+
+fn join<D: Domain>(a: &State<D>, b: &State<D>) -> State<D> {
+    combine(a, b, D::join)
+}
+
+fn get<D: Domain>(state: &State<D>, var: &str) -> D {
+    state.get(var).cloned().unwrap_or_else(D::top)
+}
+
+fn transfer<D: Domain>(state: &mut State<D>, instr: &Instruction) {
+    match instr {
+        Instruction::Constant {
+            dest,
+            const_type: Type::Int,
+            value: Literal::Int(n),
+            ..
+        } => {
+            state.insert(dest.clone(), D::constant(*n));
+        }
+        Instruction::Value {
+            dest,
+            op_type: Type::Int,
+            op,
+            args,
+            ..
+        } => {
+            let value = match op {
+                ValueOps::Id => get(state, &args[0]),
+                ValueOps::Add | ValueOps::Sub | ValueOps::Mul | ValueOps::Div => {
+                    D::arith(*op, &get(state, &args[0]), &get(state, &args[1]))
+                }
+                _ => D::top(),
+            };
+            state.insert(dest.clone(), value);
+        }
+        Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => {
+            state.remove(dest);
+        }
+        Instruction::Effect { .. } => {}
+    }
+}
+
+// The blocks which can run after block ```b``` and the state each of them starts with, given the ```state``` at the end of ```b```. A side of a branch which can't be taken is left out.
+fn edges<D: Domain>(cfg: &Cfg, b: usize, state: &State<D>) -> Vec<(usize, State<D>)> {
+    let block = &cfg.blocks[b];
+    match block.instrs.last() {
+        Some(Instruction::Effect {
+            op: EffectOps::Branch,
+            args,
+            labels,
+            ..
+        }) => {
+            let comparison = comparison(block, &args[0]);
+            [(&labels[0], true), (&labels[1], false)]
+                .into_iter()
+                .filter_map(|(label, taken)| {
+                    let mut state = state.clone();
+                    if let Some((op, x, y)) = comparison {
+                        let (relation, swap) = relation(op, taken);
+                        let (a, b) = if swap { (y, x) } else { (x, y) };
+                        let (a_value, b_value) =
+                            D::assume(relation, &get(&state, a), &get(&state, b))?;
+                        // Narrowing a comparison of a variable with itself doesn't make sense
+                        if a != b {
+                            state.insert(a.clone(), a_value);
+                            state.insert(b.clone(), b_value);
+                        }
+                    }
+                    Some((cfg.find(label).unwrap(), state))
+                })
+                .collect()
+        }
+        _ => cfg.successors[b]
+            .iter()
+            .map(|succ| (*succ, state.clone()))
+            .collect(),
+    }
+}
+
+// The comparison which computes ```cond``` in ```block```, as long as neither of its arguments change before the end of the block
+fn comparison<'a>(block: &'a BasicBlock, cond: &str) -> Option<(ValueOps, &'a String, &'a String)> {
+    let mut written = BTreeSet::new();
+    for instr in block.instrs.iter().rev() {
+        match instr {
+            Instruction::Value { dest, op, args, .. } if dest == cond => {
+                return match op {
+                    ValueOps::Lt | ValueOps::Le | ValueOps::Gt | ValueOps::Ge | ValueOps::Eq
+                        if !written.contains(&args[0]) && !written.contains(&args[1]) =>
+                    {
+                        Some((*op, &args[0], &args[1]))
+                    }
+                    _ => None,
+                };
+            }
+            Instruction::Constant { dest, .. } if dest == cond => return None,
+            Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => {
+                written.insert(dest);
+            }
+            Instruction::Effect { .. } => {}
+        }
+    }
+    None
+}
+
+// The relation which holds between the arguments of the comparison ```op``` when it is ```taken```, along with whether the arguments need to be swapped
+const fn relation(op: ValueOps, taken: bool) -> (Relation, bool) {
+    match (op, taken) {
+        (ValueOps::Lt, true) | (ValueOps::Ge, false) => (Relation::Lt, false),
+        (ValueOps::Le, true) | (ValueOps::Gt, false) => (Relation::Le, false),
+        (ValueOps::Gt, true) | (ValueOps::Le, false) => (Relation::Lt, true),
+        (ValueOps::Ge, true) | (ValueOps::Lt, false) => (Relation::Le, true),
+        (_, true) => (Relation::Eq, false),
+        (_, false) => (Relation::Ne, false),
+    }
+}
@@ -0,0 +1,64 @@
+use std::collections::BTreeMap;
+
+use bril_rs::alias::{Location, PointsTo};
+use bril_rs::{Code, Instruction, Program};
+use serde::Serialize;
+
+/// The results of [`report_program`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgramAliases {
+    /// The pointers of each function, in the order they appear in the program
+    pub functions: Vec<FunctionAliases>,
+}
+
+/// The pointers of one function and what they can point into
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionAliases {
+    /// The name of the function
+    pub name: String,
+    /// The locations each variable which can point anywhere can point into, written as the function and index among its instructions of the `alloc` which made them, like `@main[1]`, or as the function and argument they were passed in as, like `@main(xs)`
+    pub points_to: BTreeMap<String, Vec<String>>,
+}
+
+/// Runs the [`PointsTo`] analysis over ```prog``` and lists what the variables of each function can point into
+#[must_use]
+pub fn report_program(prog: &Program) -> ProgramAliases {
+    let points_to = PointsTo::new(prog);
+    let name = |location: Location| match location {
+        Location::Alloc { func, instr } => format!("@{}[{instr}]", prog.functions[func].name),
+        Location::Argument { func, arg } => format!(
+            "@{}({})",
+            prog.functions[func].name, prog.functions[func].args[arg].name
+        ),
+    };
+    ProgramAliases {
+        functions: prog
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(i, func)| {
+                let vars =
+                    func.args
+                        .iter()
+                        .map(|a| a.name.clone())
+                        .chain(func.instrs.iter().filter_map(|code| match code {
+                            Code::Instruction(
+                                Instruction::Constant { dest, .. }
+                                | Instruction::Value { dest, .. },
+                            ) => Some(dest.clone()),
+                            _ => None,
+                        }));
+                FunctionAliases {
+                    name: func.name.clone(),
+                    points_to: vars
+                        .filter_map(|var| {
+                            let locations = points_to.points_to(i, &var);
+                            (!locations.is_empty())
+                                .then(|| (var, locations.into_iter().map(name).collect()))
+                        })
+                        .collect(),
+                }
+            })
+            .collect(),
+    }
+}
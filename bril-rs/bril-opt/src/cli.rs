@@ -0,0 +1,65 @@
+use clap::Parser;
+
+use crate::absint::Analysis;
+use crate::specialize::Specialization;
+use crate::{Options, Pass};
+
+#[derive(Parser)]
+#[clap(about, version, author)] // keeps the cli synced with Cargo.toml
+#[clap(allow_hyphen_values(true))]
+pub struct Cli {
+    /// A pass to run over the program. Passes run in the order they are given and can be repeated
+    #[clap(short, long = "pass", arg_enum)]
+    pub passes: Vec<Pass>,
+
+    /// The most instructions a function can have and still be inlined by the inline pass
+    #[clap(long, default_value_t = Options::default().inline_threshold)]
+    pub inline_threshold: usize,
+
+    /// A function which the inline pass should never inline. Can be repeated
+    #[clap(long)]
+    pub noinline: Vec<String>,
+
+    /// How many copies of its body the unroll pass makes of a loop which it doesn't unroll completely
+    #[clap(long, default_value_t = Options::default().unroll_factor)]
+    pub unroll_factor: usize,
+
+    /// The most times a loop can run and still be unrolled completely by the unroll pass
+    #[clap(long, default_value_t = Options::default().full_unroll)]
+    pub full_unroll: usize,
+
+    /// A function which the dfe pass keeps along with everything it calls, like `main`, because something outside of the program calls it. Can be repeated
+    #[clap(long)]
+    pub entry_point: Vec<String>,
+
+    /// A function for the specialize pass to make a copy of with values for some of its arguments, written like `f:n=5,flag=true` or `f:arg0=5`. Can be repeated
+    #[clap(long)]
+    pub specialize: Vec<Specialization>,
+
+    /// The most copies the specialize pass makes for calls which pass constants, apart from those asked for with --specialize and the arguments of main
+    #[clap(long, default_value_t = Options::default().specialize_limit)]
+    pub specialize_limit: usize,
+
+    /// Read the rules of the peephole pass from this file instead of using the built-in ones, which are in `bril-opt/peephole.rules`
+    #[clap(long)]
+    pub rules: Option<std::path::PathBuf>,
+
+    /// Output the facts found by abstract interpretation with the given domain as JSON instead of the program, after running any passes
+    #[clap(long, arg_enum)]
+    pub absint: Option<Analysis>,
+
+    /// Output the register pressure of each block, and the registers a coloring puts the variables in, as JSON instead of the program, after running any passes
+    #[clap(long, conflicts_with = "absint")]
+    pub pressure: bool,
+
+    /// Output the control flow of each function as nested ifs, loops, and blocks instead of the program, after running any passes
+    #[clap(long, conflicts_with_all = &["absint", "pressure"])]
+    pub structure: bool,
+
+    /// Output the memory each pointer variable can point into, found by a points-to analysis of the whole program, as JSON instead of the program, after running any passes
+    #[clap(long, conflicts_with_all = &["absint", "pressure", "structure"])]
+    pub alias: bool,
+
+    /// The arguments of main, which the specialize pass builds into it
+    pub args: Vec<String>,
+}
@@ -0,0 +1,119 @@
+use std::collections::{HashMap, HashSet};
+
+use bril_rs::cfg::Cfg;
+use bril_rs::dataflow::def;
+use bril_rs::registers::LiveRanges;
+use bril_rs::{Argument, Instruction, Type, ValueOps};
+
+/// Coalesces each `id` whose destination doesn't interfere with the variable it copies, renaming the two to the same variable everywhere in the function and removing the copy.
+///
+/// A variable that is copied but still live takes up a register of its own next to the copy, so this lowers the register pressure [`LiveRanges`] estimates as well as getting rid of the copy.
+///
+/// Both variables need to have the same type. Arguments keep their names, so the copy of one argument into another stays where it is. Every copy that can be coalesced is, even when the merged variable ends up interfering with more variables than either one did.
+pub fn coalesce(cfg: &mut Cfg, args: &[Argument]) {
+    let mut interference = LiveRanges::new(cfg, args).interference;
+    // A variable which is given more than one type is never coalesced
+    let mut types: HashMap<String, Option<Type>> = HashMap::new();
+    let mut set_type = |var: &String, ty: &Type| {
+        types
+            .entry(var.clone())
+            .and_modify(|known| {
+                if known.as_ref() != Some(ty) {
+                    *known = None;
+                }
+            })
+            .or_insert_with(|| Some(ty.clone()));
+    };
+    for arg in args {
+        set_type(&arg.name, &arg.arg_type);
+    }
+    for instr in cfg.blocks.iter().flat_map(|b| &b.instrs) {
+        match instr {
+            Instruction::Constant {
+                dest, const_type, ..
+            } => set_type(dest, const_type),
+            Instruction::Value { dest, op_type, .. } => set_type(dest, op_type),
+            Instruction::Effect { .. } => {}
+        }
+    }
+    let params: HashSet<&str> = args.iter().map(|a| a.name.as_str()).collect();
+
+    // The variable each coalesced variable was merged into
+    let mut merged: HashMap<String, String> = HashMap::new();
+    for instr in cfg.blocks.iter().flat_map(|b| &b.instrs) {
+        let Instruction::Value {
+            op: ValueOps::Id,
+            dest,
+            args: copied,
+            ..
+        } = instr
+        else {
+            continue;
+        };
+        let dest = find(&merged, dest);
+        let src = find(&merged, &copied[0]);
+        if dest == src
+            || interference.get(&dest).is_some_and(|i| i.contains(&src))
+            || types.get(&dest).and_then(Option::as_ref).is_none()
+            || types.get(&dest) != types.get(&src)
+        {
+            continue;
+        }
+        let (keep, gone) = match (
+            params.contains(dest.as_str()),
+            params.contains(src.as_str()),
+        ) {
+            (true, true) => continue,
+            (true, false) => (dest, src),
+            (false, _) => (src, dest),
+        };
+        // The merged variable interferes with everything either of them did
+        let neighbours = interference.remove(&gone).unwrap_or_default();
+        for other in &neighbours {
+            if let Some(others) = interference.get_mut(other) {
+                others.remove(&gone);
+                others.insert(keep.clone());
+            }
+        }
+        interference
+            .entry(keep.clone())
+            .or_default()
+            .extend(neighbours);
+        merged.insert(gone, keep);
+    }
+    if merged.is_empty() {
+        return;
+    }
+
+    for block in &mut cfg.blocks {
+        for instr in &mut block.instrs {
+            match instr {
+                Instruction::Constant { dest, .. } => *dest = find(&merged, dest),
+                Instruction::Value { dest, args, .. } => {
+                    *dest = find(&merged, dest);
+                    for arg in args {
+                        *arg = find(&merged, arg);
+                    }
+                }
+                Instruction::Effect { args, .. } => {
+                    for arg in args {
+                        *arg = find(&merged, arg);
+                    }
+                }
+            }
+        }
+        // The copies between coalesced variables now copy a variable into itself
+        block.instrs.retain(|instr| {
+            !matches!(instr, Instruction::Value { op: ValueOps::Id, args, .. } if def(instr) == args.first())
+        });
+    }
+}
+
+// The variable which ```var``` ends up merged into
+fn find(merged: &HashMap<String, String>, var: &str) -> String {
+    let mut var = var;
+    while let Some(next) = merged.get(var) {
+        var = next;
+    }
+    var.to_string()
+}
@@ -0,0 +1,56 @@
+use std::collections::HashSet;
+
+use bril_rs::cfg::Cfg;
+use bril_rs::dataflow::{def, uses};
+
+use crate::is_pure;
+
+/// Removes instructions which only compute a result when that result is never read anywhere in the function, or is overwritten later in the same block before being read. Removing an instruction can make the instructions feeding it dead too, so this repeats until nothing else can be removed.
+pub fn eliminate(cfg: &mut Cfg) {
+    // Both have to run every time so `|` is used instead of `||`
+    while remove_unused(cfg) | remove_overwritten(cfg) {}
+}
+
+fn remove_unused(cfg: &mut Cfg) -> bool {
+    let used: HashSet<String> = cfg
+        .blocks
+        .iter()
+        .flat_map(|b| &b.instrs)
+        .flat_map(uses)
+        .cloned()
+        .collect();
+    let mut changed = false;
+    for block in &mut cfg.blocks {
+        let before = block.instrs.len();
+        block
+            .instrs
+            .retain(|i| !is_pure(i) || def(i).is_some_and(|d| used.contains(d)));
+        changed |= block.instrs.len() != before;
+    }
+    changed
+}
+
+fn remove_overwritten(cfg: &mut Cfg) -> bool {
+    let mut changed = false;
+    for block in &mut cfg.blocks {
+        // Going backwards, the variables which are written again further down the block before they are read
+        let mut overwritten = HashSet::new();
+        let mut keep = vec![true; block.instrs.len()];
+        for (idx, instr) in block.instrs.iter().enumerate().rev() {
+            if let Some(dest) = def(instr) {
+                if is_pure(instr) && overwritten.contains(dest) {
+                    keep[idx] = false;
+                    changed = true;
+                    continue;
+                }
+                overwritten.insert(dest.clone());
+            }
+            for arg in uses(instr) {
+                overwritten.remove(arg);
+            }
+        }
+        let mut keep = keep.into_iter();
+        block.instrs.retain(|_| keep.next().unwrap_or(true));
+    }
+    changed
+}
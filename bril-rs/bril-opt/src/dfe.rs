@@ -0,0 +1,40 @@
+use bril_rs::callgraph::CallGraph;
+use bril_rs::Program;
+
+use crate::Options;
+
+/// The indices of the functions of ```prog``` which can never be called, because no calls lead to them from `main` or from any of [`Options::entry_points`].
+///
+/// A program without `main` or any of the entry points, like a file of functions which other programs import, has nothing for its functions to be reached from, so none of them count as unreachable.
+#[must_use]
+pub fn unreachable(prog: &Program, options: &Options) -> Vec<usize> {
+    let graph = CallGraph::new(prog);
+    let roots: Vec<usize> = (0..graph.functions.len())
+        .filter(|&f| {
+            graph.functions[f] == "main" || options.entry_points.contains(&graph.functions[f])
+        })
+        .collect();
+    if roots.is_empty() {
+        return Vec::new();
+    }
+    let reached = graph.reachable(&roots);
+    (0..graph.functions.len())
+        .filter(|&f| !reached[f])
+        .collect()
+}
+
+/// Removes the functions which [`unreachable`] finds, giving back their names in the order they were in the program
+pub fn eliminate(prog: &mut Program, options: &Options) -> Vec<String> {
+    let mut dead = unreachable(prog, options).into_iter().peekable();
+    let mut removed = Vec::new();
+    let mut index = 0;
+    prog.functions.retain_mut(|func| {
+        let keep = dead.next_if_eq(&index).is_none();
+        index += 1;
+        if !keep {
+            removed.push(std::mem::take(&mut func.name));
+        }
+        keep
+    });
+    removed
+}
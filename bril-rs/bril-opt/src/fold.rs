@@ -0,0 +1,53 @@
+use bril_rs::cfg::Cfg;
+use bril_rs::dataflow::{solve, ConstantPropagation, ConstantValue};
+use bril_rs::{Argument, ConstOps, EffectOps, Instruction, Literal};
+
+/// Replaces every instruction which always produces the same constant with a `const` of that value, and every `br` on a condition which is always the same with a `jmp`. Constants are found across the whole function with [`ConstantPropagation`] given the function's ```args```.
+pub fn fold(cfg: &mut Cfg, args: &[Argument]) {
+    let result = solve(&ConstantPropagation::new(args), cfg);
+    for (block, mut consts) in cfg.blocks.iter_mut().zip(result.inputs) {
+        for instr in &mut block.instrs {
+            if let Instruction::Effect {
+                op: op @ EffectOps::Branch,
+                args,
+                labels,
+                ..
+            } = instr
+            {
+                if let Some(ConstantValue::Constant(Literal::Bool(cond))) = consts.get(&args[0]) {
+                    let target = labels[usize::from(!cond)].clone();
+                    *op = EffectOps::Jump;
+                    args.clear();
+                    *labels = vec![target];
+                }
+            }
+
+            ConstantPropagation::transfer_instr(&mut consts, instr);
+
+            if let Instruction::Value {
+                dest,
+                pos,
+                op_type,
+                attrs,
+                ..
+            } = instr
+            {
+                match consts.get(dest) {
+                    // Infinities and NaN can't be written as a Bril literal
+                    Some(ConstantValue::Constant(Literal::Float(f))) if !f.is_finite() => {}
+                    Some(ConstantValue::Constant(value)) => {
+                        *instr = Instruction::Constant {
+                            dest: dest.clone(),
+                            op: ConstOps::Const,
+                            pos: *pos,
+                            const_type: op_type.clone(),
+                            value: value.clone(),
+                            attrs: std::mem::take(attrs),
+                        };
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::{BTreeSet, HashMap};
+
+use bril_rs::cfg::Cfg;
+use bril_rs::dataflow::{constant_literal, fold_constant};
+use bril_rs::dominance::Dominators;
+use bril_rs::ssa::{function_to_ssa, UNDEFINED};
+use bril_rs::{
+    Argument, Attrs, ConstOps, Function, Instruction, Literal, Position, Type, ValueOps,
+};
+
+use crate::lvn::is_commutative;
+use crate::{blocks, is_pure};
+
+struct Numbering<'a> {
+    // The function being numbered, which the redundancies found in its SSA form are removed from
+    cfg: &'a mut Cfg,
+    ssa: Cfg,
+    doms: Dominators,
+    // The variable of the original function that each variable of the SSA form is a version of
+    original: HashMap<String, String>,
+    // The version that each variable of the original function holds at this point of the walk
+    current: HashMap<String, String>,
+    // The first variable of the SSA form to hold the same value as each variable, which is itself if it was the first
+    leaders: HashMap<String, String>,
+    // Every variable of the SSA form with each leader, in the order they were defined
+    members: HashMap<String, Vec<String>>,
+    // The constant each variable of the SSA form holds, if it is known
+    consts: HashMap<String, Literal>,
+    // The leader of each value which has been computed in a block dominating the current one, keyed by the operation and the leaders of its arguments
+    values: HashMap<String, String>,
+}
+
+impl Numbering<'_> {
+    fn leader(&self, var: &str) -> String {
+        self.leaders
+            .get(var)
+            .cloned()
+            .unwrap_or_else(|| var.to_string())
+    }
+
+    // A variable of the original function which holds the value of ```var``` of the SSA form at this point, if any of them still do
+    fn holder(&self, var: &str) -> Option<String> {
+        let leader = self.leaders.get(var)?;
+        self.members.get(leader)?.iter().find_map(|member| {
+            let original = self.original.get(member)?;
+            (self.current.get(original) == Some(member)).then(|| original.clone())
+        })
+    }
+
+    // Numbers ```instr``` of the SSA form, returning the leader of its value if it is redundant. Values first computed here are added to ```scope``` so that they can be forgotten once the blocks it dominates are done
+    fn number(
+        &mut self,
+        instr: &mut Instruction,
+        block: &str,
+        scope: &mut Vec<String>,
+    ) -> Option<String> {
+        // The arguments of a phi are read from the end of the previous blocks, where their leaders might not have been found yet
+        if let Instruction::Value {
+            op: ValueOps::Phi,
+            dest,
+            args,
+            labels,
+            op_type,
+            ..
+        } = instr
+        {
+            let mut incoming: Vec<(&String, String)> = labels
+                .iter()
+                .zip(args.iter())
+                .map(|(label, arg)| (label, self.leader(arg)))
+                .collect();
+            incoming.sort();
+            // A phi which only ever takes one value, apart from the value it already had, is that value
+            let distinct: BTreeSet<&String> = incoming
+                .iter()
+                .map(|(_, arg)| arg)
+                .filter(|arg| *arg != dest)
+                .collect();
+            if let [only] = distinct.into_iter().collect::<Vec<_>>()[..] {
+                if only != UNDEFINED {
+                    return Some(only.clone());
+                }
+            }
+            let key = format!("phi {op_type} {block} {incoming:?}");
+            return self.lookup(key, dest, scope);
+        }
+
+        if let Instruction::Value { args, .. } | Instruction::Effect { args, .. } = instr {
+            for arg in args.iter_mut() {
+                *arg = self.leader(arg);
+            }
+        }
+
+        // Computations on constants are constants themselves
+        if let Instruction::Value {
+            op,
+            dest,
+            args,
+            op_type,
+            pos,
+            attrs,
+            ..
+        } = instr
+        {
+            let literals: Option<Vec<Literal>> =
+                args.iter().map(|a| self.consts.get(a).cloned()).collect();
+            let folded = literals
+                .and_then(|literals| fold_constant(*op, &literals))
+                // Infinities and NaN can't be written as a Bril literal
+                .filter(|value| !matches!(value, Literal::Float(f) if !f.is_finite()));
+            if let Some(value) = folded {
+                let attrs = std::mem::take(attrs);
+                *instr = constant(dest.clone(), value, op_type.clone(), *pos, attrs);
+            }
+        }
+
+        let pure = is_pure(instr);
+        match instr {
+            Instruction::Constant {
+                dest,
+                const_type,
+                value,
+                ..
+            } => {
+                let value = constant_literal(const_type, value);
+                let key = format!("const {const_type} {value}");
+                self.consts.insert(dest.clone(), value);
+                self.lookup(key, dest, scope)
+            }
+            Instruction::Value {
+                op: ValueOps::Id,
+                args,
+                ..
+            } => Some(args[0].clone()),
+            Instruction::Value {
+                op,
+                dest,
+                args,
+                funcs,
+                labels,
+                op_type,
+                ..
+            } if pure => {
+                let mut args = args.clone();
+                if is_commutative(*op) {
+                    args.sort_unstable();
+                }
+                let key = format!("{op} {op_type} {args:?} {funcs:?} {labels:?}");
+                self.lookup(key, dest, scope)
+            }
+            Instruction::Value { .. } | Instruction::Effect { .. } => None,
+        }
+    }
+
+    // The leader of the value ```key```, or None after making ```dest``` its leader if it hasn't been computed yet
+    fn lookup(&mut self, key: String, dest: &str, scope: &mut Vec<String>) -> Option<String> {
+        if let Some(leader) = self.values.get(&key) {
+            return Some(leader.clone());
+        }
+        self.values.insert(key.clone(), dest.to_string());
+        scope.push(key);
+        None
+    }
+
+    // Makes ```var``` of the SSA form, whose value is the same as ```leader```, the version which ```original``` holds. The version it held before goes in ```overwritten``` so that it can be put back once the blocks this one dominates are done
+    fn define(
+        &mut self,
+        var: &str,
+        leader: &str,
+        original: &str,
+        overwritten: &mut Vec<(String, Option<String>)>,
+    ) {
+        if let Some(value) = self.consts.get(leader).cloned() {
+            self.consts.insert(var.to_string(), value);
+        }
+        self.leaders.insert(var.to_string(), leader.to_string());
+        self.members
+            .entry(leader.to_string())
+            .or_default()
+            .push(var.to_string());
+        self.original.insert(var.to_string(), original.to_string());
+        let before = self.current.insert(original.to_string(), var.to_string());
+        overwritten.push((original.to_string(), before));
+    }
+
+    fn visit(&mut self, block: usize) {
+        let mut scope = Vec::new();
+        let mut overwritten = Vec::new();
+        let label = self.ssa.blocks[block].label.clone();
+        let mut instrs = std::mem::take(&mut self.ssa.blocks[block].instrs);
+        // The phis which the SSA form starts some blocks with have nothing to replace in the original
+        let added = instrs.len() - self.cfg.blocks[block].instrs.len();
+        for (idx, instr) in instrs.iter_mut().enumerate() {
+            if idx < added {
+                let redundant = self.number(instr, &label, &mut scope);
+                if let Instruction::Value { dest, .. } = instr {
+                    let dest = dest.clone();
+                    // Variables of the SSA form are named `var.N` after the variable they are a version of
+                    let original = dest.rsplit_once('.').map_or(&*dest, |(var, _)| var);
+                    let leader = redundant.unwrap_or_else(|| dest.clone());
+                    self.define(&dest, &leader, original, &mut overwritten);
+                }
+                continue;
+            }
+            let idx = idx - added;
+
+            // Arguments are replaced with the first variable to hold their value as long as it still does, except for those of phis which are read from somewhere else
+            let holders: Vec<Option<String>> = match &*instr {
+                Instruction::Value {
+                    op: ValueOps::Phi, ..
+                }
+                | Instruction::Constant { .. } => Vec::new(),
+                Instruction::Value { args, .. } | Instruction::Effect { args, .. } => {
+                    args.iter().map(|arg| self.holder(arg)).collect()
+                }
+            };
+            if let Instruction::Value { args, .. } | Instruction::Effect { args, .. } =
+                &mut self.cfg.blocks[block].instrs[idx]
+            {
+                for (arg, holder) in args.iter_mut().zip(holders) {
+                    if let Some(holder) = holder {
+                        *arg = holder;
+                    }
+                }
+            }
+
+            let redundant = self.number(instr, &label, &mut scope);
+            let (Instruction::Constant { dest, .. } | Instruction::Value { dest, .. }) = &*instr
+            else {
+                continue;
+            };
+            let dest = dest.clone();
+            let leader = redundant.unwrap_or_else(|| dest.clone());
+            let (original, op_type, pos) = match &self.cfg.blocks[block].instrs[idx] {
+                Instruction::Constant {
+                    dest,
+                    const_type,
+                    pos,
+                    ..
+                } => (dest.clone(), const_type.clone(), *pos),
+                Instruction::Value {
+                    dest, op_type, pos, ..
+                } => (dest.clone(), op_type.clone(), *pos),
+                Instruction::Effect { .. } => continue,
+            };
+            // Recomputing a value into the variable which already holds it is left alone
+            let holds = self
+                .current
+                .get(&original)
+                .is_some_and(|var| self.leaders.get(var) == Some(&leader));
+            let holder = if leader == dest || holds {
+                None
+            } else {
+                self.holder(&leader)
+            };
+            let target = &mut self.cfg.blocks[block].instrs[idx];
+            match (holder, self.consts.get(&leader)) {
+                (Some(holder), _) => {
+                    let attrs = std::mem::take(target.attrs_mut());
+                    *target = copy(original.clone(), holder, op_type, pos, attrs);
+                }
+                (None, Some(value)) if !matches!(target, Instruction::Constant { .. }) => {
+                    let attrs = std::mem::take(target.attrs_mut());
+                    *target = constant(original.clone(), value.clone(), op_type, pos, attrs);
+                }
+                (None, _) => {}
+            }
+            self.define(&dest, &leader, &original, &mut overwritten);
+        }
+        self.ssa.blocks[block].instrs = instrs;
+
+        let children = self.doms.children(block).to_vec();
+        for child in children {
+            self.visit(child);
+        }
+        for key in scope {
+            self.values.remove(&key);
+        }
+        for (original, before) in overwritten.into_iter().rev() {
+            match before {
+                Some(before) => self.current.insert(original, before),
+                None => self.current.remove(&original),
+            };
+        }
+    }
+}
+
+// The instructions which replace a computation keep its metadata in ```attrs```
+fn copy(
+    dest: String,
+    arg: String,
+    op_type: Type,
+    pos: Option<Position>,
+    attrs: Attrs,
+) -> Instruction {
+    Instruction::Value {
+        args: vec![arg],
+        dest,
+        funcs: Vec::new(),
+        labels: Vec::new(),
+        op: ValueOps::Id,
+        pos,
+        op_type,
+        extra_dests: Vec::new(),
+        attrs,
+    }
+}
+
+const fn constant(
+    dest: String,
+    value: Literal,
+    const_type: Type,
+    pos: Option<Position>,
+    attrs: Attrs,
+) -> Instruction {
+    Instruction::Constant {
+        dest,
+        op: ConstOps::Const,
+        pos,
+        const_type,
+        value,
+        attrs,
+    }
+}
+
+/// Numbers the values computed across a whole function, which takes ```args```, by walking the dominator tree of its SSA form.
+///
+/// An instruction which computes a value already computed in a block dominating it is replaced with an `id` of the variable holding that value, and every argument is replaced with the first variable to hold its value, as long as those variables haven't been assigned again since. Arguments of commutative operations like `add`, `mul`, `and`, and `or` are put in order before comparing, operations on constants are folded into constants, and `phi` nodes which only take one value or take the same values as another `phi` in their block are redundant too. Only the analysis uses the SSA form, so the function keeps its variables and doesn't gain any copies for the `phi` nodes.
+///
+/// The replaced computations are left for [`crate::dce::eliminate`] to clean up.
+/// # Panics
+/// Will panic if a block of ```cfg``` jumps to a label which none of its blocks have, which can't happen to one built by [`Cfg::new`]
+pub fn number(cfg: &mut Cfg, args: &[Argument]) {
+    let mut ssa = Function {
+        args: args.to_vec(),
+        instrs: cfg.clone().into_code(),
+        name: String::new(),
+        pos: None,
+        return_type: None,
+        attrs: Attrs::new(),
+    };
+    function_to_ssa(&mut ssa).expect("a Cfg only jumps to labels it has");
+    // Every block already has a label and ends with a terminator, so the SSA form has the same blocks with phis at the start of some of them
+    let ssa = blocks(ssa.instrs);
+    let doms = ssa.dominators();
+    debug_assert_eq!(doms.verify(&ssa), Ok(()));
+    let names: HashMap<String, String> = args
+        .iter()
+        .map(|a| (a.name.clone(), a.name.clone()))
+        .collect();
+    Numbering {
+        cfg,
+        ssa,
+        doms,
+        original: names.clone(),
+        current: names.clone(),
+        members: names
+            .keys()
+            .map(|name| (name.clone(), vec![name.clone()]))
+            .collect(),
+        leaders: names,
+        consts: HashMap::new(),
+        values: HashMap::new(),
+    }
+    .visit(0);
+}
@@ -0,0 +1,287 @@
+use std::collections::{HashMap, HashSet};
+
+use bril_rs::callgraph::CallGraph;
+use bril_rs::{Attrs, Code, EffectOps, Function, Instruction, Position, Program, Type, ValueOps};
+
+use crate::{has_extra_dests, Options};
+
+/// Replaces calls with the body of the function being called.
+///
+/// A call is only inlined when the callee has at most [`Options::inline_threshold`] instructions, is not in [`Options::noinline`], is not part of a recursive cycle of calls, and neither it nor the caller use the experimental `tuples` extension. Functions are visited bottom-up through the call graph so that callees have already had their own calls inlined. The variables and labels of each inlined body are renamed so that they don't clash with the caller, its arguments are copied in with `id`, and each `ret` becomes a copy into the destination of the call followed by a jump to just after where the call was.
+pub fn inline(prog: &mut Program, options: &Options) {
+    let graph = CallGraph::new(prog);
+    let inlinable: Vec<bool> = (0..graph.functions.len())
+        .map(|f| {
+            !options.noinline.contains(&graph.functions[f])
+                && !graph.is_recursive(f)
+                && !has_extra_dests(&prog.functions[f])
+                && !matches!(prog.functions[f].return_type, Some(Type::Tuple(_)))
+        })
+        .collect();
+
+    for caller in graph.reverse_topological_order() {
+        let callees: HashMap<&str, usize> = graph.callees[caller]
+            .iter()
+            .filter(|&&f| inlinable[f] && size(&prog.functions[f]) <= options.inline_threshold)
+            .map(|&f| (graph.functions[f].as_str(), f))
+            .collect();
+        if callees.is_empty() || has_extra_dests(&prog.functions[caller]) {
+            continue;
+        }
+
+        let mut site = Site::new(&prog.functions[caller]);
+        let instrs = std::mem::take(&mut prog.functions[caller].instrs);
+        let mut code = Vec::with_capacity(instrs.len());
+        // The label which a `phi` would see as the last label when reaching each call
+        let mut last_label: Option<String> = None;
+        let mut renamed_labels = HashMap::new();
+        for c in instrs {
+            if let Code::Label { label, .. } = &c {
+                last_label = Some(label.clone());
+            }
+            match c {
+                Code::Instruction(call) if is_call_to(&call, &callees, &prog.functions) => {
+                    let callee = &prog.functions[callees[call_target(&call)]];
+                    let exit = site.inline(&mut code, &call, callee);
+                    if let Some(label) = last_label.replace(exit.clone()) {
+                        renamed_labels.insert(label, exit);
+                    }
+                }
+                c => code.push(c),
+            }
+        }
+
+        // Control reaches whatever came after a call from the end of the inlined body instead of from the block the call was in
+        for c in &mut code {
+            if let Code::Instruction(Instruction::Value {
+                op: ValueOps::Phi,
+                labels,
+                ..
+            }) = c
+            {
+                for l in labels {
+                    // A block with more than one call is renamed once for each of them
+                    while let Some(exit) = renamed_labels.get(l) {
+                        l.clone_from(exit);
+                    }
+                }
+            }
+        }
+
+        prog.functions[caller].instrs = code;
+    }
+}
+
+fn size(func: &Function) -> usize {
+    func.instrs
+        .iter()
+        .filter(|c| matches!(c, Code::Instruction(_)))
+        .count()
+}
+
+fn call_target(instr: &Instruction) -> &str {
+    match instr {
+        Instruction::Value { funcs, .. } | Instruction::Effect { funcs, .. } => &funcs[0],
+        Instruction::Constant { .. } => unreachable!(),
+    }
+}
+
+// Whether ```instr``` is a well-formed call to one of ```callees```
+fn is_call_to(instr: &Instruction, callees: &HashMap<&str, usize>, functions: &[Function]) -> bool {
+    match instr {
+        Instruction::Value {
+            op: ValueOps::Call,
+            args,
+            funcs,
+            ..
+        }
+        | Instruction::Effect {
+            op: EffectOps::Call,
+            args,
+            funcs,
+            ..
+        } => {
+            funcs.len() == 1
+                && callees
+                    .get(funcs[0].as_str())
+                    .is_some_and(|&f| functions[f].args.len() == args.len())
+        }
+        _ => false,
+    }
+}
+
+// The copies and jumps which stand in for passing arguments and returning are at the position of the call or `ret` they come from
+fn id(dest: String, op_type: Type, arg: String, pos: Option<Position>) -> Code {
+    Code::Instruction(Instruction::Value {
+        args: vec![arg],
+        dest,
+        funcs: Vec::new(),
+        labels: Vec::new(),
+        op: ValueOps::Id,
+        pos: pos.map(Position::derived),
+        op_type,
+        extra_dests: Vec::new(),
+        attrs: Attrs::new(),
+    })
+}
+
+fn jump(label: String, pos: Option<Position>) -> Code {
+    Code::Instruction(Instruction::Effect {
+        args: Vec::new(),
+        funcs: Vec::new(),
+        labels: vec![label],
+        op: EffectOps::Jump,
+        pos: pos.map(Position::derived),
+        attrs: Attrs::new(),
+    })
+}
+
+// Hands out names for inlined bodies which don't clash with anything in the caller
+struct Site {
+    used: HashSet<String>,
+    next: usize,
+}
+
+impl Site {
+    fn new(func: &Function) -> Self {
+        let mut used: HashSet<String> = func.args.iter().map(|a| a.name.clone()).collect();
+        for c in &func.instrs {
+            match c {
+                Code::Label { label, .. } => {
+                    used.insert(label.clone());
+                }
+                Code::Instruction(Instruction::Constant { dest, .. }) => {
+                    used.insert(dest.clone());
+                }
+                Code::Instruction(Instruction::Value { dest, args, .. }) => {
+                    used.insert(dest.clone());
+                    used.extend(args.iter().cloned());
+                }
+                Code::Instruction(Instruction::Effect { args, .. }) => {
+                    used.extend(args.iter().cloned());
+                }
+            }
+        }
+        Self { used, next: 0 }
+    }
+
+    // Picks a name for the exit label of an inlined ```callee``` which, along with every name of ```callee``` prefixed by it, doesn't clash with any existing name
+    fn exit(&mut self, callee: &Function) -> String {
+        let names = names(callee);
+        loop {
+            let exit = format!("{}.{}", callee.name, self.next);
+            self.next += 1;
+            let mut renamed = names.iter().map(|n| format!("{exit}.{n}"));
+            if !self.used.contains(&exit) && renamed.all(|n| !self.used.contains(&n)) {
+                self.used
+                    .extend(names.iter().map(|n| format!("{exit}.{n}")));
+                self.used.insert(exit.clone());
+                return exit;
+            }
+        }
+    }
+
+    // Appends the body of ```callee``` in place of ```call``` to ```code``` and returns the label placed after it
+    fn inline(&mut self, code: &mut Vec<Code>, call: &Instruction, callee: &Function) -> String {
+        let exit = self.exit(callee);
+        let rename = |name: &String| format!("{exit}.{name}");
+
+        let (call_args, dest) = match call {
+            Instruction::Value {
+                args,
+                dest,
+                op_type,
+                ..
+            } => (args, Some((dest, op_type))),
+            Instruction::Effect { args, .. } => (args, None),
+            Instruction::Constant { .. } => unreachable!(),
+        };
+        for (param, arg) in callee.args.iter().zip(call_args) {
+            code.push(id(
+                rename(&param.name),
+                param.arg_type.clone(),
+                arg.clone(),
+                call.get_pos(),
+            ));
+        }
+
+        for c in &callee.instrs {
+            match c {
+                Code::Label { label, pos } => code.push(Code::Label {
+                    label: rename(label),
+                    pos: *pos,
+                }),
+                Code::Instruction(Instruction::Effect {
+                    op: EffectOps::Return,
+                    args,
+                    pos,
+                    ..
+                }) => {
+                    if let (Some((dest, op_type)), Some(arg)) = (dest, args.first()) {
+                        code.push(id(dest.clone(), op_type.clone(), rename(arg), *pos));
+                    }
+                    code.push(jump(exit.clone(), *pos));
+                }
+                Code::Instruction(i) => {
+                    let mut i = i.clone();
+                    match &mut i {
+                        Instruction::Constant { dest, .. } => *dest = rename(dest),
+                        Instruction::Value {
+                            dest, args, labels, ..
+                        } => {
+                            *dest = rename(dest);
+                            for a in args.iter_mut() {
+                                *a = rename(a);
+                            }
+                            for l in labels.iter_mut() {
+                                *l = rename(l);
+                            }
+                        }
+                        Instruction::Effect { args, labels, .. } => {
+                            for a in args.iter_mut() {
+                                *a = rename(a);
+                            }
+                            for l in labels.iter_mut() {
+                                *l = rename(l);
+                            }
+                        }
+                    }
+                    code.push(Code::Instruction(i));
+                }
+            }
+        }
+
+        code.push(Code::Label {
+            label: exit.clone(),
+            pos: call.get_pos().map(Position::derived),
+        });
+        exit
+    }
+}
+
+// Every variable and label of ```func```
+fn names(func: &Function) -> HashSet<String> {
+    let mut names: HashSet<String> = func.args.iter().map(|a| a.name.clone()).collect();
+    for c in &func.instrs {
+        match c {
+            Code::Label { label, .. } => {
+                names.insert(label.clone());
+            }
+            Code::Instruction(Instruction::Constant { dest, .. }) => {
+                names.insert(dest.clone());
+            }
+            Code::Instruction(Instruction::Value {
+                dest, args, labels, ..
+            }) => {
+                names.insert(dest.clone());
+                names.extend(args.iter().cloned());
+                names.extend(labels.iter().cloned());
+            }
+            Code::Instruction(Instruction::Effect { args, labels, .. }) => {
+                names.extend(args.iter().cloned());
+                names.extend(labels.iter().cloned());
+            }
+        }
+    }
+    names
+}
@@ -0,0 +1,498 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+use bril_rs::cfg::{self, is_terminator, Cfg, CfgError};
+use bril_rs::{Code, EffectOps, Function, Instruction, Program, ValueOps};
+
+/// Provides ```absint::analyze```, an abstract interpreter which finds the possible values of integer variables at every point of a function
+pub mod absint;
+/// Provides ```alias::report_program```, which lists the memory each pointer variable of a program can point into
+pub mod alias;
+#[doc(hidden)]
+pub mod cli;
+/// Provides ```coalesce::coalesce```, which merges variables with the variables they copy when they never hold different values at the same time
+pub mod coalesce;
+/// Provides ```dce::eliminate```, which removes instructions whose results are never used
+pub mod dce;
+/// Provides ```dfe::eliminate```, which removes functions that can never be called
+pub mod dfe;
+/// Provides ```fold::fold```, which replaces instructions and branches that always compute the same constant
+pub mod fold;
+/// Provides ```gvn::number```, which removes redundant computations across a whole function using its SSA form
+pub mod gvn;
+/// Provides ```inline::inline```, which replaces calls to small functions with their bodies
+pub mod inline;
+/// Provides ```licm::hoist```, which moves code that computes the same value on every iteration of a loop out of it
+pub mod licm;
+/// Provides ```lvn::number```, which removes redundant computations within each basic block
+pub mod lvn;
+/// Provides ```mem::optimize```, which forwards stored values to the loads after them and removes stores that nothing reads
+pub mod mem;
+/// Provides ```peephole::Rules```, rewrite rules like `add x 0 => id x` which can be read from a file and are applied to each basic block until none of them match
+pub mod peephole;
+/// Provides ```pressure::report```, which estimates how many registers each block of a function needs
+pub mod pressure;
+/// Provides ```specialize::specialize```, which makes copies of functions for the constants they are called with and folds them
+pub mod specialize;
+/// Provides ```unroll::unroll```, which copies the bodies of loops that run a constant number of times
+pub mod unroll;
+
+/// The passes which can be run with [`optimize`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum Pass {
+    /// Dead code elimination with [`dce::eliminate`]
+    Dce,
+    /// Dead function elimination with [`dfe::eliminate`]
+    Dfe,
+    /// Local value numbering with [`lvn::number`]
+    Lvn,
+    /// Constant folding with [`fold::fold`]
+    Fold,
+    /// Function inlining with [`inline::inline`]
+    Inline,
+    /// Loop-invariant code motion with [`licm::hoist`]
+    Licm,
+    /// Global value numbering with [`gvn::number`]
+    Gvn,
+    /// Loop unrolling with [`unroll::unroll`]
+    Unroll,
+    /// Peephole rewriting with the rules of [`Options::peephole`]
+    Peephole,
+    /// Copy coalescing with [`coalesce::coalesce`]
+    Coalesce,
+    /// Partial evaluation with [`specialize::specialize`]
+    Specialize,
+    /// Store-to-load forwarding and dead store elimination with [`mem::optimize`]
+    Mem,
+}
+
+/// The settings of the passes for [`optimize_with_options`]
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// The most instructions a function can have and still be inlined
+    pub inline_threshold: usize,
+    /// The names of functions which are never inlined
+    pub noinline: HashSet<String>,
+    /// How many copies of its body a loop is unrolled to, with a check of its bound for each time through them
+    pub unroll_factor: usize,
+    /// The most times a loop can run and still be unrolled completely
+    pub full_unroll: usize,
+    /// The names of functions which are kept along with everything they call, like `main` is, since something outside of the program calls them
+    pub entry_points: HashSet<String>,
+    /// The rules of the peephole pass
+    pub peephole: peephole::Rules,
+    /// The functions which the specialize pass makes copies of for the values of some of their arguments, or changes in place for `main`
+    pub specializations: Vec<specialize::Specialization>,
+    /// The most copies the specialize pass makes for calls which pass constants, apart from those of [`Options::specializations`]
+    pub specialize_limit: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            inline_threshold: 20,
+            noinline: HashSet::new(),
+            unroll_factor: 4,
+            full_unroll: 8,
+            entry_points: HashSet::new(),
+            peephole: peephole::Rules::default(),
+            specializations: Vec::new(),
+            specialize_limit: 32,
+        }
+    }
+}
+
+impl Pass {
+    /// Every pass, in the order [`Registry::default`] lists them
+    pub const ALL: [Self; 12] = [
+        Self::Dce,
+        Self::Dfe,
+        Self::Lvn,
+        Self::Fold,
+        Self::Inline,
+        Self::Licm,
+        Self::Gvn,
+        Self::Unroll,
+        Self::Peephole,
+        Self::Coalesce,
+        Self::Specialize,
+        Self::Mem,
+    ];
+
+    /// The name the pass is given on the command line and registered under
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Dce => "dce",
+            Self::Dfe => "dfe",
+            Self::Lvn => "lvn",
+            Self::Fold => "fold",
+            Self::Inline => "inline",
+            Self::Licm => "licm",
+            Self::Gvn => "gvn",
+            Self::Unroll => "unroll",
+            Self::Peephole => "peephole",
+            Self::Coalesce => "coalesce",
+            Self::Specialize => "specialize",
+            Self::Mem => "mem",
+        }
+    }
+
+    /// Runs the pass over every function of ```prog```. Functions with a call which assigns more than one variable, from the experimental `tuples` extension, are left as they are since none of the passes know about the variables after the first
+    ///
+    /// # Errors
+    /// Will return [`FunctionError`] if a `jmp` or `br` targets a label which its function doesn't have, in which case ```prog``` is left as it was
+    ///
+    /// # Panics
+    /// Will panic if one of [`Options::specializations`] doesn't fit the program
+    pub fn run(self, prog: &mut Program, options: &Options) -> Result<(), FunctionError> {
+        check_labels(prog)?;
+        if self == Self::Dfe {
+            dfe::eliminate(prog, options);
+            return Ok(());
+        }
+        if self == Self::Specialize {
+            return specialize::specialize(prog, options);
+        }
+        if self == Self::Mem {
+            mem::optimize(prog)?;
+            return Ok(());
+        }
+        if self == Self::Inline {
+            inline::inline(prog, options);
+        }
+        for func in prog.functions.iter_mut().filter(|f| !has_extra_dests(f)) {
+            let mut cfg = blocks(std::mem::take(&mut func.instrs));
+            match self {
+                Self::Dce => dce::eliminate(&mut cfg),
+                Self::Lvn => lvn::number(&mut cfg),
+                Self::Fold => fold::fold(&mut cfg, &func.args),
+                Self::Licm => licm::hoist(&mut cfg),
+                Self::Gvn => gvn::number(&mut cfg, &func.args),
+                Self::Unroll => unroll::unroll(&mut cfg, &func.args, options),
+                Self::Peephole => {
+                    options.peephole.apply(&mut cfg);
+                }
+                Self::Coalesce => coalesce::coalesce(&mut cfg, &func.args),
+                Self::Inline | Self::Dfe | Self::Specialize | Self::Mem => {}
+            }
+            func.instrs = simplify(cfg.into_code());
+        }
+        Ok(())
+    }
+}
+
+/// The error for a function which can't be split into basic blocks, because a `jmp` or `br` in it targets a label which it doesn't have
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionError {
+    /// The name of the function
+    pub func: String,
+    /// What is wrong with its labels
+    pub error: CfgError,
+}
+
+impl std::fmt::Display for FunctionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "@{}: {}", self.func, self.error)
+    }
+}
+
+impl std::error::Error for FunctionError {}
+
+// Finds the first `jmp` or `br` of ```prog``` which targets a label its function doesn't have, so that passes can report it before they change anything
+pub(crate) fn check_labels(prog: &Program) -> Result<(), FunctionError> {
+    prog.functions.iter().try_for_each(|func| {
+        cfg::check_labels(&func.instrs).map_err(|error| FunctionError {
+            func: func.name.clone(),
+            error,
+        })
+    })
+}
+
+// The blocks of ```func```, for analyses which are given functions that haven't been through [`check_labels`]
+pub(crate) fn function_blocks(func: &Function) -> Result<Cfg, FunctionError> {
+    Cfg::from_function(func).map_err(|error| FunctionError {
+        func: func.name.clone(),
+        error,
+    })
+}
+
+// The blocks of ```instrs```, which come from a function that has been through [`check_labels`] or from a pass that only jumps to labels it keeps
+pub(crate) fn blocks(instrs: Vec<Code>) -> Cfg {
+    Cfg::new(instrs).expect("every jump goes to a label of its function")
+}
+
+// Whether ```func``` has a call which assigns more than one variable
+pub(crate) fn has_extra_dests(func: &Function) -> bool {
+    func.instrs.iter().any(|c| {
+        matches!(c, Code::Instruction(Instruction::Value { extra_dests, .. }) if !extra_dests.is_empty())
+    })
+}
+
+/// Runs each of ```passes``` in order over ```prog``` with the default [`Options`]
+///
+/// # Errors
+/// Will return [`FunctionError`] if a `jmp` or `br` targets a label which its function doesn't have
+pub fn optimize(prog: &mut Program, passes: &[Pass]) -> Result<(), FunctionError> {
+    optimize_with_options(prog, passes, &Options::default())
+}
+
+/// The same as [`optimize`] but configured with ```options```
+///
+/// # Errors
+/// Will return [`FunctionError`] if a `jmp` or `br` targets a label which its function doesn't have
+pub fn optimize_with_options(
+    prog: &mut Program,
+    passes: &[Pass],
+    options: &Options,
+) -> Result<(), FunctionError> {
+    passes.iter().try_for_each(|pass| pass.run(prog, options))
+}
+
+/// A pass which can be added to a [`Registry`], which transforms a whole program with the given [`Options`]
+pub type PassFn = dyn Fn(&mut Program, &Options) -> Result<(), FunctionError> + Send + Sync;
+
+/// The passes which tools like `brilirs --pass` can run by name.
+///
+/// [`Registry::default`] has every [`Pass`] under its [`Pass::name`], and embedders can add passes of their own with [`Registry::add`], which replaces any pass already registered under the same name.
+/// ```
+/// use bril_opt::{Options, Registry};
+///
+/// let mut registry = Registry::default();
+/// registry.add("strip", |prog, _| {
+///     prog.functions.retain(|f| f.name == "main");
+///     Ok(())
+/// });
+/// assert!(registry.names().any(|name| name == "strip"));
+///
+/// let json = r#"{"functions": [{"name": "main", "instrs": []}, {"name": "unused", "instrs": []}]}"#;
+/// let mut prog: bril_rs::Program = serde_json::from_str(json).unwrap();
+/// registry.run(&mut prog, "strip", &Options::default()).unwrap();
+/// assert_eq!(prog.functions.len(), 1);
+/// ```
+#[derive(Clone)]
+pub struct Registry {
+    passes: BTreeMap<String, Arc<PassFn>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        for pass in Pass::ALL {
+            registry.add(pass.name(), move |prog, options| pass.run(prog, options));
+        }
+        registry
+    }
+}
+
+impl Registry {
+    /// A registry without any passes
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self {
+            passes: BTreeMap::new(),
+        }
+    }
+
+    /// Registers ```pass``` under ```name```
+    pub fn add(
+        &mut self,
+        name: impl Into<String>,
+        pass: impl Fn(&mut Program, &Options) -> Result<(), FunctionError> + Send + Sync + 'static,
+    ) {
+        self.passes.insert(name.into(), Arc::new(pass));
+    }
+
+    /// The names of the registered passes, in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.passes.keys().map(String::as_str)
+    }
+
+    /// The pass registered under ```name```, if there is one
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&PassFn> {
+        self.passes.get(name).map(AsRef::as_ref)
+    }
+
+    /// Runs the pass registered under ```name``` over ```prog```
+    ///
+    /// # Errors
+    /// Will return [`RunError::UnknownPass`] if no pass is registered under ```name```, or [`RunError::Function`] if the pass fails
+    pub fn run(&self, prog: &mut Program, name: &str, options: &Options) -> Result<(), RunError> {
+        let pass = self.get(name).ok_or_else(|| UnknownPass {
+            name: name.to_string(),
+            known: self.names().map(str::to_string).collect(),
+        })?;
+        Ok(pass(prog, options)?)
+    }
+}
+
+impl std::fmt::Debug for Registry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+/// The error [`Registry::run`] gives for a name which no pass is registered under
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPass {
+    /// The name which was asked for
+    pub name: String,
+    /// The names of the passes which are registered
+    pub known: Vec<String>,
+}
+
+impl std::fmt::Display for UnknownPass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown pass `{}`, expected one of: {}",
+            self.name,
+            self.known.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownPass {}
+
+/// The errors of [`Registry::run`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunError {
+    /// No pass is registered under the name
+    UnknownPass(UnknownPass),
+    /// The pass couldn't handle one of the functions
+    Function(FunctionError),
+}
+
+impl From<UnknownPass> for RunError {
+    fn from(e: UnknownPass) -> Self {
+        Self::UnknownPass(e)
+    }
+}
+
+impl From<FunctionError> for RunError {
+    fn from(e: FunctionError) -> Self {
+        Self::Function(e)
+    }
+}
+
+impl std::fmt::Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownPass(e) => e.fmt(f),
+            Self::Function(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+// Whether ```instr``` only computes its result, so that it can be removed or reused without changing what the program does
+pub(crate) const fn is_pure(instr: &Instruction) -> bool {
+    match instr {
+        Instruction::Constant { .. } => true,
+        // Division can fail at runtime, the heap can change between loads, and every `rand` moves on to the next number
+        Instruction::Value { op, .. } => !matches!(
+            op,
+            ValueOps::Call
+                | ValueOps::Div
+                | ValueOps::Rem
+                | ValueOps::Alloc
+                | ValueOps::Load
+                | ValueOps::Rand
+        ),
+        Instruction::Effect { .. } => false,
+    }
+}
+
+// Undoes the labels and jumps a [`Cfg`] adds to every block so that the passes don't add instructions to the program, along with any code the passes made unreachable
+pub(crate) fn simplify(mut code: Vec<Code>) -> Vec<Code> {
+    loop {
+        let len = code.len();
+        code = remove_redundant_jumps(code);
+        remove_unused_labels(&mut code);
+        remove_unreachable(&mut code);
+        if code.len() == len {
+            break;
+        }
+    }
+
+    // Falling off the end of a function returns from it
+    if let Some(Code::Instruction(Instruction::Effect {
+        op: EffectOps::Return,
+        args,
+        ..
+    })) = code.last()
+    {
+        if args.is_empty() {
+            code.pop();
+        }
+    }
+    code
+}
+
+// A jump to the label right after it is the same as falling through
+fn remove_redundant_jumps(code: Vec<Code>) -> Vec<Code> {
+    let redundant: HashSet<usize> = code
+        .iter()
+        .enumerate()
+        .filter(|(i, c)| match c {
+            Code::Instruction(Instruction::Effect {
+                op: EffectOps::Jump,
+                labels,
+                ..
+            }) => code[i + 1..]
+                .iter()
+                .map_while(|next| match next {
+                    Code::Label { label, .. } => Some(label),
+                    Code::Instruction(_) => None,
+                })
+                .any(|label| labels.contains(label)),
+            _ => false,
+        })
+        .map(|(i, _)| i)
+        .collect();
+    code.into_iter()
+        .enumerate()
+        .filter(|(i, _)| !redundant.contains(i))
+        .map(|(_, c)| c)
+        .collect()
+}
+
+fn remove_unused_labels(code: &mut Vec<Code>) {
+    let used: HashSet<String> = code
+        .iter()
+        .flat_map(|c| match c {
+            Code::Instruction(
+                Instruction::Value { labels, .. } | Instruction::Effect { labels, .. },
+            ) => labels.clone(),
+            _ => Vec::new(),
+        })
+        .collect();
+    code.retain(|c| match c {
+        Code::Label { label, .. } => used.contains(label),
+        Code::Instruction(_) => true,
+    });
+}
+
+// Instructions between a terminator and the next label can never run
+fn remove_unreachable(code: &mut Vec<Code>) {
+    let mut reachable = true;
+    code.retain(|c| match c {
+        Code::Label { .. } => {
+            reachable = true;
+            true
+        }
+        Code::Instruction(i) => {
+            let keep = reachable;
+            reachable &= !is_terminator(i);
+            keep
+        }
+    });
+}
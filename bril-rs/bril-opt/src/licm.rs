@@ -0,0 +1,103 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use bril_rs::cfg::Cfg;
+use bril_rs::dataflow::{def, solve, uses, DataflowResult, LiveVariables};
+use bril_rs::dominance::Dominators;
+use bril_rs::loops::{insert_preheader, natural_loops, NaturalLoop};
+use bril_rs::{Instruction, ValueOps};
+
+use crate::is_pure;
+
+/// Moves instructions which compute the same value on every iteration of a loop into a preheader before the loop, so that they only run once.
+///
+/// Inner loops are handled first, so an instruction can end up moving out of several loops. An instruction moves when every variable it reads is either never assigned in the loop or only assigned by another instruction which moves, it is the only assignment to its destination in the loop, and the loop never reads the value its destination had before the loop. If its destination is read after the loop, it also has to run on every way out of the loop. Instructions which can fail at runtime or depend on the heap never move, since the loop might not have run them at all.
+pub fn hoist(cfg: &mut Cfg) {
+    // Adding a preheader moves the blocks around, so the loops are found again every time one changes
+    while hoist_loop(cfg) {}
+}
+
+// Moves the invariant code out of the first loop which has any, returning whether there was such a loop
+fn hoist_loop(cfg: &mut Cfg) -> bool {
+    let doms = cfg.dominators();
+    // The tests run debug builds, which check the dominators against their definition
+    debug_assert_eq!(doms.verify(cfg), Ok(()));
+    let live = solve(&LiveVariables, cfg);
+    for natural_loop in natural_loops(cfg, &doms) {
+        let invariant = invariant_instrs(cfg, &doms, &live, &natural_loop);
+        if invariant.is_empty() {
+            continue;
+        }
+        let preheader = insert_preheader(cfg, &natural_loop);
+        // The header and every block after it moved along by one
+        let invariant: Vec<(usize, usize)> = invariant
+            .into_iter()
+            .map(|(b, i)| (if b >= preheader { b + 1 } else { b }, i))
+            .collect();
+        let moved: Vec<Instruction> = invariant
+            .iter()
+            .map(|&(b, i)| cfg.blocks[b].instrs[i].clone())
+            .collect();
+        for (b, block) in cfg.blocks.iter_mut().enumerate() {
+            let mut idx = 0;
+            block.instrs.retain(|_| {
+                idx += 1;
+                !invariant.contains(&(b, idx - 1))
+            });
+        }
+        cfg.blocks[preheader].instrs.splice(0..0, moved);
+        return true;
+    }
+    false
+}
+
+// The instructions of ```natural_loop``` which can move into its preheader as (block, index) pairs, in an order where every instruction comes after the instructions computing its arguments
+fn invariant_instrs(
+    cfg: &Cfg,
+    doms: &Dominators,
+    live: &DataflowResult<BTreeSet<String>>,
+    natural_loop: &NaturalLoop,
+) -> Vec<(usize, usize)> {
+    // How many times each variable is assigned in the loop
+    let mut defs: HashMap<&String, usize> = HashMap::new();
+    for &b in &natural_loop.blocks {
+        for dest in cfg.blocks[b].instrs.iter().filter_map(def) {
+            *defs.entry(dest).or_default() += 1;
+        }
+    }
+    let exits = natural_loop.exits(cfg);
+
+    let mut invariant = Vec::new();
+    let mut moved: HashSet<&String> = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &b in &natural_loop.blocks {
+            for (idx, instr) in cfg.blocks[b].instrs.iter().enumerate() {
+                let Some(dest) = def(instr) else { continue };
+                let movable = !moved.contains(dest)
+                    && is_pure(instr)
+                    && !matches!(
+                        instr,
+                        Instruction::Value {
+                            op: ValueOps::Phi,
+                            ..
+                        }
+                    )
+                    && defs[dest] == 1
+                    && !live.inputs[natural_loop.header].contains(dest)
+                    && uses(instr)
+                        .iter()
+                        .all(|arg| !defs.contains_key(arg) || moved.contains(arg))
+                    && exits.iter().all(|&(from, to)| {
+                        !live.inputs[to].contains(dest) || doms.dominates(b, from)
+                    });
+                if movable {
+                    moved.insert(dest);
+                    invariant.push((b, idx));
+                    changed = true;
+                }
+            }
+        }
+    }
+    invariant
+}
@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, HashMap};
+
+use bril_rs::cfg::Cfg;
+use bril_rs::dataflow::{constant_literal, def};
+use bril_rs::{Instruction, ValueOps};
+
+use crate::is_pure;
+
+// Operations whose arguments can be swapped without changing the result
+pub(crate) const fn is_commutative(op: ValueOps) -> bool {
+    matches!(
+        op,
+        ValueOps::Add
+            | ValueOps::Mul
+            | ValueOps::Eq
+            | ValueOps::And
+            | ValueOps::Or
+            | ValueOps::Fadd
+            | ValueOps::Fmul
+            | ValueOps::Feq
+            | ValueOps::Ceq
+    )
+}
+
+#[derive(Default)]
+struct Table {
+    // The number of the value each variable holds at this point in the block
+    var2num: BTreeMap<String, usize>,
+    // A variable which still holds each value, if there is one
+    holders: Vec<Option<String>>,
+    // The number of each value which has been computed, keyed by the operation and the numbers of its arguments
+    values: HashMap<String, usize>,
+}
+
+impl Table {
+    fn fresh(&mut self) -> usize {
+        self.holders.push(None);
+        self.holders.len() - 1
+    }
+
+    fn number_of(&mut self, var: &str) -> usize {
+        // Variables which are read before being written in the block hold a value of their own
+        if let Some(num) = self.var2num.get(var) {
+            return *num;
+        }
+        let num = self.fresh();
+        self.assign(var, num);
+        num
+    }
+
+    fn holder(&mut self, var: &str) -> String {
+        let num = self.number_of(var);
+        self.holders[num].clone().unwrap_or_else(|| var.to_string())
+    }
+
+    fn assign(&mut self, var: &str, num: usize) {
+        // ```var``` is about to be overwritten, so find somewhere else to keep its old value
+        if let Some(old) = self.var2num.remove(var) {
+            if self.holders[old].as_deref() == Some(var) {
+                self.holders[old] = self
+                    .var2num
+                    .iter()
+                    .find(|(_, n)| **n == old)
+                    .map(|(v, _)| v.clone());
+            }
+        }
+        self.var2num.insert(var.to_string(), num);
+        self.holders[num].get_or_insert_with(|| var.to_string());
+    }
+}
+
+/// Numbers the values computed in each basic block.
+///
+/// An instruction which computes a value already held by another variable is replaced with an `id` of that variable, and every argument is replaced with the first variable still holding its value, which propagates copies along.
+///
+/// The replaced computations are left for [`crate::dce::eliminate`] to clean up.
+pub fn number(cfg: &mut Cfg) {
+    for block in &mut cfg.blocks {
+        let mut table = Table::default();
+        for instr in &mut block.instrs {
+            // The arguments of a phi are read from the end of the previous block, not from this one
+            if matches!(
+                instr,
+                Instruction::Value {
+                    op: ValueOps::Phi,
+                    ..
+                }
+            ) {
+                if let Some(dest) = def(instr).cloned() {
+                    let num = table.fresh();
+                    table.assign(&dest, num);
+                }
+                continue;
+            }
+
+            if let Instruction::Value { args, .. } | Instruction::Effect { args, .. } = instr {
+                for arg in args {
+                    *arg = table.holder(arg);
+                }
+            }
+
+            let pure = is_pure(instr);
+            let (dest, num) = match instr {
+                Instruction::Constant {
+                    dest,
+                    const_type,
+                    value,
+                    ..
+                } => {
+                    let key = format!("const {const_type} {}", constant_literal(const_type, value));
+                    let num = if let Some(num) = table.values.get(&key) {
+                        *num
+                    } else {
+                        let num = table.fresh();
+                        table.values.insert(key, num);
+                        num
+                    };
+                    (dest.clone(), num)
+                }
+                Instruction::Value {
+                    op: ValueOps::Id,
+                    dest,
+                    args,
+                    ..
+                } => {
+                    let num = table.number_of(&args[0]);
+                    (dest.clone(), num)
+                }
+                Instruction::Value {
+                    op,
+                    dest,
+                    args,
+                    funcs,
+                    labels,
+                    op_type,
+                    ..
+                } if pure => {
+                    let mut nums: Vec<usize> = args.iter().map(|a| table.number_of(a)).collect();
+                    if is_commutative(*op) {
+                        nums.sort_unstable();
+                    }
+                    let key = format!("{op} {op_type} {nums:?} {funcs:?} {labels:?}");
+                    let existing = table
+                        .values
+                        .get(&key)
+                        .and_then(|num| Some((*num, table.holders[*num].clone()?)));
+                    if let Some((num, holder)) = existing {
+                        // Recomputing a value into the variable which already holds it is left alone
+                        if holder != *dest {
+                            *op = ValueOps::Id;
+                            *args = vec![holder];
+                            funcs.clear();
+                            labels.clear();
+                        }
+                        (dest.clone(), num)
+                    } else {
+                        let num = table.fresh();
+                        table.values.insert(key, num);
+                        (dest.clone(), num)
+                    }
+                }
+                Instruction::Value { dest, .. } => (dest.clone(), table.fresh()),
+                Instruction::Effect { .. } => continue,
+            };
+            table.assign(&dest, num);
+        }
+    }
+}
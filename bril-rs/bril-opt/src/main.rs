@@ -0,0 +1,91 @@
+use bril_opt::cli::Cli;
+use bril_opt::peephole::Rules;
+use bril_opt::specialize::Specialization;
+use bril_opt::{alias, dfe, mem, pressure, Options, Pass};
+use bril_rs::cfg::Cfg;
+use bril_rs::structured::StructuredCfg;
+use bril_rs::{load_program, output_program};
+use clap::Parser;
+
+fn main() {
+    let args = Cli::parse();
+    let peephole = args.rules.as_ref().map_or_else(
+        || Ok(Rules::default()),
+        |path| {
+            std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| text.parse::<Rules>().map_err(|e| e.to_string()))
+                .map_err(|e| format!("{}: {e}", path.display()))
+        },
+    );
+    let peephole = peephole.unwrap_or_else(|e| fail(e));
+    let mut prog = load_program();
+    let mut specializations = args.specialize;
+    if !args.args.is_empty() {
+        specializations.push(Specialization::main(&args.args));
+    }
+    for spec in &specializations {
+        if let Err(e) = spec.values(&prog) {
+            fail(e);
+        }
+    }
+    let options = Options {
+        inline_threshold: args.inline_threshold,
+        noinline: args.noinline.into_iter().collect(),
+        unroll_factor: args.unroll_factor,
+        full_unroll: args.full_unroll,
+        entry_points: args.entry_point.into_iter().collect(),
+        peephole,
+        specializations,
+        specialize_limit: args.specialize_limit,
+    };
+    for pass in args.passes {
+        // The functions dfe removes are reported so that it is clear why they are gone
+        if pass == Pass::Dfe {
+            for name in dfe::eliminate(&mut prog, &options) {
+                eprintln!("removed @{name}, which is never called");
+            }
+        } else if pass == Pass::Mem {
+            let removed = mem::optimize(&mut prog).unwrap_or_else(|e| fail(e));
+            eprintln!(
+                "removed {} loads and {} stores",
+                removed.loads, removed.stores
+            );
+        } else {
+            pass.run(&mut prog, &options).unwrap_or_else(|e| fail(e));
+        }
+    }
+    if let Some(analysis) = args.absint {
+        analysis
+            .run(&prog, std::io::stdout())
+            .unwrap_or_else(|e| fail(e));
+        println!();
+    } else if args.pressure {
+        let report = pressure::report_program(&prog).unwrap_or_else(|e| fail(e));
+        serde_json::to_writer_pretty(std::io::stdout(), &report).unwrap();
+        println!();
+    } else if args.alias {
+        serde_json::to_writer_pretty(std::io::stdout(), &alias::report_program(&prog)).unwrap();
+        println!();
+    } else if args.structure {
+        for func in &prog.functions {
+            let structured = Cfg::from_function(func)
+                .map_err(|e| e.to_string())
+                .and_then(|cfg| StructuredCfg::new(&cfg).map_err(|e| e.to_string()))
+                .unwrap_or_else(|e| fail(format!("@{}: {e}", func.name)));
+            println!("@{} {{", func.name);
+            for line in structured.to_string().lines() {
+                println!("  {line}");
+            }
+            println!("}}");
+        }
+    } else {
+        output_program(&prog);
+    }
+}
+
+// Reports a problem with the input which leaves nothing to output
+fn fail(e: impl std::fmt::Display) -> ! {
+    eprintln!("error: {e}");
+    std::process::exit(1)
+}
@@ -0,0 +1,198 @@
+use std::collections::BTreeSet;
+
+use bril_rs::alias::{Location, PointsTo};
+use bril_rs::cfg::{BasicBlock, Cfg};
+use bril_rs::{Code, EffectOps, Instruction, Program, ValueOps};
+
+use crate::{blocks, check_labels, has_extra_dests, simplify, FunctionError};
+
+/// How many memory operations [`optimize`] removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Removed {
+    /// The loads which became an `id` of the value already known to be in memory
+    pub loads: usize,
+    /// The stores which were removed because nothing could read what they stored
+    pub stores: usize,
+}
+
+/// Forwards stores to the loads after them and removes dead stores in every function of ```prog```, using [`PointsTo`] to tell which pointers can touch the same memory.
+///
+/// Within each basic block, a `load` of a pointer which was just stored to or loaded from, with nothing in between which could have changed that memory, becomes an `id` of the value already known to be there. A `store` is removed when the same pointer is stored to again or freed further on in its block with nothing in between which could read it, or when no `load` anywhere in the program can read the memory it stores to. Calls can load and store anything their arguments reach, and other threads can change memory between a `spawn` and its `join`, so nothing is carried past any of them.
+///
+/// The loads which became an `id` are left for [`crate::dce::eliminate`] to clean up when nothing uses them.
+///
+/// # Errors
+/// Will return [`FunctionError`] if a `jmp` or `br` targets a label which its function doesn't have, in which case ```prog``` is left as it was
+pub fn optimize(prog: &mut Program) -> Result<Removed, FunctionError> {
+    check_labels(prog)?;
+    let points_to = PointsTo::new(prog);
+    let mut removed = Removed::default();
+    let mut cfgs: Vec<Option<Cfg>> = prog
+        .functions
+        .iter_mut()
+        .map(|func| (!has_extra_dests(func)).then(|| blocks(std::mem::take(&mut func.instrs))))
+        .collect();
+    for (func, cfg) in cfgs.iter_mut().enumerate() {
+        for block in cfg.iter_mut().flat_map(|cfg| &mut cfg.blocks) {
+            removed.loads += forward(block, &points_to, func);
+        }
+    }
+
+    // Stores to memory which nothing loads from can go no matter where they are, so the loads left after forwarding are found first
+    let mut read = BTreeSet::new();
+    for (func, cfg) in cfgs.iter().enumerate() {
+        let instrs: Vec<&Instruction> = match cfg {
+            Some(cfg) => cfg.blocks.iter().flat_map(|b| &b.instrs).collect(),
+            None => prog.functions[func]
+                .instrs
+                .iter()
+                .filter_map(|code| match code {
+                    Code::Instruction(instr) => Some(instr),
+                    Code::Label { .. } => None,
+                })
+                .collect(),
+        };
+        for instr in instrs {
+            if matches!(
+                instr,
+                Instruction::Value {
+                    op: ValueOps::Load,
+                    ..
+                }
+            ) {
+                read.extend(points_to.instruction(func, instr));
+            }
+        }
+    }
+
+    for (func, cfg) in cfgs.into_iter().enumerate() {
+        let Some(mut cfg) = cfg else {
+            continue;
+        };
+        for block in &mut cfg.blocks {
+            removed.stores += remove_dead_stores(block, &points_to, func, &read);
+        }
+        prog.functions[func].instrs = simplify(cfg.into_code());
+    }
+    Ok(removed)
+}
+
+// Whether ```instr``` can touch memory in ways the pass can't see, which is any call along with the points where other threads start and finish
+const fn is_barrier(instr: &Instruction) -> bool {
+    matches!(
+        instr,
+        Instruction::Value {
+            op: ValueOps::Call | ValueOps::Spawn,
+            ..
+        } | Instruction::Effect {
+            op: EffectOps::Call | EffectOps::Join,
+            ..
+        }
+    )
+}
+
+fn dest(instr: &Instruction) -> Option<&str> {
+    match instr {
+        Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => Some(dest),
+        Instruction::Effect { .. } => None,
+    }
+}
+
+// Replaces each load in ```block``` whose value is already held by a variable with an `id` of it, returning how many were
+fn forward(block: &mut BasicBlock, points_to: &PointsTo, func: usize) -> usize {
+    // Pointers along with a variable holding what is in memory where each of them points
+    let mut known: Vec<(String, String)> = Vec::new();
+    let mut forwarded = 0;
+    for instr in &mut block.instrs {
+        let mut remembered = None;
+        match instr {
+            Instruction::Value {
+                op: op @ ValueOps::Load,
+                args,
+                dest,
+                ..
+            } => {
+                if let Some((_, value)) = known.iter().find(|(ptr, _)| *ptr == args[0]) {
+                    *args = vec![value.clone()];
+                    *op = ValueOps::Id;
+                    forwarded += 1;
+                } else if *dest != args[0] {
+                    remembered = Some((args[0].clone(), dest.clone()));
+                }
+            }
+            Instruction::Effect {
+                op: EffectOps::Store,
+                args,
+                ..
+            } => {
+                known.retain(|(ptr, _)| {
+                    *ptr != args[0] && !points_to.may_alias(func, ptr, &args[0])
+                });
+                known.push((args[0].clone(), args[1].clone()));
+            }
+            Instruction::Effect {
+                op: EffectOps::Free,
+                args,
+                ..
+            } => known
+                .retain(|(ptr, _)| *ptr != args[0] && !points_to.may_alias(func, ptr, &args[0])),
+            _ if is_barrier(instr) => known.clear(),
+            _ => {}
+        }
+        if let Some(dest) = dest(instr) {
+            known.retain(|(ptr, value)| ptr != dest && value != dest);
+        }
+        known.extend(remembered);
+    }
+    forwarded
+}
+
+// Removes the stores in ```block``` which are overwritten or freed before anything could read them, or to memory which is never in ```read```, returning how many there were
+fn remove_dead_stores(
+    block: &mut BasicBlock,
+    points_to: &PointsTo,
+    func: usize,
+    read: &BTreeSet<Location>,
+) -> usize {
+    // The pointers which are stored to again or freed further on in the block before anything could read from them
+    let mut overwritten: Vec<&str> = Vec::new();
+    let mut dead = vec![false; block.instrs.len()];
+    for (i, instr) in block.instrs.iter().enumerate().rev() {
+        if let Some(dest) = dest(instr) {
+            overwritten.retain(|ptr| *ptr != dest);
+        }
+        match instr {
+            Instruction::Effect {
+                op: EffectOps::Store,
+                args,
+                ..
+            } => {
+                let locations = points_to.points_to(func, &args[0]);
+                if overwritten.contains(&args[0].as_str())
+                    || (!locations.is_empty() && locations.is_disjoint(read))
+                {
+                    dead[i] = true;
+                } else {
+                    overwritten.push(&args[0]);
+                }
+            }
+            Instruction::Effect {
+                op: EffectOps::Free,
+                args,
+                ..
+            } => overwritten.push(&args[0]),
+            Instruction::Value {
+                op: ValueOps::Load,
+                args,
+                ..
+            } => overwritten
+                .retain(|ptr| *ptr != args[0] && !points_to.may_alias(func, ptr, &args[0])),
+            _ if is_barrier(instr) => overwritten.clear(),
+            _ => {}
+        }
+    }
+    let before = block.instrs.len();
+    let mut dead = dead.into_iter();
+    block.instrs.retain(|_| !dead.next().unwrap_or_default());
+    before - block.instrs.len()
+}
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use bril_rs::cfg::Cfg;
+use bril_rs::{ConstOps, EffectOps, Instruction, Literal, ValueOps};
+
+/// The text of the rules [`Rules::default`] has, which explains how rules are written
+pub const DEFAULT_RULES: &str = include_str!("../peephole.rules");
+
+// Rules which undo each other, like `add x y => add y x`, stop being applied to a block after this many rounds instead of going on forever
+const MAX_ROUNDS: usize = 100;
+
+/// Rewrite rules which replace single instructions with simpler ones, like `add x 0 => id x`.
+///
+/// Rules are written one to a line, with `#` starting a comment, as a pattern and a replacement separated by `=>`. Each is an operation followed by its operands: variables, labels written `.name`, and functions written `@name`. In a pattern, a name matches anything, the same name twice has to match the same thing both times, and a literal like `0`, `1.5`, or `true` matches a variable set to that constant earlier in the same block. A replacement keeps the destination and type of the instruction it replaces, and its operands are names from its pattern, or a single literal for a `const`. [`DEFAULT_RULES`] has some examples.
+/// ```
+/// use bril_opt::peephole::Rules;
+///
+/// let rules: Rules = "mul x 2 => add x x".parse().unwrap();
+/// let json = r#"{"functions": [{"name": "main", "args": [{"name": "a", "type": "int"}], "instrs": [
+///     {"dest": "two", "op": "const", "type": "int", "value": 2},
+///     {"dest": "b", "op": "mul", "type": "int", "args": ["a", "two"]},
+///     {"op": "print", "args": ["b"]}
+/// ]}]}"#;
+/// let prog: bril_rs::Program = serde_json::from_str(json).unwrap();
+/// let mut cfg = bril_rs::cfg::Cfg::from_function(&prog.functions[0]).unwrap();
+/// assert_eq!(rules.apply(&mut cfg), 1);
+/// assert_eq!(cfg.blocks[0].instrs[1].to_string(), "b: int = add a a;");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        DEFAULT_RULES
+            .parse()
+            .expect("the default peephole rules are valid")
+    }
+}
+
+impl Rules {
+    /// The number of rules
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Whether there are no rules, which rewrite nothing
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Rewrites the instructions of every block of ```cfg``` which match a rule until none of them do, returning how many rewrites were made. Each instruction is replaced by the first rule it matches, in the order the rules were written
+    pub fn apply(&self, cfg: &mut Cfg) -> usize {
+        let mut rewrites = 0;
+        for block in &mut cfg.blocks {
+            for _ in 0..MAX_ROUNDS {
+                let before = rewrites;
+                let mut consts = HashMap::new();
+                for instr in &mut block.instrs {
+                    if let Some(rewritten) =
+                        self.rules.iter().find_map(|r| r.rewrite(instr, &consts))
+                    {
+                        *instr = rewritten;
+                        rewrites += 1;
+                    }
+                    record_constant(&mut consts, instr);
+                }
+                if rewrites == before {
+                    break;
+                }
+            }
+        }
+        rewrites
+    }
+}
+
+impl FromStr for Rules {
+    type Err = RuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rules = s
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                let line = line.split('#').next().unwrap_or_default().trim();
+                (!line.is_empty()).then(|| {
+                    Rule::parse(line).map_err(|message| RuleError {
+                        line: i + 1,
+                        message,
+                    })
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+}
+
+/// What is wrong with a rule which [`Rules`] couldn't read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleError {
+    /// The line the rule is on, starting from 1
+    pub line: usize,
+    /// A description of the problem
+    pub message: String,
+}
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+// Keeps track of which variables of the block hold a constant after ```instr```
+fn record_constant(consts: &mut HashMap<String, Literal>, instr: &Instruction) {
+    match instr {
+        Instruction::Constant { dest, value, .. } => {
+            consts.insert(dest.clone(), value.clone());
+        }
+        Instruction::Value {
+            dest,
+            op: ValueOps::Id,
+            args,
+            ..
+        } if consts.contains_key(&args[0]) => {
+            consts.insert(dest.clone(), consts[&args[0]].clone());
+        }
+        Instruction::Value { dest, .. } => {
+            consts.remove(dest);
+        }
+        Instruction::Effect { .. } => {}
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    pattern: Shape,
+    replacement: Shape,
+}
+
+// One side of a rule, where the operation is looked up as both kinds of operation since `call` is both
+#[derive(Debug, Clone, PartialEq)]
+struct Shape {
+    name: String,
+    value: Option<ValueOps>,
+    effect: Option<EffectOps>,
+    args: Vec<Operand>,
+    funcs: Vec<String>,
+    labels: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Name(String),
+    Literal(Literal),
+}
+
+impl Shape {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut words = text.split_whitespace();
+        let name = words
+            .next()
+            .ok_or("expected an operation on both sides of `=>`")?;
+        let op = serde_json::Value::String(name.to_string());
+        let mut shape = Self {
+            name: name.to_string(),
+            value: serde_json::from_value(op.clone()).ok(),
+            effect: serde_json::from_value(op).ok(),
+            args: Vec::new(),
+            funcs: Vec::new(),
+            labels: Vec::new(),
+        };
+        if shape.value.is_none() && shape.effect.is_none() && !shape.is_const() {
+            return Err(format!("unknown operation `{name}`"));
+        }
+        for word in words {
+            if let Some(label) = word.strip_prefix('.') {
+                shape.labels.push(label.to_string());
+            } else if let Some(func) = word.strip_prefix('@') {
+                shape.funcs.push(func.to_string());
+            } else {
+                shape.args.push(
+                    literal(word).map_or_else(|| Operand::Name(word.to_string()), Operand::Literal),
+                );
+            }
+        }
+        Ok(shape)
+    }
+
+    fn is_const(&self) -> bool {
+        self.name == "const"
+    }
+
+    // The names among the arguments, leaving out any literals
+    fn arg_names(&self) -> Vec<String> {
+        self.args
+            .iter()
+            .filter_map(|arg| match arg {
+                Operand::Name(name) => Some(name.clone()),
+                Operand::Literal(_) => None,
+            })
+            .collect()
+    }
+}
+
+// The literal written as ```word```, if it is one. Anything else starting with a letter is a name
+fn literal(word: &str) -> Option<Literal> {
+    match word {
+        "true" => Some(Literal::Bool(true)),
+        "false" => Some(Literal::Bool(false)),
+        _ if word.starts_with(|c: char| c.is_ascii_digit() || c == '-') => word
+            .parse()
+            .map(Literal::Int)
+            .or_else(|_| word.parse().map(Literal::Float))
+            .ok(),
+        _ => None,
+    }
+}
+
+impl Rule {
+    fn parse(line: &str) -> Result<Self, String> {
+        let (pattern, replacement) = line
+            .split_once("=>")
+            .ok_or("expected a rule like `add x 0 => id x`")?;
+        let pattern = Shape::parse(pattern)?;
+        let replacement = Shape::parse(replacement)?;
+
+        if pattern.is_const() {
+            return Err("a pattern can't be a `const`, which has nothing to simplify".to_string());
+        }
+        if !(pattern.value.is_some() && (replacement.value.is_some() || replacement.is_const())
+            || pattern.effect.is_some() && replacement.effect.is_some())
+        {
+            return Err(if pattern.value.is_some() {
+                format!(
+                    "`{}` produces a value but `{}` doesn't",
+                    pattern.name, replacement.name
+                )
+            } else {
+                format!(
+                    "`{}` doesn't produce a value but `{}` does",
+                    pattern.name, replacement.name
+                )
+            });
+        }
+
+        if replacement.is_const() {
+            if !matches!(replacement.args[..], [Operand::Literal(_)])
+                || !replacement.funcs.is_empty()
+                || !replacement.labels.is_empty()
+            {
+                return Err("a `const` replacement takes a single literal".to_string());
+            }
+            return Ok(Self {
+                pattern,
+                replacement,
+            });
+        }
+        let mut args = Vec::new();
+        for arg in &replacement.args {
+            match arg {
+                Operand::Name(name) => args.push(name.clone()),
+                Operand::Literal(_) => {
+                    return Err("only a `const` replacement can have a literal".to_string())
+                }
+            }
+        }
+        let unbound = [
+            ("", &args, &pattern.arg_names()),
+            ("@", &replacement.funcs, &pattern.funcs),
+            (".", &replacement.labels, &pattern.labels),
+        ]
+        .into_iter()
+        .find_map(|(sigil, used, bound)| {
+            used.iter()
+                .find(|name| !bound.contains(name))
+                .map(|name| format!("`{sigil}{name}` is not in the pattern"))
+        });
+        unbound.map_or(
+            Ok(Self {
+                pattern,
+                replacement,
+            }),
+            Err,
+        )
+    }
+
+    // What ```instr``` becomes if it matches, given the variables of its block which hold constants
+    fn rewrite(
+        &self,
+        instr: &Instruction,
+        consts: &HashMap<String, Literal>,
+    ) -> Option<Instruction> {
+        let (args, funcs, labels) = match instr {
+            Instruction::Value {
+                op,
+                args,
+                funcs,
+                labels,
+                ..
+            } if self.pattern.value == Some(*op) => (args, funcs, labels),
+            Instruction::Effect {
+                op,
+                args,
+                funcs,
+                labels,
+                ..
+            } if self.pattern.effect == Some(*op) => (args, funcs, labels),
+            _ => return None,
+        };
+        // Variables, functions, and labels are matched separately so that `x`, `@x`, and `.x` are different names
+        let mut arg_bindings = HashMap::new();
+        let mut func_bindings = HashMap::new();
+        let mut label_bindings = HashMap::new();
+        bind(&mut func_bindings, &self.pattern.funcs, funcs)?;
+        bind(&mut label_bindings, &self.pattern.labels, labels)?;
+        if self.pattern.args.len() != args.len() {
+            return None;
+        }
+        for (operand, arg) in self.pattern.args.iter().zip(args) {
+            match operand {
+                Operand::Literal(value) if consts.get(arg) == Some(value) => {}
+                Operand::Literal(_) => return None,
+                Operand::Name(name) => bind(
+                    &mut arg_bindings,
+                    std::slice::from_ref(name),
+                    std::slice::from_ref(arg),
+                )?,
+            }
+        }
+
+        let mut rewritten = instr.clone();
+        let (new_args, new_funcs, new_labels) = match &mut rewritten {
+            Instruction::Value {
+                dest,
+                op_type,
+                pos,
+                attrs,
+                ..
+            } if self.replacement.is_const() => {
+                let Some(Operand::Literal(value)) = self.replacement.args.first() else {
+                    unreachable!("checked when the rule was parsed")
+                };
+                return Some(Instruction::Constant {
+                    dest: dest.clone(),
+                    op: ConstOps::Const,
+                    pos: *pos,
+                    const_type: op_type.clone(),
+                    value: value.clone(),
+                    attrs: std::mem::take(attrs),
+                });
+            }
+            Instruction::Value {
+                op,
+                args,
+                funcs,
+                labels,
+                ..
+            } => {
+                *op = self.replacement.value?;
+                (args, funcs, labels)
+            }
+            Instruction::Effect {
+                op,
+                args,
+                funcs,
+                labels,
+                ..
+            } => {
+                *op = self.replacement.effect?;
+                (args, funcs, labels)
+            }
+            Instruction::Constant { .. } => return None,
+        };
+        *new_args = substitute(&arg_bindings, &self.replacement.arg_names());
+        *new_funcs = substitute(&func_bindings, &self.replacement.funcs);
+        *new_labels = substitute(&label_bindings, &self.replacement.labels);
+        Some(rewritten)
+    }
+}
+
+// Matches each of ```names``` with what is in the same place in ```found```, failing if there are a different number of them or a name has already been matched with something else
+fn bind<'a>(
+    bindings: &mut HashMap<&'a str, &'a str>,
+    names: &'a [String],
+    found: &'a [String],
+) -> Option<()> {
+    if names.len() != found.len() {
+        return None;
+    }
+    for (name, found) in names.iter().zip(found) {
+        if *bindings.entry(name).or_insert(found) != found.as_str() {
+            return None;
+        }
+    }
+    Some(())
+}
+
+// What each of ```names``` was matched with
+fn substitute(bindings: &HashMap<&str, &str>, names: &[String]) -> Vec<String> {
+    names
+        .iter()
+        .map(|name| bindings[name.as_str()].to_string())
+        .collect()
+}
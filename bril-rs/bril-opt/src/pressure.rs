@@ -0,0 +1,82 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use bril_rs::registers::LiveRanges;
+use bril_rs::{Function, Program};
+use serde::Serialize;
+
+use crate::{function_blocks, FunctionError};
+
+/// The results of [`report_program`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgramPressure {
+    /// The pressure of each function, in the order they appear in the program
+    pub functions: Vec<FunctionPressure>,
+}
+
+/// The results of [`report`] for one function
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionPressure {
+    /// The name of the function
+    pub name: String,
+    /// The highest pressure of any block
+    pub max_pressure: usize,
+    /// How many registers the variables are colored into
+    pub registers: usize,
+    /// The register each variable is given
+    pub colors: BTreeMap<String, usize>,
+    /// The pressure of each basic block, in the order of [`bril_rs::cfg::Cfg::blocks`]
+    pub blocks: Vec<BlockPressure>,
+}
+
+/// The register pressure of a basic block
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockPressure {
+    /// The label of the block, which is made up for blocks which were not labelled
+    pub label: String,
+    /// The most variables live at once in the block
+    pub pressure: usize,
+    /// The variables live at the start of the block
+    pub live_in: BTreeSet<String>,
+    /// The variables live at the end of the block
+    pub live_out: BTreeSet<String>,
+}
+
+/// Runs [`report`] over every function of ```prog```
+/// # Errors
+/// Will return [`FunctionError`] for the first function with a `jmp` or `br` to a label which it doesn't have
+pub fn report_program(prog: &Program) -> Result<ProgramPressure, FunctionError> {
+    Ok(ProgramPressure {
+        functions: prog
+            .functions
+            .iter()
+            .map(report)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// Estimates the register pressure of ```func``` from the [`LiveRanges`] of its variables over its control flow graph, along with the registers a coloring of them needs
+/// # Errors
+/// Will return [`FunctionError`] if a `jmp` or `br` targets a label which ```func``` doesn't have
+pub fn report(func: &Function) -> Result<FunctionPressure, FunctionError> {
+    let cfg = function_blocks(func)?;
+    let ranges = LiveRanges::new(&cfg, &func.args);
+    let colors = ranges.color();
+    Ok(FunctionPressure {
+        name: func.name.clone(),
+        max_pressure: ranges.max_pressure(),
+        registers: colors.values().map(|c| c + 1).max().unwrap_or(0),
+        colors,
+        blocks: cfg
+            .blocks
+            .iter()
+            .zip(ranges.pressure)
+            .zip(ranges.live_in.into_iter().zip(ranges.live_out))
+            .map(|((block, pressure), (live_in, live_out))| BlockPressure {
+                label: block.label.clone(),
+                pressure,
+                live_in,
+                live_out,
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,542 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use bril_rs::callgraph::CallGraph;
+use bril_rs::cfg::Cfg;
+use bril_rs::dataflow::{solve, ConstantPropagation, ConstantValue};
+use bril_rs::{
+    Attrs, Code, ConstOps, EffectOps, Function, Instruction, Literal, Program, Type, ValueOps,
+};
+
+use crate::{
+    blocks, check_labels, fold, has_extra_dests, is_pure, simplify, FunctionError, Options,
+};
+
+// Specializing a function can make more of its callers foldable, which is repeated until nothing changes or this many rounds have gone by
+const MAX_ROUNDS: usize = 100;
+
+/// An argument of a function, by its name or by where it is in the arguments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Parameter {
+    /// The argument with this name, or if there isn't one and the name is like `arg0`, the argument at that position
+    Named(String),
+    /// The argument at this position, starting from 0
+    Position(usize),
+}
+
+/// A function with values for some of its arguments, which [`specialize`] makes a copy of the function for.
+///
+/// It is written `f:n=5,flag=true` on the command line. Arguments are named like `n`, or by their position like `arg0`, and their values are parsed as the type of the argument.
+/// ```
+/// use bril_opt::specialize::{Parameter, Specialization};
+///
+/// let spec: Specialization = "pow:arg1=3".parse().unwrap();
+/// assert_eq!(spec.function, "pow");
+/// assert_eq!(spec.args, [(Parameter::Named("arg1".to_string()), "3".to_string())]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Specialization {
+    /// The name of the function, which is changed in place when it is `main` instead of being copied
+    pub function: String,
+    /// The arguments which are given values, along with the text of their values
+    pub args: Vec<(Parameter, String)>,
+}
+
+impl FromStr for Specialization {
+    type Err = SpecializeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let syntax = || SpecializeError::Syntax(s.to_string());
+        let (function, args) = s.split_once(':').ok_or_else(syntax)?;
+        let function = function.strip_prefix('@').unwrap_or(function);
+        if function.is_empty() {
+            return Err(syntax());
+        }
+        let args = args
+            .split(',')
+            .map(|arg| {
+                let (name, value) = arg.split_once('=').ok_or_else(syntax)?;
+                if name.is_empty() || value.is_empty() {
+                    return Err(syntax());
+                }
+                Ok((Parameter::Named(name.to_string()), value.to_string()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            function: function.to_string(),
+            args,
+        })
+    }
+}
+
+impl Specialization {
+    /// The specialization of `main` for ```args```, in the order `main` takes them
+    #[must_use]
+    pub fn main(args: &[String]) -> Self {
+        Self {
+            function: "main".to_string(),
+            args: args
+                .iter()
+                .enumerate()
+                .map(|(i, value)| (Parameter::Position(i), value.clone()))
+                .collect(),
+        }
+    }
+
+    /// The value which the specialization gives each argument of its function in ```prog```, or None for the arguments it leaves alone
+    ///
+    /// # Errors
+    /// Will return a [`SpecializeError`] if the function or one of the arguments doesn't exist, or if a value isn't of the type of its argument
+    pub fn values(&self, prog: &Program) -> Result<Vec<Option<Literal>>, SpecializeError> {
+        let func = prog
+            .functions
+            .iter()
+            .find(|f| f.name == self.function)
+            .ok_or_else(|| SpecializeError::UnknownFunction(self.function.clone()))?;
+        let mut values = vec![None; func.args.len()];
+        for (param, value) in &self.args {
+            let position = match param {
+                Parameter::Named(name) => {
+                    func.args.iter().position(|a| a.name == *name).or_else(|| {
+                        name.strip_prefix("arg")
+                            .and_then(|n| n.parse().ok())
+                            .filter(|n| *n < func.args.len())
+                    })
+                }
+                Parameter::Position(n) => Some(*n).filter(|n| *n < func.args.len()),
+            };
+            let position = position.ok_or_else(|| SpecializeError::UnknownArgument {
+                function: self.function.clone(),
+                arg: match param {
+                    Parameter::Named(name) => name.clone(),
+                    Parameter::Position(n) => format!("arg{n}"),
+                },
+            })?;
+            let arg = &func.args[position];
+            values[position] = Some(parse_literal(value, &arg.arg_type).ok_or_else(|| {
+                SpecializeError::InvalidValue {
+                    function: self.function.clone(),
+                    arg: arg.name.clone(),
+                    value: value.clone(),
+                    ty: arg.arg_type.clone(),
+                }
+            })?);
+        }
+        Ok(values)
+    }
+}
+
+fn parse_literal(value: &str, ty: &Type) -> Option<Literal> {
+    match ty {
+        Type::Int => value.parse().ok().map(Literal::Int),
+        Type::Bool => value.parse().ok().map(Literal::Bool),
+        Type::Float => value.parse().ok().map(Literal::Float),
+        Type::Char => {
+            let mut chars = value.chars();
+            let c = chars.next()?;
+            chars.next().is_none().then_some(Literal::Char(c))
+        }
+        _ => None,
+    }
+}
+
+/// Why a [`Specialization`] couldn't be read or doesn't fit a program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecializeError {
+    /// The text isn't written like `f:n=5`
+    Syntax(String),
+    /// There is no function with this name
+    UnknownFunction(String),
+    /// The function has no argument with this name or position
+    UnknownArgument {
+        /// The name of the function
+        function: String,
+        /// The argument which was asked for
+        arg: String,
+    },
+    /// The value can't be read as the type of its argument
+    InvalidValue {
+        /// The name of the function
+        function: String,
+        /// The name of the argument
+        arg: String,
+        /// The text of the value
+        value: String,
+        /// The type of the argument
+        ty: Type,
+    },
+}
+
+impl Display for SpecializeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax(s) => write!(f, "expected a specialization like `f:n=5`, found `{s}`"),
+            Self::UnknownFunction(func) => write!(f, "no function named @{func}"),
+            Self::UnknownArgument { function, arg } => {
+                write!(f, "@{function} has no argument `{arg}`")
+            }
+            Self::InvalidValue {
+                function,
+                arg,
+                value,
+                ty,
+            } => write!(
+                f,
+                "`{value}` isn't a value of type {ty} for argument `{arg}` of @{function}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SpecializeError {}
+
+struct Specializer {
+    // Every function which copies can be made from, as it was before any of its calls were specialized
+    bodies: HashMap<String, Function>,
+    // The copy made of each function for the constants of some of its arguments, keyed by both
+    copies: HashMap<String, String>,
+    // The copies which were asked for, which are kept even if nothing calls them
+    kept: HashSet<String>,
+    // How many more copies can be made for calls
+    budget: usize,
+    // The constant that each function which always returns the same one returns, and whether a call to it can be left out altogether
+    returns: HashMap<String, (Literal, bool)>,
+}
+
+impl Specializer {
+    // The name of the copy of ```function``` with ```consts``` built in to it, made if there isn't one yet and either ```forced``` or there is enough budget left
+    fn copy(
+        &mut self,
+        prog: &mut Program,
+        function: &str,
+        consts: &[Option<Literal>],
+        forced: bool,
+    ) -> Option<String> {
+        let key = format!("{function} {consts:?}");
+        if let Some(name) = self.copies.get(&key) {
+            return Some(name.clone());
+        }
+        if self.budget == 0 && !forced {
+            return None;
+        }
+        let body = self.bodies.get(function)?;
+        if has_extra_dests(body) {
+            return None;
+        }
+        let mut copy = Function {
+            name: fresh_name(prog, function),
+            ..body.clone()
+        };
+        bind(&mut copy, consts);
+        let name = copy.name.clone();
+        if !forced {
+            self.budget -= 1;
+        }
+        self.bodies.insert(name.clone(), copy.clone());
+        self.copies.insert(key, name.clone());
+        prog.functions.push(copy);
+        Some(name)
+    }
+
+    // The name of a copy of ```function``` which returns nothing, for calling it only for its effects, made if there isn't one yet
+    fn effects_only(&mut self, prog: &mut Program, function: &str) -> Option<String> {
+        let key = format!("{function} effects");
+        if let Some(name) = self.copies.get(&key) {
+            return Some(name.clone());
+        }
+        let body = self.bodies.get(function)?;
+        let mut copy = Function {
+            name: fresh_name(prog, function),
+            return_type: None,
+            ..body.clone()
+        };
+        for code in &mut copy.instrs {
+            if let Code::Instruction(Instruction::Effect {
+                op: EffectOps::Return,
+                args,
+                ..
+            }) = code
+            {
+                args.clear();
+            }
+        }
+        let name = copy.name.clone();
+        self.bodies.insert(name.clone(), copy.clone());
+        self.copies.insert(key, name.clone());
+        prog.functions.push(copy);
+        Some(name)
+    }
+
+    // Folds function ```index``` of ```prog``` and then points each call in it which passes a constant at a copy of the function it calls with that constant built in. Calls to functions which always return the same constant get that constant instead. Gives back whether the function changed
+    fn specialize_function(&mut self, prog: &mut Program, index: usize) -> bool {
+        let func = &mut prog.functions[index];
+        if has_extra_dests(func) {
+            return false;
+        }
+        let args = func.args.clone();
+        let before = func.instrs.clone();
+        let mut cfg = blocks(std::mem::take(&mut func.instrs));
+        fold::fold(&mut cfg, &args);
+
+        let result = solve(&ConstantPropagation::new(&args), &cfg);
+        for (block, mut consts) in cfg.blocks.iter_mut().zip(result.inputs) {
+            let mut instrs = Vec::with_capacity(block.instrs.len());
+            for mut instr in std::mem::take(&mut block.instrs) {
+                let call = match &mut instr {
+                    Instruction::Value {
+                        op: ValueOps::Call,
+                        args,
+                        funcs,
+                        extra_dests,
+                        ..
+                    } if extra_dests.is_empty() => Some((args, funcs)),
+                    Instruction::Effect {
+                        op: EffectOps::Call,
+                        args,
+                        funcs,
+                        ..
+                    } => Some((args, funcs)),
+                    _ => None,
+                };
+                if let Some((call_args, funcs)) = call {
+                    let known: Vec<Option<Literal>> = call_args
+                        .iter()
+                        .map(|a| match consts.get(a) {
+                            Some(ConstantValue::Constant(value)) => Some(value.clone()),
+                            _ => None,
+                        })
+                        .collect();
+                    if known.iter().any(Option::is_some) {
+                        if let Some(copy) = self.copy(prog, &funcs[0], &known, false) {
+                            funcs[0] = copy;
+                            let mut known = known.iter();
+                            call_args.retain(|_| known.next().is_some_and(Option::is_none));
+                        }
+                    }
+                }
+                if let Some((call, value)) = self.known_result(prog, &instr) {
+                    instrs.extend(call);
+                    instr = value;
+                }
+                ConstantPropagation::transfer_instr(&mut consts, &instr);
+                instrs.push(instr);
+            }
+            block.instrs = instrs;
+        }
+
+        let func = &mut prog.functions[index];
+        func.instrs = simplify(cfg.into_code());
+        if let Some(returned) = constant_return(func) {
+            self.returns.insert(func.name.clone(), returned);
+        }
+        func.instrs != before
+    }
+
+    // The constant that ```instr``` gives its destination, when it calls a function which always returns the same one, along with the call to keep making for its effects if it has any. That call goes to a copy of the function which returns nothing, since a call which doesn't take the value has to be to a function without one
+    fn known_result(
+        &mut self,
+        prog: &mut Program,
+        instr: &Instruction,
+    ) -> Option<(Option<Instruction>, Instruction)> {
+        let Instruction::Value {
+            op: ValueOps::Call,
+            dest,
+            args,
+            funcs,
+            op_type,
+            pos,
+            attrs,
+            extra_dests,
+            ..
+        } = instr
+        else {
+            return None;
+        };
+        let (value, removable) = self.returns.get(&funcs[0])?.clone();
+        if !extra_dests.is_empty() {
+            return None;
+        }
+        let call = if removable {
+            None
+        } else {
+            Some(self.effects_only(prog, &funcs[0])?)
+        };
+        let call = call.map(|callee| Instruction::Effect {
+            args: args.clone(),
+            funcs: vec![callee],
+            labels: Vec::new(),
+            op: EffectOps::Call,
+            pos: *pos,
+            attrs: Attrs::new(),
+        });
+        let constant = Instruction::Constant {
+            dest: dest.clone(),
+            op: ConstOps::Const,
+            pos: *pos,
+            const_type: op_type.clone(),
+            value,
+            attrs: attrs.clone(),
+        };
+        Some((call, constant))
+    }
+}
+
+// The first of `function.N` which no function of ```prog``` is called yet. One of the names up to how many functions there are is always free
+fn fresh_name(prog: &Program, function: &str) -> String {
+    (1..=prog.functions.len() + 1)
+        .map(|n| format!("{function}.{n}"))
+        .find(|name| !prog.functions.iter().any(|f| f.name == *name))
+        .unwrap()
+}
+
+// Makes ```func``` take only the arguments which ```consts``` has no value for, starting it with a `const` for each of the others
+fn bind(func: &mut Function, consts: &[Option<Literal>]) {
+    let mut prologue = Vec::new();
+    let mut args = Vec::new();
+    for (arg, value) in std::mem::take(&mut func.args).into_iter().zip(consts) {
+        match value {
+            Some(value) => prologue.push(Code::Instruction(Instruction::Constant {
+                dest: arg.name,
+                op: ConstOps::Const,
+                pos: None,
+                const_type: arg.arg_type,
+                value: value.clone(),
+                attrs: Attrs::new(),
+            })),
+            None => args.push(arg),
+        }
+    }
+    func.args = args;
+    prologue.append(&mut func.instrs);
+    func.instrs = prologue;
+}
+
+// The constant ```func``` always returns, if every `ret` of it returns the same one, and whether calling it does nothing else. A function which might never return has to be called for that, so it has to have no loops as well as no effects
+fn constant_return(func: &Function) -> Option<(Literal, bool)> {
+    func.return_type.as_ref()?;
+    let cfg = blocks(func.instrs.clone());
+    let result = solve(&ConstantPropagation::new(&func.args), &cfg);
+    let mut returned: Option<Literal> = None;
+    let mut removable = true;
+    for (block, mut consts) in cfg.blocks.iter().zip(result.inputs) {
+        for instr in &block.instrs {
+            match instr {
+                Instruction::Effect {
+                    op: EffectOps::Return,
+                    args,
+                    ..
+                } => {
+                    let Some(ConstantValue::Constant(value)) = consts.get(args.first()?) else {
+                        return None;
+                    };
+                    if returned.as_ref().is_some_and(|r| r != value) {
+                        return None;
+                    }
+                    returned = Some(value.clone());
+                }
+                Instruction::Effect {
+                    op: EffectOps::Jump | EffectOps::Branch | EffectOps::Nop,
+                    ..
+                } => {}
+                _ => removable &= is_pure(instr),
+            }
+            ConstantPropagation::transfer_instr(&mut consts, instr);
+        }
+    }
+    Some((returned?, removable && !has_cycle(&cfg)))
+}
+
+fn has_cycle(cfg: &Cfg) -> bool {
+    // Blocks which haven't been visited, are being visited, or are done
+    let mut state = vec![0_u8; cfg.blocks.len()];
+    let mut stack = vec![(0, 0)];
+    if cfg.blocks.is_empty() {
+        return false;
+    }
+    state[0] = 1;
+    while let Some((block, next)) = stack.pop() {
+        match cfg.successors[block].get(next) {
+            Some(&succ) => {
+                stack.push((block, next + 1));
+                match state[succ] {
+                    0 => {
+                        state[succ] = 1;
+                        stack.push((succ, 0));
+                    }
+                    1 => return true,
+                    _ => {}
+                }
+            }
+            None => state[block] = 2,
+        }
+    }
+    false
+}
+
+/// Partially evaluates ```prog``` with the constants it already has, along with those in [`Options::specializations`].
+///
+/// Every call which passes a constant is pointed at a copy of the function it calls with that constant built in, named like `f.1`, which starts with a `const` for each argument it no longer takes. Each function is then folded with [`fold::fold`], which can make more arguments constant and collapses branches on them, and the code it makes unreachable is removed. A call to a function which always returns the same constant is replaced with that constant, and taken out altogether if the function has no effects and no loops. This goes on until nothing changes, so recursion on a constant like `fib 10` is specialized all the way down to its base cases.
+///
+/// `main` is specialized in place with the values its specialization gives its arguments, and a copy is made of every other function with a specialization even if nothing calls it. At most [`Options::specialize_limit`] other copies are made, so that recursion which never reaches a base case finishes, and the copies nothing calls any more are removed at the end. Functions with a call which assigns more than one variable, from the experimental `tuples` extension, are left as they are like with the other passes.
+///
+/// # Errors
+/// Will return [`FunctionError`] if a `jmp` or `br` targets a label which its function doesn't have, in which case ```prog``` is left as it was
+///
+/// # Panics
+/// Will panic if one of [`Options::specializations`] doesn't fit ```prog```, which [`Specialization::values`] checks
+pub fn specialize(prog: &mut Program, options: &Options) -> Result<(), FunctionError> {
+    check_labels(prog)?;
+    let mut specializer = Specializer {
+        bodies: prog
+            .functions
+            .iter()
+            .map(|f| (f.name.clone(), f.clone()))
+            .collect(),
+        copies: HashMap::new(),
+        kept: HashSet::new(),
+        budget: options.specialize_limit,
+        returns: HashMap::new(),
+    };
+    for spec in &options.specializations {
+        let values = spec
+            .values(prog)
+            .unwrap_or_else(|e| panic!("invalid specialization: {e}"));
+        if spec.function == "main" {
+            if let Some(main) = prog.functions.iter_mut().find(|f| f.name == "main") {
+                bind(main, &values);
+            }
+        } else if let Some(name) = specializer.copy(prog, &spec.function, &values, true) {
+            specializer.kept.insert(name);
+        }
+    }
+
+    for _ in 0..MAX_ROUNDS {
+        let mut changed = false;
+        // Copies made along the way are added to the end, and are specialized in the same round
+        let mut index = 0;
+        while index < prog.functions.len() {
+            changed |= specializer.specialize_function(prog, index);
+            index += 1;
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let copies: HashSet<&String> = specializer.copies.values().collect();
+    let graph = CallGraph::new(prog);
+    let roots: Vec<usize> = (0..graph.functions.len())
+        .filter(|&f| {
+            let name = &graph.functions[f];
+            !copies.contains(name) || specializer.kept.contains(name)
+        })
+        .collect();
+    let reached = graph.reachable(&roots);
+    let mut index = 0;
+    prog.functions.retain(|_| {
+        index += 1;
+        reached[index - 1]
+    });
+    Ok(())
+}
@@ -0,0 +1,414 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use bril_rs::cfg::{BasicBlock, Cfg};
+use bril_rs::dataflow::{def, solve, ConstantPropagation, ConstantValue, DataflowResult};
+use bril_rs::dominance::Dominators;
+use bril_rs::loops::{natural_loops, NaturalLoop};
+use bril_rs::{Argument, Code, EffectOps, Instruction, Literal, Position, ValueOps};
+
+use crate::Options;
+
+/// Unrolls counted loops, whose counter starts at a constant and moves by a constant step until it passes a constant bound.
+///
+/// The counter has to be assigned once every time around, and compared against the bound in the header to decide whether to leave. Loops which run at most [`Options::full_unroll`] times, or fewer times than [`Options::unroll_factor`], are replaced by a copy of their body for every time they run. Other loops get [`Options::unroll_factor`] copies of their body for each check of the bound, with the iterations that are left over run before the loop. Only loops whose header is the only way out of them are unrolled, and functions with `phi` nodes are left alone.
+pub fn unroll(cfg: &mut Cfg, args: &[Argument], options: &Options) {
+    let has_phi = cfg.blocks.iter().flat_map(|b| &b.instrs).any(|i| {
+        matches!(
+            i,
+            Instruction::Value {
+                op: ValueOps::Phi,
+                ..
+            }
+        )
+    });
+    if has_phi {
+        return;
+    }
+    // Every loop is unrolled with the counter assigned more than once in it, so no loop is unrolled again
+    while let Some((counted, plan)) = next_loop(cfg, args, options) {
+        unroll_loop(cfg, &counted, plan);
+    }
+}
+
+// A loop found by counted_loop, with the blocks referred to by their indices
+struct CountedLoop {
+    // Every block of the loop in order, including the header
+    blocks: Vec<usize>,
+    header: usize,
+    latch: usize,
+    // Where the header goes to run the body and to leave the loop
+    body: usize,
+    exit: usize,
+    trip_count: u64,
+}
+
+// How a loop is unrolled: completely, or with copies of its body in the loop and before it
+#[derive(Clone, Copy)]
+enum Plan {
+    Full,
+    Partial { in_loop: u64, peeled: u64 },
+}
+
+// The first counted loop which there is something to do to, along with what to do
+fn next_loop(cfg: &Cfg, args: &[Argument], options: &Options) -> Option<(CountedLoop, Plan)> {
+    let doms = cfg.dominators();
+    let consts = solve(&ConstantPropagation::new(args), cfg);
+    let loops = natural_loops(cfg, &doms);
+    loops.iter().find_map(|natural_loop| {
+        let counted = counted_loop(cfg, &doms, &consts, &loops, natural_loop)?;
+        let n = counted.trip_count;
+        let factor = options.unroll_factor as u64;
+        let plan = if n == 0 {
+            // The body never runs, which fold already makes the most of
+            return None;
+        } else if n <= options.full_unroll as u64 || n < factor {
+            Plan::Full
+        } else if factor > 1 {
+            Plan::Partial {
+                in_loop: factor - 1,
+                peeled: n % factor,
+            }
+        } else {
+            return None;
+        };
+        Some((counted, plan))
+    })
+}
+
+// Whether ```natural_loop``` is a counted loop, and how many times its body runs if so
+fn counted_loop(
+    cfg: &Cfg,
+    doms: &Dominators,
+    consts: &DataflowResult<BTreeMap<String, ConstantValue>>,
+    loops: &[NaturalLoop],
+    natural_loop: &NaturalLoop,
+) -> Option<CountedLoop> {
+    let header = natural_loop.header;
+    let [latch] = natural_loop.latches[..] else {
+        return None;
+    };
+    let [(from, exit)] = natural_loop.exits(cfg)[..] else {
+        return None;
+    };
+    if from != header {
+        return None;
+    }
+
+    // The header ends by comparing the counter with the bound and branching on the result
+    let header_instrs = &cfg.blocks[header].instrs;
+    let Some(Instruction::Effect {
+        op: EffectOps::Branch,
+        args,
+        labels,
+        ..
+    }) = header_instrs.last()
+    else {
+        return None;
+    };
+    let stays = labels[0] != cfg.blocks[exit].label;
+    let body = cfg.find(&labels[usize::from(!stays)])?;
+    let cmp_idx = header_instrs
+        .iter()
+        .rposition(|i| def(i) == Some(&args[0]))?;
+    let Instruction::Value {
+        op: cmp @ (ValueOps::Lt | ValueOps::Le | ValueOps::Gt | ValueOps::Ge),
+        args: cmp_args,
+        ..
+    } = &header_instrs[cmp_idx]
+    else {
+        return None;
+    };
+    let header_consts = consts_before(cfg, consts, header, cmp_idx);
+    let (counter, bound, counter_first) = match (
+        int_constant(&header_consts, &cmp_args[0]),
+        int_constant(&header_consts, &cmp_args[1]),
+    ) {
+        (None, Some(bound)) => (&cmp_args[0], bound, true),
+        (Some(bound), None) => (&cmp_args[1], bound, false),
+        _ => return None,
+    };
+
+    // The counter is only assigned once in the loop, by adding or subtracting a constant, in a block which runs exactly once every time around
+    let mut defs = natural_loop.blocks.iter().flat_map(|&b| {
+        cfg.blocks[b]
+            .instrs
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| def(i) == Some(counter))
+            .map(move |(idx, _)| (b, idx))
+    });
+    let (step_block, step_idx) = defs.next()?;
+    if defs.next().is_some()
+        || step_block == header
+        || !doms.dominates(step_block, latch)
+        || loops.iter().any(|l| {
+            l.header != header && l.contains(step_block) && l.blocks.is_subset(&natural_loop.blocks)
+        })
+    {
+        return None;
+    }
+    let Instruction::Value {
+        op: step_op @ (ValueOps::Add | ValueOps::Sub),
+        args: step_args,
+        ..
+    } = &cfg.blocks[step_block].instrs[step_idx]
+    else {
+        return None;
+    };
+    let step_consts = consts_before(cfg, consts, step_block, step_idx);
+    let step = match (step_op, &step_args[..]) {
+        (ValueOps::Add, [a, b]) if a == counter => int_constant(&step_consts, b)?,
+        (ValueOps::Add, [a, b]) if b == counter => int_constant(&step_consts, a)?,
+        (ValueOps::Sub, [a, b]) if a == counter => int_constant(&step_consts, b)?.checked_neg()?,
+        _ => return None,
+    };
+
+    // The counter starts out with the same constant on every way into the loop
+    let mut inits = cfg.predecessors[header]
+        .iter()
+        .filter(|p| !natural_loop.contains(**p) && doms.is_reachable(**p))
+        .map(|&p| int_constant(&consts.outputs[p], counter));
+    let init = inits.next()??;
+    if !inits.all(|i| i == Some(init)) {
+        return None;
+    }
+
+    Some(CountedLoop {
+        blocks: natural_loop.blocks.iter().copied().collect(),
+        header,
+        latch,
+        body,
+        exit,
+        trip_count: trip_count(*cmp, counter_first, stays, init, bound, step)?,
+    })
+}
+
+// The constants known just before the instruction at ```idx``` of ```block```
+fn consts_before(
+    cfg: &Cfg,
+    consts: &DataflowResult<BTreeMap<String, ConstantValue>>,
+    block: usize,
+    idx: usize,
+) -> BTreeMap<String, ConstantValue> {
+    let mut known = consts.inputs[block].clone();
+    for instr in &cfg.blocks[block].instrs[..idx] {
+        ConstantPropagation::transfer_instr(&mut known, instr);
+    }
+    known
+}
+
+fn int_constant(consts: &BTreeMap<String, ConstantValue>, var: &str) -> Option<i64> {
+    match consts.get(var) {
+        Some(ConstantValue::Constant(Literal::Int(i))) => Some(*i),
+        _ => None,
+    }
+}
+
+// How many times the body of a loop runs when its counter starts at ```init``` and moves by ```step``` every time around, for as long as comparing the counter with ```bound``` by ```cmp``` gives ```stays```. The counter is the left argument of the comparison if ```counter_first``` is set. None if the loop never ends or the counter would overflow
+fn trip_count(
+    cmp: ValueOps,
+    counter_first: bool,
+    stays: bool,
+    init: i64,
+    bound: i64,
+    step: i64,
+) -> Option<u64> {
+    // Turn the condition for going around again into `counter cmp bound`
+    let cmp = match (cmp, counter_first) {
+        (ValueOps::Lt, false) => ValueOps::Gt,
+        (ValueOps::Le, false) => ValueOps::Ge,
+        (ValueOps::Gt, false) => ValueOps::Lt,
+        (ValueOps::Ge, false) => ValueOps::Le,
+        (cmp, _) => cmp,
+    };
+    let cmp = match (cmp, stays) {
+        (ValueOps::Lt, false) => ValueOps::Ge,
+        (ValueOps::Le, false) => ValueOps::Gt,
+        (ValueOps::Gt, false) => ValueOps::Le,
+        (ValueOps::Ge, false) => ValueOps::Lt,
+        (cmp, _) => cmp,
+    };
+    let (init, bound, step) = (i128::from(init), i128::from(bound), i128::from(step));
+    // The last value of the counter which goes around again
+    let (holds, last) = match cmp {
+        ValueOps::Lt => (init < bound, bound - 1),
+        ValueOps::Le => (init <= bound, bound),
+        ValueOps::Gt => (init > bound, bound + 1),
+        _ => (init >= bound, bound),
+    };
+    if !holds {
+        return Some(0);
+    }
+    let n = match cmp {
+        ValueOps::Lt | ValueOps::Le if step > 0 => (last - init) / step + 1,
+        ValueOps::Gt | ValueOps::Ge if step < 0 => (init - last) / -step + 1,
+        _ => return None,
+    };
+    // The counter is stepped once more before the comparison which leaves the loop
+    i64::try_from(init + n * step).ok()?;
+    u64::try_from(n).ok()
+}
+
+// Unrolls ```counted``` as ```plan``` says, by laying out copies of its blocks and building the cfg again from them
+fn unroll_loop(cfg: &mut Cfg, counted: &CountedLoop, plan: Plan) {
+    let mut used: HashSet<String> = cfg.blocks.iter().map(|b| b.label.clone()).collect();
+    let (in_loop, peeled) = match plan {
+        Plan::Full => (counted.trip_count - 1, 0),
+        Plan::Partial { in_loop, peeled } => (in_loop, peeled),
+    };
+    let labels = |used: &mut HashSet<String>| -> HashMap<usize, String> {
+        counted
+            .blocks
+            .iter()
+            .map(|&b| (b, fresh_label(used, &cfg.blocks[b].label)))
+            .collect()
+    };
+    let peeled_labels: Vec<HashMap<usize, String>> =
+        (0..peeled).map(|_| labels(&mut used)).collect();
+    let in_loop_labels: Vec<HashMap<usize, String>> =
+        (0..in_loop).map(|_| labels(&mut used)).collect();
+    let header_label = cfg.blocks[counted.header].label.clone();
+    let last_header = match plan {
+        Plan::Full => fresh_label(&mut used, &header_label),
+        Plan::Partial { .. } => header_label.clone(),
+    };
+
+    // Each copy goes on to the next, and the last copy in the loop goes to the header which checks the bound again, or the header which leaves the loop once it is fully unrolled
+    let copies = |all: &[HashMap<usize, String>], last: &str| -> Vec<BasicBlock> {
+        all.iter()
+            .enumerate()
+            .flat_map(|(j, labels)| {
+                let next = all.get(j + 1).map_or(last, |l| l[&counted.header].as_str());
+                copy_iteration(cfg, counted, labels, next)
+            })
+            .collect()
+    };
+    let peeled_blocks = copies(&peeled_labels, &header_label);
+    let in_loop_blocks = copies(&in_loop_labels, &last_header);
+
+    let mut blocks = cfg.blocks.clone();
+    if let Some(first) = peeled_labels.first() {
+        for &p in &cfg.predecessors[counted.header] {
+            if !counted.blocks.contains(&p) {
+                retarget(&mut blocks[p], &header_label, &first[&counted.header]);
+            }
+        }
+    }
+    let after_latch = in_loop_labels
+        .first()
+        .map_or(&last_header, |l| &l[&counted.header]);
+    retarget(&mut blocks[counted.latch], &header_label, after_latch);
+    let mut leave = None;
+    if matches!(plan, Plan::Full) {
+        // The original blocks run the first iteration and a copy of the header runs the check which leaves the loop
+        let mut last = copy_block(&cfg.blocks[counted.header], last_header);
+        stay_in_loop(&mut last, &cfg.blocks[counted.exit].label);
+        leave = Some(last);
+        stay_in_loop(&mut blocks[counted.header], &cfg.blocks[counted.body].label);
+    }
+
+    // The copies go right before the header and right after the last block of the loop, so that they can fall through to each other
+    let tail = blocks.split_off(counted.blocks.last().unwrap() + 1);
+    let in_loop_part = blocks.split_off(counted.header);
+    blocks.extend(peeled_blocks);
+    blocks.extend(in_loop_part);
+    blocks.extend(in_loop_blocks);
+    blocks.extend(leave);
+    blocks.extend(tail);
+
+    *cfg = crate::blocks(
+        blocks
+            .into_iter()
+            .flat_map(|b| {
+                std::iter::once(Code::Label {
+                    label: b.label,
+                    pos: b.pos,
+                })
+                .chain(b.instrs.into_iter().map(Code::Instruction))
+            })
+            .collect(),
+    );
+}
+
+// A copy of ```block``` labelled ```label```, whose instructions are at the positions of the ones they are copies of but derived, since they weren't in the source themselves
+fn copy_block(block: &BasicBlock, label: String) -> BasicBlock {
+    let mut instrs = block.instrs.clone();
+    for instr in &mut instrs {
+        instr.set_pos(instr.get_pos().map(Position::derived));
+    }
+    BasicBlock {
+        label,
+        pos: block.pos.map(Position::derived),
+        instrs,
+    }
+}
+
+// The blocks of one iteration of ```counted```, labelled with ```labels```, which goes around the loop again without checking the bound and then to ```next```
+fn copy_iteration(
+    cfg: &Cfg,
+    counted: &CountedLoop,
+    labels: &HashMap<usize, String>,
+    next: &str,
+) -> Vec<BasicBlock> {
+    let renamed: HashMap<&str, &str> = counted
+        .blocks
+        .iter()
+        .map(|b| (cfg.blocks[*b].label.as_str(), labels[b].as_str()))
+        .collect();
+    counted
+        .blocks
+        .iter()
+        .map(|&b| {
+            let mut block = copy_block(&cfg.blocks[b], labels[&b].clone());
+            if b == counted.header {
+                stay_in_loop(&mut block, &labels[&counted.body]);
+            }
+            if let Some(Instruction::Effect {
+                labels: targets, ..
+            }) = block.instrs.last_mut()
+            {
+                for target in targets {
+                    if b == counted.latch && *target == cfg.blocks[counted.header].label {
+                        *target = next.to_string();
+                    } else if let Some(to) = renamed.get(target.as_str()) {
+                        *target = (*to).to_string();
+                    }
+                }
+            }
+            block
+        })
+        .collect()
+}
+
+// Makes the `br` at the end of the header of a loop jump straight to ```to```
+fn stay_in_loop(header: &mut BasicBlock, to: &str) {
+    if let Some(Instruction::Effect {
+        op, args, labels, ..
+    }) = header.instrs.last_mut()
+    {
+        *op = EffectOps::Jump;
+        args.clear();
+        *labels = vec![to.to_string()];
+    }
+}
+
+// Makes the terminator of ```block``` go to ```to``` wherever it went to ```from```
+fn retarget(block: &mut BasicBlock, from: &str, to: &str) {
+    if let Some(Instruction::Effect { labels, .. }) = block.instrs.last_mut() {
+        for l in labels.iter_mut().filter(|l| *l == from) {
+            *l = to.to_string();
+        }
+    }
+}
+
+// A label made from ```label``` which isn't in ```used```, which it is then added to
+fn fresh_label(used: &mut HashSet<String>, label: &str) -> String {
+    let mut i = 0;
+    loop {
+        let candidate = format!("{label}.unroll{i}");
+        if used.insert(candidate.clone()) {
+            return candidate;
+        }
+        i += 1;
+    }
+}
@@ -0,0 +1,114 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![doc = include_str!("../README.md")]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
+
+use std::fmt::Display;
+
+use bril_rs::{AbstractProgram, Program};
+use brilirs::basic_block::BBProgram;
+use brilirs::interp::{Interpreter, Options};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+create_exception!(
+    bril,
+    BrilError,
+    PyException,
+    "A program which could not be read, failed to type check, or went wrong while running"
+);
+
+fn bril_error(e: impl Display) -> PyErr {
+    BrilError::new_err(e.to_string())
+}
+
+/// A Bril program, as read by `load_program` or `parse_text`
+#[pyclass(name = "Program", module = "bril")]
+#[derive(Clone)]
+struct PyProgram {
+    prog: Program,
+}
+
+#[pymethods]
+impl PyProgram {
+    /// The program as Bril JSON
+    fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(&self.prog).map_err(bril_error)
+    }
+
+    /// The names of the functions of the program, in the order they were defined
+    #[getter]
+    fn functions(&self) -> Vec<String> {
+        self.prog.functions.iter().map(|f| f.name.clone()).collect()
+    }
+
+    fn __str__(&self) -> String {
+        self.prog.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<bril.Program with {} functions>",
+            self.prog.functions.len()
+        )
+    }
+}
+
+// Checks that ```prog``` is structurally valid, like any `AbstractProgram` that `Program` doesn't support
+fn to_program(prog: AbstractProgram) -> PyResult<PyProgram> {
+    Program::try_from(prog)
+        .map(|prog| PyProgram { prog })
+        .map_err(bril_error)
+}
+
+/// Reads a program from Bril JSON
+#[pyfunction]
+fn load_program(json: &str) -> PyResult<PyProgram> {
+    to_program(serde_json::from_str(json).map_err(bril_error)?)
+}
+
+/// Reads a program from Bril text, keeping the positions of everything in it for error messages
+#[pyfunction]
+fn parse_text(text: &str) -> PyResult<PyProgram> {
+    to_program(bril2json::parse_abstract_program_from_str(text, true).map_err(bril_error)?)
+}
+
+/// Every problem that brilck finds in the program, errors and warnings alike, as `line:column: message`
+#[pyfunction]
+fn type_check(program: &PyProgram) -> PyResult<Vec<String>> {
+    let bbprog = BBProgram::new(program.prog.clone()).map_err(bril_error)?;
+    Ok(brilirs::check::type_check_all(&bbprog)
+        .iter()
+        .map(ToString::to_string)
+        .collect())
+}
+
+/// Type checks the program and runs its main function with ```args``` as its command line arguments, returning what it printed and the number of instructions it ran
+#[pyfunction]
+#[pyo3(signature = (program, args = Vec::new()))]
+#[allow(clippy::needless_pass_by_value)]
+fn run(program: &PyProgram, args: Vec<String>) -> PyResult<(String, u32)> {
+    let bbprog = BBProgram::new(program.prog.clone()).map_err(bril_error)?;
+    brilirs::check::type_check(&bbprog).map_err(bril_error)?;
+    let mut out = Vec::new();
+    let mut interpreter = Interpreter::new(&bbprog, &mut out, Options::default());
+    interpreter.start_main(&args).map_err(bril_error)?;
+    interpreter.run_to_completion().map_err(bril_error)?;
+    let count = interpreter.instruction_count();
+    // Memory which is never freed is an error, like it is for brilirs
+    interpreter.finish().map_err(bril_error)?;
+    Ok((String::from_utf8_lossy(&out).into_owned(), count))
+}
+
+/// Parses, type checks, and runs Bril programs with brilirs
+#[pymodule]
+fn bril(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("BrilError", m.py().get_type::<BrilError>())?;
+    m.add_class::<PyProgram>()?;
+    m.add_function(wrap_pyfunction!(load_program, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_text, m)?)?;
+    m.add_function(wrap_pyfunction!(type_check, m)?)?;
+    m.add_function(wrap_pyfunction!(run, m)?)?;
+    Ok(())
+}
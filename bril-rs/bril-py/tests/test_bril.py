@@ -0,0 +1,29 @@
+# Runs a benchmark through the `bril` module and checks it against the output and instruction count that brili gives.
+# Build the module into the current environment with `maturin develop` first, then run `pytest` here.
+from pathlib import Path
+
+import bril
+import pytest
+
+BENCHMARKS = Path(__file__).resolve().parents[3] / "benchmarks"
+
+
+def test_benchmark():
+    source = (BENCHMARKS / "sum-bits.bril").read_text()
+    args = next(
+        line.split(":", 1)[1].split()
+        for line in source.splitlines()
+        if line.startswith("# ARGS:")
+    )
+    prog = bril.parse_text(source)
+    assert bril.type_check(prog) == []
+
+    out, count = bril.run(prog, args)
+    assert out == (BENCHMARKS / "sum-bits.out").read_text()
+    prof = (BENCHMARKS / "sum-bits.prof").read_text()
+    assert f"total_dyn_inst: {count}" == prof.strip()
+
+
+def test_error():
+    with pytest.raises(bril.BrilError):
+        bril.parse_text("@main {")
@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(about, version, author)] // keeps the cli synced with Cargo.toml
+pub struct Cli {
+    /// The program, in JSON or as text if its name ends in `.bril`. `-` reads it from stdin
+    #[clap(default_value = "-")]
+    pub file: PathBuf,
+
+    /// The function to run with symbolic arguments
+    #[clap(short, long, default_value = "main")]
+    pub function: String,
+
+    /// The most paths to explore
+    #[clap(long, default_value_t = 100)]
+    pub max_paths: usize,
+
+    /// The most instructions to run along each path
+    #[clap(long, default_value_t = 10_000)]
+    pub max_steps: usize,
+
+    /// Only print the arguments found for each path, one line per path, to be passed to `brili` or `brilirs`
+    #[clap(long)]
+    pub inputs: bool,
+
+    /// Checks paths with an SMT solver instead, run with this command and its arguments, which reads SMT-LIB 2 from its stdin, like `z3 -in`
+    #[cfg(feature = "smt")]
+    #[clap(long)]
+    pub solver: Option<String>,
+}
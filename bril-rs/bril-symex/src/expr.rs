@@ -0,0 +1,173 @@
+use std::fmt::{self, Display, Formatter};
+use std::ops::Not;
+use std::rc::Rc;
+
+use bril_rs::{Literal, ValueOps};
+
+use crate::solver::Inputs;
+
+/// A value computed from the arguments of the function being explored, which is simplified as it is built so that anything which doesn't depend on the arguments is a literal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// An integer
+    Int(i64),
+    /// A boolean
+    Bool(bool),
+    /// The argument of the given name
+    Arg(String),
+    /// An arithmetic, comparison, or logic operation of the core language on other values
+    Op(ValueOps, Rc<[Self]>),
+}
+
+impl Expr {
+    /// Whether [`Expr::op`] can build an expression for ```op```
+    #[must_use]
+    pub const fn supports(op: ValueOps) -> bool {
+        matches!(
+            op,
+            ValueOps::Add
+                | ValueOps::Sub
+                | ValueOps::Mul
+                | ValueOps::Div
+                | ValueOps::Rem
+                | ValueOps::Eq
+                | ValueOps::Lt
+                | ValueOps::Gt
+                | ValueOps::Le
+                | ValueOps::Ge
+                | ValueOps::Not
+                | ValueOps::And
+                | ValueOps::Or
+        )
+    }
+
+    /// The value of ```op``` on ```args```, folded to a literal if it doesn't depend on the arguments of the function and otherwise simplified where that is easy, like `and true x` to `x`. Division by zero is left as an operation, since it is an error rather than a value
+    /// ```
+    /// use bril_rs::ValueOps;
+    /// use bril_symex::expr::Expr;
+    ///
+    /// let x = Expr::Arg("x".to_string());
+    /// let sum = Expr::op(ValueOps::Add, vec![Expr::Int(2), Expr::Int(3)]);
+    /// assert_eq!(Expr::op(ValueOps::Mul, vec![x.clone(), sum]).to_string(), "(mul x 5)");
+    /// assert_eq!(Expr::op(ValueOps::Add, vec![x.clone(), Expr::Int(0)]), x);
+    /// ```
+    #[must_use]
+    pub fn op(op: ValueOps, args: Vec<Self>) -> Self {
+        debug_assert!(Self::supports(op), "`{op}` has no expression");
+        let literals: Option<Vec<Literal>> = args.iter().map(Self::literal).collect();
+        if let Some(value) = literals.and_then(|l| apply(op, &l)) {
+            return Self::from_literal(&value);
+        }
+        match (op, args.as_slice()) {
+            (ValueOps::Not, [Self::Op(ValueOps::Not, inner)]) => return inner[0].clone(),
+            (ValueOps::Add | ValueOps::Sub, [x, Self::Int(0)])
+            | (ValueOps::Add, [Self::Int(0), x])
+            | (ValueOps::Mul | ValueOps::Div, [x, Self::Int(1)])
+            | (ValueOps::Mul, [Self::Int(1), x])
+            | (ValueOps::And, [x, Self::Bool(true)] | [Self::Bool(true), x])
+            | (ValueOps::Or, [x, Self::Bool(false)] | [Self::Bool(false), x]) => return x.clone(),
+            (ValueOps::Mul, [_, Self::Int(0)] | [Self::Int(0), _]) => return Self::Int(0),
+            (ValueOps::And, [_, Self::Bool(false)] | [Self::Bool(false), _]) => {
+                return Self::Bool(false)
+            }
+            (ValueOps::Or, [_, Self::Bool(true)] | [Self::Bool(true), _]) => {
+                return Self::Bool(true)
+            }
+            (ValueOps::Eq | ValueOps::Le | ValueOps::Ge, [x, y]) if x == y => {
+                return Self::Bool(true)
+            }
+            (ValueOps::Lt | ValueOps::Gt, [x, y]) if x == y => return Self::Bool(false),
+            _ => {}
+        }
+        Self::Op(op, args.into())
+    }
+
+    /// The expression for an `int` or `bool` literal
+    #[must_use]
+    pub fn from_literal(literal: &Literal) -> Self {
+        match literal {
+            Literal::Int(i) => Self::Int(*i),
+            Literal::Bool(b) => Self::Bool(*b),
+            _ => unreachable!("only int and bool values are symbolic"),
+        }
+    }
+
+    /// The value of the expression if it doesn't depend on the arguments of the function
+    #[must_use]
+    pub const fn literal(&self) -> Option<Literal> {
+        match self {
+            Self::Int(i) => Some(Literal::Int(*i)),
+            Self::Bool(b) => Some(Literal::Bool(*b)),
+            Self::Arg(_) | Self::Op(..) => None,
+        }
+    }
+
+    /// The value of the expression when the function is called with ```inputs```, or [`None`] if it divides by zero or uses an argument they have no value for
+    #[must_use]
+    pub fn eval(&self, inputs: &Inputs) -> Option<Literal> {
+        match self {
+            Self::Int(_) | Self::Bool(_) => self.literal(),
+            Self::Arg(name) => inputs.get(name).cloned(),
+            Self::Op(op, args) => {
+                let args: Option<Vec<Literal>> = args.iter().map(|a| a.eval(inputs)).collect();
+                apply(*op, &args?)
+            }
+        }
+    }
+
+    /// Calls ```f``` on every integer literal in the expression
+    pub fn for_each_int(&self, f: &mut impl FnMut(i64)) {
+        match self {
+            Self::Int(i) => f(*i),
+            Self::Bool(_) | Self::Arg(_) => {}
+            Self::Op(_, args) => args.iter().for_each(|a| a.for_each_int(f)),
+        }
+    }
+}
+
+// What ```op``` gives for ```args```, which wraps around on overflow like the interpreters do, or None for division by zero
+fn apply(op: ValueOps, args: &[Literal]) -> Option<Literal> {
+    Some(match (op, args) {
+        (ValueOps::Add, [Literal::Int(x), Literal::Int(y)]) => Literal::Int(x.wrapping_add(*y)),
+        (ValueOps::Sub, [Literal::Int(x), Literal::Int(y)]) => Literal::Int(x.wrapping_sub(*y)),
+        (ValueOps::Mul, [Literal::Int(x), Literal::Int(y)]) => Literal::Int(x.wrapping_mul(*y)),
+        (ValueOps::Div | ValueOps::Rem, [Literal::Int(_), Literal::Int(0)]) => return None,
+        (ValueOps::Div, [Literal::Int(x), Literal::Int(y)]) => Literal::Int(x.wrapping_div(*y)),
+        (ValueOps::Rem, [Literal::Int(x), Literal::Int(y)]) => Literal::Int(x.wrapping_rem(*y)),
+        (ValueOps::Eq, [x, y]) => Literal::Bool(x == y),
+        (ValueOps::Lt, [Literal::Int(x), Literal::Int(y)]) => Literal::Bool(x < y),
+        (ValueOps::Gt, [Literal::Int(x), Literal::Int(y)]) => Literal::Bool(x > y),
+        (ValueOps::Le, [Literal::Int(x), Literal::Int(y)]) => Literal::Bool(x <= y),
+        (ValueOps::Ge, [Literal::Int(x), Literal::Int(y)]) => Literal::Bool(x >= y),
+        (ValueOps::Not, [Literal::Bool(x)]) => Literal::Bool(!x),
+        (ValueOps::And, [Literal::Bool(x), Literal::Bool(y)]) => Literal::Bool(*x && *y),
+        (ValueOps::Or, [Literal::Bool(x), Literal::Bool(y)]) => Literal::Bool(*x || *y),
+        _ => return None,
+    })
+}
+
+impl Not for Expr {
+    type Output = Self;
+
+    /// The negation of a boolean expression
+    fn not(self) -> Self {
+        Self::op(ValueOps::Not, vec![self])
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(i) => write!(f, "{i}"),
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Arg(name) => write!(f, "{name}"),
+            Self::Op(op, args) => {
+                write!(f, "({op}")?;
+                for arg in args.iter() {
+                    write!(f, " {arg}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
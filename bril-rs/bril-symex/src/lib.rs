@@ -0,0 +1,588 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+use bril_rs::cfg::{Cfg, CfgError};
+use bril_rs::{Argument, EffectOps, Instruction, Literal, Position, Program, Type, ValueOps};
+use thiserror::Error;
+
+use expr::Expr;
+use solver::{Answer, Inputs, Solver};
+
+#[doc(hidden)]
+pub mod cli;
+/// Values computed from symbolic arguments
+pub mod expr;
+/// Checking path conditions with an SMT solver
+#[cfg(feature = "smt")]
+pub mod smt;
+/// Deciding which paths can be taken and finding inputs for them
+pub mod solver;
+
+/// How much of a function [`explore`] runs before it gives up
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    /// The most paths to report, after which the rest are left unexplored
+    pub max_paths: usize,
+    /// The most instructions to run along each path, which stops paths that go around a loop with a symbolic bound forever
+    pub max_steps: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            max_paths: 100,
+            max_steps: 10_000,
+        }
+    }
+}
+
+/// Why a function couldn't be explored at all
+#[derive(Error, Debug)]
+pub enum SymexError {
+    /// The function to explore isn't in the program
+    #[error("no function of name `@{0}` found")]
+    MissingFunction(String),
+    /// Only `int` and `bool` values can be symbolic
+    #[error("argument `{0}` has type `{1}`, but only `int` and `bool` arguments can be symbolic")]
+    UnsupportedArgument(String, Type),
+    /// A `jmp` or `br` of the function targets a label which it doesn't have
+    #[error("@{0}: {1}")]
+    Labels(String, CfgError),
+}
+
+/// How a path through the function ends
+#[derive(Debug, Clone)]
+pub enum End {
+    /// The function returned, with a value if it has a return type
+    Returned(Option<Expr>),
+    /// The path runs into an error which would stop the interpreter, like dividing by zero
+    Error(String, Option<Position>),
+    /// The path reaches an instruction which can't be run on symbolic values, like anything from the memory extension
+    Unsupported(String, Option<Position>),
+    /// The path ran [`Options::max_steps`] instructions without ending
+    StepLimit,
+}
+
+impl Display for End {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Matches how brilirs reports where an error is
+        let at = |f: &mut Formatter<'_>, pos: &Option<Position>| {
+            pos.map_or(Ok(()), |pos| {
+                write!(f, " at Line {}, Column {}", pos.row, pos.col)
+            })
+        };
+        match self {
+            Self::Returned(None) => write!(f, "returns"),
+            Self::Returned(Some(value)) => write!(f, "returns {value}"),
+            Self::Error(message, pos) => {
+                write!(f, "error")?;
+                at(f, pos)?;
+                write!(f, ": {message}")
+            }
+            Self::Unsupported(what, pos) => {
+                write!(f, "stops")?;
+                at(f, pos)?;
+                write!(f, ": {what} can't be run symbolically")
+            }
+            Self::StepLimit => write!(f, "stops: too many steps"),
+        }
+    }
+}
+
+/// One path through the function, which is taken whenever every one of its conditions holds
+#[derive(Debug, Clone)]
+pub struct Path {
+    /// The conditions of the branches taken and the divisions which didn't divide by zero, in order
+    pub conditions: Vec<Expr>,
+    /// Arguments which take this path, if the solver found some
+    pub inputs: Option<Inputs>,
+    /// The arguments of each `print` along the path
+    pub prints: Vec<Vec<Expr>>,
+    /// How the path ends
+    pub end: End,
+}
+
+impl Path {
+    /// Whether the path ends in an error
+    #[must_use]
+    pub const fn is_error(&self) -> bool {
+        matches!(self.end, End::Error(..))
+    }
+}
+
+/// Every path [`explore`] found through a function
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// The paths in the order they were explored, which takes the true side of each branch first
+    pub paths: Vec<Path>,
+    /// How many times the solver showed that one side of a branch or division could never be taken
+    pub pruned: usize,
+    /// Whether there were paths left unexplored after [`Options::max_paths`]
+    pub truncated: bool,
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, path) in self.paths.iter().enumerate() {
+            writeln!(f, "path {}:", i + 1)?;
+            match &path.inputs {
+                Some(inputs) => writeln!(f, "  inputs: {inputs}")?,
+                None => writeln!(f, "  inputs: unknown")?,
+            }
+            for condition in &path.conditions {
+                writeln!(f, "  if {condition}")?;
+            }
+            for print in &path.prints {
+                let args: Vec<String> = print.iter().map(ToString::to_string).collect();
+                writeln!(f, "  print {}", args.join(" "))?;
+            }
+            writeln!(f, "  {}", path.end)?;
+        }
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        write!(f, "{} path{}", self.paths.len(), plural(self.paths.len()))?;
+        if self.pruned > 0 {
+            write!(
+                f,
+                ", {} infeasible branch{} pruned",
+                self.pruned,
+                if self.pruned == 1 { "" } else { "es" }
+            )?;
+        }
+        if self.truncated {
+            write!(f, ", more left unexplored")?;
+        }
+        writeln!(f)
+    }
+}
+
+// A call being run along a path
+#[derive(Debug, Clone)]
+struct Frame {
+    func: usize,
+    block: usize,
+    instr: usize,
+    // The block which jumped to this one, for phis
+    prev: Option<usize>,
+    env: HashMap<String, Expr>,
+    // Where the caller stores what this call returns
+    dest: Option<String>,
+}
+
+// Everything about a path which hasn't ended yet
+#[derive(Debug, Clone)]
+struct State {
+    frames: Vec<Frame>,
+    conditions: Vec<Expr>,
+    inputs: Option<Inputs>,
+    prints: Vec<Vec<Expr>>,
+    steps: usize,
+}
+
+struct Explorer<'a> {
+    args: &'a [Argument],
+    names: HashMap<&'a str, usize>,
+    params: Vec<&'a [Argument]>,
+    cfgs: Vec<Cfg>,
+    labels: Vec<HashMap<String, usize>>,
+    options: &'a Options,
+    solver: &'a mut dyn Solver,
+    report: Report,
+}
+
+/// Runs ```func``` from ```prog``` with symbolic values for its arguments, and gives back each path through it along with what it prints and how it ends.
+///
+/// Paths fork at every branch which depends on the arguments and at every division by them. ```solver``` prunes the paths which can never be taken and finds inputs for the others. Calls to other functions are followed, but only the core language, `id`, and `phi` can be run on symbolic values, and a path stops when it reaches anything else
+/// ```
+/// use bril_symex::{explore, Options};
+/// use bril_symex::solver::Search;
+///
+/// let text = "@main(x: int) { zero: int = const 0; neg: bool = lt x zero; br neg .a .b; .a: print zero; .b: ret; }";
+/// let prog = bril2json::parse_abstract_program_from_str(text, false).unwrap();
+/// let prog: bril_rs::Program = prog.try_into().unwrap();
+/// let report = explore(&prog, "main", &Options::default(), &mut Search::new(&prog)).unwrap();
+/// assert_eq!(report.paths.len(), 2);
+/// assert_eq!(report.paths[0].inputs.as_ref().unwrap().args(), "-1");
+/// assert_eq!(report.paths[0].prints[0][0].to_string(), "0");
+/// ```
+/// # Errors
+/// When ```func``` isn't in ```prog```, takes an argument which isn't an `int` or a `bool`, or a function jumps to a label it doesn't have
+pub fn explore(
+    prog: &Program,
+    func: &str,
+    options: &Options,
+    solver: &mut dyn Solver,
+) -> Result<Report, SymexError> {
+    let entry = prog
+        .functions
+        .iter()
+        .position(|f| f.name == func)
+        .ok_or_else(|| SymexError::MissingFunction(func.to_string()))?;
+    let args = &prog.functions[entry].args;
+    if let Some(arg) = args
+        .iter()
+        .find(|a| !matches!(a.arg_type, Type::Int | Type::Bool))
+    {
+        return Err(SymexError::UnsupportedArgument(
+            arg.name.clone(),
+            arg.arg_type.clone(),
+        ));
+    }
+    let cfgs: Vec<Cfg> = prog
+        .functions
+        .iter()
+        .map(|f| Cfg::from_function(f).map_err(|e| SymexError::Labels(f.name.clone(), e)))
+        .collect::<Result<_, _>>()?;
+    let mut explorer = Explorer {
+        args,
+        names: prog
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.name.as_str(), i))
+            .collect(),
+        params: prog.functions.iter().map(|f| f.args.as_slice()).collect(),
+        labels: cfgs
+            .iter()
+            .map(|cfg| {
+                cfg.blocks
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| (b.label.clone(), i))
+                    .collect()
+            })
+            .collect(),
+        cfgs,
+        options,
+        solver,
+        report: Report {
+            paths: Vec::new(),
+            pruned: 0,
+            truncated: false,
+        },
+    };
+    let env = args
+        .iter()
+        .map(|a| (a.name.clone(), Expr::Arg(a.name.clone())))
+        .collect();
+    let mut stack = vec![State {
+        frames: vec![Frame {
+            func: entry,
+            block: 0,
+            instr: 0,
+            prev: None,
+            env,
+            dest: None,
+        }],
+        conditions: Vec::new(),
+        inputs: explorer.solve(&[]),
+        prints: Vec::new(),
+        steps: 0,
+    }];
+    while let Some(mut state) = stack.pop() {
+        if explorer.report.paths.len() >= options.max_paths {
+            explorer.report.truncated = true;
+            break;
+        }
+        if let Some(end) = explorer.run(&mut state, &mut stack) {
+            explorer.finish(state, end);
+        }
+    }
+    Ok(explorer.report)
+}
+
+impl Explorer<'_> {
+    // Inputs which satisfy ```conditions```, if the solver finds some
+    fn solve(&mut self, conditions: &[Expr]) -> Option<Inputs> {
+        match self.solver.check(self.args, conditions) {
+            Answer::Sat(inputs) => Some(inputs),
+            Answer::Unsat | Answer::Unknown => None,
+        }
+    }
+
+    // The state of the path which carries on from ```state``` when ```cond``` holds as well, or None if the solver shows that it can't
+    fn assume(&mut self, state: &State, cond: &Expr) -> Option<State> {
+        let mut conditions = state.conditions.clone();
+        conditions.push(cond.clone());
+        // Inputs which got this far often keep going, which saves asking the solver
+        let inputs = match &state.inputs {
+            Some(inputs) if inputs.satisfy(std::slice::from_ref(cond)) => Some(inputs.clone()),
+            _ => match self.solver.check(self.args, &conditions) {
+                Answer::Sat(inputs) => Some(inputs),
+                Answer::Unsat => {
+                    self.report.pruned += 1;
+                    return None;
+                }
+                Answer::Unknown => None,
+            },
+        };
+        Some(State {
+            conditions,
+            inputs,
+            ..state.clone()
+        })
+    }
+
+    fn finish(&mut self, state: State, end: End) {
+        self.report.paths.push(Path {
+            conditions: state.conditions,
+            inputs: state.inputs,
+            prints: state.prints,
+            end,
+        });
+    }
+
+    // Runs ```state``` until its path ends, pushing the paths it forks off onto ```stack```. None means the path turned out to be infeasible
+    fn run(&mut self, state: &mut State, stack: &mut Vec<State>) -> Option<End> {
+        loop {
+            if state.steps >= self.options.max_steps {
+                return Some(End::StepLimit);
+            }
+            state.steps += 1;
+            let frame = state.frames.last_mut()?;
+            let Some(block) = self.cfgs[frame.func].blocks.get(frame.block) else {
+                // A function with nothing in it returns straight away
+                if let Err(end) = Self::ret(state, None) {
+                    return end;
+                }
+                continue;
+            };
+            let instr = block.instrs[frame.instr].clone();
+            frame.instr += 1;
+            let pos = instr.get_pos();
+            let end = match instr {
+                Instruction::Constant {
+                    dest,
+                    const_type: Type::Int | Type::Bool,
+                    value,
+                    ..
+                } => {
+                    frame.env.insert(dest, Expr::from_literal(&value));
+                    continue;
+                }
+                Instruction::Constant { const_type, .. } => {
+                    End::Unsupported(format!("a `{const_type}` constant"), pos)
+                }
+                Instruction::Value {
+                    op,
+                    dest,
+                    args,
+                    funcs,
+                    labels,
+                    ..
+                } => match self.value(state, op, &dest, &args, &funcs, &labels, pos) {
+                    Ok(Some(value)) => {
+                        state.frames.last_mut()?.env.insert(dest, value);
+                        continue;
+                    }
+                    Ok(None) => continue,
+                    Err(end) => end?,
+                },
+                Instruction::Effect {
+                    op,
+                    args,
+                    funcs,
+                    labels,
+                    ..
+                } => match self.effect(state, stack, op, &args, &funcs, &labels, pos) {
+                    Ok(()) => continue,
+                    Err(end) => end?,
+                },
+            };
+            return Some(end);
+        }
+    }
+
+    // The value of ```name``` in the innermost call
+    fn get(state: &State, name: &str, pos: Option<Position>) -> Result<Expr, Option<End>> {
+        state
+            .frames
+            .last()
+            .and_then(|f| f.env.get(name))
+            .cloned()
+            .ok_or_else(|| Some(End::Error(format!("undefined variable `{name}`"), pos)))
+    }
+
+    fn get_all(
+        state: &State,
+        names: &[String],
+        pos: Option<Position>,
+    ) -> Result<Vec<Expr>, Option<End>> {
+        names.iter().map(|n| Self::get(state, n, pos)).collect()
+    }
+
+    // Runs a value operation for ```dest```, giving back its value, or None for a call which has started running and stores its result when it returns. The error is how the path ends, or None if it is infeasible
+    #[allow(clippy::too_many_arguments)]
+    fn value(
+        &mut self,
+        state: &mut State,
+        op: ValueOps,
+        dest: &str,
+        args: &[String],
+        funcs: &[String],
+        labels: &[String],
+        pos: Option<Position>,
+    ) -> Result<Option<Expr>, Option<End>> {
+        match op {
+            ValueOps::Id => Self::get(state, &args[0], pos).map(Some),
+            ValueOps::Call => {
+                self.call(state, &funcs[0], args, Some(dest), pos)?;
+                Ok(None)
+            }
+            ValueOps::Phi => {
+                let frame = state.frames.last().ok_or(None)?;
+                let prev = frame.prev.map(|b| &self.cfgs[frame.func].blocks[b].label);
+                let arg = labels
+                    .iter()
+                    .position(|l| Some(l) == prev)
+                    .and_then(|i| args.get(i))
+                    .ok_or_else(|| {
+                        Some(End::Error(
+                            "`phi` has no value for the block it came from".to_string(),
+                            pos,
+                        ))
+                    })?;
+                Self::get(state, arg, pos).map(Some)
+            }
+            ValueOps::Div | ValueOps::Rem => {
+                let values = Self::get_all(state, args, pos)?;
+                let zero = Expr::op(ValueOps::Eq, vec![values[1].clone(), Expr::Int(0)]);
+                let error = || End::Error("division by zero".to_string(), pos);
+                match zero.literal() {
+                    Some(Literal::Bool(true)) => return Err(Some(error())),
+                    Some(_) => {}
+                    None => {
+                        let divides = self.assume(state, &!zero.clone());
+                        // The path which divides by zero ends straight away
+                        if let Some(by_zero) = self.assume(state, &zero) {
+                            self.finish(by_zero, error());
+                        }
+                        *state = divides.ok_or(None)?;
+                    }
+                }
+                Ok(Some(Expr::op(op, values)))
+            }
+            op if Expr::supports(op) => Ok(Some(Expr::op(op, Self::get_all(state, args, pos)?))),
+            op => Err(Some(End::Unsupported(format!("`{op}`"), pos))),
+        }
+    }
+
+    // Runs an effect operation. The error is how the path ends, or None if it is infeasible
+    #[allow(clippy::too_many_arguments)]
+    fn effect(
+        &mut self,
+        state: &mut State,
+        stack: &mut Vec<State>,
+        op: EffectOps,
+        args: &[String],
+        funcs: &[String],
+        labels: &[String],
+        pos: Option<Position>,
+    ) -> Result<(), Option<End>> {
+        match op {
+            EffectOps::Nop => Ok(()),
+            EffectOps::Print => {
+                let values = Self::get_all(state, args, pos)?;
+                state.prints.push(values);
+                Ok(())
+            }
+            EffectOps::Jump => {
+                self.jump(state, &labels[0]);
+                Ok(())
+            }
+            EffectOps::Branch => {
+                let cond = Self::get(state, &args[0], pos)?;
+                if let Some(Literal::Bool(b)) = cond.literal() {
+                    self.jump(state, &labels[usize::from(!b)]);
+                    return Ok(());
+                }
+                let sides = [self.assume(state, &cond), self.assume(state, &!cond)];
+                // The first side which can be taken carries on in ```state```, and the other is explored after it
+                let mut sides = sides.into_iter().zip(labels).filter_map(|(side, label)| {
+                    side.map(|mut side| {
+                        self.jump(&mut side, label);
+                        side
+                    })
+                });
+                *state = sides.next().ok_or(None)?;
+                stack.extend(sides);
+                Ok(())
+            }
+            EffectOps::Call => self.call(state, &funcs[0], args, None, pos),
+            EffectOps::Return => {
+                let value = args.first().map(|a| Self::get(state, a, pos)).transpose()?;
+                Self::ret(state, value)
+            }
+            op => Err(Some(End::Unsupported(format!("`{op}`"), pos))),
+        }
+    }
+
+    // Returns ```value``` from the innermost call, to its caller or out of the function being explored
+    fn ret(state: &mut State, value: Option<Expr>) -> Result<(), Option<End>> {
+        state.frames.pop();
+        match state.frames.last_mut() {
+            None => Err(Some(End::Returned(value))),
+            Some(caller) => {
+                if let (Some(dest), Some(value)) = (caller.dest.take(), value) {
+                    caller.env.insert(dest, value);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn jump(&self, state: &mut State, label: &str) {
+        if let Some(frame) = state.frames.last_mut() {
+            frame.prev = Some(frame.block);
+            frame.block = self.labels[frame.func][label];
+            frame.instr = 0;
+        }
+    }
+
+    // Starts a call of ```func``` on ```args``` from the innermost call, which stores what it returns in ```dest```
+    fn call(
+        &self,
+        state: &mut State,
+        func: &str,
+        args: &[String],
+        dest: Option<&str>,
+        pos: Option<Position>,
+    ) -> Result<(), Option<End>> {
+        let callee = *self.names.get(func).ok_or_else(|| {
+            Some(End::Error(
+                format!("no function of name `@{func}` found"),
+                pos,
+            ))
+        })?;
+        let params = self.params[callee];
+        if params.len() != args.len() {
+            return Err(Some(End::Error(
+                format!(
+                    "`@{func}` takes {} arguments but was given {}",
+                    params.len(),
+                    args.len()
+                ),
+                pos,
+            )));
+        }
+        let values = Self::get_all(state, args, pos)?;
+        let env = params.iter().map(|p| p.name.clone()).zip(values).collect();
+        if let Some(caller) = state.frames.last_mut() {
+            caller.dest = dest.map(str::to_string);
+        }
+        state.frames.push(Frame {
+            func: callee,
+            block: 0,
+            instr: 0,
+            prev: None,
+            env,
+            dest: None,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use std::io::Read;
+use std::path::Path;
+
+use bril_rs::Program;
+use bril_symex::cli::Cli;
+use bril_symex::solver::{Search, Solver};
+use bril_symex::Options;
+use clap::Parser;
+
+// Reads the program at ```path```, or from stdin if it is `-`, as text if the name ends in `.bril` and as JSON otherwise
+fn read_program(path: &Path) -> Result<Program, String> {
+    let mut input = String::new();
+    if path == Path::new("-") {
+        std::io::stdin().read_to_string(&mut input)
+    } else {
+        std::fs::File::open(path).and_then(|mut f| f.read_to_string(&mut input))
+    }
+    .map_err(|e| e.to_string())?;
+    if path.extension().is_some_and(|e| e == "bril") {
+        let prog =
+            bril2json::parse_abstract_program_from_str(&input, true).map_err(|e| e.to_string())?;
+        Program::try_from(prog).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str(&input).map_err(|e| e.to_string())
+    }
+}
+
+fn main() {
+    let args = Cli::parse();
+    // 1 is for a path which ends in an error, so that scripts can tell it apart from this
+    let fail = |e: &dyn std::fmt::Display| -> ! {
+        eprintln!("error: {e}");
+        std::process::exit(2)
+    };
+    let prog = read_program(&args.file).unwrap_or_else(|e| fail(&e));
+    let options = Options {
+        max_paths: args.max_paths,
+        max_steps: args.max_steps,
+    };
+
+    let mut search = Search::new(&prog);
+    #[cfg(feature = "smt")]
+    let mut smt = args
+        .solver
+        .as_ref()
+        .map(|s| bril_symex::smt::Smt::new(s.split_whitespace().map(str::to_string)));
+    #[cfg(feature = "smt")]
+    let solver: &mut dyn Solver = match smt.as_mut() {
+        Some(smt) => smt,
+        None => &mut search,
+    };
+    #[cfg(not(feature = "smt"))]
+    let solver: &mut dyn Solver = &mut search;
+
+    let report =
+        bril_symex::explore(&prog, &args.function, &options, solver).unwrap_or_else(|e| fail(&e));
+    #[cfg(feature = "smt")]
+    if let Some(e) = smt.as_ref().and_then(|s| s.error()) {
+        eprintln!("warning: {e}");
+    }
+    if args.inputs {
+        let mut seen = std::collections::HashSet::new();
+        for inputs in report.paths.iter().filter_map(|p| p.inputs.as_ref()) {
+            let line = inputs.args();
+            if seen.insert(line.clone()) {
+                println!("{line}");
+            }
+        }
+    } else {
+        print!("{report}");
+    }
+    if report.paths.iter().any(bril_symex::Path::is_error) {
+        std::process::exit(1);
+    }
+}
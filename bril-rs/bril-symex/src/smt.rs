@@ -0,0 +1,222 @@
+use std::fmt::Write as _;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use bril_rs::{Argument, Literal, Type, ValueOps};
+
+use crate::expr::Expr;
+use crate::solver::{Answer, Inputs, Solver};
+
+/// A [`Solver`] which asks an SMT solver, run as a subprocess reading SMT-LIB 2 from its stdin, so that it can tell when a path can never be taken and find inputs for paths that [`crate::solver::Search`] can't. Integers are 64-bit bitvectors, so arithmetic wraps around like it does in the interpreters
+#[derive(Debug, Clone)]
+pub struct Smt {
+    command: Vec<String>,
+    error: Option<String>,
+}
+
+impl Default for Smt {
+    /// Runs `z3 -in`
+    fn default() -> Self {
+        Self::new(["z3".to_string(), "-in".to_string()])
+    }
+}
+
+impl Smt {
+    /// Runs the program and arguments of ```command```, like `cvc5 --lang smt2`
+    #[must_use]
+    pub fn new(command: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            command: command.into_iter().collect(),
+            error: None,
+        }
+    }
+
+    /// Why the solver couldn't be run or what it said instead of an answer, the first time that happened. Every condition after that is [`Answer::Unknown`]
+    #[must_use]
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    // Runs the solver on ```script``` and gives back what it printed
+    fn run(&self, script: &str) -> Result<String, String> {
+        let (program, args) = self
+            .command
+            .split_first()
+            .ok_or_else(|| "no solver command was given".to_string())?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("couldn't run `{program}`: {e}"))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(script.as_bytes())
+                .map_err(|e| format!("couldn't write to `{program}`: {e}"))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("couldn't read from `{program}`: {e}"))?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl Solver for Smt {
+    fn check(&mut self, args: &[Argument], conditions: &[Expr]) -> Answer {
+        if self.error.is_some() {
+            return Answer::Unknown;
+        }
+        let Some(script) = query(args, conditions) else {
+            return Answer::Unknown;
+        };
+        match self.run(&script).and_then(|out| answer(args, &out)) {
+            Ok(answer) => answer,
+            Err(e) => {
+                self.error = Some(e);
+                Answer::Unknown
+            }
+        }
+    }
+}
+
+/// The SMT-LIB 2 script asking whether ```conditions``` can all hold for some values of ```args```, and for those values if so, or [`None`] if an argument isn't an `int` or `bool`
+/// ```
+/// use bril_rs::{Argument, Type, ValueOps};
+/// use bril_symex::expr::Expr;
+/// use bril_symex::smt::query;
+///
+/// let args = [Argument { name: "x".to_string(), arg_type: Type::Int }];
+/// let positive = Expr::op(ValueOps::Gt, vec![Expr::Arg("x".to_string()), Expr::Int(0)]);
+/// let script = query(&args, &[positive]).unwrap();
+/// assert!(script.contains("(declare-const |x| (_ BitVec 64))"));
+/// assert!(script.contains("(assert (bvsgt |x| #x0000000000000000))"));
+/// ```
+#[must_use]
+pub fn query(args: &[Argument], conditions: &[Expr]) -> Option<String> {
+    let mut script = String::from("(set-option :produce-models true)\n(set-logic QF_BV)\n");
+    for arg in args {
+        let sort = match arg.arg_type {
+            Type::Int => "(_ BitVec 64)",
+            Type::Bool => "Bool",
+            _ => return None,
+        };
+        let _ = writeln!(script, "(declare-const {} {sort})", symbol(&arg.name));
+    }
+    for condition in conditions {
+        let _ = writeln!(script, "(assert {})", term(condition));
+    }
+    script.push_str("(check-sat)\n");
+    if !args.is_empty() {
+        let names: Vec<String> = args.iter().map(|a| symbol(&a.name)).collect();
+        let _ = writeln!(script, "(get-value ({}))", names.join(" "));
+    }
+    script.push_str("(exit)\n");
+    Some(script)
+}
+
+// Bril names can have characters which SMT-LIB symbols can't, so they are all quoted
+fn symbol(name: &str) -> String {
+    format!("|{name}|")
+}
+
+fn term(expr: &Expr) -> String {
+    match expr {
+        Expr::Int(i) => format!("#x{i:016x}"),
+        Expr::Bool(b) => b.to_string(),
+        Expr::Arg(name) => symbol(name),
+        Expr::Op(op, args) => {
+            let name = match op {
+                ValueOps::Add => "bvadd",
+                ValueOps::Sub => "bvsub",
+                ValueOps::Mul => "bvmul",
+                // These both truncate towards zero, like Rust's / and %
+                ValueOps::Div => "bvsdiv",
+                ValueOps::Rem => "bvsrem",
+                ValueOps::Eq => "=",
+                ValueOps::Lt => "bvslt",
+                ValueOps::Gt => "bvsgt",
+                ValueOps::Le => "bvsle",
+                ValueOps::Ge => "bvsge",
+                ValueOps::Not => "not",
+                ValueOps::And => "and",
+                ValueOps::Or => "or",
+                _ => unreachable!("only operations which Expr::supports are built"),
+            };
+            let args: Vec<String> = args.iter().map(term).collect();
+            format!("({name} {})", args.join(" "))
+        }
+    }
+}
+
+// Reads the answer to a query from ```out```, which is `sat` followed by the values of ```args```, or `unsat` or `unknown`
+fn answer(args: &[Argument], out: &str) -> Result<Answer, String> {
+    let mut lines = out.trim_start().splitn(2, '\n');
+    match lines.next().map(str::trim) {
+        Some("sat") => {}
+        Some("unsat") => return Ok(Answer::Unsat),
+        Some("unknown") => return Ok(Answer::Unknown),
+        _ => return Err(format!("the solver said `{}`", out.trim())),
+    }
+    let tokens = tokens(lines.next().unwrap_or_default());
+    let inputs = args
+        .iter()
+        .map(|arg| {
+            let symbol = symbol(&arg.name);
+            let at = tokens
+                .iter()
+                .position(|t| *t == symbol || *t == arg.name)
+                .ok_or_else(|| format!("the solver gave no value for `{}`", arg.name))?;
+            let value = value(&tokens[at + 1..])
+                .ok_or_else(|| format!("couldn't read the value of `{}`", arg.name))?;
+            Ok((arg.name.clone(), value))
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(Answer::Sat(Inputs(inputs)))
+}
+
+// Splits an s-expression into parentheses and atoms, keeping quoted symbols together
+fn tokens(s: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | ')' => tokens.push(c.to_string()),
+            '|' => {
+                let mut token = String::from('|');
+                for c in chars.by_ref() {
+                    token.push(c);
+                    if c == '|' {
+                        break;
+                    }
+                }
+                tokens.push(token);
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut token = String::from(c);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '(' && *c != ')')
+                {
+                    token.push(c);
+                }
+                tokens.push(token);
+            }
+        }
+    }
+    tokens
+}
+
+// The value at the start of ```tokens```, written as `true`, `false`, `#x…`, `#b…`, or `(_ bvN 64)`
+fn value(tokens: &[String]) -> Option<Literal> {
+    let bits = match tokens.first()?.as_str() {
+        "true" => return Some(Literal::Bool(true)),
+        "false" => return Some(Literal::Bool(false)),
+        "(" => tokens.get(2)?.strip_prefix("bv")?.parse::<u64>().ok()?,
+        t if t.starts_with("#x") => u64::from_str_radix(&t[2..], 16).ok()?,
+        t if t.starts_with("#b") => u64::from_str_radix(&t[2..], 2).ok()?,
+        _ => return None,
+    };
+    // The bits of a two's complement integer
+    #[allow(clippy::cast_possible_wrap)]
+    Some(Literal::Int(bits as i64))
+}
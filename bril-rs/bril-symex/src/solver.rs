@@ -0,0 +1,166 @@
+use std::fmt::{self, Display, Formatter};
+
+use bril_rs::{Argument, Code, Instruction, Literal, Program, Type};
+
+use crate::expr::Expr;
+
+/// Values for the arguments of a function, in the order it takes them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inputs(pub Vec<(String, Literal)>);
+
+impl Inputs {
+    /// The value of the argument ```name```
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Literal> {
+        self.0.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    /// Whether every one of ```conditions``` is true for these inputs
+    #[must_use]
+    pub fn satisfy(&self, conditions: &[Expr]) -> bool {
+        conditions
+            .iter()
+            .all(|c| c.eval(self) == Some(Literal::Bool(true)))
+    }
+
+    /// The values alone, separated by spaces like the arguments of `brili` and `brilirs`
+    #[must_use]
+    pub fn args(&self) -> String {
+        let values: Vec<String> = self.0.iter().map(|(_, v)| v.to_string()).collect();
+        values.join(" ")
+    }
+}
+
+impl Display for Inputs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let values: Vec<String> = self.0.iter().map(|(n, v)| format!("{n} = {v}")).collect();
+        write!(f, "{}", values.join(", "))
+    }
+}
+
+/// What a [`Solver`] found out about a path condition
+#[derive(Debug, Clone, PartialEq)]
+pub enum Answer {
+    /// The condition holds for these inputs
+    Sat(Inputs),
+    /// The condition holds for no inputs, so the path can never be taken
+    Unsat,
+    /// The solver couldn't tell either way
+    Unknown,
+}
+
+/// Decides whether a path through a function can be taken, by whether there are values of its arguments for which every condition along the path holds
+pub trait Solver {
+    /// Whether all of ```conditions```, which are boolean expressions over ```args```, can hold at once
+    fn check(&mut self, args: &[Argument], conditions: &[Expr]) -> Answer;
+}
+
+/// A [`Solver`] which needs nothing else installed, and tries combinations of the values most likely to matter: small numbers, and the constants in the program and in the path condition along with their neighbours. It can only answer [`Answer::Unsat`] when every argument is a `bool`, since then it tries them all
+#[derive(Debug, Clone)]
+pub struct Search {
+    candidates: Vec<i64>,
+    budget: usize,
+}
+
+impl Search {
+    /// The most combinations of values [`Search::new`] tries for each path condition
+    pub const DEFAULT_BUDGET: usize = 100_000;
+
+    /// A search through the constants of ```prog``` and the numbers from -2 to 2
+    #[must_use]
+    pub fn new(prog: &Program) -> Self {
+        let mut candidates = vec![0, 1, -1, 2, -2];
+        for func in &prog.functions {
+            for code in &func.instrs {
+                if let Code::Instruction(Instruction::Constant {
+                    value: Literal::Int(i),
+                    ..
+                }) = code
+                {
+                    add_neighbours(&mut candidates, *i);
+                }
+            }
+        }
+        Self {
+            candidates: sorted(candidates),
+            budget: Self::DEFAULT_BUDGET,
+        }
+    }
+
+    /// Tries at most ```budget``` combinations of values for each path condition
+    #[must_use]
+    pub const fn budget(mut self, budget: usize) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    // The values to try for an argument of type ```t```, where ```ints``` are the integers to try
+    fn values(ints: &[i64], t: &Type) -> Option<Vec<Literal>> {
+        match t {
+            Type::Int => Some(ints.iter().map(|i| Literal::Int(*i)).collect()),
+            Type::Bool => Some(vec![Literal::Bool(false), Literal::Bool(true)]),
+            _ => None,
+        }
+    }
+}
+
+impl Solver for Search {
+    fn check(&mut self, args: &[Argument], conditions: &[Expr]) -> Answer {
+        // Values computed along the path, like the bound of a loop after going around it a few times, aren't constants of the program
+        let mut ints = self.candidates.clone();
+        for condition in conditions {
+            condition.for_each_int(&mut |i| add_neighbours(&mut ints, i));
+        }
+        let ints = sorted(ints);
+        let Some(values) = args
+            .iter()
+            .map(|a| Self::values(&ints, &a.arg_type))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return Answer::Unknown;
+        };
+        if values.iter().any(Vec::is_empty) {
+            return Answer::Unknown;
+        }
+        // Counts through every combination of values like an odometer
+        let mut picks = vec![0; args.len()];
+        for _ in 0..self.budget {
+            let inputs = Inputs(
+                args.iter()
+                    .zip(&values)
+                    .zip(&picks)
+                    .map(|((a, v), p)| (a.name.clone(), v[*p].clone()))
+                    .collect(),
+            );
+            if inputs.satisfy(conditions) {
+                return Answer::Sat(inputs);
+            }
+            let Some(digit) = (0..picks.len()).find(|d| picks[*d] + 1 < values[*d].len()) else {
+                // That was the last combination, so nothing satisfies the conditions if these were all of the values
+                return if args.iter().all(|a| a.arg_type == Type::Bool) {
+                    Answer::Unsat
+                } else {
+                    Answer::Unknown
+                };
+            };
+            picks[digit] += 1;
+            picks[..digit].iter_mut().for_each(|p| *p = 0);
+        }
+        Answer::Unknown
+    }
+}
+
+fn add_neighbours(candidates: &mut Vec<i64>, i: i64) {
+    candidates.extend(
+        [i.checked_sub(1), Some(i), i.checked_add(1)]
+            .iter()
+            .flatten(),
+    );
+}
+
+// Values closer to zero come first, so they make the inputs which are found
+fn sorted(mut candidates: Vec<i64>) -> Vec<i64> {
+    candidates.sort_by_key(|i| (i.unsigned_abs(), *i < 0));
+    candidates.dedup();
+    candidates
+}
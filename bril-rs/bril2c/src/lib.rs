@@ -0,0 +1,477 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use bril_rs::dataflow::constant_literal;
+use bril_rs::{Code, EffectOps, Function, Instruction, Literal, Program, Type, ValueOps};
+use thiserror::Error;
+
+#[doc(hidden)]
+pub mod cli;
+
+/// The C source of the runtime which compiled programs call into to print values, read the arguments of `main`, do arithmetic which wraps around, and allocate memory. It only depends on the C99 standard library, and is included at the top of every compiled program.
+pub const RUNTIME: &str = include_str!("runtime.c");
+
+/// The reasons a program can't be compiled, each naming the function it happened in
+// Having the #[error(...)] for all variants derives the Display trait as well
+#[derive(Error, Debug)]
+pub enum CompileError {
+    /// @{0}: `{1}` is not supported
+    #[error("@{0}: `{1}` is not supported")]
+    UnsupportedOp(String, String),
+
+    /// @{0}: values of type `{1}` are not supported
+    #[error("@{0}: values of type `{1}` are not supported")]
+    UnsupportedType(String, Type),
+
+    /// @{0}: values of type `{1}` can't be printed
+    #[error("@{0}: values of type `{1}` can't be printed")]
+    UnprintableType(String, Type),
+
+    /// @{0}: `{1}` is used but never assigned
+    #[error("@{0}: `{1}` is used but never assigned")]
+    UndefinedVariable(String, String),
+
+    /// @{0}: `{1}` is assigned both `{2}` and `{3}`
+    #[error("@{0}: `{1}` is assigned both `{2}` and `{3}`")]
+    ConflictingTypes(String, String, Type, Type),
+
+    /// @{0}: call to unknown function `@{1}`
+    #[error("@{0}: call to unknown function `@{1}`")]
+    UnknownFunction(String, String),
+
+    /// Imports have to be linked into the program before it is compiled
+    #[error("imports have to be linked into the program before it is compiled")]
+    Imports,
+}
+
+/// Compiles ```prog``` to a C99 program, with a C function for each Bril function and [`RUNTIME`] at the top.
+///
+/// The program has a C `main` which reads the arguments of the Bril `main` from the command line, if the program has one, so it can be built with any C compiler, like `cc -O2 prog.c -o prog -lm`.
+///
+/// # Errors
+/// Fails on instructions and types outside of the core language and the memory and float extensions, on variables which are never assigned, and on imports
+pub fn compile(prog: &Program) -> Result<String, CompileError> {
+    if !prog.imports.is_empty() {
+        return Err(CompileError::Imports);
+    }
+    let functions: HashMap<&str, &Function> = prog
+        .functions
+        .iter()
+        .map(|f| (f.name.as_str(), f))
+        .collect();
+
+    let mut out = String::from(RUNTIME);
+    out.push('\n');
+    // Every function is declared up front so that functions can call those defined after them
+    for func in &prog.functions {
+        writeln!(out, "{};", signature(func)?).unwrap();
+    }
+    for func in &prog.functions {
+        out.push('\n');
+        out.push_str(&FunctionCompiler::new(func, &functions)?.compile()?);
+    }
+    if let Some(main) = functions.get("main") {
+        out.push('\n');
+        out.push_str(&entry_point(main)?);
+    }
+    Ok(out)
+}
+
+fn c_type(func: &Function, ty: &Type) -> Result<String, CompileError> {
+    match ty {
+        Type::Int => Ok("int64_t".to_string()),
+        Type::Bool => Ok("bool".to_string()),
+        Type::Float => Ok("double".to_string()),
+        Type::Pointer(pointee) => Ok(format!("{}*", c_type(func, pointee)?)),
+        Type::Char | Type::Str | Type::Thread | Type::Tuple(_) => {
+            Err(CompileError::UnsupportedType(func.name.clone(), ty.clone()))
+        }
+    }
+}
+
+fn return_type(func: &Function) -> Result<String, CompileError> {
+    func.return_type
+        .as_ref()
+        .map_or_else(|| Ok("void".to_string()), |ty| c_type(func, ty))
+}
+
+// The C declaration of ```func```, without the body
+fn signature(func: &Function) -> Result<String, CompileError> {
+    let params = func
+        .args
+        .iter()
+        .map(|a| Ok(format!("{} {}", c_type(func, &a.arg_type)?, var(&a.name))))
+        .collect::<Result<Vec<_>, _>>()?;
+    let params = if params.is_empty() {
+        "void".to_string()
+    } else {
+        params.join(", ")
+    };
+    Ok(format!(
+        "{} {}({params})",
+        return_type(func)?,
+        function_name(&func.name)
+    ))
+}
+
+// Bril names can have characters which C identifiers can't, like `.`. Letters and digits are kept, `_` is doubled, and every other byte is `_x` and its hex, which is never the same C name for two Bril names. Starting with the kind of name and `_` keeps variables, functions, and labels apart from each other, from C keywords, and from the runtime
+fn mangle(kind: char, name: &str) -> String {
+    let mut out = format!("{kind}_");
+    for b in name.bytes() {
+        match b {
+            b'_' => out.push_str("__"),
+            b if b.is_ascii_alphanumeric() => out.push(char::from(b)),
+            b => write!(out, "_x{b:02x}").unwrap(),
+        }
+    }
+    out
+}
+
+fn var(name: &str) -> String {
+    mangle('v', name)
+}
+
+fn function_name(name: &str) -> String {
+    mangle('f', name)
+}
+
+fn label(name: &str) -> String {
+    mangle('l', name)
+}
+
+// Reads the arguments of the Bril `main` from the command line before calling it
+fn entry_point(main: &Function) -> Result<String, CompileError> {
+    let mut out = String::new();
+    writeln!(out, "int main(int argc, char **argv) {{").unwrap();
+    writeln!(out, "  bril_check_args(argc, {});", main.args.len()).unwrap();
+    let mut args = Vec::new();
+    for (i, arg) in main.args.iter().enumerate() {
+        let parse = match arg.arg_type {
+            Type::Int => "bril_parse_int",
+            Type::Bool => "bril_parse_bool",
+            Type::Float => "bril_parse_float",
+            _ => {
+                return Err(CompileError::UnsupportedType(
+                    main.name.clone(),
+                    arg.arg_type.clone(),
+                ))
+            }
+        };
+        args.push(format!("{parse}(argv[{}])", i + 1));
+    }
+    writeln!(out, "  {}({});", function_name(&main.name), args.join(", ")).unwrap();
+    writeln!(out, "  return 0;").unwrap();
+    writeln!(out, "}}").unwrap();
+    Ok(out)
+}
+
+// Compiles a single function. Every variable is declared at the top of the function so that a `goto` never jumps past a declaration, and labels become the labels of `goto`s
+struct FunctionCompiler<'a> {
+    func: &'a Function,
+    functions: &'a HashMap<&'a str, &'a Function>,
+    types: HashMap<&'a str, &'a Type>,
+    out: String,
+}
+
+impl<'a> FunctionCompiler<'a> {
+    fn new(
+        func: &'a Function,
+        functions: &'a HashMap<&'a str, &'a Function>,
+    ) -> Result<Self, CompileError> {
+        let mut types: HashMap<&str, &Type> = HashMap::new();
+        let dests = func.instrs.iter().filter_map(|c| match c {
+            Code::Instruction(Instruction::Constant {
+                dest, const_type, ..
+            }) => Some((dest, const_type)),
+            Code::Instruction(Instruction::Value { dest, op_type, .. }) => Some((dest, op_type)),
+            _ => None,
+        });
+        for (var, ty) in func
+            .args
+            .iter()
+            .map(|a| (&a.name, &a.arg_type))
+            .chain(dests)
+        {
+            c_type(func, ty)?;
+            match types.insert(var, ty) {
+                Some(old) if old != ty => {
+                    return Err(CompileError::ConflictingTypes(
+                        func.name.clone(),
+                        var.clone(),
+                        old.clone(),
+                        ty.clone(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+        Ok(Self {
+            func,
+            functions,
+            types,
+            out: String::new(),
+        })
+    }
+
+    fn compile(mut self) -> Result<String, CompileError> {
+        writeln!(self.out, "{} {{", signature(self.func)?).unwrap();
+        let mut vars: Vec<(&str, &Type)> = self
+            .types
+            .iter()
+            .filter(|(v, _)| !self.func.args.iter().any(|a| a.name == **v))
+            .map(|(v, t)| (*v, *t))
+            .collect();
+        vars.sort_unstable_by_key(|(v, _)| *v);
+        for (v, ty) in vars {
+            // Starting at zero keeps variables which are only assigned on some paths from being read uninitialized
+            writeln!(self.out, "  {} {} = 0;", self.ty(ty)?, var(v)).unwrap();
+        }
+        for code in &self.func.instrs {
+            match code {
+                // The empty statement lets a label come last in the function
+                Code::Label { label: l, .. } => writeln!(self.out, "{}:;", label(l)).unwrap(),
+                Code::Instruction(instr) => self.instruction(instr)?,
+            }
+        }
+        writeln!(self.out, "}}").unwrap();
+        Ok(self.out)
+    }
+
+    fn ty(&self, ty: &Type) -> Result<String, CompileError> {
+        c_type(self.func, ty)
+    }
+
+    fn unsupported(&self, op: &impl ToString) -> CompileError {
+        CompileError::UnsupportedOp(self.func.name.clone(), op.to_string())
+    }
+
+    // The C variable for ```v```, along with its type
+    fn read(&self, v: &str) -> Result<(String, &'a Type), CompileError> {
+        let ty = self.types.get(v).copied().ok_or_else(|| {
+            CompileError::UndefinedVariable(self.func.name.clone(), v.to_string())
+        })?;
+        Ok((var(v), ty))
+    }
+
+    fn read_all(&self, args: &[String]) -> Result<Vec<String>, CompileError> {
+        args.iter().map(|a| Ok(self.read(a)?.0)).collect()
+    }
+
+    fn call(&self, name: &str, args: &[String]) -> Result<String, CompileError> {
+        if !self.functions.contains_key(name) {
+            return Err(CompileError::UnknownFunction(
+                self.func.name.clone(),
+                name.to_string(),
+            ));
+        }
+        Ok(format!(
+            "{}({})",
+            function_name(name),
+            self.read_all(args)?.join(", ")
+        ))
+    }
+
+    fn instruction(&mut self, instr: &Instruction) -> Result<(), CompileError> {
+        match instr {
+            Instruction::Constant {
+                dest,
+                const_type,
+                value,
+                ..
+            } => {
+                let value = match constant_literal(const_type, value) {
+                    Literal::Float(f) => float(f),
+                    Literal::Int(i) => int(i),
+                    Literal::Bool(b) => b.to_string(),
+                    Literal::Char(_) | Literal::Str(_) => {
+                        return Err(CompileError::UnsupportedType(
+                            self.func.name.clone(),
+                            const_type.clone(),
+                        ))
+                    }
+                };
+                writeln!(self.out, "  {} = {value};", var(dest)).unwrap();
+                Ok(())
+            }
+            Instruction::Value {
+                args,
+                dest,
+                funcs,
+                op,
+                op_type,
+                ..
+            } => self.value(*op, args, funcs, dest, op_type),
+            Instruction::Effect {
+                args,
+                funcs,
+                labels,
+                op,
+                ..
+            } => self.effect(*op, args, funcs, labels),
+        }
+    }
+
+    fn value(
+        &mut self,
+        op: ValueOps,
+        args: &[String],
+        funcs: &[String],
+        dest: &str,
+        op_type: &Type,
+    ) -> Result<(), CompileError> {
+        let rhs = match op {
+            ValueOps::Id => self.read(&args[0])?.0,
+            ValueOps::Call => self.call(&funcs[0], args)?,
+            ValueOps::Not => format!("!{}", self.read(&args[0])?.0),
+            ValueOps::Load => format!("*{}", self.read(&args[0])?.0),
+            ValueOps::Alloc => {
+                let Type::Pointer(pointee) = op_type else {
+                    return Err(self.unsupported(&op));
+                };
+                let pointee = self.ty(pointee)?;
+                let count = self.read(&args[0])?.0;
+                format!("bril_alloc({count}, sizeof({pointee}))")
+            }
+            _ => {
+                let a = self.read_all(args)?;
+                if let Some(function) = runtime_op(op) {
+                    format!("{function}({})", a.join(", "))
+                } else {
+                    let c_op = binary_op(op).ok_or_else(|| self.unsupported(&op))?;
+                    format!("{} {c_op} {}", a[0], a[1])
+                }
+            }
+        };
+        writeln!(self.out, "  {} = {rhs};", var(dest)).unwrap();
+        Ok(())
+    }
+
+    fn effect(
+        &mut self,
+        op: EffectOps,
+        args: &[String],
+        funcs: &[String],
+        labels: &[String],
+    ) -> Result<(), CompileError> {
+        match op {
+            EffectOps::Jump => {
+                writeln!(self.out, "  goto {};", label(&labels[0])).unwrap();
+            }
+            EffectOps::Branch => {
+                let c = self.read(&args[0])?.0;
+                writeln!(
+                    self.out,
+                    "  if ({c}) goto {}; else goto {};",
+                    label(&labels[0]),
+                    label(&labels[1])
+                )
+                .unwrap();
+            }
+            EffectOps::Return => match (args.first(), &self.func.return_type) {
+                (Some(arg), _) => {
+                    let v = self.read(arg)?.0;
+                    writeln!(self.out, "  return {v};").unwrap();
+                }
+                (None, None) => writeln!(self.out, "  return;").unwrap(),
+                // Falling off the end of a function which should return a value
+                (None, Some(_)) => writeln!(self.out, "  abort();").unwrap(),
+            },
+            EffectOps::Call => {
+                let call = self.call(&funcs[0], args)?;
+                writeln!(self.out, "  {call};").unwrap();
+            }
+            EffectOps::Print => {
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(self.out, "  bril_print_space();").unwrap();
+                    }
+                    let (v, ty) = self.read(arg)?;
+                    let print = match ty {
+                        Type::Int => "bril_print_int",
+                        Type::Bool => "bril_print_bool",
+                        Type::Float => "bril_print_float",
+                        _ => {
+                            return Err(CompileError::UnprintableType(
+                                self.func.name.clone(),
+                                ty.clone(),
+                            ))
+                        }
+                    };
+                    writeln!(self.out, "  {print}({v});").unwrap();
+                }
+                writeln!(self.out, "  bril_print_newline();").unwrap();
+            }
+            EffectOps::Nop => {}
+            EffectOps::Store => {
+                let a = self.read_all(args)?;
+                writeln!(self.out, "  *{} = {};", a[0], a[1]).unwrap();
+            }
+            EffectOps::Free => {
+                let p = self.read(&args[0])?.0;
+                writeln!(self.out, "  free({p});").unwrap();
+            }
+            EffectOps::Speculate | EffectOps::Commit | EffectOps::Guard | EffectOps::Join => {
+                return Err(self.unsupported(&op))
+            }
+        }
+        Ok(())
+    }
+}
+
+// The function of the runtime which does a Bril operation that C can't do with an operator, either because it is undefined on overflow or because it has to check for division by zero
+const fn runtime_op(op: ValueOps) -> Option<&'static str> {
+    Some(match op {
+        ValueOps::Add => "bril_add",
+        ValueOps::Sub => "bril_sub",
+        ValueOps::Mul => "bril_mul",
+        ValueOps::Div => "bril_div",
+        ValueOps::Rem => "bril_rem",
+        ValueOps::Float2bits => "bril_float2bits",
+        ValueOps::Bits2float => "bril_bits2float",
+        _ => return None,
+    })
+}
+
+// The C operator for a Bril operation which takes two arguments
+const fn binary_op(op: ValueOps) -> Option<&'static str> {
+    Some(match op {
+        ValueOps::Eq | ValueOps::Feq => "==",
+        ValueOps::Lt | ValueOps::Flt => "<",
+        ValueOps::Gt | ValueOps::Fgt => ">",
+        ValueOps::Le | ValueOps::Fle => "<=",
+        ValueOps::Ge | ValueOps::Fge => ">=",
+        ValueOps::And => "&&",
+        ValueOps::Or => "||",
+        ValueOps::Fadd | ValueOps::PtrAdd => "+",
+        ValueOps::Fsub => "-",
+        ValueOps::Fmul => "*",
+        ValueOps::Fdiv => "/",
+        _ => return None,
+    })
+}
+
+// The smallest int can't be written as a literal in C, since it is the negation of a literal which is too big
+fn int(i: i64) -> String {
+    match i {
+        i64::MIN => "INT64_MIN".to_string(),
+        i if i32::try_from(i).is_ok() => i.to_string(),
+        i => format!("INT64_C({i})"),
+    }
+}
+
+// The shortest digits which round trip are read back as exactly the same float. Infinities and NaNs have no literals, so they are made from their bits
+fn float(f: f64) -> String {
+    if f.is_finite() {
+        format!("{f:e}")
+    } else {
+        #[allow(clippy::cast_possible_wrap)]
+        let bits = f.to_bits() as i64;
+        format!("bril_bits2float({})", int(bits))
+    }
+}
@@ -0,0 +1,15 @@
+use bril2c::cli::Cli;
+use bril2c::compile;
+use bril_rs::load_program;
+use clap::Parser;
+
+fn main() {
+    Cli::parse();
+    match compile(&load_program()) {
+        Ok(source) => print!("{source}"),
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(1)
+        }
+    }
+}
@@ -0,0 +1,169 @@
+/* The runtime of programs compiled by bril2c, which prints values, reads the
+ * arguments of main, and reports errors the way brilirs does. It only needs
+ * the C99 standard library. */
+
+#include <math.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+void bril_print_int(int64_t x) {
+  printf("%lld", (long long)x);
+}
+
+void bril_print_bool(bool b) {
+  fputs(b ? "true" : "false", stdout);
+}
+
+/* Prints floats like JavaScript's Number.prototype.toString, which is what
+ * brili and brilirs do: the shortest digits which read back as the same
+ * float, written out in full unless the exponent is at least 21 or less than
+ * -6. */
+void bril_print_float(double v) {
+  char buf[32];
+  char digits[18];
+  int p, k, n, exp, i;
+  if (isnan(v)) {
+    fputs("NaN", stdout);
+    return;
+  }
+  if (v == 0) {
+    /* Including negative zero */
+    putchar('0');
+    return;
+  }
+  if (v < 0) {
+    putchar('-');
+    v = -v;
+  }
+  if (isinf(v)) {
+    fputs("Infinity", stdout);
+    return;
+  }
+  /* Find the fewest digits after the point in "%.*e" which read back as the
+   * same float */
+  for (p = 0;; p++) {
+    snprintf(buf, sizeof buf, "%.*e", p, v);
+    if (strtod(buf, NULL) == v) {
+      break;
+    }
+  }
+  /* buf is "d.ddde+x", or "de+x" with no digits after the point */
+  k = p + 1;
+  digits[0] = buf[0];
+  memcpy(digits + 1, buf + 2, p);
+  exp = atoi(strchr(buf, 'e') + 1);
+  /* The decimal point goes after the first n digits */
+  n = exp + 1;
+  if (n > 0 && n <= 21) {
+    if (n < k) {
+      fwrite(digits, 1, n, stdout);
+      putchar('.');
+      fwrite(digits + n, 1, k - n, stdout);
+    } else {
+      fwrite(digits, 1, k, stdout);
+      for (i = k; i < n; i++) {
+        putchar('0');
+      }
+    }
+  } else if (n > -6 && n <= 0) {
+    fputs("0.", stdout);
+    for (i = 0; i < -n; i++) {
+      putchar('0');
+    }
+    fwrite(digits, 1, k, stdout);
+  } else {
+    putchar(digits[0]);
+    if (k > 1) {
+      putchar('.');
+      fwrite(digits + 1, 1, k - 1, stdout);
+    }
+    printf("e%c%d", n > 0 ? '+' : '-', exp < 0 ? -exp : exp);
+  }
+}
+
+void bril_print_space(void) {
+  putchar(' ');
+}
+
+void bril_print_newline(void) {
+  putchar('\n');
+}
+
+void bril_check_args(int argc, int expected) {
+  if (argc - 1 != expected) {
+    fprintf(stderr, "error: Expected `%d` function arguments, found `%d`\n", expected, argc - 1);
+    exit(2);
+  }
+}
+
+int64_t bril_parse_int(const char *s) {
+  return strtoll(s, NULL, 10);
+}
+
+bool bril_parse_bool(const char *s) {
+  return strcmp(s, "true") == 0;
+}
+
+double bril_parse_float(const char *s) {
+  return strtod(s, NULL);
+}
+
+/* Arithmetic which wraps around like brili instead of overflowing, which is
+ * undefined for signed integers in C */
+int64_t bril_add(int64_t a, int64_t b) {
+  return (int64_t)((uint64_t)a + (uint64_t)b);
+}
+
+int64_t bril_sub(int64_t a, int64_t b) {
+  return (int64_t)((uint64_t)a - (uint64_t)b);
+}
+
+int64_t bril_mul(int64_t a, int64_t b) {
+  return (int64_t)((uint64_t)a * (uint64_t)b);
+}
+
+int64_t bril_div(int64_t a, int64_t b) {
+  if (b == 0) {
+    fputs("error: division by zero\n", stderr);
+    exit(2);
+  }
+  if (b == -1) {
+    return bril_sub(0, a);
+  }
+  return a / b;
+}
+
+/* The remainder of bril_div, which has the sign of a */
+int64_t bril_rem(int64_t a, int64_t b) {
+  if (b == 0) {
+    fputs("error: division by zero\n", stderr);
+    exit(2);
+  }
+  if (b == -1) {
+    return 0;
+  }
+  return a % b;
+}
+
+int64_t bril_float2bits(double f) {
+  int64_t bits;
+  memcpy(&bits, &f, sizeof bits);
+  return bits;
+}
+
+double bril_bits2float(int64_t bits) {
+  double f;
+  memcpy(&f, &bits, sizeof f);
+  return f;
+}
+
+void *bril_alloc(int64_t count, size_t size) {
+  if (count <= 0) {
+    fprintf(stderr, "error: cannot allocate `%lld` entries\n", (long long)count);
+    exit(2);
+  }
+  return malloc((size_t)count * size);
+}
@@ -0,0 +1,87 @@
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use bril2json::format::format_program;
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(
+    about = "Formats Bril text in a canonical style, keeping its comments",
+    version,
+    author
+)]
+struct Cli {
+    /// The Bril text files to format in place. stdin is formatted to stdout if no files are provided
+    files: Vec<PathBuf>,
+
+    /// Change nothing, but list the inputs which aren't formatted and exit with a status of 1 if there are any
+    #[clap(long)]
+    check: bool,
+}
+
+// Formats ```source```, returning the exit status for it
+fn format_one(
+    name: &str,
+    source: &str,
+    check: bool,
+    write: impl FnOnce(&str) -> std::io::Result<()>,
+) -> i32 {
+    match format_program(source) {
+        Err(e) => {
+            eprintln!("{name}: {e}");
+            2
+        }
+        Ok(formatted) if check => {
+            if formatted == source {
+                0
+            } else {
+                println!("{name}");
+                1
+            }
+        }
+        Ok(formatted) => match write(&formatted) {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("{name}: {e}");
+                2
+            }
+        },
+    }
+}
+
+fn main() {
+    let args = Cli::parse();
+
+    let mut status = 0;
+    if args.files.is_empty() {
+        let mut source = String::new();
+        if let Err(e) = std::io::stdin().read_to_string(&mut source) {
+            eprintln!("<stdin>: {e}");
+            std::process::exit(2);
+        }
+        status = format_one("<stdin>", &source, args.check, |formatted| {
+            std::io::stdout().write_all(formatted.as_bytes())
+        });
+    }
+    for path in &args.files {
+        let name = path.display().to_string();
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{name}: {e}");
+                status = 2;
+                continue;
+            }
+        };
+        let file_status = format_one(&name, &source, args.check, |formatted| {
+            // Files which are already formatted are left untouched
+            if formatted == source {
+                Ok(())
+            } else {
+                std::fs::write(path, formatted)
+            }
+        });
+        status = status.max(file_status);
+    }
+    std::process::exit(status);
+}
@@ -1,3 +1,4 @@
+use bril_rs::version::SchemaVersion;
 use clap::Parser;
 
 #[derive(Parser)]
@@ -6,4 +7,8 @@ pub struct Cli {
     /// Flag for whether position information should be included
     #[clap(short)]
     pub position: bool,
+
+    /// Write an older version of Bril JSON, like 1.0.0, for tools which can't read the current one. Whatever that version has no way to say, like source positions in version 1, is left out
+    #[clap(long, value_name = "VERSION")]
+    pub emit_version: Option<SchemaVersion>,
 }
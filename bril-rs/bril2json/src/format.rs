@@ -0,0 +1,269 @@
+use std::fmt::Write;
+
+use bril_rs::{AbstractCode, AbstractFunction, AbstractInstruction, AbstractProgram};
+
+use crate::{parse_abstract_program_from_str, SyntaxError};
+
+// A comment in the source. The parser skips over comments, so these are found by scanning the text
+struct Comment {
+    row: u64,
+    text: String,
+    // Whether there is code before the comment on its line, which it then stays after
+    trailing: bool,
+}
+
+// What the parser doesn't keep about how the source is laid out
+#[derive(Default)]
+struct Layout {
+    comments: Vec<Comment>,
+    // The line of each `from` outside of a function, which includes the one starting each import
+    froms: Vec<u64>,
+    // The line of the `}` closing each function
+    closes: Vec<u64>,
+    // The lines with nothing on them at all, in order
+    blank: Vec<u64>,
+}
+
+impl Layout {
+    fn scan(input: &str) -> Self {
+        let mut layout = Self::default();
+        let mut depth = 0_usize;
+        for (idx, line) in input.split('\n').enumerate() {
+            let row = idx as u64 + 1;
+            let chars: Vec<char> = line.chars().collect();
+            let mut code = false;
+            let mut i = 0;
+            while i < chars.len() {
+                let c = chars[i];
+                match c {
+                    '#' => {
+                        let text: String = chars[i..].iter().collect();
+                        layout.comments.push(Comment {
+                            row,
+                            text: text.trim_end().to_string(),
+                            trailing: code,
+                        });
+                        break;
+                    }
+                    // Import paths, string literals, and character literals can have a `#` in them which doesn't start a comment
+                    '"' => {
+                        let mut end = i + 1;
+                        while end < chars.len() && chars[end] != '"' {
+                            // An escaped quote doesn't end the string
+                            end += if chars[end] == '\\' { 2 } else { 1 };
+                        }
+                        i = end;
+                    }
+                    '\'' if chars.get(i + 2) == Some(&'\'') => i += 2,
+                    '{' => depth += 1,
+                    '}' => {
+                        depth = depth.saturating_sub(1);
+                        if depth == 0 {
+                            layout.closes.push(row);
+                        }
+                    }
+                    _ if is_word(c) => {
+                        let len = chars[i..].iter().take_while(|c| is_word(**c)).count();
+                        if depth == 0 && chars[i..i + len].iter().copied().eq("from".chars()) {
+                            layout.froms.push(row);
+                        }
+                        i += len - 1;
+                    }
+                    _ => {}
+                }
+                code |= !c.is_whitespace();
+                i += 1;
+            }
+            if !code && layout.comments.last().is_none_or(|c| c.row != row) {
+                layout.blank.push(row);
+            }
+        }
+        layout
+    }
+
+    // Whether there is a blank line somewhere after line ```from``` and before line ```to```
+    fn blank_between(&self, from: u64, to: u64) -> bool {
+        let next = self.blank.partition_point(|row| *row <= from);
+        self.blank.get(next).is_some_and(|row| *row < to)
+    }
+}
+
+const fn is_word(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '%' | '.' | '@')
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Open,
+    Close,
+    Label,
+    Instruction,
+    TopLevel,
+}
+
+impl Kind {
+    const fn indent(self) -> &'static str {
+        match self {
+            Self::Instruction => "  ",
+            Self::Open | Self::Close | Self::Label | Self::TopLevel => "",
+        }
+    }
+
+    // The kind of a comment on its own line above a line of this kind
+    const fn comment(self) -> Self {
+        match self {
+            // Comments at the end of a function are indented like the instructions before them
+            Self::Close => Self::Instruction,
+            Self::Open => Self::TopLevel,
+            Self::Label | Self::Instruction | Self::TopLevel => self,
+        }
+    }
+}
+
+// A line of the formatted output
+struct Line {
+    // The line of the source the line starts at
+    row: u64,
+    kind: Kind,
+    text: String,
+    trailing: Vec<String>,
+}
+
+impl Line {
+    const fn new(row: u64, kind: Kind, text: String) -> Self {
+        Self {
+            row,
+            kind,
+            text,
+            trailing: Vec::new(),
+        }
+    }
+}
+
+fn signature(func: &AbstractFunction) -> String {
+    let mut text = format!("@{}", func.name);
+    if !func.args.is_empty() {
+        let args: Vec<String> = func.args.iter().map(ToString::to_string).collect();
+        // Writing to a String can't fail
+        let _ = write!(text, "({})", args.join(", "));
+    }
+    if let Some(return_type) = &func.return_type {
+        let _ = write!(text, ": {return_type}");
+    }
+    text.push_str(" {");
+    text
+}
+
+fn instruction(instr: &AbstractInstruction) -> String {
+    match instr {
+        // Each argument of a phi is written next to the label it comes from, which is easier to read than all of the arguments followed by all of the labels
+        AbstractInstruction::Value {
+            args,
+            dest,
+            funcs,
+            labels,
+            op,
+            op_type,
+            ..
+        } if op == "phi" && funcs.is_empty() && args.len() == labels.len() => {
+            let mut text = op_type.as_ref().map_or_else(
+                || format!("{dest} = {op}"),
+                |op_type| format!("{dest}: {op_type} = {op}"),
+            );
+            for (arg, label) in args.iter().zip(labels) {
+                let _ = write!(text, " {arg} .{label}");
+            }
+            text.push(';');
+            text
+        }
+        _ => instr.to_string(),
+    }
+}
+
+fn lines(prog: &AbstractProgram, layout: &Layout) -> Vec<Line> {
+    let mut lines = Vec::new();
+    for (idx, import) in prog.imports.iter().enumerate() {
+        // Imports come before any function, so the first `from`s start them
+        let row = layout.froms.get(idx).copied().unwrap_or_default();
+        lines.push(Line::new(row, Kind::TopLevel, import.to_string()));
+    }
+    for (idx, func) in prog.functions.iter().enumerate() {
+        let row = func.pos.map_or(0, |pos| pos.row);
+        lines.push(Line::new(row, Kind::Open, signature(func)));
+        for code in &func.instrs {
+            lines.push(match code {
+                AbstractCode::Label { label, pos } => Line::new(
+                    pos.map_or(row, |pos| pos.row),
+                    Kind::Label,
+                    format!(".{label}:"),
+                ),
+                AbstractCode::Instruction(instr) => {
+                    let pos = match instr {
+                        AbstractInstruction::Constant { pos, .. }
+                        | AbstractInstruction::Value { pos, .. }
+                        | AbstractInstruction::Effect { pos, .. } => *pos,
+                    };
+                    Line::new(
+                        pos.map_or(row, |pos| pos.row),
+                        Kind::Instruction,
+                        instruction(instr),
+                    )
+                }
+            });
+        }
+        let close = layout.closes.get(idx).copied().unwrap_or(row);
+        lines.push(Line::new(close, Kind::Close, "}".to_string()));
+    }
+    lines
+}
+
+/// Formats the Bril text ```input``` in the canonical style written by `brilfmt`.
+///
+/// Every instruction goes on a line of its own indented by two spaces, with a single space between each part of it and a `;` straight after its last argument, while labels, function signatures, and imports start at the beginning of their line. Comments are kept: those on a line of their own stay above what follows them, indented to match it, and those after some code stay at the end of the line with whatever they came after. A single blank line is kept wherever the source had any, except at the start and end of a function. Formatting text which is already formatted leaves it unchanged.
+/// # Errors
+/// Will return what is wrong with ```input``` if it is not well-formed Bril text
+pub fn format_program(input: &str) -> Result<String, SyntaxError> {
+    let prog = parse_abstract_program_from_str(input, true)?;
+    let layout = Layout::scan(input);
+    let mut lines = lines(&prog, &layout);
+
+    let (trailing, standalone): (Vec<&Comment>, Vec<&Comment>) =
+        layout.comments.iter().partition(|c| c.trailing);
+    for comment in trailing {
+        // A comment after some code goes with the last thing starting on or before its line
+        if let Some(line) = lines.iter_mut().rev().find(|l| l.row <= comment.row) {
+            line.trailing.push(comment.text.clone());
+        }
+    }
+
+    let mut text = String::new();
+    let mut comments = standalone.into_iter().peekable();
+    let mut previous: Option<(u64, Kind)> = None;
+    let mut emit = |text: &mut String, row: u64, kind: Kind, line: &str| {
+        if let Some((prev_row, prev_kind)) = previous {
+            if prev_kind != Kind::Open && kind != Kind::Close && layout.blank_between(prev_row, row)
+            {
+                text.push('\n');
+            }
+        }
+        previous = Some((row, kind));
+        text.push_str(line);
+        text.push('\n');
+    };
+    for line in &lines {
+        while let Some(comment) = comments.next_if(|c| c.row < line.row) {
+            let kind = line.kind.comment();
+            let indented = format!("{}{}", kind.indent(), comment.text);
+            emit(&mut text, comment.row, kind, &indented);
+        }
+        let mut formatted = format!("{}{}", line.kind.indent(), line.text);
+        for comment in &line.trailing {
+            let _ = write!(formatted, "  {comment}");
+        }
+        emit(&mut text, line.row, line.kind, &formatted);
+    }
+    for comment in comments {
+        emit(&mut text, comment.row, Kind::TopLevel, &comment.text);
+    }
+    Ok(text)
+}
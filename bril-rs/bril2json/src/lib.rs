@@ -1,6 +1,8 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
 #![warn(missing_docs)]
 #![doc = include_str!("../README.md")]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
 
 // Tell the github workflow check to not format the generated rust program bril_grammar.rs
 #[doc(hidden)]
@@ -8,20 +10,42 @@
 pub mod bril_grammar;
 #[doc(hidden)]
 pub mod cli;
-use bril_rs::{AbstractProgram, Position};
+/// Formatting Bril text in a canonical style which keeps its comments, as done by `brilfmt`
+pub mod format;
+use std::collections::HashMap;
+
+use bril_rs::{
+    AbstractCode, AbstractFunction, AbstractInstruction, AbstractProgram, AbstractType, Position,
+    SourcePosition,
+};
+use lalrpop_util::{lexer::Token, ParseError};
 
 #[doc(hidden)]
 #[derive(Clone)]
 pub struct Lines {
     use_pos: bool,
-    new_lines: Vec<usize>,
+    newlines: Vec<usize>,
+    // The source position given by the `#@ file:row:col` comment at the end of each row that has one
+    sources: HashMap<u64, SourcePosition>,
 }
 
 impl Lines {
     fn new(input: &str, use_pos: bool) -> Self {
         Self {
             use_pos,
-            new_lines: input
+            sources: if use_pos {
+                input
+                    .lines()
+                    .enumerate()
+                    .filter_map(|(line_num, line)| {
+                        let source = comment(line)?.strip_prefix("#@")?.trim().parse().ok()?;
+                        Some((line_num as u64 + 1, source))
+                    })
+                    .collect()
+            } else {
+                HashMap::new()
+            },
+            newlines: input
                 .as_bytes()
                 .iter()
                 .enumerate()
@@ -32,25 +56,76 @@ impl Lines {
 
     fn get_position(&self, index: usize) -> Option<Position> {
         if self.use_pos {
-            Some(self.new_lines.iter().enumerate().fold(
-                Position { col: 1, row: 0 },
+            Some(self.newlines.iter().enumerate().fold(
+                Position {
+                    col: (index + 1) as u64,
+                    row: 1,
+                    derived: false,
+                    source: None,
+                },
                 |current, (line_num, idx)| {
                     if *idx < index {
                         Position {
                             row: (line_num + 2) as u64,
                             col: (index - idx) as u64,
+                            derived: false,
+                            source: None,
                         }
                     } else {
                         current
                     }
                 },
             ))
+            .map(|pos| Position {
+                source: self.sources.get(&pos.row).copied(),
+                ..pos
+            })
         } else {
             None
         }
     }
 }
 
+// The comment at the end of ```line```, starting from its `#`, skipping over any `#` in a string literal
+fn comment(line: &str) -> Option<&str> {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (idx, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return Some(&line[idx..]),
+            _ => {}
+        }
+    }
+    None
+}
+
+// The text of a string literal between its quotes with its escapes replaced by what they stand for, which are the same ones that `Literal::Str` writes
+fn unescape(text: &str) -> Result<String, &'static str> {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            _ => return Err(
+                "unknown escape in string literal, expected one of \\\", \\\\, \\n, \\r, \\t, \\0",
+            ),
+        });
+    }
+    Ok(out)
+}
+
 /// The entrance point to the bril2json parser. It takes an ```input```:[`std::io::Read`] which should be the Bril text file. You can control whether it includes source code positions with ```use_pos```.
 /// # Panics
 /// Will panic if the input is not well-formed Bril text
@@ -71,3 +146,133 @@ pub fn parse_abstract_program_from_read<R: std::io::Read>(
 pub fn parse_abstract_program(use_pos: bool) -> AbstractProgram {
     parse_abstract_program_from_read(std::io::stdin(), use_pos)
 }
+
+/// What is wrong with Bril text which couldn't be parsed, along with where it went wrong
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// Where in the text the problem is, if the parser could tell
+    pub pos: Option<Position>,
+    /// A description of the problem
+    pub message: String,
+}
+
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pos {
+            Some(pos) => write!(f, "Line {}, Column {}: {}", pos.row, pos.col, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Like [`parse_abstract_program_from_read`] but for text which is already in memory, and which may not be well-formed, like Bril typed in by hand.
+/// # Errors
+/// Will return what is wrong with ```input``` if it is not well-formed Bril text
+pub fn parse_abstract_program_from_str(
+    input: &str,
+    use_pos: bool,
+) -> Result<AbstractProgram, SyntaxError> {
+    let parser = bril_grammar::AbstractProgramParser::new();
+    parser
+        .parse(&Lines::new(input, use_pos), input)
+        .map_err(|e| describe_error(input, &e))
+}
+
+/// Parses a single instruction or label on its own, outside of any function, like a line typed into a REPL.
+/// # Errors
+/// Will return what is wrong with ```input``` if it is not exactly one well-formed instruction or label
+pub fn parse_abstract_code(input: &str, use_pos: bool) -> Result<AbstractCode, SyntaxError> {
+    let parser = bril_grammar::AbstractCodeParser::new();
+    parser
+        .parse(&Lines::new(input, use_pos), input)
+        .map_err(|e| describe_error(input, &e))
+}
+
+/// Parses a single function on its own, signature and all, without the rest of the program it might be part of.
+/// # Errors
+/// Will return what is wrong with ```input``` if it is not exactly one well-formed function
+/// # Example
+/// ```
+/// let func = bril2json::parse_function("@double(x: int): int { y: int = add x x; ret y; }", false).unwrap();
+/// assert_eq!(func.name, "double");
+/// assert_eq!(func.instrs.len(), 2);
+/// ```
+pub fn parse_function(input: &str, use_pos: bool) -> Result<AbstractFunction, SyntaxError> {
+    let parser = bril_grammar::AbstractFunctionParser::new();
+    parser
+        .parse(&Lines::new(input, use_pos), input)
+        .map_err(|e| describe_error(input, &e))
+}
+
+/// Like [`parse_abstract_code`] but only for instructions, so a label is an error.
+/// # Errors
+/// Will return what is wrong with ```input``` if it is not exactly one well-formed instruction
+/// # Example
+/// ```
+/// use bril_rs::AbstractInstruction;
+/// let instr = bril2json::parse_instruction("v: int = const 5;", false).unwrap();
+/// assert!(matches!(instr, AbstractInstruction::Constant { .. }));
+/// ```
+pub fn parse_instruction(input: &str, use_pos: bool) -> Result<AbstractInstruction, SyntaxError> {
+    let parser = bril_grammar::AbstractInstructionParser::new();
+    parser
+        .parse(&Lines::new(input, use_pos), input)
+        .map_err(|e| describe_error(input, &e))
+}
+
+/// Parses a type like `int` or `ptr<ptr<float>>` on its own.
+/// # Errors
+/// Will return what is wrong with ```input``` if it is not exactly one well-formed type
+/// # Example
+/// ```
+/// use bril_rs::AbstractType;
+/// assert_eq!(
+///     bril2json::parse_type("ptr<int>").unwrap(),
+///     AbstractType::Parameterized("ptr".to_string(), Box::new(AbstractType::Primitive("int".to_string())))
+/// );
+/// ```
+pub fn parse_type(input: &str) -> Result<AbstractType, SyntaxError> {
+    let parser = bril_grammar::AbstractTypeParser::new();
+    parser
+        .parse(&Lines::new(input, false), input)
+        .map_err(|e| describe_error(input, &e))
+}
+
+// Describes ```e``` by the line and column in ```input``` where it happened instead of the byte offsets that lalrpop reports
+fn describe_error(input: &str, e: &ParseError<usize, Token<'_>, &str>) -> SyntaxError {
+    let (location, problem, expected) = match e {
+        ParseError::InvalidToken { location } => (*location, "Invalid token".to_string(), &[][..]),
+        ParseError::UnrecognizedEOF { location, expected } => (
+            *location,
+            "Unexpected end of input".to_string(),
+            &expected[..],
+        ),
+        ParseError::UnrecognizedToken {
+            token: (start, token, _),
+            expected,
+        } => (
+            *start,
+            format!("Unrecognized token `{token}`"),
+            &expected[..],
+        ),
+        ParseError::ExtraToken {
+            token: (start, token, _),
+        } => (*start, format!("Extra token `{token}`"), &[][..]),
+        ParseError::User { error } => {
+            return SyntaxError {
+                pos: None,
+                message: (*error).to_string(),
+            }
+        }
+    };
+    SyntaxError {
+        pos: Lines::new(input, true).get_position(location),
+        message: if expected.is_empty() {
+            problem
+        } else {
+            format!("{problem}, expected one of {}", expected.join(", "))
+        },
+    }
+}
@@ -1,9 +1,14 @@
 use bril2json::cli::Cli;
 use bril2json::parse_abstract_program;
-use bril_rs::output_abstract_program;
+use bril_rs::{output_abstract_program, version};
 use clap::Parser;
+use std::io;
 
 fn main() {
     let args = Cli::parse();
-    output_abstract_program(&parse_abstract_program(args.position))
+    let prog = parse_abstract_program(args.position);
+    match args.emit_version {
+        Some(emit) => version::write(io::stdout(), &prog, emit).unwrap(),
+        None => output_abstract_program(&prog),
+    }
 }
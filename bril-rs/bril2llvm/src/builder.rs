@@ -0,0 +1,588 @@
+//! Builds the same LLVM IR as [`crate::compile`] through the LLVM libraries with [inkwell](https://github.com/TheDan64/inkwell), which lets LLVM check the module, optimize it, and write it out as a native object file.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+
+use bril_rs::cfg::Cfg;
+use bril_rs::dataflow::constant_literal;
+use bril_rs::{Code, EffectOps, Function, Instruction, Literal, Program, Type, ValueOps};
+use inkwell::basic_block::BasicBlock;
+use inkwell::builder::{Builder, BuilderError};
+use inkwell::context::Context;
+use inkwell::memory_buffer::MemoryBuffer;
+use inkwell::module::Module;
+use inkwell::passes::PassBuilderOptions;
+use inkwell::targets::{
+    CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine,
+};
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum};
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, FunctionValue, PointerValue};
+use inkwell::{AddressSpace, FloatPredicate, IntPredicate, OptimizationLevel};
+
+use crate::{CompileError, RUNTIME, RUNTIME_DECLARATIONS};
+
+impl From<BuilderError> for CompileError {
+    fn from(e: BuilderError) -> Self {
+        Self::Llvm(e.to_string())
+    }
+}
+
+fn llvm_error(e: impl Display) -> CompileError {
+    CompileError::Llvm(e.to_string())
+}
+
+// Reads a module of LLVM IR which is part of bril2llvm
+fn parse<'ctx>(context: &'ctx Context, ir: &str, name: &str) -> Result<Module<'ctx>, CompileError> {
+    let buffer = MemoryBuffer::create_from_memory_range_copy(ir.as_bytes(), name);
+    context.create_module_from_ir(buffer).map_err(llvm_error)
+}
+
+/// Compiles ```prog``` to a module in ```context```, which is the same as the module [`crate::compile`] writes out, and which LLVM has checked.
+///
+/// # Errors
+/// Fails for the same reasons as [`crate::compile`], and with [`CompileError::Llvm`] if LLVM rejects the module
+pub fn compile_module<'ctx>(
+    context: &'ctx Context,
+    prog: &Program,
+    runtime: bool,
+) -> Result<Module<'ctx>, CompileError> {
+    if !prog.imports.is_empty() {
+        return Err(CompileError::Imports);
+    }
+    // The declarations are merged with the definitions when the runtime is linked in
+    let module = parse(context, RUNTIME_DECLARATIONS, "bril")?;
+    let mut values = Vec::new();
+    for func in &prog.functions {
+        let params = func
+            .args
+            .iter()
+            .map(|a| Ok(basic_type(context, func, &a.arg_type)?.into()))
+            .collect::<Result<Vec<BasicMetadataTypeEnum>, CompileError>>()?;
+        let fn_type = match &func.return_type {
+            Some(ty) => basic_type(context, func, ty)?.fn_type(&params, false),
+            None => context.void_type().fn_type(&params, false),
+        };
+        values.push(module.add_function(&format!("bril.{}", func.name), fn_type, None));
+    }
+    // Calls go to the last function with the name they call, like they do for the IR written out as text
+    let by_name: HashMap<&str, (&Function, FunctionValue)> = prog
+        .functions
+        .iter()
+        .zip(&values)
+        .map(|(f, v)| (f.name.as_str(), (f, *v)))
+        .collect();
+    for (func, value) in prog.functions.iter().zip(values) {
+        FunctionCompiler::new(context, &module, func, &by_name)?.compile(value)?;
+    }
+    if let Some((main, value)) = by_name.get("main") {
+        entry_point(context, &module, main, *value)?;
+    }
+    if runtime {
+        module
+            .link_in_module(parse(context, RUNTIME, "runtime")?)
+            .map_err(llvm_error)?;
+    }
+    module.verify().map_err(llvm_error)?;
+    Ok(module)
+}
+
+/// Optimizes ```module``` at `-O2` and writes it to ```path``` as an object file for the machine bril2llvm is running on, which `cc` can link into an executable when the module includes the runtime.
+///
+/// # Errors
+/// Fails if LLVM can't target this machine or can't write the file
+pub fn write_object(module: &Module<'_>, path: &Path) -> Result<(), CompileError> {
+    Target::initialize_native(&InitializationConfig::default()).map_err(llvm_error)?;
+    let triple = TargetMachine::get_default_triple();
+    let machine = Target::from_triple(&triple)
+        .map_err(llvm_error)?
+        .create_target_machine(
+            &triple,
+            &TargetMachine::get_host_cpu_name().to_string(),
+            &TargetMachine::get_host_cpu_features().to_string(),
+            OptimizationLevel::Default,
+            RelocMode::PIC,
+            CodeModel::Default,
+        )
+        .ok_or_else(|| llvm_error(format!("can't target {triple}")))?;
+    module.set_triple(&triple);
+    module.set_data_layout(&machine.get_target_data().get_data_layout());
+    module
+        .run_passes("default<O2>", &machine, PassBuilderOptions::create())
+        .map_err(llvm_error)?;
+    machine
+        .write_to_file(module, FileType::Object, path)
+        .map_err(llvm_error)
+}
+
+fn basic_type<'ctx>(
+    context: &'ctx Context,
+    func: &Function,
+    ty: &Type,
+) -> Result<BasicTypeEnum<'ctx>, CompileError> {
+    match ty {
+        Type::Int => Ok(context.i64_type().into()),
+        Type::Bool => Ok(context.bool_type().into()),
+        Type::Float => Ok(context.f64_type().into()),
+        Type::Pointer(_) => Ok(context.ptr_type(AddressSpace::default()).into()),
+        Type::Char | Type::Str | Type::Thread | Type::Tuple(_) => {
+            Err(CompileError::UnsupportedType(func.name.clone(), ty.clone()))
+        }
+    }
+}
+
+// The function of the runtime called ```name```, which is always declared
+fn runtime_function<'ctx>(module: &Module<'ctx>, name: &str) -> FunctionValue<'ctx> {
+    module
+        .get_function(name)
+        .unwrap_or_else(|| unreachable!("the runtime declares {name}"))
+}
+
+// Reads the arguments of the Bril `main` from the command line before calling it
+fn entry_point<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    main: &Function,
+    bril_main: FunctionValue<'ctx>,
+) -> Result<(), CompileError> {
+    let i32_type = context.i32_type();
+    let ptr_type = context.ptr_type(AddressSpace::default());
+    let c_main = module.add_function(
+        "main",
+        i32_type.fn_type(&[i32_type.into(), ptr_type.into()], false),
+        None,
+    );
+    let builder = context.create_builder();
+    builder.position_at_end(context.append_basic_block(c_main, "entry"));
+    let arg_count = c_main.get_nth_param(0).unwrap_or_else(|| unreachable!());
+    let arg_values = c_main
+        .get_nth_param(1)
+        .unwrap_or_else(|| unreachable!())
+        .into_pointer_value();
+    let expected = i32_type.const_int(main.args.len() as u64, false);
+    builder.build_call(
+        runtime_function(module, "__bril_check_args"),
+        &[arg_count.into(), expected.into()],
+        "",
+    )?;
+    let mut bril_args: Vec<BasicMetadataValueEnum> = Vec::new();
+    for (i, arg) in main.args.iter().enumerate() {
+        let parse = match arg.arg_type {
+            Type::Int => "__bril_parse_int",
+            Type::Bool => "__bril_parse_bool",
+            Type::Float => "__bril_parse_float",
+            _ => {
+                return Err(CompileError::UnsupportedType(
+                    main.name.clone(),
+                    arg.arg_type.clone(),
+                ))
+            }
+        };
+        let index = context.i64_type().const_int(i as u64 + 1, false);
+        // SAFETY: __bril_check_args has made sure that there is an argument on the command line for each parameter
+        let addr =
+            unsafe { builder.build_gep(ptr_type, arg_values, &[index], &format!("arg{i}.addr"))? };
+        let text = builder.build_load(ptr_type, addr, &format!("arg{i}.text"))?;
+        let value = builder
+            .build_call(
+                runtime_function(module, parse),
+                &[text.into()],
+                &format!("arg{i}"),
+            )?
+            .try_as_basic_value()
+            .left()
+            .unwrap_or_else(|| unreachable!("{parse} returns a value"));
+        bril_args.push(value.into());
+    }
+    builder.build_call(bril_main, &bril_args, "")?;
+    builder.build_return(Some(&i32_type.const_zero()))?;
+    Ok(())
+}
+
+// Compiles a single function, giving every variable a stack slot like the IR which is written out as text
+struct FunctionCompiler<'a, 'ctx> {
+    context: &'ctx Context,
+    module: &'a Module<'ctx>,
+    builder: Builder<'ctx>,
+    func: &'a Function,
+    functions: &'a HashMap<&'a str, (&'a Function, FunctionValue<'ctx>)>,
+    types: HashMap<&'a str, &'a Type>,
+    slots: HashMap<&'a str, PointerValue<'ctx>>,
+    blocks: HashMap<String, BasicBlock<'ctx>>,
+}
+
+impl<'a, 'ctx> FunctionCompiler<'a, 'ctx> {
+    fn new(
+        context: &'ctx Context,
+        module: &'a Module<'ctx>,
+        func: &'a Function,
+        functions: &'a HashMap<&'a str, (&'a Function, FunctionValue<'ctx>)>,
+    ) -> Result<Self, CompileError> {
+        let mut types: HashMap<&str, &Type> = HashMap::new();
+        let dests = func.instrs.iter().filter_map(|c| match c {
+            Code::Instruction(Instruction::Constant {
+                dest, const_type, ..
+            }) => Some((dest, const_type)),
+            Code::Instruction(Instruction::Value { dest, op_type, .. }) => Some((dest, op_type)),
+            _ => None,
+        });
+        for (var, ty) in func
+            .args
+            .iter()
+            .map(|a| (&a.name, &a.arg_type))
+            .chain(dests)
+        {
+            basic_type(context, func, ty)?;
+            match types.insert(var, ty) {
+                Some(old) if old != ty => {
+                    return Err(CompileError::ConflictingTypes(
+                        func.name.clone(),
+                        var.clone(),
+                        old.clone(),
+                        ty.clone(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+        Ok(Self {
+            context,
+            module,
+            builder: context.create_builder(),
+            func,
+            functions,
+            types,
+            slots: HashMap::new(),
+            blocks: HashMap::new(),
+        })
+    }
+
+    fn compile(mut self, function: FunctionValue<'ctx>) -> Result<(), CompileError> {
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+        let mut vars: Vec<(&str, &Type)> = self.types.iter().map(|(v, t)| (*v, *t)).collect();
+        vars.sort_unstable_by_key(|(v, _)| *v);
+        for (var, ty) in vars {
+            let slot = self
+                .builder
+                .build_alloca(self.ty(ty)?, &format!("v.{var}"))?;
+            self.slots.insert(var, slot);
+        }
+        for (arg, param) in self.func.args.iter().zip(function.get_param_iter()) {
+            param.set_name(&format!("a.{}", arg.name));
+            self.builder
+                .build_store(self.slots[arg.name.as_str()], param)?;
+        }
+
+        let cfg = Cfg::from_function(self.func)
+            .map_err(|e| CompileError::Labels(self.func.name.clone(), e))?;
+        for block in &cfg.blocks {
+            let bb = self
+                .context
+                .append_basic_block(function, &format!("l.{}", block.label));
+            self.blocks.insert(block.label.clone(), bb);
+        }
+        self.builder
+            .build_unconditional_branch(self.blocks[&cfg.blocks[0].label])?;
+        for block in &cfg.blocks {
+            self.builder.position_at_end(self.blocks[&block.label]);
+            for instr in &block.instrs {
+                self.instruction(instr)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn ty(&self, ty: &Type) -> Result<BasicTypeEnum<'ctx>, CompileError> {
+        basic_type(self.context, self.func, ty)
+    }
+
+    fn unsupported(&self, op: &impl ToString) -> CompileError {
+        CompileError::UnsupportedOp(self.func.name.clone(), op.to_string())
+    }
+
+    fn var_type(&self, var: &str) -> Result<&'a Type, CompileError> {
+        self.types
+            .get(var)
+            .copied()
+            .ok_or_else(|| CompileError::UndefinedVariable(self.func.name.clone(), var.to_string()))
+    }
+
+    // Loads ```var``` from its stack slot, returning it along with its type
+    fn read(&self, var: &str) -> Result<(BasicValueEnum<'ctx>, &'a Type), CompileError> {
+        let ty = self.var_type(var)?;
+        let value = self
+            .builder
+            .build_load(self.ty(ty)?, self.slots[var], var)?;
+        Ok((value, ty))
+    }
+
+    fn read_all(&self, args: &[String]) -> Result<Vec<BasicValueEnum<'ctx>>, CompileError> {
+        args.iter().map(|a| Ok(self.read(a)?.0)).collect()
+    }
+
+    fn write(&self, dest: &str, value: BasicValueEnum<'ctx>) -> Result<(), CompileError> {
+        self.builder.build_store(self.slots[dest], value)?;
+        Ok(())
+    }
+
+    // Calls ```function```, returning what it returns if it returns anything
+    fn call_function(
+        &self,
+        function: FunctionValue<'ctx>,
+        args: &[BasicValueEnum<'ctx>],
+    ) -> Result<Option<BasicValueEnum<'ctx>>, CompileError> {
+        let args: Vec<BasicMetadataValueEnum> = args.iter().map(|&a| a.into()).collect();
+        Ok(self
+            .builder
+            .build_call(function, &args, "")?
+            .try_as_basic_value()
+            .left())
+    }
+
+    fn call(
+        &self,
+        name: &str,
+        args: &[String],
+    ) -> Result<Option<BasicValueEnum<'ctx>>, CompileError> {
+        let (_, callee) = self.functions.get(name).ok_or_else(|| {
+            CompileError::UnknownFunction(self.func.name.clone(), name.to_string())
+        })?;
+        self.call_function(*callee, &self.read_all(args)?)
+    }
+
+    fn call_runtime(
+        &self,
+        name: &str,
+        args: &[BasicValueEnum<'ctx>],
+    ) -> Result<Option<BasicValueEnum<'ctx>>, CompileError> {
+        self.call_function(runtime_function(self.module, name), args)
+    }
+
+    fn instruction(&self, instr: &Instruction) -> Result<(), CompileError> {
+        match instr {
+            Instruction::Constant {
+                dest,
+                const_type,
+                value,
+                ..
+            } => {
+                let value: BasicValueEnum = match constant_literal(const_type, value) {
+                    Literal::Float(f) => self.context.f64_type().const_float(f).into(),
+                    #[allow(clippy::cast_sign_loss)]
+                    Literal::Int(i) => self.context.i64_type().const_int(i as u64, true).into(),
+                    Literal::Bool(b) => self
+                        .context
+                        .bool_type()
+                        .const_int(u64::from(b), false)
+                        .into(),
+                    Literal::Char(_) | Literal::Str(_) => {
+                        return Err(CompileError::UnsupportedType(
+                            self.func.name.clone(),
+                            const_type.clone(),
+                        ))
+                    }
+                };
+                self.write(dest, value)
+            }
+            Instruction::Value {
+                args,
+                dest,
+                funcs,
+                op,
+                op_type,
+                ..
+            } => self.value(*op, args, funcs, dest, op_type),
+            Instruction::Effect {
+                args,
+                funcs,
+                labels,
+                op,
+                ..
+            } => self.effect(*op, args, funcs, labels),
+        }
+    }
+
+    fn value(
+        &self,
+        op: ValueOps,
+        args: &[String],
+        funcs: &[String],
+        dest: &str,
+        op_type: &Type,
+    ) -> Result<(), CompileError> {
+        let b = &self.builder;
+        let value: BasicValueEnum = match op {
+            ValueOps::Id => self.read(&args[0])?.0,
+            ValueOps::Call => self
+                .call(&funcs[0], args)?
+                .ok_or_else(|| self.unsupported(&op))?,
+            ValueOps::Div | ValueOps::Rem => {
+                let name = if op == ValueOps::Div {
+                    "__bril_div"
+                } else {
+                    "__bril_rem"
+                };
+                self.call_runtime(name, &self.read_all(args)?)?
+                    .unwrap_or_else(|| unreachable!("{name} returns a value"))
+            }
+            ValueOps::Not => b
+                .build_not(self.read(&args[0])?.0.into_int_value(), "")?
+                .into(),
+            ValueOps::Float2bits => {
+                b.build_bit_cast(self.read(&args[0])?.0, self.context.i64_type(), "")?
+            }
+            ValueOps::Bits2float => {
+                b.build_bit_cast(self.read(&args[0])?.0, self.context.f64_type(), "")?
+            }
+            ValueOps::Alloc => {
+                let Type::Pointer(pointee) = op_type else {
+                    return Err(self.unsupported(&op));
+                };
+                let count = self.read(&args[0])?.0;
+                let size = if **pointee == Type::Bool { 1 } else { 8 };
+                let size = self.context.i64_type().const_int(size, false).into();
+                self.call_runtime("__bril_alloc", &[count, size])?
+                    .unwrap_or_else(|| unreachable!("__bril_alloc returns a value"))
+            }
+            ValueOps::Load => {
+                let p = self.read(&args[0])?.0.into_pointer_value();
+                b.build_load(self.ty(op_type)?, p, "")?
+            }
+            ValueOps::PtrAdd => {
+                let Type::Pointer(pointee) = op_type else {
+                    return Err(self.unsupported(&op));
+                };
+                let a = self.read_all(args)?;
+                // SAFETY: Bril leaves pointers which go outside of their allocation undefined until they are used, like LLVM without `inbounds`
+                unsafe {
+                    b.build_gep(
+                        self.ty(pointee)?,
+                        a[0].into_pointer_value(),
+                        &[a[1].into_int_value()],
+                        "",
+                    )?
+                }
+                .into()
+            }
+            _ => {
+                let a = self.read_all(args)?;
+                self.binary(op, a[0], a[1])?
+            }
+        };
+        self.write(dest, value)
+    }
+
+    // A Bril operation which takes two arguments, which have to be of the type it operates on
+    fn binary(
+        &self,
+        op: ValueOps,
+        lhs: BasicValueEnum<'ctx>,
+        rhs: BasicValueEnum<'ctx>,
+    ) -> Result<BasicValueEnum<'ctx>, CompileError> {
+        let b = &self.builder;
+        match (lhs, rhs) {
+            (BasicValueEnum::IntValue(x), BasicValueEnum::IntValue(y)) => {
+                let compare = |predicate| b.build_int_compare(predicate, x, y, "");
+                Ok(match op {
+                    ValueOps::Add => b.build_int_add(x, y, "")?,
+                    ValueOps::Sub => b.build_int_sub(x, y, "")?,
+                    ValueOps::Mul => b.build_int_mul(x, y, "")?,
+                    ValueOps::Eq => compare(IntPredicate::EQ)?,
+                    ValueOps::Lt => compare(IntPredicate::SLT)?,
+                    ValueOps::Gt => compare(IntPredicate::SGT)?,
+                    ValueOps::Le => compare(IntPredicate::SLE)?,
+                    ValueOps::Ge => compare(IntPredicate::SGE)?,
+                    ValueOps::And => b.build_and(x, y, "")?,
+                    ValueOps::Or => b.build_or(x, y, "")?,
+                    _ => return Err(self.unsupported(&op)),
+                }
+                .into())
+            }
+            (BasicValueEnum::FloatValue(x), BasicValueEnum::FloatValue(y)) => {
+                let compare = |predicate| b.build_float_compare(predicate, x, y, "");
+                Ok(match op {
+                    ValueOps::Fadd => b.build_float_add(x, y, "")?.into(),
+                    ValueOps::Fsub => b.build_float_sub(x, y, "")?.into(),
+                    ValueOps::Fmul => b.build_float_mul(x, y, "")?.into(),
+                    ValueOps::Fdiv => b.build_float_div(x, y, "")?.into(),
+                    ValueOps::Feq => compare(FloatPredicate::OEQ)?.into(),
+                    ValueOps::Flt => compare(FloatPredicate::OLT)?.into(),
+                    ValueOps::Fgt => compare(FloatPredicate::OGT)?.into(),
+                    ValueOps::Fle => compare(FloatPredicate::OLE)?.into(),
+                    ValueOps::Fge => compare(FloatPredicate::OGE)?.into(),
+                    _ => return Err(self.unsupported(&op)),
+                })
+            }
+            _ => Err(self.unsupported(&op)),
+        }
+    }
+
+    fn effect(
+        &self,
+        op: EffectOps,
+        args: &[String],
+        funcs: &[String],
+        labels: &[String],
+    ) -> Result<(), CompileError> {
+        let b = &self.builder;
+        match op {
+            EffectOps::Jump => {
+                b.build_unconditional_branch(self.blocks[&labels[0]])?;
+            }
+            EffectOps::Branch => {
+                let c = self.read(&args[0])?.0.into_int_value();
+                b.build_conditional_branch(c, self.blocks[&labels[0]], self.blocks[&labels[1]])?;
+            }
+            EffectOps::Return => match (args.first(), &self.func.return_type) {
+                (Some(arg), _) => {
+                    b.build_return(Some(&self.read(arg)?.0))?;
+                }
+                (None, None) => {
+                    b.build_return(None)?;
+                }
+                // Falling off the end of a function which should return a value
+                (None, Some(_)) => {
+                    b.build_unreachable()?;
+                }
+            },
+            EffectOps::Call => {
+                self.call(&funcs[0], args)?;
+            }
+            EffectOps::Print => {
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        self.call_runtime("__bril_print_space", &[])?;
+                    }
+                    let (v, ty) = self.read(arg)?;
+                    let print = match ty {
+                        Type::Int => "__bril_print_int",
+                        Type::Bool => "__bril_print_bool",
+                        Type::Float => "__bril_print_float",
+                        _ => {
+                            return Err(CompileError::UnprintableType(
+                                self.func.name.clone(),
+                                ty.clone(),
+                            ))
+                        }
+                    };
+                    self.call_runtime(print, &[v])?;
+                }
+                self.call_runtime("__bril_print_newline", &[])?;
+            }
+            EffectOps::Nop => {}
+            EffectOps::Store => {
+                let p = self.read(&args[0])?.0.into_pointer_value();
+                b.build_store(p, self.read(&args[1])?.0)?;
+            }
+            EffectOps::Free => {
+                let p = self.read(&args[0])?.0;
+                self.call_runtime("free", &[p])?;
+            }
+            EffectOps::Speculate | EffectOps::Commit | EffectOps::Guard | EffectOps::Join => {
+                return Err(self.unsupported(&op))
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,14 @@
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(about, version, author)] // keeps the cli synced with Cargo.toml
+pub struct Cli {
+    /// Include the runtime in the output so that it can be run with `lli` or built with `llc` without linking anything else
+    #[clap(long)]
+    pub runtime: bool,
+
+    /// Optimize the program and write it to this file as an object file for this machine, instead of printing its IR
+    #[cfg(feature = "inkwell")]
+    #[clap(long)]
+    pub object: Option<std::path::PathBuf>,
+}
@@ -0,0 +1,550 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use bril_rs::cfg::{Cfg, CfgError};
+use bril_rs::dataflow::constant_literal;
+use bril_rs::{Code, EffectOps, Function, Instruction, Literal, Program, Type, ValueOps};
+use thiserror::Error;
+
+#[cfg(feature = "inkwell")]
+pub mod builder;
+#[doc(hidden)]
+pub mod cli;
+
+/// The LLVM IR of the runtime which compiled programs call into to print values, read the arguments of `main`, divide, and allocate memory. It only depends on libc.
+pub const RUNTIME: &str = include_str!("runtime.ll");
+
+// What a module needs to declare to call into the runtime when it isn't included
+const RUNTIME_DECLARATIONS: &str = "\
+declare void @__bril_print_int(i64)
+declare void @__bril_print_bool(i1)
+declare void @__bril_print_float(double)
+declare void @__bril_print_space()
+declare void @__bril_print_newline()
+declare void @__bril_check_args(i32, i32)
+declare i64 @__bril_parse_int(ptr)
+declare i1 @__bril_parse_bool(ptr)
+declare double @__bril_parse_float(ptr)
+declare i64 @__bril_div(i64, i64)
+declare i64 @__bril_rem(i64, i64)
+declare ptr @__bril_alloc(i64, i64)
+declare void @free(ptr)
+";
+
+/// The reasons a program can't be compiled, each naming the function it happened in
+// Having the #[error(...)] for all variants derives the Display trait as well
+#[derive(Error, Debug)]
+pub enum CompileError {
+    /// @{0}: `{1}` is not supported
+    #[error("@{0}: `{1}` is not supported")]
+    UnsupportedOp(String, String),
+
+    /// @{0}: values of type `{1}` are not supported
+    #[error("@{0}: values of type `{1}` are not supported")]
+    UnsupportedType(String, Type),
+
+    /// @{0}: values of type `{1}` can't be printed
+    #[error("@{0}: values of type `{1}` can't be printed")]
+    UnprintableType(String, Type),
+
+    /// @{0}: `{1}` is used but never assigned
+    #[error("@{0}: `{1}` is used but never assigned")]
+    UndefinedVariable(String, String),
+
+    /// @{0}: `{1}` is assigned both `{2}` and `{3}`
+    #[error("@{0}: `{1}` is assigned both `{2}` and `{3}`")]
+    ConflictingTypes(String, String, Type, Type),
+
+    /// @{0}: call to unknown function `@{1}`
+    #[error("@{0}: call to unknown function `@{1}`")]
+    UnknownFunction(String, String),
+
+    /// @{0}: a `jmp` or `br` targets a label which the function doesn't have
+    #[error("@{0}: {1}")]
+    Labels(String, CfgError),
+
+    /// Imports have to be linked into the program before it is compiled
+    #[error("imports have to be linked into the program before it is compiled")]
+    Imports,
+
+    /// LLVM rejected the module or couldn't write it out
+    #[cfg(feature = "inkwell")]
+    #[error("LLVM: {0}")]
+    Llvm(String),
+}
+
+/// Compiles ```prog``` to a module of LLVM IR.
+///
+/// With ```runtime```, the module includes [`RUNTIME`] and can be run on its own, and otherwise it only declares the functions of the runtime so that they can be linked in later. The module has a C `main` which reads the arguments of the Bril `main` from the command line, if the program has one.
+///
+/// # Errors
+/// Fails on instructions and types outside of the core language and the memory and float extensions, on variables which are never assigned, and on imports
+pub fn compile(prog: &Program, runtime: bool) -> Result<String, CompileError> {
+    if !prog.imports.is_empty() {
+        return Err(CompileError::Imports);
+    }
+    let functions: HashMap<&str, &Function> = prog
+        .functions
+        .iter()
+        .map(|f| (f.name.as_str(), f))
+        .collect();
+
+    let mut out = String::new();
+    for func in &prog.functions {
+        out.push_str(&FunctionCompiler::new(func, &functions)?.compile()?);
+        out.push('\n');
+    }
+    if let Some(main) = functions.get("main") {
+        out.push_str(&entry_point(main)?);
+        out.push('\n');
+    }
+    out.push_str(if runtime {
+        RUNTIME
+    } else {
+        RUNTIME_DECLARATIONS
+    });
+    Ok(out)
+}
+
+fn llvm_type(func: &Function, ty: &Type) -> Result<&'static str, CompileError> {
+    match ty {
+        Type::Int => Ok("i64"),
+        Type::Bool => Ok("i1"),
+        Type::Float => Ok("double"),
+        Type::Pointer(_) => Ok("ptr"),
+        Type::Char | Type::Str | Type::Thread | Type::Tuple(_) => {
+            Err(CompileError::UnsupportedType(func.name.clone(), ty.clone()))
+        }
+    }
+}
+
+fn return_type(func: &Function) -> Result<&'static str, CompileError> {
+    func.return_type
+        .as_ref()
+        .map_or(Ok("void"), |ty| llvm_type(func, ty))
+}
+
+// Bril functions are prefixed so that they can't clash with libc or the runtime
+fn function_name(name: &str) -> String {
+    format!("@\"bril.{name}\"")
+}
+
+// Reads the arguments of the Bril `main` from the command line before calling it
+fn entry_point(main: &Function) -> Result<String, CompileError> {
+    let mut out = String::new();
+    writeln!(out, "define i32 @main(i32 %argc, ptr %argv) {{").unwrap();
+    writeln!(
+        out,
+        "  call void @__bril_check_args(i32 %argc, i32 {})",
+        main.args.len()
+    )
+    .unwrap();
+    let mut args = Vec::new();
+    for (i, arg) in main.args.iter().enumerate() {
+        let parse = match arg.arg_type {
+            Type::Int => "@__bril_parse_int",
+            Type::Bool => "@__bril_parse_bool",
+            Type::Float => "@__bril_parse_float",
+            _ => {
+                return Err(CompileError::UnsupportedType(
+                    main.name.clone(),
+                    arg.arg_type.clone(),
+                ))
+            }
+        };
+        let ty = llvm_type(main, &arg.arg_type)?;
+        writeln!(
+            out,
+            "  %arg{i}.addr = getelementptr ptr, ptr %argv, i64 {}",
+            i + 1
+        )
+        .unwrap();
+        writeln!(out, "  %arg{i}.text = load ptr, ptr %arg{i}.addr").unwrap();
+        writeln!(out, "  %arg{i} = call {ty} {parse}(ptr %arg{i}.text)").unwrap();
+        args.push(format!("{ty} %arg{i}"));
+    }
+    writeln!(
+        out,
+        "  call {} {}({})",
+        return_type(main)?,
+        function_name(&main.name),
+        args.join(", ")
+    )
+    .unwrap();
+    writeln!(out, "  ret i32 0").unwrap();
+    writeln!(out, "}}").unwrap();
+    Ok(out)
+}
+
+// Compiles a single function. Every variable lives in a stack slot which is loaded from at each use and stored to at each assignment, which LLVM's mem2reg pass turns into registers
+struct FunctionCompiler<'a> {
+    func: &'a Function,
+    functions: &'a HashMap<&'a str, &'a Function>,
+    types: HashMap<&'a str, &'a Type>,
+    out: String,
+    temps: usize,
+}
+
+impl<'a> FunctionCompiler<'a> {
+    fn new(
+        func: &'a Function,
+        functions: &'a HashMap<&'a str, &'a Function>,
+    ) -> Result<Self, CompileError> {
+        let mut types: HashMap<&str, &Type> = HashMap::new();
+        let dests = func.instrs.iter().filter_map(|c| match c {
+            Code::Instruction(Instruction::Constant {
+                dest, const_type, ..
+            }) => Some((dest, const_type)),
+            Code::Instruction(Instruction::Value { dest, op_type, .. }) => Some((dest, op_type)),
+            _ => None,
+        });
+        for (var, ty) in func
+            .args
+            .iter()
+            .map(|a| (&a.name, &a.arg_type))
+            .chain(dests)
+        {
+            llvm_type(func, ty)?;
+            match types.insert(var, ty) {
+                Some(old) if old != ty => {
+                    return Err(CompileError::ConflictingTypes(
+                        func.name.clone(),
+                        var.clone(),
+                        old.clone(),
+                        ty.clone(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+        Ok(Self {
+            func,
+            functions,
+            types,
+            out: String::new(),
+            temps: 0,
+        })
+    }
+
+    fn compile(mut self) -> Result<String, CompileError> {
+        let params = self
+            .func
+            .args
+            .iter()
+            .map(|a| Ok(format!("{} %\"a.{}\"", self.ty(&a.arg_type)?, a.name)))
+            .collect::<Result<Vec<_>, CompileError>>()?;
+        writeln!(
+            self.out,
+            "define {} {}({}) {{",
+            return_type(self.func)?,
+            function_name(&self.func.name),
+            params.join(", ")
+        )
+        .unwrap();
+        writeln!(self.out, "entry:").unwrap();
+        let mut vars: Vec<(&str, &Type)> = self.types.iter().map(|(v, t)| (*v, *t)).collect();
+        vars.sort_unstable_by_key(|(v, _)| *v);
+        for (var, ty) in vars {
+            writeln!(self.out, "  {} = alloca {}", slot(var), self.ty(ty)?).unwrap();
+        }
+        for arg in &self.func.args {
+            writeln!(
+                self.out,
+                "  store {} %\"a.{}\", ptr {}",
+                self.ty(&arg.arg_type)?,
+                arg.name,
+                slot(&arg.name)
+            )
+            .unwrap();
+        }
+
+        let cfg = Cfg::from_function(self.func)
+            .map_err(|e| CompileError::Labels(self.func.name.clone(), e))?;
+        writeln!(self.out, "  br label {}", label(&cfg.blocks[0].label)).unwrap();
+        for block in &cfg.blocks {
+            writeln!(self.out, "\"l.{}\":", block.label).unwrap();
+            for instr in &block.instrs {
+                self.instruction(instr)?;
+            }
+        }
+        writeln!(self.out, "}}").unwrap();
+        Ok(self.out)
+    }
+
+    fn ty(&self, ty: &Type) -> Result<&'static str, CompileError> {
+        llvm_type(self.func, ty)
+    }
+
+    fn unsupported(&self, op: &impl ToString) -> CompileError {
+        CompileError::UnsupportedOp(self.func.name.clone(), op.to_string())
+    }
+
+    fn temp(&mut self) -> String {
+        self.temps += 1;
+        format!("%t{}", self.temps - 1)
+    }
+
+    fn var_type(&self, var: &str) -> Result<&'a Type, CompileError> {
+        self.types
+            .get(var)
+            .copied()
+            .ok_or_else(|| CompileError::UndefinedVariable(self.func.name.clone(), var.to_string()))
+    }
+
+    // Loads ```var``` into a fresh temporary, returning it along with its type
+    fn read(&mut self, var: &str) -> Result<(String, &'a Type), CompileError> {
+        let ty = self.var_type(var)?;
+        let t = self.temp();
+        writeln!(self.out, "  {t} = load {}, ptr {}", self.ty(ty)?, slot(var)).unwrap();
+        Ok((t, ty))
+    }
+
+    fn read_all(&mut self, args: &[String]) -> Result<Vec<String>, CompileError> {
+        args.iter().map(|a| Ok(self.read(a)?.0)).collect()
+    }
+
+    fn write(&mut self, dest: &str, ty: &Type, value: &str) -> Result<(), CompileError> {
+        writeln!(
+            self.out,
+            "  store {} {value}, ptr {}",
+            self.ty(ty)?,
+            slot(dest)
+        )
+        .unwrap();
+        Ok(())
+    }
+
+    fn call(&mut self, name: &str, args: &[String]) -> Result<String, CompileError> {
+        let callee = self.functions.get(name).ok_or_else(|| {
+            CompileError::UnknownFunction(self.func.name.clone(), name.to_string())
+        })?;
+        let mut values = Vec::new();
+        for arg in args {
+            let (t, ty) = self.read(arg)?;
+            values.push(format!("{} {t}", self.ty(ty)?));
+        }
+        Ok(format!(
+            "call {} {}({})",
+            return_type(callee)?,
+            function_name(name),
+            values.join(", ")
+        ))
+    }
+
+    fn instruction(&mut self, instr: &Instruction) -> Result<(), CompileError> {
+        match instr {
+            Instruction::Constant {
+                dest,
+                const_type,
+                value,
+                ..
+            } => {
+                let value = match constant_literal(const_type, value) {
+                    Literal::Float(f) => float(f),
+                    Literal::Int(i) => i.to_string(),
+                    Literal::Bool(b) => b.to_string(),
+                    Literal::Char(_) | Literal::Str(_) => {
+                        return Err(CompileError::UnsupportedType(
+                            self.func.name.clone(),
+                            const_type.clone(),
+                        ))
+                    }
+                };
+                self.write(dest, const_type, &value)
+            }
+            Instruction::Value {
+                args,
+                dest,
+                funcs,
+                op,
+                op_type,
+                ..
+            } => self.value(*op, args, funcs, dest, op_type),
+            Instruction::Effect {
+                args,
+                funcs,
+                labels,
+                op,
+                ..
+            } => self.effect(*op, args, funcs, labels),
+        }
+    }
+
+    fn value(
+        &mut self,
+        op: ValueOps,
+        args: &[String],
+        funcs: &[String],
+        dest: &str,
+        op_type: &Type,
+    ) -> Result<(), CompileError> {
+        let rhs = match op {
+            ValueOps::Id => {
+                let (t, _) = self.read(&args[0])?;
+                return self.write(dest, op_type, &t);
+            }
+            ValueOps::Call => self.call(&funcs[0], args)?,
+            ValueOps::Div => {
+                let a = self.read_all(args)?;
+                format!("call i64 @__bril_div(i64 {}, i64 {})", a[0], a[1])
+            }
+            ValueOps::Rem => {
+                let a = self.read_all(args)?;
+                format!("call i64 @__bril_rem(i64 {}, i64 {})", a[0], a[1])
+            }
+            ValueOps::Not => format!("xor i1 {}, true", self.read(&args[0])?.0),
+            ValueOps::Float2bits => format!("bitcast double {} to i64", self.read(&args[0])?.0),
+            ValueOps::Bits2float => format!("bitcast i64 {} to double", self.read(&args[0])?.0),
+            ValueOps::Alloc => {
+                let Type::Pointer(pointee) = op_type else {
+                    return Err(self.unsupported(&op));
+                };
+                let count = self.read(&args[0])?.0;
+                let size = if **pointee == Type::Bool { 1 } else { 8 };
+                format!("call ptr @__bril_alloc(i64 {count}, i64 {size})")
+            }
+            ValueOps::Load => {
+                let p = self.read(&args[0])?.0;
+                format!("load {}, ptr {p}", self.ty(op_type)?)
+            }
+            ValueOps::PtrAdd => {
+                let Type::Pointer(pointee) = op_type else {
+                    return Err(self.unsupported(&op));
+                };
+                let a = self.read_all(args)?;
+                format!(
+                    "getelementptr {}, ptr {}, i64 {}",
+                    self.ty(pointee)?,
+                    a[0],
+                    a[1]
+                )
+            }
+            _ => {
+                let (llvm_op, ty) = binary_op(op).ok_or_else(|| self.unsupported(&op))?;
+                let a = self.read_all(args)?;
+                format!("{llvm_op} {ty} {}, {}", a[0], a[1])
+            }
+        };
+        let t = self.temp();
+        writeln!(self.out, "  {t} = {rhs}").unwrap();
+        self.write(dest, op_type, &t)
+    }
+
+    fn effect(
+        &mut self,
+        op: EffectOps,
+        args: &[String],
+        funcs: &[String],
+        labels: &[String],
+    ) -> Result<(), CompileError> {
+        match op {
+            EffectOps::Jump => {
+                writeln!(self.out, "  br label {}", label(&labels[0])).unwrap();
+            }
+            EffectOps::Branch => {
+                let c = self.read(&args[0])?.0;
+                writeln!(
+                    self.out,
+                    "  br i1 {c}, label {}, label {}",
+                    label(&labels[0]),
+                    label(&labels[1])
+                )
+                .unwrap();
+            }
+            EffectOps::Return => match (args.first(), &self.func.return_type) {
+                (Some(arg), _) => {
+                    let (t, ty) = self.read(arg)?;
+                    writeln!(self.out, "  ret {} {t}", self.ty(ty)?).unwrap();
+                }
+                (None, None) => writeln!(self.out, "  ret void").unwrap(),
+                // Falling off the end of a function which should return a value
+                (None, Some(_)) => writeln!(self.out, "  unreachable").unwrap(),
+            },
+            EffectOps::Call => {
+                let call = self.call(&funcs[0], args)?;
+                writeln!(self.out, "  {call}").unwrap();
+            }
+            EffectOps::Print => {
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(self.out, "  call void @__bril_print_space()").unwrap();
+                    }
+                    let (t, ty) = self.read(arg)?;
+                    let print = match ty {
+                        Type::Int => "@__bril_print_int",
+                        Type::Bool => "@__bril_print_bool",
+                        Type::Float => "@__bril_print_float",
+                        _ => {
+                            return Err(CompileError::UnprintableType(
+                                self.func.name.clone(),
+                                ty.clone(),
+                            ))
+                        }
+                    };
+                    writeln!(self.out, "  call void {print}({} {t})", self.ty(ty)?).unwrap();
+                }
+                writeln!(self.out, "  call void @__bril_print_newline()").unwrap();
+            }
+            EffectOps::Nop => {}
+            EffectOps::Store => {
+                let p = self.read(&args[0])?.0;
+                let (v, ty) = self.read(&args[1])?;
+                writeln!(self.out, "  store {} {v}, ptr {p}", self.ty(ty)?).unwrap();
+            }
+            EffectOps::Free => {
+                let p = self.read(&args[0])?.0;
+                writeln!(self.out, "  call void @free(ptr {p})").unwrap();
+            }
+            EffectOps::Speculate | EffectOps::Commit | EffectOps::Guard | EffectOps::Join => {
+                return Err(self.unsupported(&op))
+            }
+        }
+        Ok(())
+    }
+}
+
+// The LLVM instruction for a Bril operation which takes two arguments, along with the type of the arguments
+const fn binary_op(op: ValueOps) -> Option<(&'static str, &'static str)> {
+    Some(match op {
+        ValueOps::Add => ("add", "i64"),
+        ValueOps::Sub => ("sub", "i64"),
+        ValueOps::Mul => ("mul", "i64"),
+        ValueOps::Eq => ("icmp eq", "i64"),
+        ValueOps::Lt => ("icmp slt", "i64"),
+        ValueOps::Gt => ("icmp sgt", "i64"),
+        ValueOps::Le => ("icmp sle", "i64"),
+        ValueOps::Ge => ("icmp sge", "i64"),
+        ValueOps::And => ("and", "i1"),
+        ValueOps::Or => ("or", "i1"),
+        ValueOps::Fadd => ("fadd", "double"),
+        ValueOps::Fsub => ("fsub", "double"),
+        ValueOps::Fmul => ("fmul", "double"),
+        ValueOps::Fdiv => ("fdiv", "double"),
+        ValueOps::Feq => ("fcmp oeq", "double"),
+        ValueOps::Flt => ("fcmp olt", "double"),
+        ValueOps::Fgt => ("fcmp ogt", "double"),
+        ValueOps::Fle => ("fcmp ole", "double"),
+        ValueOps::Fge => ("fcmp oge", "double"),
+        _ => return None,
+    })
+}
+
+// Variables, arguments, and labels are prefixed so that they can't clash with each other or the temporaries
+fn slot(var: &str) -> String {
+    format!("%\"v.{var}\"")
+}
+
+fn label(label: &str) -> String {
+    format!("%\"l.{label}\"")
+}
+
+// LLVM reads a float written as the hex of its bits exactly
+fn float(f: f64) -> String {
+    format!("0x{:016X}", f.to_bits())
+}
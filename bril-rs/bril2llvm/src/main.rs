@@ -0,0 +1,29 @@
+use bril2llvm::cli::Cli;
+use bril2llvm::CompileError;
+use bril_rs::load_program;
+use clap::Parser;
+
+#[cfg(not(feature = "inkwell"))]
+fn run(args: &Cli) -> Result<(), CompileError> {
+    print!("{}", bril2llvm::compile(&load_program(), args.runtime)?);
+    Ok(())
+}
+
+#[cfg(feature = "inkwell")]
+fn run(args: &Cli) -> Result<(), CompileError> {
+    let context = inkwell::context::Context::create();
+    let module = bril2llvm::builder::compile_module(&context, &load_program(), args.runtime)?;
+    match &args.object {
+        Some(path) => bril2llvm::builder::write_object(&module, path)?,
+        None => print!("{}", module.print_to_string().to_string()),
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = Cli::parse();
+    if let Err(e) = run(&args) {
+        eprintln!("error: {e}");
+        std::process::exit(1)
+    }
+}
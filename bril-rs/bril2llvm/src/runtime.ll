@@ -0,0 +1,283 @@
+; The runtime of programs compiled by bril2llvm, which prints values, reads the
+; arguments of main, and reports errors the way brilirs does on top of libc.
+
+declare i32 @printf(ptr, ...)
+declare i32 @dprintf(i32, ptr, ...)
+declare i32 @putchar(i32)
+declare i32 @snprintf(ptr, i64, ptr, ...)
+declare double @strtod(ptr, ptr)
+declare i64 @strtol(ptr, ptr, i32)
+declare i32 @strcmp(ptr, ptr)
+declare i32 @atoi(ptr)
+declare ptr @malloc(i64)
+declare void @free(ptr)
+declare void @exit(i32)
+
+@__bril_true = private unnamed_addr constant [5 x i8] c"true\00"
+@__bril_false = private unnamed_addr constant [6 x i8] c"false\00"
+@__bril_nan = private unnamed_addr constant [4 x i8] c"NaN\00"
+@__bril_infinity = private unnamed_addr constant [9 x i8] c"Infinity\00"
+@__bril_int_format = private unnamed_addr constant [4 x i8] c"%ld\00"
+@__bril_str_format = private unnamed_addr constant [3 x i8] c"%s\00"
+@__bril_exp_format = private unnamed_addr constant [5 x i8] c"%.*e\00"
+@__bril_args_error = private unnamed_addr constant [53 x i8] c"error: Expected `%d` function arguments, found `%d`\0A\00"
+@__bril_div_error = private unnamed_addr constant [25 x i8] c"error: division by zero\0A\00"
+@__bril_alloc_error = private unnamed_addr constant [38 x i8] c"error: cannot allocate `%ld` entries\0A\00"
+
+define void @__bril_print_int(i64 %x) {
+  call i32 (ptr, ...) @printf(ptr @__bril_int_format, i64 %x)
+  ret void
+}
+
+define void @__bril_print_bool(i1 %b) {
+  %s = select i1 %b, ptr @__bril_true, ptr @__bril_false
+  call i32 (ptr, ...) @printf(ptr @__bril_str_format, ptr %s)
+  ret void
+}
+
+define void @__bril_print_space() {
+  call i32 @putchar(i32 32)
+  ret void
+}
+
+define void @__bril_print_newline() {
+  call i32 @putchar(i32 10)
+  ret void
+}
+
+; Prints the bytes of s from start up to but not including end
+define private void @__bril_print_range(ptr %s, i64 %start, i64 %end) {
+entry:
+  %i = alloca i64
+  store i64 %start, ptr %i
+  br label %loop
+loop:
+  %cur = load i64, ptr %i
+  %done = icmp sge i64 %cur, %end
+  br i1 %done, label %exit, label %body
+body:
+  %p = getelementptr i8, ptr %s, i64 %cur
+  %c = load i8, ptr %p
+  %c32 = zext i8 %c to i32
+  call i32 @putchar(i32 %c32)
+  %next = add i64 %cur, 1
+  store i64 %next, ptr %i
+  br label %loop
+exit:
+  ret void
+}
+
+define private void @__bril_print_zeros(i64 %count) {
+entry:
+  %i = alloca i64
+  store i64 0, ptr %i
+  br label %loop
+loop:
+  %cur = load i64, ptr %i
+  %done = icmp sge i64 %cur, %count
+  br i1 %done, label %exit, label %body
+body:
+  call i32 @putchar(i32 48)
+  %next = add i64 %cur, 1
+  store i64 %next, ptr %i
+  br label %loop
+exit:
+  ret void
+}
+
+; Prints floats like JavaScript's Number.prototype.toString, which is what brili
+; and brilirs do: the shortest digits which read back as the same float, written
+; out in full unless the exponent is at least 21 or less than -6.
+define void @__bril_print_float(double %v) {
+entry:
+  %isnan = fcmp uno double %v, %v
+  br i1 %isnan, label %nan, label %notnan
+nan:
+  call i32 (ptr, ...) @printf(ptr @__bril_str_format, ptr @__bril_nan)
+  ret void
+notnan:
+  %iszero = fcmp oeq double %v, 0.0
+  br i1 %iszero, label %zero, label %nonzero
+zero:
+  ; Including negative zero
+  call i32 @putchar(i32 48)
+  ret void
+nonzero:
+  %neg = fcmp olt double %v, 0.0
+  br i1 %neg, label %minus, label %positive
+minus:
+  call i32 @putchar(i32 45)
+  br label %positive
+positive:
+  %negated = fneg double %v
+  %a = select i1 %neg, double %negated, double %v
+  %isinf = fcmp oeq double %a, 0x7FF0000000000000
+  br i1 %isinf, label %inf, label %finite
+inf:
+  call i32 (ptr, ...) @printf(ptr @__bril_str_format, ptr @__bril_infinity)
+  ret void
+finite:
+  ; Find the fewest digits after the point in "%.*e" which read back as the same float
+  %buf = alloca [32 x i8]
+  %precision = alloca i32
+  store i32 0, ptr %precision
+  br label %search
+search:
+  %p = load i32, ptr %precision
+  call i32 (ptr, i64, ptr, ...) @snprintf(ptr %buf, i64 32, ptr @__bril_exp_format, i32 %p, double %a)
+  %back = call double @strtod(ptr %buf, ptr null)
+  %same = fcmp oeq double %back, %a
+  %next = add i32 %p, 1
+  store i32 %next, ptr %precision
+  br i1 %same, label %found, label %search
+found:
+  ; buf is "d.ddde+x", or "de+x" with no digits after the point, and k is the number of digits
+  %p64 = sext i32 %p to i64
+  %k = add i64 %p64, 1
+  %noint = icmp eq i32 %p, 0
+  %epos.frac = add i64 %p64, 2
+  %epos = select i1 %noint, i64 1, i64 %epos.frac
+  %expstart = add i64 %epos, 1
+  %exptext = getelementptr i8, ptr %buf, i64 %expstart
+  %exp32 = call i32 @atoi(ptr %exptext)
+  %exp = sext i32 %exp32 to i64
+  ; The decimal point goes after the first n digits
+  %n = add i64 %exp, 1
+  ; Gather the digits without the point
+  %digits = alloca [17 x i8]
+  %first = load i8, ptr %buf
+  store i8 %first, ptr %digits
+  %fracsrc = getelementptr i8, ptr %buf, i64 2
+  %fracdst = getelementptr i8, ptr %digits, i64 1
+  call void @llvm.memcpy.p0.p0.i64(ptr %fracdst, ptr %fracsrc, i64 %p64, i1 false)
+  %npos = icmp sgt i64 %n, 0
+  %nsmall = icmp sle i64 %n, 21
+  %plain = and i1 %npos, %nsmall
+  br i1 %plain, label %fixed, label %notfixed
+fixed:
+  ; Either all of the digits followed by zeros, or the digits split by the point
+  %split = icmp slt i64 %n, %k
+  %intlen = select i1 %split, i64 %n, i64 %k
+  call void @__bril_print_range(ptr %digits, i64 0, i64 %intlen)
+  %zeros = sub i64 %n, %intlen
+  call void @__bril_print_zeros(i64 %zeros)
+  br i1 %split, label %fixedfrac, label %done
+fixedfrac:
+  call i32 @putchar(i32 46)
+  call void @__bril_print_range(ptr %digits, i64 %n, i64 %k)
+  br label %done
+notfixed:
+  %nbig = icmp sgt i64 %n, -6
+  %leading = and i1 %nbig, %nsmall
+  br i1 %leading, label %small, label %exponent
+small:
+  call i32 @putchar(i32 48)
+  call i32 @putchar(i32 46)
+  %leadzeros = sub i64 0, %n
+  call void @__bril_print_zeros(i64 %leadzeros)
+  call void @__bril_print_range(ptr %digits, i64 0, i64 %k)
+  br label %done
+exponent:
+  call void @__bril_print_range(ptr %digits, i64 0, i64 1)
+  %hasfrac = icmp sgt i64 %k, 1
+  br i1 %hasfrac, label %expfrac, label %expsign
+expfrac:
+  call i32 @putchar(i32 46)
+  call void @__bril_print_range(ptr %digits, i64 1, i64 %k)
+  br label %expsign
+expsign:
+  call i32 @putchar(i32 101)
+  %exppos = icmp sgt i64 %n, 0
+  %signchar = select i1 %exppos, i32 43, i32 45
+  call i32 @putchar(i32 %signchar)
+  %expneg = sub i64 0, %exp
+  %expabs = select i1 %exppos, i64 %exp, i64 %expneg
+  call i32 (ptr, ...) @printf(ptr @__bril_int_format, i64 %expabs)
+  br label %done
+done:
+  ret void
+}
+
+declare void @llvm.memcpy.p0.p0.i64(ptr, ptr, i64, i1)
+
+define void @__bril_check_args(i32 %argc, i32 %expected) {
+entry:
+  %found = sub i32 %argc, 1
+  %ok = icmp eq i32 %found, %expected
+  br i1 %ok, label %done, label %error
+error:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @__bril_args_error, i32 %expected, i32 %found)
+  call void @exit(i32 2)
+  unreachable
+done:
+  ret void
+}
+
+define i64 @__bril_parse_int(ptr %s) {
+  %x = call i64 @strtol(ptr %s, ptr null, i32 10)
+  ret i64 %x
+}
+
+define i1 @__bril_parse_bool(ptr %s) {
+  %cmp = call i32 @strcmp(ptr %s, ptr @__bril_true)
+  %b = icmp eq i32 %cmp, 0
+  ret i1 %b
+}
+
+define double @__bril_parse_float(ptr %s) {
+  %x = call double @strtod(ptr %s, ptr null)
+  ret double %x
+}
+
+; Integer division which wraps around like brili instead of overflowing
+define i64 @__bril_div(i64 %a, i64 %b) {
+entry:
+  %byzero = icmp eq i64 %b, 0
+  br i1 %byzero, label %error, label %nonzero
+error:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @__bril_div_error)
+  call void @exit(i32 2)
+  unreachable
+nonzero:
+  %byminus = icmp eq i64 %b, -1
+  br i1 %byminus, label %negate, label %divide
+negate:
+  %negated = sub i64 0, %a
+  ret i64 %negated
+divide:
+  %q = sdiv i64 %a, %b
+  ret i64 %q
+}
+
+; The remainder of __bril_div, which has the sign of %a
+define i64 @__bril_rem(i64 %a, i64 %b) {
+entry:
+  %byzero = icmp eq i64 %b, 0
+  br i1 %byzero, label %error, label %nonzero
+error:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @__bril_div_error)
+  call void @exit(i32 2)
+  unreachable
+nonzero:
+  %byminus = icmp eq i64 %b, -1
+  br i1 %byminus, label %zero, label %divide
+zero:
+  ret i64 0
+divide:
+  %r = srem i64 %a, %b
+  ret i64 %r
+}
+
+define ptr @__bril_alloc(i64 %count, i64 %size) {
+entry:
+  %positive = icmp sgt i64 %count, 0
+  br i1 %positive, label %alloc, label %error
+error:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @__bril_alloc_error, i64 %count)
+  call void @exit(i32 2)
+  unreachable
+alloc:
+  %bytes = mul i64 %count, %size
+  %p = call ptr @malloc(i64 %bytes)
+  ret ptr %p
+}
@@ -0,0 +1,184 @@
+use std::fmt;
+use std::io::{BufWriter, Read, StdoutLock, Write};
+
+use bril2wasm::cli::RunCli;
+use bril2wasm::{ERROR_ALLOCATION, ERROR_DIVISION_BY_ZERO, HOST_MODULE};
+use clap::Parser;
+use wasmtime::{Caller, Engine, Linker, Module, Store, Val, ValType};
+
+type Out = BufWriter<StdoutLock<'static>>;
+
+// An error which the program stopped itself with by calling `error`
+#[derive(Debug)]
+struct Stop(String);
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Stop {}
+
+fn fail(message: impl fmt::Display, code: i32) -> ! {
+    eprintln!("error: {message}");
+    std::process::exit(code)
+}
+
+fn main() {
+    let args = RunCli::parse();
+    let bytes = match &args.file {
+        Some(path) => std::fs::read(path).unwrap_or_else(|e| fail(e, 1)),
+        None => {
+            let mut bytes = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut bytes)
+                .unwrap_or_else(|e| fail(e, 1));
+            bytes
+        }
+    };
+    let engine = Engine::default();
+    let module = Module::new(&engine, &bytes).unwrap_or_else(|e| fail(e, 1));
+    let mut linker = Linker::new(&engine);
+    link(&mut linker).unwrap_or_else(|e| fail(e, 1));
+    let mut store = Store::new(&engine, BufWriter::new(std::io::stdout().lock()));
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .unwrap_or_else(|e| fail(e, 1));
+    let Some(main) = instance.get_func(&mut store, "main") else {
+        fail("the module doesn't export a main function", 1)
+    };
+
+    let ty = main.ty(&store);
+    if ty.params().len() != args.args.len() {
+        fail(
+            format!(
+                "Expected `{}` function arguments, found `{}`",
+                ty.params().len(),
+                args.args.len()
+            ),
+            2,
+        );
+    }
+    // The types of the parameters are enough to tell the Bril types apart, since main can't take a pointer
+    let params: Vec<Val> = ty
+        .params()
+        .zip(&args.args)
+        .map(|(param, arg)| {
+            let parsed = match param {
+                ValType::I64 => arg.parse().ok().map(Val::I64),
+                ValType::I32 => match arg.as_str() {
+                    "true" => Some(Val::I32(1)),
+                    "false" => Some(Val::I32(0)),
+                    _ => None,
+                },
+                ValType::F64 => arg.parse::<f64>().ok().map(|f| Val::F64(f.to_bits())),
+                _ => None,
+            };
+            parsed.unwrap_or_else(|| fail(format!("couldn't parse the argument `{arg}`"), 2))
+        })
+        .collect();
+    let mut results: Vec<Val> = ty.results().map(|_| Val::I32(0)).collect();
+    let result = main.call(&mut store, &params, &mut results);
+    store.data_mut().flush().unwrap();
+    if let Err(e) = result {
+        match e.downcast_ref::<Stop>() {
+            Some(stop) => fail(stop, 2),
+            None => fail(e, 2),
+        }
+    }
+}
+
+// Defines the functions which compiled programs import
+fn link(linker: &mut Linker<Out>) -> wasmtime::Result<()> {
+    linker.func_wrap(
+        HOST_MODULE,
+        "print_int",
+        |mut caller: Caller<'_, Out>, x: i64| {
+            write!(caller.data_mut(), "{x}").map_err(wasmtime::Error::from)
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "print_bool",
+        |mut caller: Caller<'_, Out>, b: i32| {
+            write!(caller.data_mut(), "{}", b != 0).map_err(wasmtime::Error::from)
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "print_float",
+        |mut caller: Caller<'_, Out>, f: f64| {
+            write!(caller.data_mut(), "{}", format_float(f)).map_err(wasmtime::Error::from)
+        },
+    )?;
+    linker.func_wrap(HOST_MODULE, "print_space", |mut caller: Caller<'_, Out>| {
+        write!(caller.data_mut(), " ").map_err(wasmtime::Error::from)
+    })?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "print_newline",
+        |mut caller: Caller<'_, Out>| writeln!(caller.data_mut()).map_err(wasmtime::Error::from),
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "error",
+        |_: Caller<'_, Out>, code: i32, value: i64| -> wasmtime::Result<()> {
+            let message = match code {
+                ERROR_DIVISION_BY_ZERO => "division by zero".to_string(),
+                ERROR_ALLOCATION => format!("cannot allocate `{value}` entries"),
+                _ => "out of memory".to_string(),
+            };
+            Err(wasmtime::Error::new(Stop(message)))
+        },
+    )?;
+    Ok(())
+}
+
+// Formats ```v``` the same way as JavaScript's `Number.prototype.toString`, which is how brili and brilirs print floats
+fn format_float(v: f64) -> String {
+    if v.is_nan() {
+        return "NaN".to_string();
+    } else if v == 0.0 {
+        // Including negative zero
+        return "0".to_string();
+    } else if v.is_infinite() {
+        return if v > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+
+    // LowerExp gives the shortest digits which round trip, as in "1.2345e-7"
+    let sign = if v < 0.0 { "-" } else { "" };
+    let shortest = format!("{:e}", v.abs());
+    // There can be more than one number with that many digits which rounds trip, and JavaScript picks the one closest to ```v```, which is ```v``` rounded to that many digits as long as it still round trips
+    let k = shortest.split_once('e').unwrap().0.replace('.', "").len();
+    let closest = format!("{:.*e}", k - 1, v.abs());
+    let exp_form = if closest.parse() == Ok(v.abs()) {
+        closest
+    } else {
+        shortest
+    };
+    let (mantissa, exp) = exp_form.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i64;
+    // The decimal point goes after the first n digits
+    let n = exp.parse::<i64>().unwrap() + 1;
+
+    let body = if k <= n && n <= 21 {
+        format!("{digits}{}", "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        let (int, frac) = digits.split_at(n as usize);
+        format!("{int}.{frac}")
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{digits}", "0".repeat(-n as usize))
+    } else {
+        let exp_sign = if n > 0 { "+" } else { "-" };
+        let (first, rest) = digits.split_at(1);
+        let rest = if rest.is_empty() {
+            String::new()
+        } else {
+            format!(".{rest}")
+        };
+        format!("{first}{rest}e{exp_sign}{}", (n - 1).abs())
+    };
+    format!("{sign}{body}")
+}
@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(about, version, author)] // keeps the cli synced with Cargo.toml
+pub struct Cli {}
+
+#[derive(Parser)]
+#[clap(
+    name = "bril-wasm-run",
+    about = "Runs the main function of a module compiled by bril2wasm with wasmtime",
+    version,
+    author
+)]
+#[clap(allow_hyphen_values(true))]
+pub struct RunCli {
+    /// The module to run, which is read from stdin if this isn't given
+    #[clap(short, long)]
+    pub file: Option<PathBuf>,
+
+    /// The arguments of main
+    pub args: Vec<String>,
+}
@@ -0,0 +1,210 @@
+// The parts of the WebAssembly binary format which compiled programs use, from https://webassembly.github.io/spec/core/binary/index.html
+
+pub const I32: u8 = 0x7f;
+pub const I64: u8 = 0x7e;
+pub const F64: u8 = 0x7c;
+// The type of a block, loop, or if which takes and leaves nothing on the stack
+pub const EMPTY: u8 = 0x40;
+
+pub const UNREACHABLE: u8 = 0x00;
+pub const BLOCK: u8 = 0x02;
+pub const LOOP: u8 = 0x03;
+pub const IF: u8 = 0x04;
+pub const ELSE: u8 = 0x05;
+pub const END: u8 = 0x0b;
+pub const BR: u8 = 0x0c;
+pub const BR_TABLE: u8 = 0x0e;
+pub const RETURN: u8 = 0x0f;
+pub const CALL: u8 = 0x10;
+pub const DROP: u8 = 0x1a;
+pub const LOCAL_GET: u8 = 0x20;
+pub const LOCAL_SET: u8 = 0x21;
+pub const LOCAL_TEE: u8 = 0x22;
+pub const GLOBAL_GET: u8 = 0x23;
+pub const GLOBAL_SET: u8 = 0x24;
+pub const I32_LOAD: u8 = 0x28;
+pub const I64_LOAD: u8 = 0x29;
+pub const F64_LOAD: u8 = 0x2b;
+pub const I32_STORE: u8 = 0x36;
+pub const I64_STORE: u8 = 0x37;
+pub const F64_STORE: u8 = 0x39;
+pub const MEMORY_SIZE: u8 = 0x3f;
+pub const MEMORY_GROW: u8 = 0x40;
+pub const I32_CONST: u8 = 0x41;
+pub const I64_CONST: u8 = 0x42;
+pub const F64_CONST: u8 = 0x44;
+pub const I32_EQZ: u8 = 0x45;
+pub const I32_EQ: u8 = 0x46;
+pub const I64_EQZ: u8 = 0x50;
+pub const I64_EQ: u8 = 0x51;
+pub const I64_LT_S: u8 = 0x53;
+pub const I64_GT_S: u8 = 0x55;
+pub const I64_GT_U: u8 = 0x56;
+pub const I64_LE_S: u8 = 0x57;
+pub const I64_GE_S: u8 = 0x59;
+pub const F64_EQ: u8 = 0x61;
+pub const F64_LT: u8 = 0x63;
+pub const F64_GT: u8 = 0x64;
+pub const F64_LE: u8 = 0x65;
+pub const F64_GE: u8 = 0x66;
+pub const I32_ADD: u8 = 0x6a;
+pub const I32_AND: u8 = 0x71;
+pub const I32_OR: u8 = 0x72;
+pub const I64_ADD: u8 = 0x7c;
+pub const I64_SUB: u8 = 0x7d;
+pub const I64_MUL: u8 = 0x7e;
+pub const I64_DIV_S: u8 = 0x7f;
+pub const I64_REM_S: u8 = 0x81;
+pub const I64_SHL: u8 = 0x86;
+pub const I64_SHR_U: u8 = 0x88;
+pub const F64_ADD: u8 = 0xa0;
+pub const F64_SUB: u8 = 0xa1;
+pub const F64_MUL: u8 = 0xa2;
+pub const F64_DIV: u8 = 0xa3;
+pub const I32_WRAP_I64: u8 = 0xa7;
+pub const I64_EXTEND_I32_U: u8 = 0xad;
+pub const I64_REINTERPRET_F64: u8 = 0xbd;
+pub const F64_REINTERPRET_I64: u8 = 0xbf;
+
+pub const TYPE_SECTION: u8 = 1;
+pub const IMPORT_SECTION: u8 = 2;
+pub const FUNCTION_SECTION: u8 = 3;
+pub const MEMORY_SECTION: u8 = 5;
+pub const GLOBAL_SECTION: u8 = 6;
+pub const EXPORT_SECTION: u8 = 7;
+pub const CODE_SECTION: u8 = 10;
+
+pub const FUNC: u8 = 0x00;
+pub const MEMORY: u8 = 0x02;
+
+// Integers are written in LEB128, 7 bits to a byte starting with the lowest, so only the low bits are kept by each cast
+#[allow(clippy::cast_possible_truncation)]
+pub fn u32(out: &mut Vec<u8>, mut v: u32) {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn i64(out: &mut Vec<u8>, mut v: i64) {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        // Done once the rest is all copies of the sign bit of what was just written
+        if (v == 0 && byte & 0x40 == 0) || (v == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+pub fn len(out: &mut Vec<u8>, n: usize) {
+    u32(out, u32::try_from(n).expect("more than u32::MAX items"));
+}
+
+pub fn name(out: &mut Vec<u8>, name: &str) {
+    len(out, name.len());
+    out.extend_from_slice(name.as_bytes());
+}
+
+// A section is its id followed by its contents, which are prefixed with their size
+pub fn section(out: &mut Vec<u8>, id: u8, count: usize, contents: &[u8]) {
+    let mut body = Vec::new();
+    len(&mut body, count);
+    body.extend_from_slice(contents);
+    out.push(id);
+    len(out, body.len());
+    out.extend(body);
+}
+
+// A function type with ```params``` and ```results```
+pub fn func_type(out: &mut Vec<u8>, params: &[u8], results: &[u8]) {
+    out.push(0x60);
+    len(out, params.len());
+    out.extend_from_slice(params);
+    len(out, results.len());
+    out.extend_from_slice(results);
+}
+
+/// The instructions of a function body, along with the types of the locals it has on top of its parameters
+#[derive(Default)]
+pub struct Body {
+    pub locals: Vec<u8>,
+    pub code: Vec<u8>,
+}
+
+impl Body {
+    pub fn op(&mut self, op: u8) -> &mut Self {
+        self.code.push(op);
+        self
+    }
+
+    // An instruction which takes a single index, like `local.get` or `call`
+    pub fn with_index(&mut self, op: u8, index: u32) -> &mut Self {
+        self.code.push(op);
+        u32(&mut self.code, index);
+        self
+    }
+
+    // `block`, `loop`, or `if` with ```block_type```
+    pub fn block(&mut self, op: u8, block_type: u8) -> &mut Self {
+        self.code.extend([op, block_type]);
+        self
+    }
+
+    pub fn i32_const(&mut self, v: i32) -> &mut Self {
+        self.code.push(I32_CONST);
+        i64(&mut self.code, v.into());
+        self
+    }
+
+    pub fn i64_const(&mut self, v: i64) -> &mut Self {
+        self.code.push(I64_CONST);
+        i64(&mut self.code, v);
+        self
+    }
+
+    pub fn f64_const(&mut self, v: f64) -> &mut Self {
+        self.code.push(F64_CONST);
+        self.code.extend(v.to_le_bytes());
+        self
+    }
+
+    // A load or store of ```size``` bytes at the address on the stack, which is always aligned
+    pub fn memory(&mut self, op: u8, size: u32) -> &mut Self {
+        self.code.push(op);
+        u32(&mut self.code, size.trailing_zeros());
+        u32(&mut self.code, 0);
+        self
+    }
+
+    pub fn br_table(&mut self, targets: &[u32], default: u32) -> &mut Self {
+        self.code.push(BR_TABLE);
+        len(&mut self.code, targets.len());
+        for t in targets {
+            u32(&mut self.code, *t);
+        }
+        u32(&mut self.code, default);
+        self
+    }
+
+    // The body as it is written in the code section, with its size in front
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        let mut body = Vec::new();
+        len(&mut body, self.locals.len());
+        for ty in &self.locals {
+            body.extend([1, *ty]);
+        }
+        body.extend_from_slice(&self.code);
+        body.push(END);
+        len(out, body.len());
+        out.extend(body);
+    }
+}
@@ -0,0 +1,785 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
+
+use std::collections::HashMap;
+
+use bril_rs::cfg::{is_terminator, Cfg, CfgError};
+use bril_rs::dataflow::constant_literal;
+use bril_rs::structured::{Statement, StructuredCfg};
+use bril_rs::{Code, EffectOps, Function, Instruction, Literal, Program, Type, ValueOps};
+use thiserror::Error;
+
+use encode::{
+    Body, BLOCK, BR, CALL, DROP, ELSE, EMPTY, END, F64, F64_ADD, F64_DIV, F64_EQ, F64_GE, F64_GT,
+    F64_LE, F64_LOAD, F64_LT, F64_MUL, F64_REINTERPRET_I64, F64_STORE, F64_SUB, GLOBAL_GET,
+    GLOBAL_SET, I32, I32_ADD, I32_AND, I32_EQ, I32_EQZ, I32_LOAD, I32_OR, I32_STORE, I32_WRAP_I64,
+    I64, I64_ADD, I64_DIV_S, I64_EQ, I64_EQZ, I64_EXTEND_I32_U, I64_GE_S, I64_GT_S, I64_GT_U,
+    I64_LE_S, I64_LOAD, I64_LT_S, I64_MUL, I64_REINTERPRET_F64, I64_REM_S, I64_SHL, I64_SHR_U,
+    I64_STORE, I64_SUB, IF, LOCAL_GET, LOCAL_SET, LOCAL_TEE, LOOP, MEMORY_GROW, MEMORY_SIZE,
+    RETURN, UNREACHABLE,
+};
+
+#[doc(hidden)]
+pub mod cli;
+mod encode;
+
+/// The module which every compiled program imports its host functions from
+pub const HOST_MODULE: &str = "bril";
+
+// The functions every compiled program imports from HOST_MODULE, with the types of their parameters
+const IMPORTS: [(&str, &[u8]); 6] = [
+    ("print_int", &[I64]),
+    ("print_bool", &[I32]),
+    ("print_float", &[F64]),
+    ("print_space", &[]),
+    ("print_newline", &[]),
+    ("error", &[I32, I64]),
+];
+
+/// The code `error` is called with for a `div` or `rem` by zero
+pub const ERROR_DIVISION_BY_ZERO: i32 = 0;
+/// The code `error` is called with for an `alloc` of a number of values which isn't positive, which is the value it is called with
+pub const ERROR_ALLOCATION: i32 = 1;
+/// The code `error` is called with when linear memory can't grow to fit an `alloc`
+pub const ERROR_OUT_OF_MEMORY: i32 = 2;
+
+const PRINT_INT: u32 = 0;
+const PRINT_BOOL: u32 = 1;
+const PRINT_FLOAT: u32 = 2;
+const PRINT_SPACE: u32 = 3;
+const PRINT_NEWLINE: u32 = 4;
+const ERROR: u32 = 5;
+// The functions every module defines before those of the program
+const ALLOC: u32 = 6;
+const DIV: u32 = 7;
+const REM: u32 = 8;
+const FIRST_FUNCTION: u32 = 9;
+
+// Every value in memory takes up 8 bytes, whatever its type, so that `ptradd` doesn't have to know what it points to
+const SLOT: u32 = 8;
+// The global holding the address of the next allocation
+const HEAP_TOP: u32 = 0;
+// Where the first allocation goes, which is past 0 so that no pointer is ever null
+const HEAP_START: i32 = 8;
+
+/// The reasons a program can't be compiled, each naming the function it happened in
+// Having the #[error(...)] for all variants derives the Display trait as well
+#[derive(Error, Debug)]
+pub enum CompileError {
+    /// @{0}: `{1}` is not supported
+    #[error("@{0}: `{1}` is not supported")]
+    UnsupportedOp(String, String),
+
+    /// @{0}: values of type `{1}` are not supported
+    #[error("@{0}: values of type `{1}` are not supported")]
+    UnsupportedType(String, Type),
+
+    /// @{0}: values of type `{1}` can't be printed
+    #[error("@{0}: values of type `{1}` can't be printed")]
+    UnprintableType(String, Type),
+
+    /// @{0}: `{1}` is used but never assigned
+    #[error("@{0}: `{1}` is used but never assigned")]
+    UndefinedVariable(String, String),
+
+    /// @{0}: `{1}` is assigned both `{2}` and `{3}`
+    #[error("@{0}: `{1}` is assigned both `{2}` and `{3}`")]
+    ConflictingTypes(String, String, Type, Type),
+
+    /// @{0}: call to unknown function `@{1}`
+    #[error("@{0}: call to unknown function `@{1}`")]
+    UnknownFunction(String, String),
+
+    /// @{0}: a `jmp` or `br` targets a label which the function doesn't have
+    #[error("@{0}: {1}")]
+    Labels(String, CfgError),
+
+    /// Imports have to be linked into the program before it is compiled
+    #[error("imports have to be linked into the program before it is compiled")]
+    Imports,
+}
+
+/// Compiles ```prog``` to the bytes of a WebAssembly module, with a WebAssembly function for each Bril function.
+///
+/// The module imports `print_int`, `print_bool`, `print_float`, `print_space`, `print_newline`, and `error` from [`HOST_MODULE`]. The `print_` functions each print a value the way `brilirs` does, and a `print` calls them with a `print_space` between values and a `print_newline` at the end. `error` is called with one of [`ERROR_DIVISION_BY_ZERO`], [`ERROR_ALLOCATION`], or [`ERROR_OUT_OF_MEMORY`] and the `i64` it is about, and should stop the program. The module exports its memory as `memory` and the Bril `main` as `main`, if the program has one. `int`s are `i64`s, `float`s are `f64`s, and `bool`s and pointers are `i32`s, where a pointer is an address in the memory of the module. Each function's control flow is made into WebAssembly's blocks and loops with [`StructuredCfg`], or for a function whose control flow is irreducible, into a loop around a `br_table` on the block to run next.
+///
+/// Memory is allocated from the end of the memory, which grows as needed, and is never reused since `free` does nothing. Other memory errors, like using freed memory or leaking it, aren't checked either.
+///
+/// # Errors
+/// Fails on instructions and types outside of the core language and the memory and float extensions, on variables which are never assigned, and on imports
+pub fn compile(prog: &Program) -> Result<Vec<u8>, CompileError> {
+    if !prog.imports.is_empty() {
+        return Err(CompileError::Imports);
+    }
+    let functions: HashMap<&str, (u32, &Function)> = prog
+        .functions
+        .iter()
+        .zip(FIRST_FUNCTION..)
+        .map(|(f, index)| (f.name.as_str(), (index, f)))
+        .collect();
+
+    let mut types = Types::default();
+    let mut imports = Vec::new();
+    for (name, params) in IMPORTS {
+        encode::name(&mut imports, HOST_MODULE);
+        encode::name(&mut imports, name);
+        imports.push(encode::FUNC);
+        encode::u32(&mut imports, types.index(params.to_vec(), Vec::new()));
+    }
+
+    let mut declarations = Vec::new();
+    let mut bodies = Vec::new();
+    for (params, result, body) in [
+        (vec![I64], I32, alloc()),
+        (vec![I64, I64], I64, divide(I64_DIV_S)),
+        (vec![I64, I64], I64, divide(I64_REM_S)),
+    ] {
+        encode::u32(&mut declarations, types.index(params, vec![result]));
+        body.encode(&mut bodies);
+    }
+    for func in &prog.functions {
+        let (params, results) = signature(func)?;
+        encode::u32(&mut declarations, types.index(params, results));
+        FunctionCompiler::new(func, &functions)?
+            .compile()?
+            .encode(&mut bodies);
+    }
+
+    let mut exports = Vec::new();
+    encode::name(&mut exports, "memory");
+    exports.push(encode::MEMORY);
+    encode::u32(&mut exports, 0);
+    if let Some((index, main)) = functions.get("main") {
+        for arg in &main.args {
+            if !matches!(arg.arg_type, Type::Int | Type::Bool | Type::Float) {
+                return Err(CompileError::UnsupportedType(
+                    main.name.clone(),
+                    arg.arg_type.clone(),
+                ));
+            }
+        }
+        encode::name(&mut exports, "main");
+        exports.push(encode::FUNC);
+        encode::u32(&mut exports, *index);
+    }
+
+    let mut module = b"\0asm\x01\0\0\0".to_vec();
+    encode::section(
+        &mut module,
+        encode::TYPE_SECTION,
+        types.types.len(),
+        &types.encoded,
+    );
+    encode::section(&mut module, encode::IMPORT_SECTION, IMPORTS.len(), &imports);
+    encode::section(
+        &mut module,
+        encode::FUNCTION_SECTION,
+        prog.functions.len() + 3,
+        &declarations,
+    );
+    // One page to start with, which can grow without limit
+    encode::section(&mut module, encode::MEMORY_SECTION, 1, &[0x00, 1]);
+    let mut heap_top = Body::default();
+    heap_top.i32_const(HEAP_START);
+    let mut globals = vec![I32, 0x01];
+    globals.extend(heap_top.code);
+    globals.push(END);
+    encode::section(&mut module, encode::GLOBAL_SECTION, 1, &globals);
+    encode::section(
+        &mut module,
+        encode::EXPORT_SECTION,
+        1 + usize::from(functions.contains_key("main")),
+        &exports,
+    );
+    encode::section(
+        &mut module,
+        encode::CODE_SECTION,
+        prog.functions.len() + 3,
+        &bodies,
+    );
+    Ok(module)
+}
+
+// The function types of a module, each of which is only added once
+#[derive(Default)]
+struct Types {
+    types: Vec<(Vec<u8>, Vec<u8>)>,
+    encoded: Vec<u8>,
+}
+
+impl Types {
+    fn index(&mut self, params: Vec<u8>, results: Vec<u8>) -> u32 {
+        let ty = (params, results);
+        let index = self.types.iter().position(|t| *t == ty).unwrap_or_else(|| {
+            encode::func_type(&mut self.encoded, &ty.0, &ty.1);
+            self.types.push(ty);
+            self.types.len() - 1
+        });
+        u32::try_from(index).unwrap()
+    }
+}
+
+// Makes room for the number of values given by the argument, giving back the address of the first one
+fn alloc() -> Body {
+    const COUNT: u32 = 0;
+    const START: u32 = 1;
+    const END_ADDRESS: u32 = 2;
+    let mut body = Body {
+        locals: vec![I32, I64],
+        ..Body::default()
+    };
+    body.with_index(LOCAL_GET, COUNT)
+        .i64_const(0)
+        .op(I64_LE_S)
+        .block(IF, EMPTY);
+    error(&mut body, ERROR_ALLOCATION, COUNT);
+    body.op(END);
+    // Any more values than this don't fit in the 4 GiB an `i32` can address
+    body.with_index(LOCAL_GET, COUNT)
+        .i64_const(i64::from(u32::MAX / SLOT))
+        .op(I64_GT_S)
+        .block(IF, EMPTY);
+    error(&mut body, ERROR_OUT_OF_MEMORY, COUNT);
+    body.op(END);
+    body.with_index(GLOBAL_GET, HEAP_TOP)
+        .with_index(LOCAL_TEE, START)
+        .op(I64_EXTEND_I32_U)
+        .with_index(LOCAL_GET, COUNT)
+        .i64_const(SLOT.trailing_zeros().into())
+        .op(I64_SHL)
+        .op(I64_ADD)
+        .with_index(LOCAL_SET, END_ADDRESS);
+    // Grow the memory by enough pages of 64 KiB to fit everything up to the end of the new values
+    body.with_index(LOCAL_GET, END_ADDRESS)
+        .with_index(MEMORY_SIZE, 0)
+        .op(I64_EXTEND_I32_U)
+        .i64_const(16)
+        .op(I64_SHL)
+        .op(I64_GT_U)
+        .block(IF, EMPTY)
+        .with_index(LOCAL_GET, END_ADDRESS)
+        .i64_const(0xffff)
+        .op(I64_ADD)
+        .i64_const(16)
+        .op(I64_SHR_U)
+        .with_index(MEMORY_SIZE, 0)
+        .op(I64_EXTEND_I32_U)
+        .op(I64_SUB)
+        .op(I32_WRAP_I64)
+        .with_index(MEMORY_GROW, 0)
+        .i32_const(-1)
+        .op(I32_EQ)
+        .block(IF, EMPTY);
+    error(&mut body, ERROR_OUT_OF_MEMORY, COUNT);
+    body.op(END).op(END);
+    body.with_index(LOCAL_GET, END_ADDRESS)
+        .op(I32_WRAP_I64)
+        .with_index(GLOBAL_SET, HEAP_TOP)
+        .with_index(LOCAL_GET, START);
+    body
+}
+
+// Divides the first argument by the second with ```op```, stopping on division by zero and wrapping around for the smallest int divided by -1, whose quotient is too big and which WebAssembly traps on
+fn divide(op: u8) -> Body {
+    const A: u32 = 0;
+    const B: u32 = 1;
+    let mut body = Body::default();
+    body.with_index(LOCAL_GET, B).op(I64_EQZ).block(IF, EMPTY);
+    error(&mut body, ERROR_DIVISION_BY_ZERO, B);
+    body.op(END);
+    body.with_index(LOCAL_GET, B)
+        .i64_const(-1)
+        .op(I64_EQ)
+        .block(IF, I64);
+    if op == I64_DIV_S {
+        body.i64_const(0).with_index(LOCAL_GET, A).op(I64_SUB);
+    } else {
+        body.i64_const(0);
+    }
+    body.op(ELSE)
+        .with_index(LOCAL_GET, A)
+        .with_index(LOCAL_GET, B)
+        .op(op)
+        .op(END);
+    body
+}
+
+// Calls the host's `error` with ```code``` and the `i64` in ```local```, which never returns
+fn error(body: &mut Body, code: i32, local: u32) {
+    body.i32_const(code)
+        .with_index(LOCAL_GET, local)
+        .with_index(CALL, ERROR)
+        .op(UNREACHABLE);
+}
+
+fn wasm_type(func: &Function, ty: &Type) -> Result<u8, CompileError> {
+    match ty {
+        Type::Int => Ok(I64),
+        Type::Float => Ok(F64),
+        Type::Bool | Type::Pointer(_) => Ok(I32),
+        Type::Char | Type::Str | Type::Thread | Type::Tuple(_) => {
+            Err(CompileError::UnsupportedType(func.name.clone(), ty.clone()))
+        }
+    }
+}
+
+fn signature(func: &Function) -> Result<(Vec<u8>, Vec<u8>), CompileError> {
+    let params = func
+        .args
+        .iter()
+        .map(|a| wasm_type(func, &a.arg_type))
+        .collect::<Result<_, _>>()?;
+    let results = func
+        .return_type
+        .iter()
+        .map(|ty| wasm_type(func, ty))
+        .collect::<Result<_, _>>()?;
+    Ok((params, results))
+}
+
+// Where a `br` can go to from inside of the blocks, loops, and `if`s being compiled, innermost last
+enum Target {
+    Block(String),
+    Loop(String),
+    If,
+}
+
+// Compiles a single function, where every Bril variable is a local of its own. Locals start at zero, so variables which are only assigned on some paths are never read uninitialized
+struct FunctionCompiler<'a> {
+    func: &'a Function,
+    functions: &'a HashMap<&'a str, (u32, &'a Function)>,
+    locals: HashMap<&'a str, (u32, &'a Type)>,
+    body: Body,
+    targets: Vec<Target>,
+}
+
+impl<'a> FunctionCompiler<'a> {
+    fn new(
+        func: &'a Function,
+        functions: &'a HashMap<&'a str, (u32, &'a Function)>,
+    ) -> Result<Self, CompileError> {
+        let mut compiler = Self {
+            func,
+            functions,
+            locals: HashMap::new(),
+            body: Body::default(),
+            targets: Vec::new(),
+        };
+        let dests = func.instrs.iter().filter_map(|c| match c {
+            Code::Instruction(Instruction::Constant {
+                dest, const_type, ..
+            }) => Some((dest, const_type)),
+            Code::Instruction(Instruction::Value { dest, op_type, .. }) => Some((dest, op_type)),
+            _ => None,
+        });
+        for (i, arg) in func.args.iter().enumerate() {
+            wasm_type(func, &arg.arg_type)?;
+            let index = u32::try_from(i).unwrap();
+            compiler.locals.insert(&arg.name, (index, &arg.arg_type));
+        }
+        for (var, ty) in dests {
+            let wasm_ty = wasm_type(func, ty)?;
+            match compiler.locals.get(var.as_str()) {
+                Some((_, old)) if *old != ty => {
+                    return Err(CompileError::ConflictingTypes(
+                        func.name.clone(),
+                        var.clone(),
+                        (*old).clone(),
+                        ty.clone(),
+                    ))
+                }
+                Some(_) => {}
+                None => {
+                    let index = compiler.next_local(wasm_ty);
+                    compiler.locals.insert(var, (index, ty));
+                }
+            }
+        }
+        Ok(compiler)
+    }
+
+    // Adds a local of ```ty``` after the parameters and the other locals, giving back its index
+    fn next_local(&mut self, ty: u8) -> u32 {
+        self.body.locals.push(ty);
+        u32::try_from(self.func.args.len() + self.body.locals.len() - 1).unwrap()
+    }
+
+    fn compile(mut self) -> Result<Body, CompileError> {
+        let cfg = Cfg::from_function(self.func)
+            .map_err(|e| CompileError::Labels(self.func.name.clone(), e))?;
+        match StructuredCfg::new(&cfg) {
+            Ok(structured) => self.statements(&structured.body)?,
+            Err(_) => self.dispatch(&cfg)?,
+        }
+        // Nothing falls off the end, but a function which returns a value still has to look like it leaves one
+        self.body.op(UNREACHABLE);
+        Ok(self.body)
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> Result<(), CompileError> {
+        for statement in statements {
+            match statement {
+                Statement::Code { instrs, .. } => {
+                    for instr in instrs {
+                        self.instruction(instr)?;
+                    }
+                }
+                Statement::If {
+                    cond,
+                    then,
+                    otherwise,
+                } => {
+                    self.get(cond)?;
+                    self.body.block(IF, EMPTY);
+                    self.targets.push(Target::If);
+                    self.statements(then)?;
+                    self.body.op(ELSE);
+                    self.statements(otherwise)?;
+                    self.body.op(END);
+                    self.targets.pop();
+                }
+                Statement::Loop { label, body } => {
+                    self.body.block(LOOP, EMPTY);
+                    self.targets.push(Target::Loop(label.clone()));
+                    self.statements(body)?;
+                    self.body.op(END);
+                    self.targets.pop();
+                }
+                Statement::Block { label, body } => {
+                    self.body.block(BLOCK, EMPTY);
+                    self.targets.push(Target::Block(label.clone()));
+                    self.statements(body)?;
+                    self.body.op(END);
+                    self.targets.pop();
+                }
+                Statement::Break(label) => {
+                    let depth = self.depth(|t| matches!(t, Target::Block(l) if l == label));
+                    self.body.with_index(BR, depth);
+                }
+                Statement::Continue(label) => {
+                    let depth = self.depth(|t| matches!(t, Target::Loop(l) if l == label));
+                    self.body.with_index(BR, depth);
+                }
+                Statement::Return(args) => self.ret(args)?,
+            }
+        }
+        Ok(())
+    }
+
+    // How many blocks, loops, and `if`s out the closest target which ```is_target``` is
+    fn depth(&self, is_target: impl Fn(&Target) -> bool) -> u32 {
+        let depth = self
+            .targets
+            .iter()
+            .rev()
+            .position(is_target)
+            .expect("a break or continue is always inside of what it names");
+        u32::try_from(depth).unwrap()
+    }
+
+    // Runs the blocks of ```cfg``` in a loop which picks the block to run each time around from a local holding its index, for control flow which StructuredCfg can't structure
+    fn dispatch(&mut self, cfg: &Cfg) -> Result<(), CompileError> {
+        let next = self.next_local(I32);
+        let blocks = u32::try_from(cfg.blocks.len()).unwrap();
+        self.body.block(LOOP, EMPTY);
+        for _ in 0..blocks {
+            self.body.block(BLOCK, EMPTY);
+        }
+        let targets: Vec<u32> = (0..blocks).collect();
+        self.body
+            .with_index(LOCAL_GET, next)
+            .br_table(&targets, blocks - 1);
+        // The code of each block comes right after the end of the WebAssembly block which the br_table leaves for it, inside of the rest
+        for (i, block) in (0..blocks).zip(&cfg.blocks) {
+            self.body.op(END);
+            let loop_depth = blocks - 1 - i;
+            let (instrs, terminator) = match block.instrs.split_last() {
+                Some((last, rest)) if is_terminator(last) => (rest, Some(last)),
+                _ => (block.instrs.as_slice(), None),
+            };
+            for instr in instrs {
+                self.instruction(instr)?;
+            }
+            let go_to = |body: &mut Body, to: usize| {
+                body.i32_const(i32::try_from(to).unwrap())
+                    .with_index(LOCAL_SET, next);
+            };
+            let succs = &cfg.successors[i as usize];
+            match terminator {
+                Some(Instruction::Effect {
+                    op: EffectOps::Return,
+                    args,
+                    ..
+                }) => self.ret(args)?,
+                Some(Instruction::Effect {
+                    op: EffectOps::Branch,
+                    args,
+                    ..
+                }) => {
+                    self.get(&args[0])?;
+                    self.body.block(IF, EMPTY);
+                    go_to(&mut self.body, succs[0]);
+                    self.body.op(ELSE);
+                    go_to(&mut self.body, succs[1]);
+                    self.body.op(END).with_index(BR, loop_depth);
+                }
+                _ => match succs.first() {
+                    Some(&to) => {
+                        go_to(&mut self.body, to);
+                        self.body.with_index(BR, loop_depth);
+                    }
+                    None => self.ret(&[])?,
+                },
+            }
+        }
+        self.body.op(END);
+        Ok(())
+    }
+
+    fn unsupported(&self, op: &impl ToString) -> CompileError {
+        CompileError::UnsupportedOp(self.func.name.clone(), op.to_string())
+    }
+
+    // The local for ```v```, along with its type
+    fn local(&self, v: &str) -> Result<(u32, &'a Type), CompileError> {
+        self.locals
+            .get(v)
+            .copied()
+            .ok_or_else(|| CompileError::UndefinedVariable(self.func.name.clone(), v.to_string()))
+    }
+
+    // Pushes the value of ```v```, giving back its type
+    fn get(&mut self, v: &str) -> Result<&'a Type, CompileError> {
+        let (local, ty) = self.local(v)?;
+        self.body.with_index(LOCAL_GET, local);
+        Ok(ty)
+    }
+
+    fn get_all(&mut self, args: &[String]) -> Result<(), CompileError> {
+        for arg in args {
+            self.get(arg)?;
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, v: &str) -> Result<(), CompileError> {
+        let (local, _) = self.local(v)?;
+        self.body.with_index(LOCAL_SET, local);
+        Ok(())
+    }
+
+    // Calls ```name``` with ```args```, giving back whether it leaves a value on the stack
+    fn call(&mut self, name: &str, args: &[String]) -> Result<bool, CompileError> {
+        let Some((index, callee)) = self.functions.get(name) else {
+            return Err(CompileError::UnknownFunction(
+                self.func.name.clone(),
+                name.to_string(),
+            ));
+        };
+        self.get_all(args)?;
+        self.body.with_index(CALL, *index);
+        Ok(callee.return_type.is_some())
+    }
+
+    fn ret(&mut self, args: &[String]) -> Result<(), CompileError> {
+        match (args.first(), &self.func.return_type) {
+            (Some(arg), _) => {
+                self.get(arg)?;
+                self.body.op(RETURN);
+            }
+            (None, None) => {
+                self.body.op(RETURN);
+            }
+            // Falling off the end of a function which should return a value
+            (None, Some(_)) => {
+                self.body.op(UNREACHABLE);
+            }
+        }
+        Ok(())
+    }
+
+    fn instruction(&mut self, instr: &Instruction) -> Result<(), CompileError> {
+        match instr {
+            Instruction::Constant {
+                dest,
+                const_type,
+                value,
+                ..
+            } => {
+                match constant_literal(const_type, value) {
+                    Literal::Int(i) => self.body.i64_const(i),
+                    Literal::Bool(b) => self.body.i32_const(b.into()),
+                    Literal::Float(f) => self.body.f64_const(f),
+                    Literal::Char(_) | Literal::Str(_) => {
+                        return Err(CompileError::UnsupportedType(
+                            self.func.name.clone(),
+                            const_type.clone(),
+                        ))
+                    }
+                };
+                self.set(dest)
+            }
+            Instruction::Value {
+                args,
+                dest,
+                funcs,
+                op,
+                op_type,
+                ..
+            } => self.value(*op, args, funcs, dest, op_type),
+            Instruction::Effect {
+                args, funcs, op, ..
+            } => self.effect(*op, args, funcs),
+        }
+    }
+
+    fn value(
+        &mut self,
+        op: ValueOps,
+        args: &[String],
+        funcs: &[String],
+        dest: &str,
+        op_type: &Type,
+    ) -> Result<(), CompileError> {
+        match op {
+            ValueOps::Call => {
+                self.call(&funcs[0], args)?;
+            }
+            ValueOps::Load => {
+                self.get(&args[0])?;
+                let load = match wasm_type(self.func, op_type)? {
+                    I64 => I64_LOAD,
+                    F64 => F64_LOAD,
+                    _ => I32_LOAD,
+                };
+                self.body.memory(load, value_size(load));
+            }
+            ValueOps::Alloc => {
+                self.get(&args[0])?;
+                self.body.with_index(CALL, ALLOC);
+            }
+            ValueOps::PtrAdd => {
+                self.get_all(args)?;
+                self.body
+                    .i64_const(SLOT.trailing_zeros().into())
+                    .op(I64_SHL)
+                    .op(I32_WRAP_I64)
+                    .op(I32_ADD);
+            }
+            ValueOps::Div => {
+                self.get_all(args)?;
+                self.body.with_index(CALL, DIV);
+            }
+            ValueOps::Rem => {
+                self.get_all(args)?;
+                self.body.with_index(CALL, REM);
+            }
+            ValueOps::Id => {
+                self.get(&args[0])?;
+            }
+            ValueOps::Not => {
+                self.get(&args[0])?;
+                self.body.op(I32_EQZ);
+            }
+            _ => {
+                let wasm_op = simple_op(op).ok_or_else(|| self.unsupported(&op))?;
+                self.get_all(args)?;
+                self.body.op(wasm_op);
+            }
+        }
+        self.set(dest)
+    }
+
+    fn effect(
+        &mut self,
+        op: EffectOps,
+        args: &[String],
+        funcs: &[String],
+    ) -> Result<(), CompileError> {
+        match op {
+            EffectOps::Call => {
+                if self.call(&funcs[0], args)? {
+                    self.body.op(DROP);
+                }
+            }
+            EffectOps::Print => {
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        self.body.with_index(CALL, PRINT_SPACE);
+                    }
+                    let print = match self.get(arg)? {
+                        Type::Int => PRINT_INT,
+                        Type::Bool => PRINT_BOOL,
+                        Type::Float => PRINT_FLOAT,
+                        ty => {
+                            return Err(CompileError::UnprintableType(
+                                self.func.name.clone(),
+                                ty.clone(),
+                            ))
+                        }
+                    };
+                    self.body.with_index(CALL, print);
+                }
+                self.body.with_index(CALL, PRINT_NEWLINE);
+            }
+            EffectOps::Nop | EffectOps::Free => {}
+            EffectOps::Store => {
+                self.get(&args[0])?;
+                let store = match wasm_type(self.func, self.get(&args[1])?)? {
+                    I64 => I64_STORE,
+                    F64 => F64_STORE,
+                    _ => I32_STORE,
+                };
+                self.body.memory(store, value_size(store));
+            }
+            // The terminators are compiled along with the control flow
+            EffectOps::Jump
+            | EffectOps::Branch
+            | EffectOps::Return
+            | EffectOps::Speculate
+            | EffectOps::Commit
+            | EffectOps::Guard
+            | EffectOps::Join => return Err(self.unsupported(&op)),
+        }
+        Ok(())
+    }
+}
+
+// How many bytes a load or store moves
+const fn value_size(op: u8) -> u32 {
+    match op {
+        I32_LOAD | I32_STORE => 4,
+        _ => 8,
+    }
+}
+
+// The WebAssembly instruction for a Bril operation which takes its arguments straight off of the stack
+const fn simple_op(op: ValueOps) -> Option<u8> {
+    Some(match op {
+        ValueOps::Add => I64_ADD,
+        ValueOps::Sub => I64_SUB,
+        ValueOps::Mul => I64_MUL,
+        ValueOps::Eq => I64_EQ,
+        ValueOps::Lt => I64_LT_S,
+        ValueOps::Gt => I64_GT_S,
+        ValueOps::Le => I64_LE_S,
+        ValueOps::Ge => I64_GE_S,
+        ValueOps::And => I32_AND,
+        ValueOps::Or => I32_OR,
+        ValueOps::Fadd => F64_ADD,
+        ValueOps::Fsub => F64_SUB,
+        ValueOps::Fmul => F64_MUL,
+        ValueOps::Fdiv => F64_DIV,
+        ValueOps::Feq => F64_EQ,
+        ValueOps::Flt => F64_LT,
+        ValueOps::Fgt => F64_GT,
+        ValueOps::Fle => F64_LE,
+        ValueOps::Fge => F64_GE,
+        ValueOps::Float2bits => I64_REINTERPRET_F64,
+        ValueOps::Bits2float => F64_REINTERPRET_I64,
+        _ => return None,
+    })
+}
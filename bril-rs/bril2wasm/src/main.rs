@@ -0,0 +1,17 @@
+use std::io::Write;
+
+use bril2wasm::cli::Cli;
+use bril2wasm::compile;
+use bril_rs::load_program;
+use clap::Parser;
+
+fn main() {
+    Cli::parse();
+    match compile(&load_program()) {
+        Ok(module) => std::io::stdout().write_all(&module).unwrap(),
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(1)
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(about, version, author)] // keeps the cli synced with Cargo.toml
+pub struct Cli {
+    /// The program before the change, in JSON or as text if its name ends in `.bril`. `-` reads it from stdin
+    pub old: PathBuf,
+
+    /// The program after the change, read like the first
+    pub new: PathBuf,
+}
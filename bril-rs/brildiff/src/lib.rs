@@ -0,0 +1,753 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{self, Display, Formatter};
+
+use bril_rs::cfg::{BasicBlock, Cfg, CfgError};
+use bril_rs::ssa::UNDEFINED;
+use bril_rs::{Code, Function, Instruction, Program, Type, ValueOps};
+
+#[doc(hidden)]
+pub mod cli;
+
+/// How one instruction differs between two versions of a block
+#[derive(Debug, Clone)]
+// Diffs are only made to be shown, so there aren't enough of them for boxing the instructions to be worth it
+#[allow(clippy::large_enum_variant)]
+pub enum InstrDiff {
+    /// An instruction which is only in the new block
+    Added(Instruction),
+    /// An instruction which is only in the old block
+    Removed(Instruction),
+    /// An instruction in the old block, and the one in the new block in its place, which does the same kind of thing to different variables
+    Changed(Instruction, Instruction),
+}
+
+/// How a basic block differs between two versions of a function. Labels are [`None`] for blocks which weren't labelled in the program
+#[derive(Debug, Clone)]
+pub enum BlockDiff {
+    /// A block which is only in the new function
+    Added {
+        /// The label of the block
+        label: Option<String>,
+        /// The instructions of the block
+        instrs: Vec<Instruction>,
+    },
+    /// A block which is only in the old function
+    Removed {
+        /// The label of the block
+        label: Option<String>,
+        /// The instructions of the block
+        instrs: Vec<Instruction>,
+    },
+    /// A block which is in both functions, but with different instructions
+    Changed {
+        /// The label of the block in the old function
+        old_label: Option<String>,
+        /// The label of the block in the new function
+        new_label: Option<String>,
+        /// The instructions which differ, in order
+        instrs: Vec<InstrDiff>,
+    },
+}
+
+/// How a function differs between two programs
+#[derive(Debug, Clone)]
+pub enum FunctionDiff {
+    /// A function which is only in the new program
+    Added(Function),
+    /// A function which is only in the old program
+    Removed(Function),
+    /// A function which is in both programs, but which doesn't do the same thing
+    Changed {
+        /// The name of the function
+        name: String,
+        /// The signatures of the old and new function, if the types of their arguments or what they return differ
+        signatures: Option<(String, String)>,
+        /// The blocks which differ, in the order of the old function followed by those only in the new one
+        blocks: Vec<BlockDiff>,
+    },
+}
+
+/// The differences between two programs, from [`diff_programs`]
+#[derive(Debug, Clone, Default)]
+pub struct ProgramDiff {
+    /// The functions which differ, in the order of the old program followed by those only in the new one
+    pub functions: Vec<FunctionDiff>,
+}
+
+impl ProgramDiff {
+    /// Whether the two programs are the same
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+}
+
+/// Compares ```old``` with ```new``` by what they do rather than by how they are written.
+///
+/// Functions are matched by name. Within a pair of functions, blocks are matched first by label and then by where they are in the control flow graph, starting from the entry, so blocks which were moved or relabelled still line up. The instructions of each pair of blocks are lined up by what they do, and a variable in one counts as renamed to a variable in the other wherever instructions assigning to them line up, as do the arguments of the function in order. Positions are never compared.
+/// ```
+/// let parse = |text| bril_rs::Program::try_from(bril2json::parse_abstract_program_from_str(text, false).unwrap()).unwrap();
+/// let old = parse("@main { a: int = const 1; b: int = add a a; print b; }");
+/// let new = parse("@main { x: int = const 1; y: int = mul x x; print y; }");
+/// let diff = brildiff::diff_programs(&old, &new).unwrap();
+/// assert_eq!(diff.to_string(), " @main {\n-  b: int = add a a;\n+  y: int = mul x x;\n }\n");
+/// ```
+/// # Errors
+/// Will return [`DiffError`] for the first function with a `jmp` or `br` to a label which it doesn't have
+pub fn diff_programs(old: &Program, new: &Program) -> Result<ProgramDiff, DiffError> {
+    let mut functions = Vec::new();
+    for func in &old.functions {
+        match new.functions.iter().find(|f| f.name == func.name) {
+            Some(other) => functions.extend(diff_functions(func, other)?),
+            None => functions.push(FunctionDiff::Removed(func.clone())),
+        }
+    }
+    for func in &new.functions {
+        if !old.functions.iter().any(|f| f.name == func.name) {
+            functions.push(FunctionDiff::Added(func.clone()));
+        }
+    }
+    Ok(ProgramDiff { functions })
+}
+
+/// Compares two versions of a function like [`diff_programs`] does, giving [`None`] if they are the same
+/// # Errors
+/// Will return [`DiffError`] if a `jmp` or `br` targets a label which its function doesn't have
+pub fn diff_functions(old: &Function, new: &Function) -> Result<Option<FunctionDiff>, DiffError> {
+    let old_side = Side::new(old, false)?;
+    let new_side = Side::new(new, true)?;
+    let matched = match_blocks(&old_side, &new_side);
+    let mut renames = Renames::default();
+    for (a, b) in old.args.iter().zip(&new.args) {
+        renames.add(&a.name, &b.name);
+    }
+    let alignments = align_blocks(&old_side, &new_side, &matched, &mut renames);
+    rename_phis(&old_side, &new_side, &matched, &alignments, &mut renames);
+
+    let mut blocks = Vec::new();
+    for (i, steps) in alignments.into_iter().enumerate() {
+        let old_block = &old_side.cfg.blocks[i];
+        let (Some(steps), Some(j)) = (steps, matched[i]) else {
+            blocks.push(BlockDiff::Removed {
+                label: old_side.label(old_block),
+                instrs: old_block.instrs.clone(),
+            });
+            continue;
+        };
+        let new_block = &new_side.cfg.blocks[j];
+        let instrs: Vec<InstrDiff> = steps
+            .into_iter()
+            .filter_map(|step| match step {
+                Step::Old(a) => Some(InstrDiff::Removed(old_block.instrs[a].clone())),
+                Step::New(b) => Some(InstrDiff::Added(new_block.instrs[b].clone())),
+                Step::Both(a, b) => {
+                    let (a, b) = (&old_block.instrs[a], &new_block.instrs[b]);
+                    (!renames.same_args(a, b)).then(|| InstrDiff::Changed(a.clone(), b.clone()))
+                }
+                Step::Replaced(a, b) => Some(InstrDiff::Changed(
+                    old_block.instrs[a].clone(),
+                    new_block.instrs[b].clone(),
+                )),
+            })
+            .collect();
+        if !instrs.is_empty() {
+            blocks.push(BlockDiff::Changed {
+                old_label: old_side.label(old_block),
+                new_label: new_side.label(new_block),
+                instrs,
+            });
+        }
+    }
+    for (j, block) in new_side.cfg.blocks.iter().enumerate() {
+        if !matched.contains(&Some(j)) {
+            blocks.push(BlockDiff::Added {
+                label: new_side.label(block),
+                instrs: block.instrs.clone(),
+            });
+        }
+    }
+
+    let types = |f: &Function| {
+        (
+            f.args
+                .iter()
+                .map(|a| a.arg_type.clone())
+                .collect::<Vec<_>>(),
+            f.return_type.clone(),
+        )
+    };
+    let signatures = (types(old) != types(new)).then(|| (signature(old), signature(new)));
+    if blocks.is_empty() && signatures.is_none() {
+        Ok(None)
+    } else {
+        Ok(Some(FunctionDiff::Changed {
+            name: old.name.clone(),
+            signatures,
+            blocks,
+        }))
+    }
+}
+
+/// The error for a function which can't be split into blocks to be compared, because a `jmp` or `br` in it targets a label which it doesn't have
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffError {
+    /// The name of the function
+    pub func: String,
+    /// Whether the function is the one from the new program
+    pub new: bool,
+    /// What is wrong with its labels
+    pub error: CfgError,
+}
+
+impl Display for DiffError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let side = if self.new { "new" } else { "old" };
+        write!(f, "@{} in the {side} program: {}", self.func, self.error)
+    }
+}
+
+impl std::error::Error for DiffError {}
+
+// Lines up the instructions of each pair of matched blocks, adding the variables assigned by instructions which line up to ```renames```
+fn align_blocks<'a>(
+    old: &'a Side,
+    new: &'a Side,
+    matched: &[Option<usize>],
+    renames: &mut Renames<'a>,
+) -> Vec<Option<Vec<Step>>> {
+    let labels = label_map(old, new, matched);
+
+    // Every block is lined up before any are compared, so that variables assigned in later blocks are known to be renamed when they are used in earlier ones
+    matched
+        .iter()
+        .enumerate()
+        .map(|(i, j)| {
+            j.map(|j| {
+                let steps = align(
+                    &old.cfg.blocks[i].instrs,
+                    &new.cfg.blocks[j].instrs,
+                    &labels,
+                );
+                for step in &steps {
+                    if let Step::Both(a, b) | Step::Replaced(a, b) = step {
+                        if let (Some(a), Some(b)) = (
+                            dest(&old.cfg.blocks[i].instrs[*a]),
+                            dest(&new.cfg.blocks[j].instrs[*b]),
+                        ) {
+                            renames.add(a, b);
+                        }
+                    }
+                }
+                steps
+            })
+        })
+        .collect()
+}
+
+// A phi which lines up with nothing, like one added by converting into SSA form, is renamed from whichever variable all of its arguments were renamed from. Phis can take each other as arguments, so this goes on until no more are found
+fn rename_phis<'a>(
+    old: &'a Side,
+    new: &'a Side,
+    matched: &[Option<usize>],
+    alignments: &[Option<Vec<Step>>],
+    renames: &mut Renames<'a>,
+) {
+    let mut phis = Vec::new();
+    for (i, steps) in alignments.iter().enumerate() {
+        for step in steps.iter().flatten() {
+            match step {
+                Step::Old(a) => phis.extend(phi(&old.cfg.blocks[i].instrs[*a]).map(|p| (p, false))),
+                Step::New(b) => {
+                    let block = &new.cfg.blocks[matched[i].unwrap()];
+                    phis.extend(phi(&block.instrs[*b]).map(|p| (p, true)));
+                }
+                Step::Both(..) | Step::Replaced(..) => {}
+            }
+        }
+    }
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for ((dest, args), in_new) in &phis {
+            let mut from = args
+                .iter()
+                .filter(|a| *a != UNDEFINED)
+                .map(|a| renames.other_side(a, *in_new));
+            let Some(first) = from.next() else { continue };
+            let common = from.fold(first, |common, other| &common & &other);
+            if let [var] = common.into_iter().collect::<Vec<_>>()[..] {
+                let (old_var, new_var) = if *in_new { (var, *dest) } else { (*dest, var) };
+                changed |= renames.add(old_var, new_var);
+            }
+        }
+    }
+}
+
+fn phi(instr: &Instruction) -> Option<(&str, &[String])> {
+    match instr {
+        Instruction::Value {
+            op: ValueOps::Phi,
+            dest,
+            args,
+            ..
+        } => Some((dest, args)),
+        _ => None,
+    }
+}
+
+// A function split into blocks, along with which labels were written in it rather than made up by Cfg::new
+struct Side {
+    cfg: Cfg,
+    written: HashSet<String>,
+}
+
+impl Side {
+    fn new(func: &Function, new: bool) -> Result<Self, DiffError> {
+        let written = func
+            .instrs
+            .iter()
+            .filter_map(|c| match c {
+                Code::Label { label, .. } => Some(label.clone()),
+                Code::Instruction(_) => None,
+            })
+            .collect();
+        let cfg = Cfg::from_function(func).map_err(|error| DiffError {
+            func: func.name.clone(),
+            new,
+            error,
+        })?;
+        Ok(Self { cfg, written })
+    }
+
+    fn label(&self, block: &BasicBlock) -> Option<String> {
+        self.written
+            .contains(&block.label)
+            .then(|| block.label.clone())
+    }
+}
+
+// The block of ```new``` which each block of ```old``` is matched with, if any
+fn match_blocks(old: &Side, new: &Side) -> Vec<Option<usize>> {
+    let mut matched = vec![None; old.cfg.blocks.len()];
+    let mut taken = vec![false; new.cfg.blocks.len()];
+    let mut pairs = Vec::new();
+
+    let by_label: HashMap<&str, usize> = new
+        .cfg
+        .blocks
+        .iter()
+        .enumerate()
+        .filter(|(_, b)| new.written.contains(&b.label))
+        .map(|(j, b)| (b.label.as_str(), j))
+        .collect();
+    for (i, block) in old.cfg.blocks.iter().enumerate() {
+        if old.written.contains(&block.label) {
+            if let Some(&j) = by_label.get(block.label.as_str()) {
+                pairs.push((i, j));
+            }
+        }
+    }
+    if !matched.is_empty() && !taken.is_empty() && pairs.iter().all(|(i, j)| *i != 0 && *j != 0) {
+        pairs.push((0, 0));
+    }
+
+    // Blocks which were relabelled are found by following the edges out of blocks which are already matched, in the order their terminators name them
+    let mut found: VecDeque<(usize, usize)> = pairs.into();
+    for (i, j) in &found {
+        matched[*i] = Some(*j);
+        taken[*j] = true;
+    }
+    loop {
+        while let Some((i, j)) = found.pop_front() {
+            let (next_old, next_new) = (&old.cfg.successors[i], &new.cfg.successors[j]);
+            if next_old.len() != next_new.len() {
+                continue;
+            }
+            for (&a, &b) in next_old.iter().zip(next_new) {
+                if matched[a].is_none() && !taken[b] {
+                    matched[a] = Some(b);
+                    taken[b] = true;
+                    found.push_back((a, b));
+                }
+            }
+        }
+        // Blocks which nothing matched leads to, like those which can never run, are matched with one which does exactly the same thing
+        let labels = label_map(old, new, &matched);
+        let same = |i: usize, j: usize| {
+            let (a, b) = (&old.cfg.blocks[i].instrs, &new.cfg.blocks[j].instrs);
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_shape(a, b, &labels))
+        };
+        let Some((i, j)) = (0..matched.len())
+            .filter(|i| matched[*i].is_none())
+            .find_map(|i| {
+                (0..taken.len())
+                    .find(|j| !taken[*j] && same(i, *j))
+                    .map(|j| (i, j))
+            })
+        else {
+            return matched;
+        };
+        matched[i] = Some(j);
+        taken[j] = true;
+        found.push_back((i, j));
+    }
+}
+
+// The label of the block of ```new``` that each block of ```old``` which was matched is matched with
+fn label_map<'a>(
+    old: &'a Side,
+    new: &'a Side,
+    matched: &[Option<usize>],
+) -> HashMap<&'a str, &'a str> {
+    matched
+        .iter()
+        .enumerate()
+        .filter_map(|(i, j)| {
+            j.map(|j| {
+                (
+                    old.cfg.blocks[i].label.as_str(),
+                    new.cfg.blocks[j].label.as_str(),
+                )
+            })
+        })
+        .collect()
+}
+
+// A step of lining up the instructions of two blocks
+enum Step {
+    Old(usize),
+    New(usize),
+    // Instructions which do the same kind of thing
+    Both(usize, usize),
+    // An instruction which was removed and one which was added in its place
+    Replaced(usize, usize),
+}
+
+// Lines up as many instructions of ```old``` and ```new``` as possible which do the same kind of thing, preferring those which are written exactly the same. Between two pairs which line up, an instruction which was removed and one which was added in its place are shown as a change when they assign the same type or are the same kind of effect, preferring those which assign the same variable, so that the variables they assign count as renamed too
+fn align(old: &[Instruction], new: &[Instruction], labels: &HashMap<&str, &str>) -> Vec<Step> {
+    let lined_up = lcs(old.len(), new.len(), |i, j| {
+        if !same_shape(&old[i], &new[j], labels) {
+            0
+        } else if dest(&old[i]) == dest(&new[j]) && args(&old[i]) == args(&new[j]) {
+            2
+        } else {
+            1
+        }
+    });
+    let mut steps = Vec::with_capacity(lined_up.len());
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    for step in lined_up
+        .into_iter()
+        .chain([Step::Both(old.len(), new.len())])
+    {
+        match step {
+            Step::Old(i) => removed.push(i),
+            Step::New(j) => added.push(j),
+            Step::Both(..) | Step::Replaced(..) => {
+                let gap = lcs(removed.len(), added.len(), |i, j| {
+                    let (a, b) = (&old[removed[i]], &new[added[j]]);
+                    match (replaces(a, b), dest(a) == dest(b)) {
+                        (false, _) => 0,
+                        (true, false) => 1,
+                        (true, true) => 2,
+                    }
+                });
+                steps.extend(gap.into_iter().map(|step| match step {
+                    Step::Old(i) => Step::Old(removed[i]),
+                    Step::New(j) => Step::New(added[j]),
+                    Step::Both(i, j) | Step::Replaced(i, j) => Step::Replaced(removed[i], added[j]),
+                }));
+                removed.clear();
+                added.clear();
+                steps.push(step);
+            }
+        }
+    }
+    // The last step only marks the end of the gap at the end of the blocks
+    steps.pop();
+    steps
+}
+
+// The most ```score``` that can be had by pairing up indices below ```old``` with those below ```new``` in order, as the steps which pair them. Pairs which score 0 are never made
+fn lcs(old: usize, new: usize, score: impl Fn(usize, usize) -> u32) -> Vec<Step> {
+    // best[i][j] is the best score for lining up what is from i on with what is from j on
+    let mut best = vec![vec![0_u32; new + 1]; old + 1];
+    for i in (0..old).rev() {
+        for j in (0..new).rev() {
+            let both = match score(i, j) {
+                0 => 0,
+                s => s + best[i + 1][j + 1],
+            };
+            best[i][j] = both.max(best[i + 1][j]).max(best[i][j + 1]);
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut steps = Vec::new();
+    while i < old || j < new {
+        if i < old && j < new {
+            let s = score(i, j);
+            if s != 0 && best[i][j] == s + best[i + 1][j + 1] {
+                steps.push(Step::Both(i, j));
+                i += 1;
+                j += 1;
+                continue;
+            }
+        }
+        if j == new || (i < old && best[i][j] == best[i + 1][j]) {
+            steps.push(Step::Old(i));
+            i += 1;
+        } else {
+            steps.push(Step::New(j));
+            j += 1;
+        }
+    }
+    steps
+}
+
+// Whether ```new``` could have been written in place of ```old```
+fn replaces(old: &Instruction, new: &Instruction) -> bool {
+    match (old, new) {
+        (Instruction::Effect { op: op_a, .. }, Instruction::Effect { op: op_b, .. }) => {
+            op_a == op_b
+        }
+        (Instruction::Effect { .. }, _) | (_, Instruction::Effect { .. }) => false,
+        _ => dest_type(old) == dest_type(new),
+    }
+}
+
+// Whether ```old``` and ```new``` do the same thing, other than which variables they use and assign. Labels of ```old``` are compared with the labels of the blocks of ```new``` they were matched with
+fn same_shape(old: &Instruction, new: &Instruction, labels: &HashMap<&str, &str>) -> bool {
+    let same_labels = |a: &[String], b: &[String]| {
+        a.len() == b.len()
+            && a.iter()
+                .zip(b)
+                .all(|(a, b)| labels.get(a.as_str()) == Some(&b.as_str()))
+    };
+    match (old, new) {
+        (
+            Instruction::Constant {
+                op: op_a,
+                const_type: ty_a,
+                value: value_a,
+                ..
+            },
+            Instruction::Constant {
+                op: op_b,
+                const_type: ty_b,
+                value: value_b,
+                ..
+            },
+        ) => op_a == op_b && ty_a == ty_b && value_a == value_b,
+        (
+            Instruction::Value {
+                args: args_a,
+                funcs: funcs_a,
+                labels: labels_a,
+                op: op_a,
+                op_type: ty_a,
+                ..
+            },
+            Instruction::Value {
+                args: args_b,
+                funcs: funcs_b,
+                labels: labels_b,
+                op: op_b,
+                op_type: ty_b,
+                ..
+            },
+        ) => {
+            op_a == op_b
+                && ty_a == ty_b
+                && funcs_a == funcs_b
+                && args_a.len() == args_b.len()
+                && same_labels(labels_a, labels_b)
+        }
+        (
+            Instruction::Effect {
+                args: args_a,
+                funcs: funcs_a,
+                labels: labels_a,
+                op: op_a,
+                ..
+            },
+            Instruction::Effect {
+                args: args_b,
+                funcs: funcs_b,
+                labels: labels_b,
+                op: op_b,
+                ..
+            },
+        ) => {
+            op_a == op_b
+                && funcs_a == funcs_b
+                && args_a.len() == args_b.len()
+                && same_labels(labels_a, labels_b)
+        }
+        _ => false,
+    }
+}
+
+fn dest(instr: &Instruction) -> Option<&str> {
+    match instr {
+        Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => Some(dest),
+        Instruction::Effect { .. } => None,
+    }
+}
+
+const fn dest_type(instr: &Instruction) -> Option<&Type> {
+    match instr {
+        Instruction::Constant { const_type, .. } => Some(const_type),
+        Instruction::Value { op_type, .. } => Some(op_type),
+        Instruction::Effect { .. } => None,
+    }
+}
+
+fn args(instr: &Instruction) -> &[String] {
+    match instr {
+        Instruction::Value { args, .. } | Instruction::Effect { args, .. } => args,
+        Instruction::Constant { .. } => &[],
+    }
+}
+
+// Which variables of the old function were renamed to which variables of the new one. A variable can be renamed to more than one, like when it is assigned more than once and the new function is in SSA form
+#[derive(Default)]
+struct Renames<'a> {
+    pairs: HashSet<(&'a str, &'a str)>,
+    old: HashSet<&'a str>,
+    new: HashSet<&'a str>,
+}
+
+impl<'a> Renames<'a> {
+    // Whether the pair wasn't already known
+    fn add(&mut self, old: &'a str, new: &'a str) -> bool {
+        self.old.insert(old);
+        self.new.insert(new);
+        self.pairs.insert((old, new))
+    }
+
+    // The variables that ```var``` was renamed from, if it is in the new function, or to otherwise
+    fn other_side(&self, var: &str, in_new: bool) -> HashSet<&'a str> {
+        self.pairs
+            .iter()
+            .filter(|(old, new)| if in_new { *new == var } else { *old == var })
+            .map(|(old, new)| if in_new { *old } else { *new })
+            .collect()
+    }
+
+    // Variables which weren't renamed, like those which are never assigned, are only the same as themselves
+    fn same(&self, old: &str, new: &str) -> bool {
+        self.pairs.contains(&(old, new))
+            || (old == new && !self.old.contains(old) && !self.new.contains(new))
+    }
+
+    fn same_args(&self, old: &Instruction, new: &Instruction) -> bool {
+        args(old)
+            .iter()
+            .zip(args(new))
+            .all(|(a, b)| self.same(a, b))
+    }
+}
+
+fn signature(func: &Function) -> String {
+    let args: Vec<String> = func.args.iter().map(ToString::to_string).collect();
+    let args = if args.is_empty() {
+        String::new()
+    } else {
+        format!("({})", args.join(", "))
+    };
+    let return_type = func
+        .return_type
+        .as_ref()
+        .map_or_else(String::new, |ty| format!(": {ty}"));
+    format!("@{}{args}{return_type}", func.name)
+}
+
+fn write_function(f: &mut Formatter<'_>, prefix: char, func: &Function) -> fmt::Result {
+    for line in func.to_string().lines() {
+        writeln!(f, "{prefix}{line}")?;
+    }
+    Ok(())
+}
+
+fn write_block(
+    f: &mut Formatter<'_>,
+    prefix: char,
+    label: Option<&String>,
+    instrs: &[Instruction],
+) -> fmt::Result {
+    if let Some(label) = label {
+        writeln!(f, "{prefix}.{label}:")?;
+    }
+    for instr in instrs {
+        writeln!(f, "{prefix}  {instr}")?;
+    }
+    Ok(())
+}
+
+impl Display for ProgramDiff {
+    // Written like a unified diff of the programs as text, with a line starting with `-` for each line only in the old program, `+` for each line only in the new one, and a space for the function and block each change is in
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for func in &self.functions {
+            match func {
+                FunctionDiff::Added(func) => write_function(f, '+', func)?,
+                FunctionDiff::Removed(func) => write_function(f, '-', func)?,
+                FunctionDiff::Changed {
+                    name,
+                    signatures,
+                    blocks,
+                } => {
+                    match signatures {
+                        Some((old, new)) => {
+                            writeln!(f, "-{old} {{")?;
+                            writeln!(f, "+{new} {{")?;
+                        }
+                        None => writeln!(f, " @{name} {{")?,
+                    }
+                    for block in blocks {
+                        match block {
+                            BlockDiff::Added { label, instrs } => {
+                                write_block(f, '+', label.as_ref(), instrs)?;
+                            }
+                            BlockDiff::Removed { label, instrs } => {
+                                write_block(f, '-', label.as_ref(), instrs)?;
+                            }
+                            BlockDiff::Changed {
+                                old_label,
+                                new_label,
+                                instrs,
+                            } => {
+                                match (old_label, new_label) {
+                                    (Some(old), Some(new)) if old != new => {
+                                        writeln!(f, " .{old}:  # .{new} in the new program")?;
+                                    }
+                                    (Some(label), _) | (None, Some(label)) => {
+                                        writeln!(f, " .{label}:")?;
+                                    }
+                                    (None, None) => {}
+                                }
+                                for instr in instrs {
+                                    match instr {
+                                        InstrDiff::Added(instr) => writeln!(f, "+  {instr}")?,
+                                        InstrDiff::Removed(instr) => writeln!(f, "-  {instr}")?,
+                                        InstrDiff::Changed(old, new) => {
+                                            writeln!(f, "-  {old}")?;
+                                            writeln!(f, "+  {new}")?;
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    writeln!(f, " }}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use std::io::Read;
+use std::path::Path;
+
+use bril_rs::Program;
+use brildiff::cli::Cli;
+use clap::Parser;
+
+// Reads the program at ```path```, or from stdin if it is `-`, as text if the name ends in `.bril` and as JSON otherwise
+fn read_program(path: &Path) -> Result<Program, String> {
+    let mut input = String::new();
+    if path == Path::new("-") {
+        std::io::stdin().read_to_string(&mut input)
+    } else {
+        std::fs::File::open(path).and_then(|mut f| f.read_to_string(&mut input))
+    }
+    .map_err(|e| e.to_string())?;
+    if path.extension().is_some_and(|e| e == "bril") {
+        let prog =
+            bril2json::parse_abstract_program_from_str(&input, false).map_err(|e| e.to_string())?;
+        Program::try_from(prog).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str(&input).map_err(|e| e.to_string())
+    }
+}
+
+fn main() {
+    let args = Cli::parse();
+    let [old, new] = [&args.old, &args.new].map(|path| {
+        read_program(path).unwrap_or_else(|e| {
+            eprintln!("error: {}: {e}", path.display());
+            // Like diff, 1 means the programs differ and 2 that they couldn't be compared
+            std::process::exit(2)
+        })
+    });
+    let diff = brildiff::diff_programs(&old, &new).unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        std::process::exit(2)
+    });
+    print!("{diff}");
+    if !diff.is_empty() {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,15 @@
+use std::io::{Read, Write};
+
+fn main() {
+    // `-d` turns a binary program back into JSON, so that `bril2bc | bril2bc -d` is the identity
+    if std::env::args().any(|arg| arg == "-d") {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes).unwrap();
+        bril_rs::output_program(&bril_rs::binary::from_bytes(&bytes).unwrap());
+    } else {
+        let program = bril_rs::load_program();
+        std::io::stdout()
+            .write_all(&bril_rs::binary::to_bytes(&program))
+            .unwrap();
+    }
+}
@@ -1,5 +1,11 @@
 use bril_rs::load_abstract_program;
 
 fn main() {
-    print!("{}", load_abstract_program());
+    let program = load_abstract_program();
+    // Mirror bril2json's flag so that `bril2json -p | bril2txt -p` keeps every position
+    if std::env::args().any(|arg| arg == "-p") {
+        bril_rs::output::write_text_with_positions(std::io::stdout(), &program).unwrap();
+    } else {
+        print!("{program}");
+    }
 }
@@ -0,0 +1,14 @@
+fn main() {
+    let mut program = bril_rs::load_program();
+    // `--from` converts out of SSA form instead, so that `ssa | ssa --from` gives back a program which does the same thing
+    let result = if std::env::args().any(|arg| arg == "--from") {
+        bril_rs::ssa::from_ssa(&mut program)
+    } else {
+        bril_rs::ssa::to_ssa(&mut program)
+    };
+    if let Err(e) = result {
+        eprintln!("error: {e}");
+        std::process::exit(1)
+    }
+    bril_rs::output_program(&program);
+}
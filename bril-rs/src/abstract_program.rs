@@ -1,8 +1,15 @@
-use std::fmt::{self, Display, Formatter};
-use std::marker::PhantomData;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+use core::marker::PhantomData;
 
 use crate::{program::Literal, ConstOps};
 
+#[cfg(feature = "attrs")]
+use crate::program::Attrs;
+#[cfg(feature = "import")]
+use crate::program::Import;
 #[cfg(feature = "position")]
 use crate::program::Position;
 
@@ -17,10 +24,22 @@ use serde::ser::{SerializeMap, Serializer};
 pub struct AbstractProgram {
     /// A list of functions declared in the program
     pub functions: Vec<AbstractFunction>,
+    /// The functions this program uses from other files
+    #[cfg(feature = "import")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub imports: Vec<Import>,
+    /// Metadata about the whole program, which is any other fields it has in JSON
+    #[cfg(feature = "attrs")]
+    #[serde(flatten)]
+    pub attrs: Attrs,
 }
 
 impl Display for AbstractProgram {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "import")]
+        for import in &self.imports {
+            writeln!(f, "{import}")?;
+        }
         for func in &self.functions {
             writeln!(f, "{func}")?;
         }
@@ -47,6 +66,10 @@ pub struct AbstractFunction {
     #[serde(rename = "type")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub return_type: Option<AbstractType>,
+    /// Metadata about this function, which is any other fields it has in JSON
+    #[cfg(feature = "attrs")]
+    #[serde(flatten)]
+    pub attrs: Attrs,
 }
 
 impl Display for AbstractFunction {
@@ -77,7 +100,7 @@ impl Display for AbstractFunction {
 /// An argument of a function
 /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#function>
 /// Example: a : int
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct AbstractArgument {
     /// a
     pub name: String,
@@ -113,12 +136,12 @@ pub enum AbstractCode {
 impl Display for AbstractCode {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            AbstractCode::Label {
+            Self::Label {
                 label,
                 #[cfg(feature = "position")]
                     pos: _,
             } => write!(f, ".{label}:"),
-            AbstractCode::Instruction(instr) => write!(f, "  {instr}"),
+            Self::Instruction(instr) => write!(f, "  {instr}"),
         }
     }
 }
@@ -142,6 +165,10 @@ pub enum AbstractInstruction {
         const_type: Option<AbstractType>,
         /// The literal being stored in the variable
         value: Literal,
+        /// Metadata about this instruction, which is any other fields it has in JSON
+        #[cfg(feature = "attrs")]
+        #[serde(flatten)]
+        attrs: Attrs,
     },
     /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#value-operation>
     Value {
@@ -165,6 +192,14 @@ pub enum AbstractInstruction {
         /// Type of variable
         #[serde(rename = "type")]
         op_type: Option<AbstractType>,
+        /// The variables after ```dest``` which a `call` of a function returning more than one value assigns to, in the order the values are returned
+        #[cfg(feature = "tuples")]
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        extra_dests: Vec<AbstractArgument>,
+        /// Metadata about this instruction, which is any other fields it has in JSON
+        #[cfg(feature = "attrs")]
+        #[serde(flatten)]
+        attrs: Attrs,
     },
     /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#effect-operation>
     Effect {
@@ -183,24 +218,36 @@ pub enum AbstractInstruction {
         #[cfg(feature = "position")]
         #[serde(skip_serializing_if = "Option::is_none")]
         pos: Option<Position>,
+        /// Metadata about this instruction, which is any other fields it has in JSON
+        #[cfg(feature = "attrs")]
+        #[serde(flatten)]
+        attrs: Attrs,
     },
 }
 
 impl Display for AbstractInstruction {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            AbstractInstruction::Constant {
+            Self::Constant {
                 op,
                 dest,
                 const_type,
                 value,
                 #[cfg(feature = "position")]
                     pos: _,
+                #[cfg(feature = "attrs")]
+                    attrs: _,
             } => match const_type {
+                // JSON reads a `str` of one character as a `char`
+                #[cfg(feature = "strings")]
+                Some(AbstractType::Primitive(t)) if t == "str" => {
+                    let value = value.clone().for_type(&crate::Type::Str);
+                    write!(f, "{dest}: str = {op} {value};")
+                }
                 Some(const_type) => write!(f, "{dest}: {const_type} = {op} {value};"),
                 None => write!(f, "{dest} = {op} {value};"),
             },
-            AbstractInstruction::Value {
+            Self::Value {
                 op,
                 dest,
                 op_type,
@@ -209,11 +256,20 @@ impl Display for AbstractInstruction {
                 labels,
                 #[cfg(feature = "position")]
                     pos: _,
+                #[cfg(feature = "tuples")]
+                extra_dests,
+                #[cfg(feature = "attrs")]
+                    attrs: _,
             } => {
                 match op_type {
-                    Some(op_type) => write!(f, "{dest}: {op_type} = {op}")?,
-                    None => write!(f, "{dest} = {op}")?,
+                    Some(op_type) => write!(f, "{dest}: {op_type}")?,
+                    None => write!(f, "{dest}")?,
+                }
+                #[cfg(feature = "tuples")]
+                for extra in extra_dests {
+                    write!(f, ", {extra}")?;
                 }
+                write!(f, " = {op}")?;
                 for func in funcs {
                     write!(f, " @{func}")?;
                 }
@@ -225,13 +281,15 @@ impl Display for AbstractInstruction {
                 }
                 write!(f, ";")
             }
-            AbstractInstruction::Effect {
+            Self::Effect {
                 op,
                 args,
                 funcs,
                 labels,
                 #[cfg(feature = "position")]
                     pos: _,
+                #[cfg(feature = "attrs")]
+                    attrs: _,
             } => {
                 write!(f, "{op}")?;
                 for func in funcs {
@@ -254,8 +312,11 @@ impl Display for AbstractInstruction {
 pub enum AbstractType {
     /// For example "bool" => Primitive("bool")
     Primitive(String),
-    /// For example "ptr<bool>" => Parameterized("ptr", Box::new(Primitive("bool")))
+    /// For example "ptr<bool>" => Parameterized("ptr", `Box::new(Primitive("bool`")))
     Parameterized(String, Box<Self>),
+    /// For example the return type "int, bool" => Tuple(vec![Primitive("int"), Primitive("bool")]), which is `{"tuple": ["int", "bool"]}` in JSON
+    #[cfg(feature = "tuples")]
+    Tuple(Vec<Self>),
 }
 
 struct AbstractTypeVisitor {
@@ -297,12 +358,13 @@ impl<'de> Visitor<'de> for AbstractTypeVisitor {
     {
         // While there are entries remaining in the input, add them
         // into our map.
-        if let Some((key, value)) = access.next_entry()? {
-            Ok(AbstractType::Parameterized(key, value))
-        } else {
-            Err(M::Error::custom(
+        match access.next_key::<String>()? {
+            #[cfg(feature = "tuples")]
+            Some(key) if key == "tuple" => Ok(AbstractType::Tuple(access.next_value()?)),
+            Some(key) => Ok(AbstractType::Parameterized(key, access.next_value()?)),
+            None => Err(M::Error::custom(
                 "Expected one value in map for AbstractType",
-            ))
+            )),
         }
     }
 }
@@ -322,12 +384,18 @@ impl Serialize for AbstractType {
         S: Serializer,
     {
         match self {
-            AbstractType::Primitive(s) => serializer.serialize_str(s),
-            AbstractType::Parameterized(t, at) => {
+            Self::Primitive(s) => serializer.serialize_str(s),
+            Self::Parameterized(t, at) => {
                 let mut map = serializer.serialize_map(Some(1))?;
                 map.serialize_entry(t, at)?;
                 map.end()
             }
+            #[cfg(feature = "tuples")]
+            Self::Tuple(types) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("tuple", types)?;
+                map.end()
+            }
         }
     }
 }
@@ -335,9 +403,19 @@ impl Serialize for AbstractType {
 impl Display for AbstractType {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            AbstractType::Primitive(t) => write!(f, "{t}"),
+            Self::Primitive(t) => write!(f, "{t}"),
 
-            AbstractType::Parameterized(t, at) => write!(f, "{t}<{at}>"),
+            Self::Parameterized(t, at) => write!(f, "{t}<{at}>"),
+            #[cfg(feature = "tuples")]
+            Self::Tuple(types) => {
+                for (i, t) in types.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{t}")?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -0,0 +1,353 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::callgraph::{calls, CallGraph};
+use crate::{Code, EffectOps, Instruction, Program, Type, ValueOps};
+
+/// Memory which a pointer of a [`Program`] can point into, as the place it is allocated. Functions are referred to by their index in ```functions```, like in [`CallGraph`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Location {
+    /// Everything allocated by the `alloc` at ```instr``` of the instructions of function ```func```, however many times it runs
+    Alloc {
+        /// The index of the function
+        func: usize,
+        /// The index of the `alloc` in the ```instrs``` of the function, counting labels
+        instr: usize,
+    },
+    /// Everything passed in as the pointer argument ```arg``` of function ```func```, which nothing in the program calls, like the arrays `brilirs` allocates for the arguments of main. The pointers stored in it, for arguments like `ptr<ptr<int>>`, are counted as pointing back into it
+    Argument {
+        /// The index of the function
+        func: usize,
+        /// The index of the argument
+        arg: usize,
+    },
+}
+
+/// A flow-insensitive points-to analysis of a [`Program`] using the memory extension, in the style of Andersen's, which finds the [`Location`]s every pointer variable can point into.
+///
+/// The whole program is analyzed at once, with the arguments of every `call` flowing into the arguments of the function called and what it returns flowing back out to the variables the `call` assigns. Each variable gets one set of locations for its whole function, no matter which instruction assigns it or in what order they run, and a `ptradd` points into the same allocation as the pointer it starts from, so two pointers can alias without ever pointing to the same offset. Calls to functions which aren't in the program are left out, like in [`CallGraph::undefined_calls`].
+/// ```
+/// use bril_rs::alias::{Location, PointsTo};
+///
+/// let json = r#"{"functions": [
+///     {"name": "main", "instrs": [
+///         {"op": "const", "dest": "n", "type": "int", "value": 4},
+///         {"op": "alloc", "dest": "a", "type": {"ptr": "int"}, "args": ["n"]},
+///         {"op": "alloc", "dest": "b", "type": {"ptr": "int"}, "args": ["n"]},
+///         {"op": "call", "dest": "c", "type": {"ptr": "int"}, "args": ["a", "b"], "funcs": ["second"]},
+///         {"op": "free", "args": ["a"]},
+///         {"op": "free", "args": ["b"]}
+///     ]},
+///     {"name": "second", "type": {"ptr": "int"}, "args": [{"name": "x", "type": {"ptr": "int"}}, {"name": "y", "type": {"ptr": "int"}}], "instrs": [
+///         {"op": "const", "dest": "one", "type": "int", "value": 1},
+///         {"op": "ptradd", "dest": "z", "type": {"ptr": "int"}, "args": ["y", "one"]},
+///         {"op": "ret", "args": ["z"]}
+///     ]}
+/// ]}"#;
+/// let prog: bril_rs::Program = serde_json::from_str(json).unwrap();
+/// let points_to = PointsTo::new(&prog);
+/// assert!(points_to.may_alias(0, "b", "c"));
+/// assert!(!points_to.may_alias(0, "a", "c"));
+/// assert_eq!(
+///     points_to.points_to(1, "z"),
+///     [Location::Alloc { func: 0, instr: 2 }].into()
+/// );
+/// ```
+#[derive(Debug, Clone)]
+#[allow(clippy::module_name_repetitions)]
+pub struct PointsTo {
+    locations: Vec<Location>,
+    // The node of each variable of each function
+    vars: Vec<HashMap<String, usize>>,
+    // The locations each node can point into, by their index in locations
+    pts: Vec<BTreeSet<usize>>,
+    // The node of the pointers stored in each location
+    contents: Vec<usize>,
+}
+
+impl PointsTo {
+    /// Analyzes ```prog```. If more than one function has the same name, calls to that name go to the last of them
+    #[must_use]
+    pub fn new(prog: &Program) -> Self {
+        let mut builder = Builder::default();
+        let graph = CallGraph::new(prog);
+        let index: HashMap<&str, usize> = prog
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.name.as_str(), i))
+            .collect();
+        let mut vars: Vec<HashMap<String, usize>> = vec![HashMap::new(); prog.functions.len()];
+        let returns: Vec<usize> = prog.functions.iter().map(|_| builder.node()).collect();
+        let mut var = |builder: &mut Builder, func: usize, name: &str| {
+            *vars[func]
+                .entry(name.to_string())
+                .or_insert_with(|| builder.node())
+        };
+
+        for (func, function) in prog.functions.iter().enumerate() {
+            // Nothing in the program passes the arguments of a function which is never called, so they are memory of their own
+            for (arg, a) in function.args.iter().enumerate() {
+                let node = var(&mut builder, func, &a.name);
+                if let (true, Type::Pointer(pointee)) =
+                    (graph.callers[func].is_empty(), &a.arg_type)
+                {
+                    let location = builder.location(Location::Argument { func, arg });
+                    builder.pts[node].insert(location);
+                    if matches!(**pointee, Type::Pointer(_)) {
+                        let contents = builder.contents[location];
+                        builder.pts[contents].insert(location);
+                    }
+                }
+            }
+
+            for (instr, code) in function.instrs.iter().enumerate() {
+                let Code::Instruction(i) = code else {
+                    continue;
+                };
+                if let Some(callee) = calls(i).first() {
+                    let Some(&callee) = index.get(callee.as_str()) else {
+                        continue;
+                    };
+                    let (Instruction::Value { args, .. } | Instruction::Effect { args, .. }) = i
+                    else {
+                        continue;
+                    };
+                    for (arg, param) in args.iter().zip(&prog.functions[callee].args) {
+                        let (from, to) = (
+                            var(&mut builder, func, arg),
+                            var(&mut builder, callee, &param.name),
+                        );
+                        builder.copies[from].insert(to);
+                    }
+                    // A `spawn` assigns the thread rather than what the function returns
+                    if let Instruction::Value {
+                        op: ValueOps::Call,
+                        dest,
+                        ..
+                    } = i
+                    {
+                        let to = var(&mut builder, func, dest);
+                        builder.copies[returns[callee]].insert(to);
+                        // Every value a function returns goes into all of the variables a call of it assigns
+                        #[cfg(feature = "tuples")]
+                        if let Instruction::Value { extra_dests, .. } = i {
+                            for extra in extra_dests {
+                                let to = var(&mut builder, func, &extra.name);
+                                builder.copies[returns[callee]].insert(to);
+                            }
+                        }
+                    }
+                    continue;
+                }
+                match i {
+                    Instruction::Value {
+                        op: ValueOps::Alloc,
+                        dest,
+                        ..
+                    } => {
+                        let node = var(&mut builder, func, dest);
+                        let location = builder.location(Location::Alloc { func, instr });
+                        builder.pts[node].insert(location);
+                    }
+                    Instruction::Value {
+                        op: ValueOps::Id | ValueOps::PtrAdd,
+                        dest,
+                        args,
+                        ..
+                    } => {
+                        let (from, to) = (
+                            var(&mut builder, func, &args[0]),
+                            var(&mut builder, func, dest),
+                        );
+                        builder.copies[from].insert(to);
+                    }
+                    #[cfg(feature = "ssa")]
+                    Instruction::Value {
+                        op: ValueOps::Phi,
+                        dest,
+                        args,
+                        ..
+                    } => {
+                        let to = var(&mut builder, func, dest);
+                        for arg in args {
+                            let from = var(&mut builder, func, arg);
+                            builder.copies[from].insert(to);
+                        }
+                    }
+                    Instruction::Value {
+                        op: ValueOps::Load,
+                        dest,
+                        args,
+                        ..
+                    } => {
+                        let (ptr, to) = (
+                            var(&mut builder, func, &args[0]),
+                            var(&mut builder, func, dest),
+                        );
+                        builder.loads[ptr].push(to);
+                    }
+                    Instruction::Effect {
+                        op: EffectOps::Store,
+                        args,
+                        ..
+                    } => {
+                        let (ptr, from) = (
+                            var(&mut builder, func, &args[0]),
+                            var(&mut builder, func, &args[1]),
+                        );
+                        builder.stores[ptr].push(from);
+                    }
+                    Instruction::Effect {
+                        op: EffectOps::Return,
+                        args,
+                        ..
+                    } => {
+                        for arg in args {
+                            let from = var(&mut builder, func, arg);
+                            builder.copies[from].insert(returns[func]);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let pts = builder.solve();
+        Self {
+            locations: builder.locations,
+            vars,
+            pts,
+            contents: builder.contents,
+        }
+    }
+}
+
+impl PointsTo {
+    /// The locations the variable ```var``` of function ```func``` can point into, which is empty for variables which aren't pointers or don't exist
+    #[must_use]
+    pub fn points_to(&self, func: usize, var: &str) -> BTreeSet<Location> {
+        self.set(func, var)
+            .map(|s| self.named(s))
+            .unwrap_or_default()
+    }
+
+    /// Whether the pointers ```a``` and ```b``` of function ```func``` can point into the same allocation. Pointers which the analysis finds nothing for, like ones which are never assigned, don't alias anything
+    #[must_use]
+    pub fn may_alias(&self, func: usize, a: &str, b: &str) -> bool {
+        match (self.set(func, a), self.set(func, b)) {
+            (Some(a), Some(b)) => !a.is_disjoint(b),
+            _ => false,
+        }
+    }
+
+    /// The locations ```instr``` of function ```func``` works with: where the pointer of a `load`, `store`, or `free` can point into, or where the pointer made by any other instruction can. This is empty for instructions which neither use nor make a pointer. Calls can still load from and store to the memory their arguments point into, which isn't counted here
+    #[must_use]
+    pub fn instruction(&self, func: usize, instr: &Instruction) -> BTreeSet<Location> {
+        match instr {
+            Instruction::Value {
+                op: ValueOps::Load,
+                args,
+                ..
+            }
+            | Instruction::Effect {
+                op: EffectOps::Store | EffectOps::Free,
+                args,
+                ..
+            } => self.points_to(func, &args[0]),
+            Instruction::Value { dest, .. } => self.points_to(func, dest),
+            _ => BTreeSet::new(),
+        }
+    }
+
+    /// The locations which the pointers stored in ```location``` can point into
+    #[must_use]
+    pub fn stored_in(&self, location: Location) -> BTreeSet<Location> {
+        self.locations
+            .iter()
+            .position(|l| *l == location)
+            .map_or_else(BTreeSet::new, |l| self.named(&self.pts[self.contents[l]]))
+    }
+
+    /// Every location the program can allocate, in the order they appear in it
+    #[must_use]
+    pub fn locations(&self) -> &[Location] {
+        &self.locations
+    }
+
+    fn set(&self, func: usize, var: &str) -> Option<&BTreeSet<usize>> {
+        self.vars.get(func)?.get(var).map(|node| &self.pts[*node])
+    }
+
+    fn named(&self, set: &BTreeSet<usize>) -> BTreeSet<Location> {
+        set.iter().map(|l| self.locations[*l]).collect()
+    }
+}
+
+// The constraints between the nodes of the analysis, which are the variables of every function, what each function returns, and the pointers stored in each location. Each node gets the locations it can point into
+#[derive(Default)]
+struct Builder {
+    locations: Vec<Location>,
+    contents: Vec<usize>,
+    pts: Vec<BTreeSet<usize>>,
+    // Everything the first node points into, the second does too
+    copies: Vec<BTreeSet<usize>>,
+    // The nodes which are loaded into from a pointer of this node
+    loads: Vec<Vec<usize>>,
+    // The nodes which are stored into a pointer of this node
+    stores: Vec<Vec<usize>>,
+}
+
+impl Builder {
+    fn node(&mut self) -> usize {
+        self.pts.push(BTreeSet::new());
+        self.copies.push(BTreeSet::new());
+        self.loads.push(Vec::new());
+        self.stores.push(Vec::new());
+        self.pts.len() - 1
+    }
+
+    fn location(&mut self, location: Location) -> usize {
+        let contents = self.node();
+        self.locations.push(location);
+        self.contents.push(contents);
+        self.locations.len() - 1
+    }
+
+    // Propagates along the copies with a work list, adding the copies which loads and stores make as the pointers they go through find more locations
+    fn solve(&mut self) -> Vec<BTreeSet<usize>> {
+        let mut work_list: Vec<usize> = (0..self.pts.len())
+            .filter(|n| !self.pts[*n].is_empty())
+            .collect();
+        while let Some(node) = work_list.pop() {
+            for location in self.pts[node].clone() {
+                let contents = self.contents[location];
+                for to in self.loads[node].clone() {
+                    if self.copies[contents].insert(to) && self.flow(contents, to) {
+                        work_list.push(to);
+                    }
+                }
+                for from in self.stores[node].clone() {
+                    if self.copies[from].insert(contents) && self.flow(from, contents) {
+                        work_list.push(contents);
+                    }
+                }
+            }
+            for to in self.copies[node].clone() {
+                if self.flow(node, to) {
+                    work_list.push(to);
+                }
+            }
+        }
+        std::mem::take(&mut self.pts)
+    }
+
+    // Adds everything ```from``` points into to what ```to``` does, returning whether that was anything new
+    fn flow(&mut self, from: usize, to: usize) -> bool {
+        if from == to {
+            return false;
+        }
+        let new: Vec<usize> = self.pts[from].difference(&self.pts[to]).copied().collect();
+        self.pts[to].extend(&new);
+        !new.is_empty()
+    }
+}
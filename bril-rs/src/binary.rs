@@ -0,0 +1,921 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+#[cfg(feature = "attrs")]
+use crate::Attrs;
+#[cfg(feature = "position")]
+use crate::Position;
+use crate::{
+    Argument, Code, ConstOps, EffectOps, Function, Instruction, Literal, Program, Type, ValueOps,
+};
+#[cfg(feature = "import")]
+use crate::{Import, ImportedFunction};
+
+/// The bytes that every binary Bril program starts with
+pub const MAGIC: &[u8; 4] = b"BRIL";
+
+/// The version of the format written by [`to_bytes`], which comes right after [`MAGIC`]
+pub const VERSION: u8 = 1;
+
+/// The errors from reading a binary Bril program with [`from_bytes`]
+#[derive(Error, Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub enum BinaryError {
+    /// The input does not start with [`MAGIC`]
+    #[error("not a binary Bril program")]
+    BadMagic,
+    /// The input was written with a version of the format which this one can't read
+    #[error("unsupported version {0} of the binary Bril format")]
+    UnsupportedVersion(u8),
+    /// The input ends part of the way through the program
+    #[error("the program ends unexpectedly at byte {0}")]
+    UnexpectedEnd(usize),
+    /// A number at the given byte is too large for what it is used for
+    #[error("number too large at byte {0}")]
+    TooLarge(usize),
+    /// The name at the given index of the name table is not UTF-8
+    #[error("name {0} is not valid UTF-8")]
+    InvalidName(usize),
+    /// A name refers past the end of the name table
+    #[error("there is no name {0}")]
+    UnknownName(u64),
+    /// A code for a kind of instruction, operation, type, or literal is not one that this build knows. Either the input is not valid or it uses an extension whose feature is not enabled
+    #[error("unknown {0} code {1} at byte {2}")]
+    UnknownCode(&'static str, u8, usize),
+    /// A `char` literal is not a Unicode scalar value
+    #[error("{0:#x} is not a valid char")]
+    InvalidChar(u64),
+    /// There is more input after the end of the program
+    #[error("unexpected data after the end of the program at byte {0}")]
+    TrailingData(usize),
+}
+
+// The code of every value operation. These never change so that programs stay readable, and they don't depend on which features are enabled
+const VALUE_OPS: &[(u8, ValueOps)] = &[
+    (0, ValueOps::Add),
+    (1, ValueOps::Sub),
+    (2, ValueOps::Mul),
+    (3, ValueOps::Div),
+    (4, ValueOps::Eq),
+    (5, ValueOps::Lt),
+    (6, ValueOps::Gt),
+    (7, ValueOps::Le),
+    (8, ValueOps::Ge),
+    (9, ValueOps::Not),
+    (10, ValueOps::And),
+    (11, ValueOps::Or),
+    (12, ValueOps::Call),
+    (13, ValueOps::Id),
+    #[cfg(feature = "ssa")]
+    (14, ValueOps::Phi),
+    #[cfg(feature = "float")]
+    (15, ValueOps::Fadd),
+    #[cfg(feature = "float")]
+    (16, ValueOps::Fsub),
+    #[cfg(feature = "float")]
+    (17, ValueOps::Fmul),
+    #[cfg(feature = "float")]
+    (18, ValueOps::Fdiv),
+    #[cfg(feature = "float")]
+    (19, ValueOps::Feq),
+    #[cfg(feature = "float")]
+    (20, ValueOps::Flt),
+    #[cfg(feature = "float")]
+    (21, ValueOps::Fgt),
+    #[cfg(feature = "float")]
+    (22, ValueOps::Fle),
+    #[cfg(feature = "float")]
+    (23, ValueOps::Fge),
+    #[cfg(feature = "memory")]
+    (24, ValueOps::Alloc),
+    #[cfg(feature = "memory")]
+    (25, ValueOps::Load),
+    #[cfg(feature = "memory")]
+    (26, ValueOps::PtrAdd),
+    #[cfg(feature = "char")]
+    (27, ValueOps::Ceq),
+    #[cfg(feature = "char")]
+    (28, ValueOps::Clt),
+    #[cfg(feature = "char")]
+    (29, ValueOps::Cle),
+    #[cfg(feature = "char")]
+    (30, ValueOps::Cgt),
+    #[cfg(feature = "char")]
+    (31, ValueOps::Cge),
+    #[cfg(feature = "char")]
+    (32, ValueOps::Char2int),
+    #[cfg(feature = "char")]
+    (33, ValueOps::Int2char),
+    #[cfg(feature = "float")]
+    (34, ValueOps::Float2bits),
+    #[cfg(feature = "float")]
+    (35, ValueOps::Bits2float),
+    (36, ValueOps::Rem),
+    #[cfg(feature = "strings")]
+    (37, ValueOps::Concat),
+    #[cfg(feature = "strings")]
+    (38, ValueOps::Length),
+    #[cfg(feature = "strings")]
+    (39, ValueOps::Index),
+    #[cfg(feature = "concurrency")]
+    (40, ValueOps::Spawn),
+    #[cfg(feature = "rand")]
+    (41, ValueOps::Rand),
+];
+
+// The code of every effect operation, which are as fixed as those of VALUE_OPS
+const EFFECT_OPS: &[(u8, EffectOps)] = &[
+    (0, EffectOps::Jump),
+    (1, EffectOps::Branch),
+    (2, EffectOps::Call),
+    (3, EffectOps::Return),
+    (4, EffectOps::Print),
+    (5, EffectOps::Nop),
+    #[cfg(feature = "memory")]
+    (6, EffectOps::Store),
+    #[cfg(feature = "memory")]
+    (7, EffectOps::Free),
+    #[cfg(feature = "speculate")]
+    (8, EffectOps::Speculate),
+    #[cfg(feature = "speculate")]
+    (9, EffectOps::Commit),
+    #[cfg(feature = "speculate")]
+    (10, EffectOps::Guard),
+    #[cfg(feature = "concurrency")]
+    (11, EffectOps::Join),
+];
+
+// The kinds of code
+const LABEL: u8 = 0;
+const CONSTANT: u8 = 1;
+const VALUE: u8 = 2;
+const EFFECT: u8 = 3;
+// A value instruction with more than one destination, which is written like one with a single destination followed by the others
+#[cfg(feature = "tuples")]
+const VALUES: u8 = 4;
+
+// The codes of types, where a pointer is followed by the type it points to and a tuple by the number of types in it and then each of them
+const INT: u8 = 0;
+const BOOL: u8 = 1;
+#[cfg(feature = "float")]
+const FLOAT: u8 = 2;
+#[cfg(feature = "char")]
+const CHAR: u8 = 3;
+#[cfg(feature = "memory")]
+const POINTER: u8 = 4;
+#[cfg(feature = "strings")]
+const STR: u8 = 5;
+#[cfg(feature = "concurrency")]
+const THREAD: u8 = 6;
+#[cfg(feature = "tuples")]
+const TUPLE: u8 = 7;
+
+// Whether a position follows, which is a row and a column
+const NO_POSITION: u8 = 0;
+const POSITION: u8 = 1;
+const DERIVED_POSITION: u8 = 2;
+// The same, with a source position after the column, which is the name of its file and then its row and column
+const SOURCE_POSITION: u8 = 3;
+const DERIVED_SOURCE_POSITION: u8 = 4;
+
+// The codes of literals, where booleans need nothing after them and strings are kept in the name table
+const INT_LITERAL: u8 = 0;
+const FALSE_LITERAL: u8 = 1;
+const TRUE_LITERAL: u8 = 2;
+#[cfg(feature = "float")]
+const FLOAT_LITERAL: u8 = 3;
+#[cfg(feature = "char")]
+const CHAR_LITERAL: u8 = 4;
+#[cfg(feature = "strings")]
+const STR_LITERAL: u8 = 5;
+
+/// Encodes ```prog``` in the binary format of Bril, which is much smaller than JSON and much faster to load.
+///
+/// The format is [`MAGIC`] and [`VERSION`], then a table of every name in the program, like the names of variables, functions, and labels, and then the program itself with each name written as its index in the table. Numbers are unsigned LEB128 varints, integer literals are zigzag encoded first so that small negative numbers stay small, and floats are their 8 bytes in little-endian order so that every bit pattern survives. Operations and types have fixed codes which don't depend on which features are enabled, so programs can be read by any build with the features they use. The [`Attrs`](crate::Attrs) of the program and of its functions and instructions are left out, like they are from the text format. [`from_bytes`] reads it back.
+/// ```
+/// # use bril_rs::{binary, builder::FunctionBuilder, Literal, Program, Type};
+/// let mut builder = FunctionBuilder::new("main");
+/// let x = builder.constant(Type::Float, Literal::Float(-0.1));
+/// builder.print(&[&x]);
+/// let prog = Program {
+///     functions: vec![builder.finish().unwrap()],
+///     imports: Vec::new(),
+///     attrs: Default::default(),
+/// };
+///
+/// let bytes = binary::to_bytes(&prog);
+/// assert!(bytes.len() < serde_json::to_vec(&prog).unwrap().len() / 2);
+/// let decoded = binary::from_bytes(&bytes).unwrap();
+/// assert_eq!(decoded.to_string(), prog.to_string());
+/// ```
+/// # Panics
+/// Will panic if the path of an import is not valid UTF-8, which JSON can't represent either
+#[must_use]
+pub fn to_bytes(prog: &Program) -> Vec<u8> {
+    let mut body = Writer::default();
+    #[cfg(feature = "import")]
+    {
+        body.varint(prog.imports.len() as u64);
+        for import in &prog.imports {
+            body.import(import);
+        }
+    }
+    #[cfg(not(feature = "import"))]
+    body.varint(0);
+    body.varint(prog.functions.len() as u64);
+    for func in &prog.functions {
+        body.function(func);
+    }
+
+    let mut header = Writer::default();
+    header.out.extend_from_slice(MAGIC);
+    header.out.push(VERSION);
+    header.varint(body.names.len() as u64);
+    for name in &body.names {
+        header.varint(name.len() as u64);
+        header.out.extend_from_slice(name.as_bytes());
+    }
+    header.out.extend_from_slice(&body.out);
+    header.out
+}
+
+/// Decodes a program which was encoded with [`to_bytes`]
+/// # Errors
+/// Will return an error if ```bytes``` is not a whole binary Bril program, or it uses an extension whose feature is not enabled
+pub fn from_bytes(bytes: &[u8]) -> Result<Program, BinaryError> {
+    let mut reader = Reader {
+        bytes,
+        at: 0,
+        names: Vec::new(),
+    };
+    if bytes.get(..MAGIC.len()) != Some(MAGIC) {
+        return Err(BinaryError::BadMagic);
+    }
+    reader.at = MAGIC.len();
+    let version = reader.byte()?;
+    if version != VERSION {
+        return Err(BinaryError::UnsupportedVersion(version));
+    }
+    for i in 0..reader.len()? {
+        let len = reader.len()?;
+        let name = reader.take(len)?;
+        reader
+            .names
+            .push(String::from_utf8(name.to_vec()).map_err(|_| BinaryError::InvalidName(i))?);
+    }
+
+    #[cfg(feature = "import")]
+    let imports = (0..reader.len()?)
+        .map(|_| reader.import())
+        .collect::<Result<_, _>>()?;
+    // Imports can't be represented without the feature, so they are left out like they are from JSON
+    #[cfg(not(feature = "import"))]
+    for _ in 0..reader.len()? {
+        reader.name()?;
+        for _ in 0..reader.len()? {
+            reader.name()?;
+            reader.optional_name()?;
+        }
+    }
+    let functions = (0..reader.len()?)
+        .map(|_| reader.function())
+        .collect::<Result<_, _>>()?;
+    if reader.at != bytes.len() {
+        return Err(BinaryError::TrailingData(reader.at));
+    }
+    Ok(Program {
+        functions,
+        #[cfg(feature = "import")]
+        imports,
+        #[cfg(feature = "attrs")]
+        attrs: Attrs::new(),
+    })
+}
+
+// Writes the body of a program, collecting the names it uses along the way
+#[derive(Default)]
+struct Writer<'a> {
+    out: Vec<u8>,
+    indices: HashMap<&'a str, u64>,
+    names: Vec<&'a str>,
+}
+
+impl<'a> Writer<'a> {
+    #[allow(clippy::cast_possible_truncation)]
+    fn varint(&mut self, mut n: u64) {
+        // Each byte has 7 bits of the number, lowest first, with the top bit set on every byte but the last
+        while n >= 0x80 {
+            self.out.push((n & 0x7f) as u8 | 0x80);
+            n >>= 7;
+        }
+        self.out.push(n as u8);
+    }
+
+    fn name(&mut self, name: &'a str) {
+        let next = self.names.len() as u64;
+        let index = *self.indices.entry(name).or_insert_with(|| {
+            self.names.push(name);
+            next
+        });
+        self.varint(index);
+    }
+
+    fn names(&mut self, names: &'a [String]) {
+        self.varint(names.len() as u64);
+        for name in names {
+            self.name(name);
+        }
+    }
+
+    #[cfg(feature = "position")]
+    fn position(&mut self, pos: Option<Position>) {
+        match pos {
+            None => self.out.push(NO_POSITION),
+            Some(pos) => {
+                self.out.push(match (pos.derived, pos.source.is_some()) {
+                    (false, false) => POSITION,
+                    (true, false) => DERIVED_POSITION,
+                    (false, true) => SOURCE_POSITION,
+                    (true, true) => DERIVED_SOURCE_POSITION,
+                });
+                self.varint(pos.row);
+                self.varint(pos.col);
+                if let Some(source) = pos.source {
+                    self.name(source.file.name());
+                    self.varint(source.row);
+                    self.varint(source.col);
+                }
+            }
+        }
+    }
+
+    fn ty(&mut self, ty: &Type) {
+        match ty {
+            Type::Int => self.out.push(INT),
+            Type::Bool => self.out.push(BOOL),
+            #[cfg(feature = "float")]
+            Type::Float => self.out.push(FLOAT),
+            #[cfg(feature = "char")]
+            Type::Char => self.out.push(CHAR),
+            #[cfg(feature = "strings")]
+            Type::Str => self.out.push(STR),
+            #[cfg(feature = "concurrency")]
+            Type::Thread => self.out.push(THREAD),
+            #[cfg(feature = "memory")]
+            Type::Pointer(ty) => {
+                self.out.push(POINTER);
+                self.ty(ty);
+            }
+            #[cfg(feature = "tuples")]
+            Type::Tuple(types) => {
+                self.out.push(TUPLE);
+                self.varint(types.len() as u64);
+                for ty in types {
+                    self.ty(ty);
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn literal(&mut self, value: &'a Literal) {
+        match value {
+            Literal::Int(i) => {
+                self.out.push(INT_LITERAL);
+                self.varint(((i << 1) ^ (i >> 63)) as u64);
+            }
+            Literal::Bool(false) => self.out.push(FALSE_LITERAL),
+            Literal::Bool(true) => self.out.push(TRUE_LITERAL),
+            #[cfg(feature = "float")]
+            Literal::Float(f) => {
+                self.out.push(FLOAT_LITERAL);
+                self.out.extend_from_slice(&f.to_le_bytes());
+            }
+            #[cfg(feature = "char")]
+            Literal::Char(c) => {
+                self.out.push(CHAR_LITERAL);
+                self.varint(u64::from(*c));
+            }
+            #[cfg(feature = "strings")]
+            Literal::Str(s) => {
+                self.out.push(STR_LITERAL);
+                self.name(s);
+            }
+        }
+    }
+
+    #[cfg(feature = "import")]
+    fn import(&mut self, import: &'a Import) {
+        self.name(
+            import
+                .path
+                .to_str()
+                .expect("import paths have to be valid UTF-8"),
+        );
+        self.varint(import.functions.len() as u64);
+        for func in &import.functions {
+            self.name(&func.name);
+            match &func.alias {
+                None => self.varint(0),
+                Some(alias) => {
+                    self.varint(1);
+                    self.name(alias);
+                }
+            }
+        }
+    }
+
+    fn function(&mut self, func: &'a Function) {
+        self.name(&func.name);
+        self.varint(func.args.len() as u64);
+        for arg in &func.args {
+            self.name(&arg.name);
+            self.ty(&arg.arg_type);
+        }
+        match &func.return_type {
+            None => self.out.push(0),
+            Some(ty) => {
+                self.out.push(1);
+                self.ty(ty);
+            }
+        }
+        #[cfg(feature = "position")]
+        self.position(func.pos);
+        #[cfg(not(feature = "position"))]
+        self.out.push(0);
+        self.varint(func.instrs.len() as u64);
+        for code in &func.instrs {
+            self.code(code);
+        }
+    }
+
+    fn code(&mut self, code: &'a Code) {
+        match code {
+            Code::Label {
+                label,
+                #[cfg(feature = "position")]
+                pos,
+            } => {
+                self.out.push(LABEL);
+                self.name(label);
+                #[cfg(feature = "position")]
+                self.position(*pos);
+            }
+            Code::Instruction(Instruction::Constant {
+                dest,
+                op: ConstOps::Const,
+                #[cfg(feature = "position")]
+                pos,
+                const_type,
+                value,
+                #[cfg(feature = "attrs")]
+                    attrs: _,
+            }) => {
+                self.out.push(CONSTANT);
+                self.name(dest);
+                self.ty(const_type);
+                self.literal(value);
+                #[cfg(feature = "position")]
+                self.position(*pos);
+            }
+            Code::Instruction(Instruction::Value {
+                args,
+                dest,
+                funcs,
+                labels,
+                op,
+                #[cfg(feature = "position")]
+                pos,
+                op_type,
+                #[cfg(feature = "tuples")]
+                extra_dests,
+                #[cfg(feature = "attrs")]
+                    attrs: _,
+            }) => {
+                #[cfg(feature = "tuples")]
+                self.out.push(if extra_dests.is_empty() {
+                    VALUE
+                } else {
+                    VALUES
+                });
+                #[cfg(not(feature = "tuples"))]
+                self.out.push(VALUE);
+                self.out.push(code_of(VALUE_OPS, op));
+                self.name(dest);
+                self.ty(op_type);
+                self.names(args);
+                self.names(funcs);
+                self.names(labels);
+                #[cfg(feature = "position")]
+                self.position(*pos);
+                #[cfg(feature = "tuples")]
+                if !extra_dests.is_empty() {
+                    self.varint(extra_dests.len() as u64);
+                    for dest in extra_dests {
+                        self.name(&dest.name);
+                        self.ty(&dest.arg_type);
+                    }
+                }
+            }
+            Code::Instruction(Instruction::Effect {
+                args,
+                funcs,
+                labels,
+                op,
+                #[cfg(feature = "position")]
+                pos,
+                #[cfg(feature = "attrs")]
+                    attrs: _,
+            }) => {
+                self.out.push(EFFECT);
+                self.out.push(code_of(EFFECT_OPS, op));
+                self.names(args);
+                self.names(funcs);
+                self.names(labels);
+                #[cfg(feature = "position")]
+                self.position(*pos);
+            }
+        }
+        #[cfg(not(feature = "position"))]
+        self.out.push(0);
+    }
+}
+
+fn code_of<T: PartialEq>(table: &[(u8, T)], op: &T) -> u8 {
+    // Every operation which can be compiled in is in its table
+    table.iter().find(|(_, o)| o == op).unwrap().0
+}
+
+// Reads a program back in, once the table of names has been read
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+    names: Vec<String>,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, BinaryError> {
+        let byte = *self
+            .bytes
+            .get(self.at)
+            .ok_or(BinaryError::UnexpectedEnd(self.at))?;
+        self.at += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BinaryError> {
+        let bytes = self
+            .bytes
+            .get(self.at..self.at.saturating_add(len))
+            .ok_or(BinaryError::UnexpectedEnd(self.bytes.len()))?;
+        self.at += len;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64, BinaryError> {
+        let start = self.at;
+        let mut n = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            let bits = u64::from(byte & 0x7f);
+            // The last of the ten bytes a u64 can take only has room for one bit
+            if shift == 63 && bits > 1 {
+                return Err(BinaryError::TooLarge(start));
+            }
+            n |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(BinaryError::TooLarge(start))
+    }
+
+    // The number of things in a list, which each take at least a byte, so that nothing too large is allocated for bad input
+    fn len(&mut self) -> Result<usize, BinaryError> {
+        let start = self.at;
+        usize::try_from(self.varint()?)
+            .ok()
+            .filter(|len| *len <= self.bytes.len() - self.at)
+            .ok_or(BinaryError::TooLarge(start))
+    }
+
+    fn name(&mut self) -> Result<String, BinaryError> {
+        let index = self.varint()?;
+        usize::try_from(index)
+            .ok()
+            .and_then(|i| self.names.get(i))
+            .cloned()
+            .ok_or(BinaryError::UnknownName(index))
+    }
+
+    fn names(&mut self) -> Result<Vec<String>, BinaryError> {
+        (0..self.len()?).map(|_| self.name()).collect()
+    }
+
+    fn optional_name(&mut self) -> Result<Option<String>, BinaryError> {
+        if self.flag("name")? {
+            self.name().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Whether something optional is there
+    fn flag(&mut self, what: &'static str) -> Result<bool, BinaryError> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            code => Err(BinaryError::UnknownCode(what, code, self.at - 1)),
+        }
+    }
+
+    // Whether there is a position next, and if so whether it is derived and whether it has a source position
+    fn position_flag(&mut self) -> Result<Option<(bool, bool)>, BinaryError> {
+        match self.byte()? {
+            NO_POSITION => Ok(None),
+            POSITION => Ok(Some((false, false))),
+            DERIVED_POSITION => Ok(Some((true, false))),
+            SOURCE_POSITION => Ok(Some((false, true))),
+            DERIVED_SOURCE_POSITION => Ok(Some((true, true))),
+            code => Err(BinaryError::UnknownCode("position", code, self.at - 1)),
+        }
+    }
+
+    #[cfg(feature = "position")]
+    fn position(&mut self) -> Result<Option<Position>, BinaryError> {
+        match self.position_flag()? {
+            Some((derived, has_source)) => Ok(Some(Position {
+                row: self.varint()?,
+                col: self.varint()?,
+                derived,
+                source: if has_source {
+                    Some(crate::SourcePosition {
+                        file: crate::SourceFile::new(&self.name()?),
+                        row: self.varint()?,
+                        col: self.varint()?,
+                    })
+                } else {
+                    None
+                },
+            })),
+            None => Ok(None),
+        }
+    }
+
+    // Reads a position which there is nowhere to keep without the feature
+    #[cfg(not(feature = "position"))]
+    fn position(&mut self) -> Result<(), BinaryError> {
+        if let Some((_, has_source)) = self.position_flag()? {
+            self.varint()?;
+            self.varint()?;
+            if has_source {
+                self.name()?;
+                self.varint()?;
+                self.varint()?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reads a type, which can be a tuple of types that aren't tuples themselves
+    fn ty(&mut self) -> Result<Type, BinaryError> {
+        #[cfg(feature = "tuples")]
+        if self.bytes.get(self.at) == Some(&TUPLE) {
+            self.at += 1;
+            return Ok(Type::Tuple(
+                (0..self.len()?)
+                    .map(|_| self.element_ty())
+                    .collect::<Result<_, _>>()?,
+            ));
+        }
+        self.element_ty()
+    }
+
+    #[cfg_attr(not(feature = "memory"), allow(unused_mut, clippy::never_loop))]
+    fn element_ty(&mut self) -> Result<Type, BinaryError> {
+        // Pointers are counted up instead of read recursively so that deeply nested types can't overflow the stack
+        let mut pointers = 0;
+        let ty = loop {
+            let at = self.at;
+            match self.byte()? {
+                INT => break Type::Int,
+                BOOL => break Type::Bool,
+                #[cfg(feature = "float")]
+                FLOAT => break Type::Float,
+                #[cfg(feature = "char")]
+                CHAR => break Type::Char,
+                #[cfg(feature = "strings")]
+                STR => break Type::Str,
+                #[cfg(feature = "concurrency")]
+                THREAD => break Type::Thread,
+                #[cfg(feature = "memory")]
+                POINTER => pointers += 1,
+                code => return Err(BinaryError::UnknownCode("type", code, at)),
+            }
+        };
+        #[cfg(feature = "memory")]
+        let ty = (0..pointers).fold(ty, |ty, _| Type::Pointer(Box::new(ty)));
+        #[cfg(not(feature = "memory"))]
+        let _: u32 = pointers;
+        Ok(ty)
+    }
+
+    #[allow(clippy::cast_possible_wrap)]
+    fn literal(&mut self) -> Result<Literal, BinaryError> {
+        let at = self.at;
+        match self.byte()? {
+            INT_LITERAL => {
+                let n = self.varint()?;
+                Ok(Literal::Int((n >> 1) as i64 ^ -((n & 1) as i64)))
+            }
+            FALSE_LITERAL => Ok(Literal::Bool(false)),
+            TRUE_LITERAL => Ok(Literal::Bool(true)),
+            #[cfg(feature = "float")]
+            FLOAT_LITERAL => {
+                let bytes = self.take(8)?;
+                Ok(Literal::Float(f64::from_le_bytes(
+                    bytes.try_into().unwrap(),
+                )))
+            }
+            #[cfg(feature = "char")]
+            CHAR_LITERAL => {
+                let n = self.varint()?;
+                u32::try_from(n)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map(Literal::Char)
+                    .ok_or(BinaryError::InvalidChar(n))
+            }
+            #[cfg(feature = "strings")]
+            STR_LITERAL => self.name().map(Literal::Str),
+            code => Err(BinaryError::UnknownCode("literal", code, at)),
+        }
+    }
+
+    fn op<T: Copy>(&mut self, table: &[(u8, T)]) -> Result<T, BinaryError> {
+        let at = self.at;
+        let code = self.byte()?;
+        table
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, op)| *op)
+            .ok_or(BinaryError::UnknownCode("operation", code, at))
+    }
+
+    #[cfg(feature = "import")]
+    fn import(&mut self) -> Result<Import, BinaryError> {
+        let path = self.name()?.into();
+        let functions = (0..self.len()?)
+            .map(|_| {
+                Ok(ImportedFunction {
+                    name: self.name()?,
+                    alias: self.optional_name()?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Import { functions, path })
+    }
+
+    fn function(&mut self) -> Result<Function, BinaryError> {
+        let name = self.name()?;
+        let args = (0..self.len()?)
+            .map(|_| {
+                Ok(Argument {
+                    name: self.name()?,
+                    arg_type: self.ty()?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let return_type = if self.flag("return type")? {
+            Some(self.ty()?)
+        } else {
+            None
+        };
+        #[cfg_attr(
+            not(feature = "position"),
+            allow(unused_variables, clippy::let_unit_value)
+        )]
+        let pos = self.position()?;
+        let instrs = (0..self.len()?)
+            .map(|_| self.code())
+            .collect::<Result<_, _>>()?;
+        Ok(Function {
+            args,
+            instrs,
+            name,
+            #[cfg(feature = "position")]
+            pos,
+            return_type,
+            #[cfg(feature = "attrs")]
+            attrs: Attrs::new(),
+        })
+    }
+
+    #[cfg_attr(
+        not(feature = "position"),
+        allow(unused_variables, clippy::let_unit_value)
+    )]
+    fn code(&mut self) -> Result<Code, BinaryError> {
+        let at = self.at;
+        Ok(match self.byte()? {
+            LABEL => {
+                let label = self.name()?;
+                let pos = self.position()?;
+                Code::Label {
+                    label,
+                    #[cfg(feature = "position")]
+                    pos,
+                }
+            }
+            CONSTANT => {
+                let dest = self.name()?;
+                let const_type = self.ty()?;
+                let value = self.literal()?;
+                let pos = self.position()?;
+                Code::Instruction(Instruction::Constant {
+                    dest,
+                    op: ConstOps::Const,
+                    #[cfg(feature = "position")]
+                    pos,
+                    const_type,
+                    value,
+                    #[cfg(feature = "attrs")]
+                    attrs: Attrs::new(),
+                })
+            }
+            VALUE => {
+                let op = self.op(VALUE_OPS)?;
+                let dest = self.name()?;
+                let op_type = self.ty()?;
+                let args = self.names()?;
+                let funcs = self.names()?;
+                let labels = self.names()?;
+                let pos = self.position()?;
+                Code::Instruction(Instruction::Value {
+                    args,
+                    dest,
+                    funcs,
+                    labels,
+                    op,
+                    #[cfg(feature = "position")]
+                    pos,
+                    op_type,
+                    #[cfg(feature = "tuples")]
+                    extra_dests: Vec::new(),
+                    #[cfg(feature = "attrs")]
+                    attrs: Attrs::new(),
+                })
+            }
+            #[cfg(feature = "tuples")]
+            VALUES => {
+                let op = self.op(VALUE_OPS)?;
+                let dest = self.name()?;
+                let op_type = self.ty()?;
+                let args = self.names()?;
+                let funcs = self.names()?;
+                let labels = self.names()?;
+                let pos = self.position()?;
+                let extra_dests = (0..self.len()?)
+                    .map(|_| {
+                        Ok(Argument {
+                            name: self.name()?,
+                            arg_type: self.ty()?,
+                        })
+                    })
+                    .collect::<Result<_, _>>()?;
+                Code::Instruction(Instruction::Value {
+                    args,
+                    dest,
+                    funcs,
+                    labels,
+                    op,
+                    #[cfg(feature = "position")]
+                    pos,
+                    op_type,
+                    extra_dests,
+                    #[cfg(feature = "attrs")]
+                    attrs: Attrs::new(),
+                })
+            }
+            EFFECT => {
+                let op = self.op(EFFECT_OPS)?;
+                let args = self.names()?;
+                let funcs = self.names()?;
+                let labels = self.names()?;
+                let pos = self.position()?;
+                Code::Instruction(Instruction::Effect {
+                    args,
+                    funcs,
+                    labels,
+                    op,
+                    #[cfg(feature = "position")]
+                    pos,
+                    #[cfg(feature = "attrs")]
+                    attrs: Attrs::new(),
+                })
+            }
+            code => return Err(BinaryError::UnknownCode("instruction", code, at)),
+        })
+    }
+}
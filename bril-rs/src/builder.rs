@@ -0,0 +1,679 @@
+use std::collections::{HashMap, HashSet};
+
+use thiserror::Error;
+
+use crate::{
+    Argument, Code, ConstOps, EffectOps, Function, Instruction, Literal, Program, Type, ValueOps,
+};
+
+#[cfg(feature = "attrs")]
+use crate::Attrs;
+#[cfg(feature = "position")]
+use crate::Position;
+
+/// The errors found when finishing a [`FunctionBuilder`] or [`ProgramBuilder`]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+pub enum BuildError {
+    /// `{op}` in @{func} expects {expected} arguments, found {found}
+    #[error("`{op}` in @{func} expects {expected} arguments, found {found}")]
+    BadNumArgs {
+        /// The function containing the instruction
+        func: String,
+        /// The operation of the instruction
+        op: String,
+        /// The number of arguments the operation takes
+        expected: usize,
+        /// The number of arguments the instruction was given
+        found: usize,
+    },
+    /// `{op}` in @{func} expects {expected} functions, found {found}
+    #[error("`{op}` in @{func} expects {expected} functions, found {found}")]
+    BadNumFuncs {
+        /// The function containing the instruction
+        func: String,
+        /// The operation of the instruction
+        op: String,
+        /// The number of functions the operation takes
+        expected: usize,
+        /// The number of functions the instruction was given
+        found: usize,
+    },
+    /// `{op}` in @{func} expects {expected} labels, found {found}
+    #[error("`{op}` in @{func} expects {expected} labels, found {found}")]
+    BadNumLabels {
+        /// The function containing the instruction
+        func: String,
+        /// The operation of the instruction
+        op: String,
+        /// The number of labels the operation takes
+        expected: usize,
+        /// The number of labels the instruction was given
+        found: usize,
+    },
+    /// Label .{label} is used in @{func} but never defined
+    #[error("label .{label} is used in @{func} but never defined")]
+    UndefinedLabel {
+        /// The function using the label
+        func: String,
+        /// The missing label
+        label: String,
+    },
+    /// Label .{label} is defined more than once in @{func}
+    #[error("label .{label} is defined more than once in @{func}")]
+    DuplicateLabel {
+        /// The function defining the label
+        func: String,
+        /// The duplicated label
+        label: String,
+    },
+    /// @{0} is defined more than once
+    #[error("@{0} is defined more than once")]
+    DuplicateFunction(String),
+    /// @{caller} calls @{callee} which is never defined
+    #[error("@{caller} calls @{callee} which is never defined")]
+    UndefinedFunction {
+        /// The function making the call
+        caller: String,
+        /// The missing function
+        callee: String,
+    },
+}
+
+// The number of arguments and labels each operation requires. Operations that accept any number of arguments give None
+const fn value_arity(op: ValueOps) -> (Option<usize>, usize) {
+    match op {
+        ValueOps::Add
+        | ValueOps::Sub
+        | ValueOps::Mul
+        | ValueOps::Div
+        | ValueOps::Rem
+        | ValueOps::Eq
+        | ValueOps::Lt
+        | ValueOps::Gt
+        | ValueOps::Le
+        | ValueOps::Ge
+        | ValueOps::And
+        | ValueOps::Or => (Some(2), 0),
+        ValueOps::Not | ValueOps::Id => (Some(1), 0),
+        ValueOps::Call => (None, 0),
+        #[cfg(feature = "ssa")]
+        ValueOps::Phi => (None, 0),
+        #[cfg(feature = "float")]
+        ValueOps::Fadd
+        | ValueOps::Fsub
+        | ValueOps::Fmul
+        | ValueOps::Fdiv
+        | ValueOps::Feq
+        | ValueOps::Flt
+        | ValueOps::Fgt
+        | ValueOps::Fle
+        | ValueOps::Fge => (Some(2), 0),
+        #[cfg(feature = "float")]
+        ValueOps::Float2bits | ValueOps::Bits2float => (Some(1), 0),
+        #[cfg(feature = "memory")]
+        ValueOps::Alloc | ValueOps::Load => (Some(1), 0),
+        #[cfg(feature = "memory")]
+        ValueOps::PtrAdd => (Some(2), 0),
+        #[cfg(feature = "char")]
+        ValueOps::Ceq | ValueOps::Clt | ValueOps::Cle | ValueOps::Cgt | ValueOps::Cge => {
+            (Some(2), 0)
+        }
+        #[cfg(feature = "char")]
+        ValueOps::Char2int | ValueOps::Int2char => (Some(1), 0),
+        #[cfg(feature = "strings")]
+        ValueOps::Concat | ValueOps::Index => (Some(2), 0),
+        #[cfg(feature = "strings")]
+        ValueOps::Length => (Some(1), 0),
+        #[cfg(feature = "concurrency")]
+        ValueOps::Spawn => (None, 0),
+        #[cfg(feature = "rand")]
+        ValueOps::Rand => (None, 0),
+    }
+}
+
+const fn effect_arity(op: EffectOps) -> (Option<usize>, usize) {
+    match op {
+        EffectOps::Jump => (Some(0), 1),
+        EffectOps::Branch => (Some(1), 2),
+        EffectOps::Call | EffectOps::Print => (None, 0),
+        // Whether ret has an argument depends on the function so it is checked separately
+        EffectOps::Return => (None, 0),
+        EffectOps::Nop => (Some(0), 0),
+        #[cfg(feature = "memory")]
+        EffectOps::Store => (Some(2), 0),
+        #[cfg(feature = "memory")]
+        EffectOps::Free => (Some(1), 0),
+        #[cfg(feature = "speculate")]
+        EffectOps::Speculate | EffectOps::Commit => (Some(0), 0),
+        #[cfg(feature = "speculate")]
+        EffectOps::Guard => (Some(1), 1),
+        #[cfg(feature = "concurrency")]
+        EffectOps::Join => (Some(1), 0),
+    }
+}
+
+/// Builds up a single [`Function`] one instruction at a time.
+///
+/// Instructions which produce a value return the name of their destination so that it can be passed along to later instructions, and [`FunctionBuilder::fresh_var`] and [`FunctionBuilder::fresh_label`] hand out names which don't clash with any others in the function.
+/// ```
+/// # use bril_rs::{builder::FunctionBuilder, Literal, Type, ValueOps};
+/// let mut builder = FunctionBuilder::new("main");
+/// let one = builder.constant(Type::Int, Literal::Int(1));
+/// let two = builder.value(ValueOps::Add, Type::Int, &[&one, &one]);
+/// builder.print(&[&two]);
+/// let main = builder.finish().unwrap();
+/// assert_eq!(main.to_string().lines().count(), 5);
+/// ```
+#[derive(Debug, Clone)]
+#[allow(clippy::module_name_repetitions)]
+pub struct FunctionBuilder {
+    name: String,
+    args: Vec<Argument>,
+    return_type: Option<Type>,
+    instrs: Vec<Code>,
+    // Every variable and label name which has been used so far, so that fresh ones can avoid them
+    vars: HashSet<String>,
+    labels: HashSet<String>,
+    next_fresh: HashMap<String, usize>,
+    // Given to every label and instruction added which doesn't have a position of its own
+    #[cfg(feature = "position")]
+    pos: Option<Position>,
+}
+
+impl FunctionBuilder {
+    /// Starts building an empty function called ```name``` which takes no arguments and returns nothing
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            args: Vec::new(),
+            return_type: None,
+            instrs: Vec::new(),
+            vars: HashSet::new(),
+            labels: HashSet::new(),
+            next_fresh: HashMap::new(),
+            #[cfg(feature = "position")]
+            pos: None,
+        }
+    }
+
+    /// The name of the function being built
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Adds an argument to the end of the function's signature and returns its name
+    pub fn arg(&mut self, name: impl Into<String>, arg_type: Type) -> String {
+        let name = name.into();
+        self.vars.insert(name.clone());
+        self.args.push(Argument {
+            name: name.clone(),
+            arg_type,
+        });
+        name
+    }
+
+    /// Sets the type of the value the function returns
+    // This can only be const when pointer types, which need to be dropped, are disabled
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn return_type(&mut self, return_type: Type) -> &mut Self {
+        self.return_type = Some(return_type);
+        self
+    }
+
+    fn fresh(
+        used: &HashSet<String>,
+        next_fresh: &mut HashMap<String, usize>,
+        prefix: &str,
+    ) -> String {
+        let next = next_fresh.entry(prefix.to_string()).or_insert(0);
+        loop {
+            let name = format!("{prefix}{next}");
+            *next += 1;
+            if !used.contains(&name) {
+                return name;
+            }
+        }
+    }
+
+    /// Returns a variable name starting with ```prefix``` which has not been used in this function yet
+    pub fn fresh_var(&mut self, prefix: &str) -> String {
+        let name = Self::fresh(&self.vars, &mut self.next_fresh, prefix);
+        self.vars.insert(name.clone());
+        name
+    }
+
+    /// Returns a label starting with ```prefix``` which has not been used in this function yet. The label still needs to be placed with [`FunctionBuilder::label`].
+    pub fn fresh_label(&mut self, prefix: &str) -> String {
+        let name = Self::fresh(&self.labels, &mut self.next_fresh, prefix);
+        self.labels.insert(name.clone());
+        name
+    }
+
+    /// Gives the labels and instructions added from now on the position ```pos```, unless they were constructed with one of their own. A pass building code to replace other code can set this to the position of what it replaces, [`Position::derived`] if it is new, so that the code it builds still points at the source
+    #[cfg(feature = "position")]
+    pub const fn at(&mut self, pos: Option<Position>) -> &mut Self {
+        self.pos = pos;
+        self
+    }
+
+    /// Places ```label``` before the next instruction
+    pub fn label(&mut self, label: impl Into<String>) -> &mut Self {
+        let label = label.into();
+        self.labels.insert(label.clone());
+        self.instrs.push(Code::Label {
+            label,
+            #[cfg(feature = "position")]
+            pos: self.pos,
+        });
+        self
+    }
+
+    /// Appends an already constructed instruction
+    #[cfg_attr(not(feature = "position"), allow(unused_mut))]
+    pub fn instr(&mut self, mut instr: Instruction) -> &mut Self {
+        #[cfg(feature = "position")]
+        if instr.get_pos().is_none() {
+            instr.set_pos(self.pos);
+        }
+        match &instr {
+            Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => {
+                self.vars.insert(dest.clone());
+            }
+            Instruction::Effect { .. } => {}
+        }
+        self.instrs.push(Code::Instruction(instr));
+        self
+    }
+
+    /// Stores ```value``` in ```dest```
+    pub fn constant_into(
+        &mut self,
+        dest: impl Into<String>,
+        const_type: Type,
+        value: Literal,
+    ) -> &mut Self {
+        self.instr(Instruction::Constant {
+            dest: dest.into(),
+            op: ConstOps::Const,
+            #[cfg(feature = "position")]
+            pos: None,
+            const_type,
+            value,
+            #[cfg(feature = "attrs")]
+            attrs: Attrs::new(),
+        })
+    }
+
+    /// Stores ```value``` in a fresh variable and returns its name
+    pub fn constant(&mut self, const_type: Type, value: Literal) -> String {
+        let dest = self.fresh_var("v");
+        self.constant_into(dest.clone(), const_type, value);
+        dest
+    }
+
+    /// Stores the result of ```op``` on ```args``` in ```dest```. Use [`FunctionBuilder::call_into`] and [`FunctionBuilder::phi_into`] for operations that need functions or labels.
+    pub fn value_into(
+        &mut self,
+        dest: impl Into<String>,
+        op: ValueOps,
+        op_type: Type,
+        args: &[&str],
+    ) -> &mut Self {
+        self.full_value_into(dest.into(), op, op_type, args, Vec::new(), Vec::new())
+    }
+
+    /// Stores the result of ```op``` on ```args``` in a fresh variable and returns its name
+    pub fn value(&mut self, op: ValueOps, op_type: Type, args: &[&str]) -> String {
+        let dest = self.fresh_var("v");
+        self.value_into(dest.clone(), op, op_type, args);
+        dest
+    }
+
+    fn full_value_into(
+        &mut self,
+        dest: String,
+        op: ValueOps,
+        op_type: Type,
+        args: &[&str],
+        funcs: Vec<String>,
+        labels: Vec<String>,
+    ) -> &mut Self {
+        self.instr(Instruction::Value {
+            args: args.iter().map(ToString::to_string).collect(),
+            dest,
+            funcs,
+            labels,
+            op,
+            #[cfg(feature = "position")]
+            pos: None,
+            op_type,
+            #[cfg(feature = "tuples")]
+            extra_dests: Vec::new(),
+            #[cfg(feature = "attrs")]
+            attrs: Attrs::new(),
+        })
+    }
+
+    /// Stores the result of calling ```func``` with ```args``` in ```dest```
+    pub fn call_into(
+        &mut self,
+        dest: impl Into<String>,
+        func: impl Into<String>,
+        op_type: Type,
+        args: &[&str],
+    ) -> &mut Self {
+        self.full_value_into(
+            dest.into(),
+            ValueOps::Call,
+            op_type,
+            args,
+            vec![func.into()],
+            Vec::new(),
+        )
+    }
+
+    /// Stores the result of calling ```func``` with ```args``` in a fresh variable and returns its name
+    pub fn call(&mut self, func: impl Into<String>, op_type: Type, args: &[&str]) -> String {
+        let dest = self.fresh_var("v");
+        self.call_into(dest.clone(), func, op_type, args);
+        dest
+    }
+
+    /// Stores whichever of ```incoming```'s variables corresponds to the label that was executed last in ```dest```
+    #[cfg(feature = "ssa")]
+    pub fn phi_into(
+        &mut self,
+        dest: impl Into<String>,
+        op_type: Type,
+        incoming: &[(&str, &str)],
+    ) -> &mut Self {
+        let (labels, args): (Vec<String>, Vec<&str>) = incoming
+            .iter()
+            .map(|(label, arg)| ((*label).to_string(), *arg))
+            .unzip();
+        self.full_value_into(
+            dest.into(),
+            ValueOps::Phi,
+            op_type,
+            &args,
+            Vec::new(),
+            labels,
+        )
+    }
+
+    fn full_effect(
+        &mut self,
+        op: EffectOps,
+        args: &[&str],
+        funcs: Vec<String>,
+        labels: Vec<String>,
+    ) -> &mut Self {
+        self.instr(Instruction::Effect {
+            args: args.iter().map(ToString::to_string).collect(),
+            funcs,
+            labels,
+            op,
+            #[cfg(feature = "position")]
+            pos: None,
+            #[cfg(feature = "attrs")]
+            attrs: Attrs::new(),
+        })
+    }
+
+    /// Appends ```op``` with ```args``` for effect operations which don't need functions or labels
+    pub fn effect(&mut self, op: EffectOps, args: &[&str]) -> &mut Self {
+        self.full_effect(op, args, Vec::new(), Vec::new())
+    }
+
+    /// Calls ```func``` with ```args``` for its side effects
+    pub fn call_effect(&mut self, func: impl Into<String>, args: &[&str]) -> &mut Self {
+        self.full_effect(EffectOps::Call, args, vec![func.into()], Vec::new())
+    }
+
+    /// Jumps to ```label```
+    pub fn jmp(&mut self, label: impl Into<String>) -> &mut Self {
+        self.full_effect(EffectOps::Jump, &[], Vec::new(), vec![label.into()])
+    }
+
+    /// Jumps to ```then_label``` if ```cond``` is true and to ```else_label``` otherwise
+    pub fn br(
+        &mut self,
+        cond: &str,
+        then_label: impl Into<String>,
+        else_label: impl Into<String>,
+    ) -> &mut Self {
+        self.full_effect(
+            EffectOps::Branch,
+            &[cond],
+            Vec::new(),
+            vec![then_label.into(), else_label.into()],
+        )
+    }
+
+    /// Guards that ```cond``` is true, aborting to ```label``` otherwise
+    #[cfg(feature = "speculate")]
+    pub fn guard(&mut self, cond: &str, label: impl Into<String>) -> &mut Self {
+        self.full_effect(EffectOps::Guard, &[cond], Vec::new(), vec![label.into()])
+    }
+
+    /// Returns from the function with ```value``` if there is one
+    pub fn ret(&mut self, value: Option<&str>) -> &mut Self {
+        self.effect(EffectOps::Return, value.as_slice())
+    }
+
+    /// Prints ```args```
+    pub fn print(&mut self, args: &[&str]) -> &mut Self {
+        self.effect(EffectOps::Print, args)
+    }
+
+    /// Checks that every instruction has the right number of arguments, functions, and labels and that every label it uses is defined, then returns the finished function
+    /// # Errors
+    /// Returns the first problem found with the function's instructions
+    pub fn finish(self) -> Result<Function, BuildError> {
+        let mut defined = HashSet::new();
+        for code in &self.instrs {
+            if let Code::Label { label, .. } = code {
+                if !defined.insert(label) {
+                    return Err(BuildError::DuplicateLabel {
+                        func: self.name.clone(),
+                        label: label.clone(),
+                    });
+                }
+            }
+        }
+
+        for code in &self.instrs {
+            let (op, (expected_args, expected_labels), args, funcs, labels) = match code {
+                Code::Label { .. } | Code::Instruction(Instruction::Constant { .. }) => continue,
+                Code::Instruction(Instruction::Value {
+                    op,
+                    args,
+                    funcs,
+                    labels,
+                    ..
+                }) => {
+                    #[cfg(feature = "ssa")]
+                    let arity = if *op == ValueOps::Phi {
+                        (Some(labels.len()), labels.len())
+                    } else {
+                        value_arity(*op)
+                    };
+                    #[cfg(not(feature = "ssa"))]
+                    let arity = value_arity(*op);
+                    (op.to_string(), arity, args, funcs, labels)
+                }
+                Code::Instruction(Instruction::Effect {
+                    op,
+                    args,
+                    funcs,
+                    labels,
+                    ..
+                }) => {
+                    let arity = if *op == EffectOps::Return {
+                        (Some(usize::from(self.return_type.is_some())), 0)
+                    } else {
+                        effect_arity(*op)
+                    };
+                    (op.to_string(), arity, args, funcs, labels)
+                }
+            };
+            if let Some(expected) = expected_args {
+                if args.len() != expected {
+                    return Err(BuildError::BadNumArgs {
+                        func: self.name.clone(),
+                        op,
+                        expected,
+                        found: args.len(),
+                    });
+                }
+            }
+            let expected_funcs = usize::from(op == "call");
+            if funcs.len() != expected_funcs {
+                return Err(BuildError::BadNumFuncs {
+                    func: self.name.clone(),
+                    op,
+                    expected: expected_funcs,
+                    found: funcs.len(),
+                });
+            }
+            if labels.len() != expected_labels {
+                return Err(BuildError::BadNumLabels {
+                    func: self.name.clone(),
+                    op,
+                    expected: expected_labels,
+                    found: labels.len(),
+                });
+            }
+            if let Some(label) = labels.iter().find(|l| !defined.contains(l)) {
+                return Err(BuildError::UndefinedLabel {
+                    func: self.name.clone(),
+                    label: label.clone(),
+                });
+            }
+        }
+
+        Ok(Function {
+            args: self.args,
+            instrs: self.instrs,
+            name: self.name,
+            #[cfg(feature = "position")]
+            pos: None,
+            return_type: self.return_type,
+            #[cfg(feature = "attrs")]
+            attrs: Attrs::new(),
+        })
+    }
+}
+
+/// Collects [`Function`]s into a [`Program`]
+/// ```
+/// # use bril_rs::{builder::{FunctionBuilder, ProgramBuilder}, Type};
+/// let mut double = FunctionBuilder::new("double");
+/// let x = double.arg("x", Type::Int);
+/// double.return_type(Type::Int);
+/// let result = double.value(bril_rs::ValueOps::Add, Type::Int, &[&x, &x]);
+/// double.ret(Some(&result));
+///
+/// let mut main = FunctionBuilder::new("main");
+/// let four = main.constant(Type::Int, bril_rs::Literal::Int(4));
+/// let eight = main.call("double", Type::Int, &[&four]);
+/// main.print(&[&eight]);
+///
+/// let program = ProgramBuilder::new()
+///     .function(main)
+///     .function(double)
+///     .finish()
+///     .unwrap();
+/// assert_eq!(program.functions.len(), 2);
+/// ```
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct ProgramBuilder {
+    functions: Vec<FunctionBuilder>,
+}
+
+impl ProgramBuilder {
+    /// Starts building a program with no functions
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds ```func``` to the program
+    #[must_use]
+    pub fn function(mut self, func: FunctionBuilder) -> Self {
+        self.functions.push(func);
+        self
+    }
+
+    /// Finishes every function and checks that function names are unique and that every call is to a function in the program with the right number of arguments
+    /// # Errors
+    /// Returns the first problem found with the program
+    pub fn finish(self) -> Result<Program, BuildError> {
+        let functions = self
+            .functions
+            .into_iter()
+            .map(FunctionBuilder::finish)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut arities = HashMap::new();
+        for func in &functions {
+            if arities
+                .insert(func.name.as_str(), func.args.len())
+                .is_some()
+            {
+                return Err(BuildError::DuplicateFunction(func.name.clone()));
+            }
+        }
+
+        for func in &functions {
+            for code in &func.instrs {
+                let Code::Instruction(
+                    Instruction::Value {
+                        op: ValueOps::Call,
+                        args,
+                        funcs,
+                        ..
+                    }
+                    | Instruction::Effect {
+                        op: EffectOps::Call,
+                        args,
+                        funcs,
+                        ..
+                    },
+                ) = code
+                else {
+                    continue;
+                };
+                // Finishing the function already checked that every call names exactly one function
+                let callee = &funcs[0];
+                let expected =
+                    *arities
+                        .get(callee.as_str())
+                        .ok_or_else(|| BuildError::UndefinedFunction {
+                            caller: func.name.clone(),
+                            callee: callee.clone(),
+                        })?;
+                if args.len() != expected {
+                    return Err(BuildError::BadNumArgs {
+                        func: func.name.clone(),
+                        op: "call".to_string(),
+                        expected,
+                        found: args.len(),
+                    });
+                }
+            }
+        }
+
+        Ok(Program {
+            functions,
+            #[cfg(feature = "import")]
+            imports: Vec::new(),
+            #[cfg(feature = "attrs")]
+            attrs: Attrs::new(),
+        })
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+
+use crate::{Code, EffectOps, Instruction, Program, ValueOps};
+
+#[cfg(feature = "position")]
+use crate::Position;
+
+/// A `call` to a function which is not part of the [`Program`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndefinedCall {
+    /// The index of the function making the call
+    pub caller: usize,
+    /// The name of the function being called
+    pub callee: String,
+    /// Where the call is located in source code
+    #[cfg(feature = "position")]
+    pub pos: Option<Position>,
+}
+
+/// Which functions of a [`Program`] call which. Functions are referred to by their index in ```functions```, which is the order they appear in the program. A `spawn` counts as a call to the function the thread runs.
+#[derive(Debug, Clone)]
+pub struct CallGraph {
+    /// The names of the functions of the program
+    pub functions: Vec<String>,
+    /// The indices of the functions called by each function, in the order they are first called
+    pub callees: Vec<Vec<usize>>,
+    /// The indices of the functions which call each function
+    pub callers: Vec<Vec<usize>>,
+    /// Every call to a function which does not exist, in the order they appear in the program
+    pub undefined_calls: Vec<UndefinedCall>,
+}
+
+// The functions called by ```instr```, or started in a thread by it
+pub(crate) fn calls(instr: &Instruction) -> &[String] {
+    match instr {
+        Instruction::Value {
+            op: ValueOps::Call,
+            funcs,
+            ..
+        }
+        | Instruction::Effect {
+            op: EffectOps::Call,
+            funcs,
+            ..
+        } => funcs,
+        #[cfg(feature = "concurrency")]
+        Instruction::Value {
+            op: ValueOps::Spawn,
+            funcs,
+            ..
+        } => funcs,
+        _ => &[],
+    }
+}
+
+impl CallGraph {
+    /// Finds every `call` in ```prog```. If more than one function has the same name, calls to that name go to the last of them.
+    #[must_use]
+    pub fn new(prog: &Program) -> Self {
+        let index: HashMap<&str, usize> = prog
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.name.as_str(), i))
+            .collect();
+
+        let num_funcs = prog.functions.len();
+        let mut graph = Self {
+            functions: prog.functions.iter().map(|f| f.name.clone()).collect(),
+            callees: vec![Vec::new(); num_funcs],
+            callers: vec![Vec::new(); num_funcs],
+            undefined_calls: Vec::new(),
+        };
+        for (caller, func) in prog.functions.iter().enumerate() {
+            for code in &func.instrs {
+                let Code::Instruction(instr) = code else {
+                    continue;
+                };
+                for callee in calls(instr) {
+                    match index.get(callee.as_str()) {
+                        Some(&i) => {
+                            if !graph.callees[caller].contains(&i) {
+                                graph.callees[caller].push(i);
+                                graph.callers[i].push(caller);
+                            }
+                        }
+                        None => graph.undefined_calls.push(UndefinedCall {
+                            caller,
+                            callee: callee.clone(),
+                            #[cfg(feature = "position")]
+                            pos: instr.get_pos(),
+                        }),
+                    }
+                }
+            }
+        }
+        graph
+    }
+
+    /// Finds the index of the function named ```name```
+    #[must_use]
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.functions.iter().rposition(|f| f == name)
+    }
+
+    /// Groups the functions into strongly connected components, the sets of functions which can all reach each other through calls. Every component comes after all of the components it calls into, so the components are in reverse topological order.
+    #[must_use]
+    pub fn components(&self) -> Vec<Vec<usize>> {
+        Tarjan::new(self).run()
+    }
+
+    /// Orders the functions so that every function comes after the functions it calls, except for calls within a recursive cycle. This is the order to visit functions in for bottom-up interprocedural analyses and inlining.
+    #[must_use]
+    pub fn reverse_topological_order(&self) -> Vec<usize> {
+        self.components().into_iter().flatten().collect()
+    }
+
+    /// Whether the function at index ```func``` can call itself, either directly or through other functions
+    #[must_use]
+    pub fn is_recursive(&self, func: usize) -> bool {
+        self.callees[func].contains(&func)
+            || self
+                .components()
+                .iter()
+                .any(|c| c.len() > 1 && c.contains(&func))
+    }
+
+    /// Which functions can be reached through calls from any of ```roots```, counting the roots themselves, by the index of each function
+    /// ```
+    /// use bril_rs::callgraph::CallGraph;
+    ///
+    /// let call = |f: &str| format!(r#"{{"op": "call", "funcs": ["{f}"], "args": []}}"#);
+    /// let json = format!(
+    ///     r#"{{"functions": [
+    ///         {{"name": "main", "instrs": [{}]}},
+    ///         {{"name": "used", "instrs": []}},
+    ///         {{"name": "unused", "instrs": [{}]}}
+    ///     ]}}"#,
+    ///     call("used"),
+    ///     call("used"),
+    /// );
+    /// let prog: bril_rs::Program = serde_json::from_str(&json).unwrap();
+    /// let graph = CallGraph::new(&prog);
+    /// assert_eq!(graph.reachable(&[graph.find("main").unwrap()]), [true, true, false]);
+    /// ```
+    #[must_use]
+    pub fn reachable(&self, roots: &[usize]) -> Vec<bool> {
+        let mut reached = vec![false; self.functions.len()];
+        let mut stack = roots.to_vec();
+        while let Some(func) = stack.pop() {
+            if !std::mem::replace(&mut reached[func], true) {
+                stack.extend(&self.callees[func]);
+            }
+        }
+        reached
+    }
+
+    /// Every recursive cycle of calls, each of which is a strongly connected component that either has more than one function or a function that calls itself
+    #[must_use]
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        self.components()
+            .into_iter()
+            .filter(|c| c.len() > 1 || self.callees[c[0]].contains(&c[0]))
+            .collect()
+    }
+}
+
+// Tarjan's strongly connected components algorithm, which produces the components in reverse topological order
+struct Tarjan<'a> {
+    graph: &'a CallGraph,
+    next_index: usize,
+    index: Vec<Option<usize>>,
+    low_link: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    components: Vec<Vec<usize>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn new(graph: &'a CallGraph) -> Self {
+        let n = graph.functions.len();
+        Self {
+            graph,
+            next_index: 0,
+            index: vec![None; n],
+            low_link: vec![0; n],
+            on_stack: vec![false; n],
+            stack: Vec::new(),
+            components: Vec::new(),
+        }
+    }
+
+    fn run(mut self) -> Vec<Vec<usize>> {
+        for func in 0..self.graph.functions.len() {
+            if self.index[func].is_none() {
+                self.visit(func);
+            }
+        }
+        self.components
+    }
+
+    fn visit(&mut self, func: usize) {
+        self.index[func] = Some(self.next_index);
+        self.low_link[func] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(func);
+        self.on_stack[func] = true;
+
+        for &callee in &self.graph.callees[func] {
+            match self.index[callee] {
+                None => {
+                    self.visit(callee);
+                    self.low_link[func] = self.low_link[func].min(self.low_link[callee]);
+                }
+                Some(i) if self.on_stack[callee] => {
+                    self.low_link[func] = self.low_link[func].min(i);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(self.low_link[func]) == self.index[func] {
+            let mut component = Vec::new();
+            loop {
+                let f = self.stack.pop().unwrap();
+                self.on_stack[f] = false;
+                component.push(f);
+                if f == func {
+                    break;
+                }
+            }
+            component.reverse();
+            self.components.push(component);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A program of functions which only call the functions listed with them
+    fn graph(funcs: &[(&str, &[&str])]) -> CallGraph {
+        let functions: Vec<_> = funcs
+            .iter()
+            .map(|(name, callees)| {
+                let instrs: Vec<_> = callees
+                    .iter()
+                    .map(|f| serde_json::json!({"op": "call", "funcs": [f], "args": []}))
+                    .collect();
+                serde_json::json!({"name": name, "instrs": instrs})
+            })
+            .collect();
+        let prog: Program =
+            serde_json::from_value(serde_json::json!({ "functions": functions })).unwrap();
+        CallGraph::new(&prog)
+    }
+
+    #[test]
+    fn acyclic() {
+        let g = graph(&[("main", &["a", "b"]), ("a", &["b"]), ("b", &[])]);
+        assert_eq!(g.callees, [vec![1, 2], vec![2], vec![]]);
+        assert_eq!(g.callers, [vec![], vec![0], vec![0, 1]]);
+        assert_eq!(g.components(), [vec![2], vec![1], vec![0]]);
+        assert_eq!(g.reverse_topological_order(), [2, 1, 0]);
+        assert!((0..3).all(|f| !g.is_recursive(f)));
+        assert!(g.cycles().is_empty());
+        assert!(g.undefined_calls.is_empty());
+    }
+
+    #[test]
+    fn repeated_calls() {
+        let g = graph(&[("main", &["a", "a", "main"]), ("a", &[])]);
+        assert_eq!(g.callees, [vec![1, 0], vec![]]);
+        assert_eq!(g.callers, [vec![0], vec![0]]);
+    }
+
+    #[test]
+    fn self_call() {
+        let g = graph(&[("main", &["fact"]), ("fact", &["fact"])]);
+        assert_eq!(g.components(), [vec![1], vec![0]]);
+        assert!(g.is_recursive(1));
+        assert!(!g.is_recursive(0));
+        assert_eq!(g.cycles(), [vec![1]]);
+    }
+
+    #[test]
+    fn mutual_recursion() {
+        let g = graph(&[
+            ("main", &["even"]),
+            ("even", &["odd", "done"]),
+            ("odd", &["even"]),
+            ("done", &[]),
+        ]);
+        assert_eq!(g.components(), [vec![3], vec![1, 2], vec![0]]);
+        assert_eq!(g.reverse_topological_order(), [3, 1, 2, 0]);
+        assert!(g.is_recursive(1));
+        assert!(g.is_recursive(2));
+        assert!(!g.is_recursive(0));
+        assert!(!g.is_recursive(3));
+        assert_eq!(g.cycles(), [vec![1, 2]]);
+    }
+
+    #[test]
+    fn separate_cycles() {
+        let g = graph(&[
+            ("main", &["a", "c"]),
+            ("a", &["b"]),
+            ("b", &["a", "c"]),
+            ("c", &["c"]),
+        ]);
+        assert_eq!(g.cycles(), [vec![3], vec![1, 2]]);
+        let order = g.reverse_topological_order();
+        let pos = |f| order.iter().position(|&g| g == f).unwrap();
+        assert!(pos(3) < pos(1) && pos(3) < pos(2) && pos(2) < pos(0));
+    }
+
+    #[test]
+    fn undefined_callees() {
+        let g = graph(&[("main", &["a", "missing"]), ("a", &["gone", "missing"])]);
+        assert_eq!(g.callees, [vec![1], vec![]]);
+        let undefined: Vec<_> = g
+            .undefined_calls
+            .iter()
+            .map(|call| (call.caller, call.callee.as_str()))
+            .collect();
+        assert_eq!(undefined, [(0, "missing"), (1, "gone"), (1, "missing")]);
+        assert_eq!(g.reverse_topological_order(), [1, 0]);
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let g = graph(&[("main", &["f"]), ("f", &["main"]), ("f", &[])]);
+        assert_eq!(g.find("f"), Some(2));
+        assert_eq!(g.callees, [vec![2], vec![0], vec![]]);
+        assert_eq!(g.callers, [vec![1], vec![], vec![0]]);
+        assert_eq!(g.components(), [vec![2], vec![0], vec![1]]);
+        assert!(!g.is_recursive(0));
+        assert!(g.cycles().is_empty());
+    }
+}
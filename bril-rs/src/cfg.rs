@@ -0,0 +1,650 @@
+use std::collections::{HashMap, HashSet};
+
+use thiserror::Error;
+
+use crate::dominance::Dominators;
+use crate::{Code, EffectOps, Function, Instruction, ValueOps};
+
+#[cfg(feature = "attrs")]
+use crate::Attrs;
+#[cfg(feature = "position")]
+use crate::Position;
+
+/// A labelled sequence of instructions which can only be entered at the top and only exited at the bottom. Every block of a [`Cfg`] ends with a terminator(`jmp`, `br`, or `ret`), unless it was built by a [`BlockBuilder`] which doesn't canonicalize terminators, in which case it can instead fall through to the next block.
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    /// The label of the block, which is freshly generated if the block was not labelled in the original code
+    pub label: String,
+    /// Where the label is located in source code
+    #[cfg(feature = "position")]
+    pub pos: Option<Position>,
+    /// The instructions of the block
+    pub instrs: Vec<Instruction>,
+}
+
+#[cfg(feature = "position")]
+impl BasicBlock {
+    /// Where the end of the block is in the source as far as can be told, which is the position of its last instruction or otherwise of its label, derived for anything added there like a terminator
+    #[must_use]
+    pub fn end_pos(&self) -> Option<Position> {
+        self.instrs
+            .last()
+            .and_then(Instruction::get_pos)
+            .or(self.pos)
+            .map(Position::derived)
+    }
+}
+
+/// The basic blocks of a function along with their control flow edges. Blocks are referred to by their index in ```blocks``` and the first block is the entry of the function.
+///
+/// Unlike the blocks that `brilirs` runs, these keep their instructions as [`Instruction`]s and their labels as names, so passes can change them freely and turn the result back into a [`Function`]. The edges are worked out again whenever blocks are inserted, removed, or split through the methods here, but have to be kept up to date by hand when a terminator is changed directly.
+/// ```
+/// # use bril_rs::{builder::FunctionBuilder, cfg::Cfg, Literal, Type};
+/// let mut builder = FunctionBuilder::new("main");
+/// let one = builder.constant(Type::Int, Literal::Int(1));
+/// builder.print(&[&one]);
+/// builder.print(&[&one]);
+/// let main = builder.finish().unwrap();
+///
+/// let mut cfg = Cfg::from_function(&main).unwrap();
+/// let tail = cfg.split_block(0, 2);
+/// assert_eq!(cfg.successors[0], [tail]);
+/// assert_eq!(cfg.block(&cfg.blocks[tail].label).unwrap().instrs.len(), 2);
+/// let main = cfg.into_function(&main);
+/// assert_eq!(main.instrs.len(), 7);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    /// The basic blocks in the order they appeared in the function
+    pub blocks: Vec<BasicBlock>,
+    /// The indices of the blocks which can be executed right after each block
+    pub successors: Vec<Vec<usize>>,
+    /// The indices of the blocks which can be executed right before each block
+    pub predecessors: Vec<Vec<usize>>,
+}
+
+/// Why code couldn't be split into the blocks of a [`Cfg`]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+pub enum CfgError {
+    /// A `jmp` or `br` goes to .{0}, which isn't the label of any block
+    #[error("there is no label .{0} to jump to")]
+    UnknownLabel(String),
+}
+
+/// Whether ```instr``` ends a basic block
+#[must_use]
+pub const fn is_terminator(instr: &Instruction) -> bool {
+    matches!(
+        instr,
+        Instruction::Effect {
+            op: EffectOps::Jump | EffectOps::Branch | EffectOps::Return,
+            ..
+        }
+    )
+}
+
+/// Checks that every `jmp` and `br` of ```code``` goes to one of its labels, which is what [`Cfg::new`] needs to split it into blocks, without building them
+///
+/// # Errors
+/// Will return [`CfgError::UnknownLabel`] for the first label which ```code``` jumps to but doesn't have
+pub fn check_labels(code: &[Code]) -> Result<(), CfgError> {
+    let labels: HashSet<&str> = code
+        .iter()
+        .filter_map(|c| match c {
+            Code::Label { label, .. } => Some(label.as_str()),
+            Code::Instruction(_) => None,
+        })
+        .collect();
+    code.iter()
+        .filter_map(|c| match c {
+            Code::Instruction(Instruction::Effect {
+                op: EffectOps::Jump | EffectOps::Branch,
+                labels: targets,
+                ..
+            }) => Some(targets),
+            _ => None,
+        })
+        .flatten()
+        .find(|l| !labels.contains(l.as_str()))
+        .map_or(Ok(()), |l| Err(CfgError::UnknownLabel(l.clone())))
+}
+
+pub(crate) fn jump(label: String) -> Instruction {
+    Instruction::Effect {
+        args: Vec::new(),
+        funcs: Vec::new(),
+        labels: vec![label],
+        op: EffectOps::Jump,
+        #[cfg(feature = "position")]
+        pos: None,
+        #[cfg(feature = "attrs")]
+        attrs: Attrs::new(),
+    }
+}
+
+// This can only be const when the attrs feature, whose map can't be made in a const fn, is disabled
+#[cfg_attr(not(feature = "attrs"), allow(clippy::missing_const_for_fn))]
+fn ret() -> Instruction {
+    Instruction::Effect {
+        args: Vec::new(),
+        funcs: Vec::new(),
+        labels: Vec::new(),
+        op: EffectOps::Return,
+        #[cfg(feature = "position")]
+        pos: None,
+        #[cfg(feature = "attrs")]
+        attrs: Attrs::new(),
+    }
+}
+
+fn fresh_label(prefix: &str, used: &mut HashSet<String>) -> String {
+    let mut i = 0;
+    loop {
+        let candidate = format!("{prefix}{i}");
+        if used.insert(candidate.clone()) {
+            return candidate;
+        }
+        i += 1;
+    }
+}
+
+/// The choices made when splitting code into the basic blocks of a [`Cfg`]
+///
+/// [`Cfg::new`] uses the [`Default`], which splits only after terminators, keeps empty blocks, and makes every fallthrough an explicit `jmp`, while an analysis which wants other blocks can pick its own. For any choices, every block gets a label, a fresh entry block is added if the first block is the target of a jump, and [`BlockBuilder::build`] also tells where each instruction came from.
+/// ```
+/// # use bril_rs::{builder::FunctionBuilder, cfg::BlockBuilder, Literal, Type};
+/// let mut builder = FunctionBuilder::new("main");
+/// let one = builder.constant(Type::Int, Literal::Int(1));
+/// builder.call_effect("log", &[&one]);
+/// builder.print(&[&one]);
+/// let main = builder.finish().unwrap();
+///
+/// let blocks = BlockBuilder {
+///     split_on_calls: true,
+///     ..BlockBuilder::default()
+/// };
+/// let (cfg, origins) = blocks.build(main.instrs).unwrap();
+/// assert_eq!(cfg.successors, [vec![1], vec![]]);
+/// // The `jmp` and `ret` at the end of each block were added
+/// assert_eq!(origins, [vec![Some(0), Some(1), None], vec![Some(2), None]]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct BlockBuilder {
+    /// Whether a `call` ends its block, like a terminator does, so that the instructions after it start a block of their own which it falls through to. This is off by default
+    pub split_on_calls: bool,
+    /// Whether labels with no instructions before the next label still get a block of their own, which they do by default. When this is off, jumps to such a block go to the block it falls through to instead, except that a block is kept when a `phi` names it as where a value comes from or when it is the last block, which has nowhere to fall through to
+    pub keep_empty_blocks: bool,
+    /// Whether each block which falls through to the next is made to end with a `jmp` to it, and the last block with a `ret`, so that every block ends with a terminator. This is on by default. When it is off, the edges of a block without a terminator go to the block after it, if there is one
+    pub canonicalize_terminators: bool,
+}
+
+impl Default for BlockBuilder {
+    fn default() -> Self {
+        Self {
+            split_on_calls: false,
+            keep_empty_blocks: true,
+            canonicalize_terminators: true,
+        }
+    }
+}
+
+// Whether ```instr``` calls a function
+const fn is_call(instr: &Instruction) -> bool {
+    matches!(
+        instr,
+        Instruction::Value {
+            op: ValueOps::Call,
+            ..
+        } | Instruction::Effect {
+            op: EffectOps::Call,
+            ..
+        }
+    )
+}
+
+impl BlockBuilder {
+    /// Splits ```instrs``` into basic blocks as chosen. Along with the blocks, it gives back where each of their instructions came from, as its index in ```instrs```, which counts labels too. The indices are in the same order as [`BasicBlock::instrs`] of each block of [`Cfg::blocks`], and [`None`] for the terminators which were added. Labels have no index since [`BasicBlock::label`] is the label itself.
+    /// ```
+    /// # use bril_rs::{builder::FunctionBuilder, cfg::BlockBuilder};
+    /// let mut builder = FunctionBuilder::new("main");
+    /// builder.jmp("empty").label("empty").label("body").print(&[]);
+    /// let main = builder.finish().unwrap();
+    ///
+    /// let blocks = BlockBuilder {
+    ///     keep_empty_blocks: false,
+    ///     canonicalize_terminators: false,
+    ///     ..BlockBuilder::default()
+    /// };
+    /// let (cfg, origins) = blocks.build(main.instrs).unwrap();
+    /// let labels: Vec<&str> = cfg.blocks.iter().map(|b| b.label.as_str()).collect();
+    /// assert_eq!(labels, ["b0", "body"]);
+    /// assert_eq!(cfg.blocks[0].instrs[0].to_string(), "jmp .body;");
+    /// assert_eq!(origins, [vec![Some(0)], vec![Some(3)]]);
+    /// ```
+    /// # Errors
+    /// Will return [`CfgError::UnknownLabel`] if a `jmp` or `br` targets a label which does not exist
+    #[allow(clippy::type_complexity)]
+    pub fn build(&self, instrs: Vec<Code>) -> Result<(Cfg, Vec<Vec<Option<usize>>>), CfgError> {
+        let mut used: HashSet<String> = instrs
+            .iter()
+            .filter_map(|c| match c {
+                Code::Label { label, .. } => Some(label.clone()),
+                Code::Instruction(_) => None,
+            })
+            .collect();
+
+        let mut unlabelled = Vec::new();
+        let mut current: Option<BasicBlock> = None;
+        let mut current_origins = Vec::new();
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        let mut origins: Vec<Vec<Option<usize>>> = Vec::new();
+        for (index, code) in instrs.into_iter().enumerate() {
+            match code {
+                Code::Label {
+                    label,
+                    #[cfg(feature = "position")]
+                    pos,
+                } => {
+                    if let Some(b) = current.take() {
+                        blocks.push(b);
+                        origins.push(std::mem::take(&mut current_origins));
+                    }
+                    current = Some(BasicBlock {
+                        label,
+                        #[cfg(feature = "position")]
+                        pos,
+                        instrs: Vec::new(),
+                    });
+                }
+                Code::Instruction(i) => {
+                    let block = current.get_or_insert_with(|| {
+                        unlabelled.push(blocks.len());
+                        BasicBlock {
+                            label: String::new(),
+                            #[cfg(feature = "position")]
+                            pos: i.get_pos().map(Position::derived),
+                            instrs: Vec::new(),
+                        }
+                    });
+                    let done = is_terminator(&i) || (self.split_on_calls && is_call(&i));
+                    block.instrs.push(i);
+                    current_origins.push(Some(index));
+                    if done {
+                        blocks.extend(current.take());
+                        origins.push(std::mem::take(&mut current_origins));
+                    }
+                }
+            }
+        }
+        if let Some(b) = current.take() {
+            blocks.push(b);
+            origins.push(current_origins);
+        }
+        for idx in unlabelled {
+            blocks[idx].label = fresh_label("b", &mut used);
+        }
+
+        if !self.keep_empty_blocks {
+            remove_empty_blocks(&mut blocks, &mut origins);
+        }
+
+        // The entry block can not have any predecessors so that phi nodes have somewhere to go. Phis taking a value from the entry don't jump to it, so they don't count
+        let entry_is_target = blocks.first().is_none_or(|entry| {
+            blocks.iter().flat_map(|b| &b.instrs).any(|i| match i {
+                Instruction::Effect { labels, .. } => labels.contains(&entry.label),
+                Instruction::Value { .. } | Instruction::Constant { .. } => false,
+            })
+        });
+        if entry_is_target {
+            blocks.insert(
+                0,
+                BasicBlock {
+                    label: fresh_label("entry", &mut used),
+                    #[cfg(feature = "position")]
+                    pos: blocks.first().and_then(|b| b.pos).map(Position::derived),
+                    instrs: Vec::new(),
+                },
+            );
+            origins.insert(0, Vec::new());
+        }
+
+        // Make all fallthroughs explicit
+        if self.canonicalize_terminators {
+            let num_blocks = blocks.len();
+            for i in 0..num_blocks {
+                if blocks[i].instrs.last().is_none_or(|i| !is_terminator(i)) {
+                    #[cfg_attr(not(feature = "position"), allow(unused_mut))]
+                    let mut term = if i + 1 == num_blocks {
+                        ret()
+                    } else {
+                        jump(blocks[i + 1].label.clone())
+                    };
+                    #[cfg(feature = "position")]
+                    term.set_pos(blocks[i].end_pos());
+                    blocks[i].instrs.push(term);
+                    origins[i].push(None);
+                }
+            }
+        }
+
+        let (successors, predecessors) = edges(&blocks)?;
+        Ok((
+            Cfg {
+                blocks,
+                successors,
+                predecessors,
+            },
+            origins,
+        ))
+    }
+}
+
+// The labels which ```instr``` takes values from, if it is a phi node
+#[cfg(feature = "ssa")]
+fn phi_labels(instr: &Instruction) -> &[String] {
+    match instr {
+        Instruction::Value {
+            op: ValueOps::Phi,
+            labels,
+            ..
+        } => labels,
+        _ => &[],
+    }
+}
+
+#[cfg(not(feature = "ssa"))]
+const fn phi_labels(_: &Instruction) -> &[String] {
+    &[]
+}
+
+// Leaves out the blocks without instructions, other than the last block and those which a phi takes a value from, making anything which goes to them go to the next block that is kept instead
+fn remove_empty_blocks(blocks: &mut Vec<BasicBlock>, origins: &mut Vec<Vec<Option<usize>>>) {
+    let phi_labels: HashSet<&str> = blocks
+        .iter()
+        .flat_map(|b| &b.instrs)
+        .flat_map(phi_labels)
+        .map(String::as_str)
+        .collect();
+    // Walking backwards, a removed block goes to the label of the last block that was kept
+    let mut renames: HashMap<String, String> = HashMap::new();
+    let mut keep = vec![true; blocks.len()];
+    let mut next_kept: Option<&str> = None;
+    for (i, b) in blocks.iter().enumerate().rev() {
+        match next_kept {
+            Some(next) if b.instrs.is_empty() && !phi_labels.contains(b.label.as_str()) => {
+                renames.insert(b.label.clone(), next.to_string());
+                keep[i] = false;
+            }
+            _ => next_kept = Some(&b.label),
+        }
+    }
+    if renames.is_empty() {
+        return;
+    }
+    let mut kept = keep.iter();
+    blocks.retain(|_| *kept.next().unwrap());
+    let mut kept = keep.iter();
+    origins.retain(|_| *kept.next().unwrap());
+    for instr in blocks.iter_mut().flat_map(|b| &mut b.instrs) {
+        if let Instruction::Effect { labels, .. } = instr {
+            for label in labels.iter_mut() {
+                if let Some(to) = renames.get(label) {
+                    label.clone_from(to);
+                }
+            }
+        }
+    }
+}
+
+impl Cfg {
+    /// Splits `instrs` into basic blocks, giving every block a label, adding a fresh entry block if the first block is the target of a jump, and making every fallthrough an explicit `jmp`. This is [`BlockBuilder::build`] with the [`Default`] choices.
+    /// # Errors
+    /// Will return [`CfgError::UnknownLabel`] if a `jmp` or `br` targets a label which does not exist
+    pub fn new(instrs: Vec<Code>) -> Result<Self, CfgError> {
+        Ok(BlockBuilder::default().build(instrs)?.0)
+    }
+
+    /// The blocks of the body of ```func```, as [`Cfg::new`] splits them
+    /// # Errors
+    /// Will return [`CfgError::UnknownLabel`] if a `jmp` or `br` targets a label which does not exist
+    pub fn from_function(func: &Function) -> Result<Self, CfgError> {
+        Self::new(func.instrs.clone())
+    }
+
+    /// Converts the blocks back into a function with the same name, arguments, return type, and attributes as ```func```, which is usually the function they came from
+    #[must_use]
+    pub fn into_function(self, func: &Function) -> Function {
+        Function {
+            args: func.args.clone(),
+            instrs: self.into_code(),
+            name: func.name.clone(),
+            #[cfg(feature = "position")]
+            pos: func.pos,
+            return_type: func.return_type.clone(),
+            #[cfg(feature = "attrs")]
+            attrs: func.attrs.clone(),
+        }
+    }
+
+    /// The dominator tree of the blocks, as [`Dominators::new`] finds it
+    #[must_use]
+    pub fn dominators(&self) -> Dominators {
+        Dominators::new(self)
+    }
+
+    /// The post-dominator tree of the blocks, as [`Dominators::post`] finds it
+    #[must_use]
+    pub fn post_dominators(&self) -> Dominators {
+        Dominators::post(self)
+    }
+
+    /// Finds the index of the block with the label ```label```
+    #[must_use]
+    pub fn find(&self, label: &str) -> Option<usize> {
+        self.blocks.iter().position(|b| b.label == label)
+    }
+
+    /// The block with the label ```label```
+    #[must_use]
+    pub fn block(&self, label: &str) -> Option<&BasicBlock> {
+        self.blocks.iter().find(|b| b.label == label)
+    }
+
+    /// The block with the label ```label```, for changing its instructions. Changing its terminator or its label leaves the edges out of date
+    pub fn block_mut(&mut self, label: &str) -> Option<&mut BasicBlock> {
+        self.blocks.iter_mut().find(|b| b.label == label)
+    }
+
+    /// A label which none of the blocks have, which is ```prefix``` itself if that is free and ```prefix``` followed by a number otherwise
+    #[must_use]
+    pub fn fresh_label(&self, prefix: &str) -> String {
+        let mut used: HashSet<String> = self.blocks.iter().map(|b| b.label.clone()).collect();
+        if used.contains(prefix) {
+            fresh_label(prefix, &mut used)
+        } else {
+            prefix.to_string()
+        }
+    }
+
+    /// Inserts ```block```, which has to end with a terminator, so that it is at ```index``` and the blocks from there on move along by one. The edges are worked out again from the terminators of every block.
+    /// # Errors
+    /// Will return [`CfgError::UnknownLabel`], leaving the blocks as they were, if the terminator of ```block``` targets a label which does not exist
+    /// # Panics
+    /// Will panic if ```index``` is 0 since the entry block has to stay first
+    pub fn insert_block(&mut self, index: usize, block: BasicBlock) -> Result<(), CfgError> {
+        assert!(index > 0, "the entry block can not be replaced");
+        self.blocks.insert(index, block);
+        match edges(&self.blocks) {
+            Ok(edges) => {
+                (self.successors, self.predecessors) = edges;
+                Ok(())
+            }
+            Err(e) => {
+                self.blocks.remove(index);
+                Err(e)
+            }
+        }
+    }
+
+    /// Removes the block at ```index``` and returns it, so that the blocks after it move back by one. Any `phi` nodes in the blocks it went to forget the values that came from it. The edges are worked out again from the terminators of the remaining blocks.
+    /// # Panics
+    /// Will panic if any of the remaining blocks can still go to the removed block, or if ```index``` is 0 since the entry block has to stay first
+    pub fn remove_block(&mut self, index: usize) -> BasicBlock {
+        assert!(index > 0, "the entry block can not be removed");
+        let block = self.blocks.remove(index);
+        #[cfg(feature = "ssa")]
+        for &succ in &self.successors[index] {
+            // The blocks after the removed block have moved back by one
+            let succ = match succ.cmp(&index) {
+                std::cmp::Ordering::Less => succ,
+                std::cmp::Ordering::Equal => continue,
+                std::cmp::Ordering::Greater => succ - 1,
+            };
+            for instr in &mut self.blocks[succ].instrs {
+                retain_phi_args(instr, |label| label != block.label);
+            }
+        }
+        (self.successors, self.predecessors) =
+            edges(&self.blocks).expect("a remaining block still goes to the removed block");
+        block
+    }
+
+    /// Splits the block at ```index``` in two just before the instruction at ```at```, which moves along with everything after it into a new block right after the original. The original block then jumps to the new one, whose label is made from the original label with [`Cfg::fresh_label`], and any `phi` nodes in the blocks it goes to are updated to say that their values come from the new block. Returns the index of the new block.
+    /// # Panics
+    /// Will panic if ```at``` is not the index of one of the instructions of the block, since the new block needs at least the terminator
+    pub fn split_block(&mut self, index: usize, at: usize) -> usize {
+        assert!(
+            at < self.blocks[index].instrs.len(),
+            "a block can only be split before one of its instructions"
+        );
+        let label = self.fresh_label(&format!("{}.split", self.blocks[index].label));
+        #[cfg(feature = "ssa")]
+        {
+            let old_label = self.blocks[index].label.clone();
+            for &succ in &self.successors[index] {
+                for instr in &mut self.blocks[succ].instrs {
+                    rename_phi_label(instr, &old_label, &label);
+                }
+            }
+        }
+        let block = &mut self.blocks[index];
+        let instrs = block.instrs.split_off(at);
+        #[cfg(feature = "position")]
+        let pos = instrs[0].get_pos().map(Position::derived);
+        #[cfg_attr(not(feature = "position"), allow(unused_mut))]
+        let mut term = jump(label.clone());
+        #[cfg(feature = "position")]
+        term.set_pos(pos);
+        block.instrs.push(term);
+        // The new block goes wherever the original block went, so all of its labels exist
+        self.insert_block(
+            index + 1,
+            BasicBlock {
+                label,
+                #[cfg(feature = "position")]
+                pos,
+                instrs,
+            },
+        )
+        .expect("the labels of a block which was split exist");
+        index + 1
+    }
+
+    /// Converts the blocks back into a list of instructions, each block starting with its label
+    #[must_use]
+    pub fn into_code(self) -> Vec<Code> {
+        self.blocks
+            .into_iter()
+            .flat_map(|b| {
+                std::iter::once(Code::Label {
+                    label: b.label,
+                    #[cfg(feature = "position")]
+                    pos: b.pos,
+                })
+                .chain(b.instrs.into_iter().map(Code::Instruction))
+            })
+            .collect()
+    }
+}
+
+// Keeps only the arguments of ```instr```, if it is a phi node, whose labels satisfy ```keep```
+#[cfg(feature = "ssa")]
+fn retain_phi_args(instr: &mut Instruction, keep: impl Fn(&str) -> bool) {
+    if let Instruction::Value {
+        op: ValueOps::Phi,
+        args,
+        labels,
+        ..
+    } = instr
+    {
+        let (kept_args, kept_labels) = args
+            .drain(..)
+            .zip(labels.drain(..))
+            .filter(|(_, label)| keep(label))
+            .unzip();
+        *args = kept_args;
+        *labels = kept_labels;
+    }
+}
+
+// Makes ```instr```, if it is a phi node, take the values it took from ```from``` from ```to``` instead
+#[cfg(feature = "ssa")]
+fn rename_phi_label(instr: &mut Instruction, from: &str, to: &str) {
+    if let Instruction::Value {
+        op: ValueOps::Phi,
+        labels,
+        ..
+    } = instr
+    {
+        for label in labels.iter_mut().filter(|l| *l == from) {
+            to.clone_into(label);
+        }
+    }
+}
+
+// The successors and predecessors of each of ```blocks``` from the labels their terminators go to, or the next block for those without one
+#[allow(clippy::type_complexity)]
+fn edges(blocks: &[BasicBlock]) -> Result<(Vec<Vec<usize>>, Vec<Vec<usize>>), CfgError> {
+    let label_map: HashMap<&str, usize> = blocks
+        .iter()
+        .enumerate()
+        .map(|(i, b)| (b.label.as_str(), i))
+        .collect();
+    let successors: Vec<Vec<usize>> = blocks
+        .iter()
+        .enumerate()
+        .map(|(i, b)| match b.instrs.last() {
+            Some(Instruction::Effect {
+                op: EffectOps::Jump | EffectOps::Branch,
+                labels,
+                ..
+            }) => labels
+                .iter()
+                .map(|l| {
+                    label_map
+                        .get(l.as_str())
+                        .copied()
+                        .ok_or_else(|| CfgError::UnknownLabel(l.clone()))
+                })
+                .collect(),
+            Some(Instruction::Effect {
+                op: EffectOps::Return,
+                ..
+            }) => Ok(Vec::new()),
+            // A block which doesn't end with a terminator falls through
+            _ if i + 1 < blocks.len() => Ok(vec![i + 1]),
+            _ => Ok(Vec::new()),
+        })
+        .collect::<Result<_, _>>()?;
+    let mut predecessors = vec![Vec::new(); blocks.len()];
+    for (from, succs) in successors.iter().enumerate() {
+        for to in succs {
+            if !predecessors[*to].contains(&from) {
+                predecessors[*to].push(from);
+            }
+        }
+    }
+    Ok((successors, predecessors))
+}
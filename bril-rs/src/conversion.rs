@@ -1,4 +1,7 @@
-use std::fmt::Display;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
 
 use crate::{
     AbstractArgument, AbstractCode, AbstractFunction, AbstractInstruction, AbstractProgram,
@@ -6,56 +9,72 @@ use crate::{
     ValueOps,
 };
 
-use thiserror::Error;
-
 // This is a nifty trick to supply a global value for pos when it is not defined
 #[cfg(not(feature = "position"))]
 #[allow(non_upper_case_globals)]
 const pos: Option<Position> = None;
 
-/// This is the [`std::error::Error`] implementation for `bril_rs`. This crate currently only supports errors from converting between [`AbstractProgram`] and [Program]
+/// This is the [`core::error::Error`] implementation for `bril_rs`. This crate currently only supports errors from converting between [`AbstractProgram`] and [Program]
 // todo Should this also wrap Serde errors? In this case, maybe change the name from ConversionError
-// Having the #[error(...)] for all variants derives the Display trait as well
-#[derive(Error, Debug)]
+// Display is written out by hand rather than derived with thiserror, which needs std, so that conversions work without it
+#[derive(Debug)]
 #[allow(clippy::module_name_repetitions)]
 pub enum ConversionError {
     /// Expected a primitive type like int or bool, found {0}"
-    #[error("Expected a primitive type like int or bool, found {0}")]
     InvalidPrimitive(String),
 
     /// Expected a parameterized type like ptr, found {0}<{1}>
-    #[error("Expected a parameterized type like ptr, found {0}<{1}>")]
     InvalidParameterized(String, String),
 
     /// Expected an value operation, found {0}
-    #[error("Expected an value operation, found {0}")]
     InvalidValueOps(String),
 
     /// Expected an effect operation, found {0}
-    #[error("Expected an effect operation, found {0}")]
     InvalidEffectOps(String),
 
     /// Missing type signature
-    #[error("Missing type signature")]
     MissingType,
 }
 
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPrimitive(found) => {
+                write!(
+                    f,
+                    "Expected a primitive type like int or bool, found {found}"
+                )
+            }
+            Self::InvalidParameterized(ty, param) => write!(
+                f,
+                "Expected a parameterized type like ptr, found {ty}<{param}>"
+            ),
+            Self::InvalidValueOps(found) => {
+                write!(f, "Expected an value operation, found {found}")
+            }
+            Self::InvalidEffectOps(found) => {
+                write!(f, "Expected an effect operation, found {found}")
+            }
+            Self::MissingType => write!(f, "Missing type signature"),
+        }
+    }
+}
+
+impl core::error::Error for ConversionError {}
+
 impl ConversionError {
     #[doc(hidden)]
     #[must_use]
     pub fn add_pos(self, pos_var: Option<Position>) -> PositionalConversionError {
-        match self {
-            //Self::PositionalConversionErrorConversion(e) => e,
-            _ => PositionalConversionError {
-                e: Box::new(self),
-                pos: pos_var,
-            },
+        PositionalConversionError {
+            e: Box::new(self),
+            pos: pos_var,
         }
     }
 }
 
 /// Wraps [`ConversionError`] to optionally provide source code positions if they are available.
-#[derive(Error, Debug)]
+#[derive(Debug)]
 pub struct PositionalConversionError {
     e: Box<ConversionError>,
     pos: Option<Position>,
@@ -70,32 +89,64 @@ impl PositionalConversionError {
             pos: None,
         }
     }
+
+    /// The underlying error without its position
+    #[must_use]
+    pub fn error(&self) -> &ConversionError {
+        &self.e
+    }
+
+    /// Where the error occurred if the program has source positions
+    #[must_use]
+    pub const fn pos(&self) -> Option<Position> {
+        self.pos
+    }
+
+    /// Separates the underlying error from its position so that it can be wrapped in another error type
+    #[must_use]
+    pub fn into_parts(self) -> (ConversionError, Option<Position>) {
+        (*self.e, self.pos)
+    }
 }
 
 impl Display for PositionalConversionError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             #[cfg(feature = "position")]
-            PositionalConversionError { e, pos: Some(pos) } => {
+            Self { e, pos: Some(pos) } => {
                 write!(f, "Line {}, Column {}: {e}", pos.row, pos.col)
             }
             #[cfg(not(feature = "position"))]
-            PositionalConversionError { e: _, pos: Some(_) } => {
+            Self { e: _, pos: Some(_) } => {
                 unreachable!()
             }
-            PositionalConversionError { e, pos: None } => write!(f, "{e}"),
+            Self { e, pos: None } => write!(f, "{e}"),
         }
     }
 }
 
+impl core::error::Error for PositionalConversionError {}
+
 impl TryFrom<AbstractProgram> for Program {
     type Error = PositionalConversionError;
-    fn try_from(AbstractProgram { functions }: AbstractProgram) -> Result<Self, Self::Error> {
+    fn try_from(
+        AbstractProgram {
+            functions,
+            #[cfg(feature = "import")]
+            imports,
+            #[cfg(feature = "attrs")]
+            attrs,
+        }: AbstractProgram,
+    ) -> Result<Self, Self::Error> {
         Ok(Self {
             functions: functions
                 .into_iter()
-                .map(std::convert::TryInto::try_into)
+                .map(core::convert::TryInto::try_into)
                 .collect::<Result<Vec<Function>, _>>()?,
+            #[cfg(feature = "import")]
+            imports,
+            #[cfg(feature = "attrs")]
+            attrs,
         })
     }
 }
@@ -110,17 +161,19 @@ impl TryFrom<AbstractFunction> for Function {
             return_type,
             #[cfg(feature = "position")]
             pos,
+            #[cfg(feature = "attrs")]
+            attrs,
         }: AbstractFunction,
     ) -> Result<Self, Self::Error> {
         Ok(Self {
             args: args
                 .into_iter()
-                .map(std::convert::TryInto::try_into)
+                .map(core::convert::TryInto::try_into)
                 .collect::<Result<Vec<Argument>, _>>()
                 .map_err(|e| e.add_pos(pos))?,
             instrs: instrs
                 .into_iter()
-                .map(std::convert::TryInto::try_into)
+                .map(core::convert::TryInto::try_into)
                 .collect::<Result<Vec<Code>, _>>()?,
             name,
             return_type: match return_type {
@@ -129,6 +182,8 @@ impl TryFrom<AbstractFunction> for Function {
             },
             #[cfg(feature = "position")]
             pos,
+            #[cfg(feature = "attrs")]
+            attrs,
         })
     }
 }
@@ -174,16 +229,23 @@ impl TryFrom<AbstractInstruction> for Instruction {
                 value,
                 #[cfg(feature = "position")]
                 pos,
-            } => Self::Constant {
-                dest,
-                op,
-                const_type: const_type
+                #[cfg(feature = "attrs")]
+                attrs,
+            } => {
+                let const_type: Type = const_type
                     .try_into()
-                    .map_err(|e: ConversionError| e.add_pos(pos))?,
-                value,
-                #[cfg(feature = "position")]
-                pos,
-            },
+                    .map_err(|e: ConversionError| e.add_pos(pos))?;
+                Self::Constant {
+                    dest,
+                    op,
+                    value: value.for_type(&const_type),
+                    const_type,
+                    #[cfg(feature = "position")]
+                    pos,
+                    #[cfg(feature = "attrs")]
+                    attrs,
+                }
+            }
             AbstractInstruction::Value {
                 args,
                 dest,
@@ -193,6 +255,10 @@ impl TryFrom<AbstractInstruction> for Instruction {
                 op_type,
                 #[cfg(feature = "position")]
                 pos,
+                #[cfg(feature = "tuples")]
+                extra_dests,
+                #[cfg(feature = "attrs")]
+                attrs,
             } => Self::Value {
                 args,
                 dest,
@@ -201,12 +267,19 @@ impl TryFrom<AbstractInstruction> for Instruction {
                 op_type: op_type
                     .try_into()
                     .map_err(|e: ConversionError| e.add_pos(pos))?,
+                #[cfg(feature = "tuples")]
+                extra_dests: extra_dests
+                    .into_iter()
+                    .map(TryInto::try_into)
+                    .collect::<Result<_, _>>()
+                    .map_err(|e: ConversionError| e.add_pos(pos))?,
                 #[cfg(feature = "position")]
                 pos,
                 op: match op.as_ref() {
                     "add" => ValueOps::Add,
                     "mul" => ValueOps::Mul,
                     "div" => ValueOps::Div,
+                    "rem" => ValueOps::Rem,
                     "eq" => ValueOps::Eq,
                     "lt" => ValueOps::Lt,
                     "gt" => ValueOps::Gt,
@@ -238,17 +311,47 @@ impl TryFrom<AbstractInstruction> for Instruction {
                     "fle" => ValueOps::Fle,
                     #[cfg(feature = "float")]
                     "fge" => ValueOps::Fge,
+                    #[cfg(feature = "float")]
+                    "float2bits" => ValueOps::Float2bits,
+                    #[cfg(feature = "float")]
+                    "bits2float" => ValueOps::Bits2float,
                     #[cfg(feature = "memory")]
                     "alloc" => ValueOps::Alloc,
                     #[cfg(feature = "memory")]
                     "load" => ValueOps::Load,
                     #[cfg(feature = "memory")]
                     "ptradd" => ValueOps::PtrAdd,
+                    #[cfg(feature = "char")]
+                    "ceq" => ValueOps::Ceq,
+                    #[cfg(feature = "char")]
+                    "clt" => ValueOps::Clt,
+                    #[cfg(feature = "char")]
+                    "cle" => ValueOps::Cle,
+                    #[cfg(feature = "char")]
+                    "cgt" => ValueOps::Cgt,
+                    #[cfg(feature = "char")]
+                    "cge" => ValueOps::Cge,
+                    #[cfg(feature = "char")]
+                    "char2int" => ValueOps::Char2int,
+                    #[cfg(feature = "char")]
+                    "int2char" => ValueOps::Int2char,
+                    #[cfg(feature = "strings")]
+                    "concat" => ValueOps::Concat,
+                    #[cfg(feature = "strings")]
+                    "length" => ValueOps::Length,
+                    #[cfg(feature = "strings")]
+                    "index" => ValueOps::Index,
+                    #[cfg(feature = "concurrency")]
+                    "spawn" => ValueOps::Spawn,
+                    #[cfg(feature = "rand")]
+                    "rand" => ValueOps::Rand,
                     v => {
                         return Err(ConversionError::InvalidValueOps(v.to_string()))
                             .map_err(|e| e.add_pos(pos))
                     }
                 },
+                #[cfg(feature = "attrs")]
+                attrs,
             },
             AbstractInstruction::Effect {
                 args,
@@ -257,6 +360,8 @@ impl TryFrom<AbstractInstruction> for Instruction {
                 op,
                 #[cfg(feature = "position")]
                 pos,
+                #[cfg(feature = "attrs")]
+                attrs,
             } => Self::Effect {
                 args,
                 funcs,
@@ -280,11 +385,15 @@ impl TryFrom<AbstractInstruction> for Instruction {
                     "commit" => EffectOps::Commit,
                     #[cfg(feature = "speculate")]
                     "guard" => EffectOps::Guard,
+                    #[cfg(feature = "concurrency")]
+                    "join" => EffectOps::Join,
                     e => {
                         return Err(ConversionError::InvalidEffectOps(e.to_string()))
                             .map_err(|e| e.add_pos(pos))
                     }
                 },
+                #[cfg(feature = "attrs")]
+                attrs,
             },
         })
     }
@@ -294,10 +403,7 @@ impl TryFrom<Option<AbstractType>> for Type {
     type Error = ConversionError;
 
     fn try_from(value: Option<AbstractType>) -> Result<Self, Self::Error> {
-        match value {
-            Some(t) => t.try_into(),
-            None => Err(ConversionError::MissingType),
-        }
+        value.map_or(Err(ConversionError::MissingType), TryInto::try_into)
     }
 }
 
@@ -309,6 +415,12 @@ impl TryFrom<AbstractType> for Type {
             AbstractType::Primitive(t) if t == "bool" => Self::Bool,
             #[cfg(feature = "float")]
             AbstractType::Primitive(t) if t == "float" => Self::Float,
+            #[cfg(feature = "char")]
+            AbstractType::Primitive(t) if t == "char" => Self::Char,
+            #[cfg(feature = "strings")]
+            AbstractType::Primitive(t) if t == "str" => Self::Str,
+            #[cfg(feature = "concurrency")]
+            AbstractType::Primitive(t) if t == "thread" => Self::Thread,
             AbstractType::Primitive(t) => return Err(ConversionError::InvalidPrimitive(t)),
             #[cfg(feature = "memory")]
             AbstractType::Parameterized(t, ty) if t == "ptr" => {
@@ -317,6 +429,13 @@ impl TryFrom<AbstractType> for Type {
             AbstractType::Parameterized(t, ty) => {
                 return Err(ConversionError::InvalidParameterized(t, ty.to_string()))
             }
+            #[cfg(feature = "tuples")]
+            AbstractType::Tuple(types) => Self::Tuple(
+                types
+                    .into_iter()
+                    .map(TryInto::try_into)
+                    .collect::<Result<_, _>>()?,
+            ),
         })
     }
 }
@@ -0,0 +1,426 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::cfg::{BasicBlock, Cfg};
+use crate::{Argument, Instruction, Literal, Type, ValueOps};
+
+/// The direction in which facts flow through the [`Cfg`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Facts flow from the entry of the function towards its exits
+    Forward,
+    /// Facts flow from the exits of the function towards its entry
+    Backward,
+}
+
+/// A dataflow analysis which can be solved over a [`Cfg`] with [`solve`]
+pub trait DataflowAnalysis {
+    /// The facts which are computed at the boundaries of each block
+    type Fact: Clone + PartialEq;
+
+    /// Which way the analysis goes
+    const DIRECTION: Direction;
+
+    /// The fact flowing into the entry block(or out of the exit blocks for a backward analysis)
+    fn boundary(&self, cfg: &Cfg) -> Self::Fact;
+
+    /// The fact every other block starts with before the solver has visited it
+    fn initial(&self, cfg: &Cfg) -> Self::Fact;
+
+    /// Combines the facts flowing into a block from its neighbours. ```facts``` is never empty
+    fn meet(&self, facts: &[&Self::Fact]) -> Self::Fact;
+
+    /// Computes the fact at the other end of ```block```, which is ```cfg.blocks[index]```, given the fact flowing into it
+    fn transfer(&self, index: usize, block: &BasicBlock, input: &Self::Fact) -> Self::Fact;
+}
+
+/// The solution of a [`DataflowAnalysis`]. Both vectors are indexed by the position of the block in [`Cfg::blocks`]
+#[derive(Debug, Clone)]
+pub struct DataflowResult<F> {
+    /// The fact at the start of each block
+    pub inputs: Vec<F>,
+    /// The fact at the end of each block
+    pub outputs: Vec<F>,
+}
+
+/// Solves ```analysis``` over ```cfg``` with a worklist algorithm, iterating until a fixpoint is reached.
+/// The analysis must be monotone for this to terminate.
+pub fn solve<A: DataflowAnalysis>(analysis: &A, cfg: &Cfg) -> DataflowResult<A::Fact> {
+    let num_blocks = cfg.blocks.len();
+    let (ins, outs) = match A::DIRECTION {
+        Direction::Forward => (&cfg.predecessors, &cfg.successors),
+        Direction::Backward => (&cfg.successors, &cfg.predecessors),
+    };
+    let is_boundary = |b: usize| match A::DIRECTION {
+        Direction::Forward => b == 0,
+        Direction::Backward => cfg.successors[b].is_empty(),
+    };
+
+    // "before" is the fact flowing into the block in the direction of the analysis
+    let boundary = analysis.boundary(cfg);
+    let initial = analysis.initial(cfg);
+    let mut before: Vec<A::Fact> = (0..num_blocks)
+        .map(|b| {
+            if is_boundary(b) {
+                boundary.clone()
+            } else {
+                initial.clone()
+            }
+        })
+        .collect();
+    let mut after: Vec<A::Fact> = vec![initial; num_blocks];
+
+    let mut work_list: VecDeque<usize> = match A::DIRECTION {
+        Direction::Forward => (0..num_blocks).collect(),
+        Direction::Backward => (0..num_blocks).rev().collect(),
+    };
+    let mut queued = vec![true; num_blocks];
+
+    while let Some(b) = work_list.pop_front() {
+        queued[b] = false;
+        if !ins[b].is_empty() {
+            let mut incoming: Vec<&A::Fact> = ins[b].iter().map(|n| &after[*n]).collect();
+            if is_boundary(b) {
+                incoming.push(&boundary);
+            }
+            before[b] = analysis.meet(&incoming);
+        }
+        let new_after = analysis.transfer(b, &cfg.blocks[b], &before[b]);
+        if new_after != after[b] {
+            after[b] = new_after;
+            for n in &outs[b] {
+                if !queued[*n] {
+                    queued[*n] = true;
+                    work_list.push_back(*n);
+                }
+            }
+        }
+    }
+
+    match A::DIRECTION {
+        Direction::Forward => DataflowResult {
+            inputs: before,
+            outputs: after,
+        },
+        Direction::Backward => DataflowResult {
+            inputs: after,
+            outputs: before,
+        },
+    }
+}
+
+/// The variables read by ```instr```
+#[must_use]
+pub const fn uses(instr: &Instruction) -> &[String] {
+    match instr {
+        Instruction::Constant { .. } => &[],
+        Instruction::Value { args, .. } | Instruction::Effect { args, .. } => args.as_slice(),
+    }
+}
+
+/// The variable written by ```instr``` if there is one
+#[must_use]
+pub const fn def(instr: &Instruction) -> Option<&String> {
+    match instr {
+        Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => Some(dest),
+        Instruction::Effect { .. } => None,
+    }
+}
+
+/// Live variables: the set of variables which may be read before being written again
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LiveVariables;
+
+impl DataflowAnalysis for LiveVariables {
+    type Fact = BTreeSet<String>;
+
+    const DIRECTION: Direction = Direction::Backward;
+
+    fn boundary(&self, _cfg: &Cfg) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn initial(&self, _cfg: &Cfg) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn meet(&self, facts: &[&Self::Fact]) -> Self::Fact {
+        facts.iter().flat_map(|f| f.iter().cloned()).collect()
+    }
+
+    fn transfer(&self, _index: usize, block: &BasicBlock, input: &Self::Fact) -> Self::Fact {
+        let mut live = input.clone();
+        for instr in block.instrs.iter().rev() {
+            if let Some(dest) = def(instr) {
+                live.remove(dest);
+            }
+            live.extend(uses(instr).iter().cloned());
+        }
+        live
+    }
+}
+
+/// Where a variable was defined
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DefinitionSite {
+    /// The variable is an argument of the function
+    Argument,
+    /// The variable is the destination of ```cfg.blocks[block].instrs[index]```
+    Instruction {
+        /// The index of the block
+        block: usize,
+        /// The index of the instruction in the block
+        index: usize,
+    },
+}
+
+/// A single definition of a variable
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Definition {
+    /// The variable being defined
+    pub var: String,
+    /// Where it is defined
+    pub site: DefinitionSite,
+}
+
+/// Reaching definitions: the set of definitions which may reach a program point without being overwritten
+#[derive(Debug, Clone)]
+pub struct ReachingDefinitions {
+    args: Vec<String>,
+}
+
+impl ReachingDefinitions {
+    /// Creates the analysis for a function with arguments ```args```
+    #[must_use]
+    pub fn new(args: &[Argument]) -> Self {
+        Self {
+            args: args.iter().map(|a| a.name.clone()).collect(),
+        }
+    }
+}
+
+impl DataflowAnalysis for ReachingDefinitions {
+    type Fact = BTreeSet<Definition>;
+
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn boundary(&self, _cfg: &Cfg) -> Self::Fact {
+        self.args
+            .iter()
+            .map(|a| Definition {
+                var: a.clone(),
+                site: DefinitionSite::Argument,
+            })
+            .collect()
+    }
+
+    fn initial(&self, _cfg: &Cfg) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn meet(&self, facts: &[&Self::Fact]) -> Self::Fact {
+        facts.iter().flat_map(|f| f.iter().cloned()).collect()
+    }
+
+    fn transfer(&self, block_idx: usize, block: &BasicBlock, input: &Self::Fact) -> Self::Fact {
+        let mut defs = input.clone();
+        for (index, instr) in block.instrs.iter().enumerate() {
+            if let Some(dest) = def(instr) {
+                defs.retain(|d| &d.var != dest);
+                defs.insert(Definition {
+                    var: dest.clone(),
+                    site: DefinitionSite::Instruction {
+                        block: block_idx,
+                        index,
+                    },
+                });
+            }
+        }
+        defs
+    }
+}
+
+/// The abstract value of a variable in [`ConstantPropagation`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstantValue {
+    /// The variable always holds this value
+    Constant(Literal),
+    /// The variable may hold different values
+    NotConstant,
+}
+
+/// Evaluates ```op``` on constant arguments if the result is statically known. Integer arithmetic wraps on overflow, `div` and `rem` round towards zero like brili does by default, and division by zero is not folded.
+#[must_use]
+pub fn fold_constant(op: ValueOps, args: &[Literal]) -> Option<Literal> {
+    #[cfg(feature = "char")]
+    use Literal::Char;
+    #[cfg(feature = "float")]
+    use Literal::Float;
+    #[cfg(feature = "strings")]
+    use Literal::Str;
+    use Literal::{Bool, Int};
+    Some(match (op, args) {
+        (ValueOps::Add, [Int(a), Int(b)]) => Int(a.wrapping_add(*b)),
+        (ValueOps::Sub, [Int(a), Int(b)]) => Int(a.wrapping_sub(*b)),
+        (ValueOps::Mul, [Int(a), Int(b)]) => Int(a.wrapping_mul(*b)),
+        (ValueOps::Div, [Int(a), Int(b)]) if *b != 0 => Int(a.wrapping_div(*b)),
+        (ValueOps::Rem, [Int(a), Int(b)]) if *b != 0 => Int(a.wrapping_rem(*b)),
+        (ValueOps::Eq, [Int(a), Int(b)]) => Bool(a == b),
+        (ValueOps::Lt, [Int(a), Int(b)]) => Bool(a < b),
+        (ValueOps::Gt, [Int(a), Int(b)]) => Bool(a > b),
+        (ValueOps::Le, [Int(a), Int(b)]) => Bool(a <= b),
+        (ValueOps::Ge, [Int(a), Int(b)]) => Bool(a >= b),
+        (ValueOps::Not, [Bool(a)]) => Bool(!a),
+        (ValueOps::And, [Bool(a), Bool(b)]) => Bool(*a && *b),
+        (ValueOps::Or, [Bool(a), Bool(b)]) => Bool(*a || *b),
+        (ValueOps::Id, [a]) => a.clone(),
+        #[cfg(feature = "float")]
+        (ValueOps::Fadd, [Float(a), Float(b)]) => Float(a + b),
+        #[cfg(feature = "float")]
+        (ValueOps::Fsub, [Float(a), Float(b)]) => Float(a - b),
+        #[cfg(feature = "float")]
+        (ValueOps::Fmul, [Float(a), Float(b)]) => Float(a * b),
+        #[cfg(feature = "float")]
+        (ValueOps::Fdiv, [Float(a), Float(b)]) => Float(a / b),
+        #[cfg(feature = "float")]
+        #[allow(clippy::float_cmp)]
+        (ValueOps::Feq, [Float(a), Float(b)]) => Bool(a == b),
+        #[cfg(feature = "float")]
+        (ValueOps::Flt, [Float(a), Float(b)]) => Bool(a < b),
+        #[cfg(feature = "float")]
+        (ValueOps::Fgt, [Float(a), Float(b)]) => Bool(a > b),
+        #[cfg(feature = "float")]
+        (ValueOps::Fle, [Float(a), Float(b)]) => Bool(a <= b),
+        #[cfg(feature = "float")]
+        (ValueOps::Fge, [Float(a), Float(b)]) => Bool(a >= b),
+        #[cfg(feature = "float")]
+        (ValueOps::Float2bits, [Float(a)]) => Int(i64::from_ne_bytes(a.to_bits().to_ne_bytes())),
+        // JSON has no way to write infinities and NaNs, so they are left for the program to compute
+        #[cfg(feature = "float")]
+        (ValueOps::Bits2float, [Int(a)]) => Float(
+            Some(f64::from_bits(u64::from_ne_bytes(a.to_ne_bytes()))).filter(|f| f.is_finite())?,
+        ),
+        #[cfg(feature = "char")]
+        (ValueOps::Ceq, [Char(a), Char(b)]) => Bool(a == b),
+        #[cfg(feature = "char")]
+        (ValueOps::Clt, [Char(a), Char(b)]) => Bool(a < b),
+        #[cfg(feature = "char")]
+        (ValueOps::Cgt, [Char(a), Char(b)]) => Bool(a > b),
+        #[cfg(feature = "char")]
+        (ValueOps::Cle, [Char(a), Char(b)]) => Bool(a <= b),
+        #[cfg(feature = "char")]
+        (ValueOps::Cge, [Char(a), Char(b)]) => Bool(a >= b),
+        #[cfg(feature = "char")]
+        (ValueOps::Char2int, [Char(c)]) => Int(i64::from(u32::from(*c))),
+        // Integers which aren't a Unicode scalar value are an error at runtime
+        #[cfg(feature = "char")]
+        (ValueOps::Int2char, [Int(i)]) => Char(char::from_u32(u32::try_from(*i).ok()?)?),
+        #[cfg(feature = "strings")]
+        (ValueOps::Concat, [Str(a), Str(b)]) => Str(format!("{a}{b}")),
+        #[cfg(feature = "strings")]
+        (ValueOps::Length, [Str(s)]) => Int(i64::try_from(s.chars().count()).ok()?),
+        // Indices outside of the string are an error at runtime
+        #[cfg(feature = "strings")]
+        (ValueOps::Index, [Str(s), Int(i)]) => Char(s.chars().nth(usize::try_from(*i).ok()?)?),
+        _ => return None,
+    })
+}
+
+/// The literal a `const` instruction of type ```const_type``` produces. Integer literals of `float` constants are promoted, and `char` literals of `str` constants become strings as [`Literal::for_type`] does.
+#[must_use]
+pub fn constant_literal(const_type: &Type, value: &Literal) -> Literal {
+    match (const_type, value) {
+        #[cfg(feature = "float")]
+        #[allow(clippy::cast_precision_loss)]
+        (Type::Float, Literal::Int(i)) => Literal::Float(*i as f64),
+        _ => value.clone().for_type(const_type),
+    }
+}
+
+/// Constant propagation: which variables are known to hold a single constant value. Variables which are not in the map have not been defined on any path.
+#[derive(Debug, Clone)]
+pub struct ConstantPropagation {
+    args: Vec<String>,
+}
+
+impl ConstantPropagation {
+    /// Creates the analysis for a function with arguments ```args```, which are never constant
+    #[must_use]
+    pub fn new(args: &[Argument]) -> Self {
+        Self {
+            args: args.iter().map(|a| a.name.clone()).collect(),
+        }
+    }
+
+    /// Updates ```consts``` with the effect of a single instruction, for passes which need the facts in the middle of a block
+    pub fn transfer_instr(consts: &mut BTreeMap<String, ConstantValue>, instr: &Instruction) {
+        match instr {
+            Instruction::Constant {
+                dest,
+                const_type,
+                value,
+                ..
+            } => {
+                consts.insert(
+                    dest.clone(),
+                    ConstantValue::Constant(constant_literal(const_type, value)),
+                );
+            }
+            Instruction::Value { dest, op, args, .. } => {
+                let known: Option<Vec<Literal>> = args
+                    .iter()
+                    .map(|a| match consts.get(a) {
+                        Some(ConstantValue::Constant(l)) => Some(l.clone()),
+                        _ => None,
+                    })
+                    .collect();
+                let val = known
+                    .and_then(|lits| fold_constant(*op, &lits))
+                    .map_or(ConstantValue::NotConstant, ConstantValue::Constant);
+                consts.insert(dest.clone(), val);
+            }
+            Instruction::Effect { .. } => {}
+        }
+    }
+}
+
+impl DataflowAnalysis for ConstantPropagation {
+    type Fact = BTreeMap<String, ConstantValue>;
+
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn boundary(&self, _cfg: &Cfg) -> Self::Fact {
+        self.args
+            .iter()
+            .map(|a| (a.clone(), ConstantValue::NotConstant))
+            .collect()
+    }
+
+    fn initial(&self, _cfg: &Cfg) -> Self::Fact {
+        BTreeMap::new()
+    }
+
+    fn meet(&self, facts: &[&Self::Fact]) -> Self::Fact {
+        let mut result = BTreeMap::new();
+        for fact in facts {
+            for (var, val) in *fact {
+                result
+                    .entry(var.clone())
+                    .and_modify(|current: &mut ConstantValue| {
+                        if current != val {
+                            *current = ConstantValue::NotConstant;
+                        }
+                    })
+                    .or_insert_with(|| val.clone());
+            }
+        }
+        result
+    }
+
+    fn transfer(&self, _index: usize, block: &BasicBlock, input: &Self::Fact) -> Self::Fact {
+        let mut consts = input.clone();
+        for instr in &block.instrs {
+            Self::transfer_instr(&mut consts, instr);
+        }
+        consts
+    }
+}
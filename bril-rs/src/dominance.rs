@@ -0,0 +1,367 @@
+use std::collections::BTreeSet;
+
+use thiserror::Error;
+
+use crate::cfg::Cfg;
+
+/// The dominator tree of a [`Cfg`] along with the dominance frontier of every block, made by [`Cfg::dominators`], or the post-dominator tree made by [`Cfg::post_dominators`].
+///
+/// Block ```a``` dominates block ```b``` when every path from the entry to ```b``` goes through ```a```, so every block dominates itself. Blocks which can't be reached from the entry aren't dominated by anything, not even themselves, and don't dominate anything.
+///
+/// For the post-dominator tree, the same methods answer the same questions about the paths from each block to the end of the function, as though every edge were reversed: ```a``` post-dominates ```b``` when every path from ```b``` to a return goes through ```a```. A function can return from more than one block, so the post-dominator tree can have more than one root, and blocks which can never reach a return, like those in an infinite loop, play the part of unreachable blocks. The post-dominance frontier of a block is the set of blocks with a branch which decides whether it runs, which is what control dependence is built from.
+/// ```
+/// # use bril_rs::{builder::FunctionBuilder, cfg::Cfg, Literal, Type};
+/// let mut builder = FunctionBuilder::new("main");
+/// let cond = builder.constant(Type::Bool, Literal::Bool(true));
+/// builder.br(&cond, "then".to_string(), "else".to_string());
+/// builder.label("then".to_string());
+/// builder.jmp("end".to_string());
+/// builder.label("else".to_string());
+/// builder.label("end".to_string());
+/// builder.ret(None);
+/// let cfg = Cfg::from_function(&builder.finish().unwrap()).unwrap();
+/// let [entry, then, els, end] = ["b0", "then", "else", "end"].map(|l| cfg.find(l).unwrap());
+///
+/// let doms = cfg.dominators();
+/// assert_eq!(doms.verify(&cfg), Ok(()));
+/// assert_eq!(doms.immediate_dominator(end), Some(entry));
+/// assert!(doms.dominates(entry, then) && !doms.dominates(then, end));
+/// assert!(doms.frontier(then).contains(&end));
+///
+/// let post = cfg.post_dominators();
+/// assert_eq!(post.verify(&cfg), Ok(()));
+/// assert_eq!(post.immediate_dominator(entry), Some(end));
+/// assert!(post.dominates(end, els) && !post.dominates(els, entry));
+/// assert!(post.frontier(then).contains(&entry));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Dominators {
+    // Each root is its own immediate dominator so that it can be told apart from unreachable blocks
+    idom: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    frontiers: Vec<BTreeSet<usize>>,
+    post: bool,
+}
+
+/// A way in which a [`Dominators`] doesn't match its [`Cfg`], found by [`Dominators::verify`]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+pub enum DominanceError {
+    /// .{dominator} {relation} .{block}, but the tree says it doesn't
+    #[error(".{dominator} {relation} .{block}, but the tree says it doesn't")]
+    Missing {
+        /// The label of the block which dominates ```block```
+        dominator: String,
+        /// The label of the dominated block
+        block: String,
+        /// Either `dominates` or `post-dominates`
+        relation: &'static str,
+    },
+    /// The tree says that .{dominator} {relation} .{block}, but it doesn't
+    #[error("the tree says that .{dominator} {relation} .{block}, but it doesn't")]
+    Extra {
+        /// The label of the block which the tree says dominates ```block```
+        dominator: String,
+        /// The label of the block it doesn't actually dominate
+        block: String,
+        /// Either `dominates` or `post-dominates`
+        relation: &'static str,
+    },
+    /// The frontier of .{block} should be {expected:?}, but the tree says it is {found:?}
+    #[error("the frontier of .{block} should be {expected:?}, but the tree says it is {found:?}")]
+    Frontier {
+        /// The label of the block whose frontier is wrong
+        block: String,
+        /// The labels of the blocks which are actually in the frontier
+        expected: Vec<String>,
+        /// The labels of the blocks which the tree has in the frontier
+        found: Vec<String>,
+    },
+}
+
+impl Dominators {
+    /// Finds the dominators of every block of ```cfg``` using "A Simple, Fast Dominance Algorithm" by Cooper, Harvey, and Kennedy. This is the same as [`Cfg::dominators`]
+    #[must_use]
+    pub fn new(cfg: &Cfg) -> Self {
+        let idom = immediate_dominators(&cfg.successors, &cfg.predecessors, 0);
+        let frontiers = dominance_frontiers(&cfg.predecessors, &idom);
+        Self::from_parts(idom, frontiers, false)
+    }
+
+    /// Finds the post-dominators of every block of ```cfg``` in the same way as [`Dominators::new`], but going backwards from every block which leaves the function. This is the same as [`Cfg::post_dominators`]
+    #[must_use]
+    pub fn post(cfg: &Cfg) -> Self {
+        // The edges are reversed and the blocks which leave the function all come from a virtual block after the last one, which is the single root the algorithm needs
+        let exit = cfg.blocks.len();
+        let exits: Vec<usize> = (0..exit)
+            .filter(|b| cfg.successors[*b].is_empty())
+            .collect();
+        let mut successors = cfg.predecessors.clone();
+        successors.push(exits);
+        let mut predecessors = cfg.successors.clone();
+        for preds in &mut predecessors {
+            if preds.is_empty() {
+                preds.push(exit);
+            }
+        }
+        predecessors.push(Vec::new());
+
+        let mut idom = immediate_dominators(&successors, &predecessors, exit);
+        // Nothing is reached from the virtual block but through its successors, so it is never in a frontier
+        let mut frontiers = dominance_frontiers(&predecessors, &idom);
+        idom.pop();
+        frontiers.pop();
+        for (b, d) in idom.iter_mut().enumerate() {
+            if *d == Some(exit) {
+                *d = Some(b);
+            }
+        }
+        Self::from_parts(idom, frontiers, true)
+    }
+
+    fn from_parts(idom: Vec<Option<usize>>, frontiers: Vec<BTreeSet<usize>>, post: bool) -> Self {
+        let mut children = vec![Vec::new(); idom.len()];
+        for (b, d) in idom.iter().enumerate() {
+            if let Some(d) = d {
+                if *d != b {
+                    children[*d].push(b);
+                }
+            }
+        }
+        Self {
+            idom,
+            children,
+            frontiers,
+            post,
+        }
+    }
+
+    /// Whether this is the post-dominator tree rather than the dominator tree
+    #[must_use]
+    pub const fn is_post(&self) -> bool {
+        self.post
+    }
+
+    /// The closest block other than ```block``` which dominates it, which neither the roots nor unreachable blocks have
+    #[must_use]
+    pub fn immediate_dominator(&self, block: usize) -> Option<usize> {
+        self.idom[block].filter(|d| *d != block)
+    }
+
+    /// The blocks which ```block``` is the immediate dominator of, in increasing order
+    #[must_use]
+    pub fn children(&self, block: usize) -> &[usize] {
+        &self.children[block]
+    }
+
+    /// The blocks which aren't dominated by ```block``` but have a predecessor which is, in increasing order. These are where the definitions in ```block``` meet the definitions from other paths.
+    #[must_use]
+    pub fn frontier(&self, block: usize) -> &BTreeSet<usize> {
+        &self.frontiers[block]
+    }
+
+    /// Whether ```block``` can be reached from the entry, or for post-dominators whether a return can be reached from ```block```
+    #[must_use]
+    pub fn is_reachable(&self, block: usize) -> bool {
+        self.idom[block].is_some()
+    }
+
+    /// Whether every path from the entry to ```b``` goes through ```a```
+    #[must_use]
+    pub fn dominates(&self, a: usize, b: usize) -> bool {
+        if !self.is_reachable(b) {
+            return false;
+        }
+        let mut runner = b;
+        loop {
+            if runner == a {
+                return true;
+            }
+            match self.immediate_dominator(runner) {
+                Some(d) => runner = d,
+                None => return false,
+            }
+        }
+    }
+
+    /// Whether ```a``` dominates ```b``` and isn't ```b```
+    #[must_use]
+    pub fn strictly_dominates(&self, a: usize, b: usize) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    /// Checks every answer of [`Dominators::dominates`] and [`Dominators::frontier`] against ```cfg```, which has to be the graph these are the dominators of, by working out dominance straight from its definition: ```a``` dominates ```b``` when ```b``` can be reached, but not without going through ```a```.
+    ///
+    /// This takes time cubic in the number of blocks, so it is meant for testing rather than for passes to call.
+    /// # Errors
+    /// Will return the first answer which is wrong
+    pub fn verify(&self, cfg: &Cfg) -> Result<(), DominanceError> {
+        let n = cfg.blocks.len();
+        let label = |b: usize| cfg.blocks[b].label.clone();
+        let relation = if self.post {
+            "post-dominates"
+        } else {
+            "dominates"
+        };
+        let reachable = self.reach(cfg, None);
+        let dominates: Vec<Vec<bool>> = (0..n)
+            .map(|a| {
+                let without = self.reach(cfg, Some(a));
+                (0..n)
+                    .map(|b| reachable[b] && (a == b || !without[b]))
+                    .collect()
+            })
+            .collect();
+
+        for (a, dominated) in dominates.iter().enumerate() {
+            for (b, expected) in dominated.iter().enumerate() {
+                match (*expected, self.dominates(a, b)) {
+                    (true, false) => {
+                        return Err(DominanceError::Missing {
+                            dominator: label(a),
+                            block: label(b),
+                            relation,
+                        })
+                    }
+                    (false, true) => {
+                        return Err(DominanceError::Extra {
+                            dominator: label(a),
+                            block: label(b),
+                            relation,
+                        })
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let predecessors = if self.post {
+            &cfg.successors
+        } else {
+            &cfg.predecessors
+        };
+        for (a, dominated) in dominates.iter().enumerate() {
+            let expected: BTreeSet<usize> = (0..n)
+                .filter(|b| {
+                    !(dominated[*b] && a != *b) && predecessors[*b].iter().any(|p| dominated[*p])
+                })
+                .collect();
+            if &expected != self.frontier(a) {
+                return Err(DominanceError::Frontier {
+                    block: label(a),
+                    expected: expected.into_iter().map(label).collect(),
+                    found: self.frontier(a).iter().copied().map(label).collect(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // The blocks which can be reached from the entry without going through ```skip```, or for post-dominators the blocks which can reach a return that way
+    fn reach(&self, cfg: &Cfg, skip: Option<usize>) -> Vec<bool> {
+        let (mut work_list, next): (Vec<usize>, _) = if self.post {
+            let exits = (0..cfg.blocks.len())
+                .filter(|b| cfg.successors[*b].is_empty())
+                .collect();
+            (exits, &cfg.predecessors)
+        } else {
+            (vec![0], &cfg.successors)
+        };
+        work_list.retain(|b| Some(*b) != skip);
+        let mut reached = vec![false; cfg.blocks.len()];
+        for b in &work_list {
+            reached[*b] = true;
+        }
+        while let Some(b) = work_list.pop() {
+            for &s in &next[b] {
+                if !reached[s] && Some(s) != skip {
+                    reached[s] = true;
+                    work_list.push(s);
+                }
+            }
+        }
+        reached
+    }
+}
+
+// The immediate dominator of every block reachable from ```root```, which is its own immediate dominator
+fn immediate_dominators(
+    successors: &[Vec<usize>],
+    predecessors: &[Vec<usize>],
+    root: usize,
+) -> Vec<Option<usize>> {
+    // Reverse postorder numbering of reachable blocks
+    let mut postorder = Vec::new();
+    let mut visited = vec![false; successors.len()];
+    let mut stack = vec![(root, 0)];
+    visited[root] = true;
+    while let Some((b, child)) = stack.pop() {
+        if let Some(&s) = successors[b].get(child) {
+            stack.push((b, child + 1));
+            if !visited[s] {
+                visited[s] = true;
+                stack.push((s, 0));
+            }
+        } else {
+            postorder.push(b);
+        }
+    }
+    let mut order = vec![usize::MAX; successors.len()];
+    for (i, b) in postorder.iter().enumerate() {
+        order[*b] = i;
+    }
+
+    let mut idom = vec![None; successors.len()];
+    idom[root] = Some(root);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &b in postorder.iter().rev().skip(1) {
+            let mut new_idom: Option<usize> = None;
+            for &p in &predecessors[b] {
+                if idom[p].is_none() {
+                    continue;
+                }
+                new_idom = Some(new_idom.map_or(p, |mut other| {
+                    let mut finger = p;
+                    while finger != other {
+                        while order[finger] < order[other] {
+                            finger = idom[finger].unwrap();
+                        }
+                        while order[other] < order[finger] {
+                            other = idom[other].unwrap();
+                        }
+                    }
+                    finger
+                }));
+            }
+            if new_idom != idom[b] {
+                idom[b] = new_idom;
+                changed = true;
+            }
+        }
+    }
+    idom
+}
+
+// The dominance frontier of every block given its immediate dominators
+fn dominance_frontiers(
+    predecessors: &[Vec<usize>],
+    idom: &[Option<usize>],
+) -> Vec<BTreeSet<usize>> {
+    let mut frontiers = vec![BTreeSet::new(); predecessors.len()];
+    for (b, preds) in predecessors.iter().enumerate() {
+        let Some(b_idom) = idom[b] else { continue };
+        if preds.len() < 2 {
+            continue;
+        }
+        for &p in preds {
+            let mut runner = p;
+            while idom[runner].is_some() && runner != b_idom {
+                frontiers[runner].insert(b);
+                runner = idom[runner].unwrap();
+            }
+        }
+    }
+    frontiers
+}
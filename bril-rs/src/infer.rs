@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use crate::{
+    AbstractCode, AbstractFunction, AbstractInstruction, AbstractProgram, AbstractType, Literal,
+};
+
+// The argument types and return type of a function
+type Signature = (Vec<AbstractType>, Option<AbstractType>);
+
+/// Fills in the type of every `const` and value operation in ```prog``` which doesn't have one but whose type can be worked out, like the `type-infer` tool.
+///
+/// A destination gets its type from the literal of a `const`, from the operation itself, like `bool` for `lt`, or from the types of the arguments for operations like `id`, `load`, and `call`. The types of variables are also worked out from how they are used, so that the `ptr<int>` of `p = alloc n` can come from a later `store p x`. Types that are written out are never changed, and types that can't be worked out are left missing for the conversion to [`crate::Program`] to report. Nothing is type checked here, so a variable used as two different types just gets the first one found.
+pub fn infer_types(prog: &mut AbstractProgram) {
+    let signatures: HashMap<String, Signature> = prog
+        .functions
+        .iter()
+        .map(|f| {
+            (
+                f.name.clone(),
+                (
+                    f.args.iter().map(|a| a.arg_type.clone()).collect(),
+                    f.return_type.clone(),
+                ),
+            )
+        })
+        .collect();
+    for func in &mut prog.functions {
+        infer_function_types(func, &signatures);
+    }
+}
+
+fn infer_function_types(func: &mut AbstractFunction, signatures: &HashMap<String, Signature>) {
+    let mut types: HashMap<String, AbstractType> = func
+        .args
+        .iter()
+        .map(|a| (a.name.clone(), a.arg_type.clone()))
+        .collect();
+    for instr in instructions(&func.instrs) {
+        if let Some((dest, Some(ty))) = dest(instr) {
+            types.entry(dest.clone()).or_insert_with(|| ty.clone());
+        }
+    }
+
+    // What each instruction says about its destination is trusted over what the uses of a variable say about it
+    loop {
+        let mut changed = false;
+        loop {
+            let mut found = false;
+            for instr in instructions(&func.instrs) {
+                if let Some((dest, None)) = dest(instr) {
+                    if !types.contains_key(dest) {
+                        if let Some(ty) = dest_type(instr, &types, signatures) {
+                            types.insert(dest.clone(), ty);
+                            found = true;
+                        }
+                    }
+                }
+            }
+            if !found {
+                break;
+            }
+            changed = true;
+        }
+        for instr in instructions(&func.instrs) {
+            for (arg, ty) in arg_types(instr, &types, signatures, func.return_type.as_ref()) {
+                if !types.contains_key(arg) {
+                    types.insert(arg.clone(), ty);
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    for code in &mut func.instrs {
+        if let AbstractCode::Instruction(
+            AbstractInstruction::Constant {
+                dest,
+                const_type: ty @ None,
+                ..
+            }
+            | AbstractInstruction::Value {
+                dest,
+                op_type: ty @ None,
+                ..
+            },
+        ) = code
+        {
+            *ty = types.get(dest).cloned();
+        }
+    }
+}
+
+fn instructions(code: &[AbstractCode]) -> impl Iterator<Item = &AbstractInstruction> {
+    code.iter().filter_map(|c| match c {
+        AbstractCode::Instruction(i) => Some(i),
+        AbstractCode::Label { .. } => None,
+    })
+}
+
+const fn dest(instr: &AbstractInstruction) -> Option<(&String, Option<&AbstractType>)> {
+    match instr {
+        AbstractInstruction::Constant {
+            dest, const_type, ..
+        } => Some((dest, const_type.as_ref())),
+        AbstractInstruction::Value { dest, op_type, .. } => Some((dest, op_type.as_ref())),
+        AbstractInstruction::Effect { .. } => None,
+    }
+}
+
+fn primitive(name: &str) -> AbstractType {
+    AbstractType::Primitive(name.to_string())
+}
+
+fn pointer_to(ty: AbstractType) -> AbstractType {
+    AbstractType::Parameterized("ptr".to_string(), Box::new(ty))
+}
+
+fn pointee(ty: &AbstractType) -> Option<AbstractType> {
+    match ty {
+        AbstractType::Parameterized(name, ty) if name == "ptr" => Some((**ty).clone()),
+        _ => None,
+    }
+}
+
+// The type of the arguments of an operation which always takes arguments of the same primitive type
+fn operand_type(op: &str) -> Option<&'static str> {
+    match op {
+        "add" | "sub" | "mul" | "div" | "rem" | "eq" | "lt" | "gt" | "le" | "ge" | "int2char"
+        | "bits2float" | "rand" => Some("int"),
+        "not" | "and" | "or" => Some("bool"),
+        "fadd" | "fsub" | "fmul" | "fdiv" | "feq" | "flt" | "fgt" | "fle" | "fge"
+        | "float2bits" => Some("float"),
+        "ceq" | "clt" | "cle" | "cgt" | "cge" | "char2int" => Some("char"),
+        "concat" | "length" => Some("str"),
+        _ => None,
+    }
+}
+
+// The type of the destination of an instruction whose type is missing
+fn dest_type(
+    instr: &AbstractInstruction,
+    types: &HashMap<String, AbstractType>,
+    signatures: &HashMap<String, Signature>,
+) -> Option<AbstractType> {
+    match instr {
+        AbstractInstruction::Constant { value, .. } => Some(primitive(match value {
+            Literal::Int(_) => "int",
+            Literal::Bool(_) => "bool",
+            #[cfg(feature = "float")]
+            Literal::Float(_) => "float",
+            #[cfg(feature = "char")]
+            Literal::Char(_) => "char",
+            #[cfg(feature = "strings")]
+            Literal::Str(_) => "str",
+        })),
+        AbstractInstruction::Value {
+            args, funcs, op, ..
+        } => match op.as_str() {
+            "add" | "sub" | "mul" | "div" | "rem" | "char2int" | "float2bits" | "length" => {
+                Some(primitive("int"))
+            }
+            "eq" | "lt" | "gt" | "le" | "ge" | "not" | "and" | "or" | "feq" | "flt" | "fgt"
+            | "fle" | "fge" | "ceq" | "clt" | "cle" | "cgt" | "cge" => Some(primitive("bool")),
+            "fadd" | "fsub" | "fmul" | "fdiv" | "bits2float" => Some(primitive("float")),
+            "int2char" | "index" => Some(primitive("char")),
+            "concat" => Some(primitive("str")),
+            "spawn" => Some(primitive("thread")),
+            // A float between 0 and 1 without a range, and an int within it with one
+            "rand" if args.is_empty() => Some(primitive("float")),
+            "rand" => Some(primitive("int")),
+            "id" | "ptradd" => args.first().and_then(|a| types.get(a)).cloned(),
+            "phi" => args.iter().find_map(|a| types.get(a)).cloned(),
+            "load" => args.first().and_then(|a| types.get(a)).and_then(pointee),
+            "call" => funcs
+                .first()
+                .and_then(|f| signatures.get(f))
+                .and_then(|(_, ret)| ret.clone()),
+            _ => None,
+        },
+        AbstractInstruction::Effect { .. } => None,
+    }
+}
+
+// The types which ```instr``` expects its arguments to have, as far as they are known
+fn arg_types<'a>(
+    instr: &'a AbstractInstruction,
+    types: &HashMap<String, AbstractType>,
+    signatures: &HashMap<String, Signature>,
+    return_type: Option<&AbstractType>,
+) -> Vec<(&'a String, AbstractType)> {
+    let (args, funcs, op, dest) = match instr {
+        AbstractInstruction::Constant { .. } => return Vec::new(),
+        AbstractInstruction::Value {
+            args,
+            funcs,
+            op,
+            dest,
+            ..
+        } => (args, funcs, op.as_str(), types.get(dest)),
+        AbstractInstruction::Effect {
+            args, funcs, op, ..
+        } => (args, funcs, op.as_str(), None),
+    };
+    let arg_type = |i: usize| args.get(i).and_then(|a| types.get(a));
+
+    let expected: Vec<Option<AbstractType>> = match op {
+        "call" | "spawn" => funcs
+            .first()
+            .and_then(|f| signatures.get(f))
+            .map(|(params, _)| params.iter().cloned().map(Some).collect())
+            .unwrap_or_default(),
+        "id" | "phi" => vec![dest.cloned(); args.len()],
+        "ptradd" => vec![dest.cloned(), Some(primitive("int"))],
+        "load" => vec![dest.cloned().map(pointer_to)],
+        "alloc" => vec![Some(primitive("int"))],
+        "index" => vec![Some(primitive("str")), Some(primitive("int"))],
+        "store" => vec![
+            arg_type(1).cloned().map(pointer_to),
+            arg_type(0).and_then(pointee),
+        ],
+        "br" | "guard" => vec![Some(primitive("bool"))],
+        "join" => vec![Some(primitive("thread"))],
+        "ret" => vec![return_type.cloned()],
+        _ => vec![operand_type(op).map(primitive); args.len()],
+    };
+    args.iter()
+        .zip(expected)
+        .filter_map(|(arg, ty)| Some((arg, ty?)))
+        .collect()
+}
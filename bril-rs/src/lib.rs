@@ -2,33 +2,89 @@
 #![warn(missing_docs)]
 #![doc = include_str!("../README.md")]
 #![allow(clippy::too_many_lines)]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 /// Provides the unstructured representation of Bril programs
 pub mod abstract_program;
+/// Provides [`alias::PointsTo`], which finds the memory each pointer of a [Program] can point into and which pointers may alias
+#[cfg(all(feature = "memory", feature = "std"))]
+pub mod alias;
+/// Provides [`binary::to_bytes`] and [`binary::from_bytes`] for a compact binary encoding of [Program] which is much faster to load than JSON
+#[cfg(feature = "std")]
+pub mod binary;
+/// Provides a builder API for constructing a [Program] programmatically
+#[cfg(feature = "std")]
+pub mod builder;
+/// Provides the call graph of a [Program] for interprocedural analyses
+#[cfg(feature = "std")]
+pub mod callgraph;
+/// Provides the control flow graph representation of a [Function]
+#[cfg(feature = "std")]
+pub mod cfg;
 /// Provides the Error handling and conversion between [`AbstractProgram`] and [Program]
 pub mod conversion;
+/// Provides a generic dataflow solver over a [`cfg::Cfg`] along with some common analyses
+#[cfg(feature = "std")]
+pub mod dataflow;
+/// Provides the dominator tree of a [`cfg::Cfg`] for passes which need to know which blocks always run before others
+#[cfg(feature = "std")]
+pub mod dominance;
+/// Provides [`infer::infer_types`] for filling in the types an [`AbstractProgram`] leaves out
+#[cfg(feature = "std")]
+pub mod infer;
+/// Provides [`link::Linker`] for bringing the functions a [Program] imports from other files into it
+#[cfg(feature = "import")]
+pub mod link;
+/// Provides the natural loops of a [`cfg::Cfg`] and the preheaders that loop optimizations move code into
+#[cfg(feature = "std")]
+pub mod loops;
+/// Provides the conversion of an [`AbstractProgram`] back into Bril text
+#[cfg(feature = "std")]
+pub mod output;
 /// Provides the structured representation of Bril programs
 pub mod program;
+/// Provides the live ranges of the variables of a [`cfg::Cfg`], the register pressure of each block, and a coloring of them into registers
+#[cfg(feature = "std")]
+pub mod registers;
+/// Provides conversion into and out of the [SSA](https://capra.cs.cornell.edu/bril/lang/ssa.html) form of a [Program]
+#[cfg(all(feature = "ssa", feature = "std"))]
+pub mod ssa;
+/// Provides function-at-a-time loading of Bril programs which are too large to read into memory at once
+#[cfg(feature = "std")]
+pub mod stream;
+/// Provides [`structured::StructuredCfg`], the `if`s, loops, and blocks that the control flow of a [`cfg::Cfg`] can be made into for backends without `goto`
+#[cfg(feature = "std")]
+pub mod structured;
+/// Provides [`version::SchemaVersion`] along with [`version::upgrade`] and [`version::downgrade`], which move programs between the versions of Bril JSON that tools have written over time
+#[cfg(feature = "std")]
+pub mod version;
 // todo maybe not reexport the program structs? I don't know the most rust way to provide these especially since abstract_program relies on Literal in program
 pub use abstract_program::*;
 pub use program::*;
 
+#[cfg(feature = "std")]
 use std::io::{self, Write};
 
 // todo Have versions of the output_* functions that take a [std::io::Write]
 // todo possible deprecate/remove the wrapper functions to make the code base cleaner
 // todo Wrap the outputs of serde in an error instead of panic-ing
 
-/// A helper function for parsing a Bril program from ```input``` in JSON format to [Program]
+/// A helper function for parsing a Bril program from ```input``` in JSON format to [Program]. Programs in older versions of Bril JSON are migrated with [`version::upgrade`]
 /// # Panics
-/// Will panic if the input JSON is not well-formed bril JSON
+/// Will panic if the input JSON is not well-formed bril JSON in a supported version
+#[cfg(feature = "std")]
 pub fn load_program_from_read<R: std::io::Read>(mut input: R) -> Program {
     let mut buffer = String::new();
     input.read_to_string(&mut buffer).unwrap();
-    serde_json::from_str(&buffer).unwrap()
+    version::from_str(&buffer).unwrap()
 }
 
 /// A wrapper of [`load_program_from_read`] which assumes [`std::io::Stdin`]
+#[cfg(feature = "std")]
 #[must_use]
 pub fn load_program() -> Program {
     load_program_from_read(std::io::stdin())
@@ -37,21 +93,24 @@ pub fn load_program() -> Program {
 /// Outputs a [Program] to [`std::io::Stdout`]
 /// # Panics
 /// This can panic, though I'm not sure when since serialization should always succeed
+#[cfg(feature = "std")]
 pub fn output_program(p: &Program) {
     serde_json::to_writer_pretty(io::stdout(), p).unwrap();
     io::stdout().write_all(b"\n").unwrap();
 }
 
-/// A helper function for parsing a Bril program from ```input``` in JSON format to [`AbstractProgram`]
+/// A helper function for parsing a Bril program from ```input``` in JSON format to [`AbstractProgram`]. Programs in older versions of Bril JSON are migrated with [`version::upgrade`]
 /// # Panics
-/// Will panic if the input JSON is not well-formed bril JSON
+/// Will panic if the input JSON is not well-formed bril JSON in a supported version
+#[cfg(feature = "std")]
 pub fn load_abstract_program_from_read<R: std::io::Read>(mut input: R) -> AbstractProgram {
     let mut buffer = String::new();
     input.read_to_string(&mut buffer).unwrap();
-    serde_json::from_str(&buffer).unwrap()
+    version::from_str(&buffer).unwrap()
 }
 
 /// A wrapper of [`load_abstract_program_from_read`] which assumes [`std::io::Stdin`]
+#[cfg(feature = "std")]
 #[must_use]
 pub fn load_abstract_program() -> AbstractProgram {
     load_abstract_program_from_read(std::io::stdin())
@@ -60,6 +119,7 @@ pub fn load_abstract_program() -> AbstractProgram {
 /// Outputs an [`AbstractProgram`] to [`std::io::Stdout`]
 /// # Panics
 /// This can panic, though I'm not sure when since serialization should always succeed
+#[cfg(feature = "std")]
 pub fn output_abstract_program(p: &AbstractProgram) {
     serde_json::to_writer_pretty(io::stdout(), p).unwrap();
     io::stdout().write_all(b"\n").unwrap();
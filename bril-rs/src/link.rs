@@ -0,0 +1,244 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::{Code, Function, Import, Instruction, Program};
+
+/// The errors from linking the imports of a [`Program`] with a [`Linker`]
+#[derive(Error, Debug)]
+pub enum LinkError<E> {
+    /// None of the places searched for an imported file have it
+    #[error("could not find {0} next to the importing file or in any library path")]
+    NotFound(PathBuf),
+    /// The loader given to the [`Linker`] could not load a file
+    #[error("could not load {0}: {1}")]
+    Load(PathBuf, E),
+    /// A file imports a function which it doesn't define
+    #[error("{path} does not define @{func}")]
+    UndefinedImport {
+        /// The file being imported from
+        path: PathBuf,
+        /// The name of the function being imported
+        func: String,
+    },
+    /// A file imports a function under a name which it already defines or imports
+    #[error("@{name} is imported from {path} but that name is already taken")]
+    ConflictingImport {
+        /// The file being imported from
+        path: PathBuf,
+        /// The name given to the imported function
+        name: String,
+    },
+}
+
+/// Finds, loads, and links in the files imported by a [`Program`] so that it can be run on its own, like `brild`.
+///
+/// The path of each import is looked up next to the file doing the importing, and then in each of the library paths in order. Every file is only loaded once however many times it is imported. The functions imported by the original program take on the names it gives them, and every other function linked in is renamed if needed so that it doesn't clash with anything else, with calls between them renamed to match. The functions of the original program are never renamed.
+pub struct Linker<F> {
+    lib_paths: Vec<PathBuf>,
+    load: F,
+}
+
+// A file which has been loaded from ```path```
+struct Module {
+    path: PathBuf,
+    functions: Vec<Function>,
+    imports: Vec<Import>,
+    // The module and function index of every imported function, along with the name it is imported as
+    resolved: Vec<(usize, usize, String)>,
+}
+
+impl<E, F: FnMut(&Path) -> Result<Program, E>> Linker<F> {
+    /// Searches ```lib_paths``` for imported files, using ```load``` to read each of them into a [`Program`]
+    pub const fn new(lib_paths: Vec<PathBuf>, load: F) -> Self {
+        Self { lib_paths, load }
+    }
+
+    /// Moves every function that ```prog``` imports, directly or through other files, into ```prog```, leaving it without any imports. Relative import paths of ```prog``` start from ```dir```.
+    /// # Errors
+    /// Will return an error if an imported file can't be found or loaded, or an import names a function that doesn't exist or clashes with another name
+    pub fn link(&mut self, prog: &mut Program, dir: &Path) -> Result<(), LinkError<E>> {
+        let imports = std::mem::take(&mut prog.imports);
+        let defined = prog.functions.iter().map(|f| f.name.clone()).collect();
+        let functions = self.link_imports(&imports, dir, &defined)?;
+        prog.functions.extend(functions);
+        Ok(())
+    }
+
+    /// Finds the functions needed for the ```imports``` of a program in ```dir``` whose functions are named ```defined```, without needing the rest of the program. This is for programs which are never held in memory all at once, like those read with [`crate::stream`].
+    /// # Errors
+    /// Will return an error if an imported file can't be found or loaded, or an import names a function that doesn't exist or clashes with another name
+    pub fn link_imports(
+        &mut self,
+        imports: &[Import],
+        dir: &Path,
+        defined: &HashSet<String>,
+    ) -> Result<Vec<Function>, LinkError<E>> {
+        let mut modules = Vec::new();
+        let mut index = HashMap::new();
+        let root = self.resolve(imports, dir, &mut modules, &mut index)?;
+        // Resolving the imports of a module can load more modules, which are added to the end
+        let mut next = 0;
+        while next < modules.len() {
+            let imports = std::mem::take(&mut modules[next].imports);
+            let dir = modules[next].path.parent().unwrap_or(dir).to_path_buf();
+            let resolved = self.resolve(&imports, &dir, &mut modules, &mut index)?;
+            let mut taken: HashSet<&str> = modules[next]
+                .functions
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect();
+            for (m, _, name) in &resolved {
+                if !taken.insert(name) {
+                    return Err(LinkError::ConflictingImport {
+                        path: modules[*m].path.clone(),
+                        name: name.clone(),
+                    });
+                }
+            }
+            modules[next].resolved = resolved;
+            next += 1;
+        }
+
+        // The program's own imports are named first so that its calls don't need renaming
+        let mut used = defined.clone();
+        let mut names: HashMap<(usize, usize), String> = HashMap::new();
+        let mut copies = Vec::new();
+        for (m, f, name) in root {
+            if !used.insert(name.clone()) {
+                return Err(LinkError::ConflictingImport {
+                    path: modules[m].path.clone(),
+                    name,
+                });
+            }
+            match names.entry((m, f)) {
+                // A function imported under more than one name has a copy for each of them
+                Entry::Occupied(_) => copies.push((m, f, name)),
+                Entry::Vacant(entry) => {
+                    entry.insert(name);
+                }
+            }
+        }
+        for (m, module) in modules.iter().enumerate() {
+            for (f, func) in module.functions.iter().enumerate() {
+                names
+                    .entry((m, f))
+                    .or_insert_with(|| fresh_name(&mut used, &func.name));
+            }
+        }
+
+        let mut linked = Vec::new();
+        // Where the functions of each module start in ```linked```
+        let mut offsets = Vec::with_capacity(modules.len());
+        for (m, module) in modules.iter().enumerate() {
+            offsets.push(linked.len());
+            // What each name called in this module refers to after linking
+            let scope: HashMap<&str, &str> = module
+                .functions
+                .iter()
+                .enumerate()
+                .map(|(f, func)| (func.name.as_str(), names[&(m, f)].as_str()))
+                .chain(
+                    module
+                        .resolved
+                        .iter()
+                        .map(|(m2, f, name)| (name.as_str(), names[&(*m2, *f)].as_str())),
+                )
+                .collect();
+            for (f, func) in module.functions.iter().enumerate() {
+                linked.push(rename(func, &names[&(m, f)], &scope));
+            }
+        }
+        for (m, f, name) in copies {
+            let original = &linked[offsets[m] + f];
+            linked.push(Function {
+                name,
+                ..original.clone()
+            });
+        }
+        Ok(linked)
+    }
+
+    // Loads the files of ```imports``` from a file in ```dir``` which haven't been loaded yet and finds each function they import
+    fn resolve(
+        &mut self,
+        imports: &[Import],
+        dir: &Path,
+        modules: &mut Vec<Module>,
+        index: &mut HashMap<PathBuf, usize>,
+    ) -> Result<Vec<(usize, usize, String)>, LinkError<E>> {
+        let mut resolved = Vec::new();
+        for import in imports {
+            let (path, canonical) = self.find(&import.path, dir)?;
+            let m = if let Some(&m) = index.get(&canonical) {
+                m
+            } else {
+                let prog = (self.load)(&path).map_err(|e| LinkError::Load(path.clone(), e))?;
+                index.insert(canonical, modules.len());
+                modules.push(Module {
+                    path,
+                    functions: prog.functions,
+                    imports: prog.imports,
+                    resolved: Vec::new(),
+                });
+                modules.len() - 1
+            };
+            for func in &import.functions {
+                let f = modules[m]
+                    .functions
+                    .iter()
+                    .rposition(|f| f.name == func.name)
+                    .ok_or_else(|| LinkError::UndefinedImport {
+                        path: modules[m].path.clone(),
+                        func: func.name.clone(),
+                    })?;
+                let name = func.alias.as_ref().unwrap_or(&func.name).clone();
+                resolved.push((m, f, name));
+            }
+        }
+        Ok(resolved)
+    }
+
+    // The first place that has ```path```, along with its canonical path
+    fn find(&self, path: &Path, dir: &Path) -> Result<(PathBuf, PathBuf), LinkError<E>> {
+        std::iter::once(dir)
+            .chain(self.lib_paths.iter().map(PathBuf::as_path))
+            .map(|d| d.join(path))
+            .find(|p| p.is_file())
+            .and_then(|p| p.canonicalize().ok().map(|c| (p, c)))
+            .ok_or_else(|| LinkError::NotFound(path.to_path_buf()))
+    }
+}
+
+// ```name``` if it hasn't been used yet, or otherwise the first of `name.1`, `name.2`, ... that hasn't
+fn fresh_name(used: &mut HashSet<String>, name: &str) -> String {
+    let mut fresh = name.to_string();
+    let mut i = 0;
+    while used.contains(&fresh) {
+        i += 1;
+        fresh = format!("{name}.{i}");
+    }
+    used.insert(fresh.clone());
+    fresh
+}
+
+// A copy of ```func``` called ```name``` that calls each function by what it is called in ```scope```
+fn rename(func: &Function, name: &str, scope: &HashMap<&str, &str>) -> Function {
+    let mut func = func.clone();
+    func.name = name.to_string();
+    for code in &mut func.instrs {
+        if let Code::Instruction(
+            Instruction::Value { funcs, .. } | Instruction::Effect { funcs, .. },
+        ) = code
+        {
+            for f in funcs {
+                if let Some(linked) = scope.get(f.as_str()) {
+                    *f = (*linked).to_string();
+                }
+            }
+        }
+    }
+    func
+}
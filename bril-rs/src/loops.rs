@@ -0,0 +1,113 @@
+use std::collections::BTreeSet;
+
+use crate::cfg::{jump, BasicBlock, Cfg};
+use crate::dominance::Dominators;
+use crate::Instruction;
+
+/// A natural loop of a [`Cfg`], which is a header along with the blocks that can reach a back edge to it without going through it. A back edge goes from a block to one which dominates it, so the header dominates every block of its loop and every way into the loop goes through the header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NaturalLoop {
+    /// The block at the top of the loop
+    pub header: usize,
+    /// Every block of the loop, including the header
+    pub blocks: BTreeSet<usize>,
+    /// The blocks with a back edge to the header
+    pub latches: Vec<usize>,
+}
+
+impl NaturalLoop {
+    /// Whether ```block``` is part of the loop
+    #[must_use]
+    pub fn contains(&self, block: usize) -> bool {
+        self.blocks.contains(&block)
+    }
+
+    /// Every edge out of the loop, as the block in the loop that it leaves from and the block outside of the loop that it goes to
+    #[must_use]
+    pub fn exits(&self, cfg: &Cfg) -> Vec<(usize, usize)> {
+        self.blocks
+            .iter()
+            .flat_map(|&from| {
+                cfg.successors[from]
+                    .iter()
+                    .filter(|to| !self.contains(**to))
+                    .map(move |&to| (from, to))
+            })
+            .collect()
+    }
+}
+
+/// Finds the natural loops of ```cfg``` given its dominators. All of the back edges to the same header make up a single loop. The loops are ordered from smallest to largest, so every loop comes before the loops it is nested in.
+#[must_use]
+pub fn natural_loops(cfg: &Cfg, doms: &Dominators) -> Vec<NaturalLoop> {
+    let mut loops = Vec::new();
+    for header in 0..cfg.blocks.len() {
+        let latches: Vec<usize> = cfg.predecessors[header]
+            .iter()
+            .copied()
+            .filter(|p| doms.dominates(header, *p))
+            .collect();
+        if latches.is_empty() {
+            continue;
+        }
+        // Walk backwards from the latches until reaching the header. Unreachable blocks can jump into a loop without being part of it
+        let mut blocks = BTreeSet::from([header]);
+        let mut work_list = latches.clone();
+        while let Some(b) = work_list.pop() {
+            if blocks.insert(b) {
+                work_list.extend(
+                    cfg.predecessors[b]
+                        .iter()
+                        .filter(|p| doms.is_reachable(**p)),
+                );
+            }
+        }
+        loops.push(NaturalLoop {
+            header,
+            blocks,
+            latches,
+        });
+    }
+    // A loop nested inside of another is always smaller than it
+    loops.sort_by_key(|l| l.blocks.len());
+    loops
+}
+
+/// Gives ```natural_loop``` a preheader, which is a new block right before its header that every edge into the loop from outside of it goes through instead.
+///
+/// Code that only needs to run once before the loop can then be moved into the preheader. Returns the index of the preheader, which is where the header used to be. The blocks from the header on move along by one, so ```natural_loop``` and the [`Dominators`] of ```cfg``` are out of date afterwards.
+/// # Panics
+/// Will panic if ```natural_loop``` is not a loop of ```cfg```
+pub fn insert_preheader(cfg: &mut Cfg, natural_loop: &NaturalLoop) -> usize {
+    let header = natural_loop.header;
+    let header_label = cfg.blocks[header].label.clone();
+    let label = cfg.fresh_label(&format!("{header_label}.preheader"));
+    for &pred in &cfg.predecessors[header] {
+        if natural_loop.contains(pred) {
+            continue;
+        }
+        if let Some(Instruction::Effect { labels, .. }) = cfg.blocks[pred].instrs.last_mut() {
+            for l in labels.iter_mut().filter(|l| **l == header_label) {
+                l.clone_from(&label);
+            }
+        }
+    }
+    // The preheader stands for the start of the loop, wherever the header is in the source
+    #[cfg(feature = "position")]
+    let pos = cfg.blocks[header].pos.map(crate::Position::derived);
+    #[cfg_attr(not(feature = "position"), allow(unused_mut))]
+    let mut term = jump(header_label);
+    #[cfg(feature = "position")]
+    term.set_pos(pos);
+    cfg.insert_block(
+        header,
+        BasicBlock {
+            label,
+            #[cfg(feature = "position")]
+            pos,
+            instrs: vec![term],
+        },
+    )
+    .expect("the preheader only jumps to the header");
+    header
+}
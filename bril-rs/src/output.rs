@@ -0,0 +1,128 @@
+use std::io::{self, Write};
+
+use crate::AbstractProgram;
+#[cfg(feature = "position")]
+use crate::{AbstractCode, AbstractFunction, AbstractInstruction, Position, SourcePosition};
+
+/// Writes ```prog``` to ```out``` as canonical Bril text. This is the same text as the [`std::fmt::Display`] implementation of [`AbstractProgram`], which parses back into the same program.
+/// # Errors
+/// Will return an error if writing to ```out``` fails
+pub fn write_text<W: Write>(mut out: W, prog: &AbstractProgram) -> io::Result<()> {
+    write!(out, "{prog}")
+}
+
+/// Writes ```prog``` to ```out``` as Bril text with every function, label, and instruction placed at the row and column of its source position.
+///
+/// Parsing the text with positions enabled gives back the same program, positions included. Anything without a position, or whose position has already been passed, starts a new line with the canonical indentation instead. A line with things on it that came from a frontend's source ends with a `#@ file:row:col` comment giving their [`Position::source`], and something from elsewhere in the frontend's source starts a new line so that it can have its own.
+/// # Errors
+/// Will return an error if writing to ```out``` fails
+#[cfg(feature = "position")]
+pub fn write_text_with_positions<W: Write>(mut out: W, prog: &AbstractProgram) -> io::Result<()> {
+    let mut layout = Layout {
+        text: String::new(),
+        row: 1,
+        col: 1,
+        row_source: None,
+    };
+    // Imports don't have positions so they always come first
+    #[cfg(feature = "import")]
+    for import in &prog.imports {
+        layout.move_to(None, 0);
+        layout.push(&import.to_string());
+    }
+    for (i, func) in prog.functions.iter().enumerate() {
+        let next_pos = prog.functions.get(i + 1).and_then(|f| f.pos);
+        layout.function(func, next_pos);
+    }
+    layout.newline();
+    out.write_all(layout.text.as_bytes())
+}
+
+// A buffer of Bril text which keeps track of where the next character will be placed
+#[cfg(feature = "position")]
+struct Layout {
+    text: String,
+    row: u64,
+    col: u64,
+    // The source position of everything on the current row, which ends it in a comment
+    row_source: Option<SourcePosition>,
+}
+
+#[cfg(feature = "position")]
+impl Layout {
+    // ```s``` should not contain a newline
+    fn push(&mut self, s: &str) {
+        self.text.push_str(s);
+        self.col += s.len() as u64;
+    }
+
+    fn pad(&mut self, spaces: u64) {
+        for _ in 0..spaces {
+            self.text.push(' ');
+        }
+        self.col += spaces;
+    }
+
+    fn newline(&mut self) {
+        if let Some(source) = self.row_source.take() {
+            self.push(&format!(" #@ {source}"));
+        }
+        self.text.push('\n');
+        self.row += 1;
+        self.col = 1;
+    }
+
+    fn move_to(&mut self, pos: Option<Position>, indent: u64) {
+        // bril2json gives everything on the first line a row of 0
+        // Only things from the same place in the frontend's source can share a row, unless it is empty so far
+        let source = pos.and_then(|p| p.source);
+        let fits = self.col == 1 || self.row_source == source;
+        match pos.map(|p| (p.row.max(1), p.col)) {
+            Some((row, col)) if row > self.row || (row == self.row && col >= self.col && fits) => {
+                while self.row < row {
+                    self.newline();
+                }
+                self.pad(col.saturating_sub(self.col));
+            }
+            _ => {
+                if !self.text.is_empty() {
+                    self.newline();
+                }
+                self.pad(indent);
+            }
+        }
+        self.row_source = source;
+    }
+
+    fn function(&mut self, func: &AbstractFunction, next_pos: Option<Position>) {
+        let header = AbstractFunction {
+            instrs: Vec::new(),
+            ..func.clone()
+        }
+        .to_string();
+        // The header is displayed as "@name ... {\n}"
+        self.move_to(func.pos, 0);
+        self.push(header.lines().next().unwrap_or_default());
+
+        for code in &func.instrs {
+            match code {
+                AbstractCode::Label { pos, .. } => self.move_to(*pos, 0),
+                AbstractCode::Instruction(
+                    AbstractInstruction::Constant { pos, .. }
+                    | AbstractInstruction::Value { pos, .. }
+                    | AbstractInstruction::Effect { pos, .. },
+                ) => self.move_to(*pos, 2),
+            }
+            self.push(code.to_string().trim_start());
+        }
+
+        // Keep the closing brace out of the way of the next function
+        match next_pos {
+            Some(p) if p.row.max(1) <= self.row + 1 => self.push(" }"),
+            _ => {
+                self.newline();
+                self.push("}");
+            }
+        }
+    }
+}
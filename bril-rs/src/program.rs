@@ -1,16 +1,40 @@
-use std::fmt::{self, Display, Formatter};
+#[cfg(feature = "memory")]
+use alloc::boxed::Box;
+use alloc::string::String;
+#[cfg(feature = "strings")]
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+#[cfg(feature = "import")]
+use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 
+/// Metadata which passes and tools attach to a [`Program`], [`Function`], or [`Instruction`] by name, like an `inline_hint` on a function or the `loop_depth` of an instruction, instead of keeping it in a table on the side. In JSON these are extra fields of the object they belong to, so any field which isn't part of Bril is kept here when a program is read and written back out as it was
+#[cfg(feature = "attrs")]
+pub type Attrs = serde_json::Map<String, serde_json::Value>;
+
 /// Equivalent to a file of bril code
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Program {
     /// A list of functions declared in the program
     pub functions: Vec<Function>,
+    /// The functions this program uses from other files
+    #[cfg(feature = "import")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub imports: Vec<Import>,
+    /// Metadata about the whole program, which is any other fields it has in JSON
+    #[cfg(feature = "attrs")]
+    #[serde(flatten)]
+    pub attrs: Attrs,
 }
 
 impl Display for Program {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "import")]
+        for import in &self.imports {
+            writeln!(f, "{import}")?;
+        }
         for func in &self.functions {
             writeln!(f, "{func}")?;
         }
@@ -18,6 +42,52 @@ impl Display for Program {
     }
 }
 
+/// The functions that a program imports from another file, written as `from "path.bril" import @f, @g as @h;`
+#[cfg(feature = "import")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    /// The functions to import
+    pub functions: Vec<ImportedFunction>,
+    /// The file to import from, relative to the directory of the importing file or a library search path
+    pub path: PathBuf,
+}
+
+#[cfg(feature = "import")]
+impl Display for Import {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "from \"{}\" import ", self.path.display())?;
+        for (i, func) in self.functions.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{func}")?;
+        }
+        write!(f, ";")
+    }
+}
+
+/// One of the functions of an [`Import`]
+#[cfg(feature = "import")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ImportedFunction {
+    /// The name to call the function by in the importing program instead of ```name```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// The name of the function in the file it is imported from
+    pub name: String,
+}
+
+#[cfg(feature = "import")]
+impl Display for ImportedFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}", self.name)?;
+        if let Some(alias) = &self.alias {
+            write!(f, " as @{alias}")?;
+        }
+        Ok(())
+    }
+}
+
 /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#function>
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Function {
@@ -37,6 +107,10 @@ pub struct Function {
     #[serde(rename = "type")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub return_type: Option<Type>,
+    /// Metadata about this function, which is any other fields it has in JSON
+    #[cfg(feature = "attrs")]
+    #[serde(flatten)]
+    pub attrs: Attrs,
 }
 
 impl Display for Function {
@@ -67,7 +141,7 @@ impl Display for Function {
 /// An argument of a function
 /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#function>
 /// Example: a : int
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Argument {
     /// a
     pub name: String,
@@ -103,12 +177,12 @@ pub enum Code {
 impl Display for Code {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Code::Label {
+            Self::Label {
                 label,
                 #[cfg(feature = "position")]
                     pos: _,
             } => write!(f, ".{label}:"),
-            Code::Instruction(instr) => write!(f, "  {instr}"),
+            Self::Instruction(instr) => write!(f, "  {instr}"),
         }
     }
 }
@@ -132,6 +206,10 @@ pub enum Instruction {
         const_type: Type,
         /// The literal being stored in the variable
         value: Literal,
+        /// Metadata about this instruction, which is any other fields it has in JSON
+        #[cfg(feature = "attrs")]
+        #[serde(flatten)]
+        attrs: Attrs,
     },
     /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#value-operation>
     Value {
@@ -155,6 +233,14 @@ pub enum Instruction {
         /// Type of variable
         #[serde(rename = "type")]
         op_type: Type,
+        /// The variables after ```dest``` which a `call` of a function returning more than one value assigns to, in the order the values are returned
+        #[cfg(feature = "tuples")]
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        extra_dests: Vec<Argument>,
+        /// Metadata about this instruction, which is any other fields it has in JSON
+        #[cfg(feature = "attrs")]
+        #[serde(flatten)]
+        attrs: Attrs,
     },
     /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#effect-operation>
     Effect {
@@ -173,6 +259,10 @@ pub enum Instruction {
         #[cfg(feature = "position")]
         #[serde(skip_serializing_if = "Option::is_none")]
         pos: Option<Position>,
+        /// Metadata about this instruction, which is any other fields it has in JSON
+        #[cfg(feature = "attrs")]
+        #[serde(flatten)]
+        attrs: Attrs,
     },
 }
 
@@ -182,9 +272,40 @@ impl Instruction {
     #[must_use]
     pub const fn get_pos(&self) -> Option<Position> {
         match self {
-            Instruction::Constant { pos, .. }
-            | Instruction::Value { pos, .. }
-            | Instruction::Effect { pos, .. } => *pos,
+            Self::Constant { pos, .. } | Self::Value { pos, .. } | Self::Effect { pos, .. } => *pos,
+        }
+    }
+
+    /// Gives the instruction the position ```pos```
+    pub const fn set_pos(&mut self, pos: Option<Position>) {
+        match self {
+            Self::Constant { pos: p, .. }
+            | Self::Value { pos: p, .. }
+            | Self::Effect { pos: p, .. } => {
+                *p = pos;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "attrs")]
+impl Instruction {
+    /// The metadata attached to the instruction
+    #[must_use]
+    pub const fn attrs(&self) -> &Attrs {
+        match self {
+            Self::Constant { attrs, .. }
+            | Self::Value { attrs, .. }
+            | Self::Effect { attrs, .. } => attrs,
+        }
+    }
+
+    /// The metadata attached to the instruction, to add to or change. A pass which replaces an instruction with another can move these over with [`core::mem::take`] to keep them
+    pub const fn attrs_mut(&mut self) -> &mut Attrs {
+        match self {
+            Self::Constant { attrs, .. }
+            | Self::Value { attrs, .. }
+            | Self::Effect { attrs, .. } => attrs,
         }
     }
 }
@@ -192,17 +313,20 @@ impl Instruction {
 impl Display for Instruction {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Instruction::Constant {
+            Self::Constant {
                 op,
                 dest,
                 const_type,
                 value,
                 #[cfg(feature = "position")]
                     pos: _,
+                #[cfg(feature = "attrs")]
+                    attrs: _,
             } => {
+                let value = value.clone().for_type(const_type);
                 write!(f, "{dest}: {const_type} = {op} {value};")
             }
-            Instruction::Value {
+            Self::Value {
                 op,
                 dest,
                 op_type,
@@ -211,8 +335,17 @@ impl Display for Instruction {
                 labels,
                 #[cfg(feature = "position")]
                     pos: _,
+                #[cfg(feature = "tuples")]
+                extra_dests,
+                #[cfg(feature = "attrs")]
+                    attrs: _,
             } => {
-                write!(f, "{dest}: {op_type} = {op}")?;
+                write!(f, "{dest}: {op_type}")?;
+                #[cfg(feature = "tuples")]
+                for extra in extra_dests {
+                    write!(f, ", {extra}")?;
+                }
+                write!(f, " = {op}")?;
                 for func in funcs {
                     write!(f, " @{func}")?;
                 }
@@ -224,13 +357,15 @@ impl Display for Instruction {
                 }
                 write!(f, ";")
             }
-            Instruction::Effect {
+            Self::Effect {
                 op,
                 args,
                 funcs,
                 labels,
                 #[cfg(feature = "position")]
                     pos: _,
+                #[cfg(feature = "attrs")]
+                    attrs: _,
             } => {
                 write!(f, "{op}")?;
                 for func in funcs {
@@ -259,7 +394,7 @@ pub enum ConstOps {
 impl Display for ConstOps {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            ConstOps::Const => write!(f, "const"),
+            Self::Const => write!(f, "const"),
         }
     }
 }
@@ -298,27 +433,32 @@ pub enum EffectOps {
     /// <https://capra.cs.cornell.edu/bril/lang/spec.html#operations>
     #[cfg(feature = "speculate")]
     Guard,
+    /// <https://capra.cs.cornell.edu/bril/lang/concurrency.html#operations>
+    #[cfg(feature = "concurrency")]
+    Join,
 }
 
 impl Display for EffectOps {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            EffectOps::Jump => write!(f, "jmp"),
-            EffectOps::Branch => write!(f, "br"),
-            EffectOps::Call => write!(f, "call"),
-            EffectOps::Return => write!(f, "ret"),
-            EffectOps::Print => write!(f, "print"),
-            EffectOps::Nop => write!(f, "nop"),
+            Self::Jump => write!(f, "jmp"),
+            Self::Branch => write!(f, "br"),
+            Self::Call => write!(f, "call"),
+            Self::Return => write!(f, "ret"),
+            Self::Print => write!(f, "print"),
+            Self::Nop => write!(f, "nop"),
             #[cfg(feature = "memory")]
-            EffectOps::Store => write!(f, "store"),
+            Self::Store => write!(f, "store"),
             #[cfg(feature = "memory")]
-            EffectOps::Free => write!(f, "free"),
+            Self::Free => write!(f, "free"),
             #[cfg(feature = "speculate")]
-            EffectOps::Speculate => write!(f, "speculate"),
+            Self::Speculate => write!(f, "speculate"),
             #[cfg(feature = "speculate")]
-            EffectOps::Commit => write!(f, "commit"),
+            Self::Commit => write!(f, "commit"),
             #[cfg(feature = "speculate")]
-            EffectOps::Guard => write!(f, "guard"),
+            Self::Guard => write!(f, "guard"),
+            #[cfg(feature = "concurrency")]
+            Self::Join => write!(f, "join"),
         }
     }
 }
@@ -335,6 +475,8 @@ pub enum ValueOps {
     Mul,
     /// <https://capra.cs.cornell.edu/bril/lang/core.html#arithmetic>
     Div,
+    /// <https://capra.cs.cornell.edu/bril/lang/core.html#arithmetic>
+    Rem,
     /// <https://capra.cs.cornell.edu/bril/lang/core.html#comparison>
     Eq,
     /// <https://capra.cs.cornell.edu/bril/lang/core.html#comparison>
@@ -385,6 +527,12 @@ pub enum ValueOps {
     /// <https://capra.cs.cornell.edu/bril/lang/float.html#operations>
     #[cfg(feature = "float")]
     Fge,
+    /// <https://capra.cs.cornell.edu/bril/lang/float.html#operations>
+    #[cfg(feature = "float")]
+    Float2bits,
+    /// <https://capra.cs.cornell.edu/bril/lang/float.html#operations>
+    #[cfg(feature = "float")]
+    Bits2float,
     /// <https://capra.cs.cornell.edu/bril/lang/memory.html#operations>
     #[cfg(feature = "memory")]
     Alloc,
@@ -394,51 +542,116 @@ pub enum ValueOps {
     /// <https://capra.cs.cornell.edu/bril/lang/memory.html#operations>
     #[cfg(feature = "memory")]
     PtrAdd,
+    /// <https://capra.cs.cornell.edu/bril/lang/char.html#operations>
+    #[cfg(feature = "char")]
+    Ceq,
+    /// <https://capra.cs.cornell.edu/bril/lang/char.html#operations>
+    #[cfg(feature = "char")]
+    Clt,
+    /// <https://capra.cs.cornell.edu/bril/lang/char.html#operations>
+    #[cfg(feature = "char")]
+    Cle,
+    /// <https://capra.cs.cornell.edu/bril/lang/char.html#operations>
+    #[cfg(feature = "char")]
+    Cgt,
+    /// <https://capra.cs.cornell.edu/bril/lang/char.html#operations>
+    #[cfg(feature = "char")]
+    Cge,
+    /// <https://capra.cs.cornell.edu/bril/lang/char.html#operations>
+    #[cfg(feature = "char")]
+    Char2int,
+    /// <https://capra.cs.cornell.edu/bril/lang/char.html#operations>
+    #[cfg(feature = "char")]
+    Int2char,
+    /// <https://capra.cs.cornell.edu/bril/lang/string.html#operations>
+    #[cfg(feature = "strings")]
+    Concat,
+    /// <https://capra.cs.cornell.edu/bril/lang/string.html#operations>
+    #[cfg(feature = "strings")]
+    Length,
+    /// <https://capra.cs.cornell.edu/bril/lang/string.html#operations>
+    #[cfg(feature = "strings")]
+    Index,
+    /// <https://capra.cs.cornell.edu/bril/lang/concurrency.html#operations>
+    #[cfg(feature = "concurrency")]
+    Spawn,
+    /// <https://capra.cs.cornell.edu/bril/lang/rand.html#operations>
+    #[cfg(feature = "rand")]
+    Rand,
 }
 
 impl Display for ValueOps {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            ValueOps::Add => write!(f, "add"),
-            ValueOps::Sub => write!(f, "sub"),
-            ValueOps::Mul => write!(f, "mul"),
-            ValueOps::Div => write!(f, "div"),
-            ValueOps::Eq => write!(f, "eq"),
-            ValueOps::Lt => write!(f, "lt"),
-            ValueOps::Gt => write!(f, "gt"),
-            ValueOps::Le => write!(f, "le"),
-            ValueOps::Ge => write!(f, "ge"),
-            ValueOps::Not => write!(f, "not"),
-            ValueOps::And => write!(f, "and"),
-            ValueOps::Or => write!(f, "or"),
-            ValueOps::Call => write!(f, "call"),
-            ValueOps::Id => write!(f, "id"),
+            Self::Add => write!(f, "add"),
+            Self::Sub => write!(f, "sub"),
+            Self::Mul => write!(f, "mul"),
+            Self::Div => write!(f, "div"),
+            Self::Rem => write!(f, "rem"),
+            Self::Eq => write!(f, "eq"),
+            Self::Lt => write!(f, "lt"),
+            Self::Gt => write!(f, "gt"),
+            Self::Le => write!(f, "le"),
+            Self::Ge => write!(f, "ge"),
+            Self::Not => write!(f, "not"),
+            Self::And => write!(f, "and"),
+            Self::Or => write!(f, "or"),
+            Self::Call => write!(f, "call"),
+            Self::Id => write!(f, "id"),
             #[cfg(feature = "ssa")]
-            ValueOps::Phi => write!(f, "phi"),
+            Self::Phi => write!(f, "phi"),
+            #[cfg(feature = "float")]
+            Self::Fadd => write!(f, "fadd"),
+            #[cfg(feature = "float")]
+            Self::Fsub => write!(f, "fsub"),
             #[cfg(feature = "float")]
-            ValueOps::Fadd => write!(f, "fadd"),
+            Self::Fmul => write!(f, "fmul"),
             #[cfg(feature = "float")]
-            ValueOps::Fsub => write!(f, "fsub"),
+            Self::Fdiv => write!(f, "fdiv"),
             #[cfg(feature = "float")]
-            ValueOps::Fmul => write!(f, "fmul"),
+            Self::Feq => write!(f, "feq"),
             #[cfg(feature = "float")]
-            ValueOps::Fdiv => write!(f, "fdiv"),
+            Self::Flt => write!(f, "flt"),
             #[cfg(feature = "float")]
-            ValueOps::Feq => write!(f, "feq"),
+            Self::Fgt => write!(f, "fgt"),
             #[cfg(feature = "float")]
-            ValueOps::Flt => write!(f, "flt"),
+            Self::Fle => write!(f, "fle"),
             #[cfg(feature = "float")]
-            ValueOps::Fgt => write!(f, "fgt"),
+            Self::Fge => write!(f, "fge"),
             #[cfg(feature = "float")]
-            ValueOps::Fle => write!(f, "fle"),
+            Self::Float2bits => write!(f, "float2bits"),
             #[cfg(feature = "float")]
-            ValueOps::Fge => write!(f, "fge"),
+            Self::Bits2float => write!(f, "bits2float"),
             #[cfg(feature = "memory")]
-            ValueOps::Alloc => write!(f, "alloc"),
+            Self::Alloc => write!(f, "alloc"),
             #[cfg(feature = "memory")]
-            ValueOps::Load => write!(f, "load"),
+            Self::Load => write!(f, "load"),
             #[cfg(feature = "memory")]
-            ValueOps::PtrAdd => write!(f, "ptradd"),
+            Self::PtrAdd => write!(f, "ptradd"),
+            #[cfg(feature = "char")]
+            Self::Ceq => write!(f, "ceq"),
+            #[cfg(feature = "char")]
+            Self::Clt => write!(f, "clt"),
+            #[cfg(feature = "char")]
+            Self::Cle => write!(f, "cle"),
+            #[cfg(feature = "char")]
+            Self::Cgt => write!(f, "cgt"),
+            #[cfg(feature = "char")]
+            Self::Cge => write!(f, "cge"),
+            #[cfg(feature = "char")]
+            Self::Char2int => write!(f, "char2int"),
+            #[cfg(feature = "char")]
+            Self::Int2char => write!(f, "int2char"),
+            #[cfg(feature = "strings")]
+            Self::Concat => write!(f, "concat"),
+            #[cfg(feature = "strings")]
+            Self::Length => write!(f, "length"),
+            #[cfg(feature = "strings")]
+            Self::Index => write!(f, "index"),
+            #[cfg(feature = "concurrency")]
+            Self::Spawn => write!(f, "spawn"),
+            #[cfg(feature = "rand")]
+            Self::Rand => write!(f, "rand"),
         }
     }
 }
@@ -458,23 +671,54 @@ pub enum Type {
     #[cfg(feature = "memory")]
     #[serde(rename = "ptr")]
     Pointer(Box<Self>),
+    /// <https://capra.cs.cornell.edu/bril/lang/char.html#types>
+    #[cfg(feature = "char")]
+    Char,
+    /// <https://capra.cs.cornell.edu/bril/lang/string.html#types>
+    #[cfg(feature = "strings")]
+    Str,
+    /// <https://capra.cs.cornell.edu/bril/lang/concurrency.html#types>
+    #[cfg(feature = "concurrency")]
+    Thread,
+    /// The values returned together by a function with more than one return type, which isn't the type of any variable
+    #[cfg(feature = "tuples")]
+    Tuple(Vec<Self>),
 }
 
 impl Display for Type {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Type::Int => write!(f, "int"),
-            Type::Bool => write!(f, "bool"),
+            Self::Int => write!(f, "int"),
+            Self::Bool => write!(f, "bool"),
             #[cfg(feature = "float")]
-            Type::Float => write!(f, "float"),
+            Self::Float => write!(f, "float"),
             #[cfg(feature = "memory")]
-            Type::Pointer(tpe) => write!(f, "ptr<{tpe}>"),
+            Self::Pointer(tpe) => write!(f, "ptr<{tpe}>"),
+            #[cfg(feature = "char")]
+            Self::Char => write!(f, "char"),
+            #[cfg(feature = "strings")]
+            Self::Str => write!(f, "str"),
+            #[cfg(feature = "concurrency")]
+            Self::Thread => write!(f, "thread"),
+            // As they are written in the signature of the function returning them
+            #[cfg(feature = "tuples")]
+            Self::Tuple(types) => {
+                for (i, tpe) in types.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{tpe}")?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
-/// A JSON number/value
+/// A JSON number/value, or a string. A string holding a single character is read as a [`Literal::Char`], which [`Literal::for_type`] turns back into a [`Literal::Str`] for a `str` constant
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+// Floats can't be compared with Eq, so it is only missing when the float feature is off
+#[cfg_attr(not(feature = "float"), allow(clippy::derive_partial_eq_without_eq))]
 #[serde(untagged)]
 pub enum Literal {
     /// Integers
@@ -484,15 +728,40 @@ pub enum Literal {
     /// Floating Points
     #[cfg(feature = "float")]
     Float(f64),
+    /// Characters
+    #[cfg(feature = "char")]
+    Char(char),
+    /// Strings
+    #[cfg(feature = "strings")]
+    Str(String),
 }
 
 impl Display for Literal {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Literal::Int(i) => write!(f, "{i}"),
-            Literal::Bool(b) => write!(f, "{b}"),
+            Self::Int(i) => write!(f, "{i}"),
+            Self::Bool(b) => write!(f, "{b}"),
+            // Debug gives the shortest text which parses back into the same float, always with a decimal point or exponent, and switches to scientific notation for very large or small magnitudes
             #[cfg(feature = "float")]
-            Literal::Float(x) => write!(f, "{x}"),
+            Self::Float(x) => write!(f, "{x:?}"),
+            #[cfg(feature = "char")]
+            Self::Char(c) => write!(f, "'{c}'"),
+            #[cfg(feature = "strings")]
+            Self::Str(s) => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\t' => write!(f, "\\t")?,
+                        '\0' => write!(f, "\\0")?,
+                        c => write!(f, "{c}")?,
+                    }
+                }
+                write!(f, "\"")
+            }
         }
     }
 }
@@ -502,19 +771,149 @@ impl Literal {
     #[must_use]
     pub const fn get_type(&self) -> Type {
         match self {
-            Literal::Int(_) => Type::Int,
-            Literal::Bool(_) => Type::Bool,
+            Self::Int(_) => Type::Int,
+            Self::Bool(_) => Type::Bool,
             #[cfg(feature = "float")]
-            Literal::Float(_) => Type::Float,
+            Self::Float(_) => Type::Float,
+            #[cfg(feature = "char")]
+            Self::Char(_) => Type::Char,
+            #[cfg(feature = "strings")]
+            Self::Str(_) => Type::Str,
+        }
+    }
+
+    /// The literal as a constant of type ```const_type```. JSON can't tell a `str` of one character from a `char`, so that is the only literal which changes, and anything else is returned as it is
+    #[must_use]
+    // This can only be const when the strings feature, which makes a `String`, is disabled
+    #[cfg_attr(not(feature = "strings"), allow(clippy::missing_const_for_fn))]
+    pub fn for_type(self, const_type: &Type) -> Self {
+        match (const_type, self) {
+            #[cfg(feature = "strings")]
+            (Type::Str, Self::Char(c)) => Self::Str(c.to_string()),
+            (_, literal) => literal,
         }
     }
 }
 
 /// <https://capra.cs.cornell.edu/bril/lang/syntax.html#source-positions>
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Position {
     /// Column
     pub col: u64,
     /// Row
     pub row: u64,
+    /// Whether this is the position of the code that a transformation added an instruction or label for, rather than of something which was in the source. It is only written to JSON when it is set
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    pub derived: bool,
+    /// Where the frontend which generated this code had it in its own source, if it said. Bril text keeps this in a `#@ file:row:col` comment at the end of the line
+    #[cfg(feature = "std")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<SourcePosition>,
+}
+
+impl Position {
+    /// The position of something a transformation adds for the code at ```self```, like the jump made explicit at the end of a block or the copies an inlined call is replaced with, so that errors in it still point somewhere meaningful
+    #[must_use]
+    pub const fn derived(self) -> Self {
+        Self {
+            derived: true,
+            ..self
+        }
+    }
+}
+
+/// Where something in a Bril program came from in the source of the frontend which generated it, like the line of TypeScript that `ts2bril` compiled an instruction from
+#[cfg(feature = "std")]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourcePosition {
+    /// Column
+    pub col: u64,
+    /// The file, as the frontend named it
+    pub file: SourceFile,
+    /// Row
+    pub row: u64,
+}
+
+/// Written as `file:row:col`, which is how it appears in Bril text
+#[cfg(feature = "std")]
+impl Display for SourcePosition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.row, self.col)
+    }
+}
+
+/// Reads `file:row:col`, where the file name may contain colons of its own
+#[cfg(feature = "std")]
+impl core::str::FromStr for SourcePosition {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.rsplitn(3, ':');
+        let col = parts.next().and_then(|col| col.parse().ok());
+        let row = parts.next().and_then(|row| row.parse().ok());
+        match (parts.next(), row, col) {
+            (Some(file), Some(row), Some(col)) if !file.is_empty() => Ok(Self {
+                file: SourceFile::new(file),
+                row,
+                col,
+            }),
+            _ => Err("expected a source position like file:row:col"),
+        }
+    }
+}
+
+/// The name of the file of a [`SourcePosition`]. Names are interned so that positions can stay [`Copy`], which means each distinct name is kept until the program exits
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+// A reference to a `String` rather than a `str` is half the size, which keeps every instruction smaller
+pub struct SourceFile(&'static String);
+
+#[cfg(feature = "std")]
+impl SourceFile {
+    /// The interned file called ```name```
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        use std::collections::HashMap;
+        use std::sync::{Mutex, OnceLock, PoisonError};
+
+        static NAMES: OnceLock<Mutex<HashMap<&'static str, SourceFile>>> = OnceLock::new();
+        let mut names = NAMES
+            .get_or_init(Mutex::default)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(file) = names.get(name) {
+            return *file;
+        }
+        let interned: &'static String = Box::leak(Box::new(String::from(name)));
+        names.insert(interned, Self(interned));
+        drop(names);
+        Self(interned)
+    }
+
+    /// The name of the file
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl Display for SourceFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Serialize for SourceFile {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de> Deserialize<'de> for SourceFile {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|name| Self::new(&name))
+    }
 }
@@ -0,0 +1,184 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::cfg::Cfg;
+use crate::dataflow::{def, solve, uses, LiveVariables};
+use crate::{Argument, Instruction, ValueOps};
+
+/// The live ranges of the variables of a [`Cfg`], which are the points where each variable holds a value that may still be read, along with how many of them are live at once.
+///
+/// Two variables interfere when one of them is written while the other is live, so they can't be kept in the same register. The exception is the variable an `id` copies, which holds the same value as its destination afterwards. Arguments are written on entry to the function. `phi` nodes read their arguments where they are like any other instruction, which makes those arguments live for longer than they need to be but never shorter.
+/// ```
+/// use bril_rs::cfg::Cfg;
+/// use bril_rs::registers::LiveRanges;
+///
+/// let json = r#"{"functions": [{"name": "main", "instrs": [
+///     {"op": "const", "dest": "a", "type": "int", "value": 1},
+///     {"op": "const", "dest": "b", "type": "int", "value": 2},
+///     {"op": "add", "dest": "c", "type": "int", "args": ["a", "b"]},
+///     {"op": "id", "dest": "d", "type": "int", "args": ["c"]},
+///     {"op": "print", "args": ["c", "d"]}
+/// ]}]}"#;
+/// let prog: bril_rs::Program = serde_json::from_str(json).unwrap();
+/// let ranges = LiveRanges::new(&Cfg::from_function(&prog.functions[0]).unwrap(), &[]);
+/// assert_eq!(ranges.pressure, vec![2]);
+/// assert!(ranges.interferes("a", "b"));
+/// // `d` is a copy of `c`, so they can share a register
+/// assert!(!ranges.interferes("c", "d"));
+/// assert_eq!(ranges.registers(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveRanges {
+    /// The variables live at the start of each block, in the order of [`Cfg::blocks`]
+    pub live_in: Vec<BTreeSet<String>>,
+    /// The variables live at the end of each block
+    pub live_out: Vec<BTreeSet<String>>,
+    /// The most variables live at once in each block, counting a variable as live right after it is written even if it is never read
+    pub pressure: Vec<usize>,
+    /// The blocks which each variable is live somewhere in
+    pub blocks: BTreeMap<String, BTreeSet<usize>>,
+    /// The variables which each variable interferes with. Every variable of the function is a key, even if it interferes with nothing
+    pub interference: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl LiveRanges {
+    /// Computes the live ranges of the variables of ```cfg```, which takes ```args```
+    #[must_use]
+    pub fn new(cfg: &Cfg, args: &[Argument]) -> Self {
+        let live = solve(&LiveVariables, cfg);
+        let mut ranges = Self {
+            pressure: vec![0; cfg.blocks.len()],
+            blocks: BTreeMap::new(),
+            interference: BTreeMap::new(),
+            live_in: live.inputs,
+            live_out: live.outputs,
+        };
+        for arg in args {
+            ranges.interference.entry(arg.name.clone()).or_default();
+        }
+        for (index, block) in cfg.blocks.iter().enumerate() {
+            let mut live = ranges.live_out[index].clone();
+            let mut pressure = live.len();
+            ranges.note_live(index, &live);
+            for instr in block.instrs.iter().rev() {
+                if let Some(dest) = def(instr) {
+                    ranges.interference.entry(dest.clone()).or_default();
+                    let copied = copied(instr);
+                    for other in &live {
+                        if other != dest && Some(other) != copied {
+                            ranges.interfere(dest, other);
+                        }
+                    }
+                    pressure = pressure.max(live.len() + usize::from(!live.contains(dest)));
+                    live.remove(dest);
+                }
+                live.extend(uses(instr).iter().cloned());
+                pressure = pressure.max(live.len());
+                ranges.note_live(index, &live);
+            }
+            ranges.pressure[index] = pressure;
+        }
+        // The arguments are all written on entry, before the first block runs
+        if let Some(entry) = ranges.live_in.first().cloned() {
+            for arg in args {
+                for other in &entry {
+                    if *other != arg.name {
+                        ranges.interfere(&arg.name, other);
+                    }
+                }
+            }
+            let written: BTreeSet<&String> =
+                entry.iter().chain(args.iter().map(|a| &a.name)).collect();
+            ranges.pressure[0] = ranges.pressure[0].max(written.len());
+        }
+        ranges
+    }
+
+    fn note_live(&mut self, block: usize, live: &BTreeSet<String>) {
+        for var in live {
+            self.blocks.entry(var.clone()).or_default().insert(block);
+        }
+    }
+
+    fn interfere(&mut self, a: &str, b: &str) {
+        self.interference
+            .entry(a.to_string())
+            .or_default()
+            .insert(b.to_string());
+        self.interference
+            .entry(b.to_string())
+            .or_default()
+            .insert(a.to_string());
+    }
+
+    /// Whether ```a``` and ```b``` interfere, so that they can't share a register
+    #[must_use]
+    pub fn interferes(&self, a: &str, b: &str) -> bool {
+        self.interference.get(a).is_some_and(|i| i.contains(b))
+    }
+
+    /// The highest pressure of any block, which is the fewest registers the function can be run in without spilling
+    #[must_use]
+    pub fn max_pressure(&self) -> usize {
+        self.pressure.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Colors the interference graph, giving each variable the number of a register so that variables which interfere are in different registers.
+    ///
+    /// The variables are taken out of the graph one at a time starting with the one with the fewest neighbours left, like in Chaitin's allocator, and then given the lowest number which none of their neighbours has in the opposite order. There is always a register for every variable, since nothing is spilled.
+    #[must_use]
+    pub fn color(&self) -> BTreeMap<String, usize> {
+        let mut degrees: BTreeMap<&str, usize> = self
+            .interference
+            .iter()
+            .map(|(var, others)| (var.as_str(), others.len()))
+            .collect();
+        let mut remaining: BTreeSet<(usize, &str)> = degrees
+            .iter()
+            .map(|(var, degree)| (*degree, *var))
+            .collect();
+        let mut order = Vec::with_capacity(remaining.len());
+        while let Some((_, var)) = remaining.pop_first() {
+            degrees.remove(var);
+            for other in &self.interference[var] {
+                if let Some(degree) = degrees.get_mut(other.as_str()) {
+                    remaining.remove(&(*degree, other.as_str()));
+                    *degree -= 1;
+                    remaining.insert((*degree, other.as_str()));
+                }
+            }
+            order.push(var);
+        }
+
+        let mut colors: BTreeMap<String, usize> = BTreeMap::new();
+        for var in order.into_iter().rev() {
+            let taken: BTreeSet<usize> = self.interference[var]
+                .iter()
+                .filter_map(|other| colors.get(other).copied())
+                .collect();
+            // One of the numbers up to how many neighbours it has is always free
+            let color = (0..=taken.len())
+                .find(|c| !taken.contains(c))
+                .unwrap_or_default();
+            colors.insert(var.to_string(), color);
+        }
+        colors
+    }
+
+    /// How many registers [`LiveRanges::color`] puts the variables in, which is at least [`LiveRanges::max_pressure`] and often the same
+    #[must_use]
+    pub fn registers(&self) -> usize {
+        self.color().values().map(|c| c + 1).max().unwrap_or(0)
+    }
+}
+
+// The variable which ```instr``` copies into its destination if it is an `id`
+fn copied(instr: &Instruction) -> Option<&String> {
+    match instr {
+        Instruction::Value {
+            op: ValueOps::Id,
+            args,
+            ..
+        } => args.first(),
+        _ => None,
+    }
+}
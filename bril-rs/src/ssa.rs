@@ -0,0 +1,427 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::cfg::{Cfg, CfgError};
+use crate::dataflow::{def, solve, LiveVariables, ReachingDefinitions};
+use crate::dominance::Dominators;
+use crate::{Code, Function, Instruction, Program, Type, ValueOps};
+
+#[cfg(feature = "attrs")]
+use crate::Attrs;
+
+/// The name given to a `phi` argument when the variable is not defined along the corresponding incoming edge
+pub const UNDEFINED: &str = "__undefined";
+
+// Every variable named in ```func```, as an argument, a destination, or an argument of an instruction
+fn variables(func: &Function) -> HashSet<String> {
+    let mut used: HashSet<String> = func.args.iter().map(|a| a.name.clone()).collect();
+    for c in &func.instrs {
+        match c {
+            Code::Label { .. } => {}
+            Code::Instruction(Instruction::Constant { dest, .. }) => {
+                used.insert(dest.clone());
+            }
+            Code::Instruction(Instruction::Value { dest, args, .. }) => {
+                used.insert(dest.clone());
+                used.extend(args.iter().cloned());
+            }
+            Code::Instruction(Instruction::Effect { args, .. }) => {
+                used.extend(args.iter().cloned());
+            }
+        }
+    }
+    used
+}
+
+// The first of `prefix.N`, counting up from ```counter```, which isn't in ```used```, which it is then added to
+fn fresh_variable(prefix: &str, counter: &mut usize, used: &mut HashSet<String>) -> String {
+    loop {
+        let candidate = format!("{prefix}.{counter}");
+        *counter += 1;
+        if used.insert(candidate.clone()) {
+            return candidate;
+        }
+    }
+}
+
+struct Renamer<'a> {
+    phis: &'a [BTreeMap<String, Type>],
+    doms: &'a Dominators,
+    stacks: HashMap<String, Vec<String>>,
+    counters: HashMap<String, usize>,
+    /// Every variable name in the function, including the fresh names given out so far, which no fresh name can be
+    used: HashSet<String>,
+    /// For each block and each variable needing a phi: the new destination name and the incoming (label, name) pairs
+    phi_dests: Vec<HashMap<String, String>>,
+    phi_args: Vec<HashMap<String, Vec<(String, String)>>>,
+}
+
+impl Renamer<'_> {
+    fn push_fresh(&mut self, var: &str, pushed: &mut Vec<String>) -> String {
+        let counter = self.counters.entry(var.to_string()).or_insert(0);
+        let fresh = fresh_variable(var, counter, &mut self.used);
+        self.stacks
+            .entry(var.to_string())
+            .or_default()
+            .push(fresh.clone());
+        pushed.push(var.to_string());
+        fresh
+    }
+
+    fn current(&self, var: &str) -> Option<&String> {
+        self.stacks.get(var).and_then(|s| s.last())
+    }
+
+    fn rename(&mut self, blocks: &mut Cfg, block: usize) {
+        let mut pushed = Vec::new();
+
+        let phi_vars: Vec<String> = self.phis[block].keys().cloned().collect();
+        for var in phi_vars {
+            let fresh = self.push_fresh(&var, &mut pushed);
+            self.phi_dests[block].insert(var, fresh);
+        }
+
+        let mut instrs = std::mem::take(&mut blocks.blocks[block].instrs);
+        for instr in &mut instrs {
+            match instr {
+                Instruction::Value { args, .. } | Instruction::Effect { args, .. } => {
+                    for arg in args.iter_mut() {
+                        if let Some(new) = self.current(arg) {
+                            *arg = new.clone();
+                        }
+                    }
+                }
+                Instruction::Constant { .. } => {}
+            }
+            match instr {
+                Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => {
+                    *dest = self.push_fresh(dest, &mut pushed);
+                }
+                Instruction::Effect { .. } => {}
+            }
+        }
+        blocks.blocks[block].instrs = instrs;
+
+        let label = blocks.blocks[block].label.clone();
+        for &s in &blocks.successors[block] {
+            for var in self.phis[s].keys() {
+                let arg = self
+                    .current(var)
+                    .cloned()
+                    .unwrap_or_else(|| UNDEFINED.to_string());
+                self.phi_args[s]
+                    .entry(var.clone())
+                    .or_default()
+                    .push((label.clone(), arg));
+            }
+        }
+
+        let doms = self.doms;
+        for &child in doms.children(block) {
+            self.rename(blocks, child);
+        }
+
+        for var in pushed {
+            self.stacks.get_mut(&var).unwrap().pop();
+        }
+    }
+}
+
+/// Converts a single function into SSA form.
+/// See [`to_ssa`]
+/// # Errors
+/// Will return [`CfgError::UnknownLabel`], leaving ```func``` as it was, if a `jmp` or `br` targets a label which does not exist
+pub fn function_to_ssa(func: &mut Function) -> Result<(), CfgError> {
+    if func.instrs.is_empty() {
+        return Ok(());
+    }
+    let used = variables(func);
+    let mut blocks = Cfg::from_function(func)?;
+    let doms = Dominators::new(&blocks);
+
+    // The type and defining blocks of every variable
+    let mut types: HashMap<String, Type> = func
+        .args
+        .iter()
+        .map(|a| (a.name.clone(), a.arg_type.clone()))
+        .collect();
+    let mut defs: BTreeMap<String, BTreeSet<usize>> = BTreeMap::new();
+    for (i, block) in blocks.blocks.iter().enumerate() {
+        for instr in &block.instrs {
+            match instr {
+                Instruction::Constant {
+                    dest, const_type, ..
+                } => {
+                    types.insert(dest.clone(), const_type.clone());
+                    defs.entry(dest.clone()).or_default().insert(i);
+                }
+                Instruction::Value { dest, op_type, .. } => {
+                    types.insert(dest.clone(), op_type.clone());
+                    defs.entry(dest.clone()).or_default().insert(i);
+                }
+                Instruction::Effect { .. } => {}
+            }
+        }
+    }
+
+    // Place phi nodes on the iterated dominance frontier of each variable's definitions, wherever the variable is still live. Anywhere else a phi would only merge values that are never read, which can be undefined along some of the paths into it
+    let live = solve(&LiveVariables, &blocks).inputs;
+    let mut phis: Vec<BTreeMap<String, Type>> = vec![BTreeMap::new(); blocks.blocks.len()];
+    for (var, def_blocks) in &defs {
+        let mut work_list: Vec<usize> = def_blocks.iter().copied().collect();
+        let mut has_def = def_blocks.clone();
+        while let Some(d) = work_list.pop() {
+            for &f in doms.frontier(d) {
+                if !phis[f].contains_key(var) && live[f].contains(var) {
+                    phis[f].insert(var.clone(), types[var].clone());
+                    if has_def.insert(f) {
+                        work_list.push(f);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut renamer = Renamer {
+        phis: &phis,
+        doms: &doms,
+        stacks: func
+            .args
+            .iter()
+            .map(|a| (a.name.clone(), vec![a.name.clone()]))
+            .collect(),
+        counters: HashMap::new(),
+        used,
+        phi_dests: vec![HashMap::new(); blocks.blocks.len()],
+        phi_args: vec![HashMap::new(); blocks.blocks.len()],
+    };
+    renamer.rename(&mut blocks, 0);
+    let Renamer {
+        mut phi_dests,
+        mut phi_args,
+        ..
+    } = renamer;
+
+    for (i, block) in blocks.blocks.iter_mut().enumerate() {
+        // The phi nodes come from where the paths into the block meet, which is its label
+        #[cfg(feature = "position")]
+        let pos = block.pos.map(crate::Position::derived);
+        // Unreachable blocks are never renamed so they do not get phi nodes
+        let new_phis = phis[i].iter().filter_map(|(var, ty)| {
+            let dest = phi_dests[i].remove(var)?;
+            let (labels, args) = phi_args[i]
+                .remove(var)
+                .unwrap_or_default()
+                .into_iter()
+                .unzip();
+            Some(Instruction::Value {
+                args,
+                dest,
+                funcs: Vec::new(),
+                labels,
+                op: ValueOps::Phi,
+                #[cfg(feature = "position")]
+                pos,
+                op_type: ty.clone(),
+                #[cfg(feature = "tuples")]
+                extra_dests: Vec::new(),
+                #[cfg(feature = "attrs")]
+                attrs: Attrs::new(),
+            })
+        });
+        block.instrs.splice(0..0, new_phis.collect::<Vec<_>>());
+    }
+
+    func.instrs = blocks.into_code();
+    Ok(())
+}
+
+/// Converts a single function out of SSA form.
+/// See [`from_ssa`]
+/// # Errors
+/// Will return [`CfgError::UnknownLabel`], leaving ```func``` as it was, if a `jmp` or `br` targets a label which does not exist
+pub fn function_from_ssa(func: &mut Function) -> Result<(), CfgError> {
+    if func.instrs.is_empty() {
+        return Ok(());
+    }
+    let mut used = variables(func);
+    let mut blocks = Cfg::from_function(func)?;
+
+    // The copies each predecessor makes for the phi nodes of the blocks it goes to
+    let mut copies: BTreeMap<usize, Vec<Instruction>> = BTreeMap::new();
+    let labels: HashMap<String, usize> = blocks
+        .blocks
+        .iter()
+        .enumerate()
+        .map(|(i, b)| (b.label.clone(), i))
+        .collect();
+    for block in &mut blocks.blocks {
+        block.instrs.retain(|instr| match instr {
+            Instruction::Value {
+                op: ValueOps::Phi,
+                args,
+                dest,
+                labels: phi_labels,
+                op_type,
+                #[cfg(feature = "position")]
+                pos,
+                ..
+            } => {
+                for (label, arg) in phi_labels.iter().zip(args.iter()) {
+                    let Some(&pred) = labels.get(label) else {
+                        continue;
+                    };
+                    if arg == UNDEFINED {
+                        continue;
+                    }
+                    copies.entry(pred).or_default().push(id(
+                        dest.clone(),
+                        op_type.clone(),
+                        arg.clone(),
+                        #[cfg(feature = "position")]
+                        pos.map(crate::Position::derived),
+                    ));
+                }
+                false
+            }
+            _ => true,
+        });
+    }
+
+    // A copy out of a variable which isn't defined by the end of its block would read it undefined, which is only allowed as the argument of a phi. Such copies are left out. A copy only defines its destination if it is kept itself, so starting from none of them, copies are kept once what they read is defined, until no more are, which never lets a phi which only ever gets its own value keep itself defined
+    let mut kept: BTreeMap<usize, Vec<Instruction>> = BTreeMap::new();
+    loop {
+        let defined = defined_before_copies(&blocks, &kept, &func.args);
+        let next: BTreeMap<usize, Vec<Instruction>> = copies
+            .iter()
+            .map(|(&pred, copies)| {
+                let reachable = copies
+                    .iter()
+                    .filter(|copy| copy_source(copy).is_some_and(|arg| defined[pred].contains(arg)))
+                    .cloned()
+                    .collect();
+                (pred, reachable)
+            })
+            .collect();
+        if next == kept {
+            break;
+        }
+        kept = next;
+    }
+
+    // Insert the copies of each predecessor right before its terminator. They happen all at once, so a value which one of them overwrites is first copied into a temporary when another one reads it, like when two phi nodes swap their values around a loop
+    let mut counter = 0;
+    for (pred, mut copies) in kept {
+        let dests: HashSet<String> = copies.iter().filter_map(copy_dest).collect();
+        let mut temps = Vec::new();
+        for copy in &mut copies {
+            let Instruction::Value {
+                args,
+                dest,
+                op_type,
+                ..
+            } = copy
+            else {
+                unreachable!()
+            };
+            if args[0] != *dest && dests.contains(&args[0]) {
+                let temp = fresh_variable(&format!("{}.copy", args[0]), &mut counter, &mut used);
+                temps.push(id(
+                    temp.clone(),
+                    op_type.clone(),
+                    std::mem::replace(&mut args[0], temp),
+                    #[cfg(feature = "position")]
+                    copy.get_pos(),
+                ));
+            }
+        }
+        let instrs = &mut blocks.blocks[pred].instrs;
+        let term = instrs.len() - 1;
+        instrs.splice(term..term, temps.into_iter().chain(copies));
+    }
+
+    func.instrs = blocks.into_code();
+    Ok(())
+}
+
+// The variables which have a definition reaching the end of each block of ```blocks```, before the copies made there for the phi nodes of its successors. The ```copies``` are where the phis' destinations are defined, so they are counted for the blocks after them
+fn defined_before_copies(
+    blocks: &Cfg,
+    copies: &BTreeMap<usize, Vec<Instruction>>,
+    args: &[crate::Argument],
+) -> Vec<HashSet<String>> {
+    let mut with_copies = blocks.clone();
+    for (&pred, copies) in copies {
+        with_copies.blocks[pred]
+            .instrs
+            .extend(copies.iter().cloned());
+    }
+    let inputs = solve(&ReachingDefinitions::new(args), &with_copies).inputs;
+    inputs
+        .into_iter()
+        .zip(&blocks.blocks)
+        .map(|(reaching, block)| {
+            reaching
+                .into_iter()
+                .map(|d| d.var)
+                .chain(block.instrs.iter().filter_map(def).cloned())
+                .collect()
+        })
+        .collect()
+}
+
+// The variable an `id` copy made by [`function_from_ssa`] reads
+fn copy_source(copy: &Instruction) -> Option<&String> {
+    match copy {
+        Instruction::Value { args, .. } => args.first(),
+        Instruction::Constant { .. } | Instruction::Effect { .. } => None,
+    }
+}
+
+// The variable an `id` copy made by [`function_from_ssa`] assigns
+fn copy_dest(copy: &Instruction) -> Option<String> {
+    match copy {
+        Instruction::Value { dest, .. } => Some(dest.clone()),
+        Instruction::Constant { .. } | Instruction::Effect { .. } => None,
+    }
+}
+
+// `dest: op_type = id arg`
+fn id(
+    dest: String,
+    op_type: Type,
+    arg: String,
+    #[cfg(feature = "position")] pos: Option<crate::Position>,
+) -> Instruction {
+    Instruction::Value {
+        args: vec![arg],
+        dest,
+        funcs: Vec::new(),
+        labels: Vec::new(),
+        op: ValueOps::Id,
+        #[cfg(feature = "position")]
+        pos,
+        op_type,
+        #[cfg(feature = "tuples")]
+        extra_dests: Vec::new(),
+        #[cfg(feature = "attrs")]
+        attrs: Attrs::new(),
+    }
+}
+
+/// Converts every function of `prog` into SSA form using the `phi` instruction of the [SSA extension](https://capra.cs.cornell.edu/bril/lang/ssa.html).
+///
+/// Phi nodes are placed on the iterated dominance frontier of each variable's definitions, wherever the variable is live, and every definition gets a fresh name of the form `var.N`, skipping any such name the function already uses. When a variable is not defined along an incoming edge, the corresponding `phi` argument is [`UNDEFINED`]. As part of this process, every basic block is given a label and every fallthrough is made into an explicit `jmp`.
+/// # Errors
+/// Will return [`CfgError::UnknownLabel`] if a `jmp` or `br` targets a label which does not exist, leaving the function it is in as it was
+pub fn to_ssa(prog: &mut Program) -> Result<(), CfgError> {
+    prog.functions.iter_mut().try_for_each(function_to_ssa)
+}
+
+/// Converts every function of `prog` out of SSA form by replacing each `phi` instruction with `id` copies at the end of its predecessor blocks.
+///
+/// Arguments that are [`UNDEFINED`] do not produce a copy, and neither do arguments without a definition reaching the end of their predecessor. The copies at the end of a block act as if they all happened at once, using fresh temporaries where one of them would otherwise overwrite a value that another still has to read.
+/// # Errors
+/// Will return [`CfgError::UnknownLabel`] if a `jmp` or `br` targets a label which does not exist, leaving the function it is in as it was
+pub fn from_ssa(prog: &mut Program) -> Result<(), CfgError> {
+    prog.functions.iter_mut().try_for_each(function_from_ssa)
+}
@@ -0,0 +1,204 @@
+use std::fmt;
+use std::io::{BufReader, Read};
+
+use serde::de::{
+    self, Deserialize, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor,
+};
+use thiserror::Error;
+
+use crate::conversion::PositionalConversionError;
+use crate::version::{self, SchemaVersion};
+#[cfg(feature = "import")]
+use crate::Import;
+use crate::{AbstractFunction, Function};
+
+/// The errors from streaming in a program with [`for_each_abstract_function`] or [`for_each_function`]
+#[derive(Error, Debug)]
+pub enum StreamError<E> {
+    /// The input is not well-formed Bril JSON
+    #[error(transparent)]
+    Json(serde_json::Error),
+    /// The callback given a function returned an error, which stopped the stream
+    #[error("{0}")]
+    Function(E),
+}
+
+/// Reads a Bril program in JSON format from ```input``` one function at a time, calling ```f``` with each function as soon as it has been read.
+///
+/// Unlike [`crate::load_abstract_program_from_read`], neither the JSON text nor the whole program is ever held in memory, so this can load programs which are larger than the memory available as long as ```f``` doesn't keep every function around. Functions in older versions of Bril JSON are migrated the same way as [`version::upgrade`] does, going by the `version` field of the program when it comes before them and by what each function looks like when it doesn't. The first error returned by ```f``` stops the stream and is returned as [`StreamError::Function`].
+/// # Errors
+/// Will return an error if the input is not well-formed Bril JSON or ```f``` returns an error
+pub fn for_each_abstract_function<R, E, F>(input: R, f: F) -> Result<(), StreamError<E>>
+where
+    R: Read,
+    F: FnMut(AbstractFunction) -> Result<(), E>,
+{
+    stream(input, f, &mut Imports::default())
+}
+
+/// The same as [`for_each_abstract_function`] but with each function converted into a [`Function`] before being given to ```f```.
+///
+/// A function which fails to convert stops the stream with its [`PositionalConversionError`] converted into ```E```.
+/// # Errors
+/// Will return an error if the input is not well-formed Bril JSON, a function can not be converted, or ```f``` returns an error
+pub fn for_each_function<R, E, F>(input: R, mut f: F) -> Result<(), StreamError<E>>
+where
+    R: Read,
+    E: From<PositionalConversionError>,
+    F: FnMut(Function) -> Result<(), E>,
+{
+    for_each_abstract_function(input, |func| f(func.try_into()?))
+}
+
+/// The same as [`for_each_abstract_function`] but also returns the imports of the program once all of it has been read, which can come before or after its functions.
+/// # Errors
+/// Will return an error if the input is not well-formed Bril JSON or ```f``` returns an error
+#[cfg(feature = "import")]
+pub fn for_each_abstract_function_with_imports<R, E, F>(
+    input: R,
+    f: F,
+) -> Result<Vec<Import>, StreamError<E>>
+where
+    R: Read,
+    F: FnMut(AbstractFunction) -> Result<(), E>,
+{
+    let mut imports = Vec::new();
+    stream(input, f, &mut imports)?;
+    Ok(imports)
+}
+
+/// The same as [`for_each_function`] but also returns the imports of the program once all of it has been read, which can come before or after its functions.
+/// # Errors
+/// Will return an error if the input is not well-formed Bril JSON, a function can not be converted, or ```f``` returns an error
+#[cfg(feature = "import")]
+pub fn for_each_function_with_imports<R, E, F>(
+    input: R,
+    mut f: F,
+) -> Result<Vec<Import>, StreamError<E>>
+where
+    R: Read,
+    E: From<PositionalConversionError>,
+    F: FnMut(Function) -> Result<(), E>,
+{
+    for_each_abstract_function_with_imports(input, |func| f(func.try_into()?))
+}
+
+// Where the imports of the program are put, which are skipped over without the import feature
+#[cfg(feature = "import")]
+type Imports = Vec<Import>;
+#[cfg(not(feature = "import"))]
+type Imports = ();
+
+fn stream<R, E, F>(input: R, mut f: F, imports: &mut Imports) -> Result<(), StreamError<E>>
+where
+    R: Read,
+    F: FnMut(AbstractFunction) -> Result<(), E>,
+{
+    let mut error = None;
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(input));
+    let result = deserializer
+        .deserialize_map(ProgramVisitor {
+            f: &mut f,
+            error: &mut error,
+            imports,
+        })
+        .and_then(|()| deserializer.end());
+    match (result, error) {
+        (_, Some(e)) => Err(StreamError::Function(e)),
+        (Err(e), None) => Err(StreamError::Json(e)),
+        (Ok(()), None) => Ok(()),
+    }
+}
+
+// Looks for the "version", "functions", and "imports" fields of the program, skipping over anything else
+struct ProgramVisitor<'a, F, E> {
+    f: &'a mut F,
+    error: &'a mut Option<E>,
+    #[cfg_attr(not(feature = "import"), allow(dead_code))]
+    imports: &'a mut Imports,
+}
+
+impl<'de, F, E> Visitor<'de> for ProgramVisitor<'_, F, E>
+where
+    F: FnMut(AbstractFunction) -> Result<(), E>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a Bril program")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut found = false;
+        let mut version = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "version" {
+                let v: String = map.next_value()?;
+                version = Some(v.parse::<SchemaVersion>().map_err(de::Error::custom)?);
+            } else if key == "functions" && !found {
+                found = true;
+                map.next_value_seed(FunctionsSeed {
+                    f: &mut *self.f,
+                    error: &mut *self.error,
+                    version,
+                })?;
+            } else if key == "functions" {
+                return Err(de::Error::duplicate_field("functions"));
+            } else if cfg!(feature = "import") && key == "imports" {
+                #[cfg(feature = "import")]
+                {
+                    *self.imports = map.next_value()?;
+                }
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        if found {
+            Ok(())
+        } else {
+            Err(de::Error::missing_field("functions"))
+        }
+    }
+}
+
+// Hands each element of the "functions" field to the callback as it is parsed
+struct FunctionsSeed<'a, F, E> {
+    f: &'a mut F,
+    error: &'a mut Option<E>,
+    version: Option<SchemaVersion>,
+}
+
+impl<'de, F, E> DeserializeSeed<'de> for FunctionsSeed<'_, F, E>
+where
+    F: FnMut(AbstractFunction) -> Result<(), E>,
+{
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, F, E> Visitor<'de> for FunctionsSeed<'_, F, E>
+where
+    F: FnMut(AbstractFunction) -> Result<(), E>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of Bril functions")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(mut func) = seq.next_element::<serde_json::Value>()? {
+            version::upgrade_function(&mut func, self.version).map_err(de::Error::custom)?;
+            let func = AbstractFunction::deserialize(func).map_err(de::Error::custom)?;
+            if let Err(e) = (self.f)(func) {
+                *self.error = Some(e);
+                // The message is never seen since the callback's error is returned instead
+                return Err(de::Error::custom("stopped by the callback"));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+use std::fmt::{self, Display, Formatter};
+
+use thiserror::Error;
+
+use crate::cfg::{is_terminator, Cfg};
+use crate::dominance::Dominators;
+use crate::{EffectOps, Instruction};
+
+/// The control flow of a [`Cfg`] as nested `if`/`else`, loops, and blocks that are broken out of.
+///
+/// Backends for targets without `goto`, like WebAssembly, need this instead of jumps between labels. This is the dominator tree based algorithm from "Beyond Relooper" by Norman Ramsey, which structures every reducible [`Cfg`] without copying any of its blocks. Every block which can be reached from the entry is in the result once, and blocks which can't are left out. The instructions of each block are kept apart from its terminator, which is made into the [`Statement`]s that do the same thing, so `phi` nodes and `guard`s are left in the code of their blocks like any other instruction.
+/// ```
+/// use bril_rs::cfg::Cfg;
+/// use bril_rs::structured::{Statement, StructuredCfg};
+///
+/// let json = r#"{"functions": [{"name": "main", "args": [{"name": "n", "type": "int"}], "instrs": [
+///     {"op": "const", "dest": "i", "type": "int", "value": 0},
+///     {"label": "loop"},
+///     {"op": "lt", "dest": "more", "type": "bool", "args": ["i", "n"]},
+///     {"op": "br", "args": ["more"], "labels": ["body", "done"]},
+///     {"label": "body"},
+///     {"op": "print", "args": ["i"]},
+///     {"op": "jmp", "labels": ["loop"]},
+///     {"label": "done"}
+/// ]}]}"#;
+/// let prog: bril_rs::Program = serde_json::from_str(json).unwrap();
+/// let structured = StructuredCfg::new(&Cfg::from_function(&prog.functions[0]).unwrap()).unwrap();
+/// assert!(matches!(&structured.body[1], Statement::Loop { label, .. } if label == "loop"));
+/// assert_eq!(
+///     structured.to_string(),
+///     "\
+/// .b0:
+///   i: int = const 0;
+/// loop .loop {
+///   .loop:
+///     more: bool = lt i n;
+///   if more {
+///     .body:
+///       print i;
+///     continue .loop;
+///   } else {
+///     ret;
+///   }
+/// }
+/// "
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructuredCfg {
+    /// What the function does, starting from its entry block
+    pub body: Vec<Statement>,
+}
+
+/// A statement of a [`StructuredCfg`].
+///
+/// A list of statements never runs off its end, since the last statement of every list is a [`Statement::Break`], [`Statement::Continue`], [`Statement::Return`], or a statement made up of lists which end in one. The only way out of a [`Statement::Block`] is a break naming it, and the only way out of a [`Statement::Loop`] is a break naming a block around it or a return.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// The instructions of the block labelled ```label``` other than its terminator, which only comes up for blocks that have any
+    Code {
+        /// The label of the block in the [`Cfg`]
+        label: String,
+        /// The instructions of the block, in order
+        instrs: Vec<Instruction>,
+    },
+    /// Runs ```then``` if ```cond``` is true and ```otherwise``` if it is false, which is what a `br` becomes
+    If {
+        /// The variable the `br` branches on
+        cond: String,
+        /// What happens when ```cond``` is true
+        then: Vec<Self>,
+        /// What happens when ```cond``` is false
+        otherwise: Vec<Self>,
+    },
+    /// Runs ```body``` over and over, where ```body``` starts with the block labelled ```label```, which is the header of the loop
+    Loop {
+        /// The label of the header, which a [`Statement::Continue`] names to go back to the top of the loop
+        label: String,
+        /// What is run each time around the loop
+        body: Vec<Self>,
+    },
+    /// Runs ```body```, which a [`Statement::Break`] naming ```label``` leaves to go on to whatever comes after the block. The statements right after it always start with the block labelled ```label```, which is reached from more than one place
+    Block {
+        /// The label of the block which comes after this one
+        label: String,
+        /// What is run inside of the block
+        body: Vec<Self>,
+    },
+    /// Leaves the [`Statement::Block`] with this label around this statement
+    Break(String),
+    /// Goes back to the top of the [`Statement::Loop`] with this label around this statement
+    Continue(String),
+    /// Returns from the function with the arguments of its `ret`
+    Return(Vec<String>),
+}
+
+/// Why a [`Cfg`] couldn't be made into a [`StructuredCfg`]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+pub enum StructureError {
+    /// The edge from .{from} to .{to} goes into a loop without going through its header, so the control flow is irreducible
+    #[error("the edge from .{from} to .{to} goes into a loop without going through its header, so the control flow is irreducible")]
+    Irreducible {
+        /// The label of the block the edge goes from
+        from: String,
+        /// The label of the block the edge goes to
+        to: String,
+    },
+}
+
+impl StructuredCfg {
+    /// Structures the control flow of ```cfg```, which has to be reducible, meaning that every loop can only be entered through its header. This is always true of code made from `if`s and `while`s, but jumps written by hand can still make loops with more than one entry.
+    /// # Errors
+    /// Will return the first edge found which goes back into a loop without it being to the header of that loop
+    pub fn new(cfg: &Cfg) -> Result<Self, StructureError> {
+        if cfg.blocks.is_empty() {
+            return Ok(Self { body: Vec::new() });
+        }
+        let doms = cfg.dominators();
+        let order = reverse_postorder(cfg);
+        // A block with more than one edge coming forwards into it needs a `Block` around the code which can go to it, and its header needs a `Loop` when it has any edges going back to it
+        let mut forward_edges = vec![0_usize; cfg.blocks.len()];
+        let mut headers = vec![false; cfg.blocks.len()];
+        for (from, succs) in cfg.successors.iter().enumerate() {
+            let Some(from_order) = order[from] else {
+                continue;
+            };
+            for &to in succs {
+                if order[to] > Some(from_order) {
+                    forward_edges[to] += 1;
+                } else if doms.dominates(to, from) {
+                    headers[to] = true;
+                } else {
+                    return Err(StructureError::Irreducible {
+                        from: cfg.blocks[from].label.clone(),
+                        to: cfg.blocks[to].label.clone(),
+                    });
+                }
+            }
+        }
+        let structurer = Structurer {
+            cfg,
+            doms: &doms,
+            order,
+            merges: forward_edges.iter().map(|n| *n > 1).collect(),
+            headers,
+        };
+        Ok(Self {
+            body: structurer.tree(0),
+        })
+    }
+}
+
+struct Structurer<'a> {
+    cfg: &'a Cfg,
+    doms: &'a Dominators,
+    // Where each block which can be reached comes in a reverse postorder
+    order: Vec<Option<usize>>,
+    merges: Vec<bool>,
+    headers: Vec<bool>,
+}
+
+impl Structurer<'_> {
+    // The code for ```block``` and every block it immediately dominates
+    fn tree(&self, block: usize) -> Vec<Statement> {
+        let mut merges: Vec<usize> = self
+            .doms
+            .children(block)
+            .iter()
+            .copied()
+            .filter(|b| self.merges[*b])
+            .collect();
+        // The merge block which comes last gets the outermost `Block`, since everything before it can go to it
+        merges.sort_by_key(|b| std::cmp::Reverse(self.order[*b]));
+        let body = self.within(block, &merges);
+        if self.headers[block] {
+            vec![Statement::Loop {
+                label: self.cfg.blocks[block].label.clone(),
+                body,
+            }]
+        } else {
+            body
+        }
+    }
+
+    // The code for ```block``` inside of a `Block` for each of ```merges```, each of which are followed by their own code
+    fn within(&self, block: usize, merges: &[usize]) -> Vec<Statement> {
+        if let Some((&merge, inner)) = merges.split_first() {
+            let mut code = vec![Statement::Block {
+                label: self.cfg.blocks[merge].label.clone(),
+                body: self.within(block, inner),
+            }];
+            code.extend(self.tree(merge));
+            return code;
+        }
+
+        let bb = &self.cfg.blocks[block];
+        let (instrs, terminator) = match bb.instrs.split_last() {
+            Some((last, rest)) if is_terminator(last) => (rest, Some(last)),
+            _ => (bb.instrs.as_slice(), None),
+        };
+        let mut code = Vec::new();
+        if !instrs.is_empty() {
+            code.push(Statement::Code {
+                label: bb.label.clone(),
+                instrs: instrs.to_vec(),
+            });
+        }
+        let succs = &self.cfg.successors[block];
+        match terminator {
+            Some(Instruction::Effect {
+                op: EffectOps::Return,
+                args,
+                ..
+            }) => code.push(Statement::Return(args.clone())),
+            Some(Instruction::Effect {
+                op: EffectOps::Branch,
+                args,
+                ..
+            }) => code.push(Statement::If {
+                cond: args[0].clone(),
+                then: self.branch(block, succs[0]),
+                otherwise: self.branch(block, succs[1]),
+            }),
+            // A `jmp`, or a block which falls through to the next one or off of the end of the function
+            _ => match succs.first() {
+                Some(&to) => code.extend(self.branch(block, to)),
+                None => code.push(Statement::Return(Vec::new())),
+            },
+        }
+        code
+    }
+
+    // The code for going from ```from``` to ```to```
+    fn branch(&self, from: usize, to: usize) -> Vec<Statement> {
+        let label = || self.cfg.blocks[to].label.clone();
+        if self.order[to] <= self.order[from] {
+            vec![Statement::Continue(label())]
+        } else if self.merges[to] {
+            vec![Statement::Break(label())]
+        } else {
+            // Nothing else goes to ```to```, so its code goes right here
+            self.tree(to)
+        }
+    }
+}
+
+// Where each block comes in a reverse postorder of the blocks which can be reached from the entry
+fn reverse_postorder(cfg: &Cfg) -> Vec<Option<usize>> {
+    let mut visited = vec![false; cfg.blocks.len()];
+    let mut postorder = Vec::with_capacity(cfg.blocks.len());
+    // Each block being visited along with how many of its successors have been
+    let mut stack = vec![(0, 0)];
+    visited[0] = true;
+    while let Some((block, next)) = stack.last_mut() {
+        if let Some(&succ) = cfg.successors[*block].get(*next) {
+            *next += 1;
+            if !visited[succ] {
+                visited[succ] = true;
+                stack.push((succ, 0));
+            }
+        } else {
+            postorder.push(*block);
+            stack.pop();
+        }
+    }
+    let mut order = vec![None; cfg.blocks.len()];
+    for (i, block) in postorder.into_iter().rev().enumerate() {
+        order[block] = Some(i);
+    }
+    order
+}
+
+impl Display for StructuredCfg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_statements(f, &self.body, 0)
+    }
+}
+
+fn write_statements(f: &mut Formatter<'_>, statements: &[Statement], depth: usize) -> fmt::Result {
+    let indent = "  ".repeat(depth);
+    for statement in statements {
+        match statement {
+            Statement::Code { label, instrs } => {
+                writeln!(f, "{indent}.{label}:")?;
+                for instr in instrs {
+                    writeln!(f, "{indent}  {instr}")?;
+                }
+            }
+            Statement::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                writeln!(f, "{indent}if {cond} {{")?;
+                write_statements(f, then, depth + 1)?;
+                writeln!(f, "{indent}}} else {{")?;
+                write_statements(f, otherwise, depth + 1)?;
+                writeln!(f, "{indent}}}")?;
+            }
+            Statement::Loop { label, body } => {
+                writeln!(f, "{indent}loop .{label} {{")?;
+                write_statements(f, body, depth + 1)?;
+                writeln!(f, "{indent}}}")?;
+            }
+            Statement::Block { label, body } => {
+                writeln!(f, "{indent}block .{label} {{")?;
+                write_statements(f, body, depth + 1)?;
+                writeln!(f, "{indent}}}")?;
+            }
+            Statement::Break(label) => writeln!(f, "{indent}break .{label};")?,
+            Statement::Continue(label) => writeln!(f, "{indent}continue .{label};")?,
+            Statement::Return(args) => {
+                write!(f, "{indent}ret")?;
+                for arg in args {
+                    write!(f, " {arg}")?;
+                }
+                writeln!(f, ";")?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,529 @@
+use std::fmt::{self, Display, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::{
+    AbstractCode, AbstractInstruction, AbstractProgram, Code, EffectOps, Instruction, Program,
+    ValueOps,
+};
+#[cfg(feature = "float")]
+use crate::{AbstractType, Literal, Type};
+
+/// A version of the JSON form of Bril programs, which a program can give as a `"major.minor.patch"` string in a top-level `version` field.
+///
+/// Versions with the same major number only ever add things, so anything written in one of them means the same in a later one, while a new major number means something older tools can't read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SchemaVersion {
+    /// Changes which older tools can't read
+    pub major: u64,
+    /// Additions which older tools can ignore
+    pub minor: u64,
+    /// Fixes to the schema which don't change what programs can say
+    pub patch: u64,
+}
+
+impl SchemaVersion {
+    /// The first version, where each instruction has a single `args` list holding its labels and functions along with its variables, there are no source positions, and floats are written the way JavaScript writes numbers, so that `1.0` is `1`
+    pub const V1: Self = Self::new(1, 0, 0);
+    /// Labels and functions are in their own `labels` and `funcs` lists, and positions are only a `row` and a `col`
+    pub const V2: Self = Self::new(2, 0, 0);
+    /// Positions can be `derived` and have a `source`, and floats are always written with a decimal point or an exponent
+    pub const V3: Self = Self::new(3, 0, 0);
+    /// The version [`crate::Program`] and [`crate::AbstractProgram`] are read from and written to
+    pub const CURRENT: Self = Self::V3;
+
+    /// The version ```major```.```minor```.```patch```
+    #[must_use]
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    const fn check(self) -> Result<Self, VersionError> {
+        if self.major == 0 || self.major > Self::CURRENT.major {
+            Err(VersionError::Unsupported(self))
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+impl Display for SchemaVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Reads `major.minor.patch`, where the minor and patch numbers are 0 if they are left off. Versions with a major number this library doesn't know are an error
+impl FromStr for SchemaVersion {
+    type Err = VersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut numbers = s.split('.').map(str::parse::<u64>);
+        let mut next = |required: bool| match numbers.next() {
+            Some(Ok(n)) => Ok(n),
+            None if !required => Ok(0),
+            _ => Err(VersionError::Invalid(s.to_string())),
+        };
+        let version = Self::new(next(true)?, next(false)?, next(false)?);
+        if numbers.next().is_some() {
+            return Err(VersionError::Invalid(s.to_string()));
+        }
+        version.check()
+    }
+}
+
+/// The errors from reading or writing a program in some [`SchemaVersion`]
+#[derive(Error, Debug)]
+pub enum VersionError {
+    /// A version which isn't `major.minor.patch`
+    #[error("`{0}` is not a version like 2.0.0")]
+    Invalid(String),
+    /// A version with a major number this library doesn't know
+    #[error("version {0} of Bril JSON is not supported, only versions 1 to 3 are")]
+    Unsupported(SchemaVersion),
+    /// An instruction of a version 1 program without enough `args` for the labels and functions its op needs
+    #[error("`{op}` in @{func} doesn't have enough args for its labels and functions")]
+    MissingArgs {
+        /// The function the instruction is in
+        func: String,
+        /// The op of the instruction
+        op: String,
+    },
+    /// The JSON itself is malformed or isn't a Bril program
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+// The ops which keep functions or labels in `args` in version 1, which are the only ones that have to be told apart from version 2
+const V1_OPS: [&str; 6] = ["call", "spawn", "jmp", "br", "guard", "phi"];
+
+/// The version ```json``` is in, which is its `version` field if it has one.
+///
+/// Otherwise it is the oldest version it could be in: [`SchemaVersion::V1`] if any of its instructions keeps its labels or functions in `args`, and [`SchemaVersion::V2`] if none do.
+/// # Errors
+/// Will return an error if the `version` field isn't a supported version
+pub fn detect(json: &Value) -> Result<SchemaVersion, VersionError> {
+    match json.get("version") {
+        Some(Value::String(version)) => version.parse(),
+        Some(version) => Err(VersionError::Invalid(version.to_string())),
+        None => Ok(json["functions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(detect_function)
+            .min()
+            .unwrap_or(SchemaVersion::V2)),
+    }
+}
+
+// The oldest version ```func``` could be in, for programs which don't say
+fn detect_function(func: &Value) -> SchemaVersion {
+    let v1 = func["instrs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .any(|instr| {
+            instr["op"].as_str().is_some_and(|op| V1_OPS.contains(&op))
+                && instr.get("labels").is_none()
+                && instr.get("funcs").is_none()
+        });
+    if v1 {
+        SchemaVersion::V1
+    } else {
+        SchemaVersion::V2
+    }
+}
+
+/// Rewrites ```json``` from the version [`detect`] finds it in into [`SchemaVersion::CURRENT`] without its `version` field, returning the version it was in.
+///
+/// This is what [`from_str`] and the `load_*` functions read programs with, so that programs from older tools can be used as they are.
+///
+/// ```
+/// use bril_rs::version::{upgrade, SchemaVersion};
+/// use serde_json::json;
+///
+/// let mut prog = json!({"functions": [{"name": "main", "instrs": [
+///     {"op": "call", "args": ["f", "x"]},
+///     {"op": "br", "args": ["c", "then", "else"]},
+/// ]}]});
+/// assert_eq!(upgrade(&mut prog).unwrap(), SchemaVersion::V1);
+/// assert_eq!(prog["functions"][0]["instrs"][0], json!({"op": "call", "args": ["x"], "funcs": ["f"]}));
+/// assert_eq!(prog["functions"][0]["instrs"][1], json!({"op": "br", "args": ["c"], "labels": ["then", "else"]}));
+/// ```
+/// # Errors
+/// Will return an error if the `version` field isn't a supported version, or an instruction of a version 1 program has too few `args` for its op
+pub fn upgrade(json: &mut Value) -> Result<SchemaVersion, VersionError> {
+    let version = detect(json)?;
+    if let Some(prog) = json.as_object_mut() {
+        prog.remove("version");
+    }
+    for func in json
+        .get_mut("functions")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+    {
+        upgrade_function(func, Some(version))?;
+    }
+    Ok(version)
+}
+
+// Rewrites ```func``` from ```version``` into the current version, or from the oldest version it could be in when the program doesn't say. This is how [`crate::stream`] reads each function, since the `version` field can come after them
+pub(crate) fn upgrade_function(
+    func: &mut Value,
+    version: Option<SchemaVersion>,
+) -> Result<(), VersionError> {
+    let version = version.unwrap_or_else(|| detect_function(func));
+    let Some(func) = func.as_object_mut() else {
+        return Ok(());
+    };
+    let name = func
+        .get("name")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    for instr in code(func) {
+        if version.major < 2 && instr.get("labels").is_none() && instr.get("funcs").is_none() {
+            split_args(instr).map_err(|op| VersionError::MissingArgs {
+                func: name.clone(),
+                op,
+            })?;
+        }
+        if version.major < 3 {
+            if let Some(value) = float_constant(instr).filter(|value| !value.is_f64()) {
+                *value = value.as_f64().map_or(Value::Null, Value::from);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Rewrites ```json```, which has to be in [`SchemaVersion::CURRENT`], into ```version``` and sets its `version` field to it.
+///
+/// What ```version``` can't say is left out, which is every source position for version 1 and whether positions are `derived` or have a `source` for version 2.
+/// # Errors
+/// Will return an error if ```version``` isn't supported
+pub fn downgrade(json: &mut Value, version: SchemaVersion) -> Result<(), VersionError> {
+    let version = version.check()?;
+    for func in functions(json) {
+        old_position(func, version);
+        for instr in code(func) {
+            old_position(instr, version);
+            if version.major < 2 {
+                join_args(instr);
+            }
+            if version.major < 3 {
+                if let Some(value) = float_constant(instr) {
+                    js_float(value);
+                }
+            }
+        }
+    }
+    if let Some(prog) = json.as_object_mut() {
+        prog.insert("version".to_string(), Value::String(version.to_string()));
+    }
+    Ok(())
+}
+
+/// The representations of programs which [`from_str`] reads, [`crate::Program`] and [`crate::AbstractProgram`].
+///
+/// Reading a program straight into one of them is much faster than going through a [`Value`], so that is what [`from_str`] does first, and these are what it needs to tell whether [`upgrade`] would have changed anything
+pub trait Versioned: DeserializeOwned {
+    /// Whether any instruction keeps its labels or functions in `args`, which is how [`detect`] finds version 1 programs that don't say which version they are in
+    fn has_v1_args(&self) -> bool;
+
+    /// Does what [`upgrade`] does from ```version```, which is version 2 or later, to a program that was read directly: the `version` field, which the `attrs` feature keeps, is removed, and before version 3 the float constants written like integers are made into floats
+    fn finish_upgrade(&mut self, version: SchemaVersion);
+}
+
+// Whether an instruction with ```funcs``` and ```labels``` keeps them in `args` like in version 1, since its op is ```v1_op```, one of [`V1_OPS`], and has none of either
+const fn has_v1_args(v1_op: bool, funcs: &[String], labels: &[String]) -> bool {
+    v1_op && funcs.is_empty() && labels.is_empty()
+}
+
+// Whether ```op``` is one of [`V1_OPS`]
+const fn v1_value_op(op: ValueOps) -> bool {
+    match op {
+        ValueOps::Call => true,
+        #[cfg(feature = "ssa")]
+        ValueOps::Phi => true,
+        #[cfg(feature = "concurrency")]
+        ValueOps::Spawn => true,
+        _ => false,
+    }
+}
+
+// Whether ```op``` is one of [`V1_OPS`]
+const fn v1_effect_op(op: EffectOps) -> bool {
+    match op {
+        EffectOps::Jump | EffectOps::Branch | EffectOps::Call => true,
+        #[cfg(feature = "speculate")]
+        EffectOps::Guard => true,
+        _ => false,
+    }
+}
+
+impl Versioned for Program {
+    fn has_v1_args(&self) -> bool {
+        self.functions
+            .iter()
+            .flat_map(|f| &f.instrs)
+            .any(|code| match code {
+                Code::Instruction(Instruction::Value {
+                    op, funcs, labels, ..
+                }) => has_v1_args(v1_value_op(*op), funcs, labels),
+                Code::Instruction(Instruction::Effect {
+                    op, funcs, labels, ..
+                }) => has_v1_args(v1_effect_op(*op), funcs, labels),
+                Code::Instruction(Instruction::Constant { .. }) | Code::Label { .. } => false,
+            })
+    }
+
+    #[cfg_attr(not(feature = "float"), allow(unused_variables))]
+    fn finish_upgrade(&mut self, version: SchemaVersion) {
+        #[cfg(feature = "attrs")]
+        self.attrs.remove("version");
+        #[cfg(feature = "float")]
+        if version.major < 3 {
+            for code in self.functions.iter_mut().flat_map(|f| &mut f.instrs) {
+                if let Code::Instruction(Instruction::Constant {
+                    const_type: Type::Float,
+                    value,
+                    ..
+                }) = code
+                {
+                    js_float_literal(value);
+                }
+            }
+        }
+    }
+}
+
+impl Versioned for AbstractProgram {
+    fn has_v1_args(&self) -> bool {
+        self.functions
+            .iter()
+            .flat_map(|f| &f.instrs)
+            .any(|code| match code {
+                AbstractCode::Instruction(
+                    AbstractInstruction::Value {
+                        op, funcs, labels, ..
+                    }
+                    | AbstractInstruction::Effect {
+                        op, funcs, labels, ..
+                    },
+                ) => has_v1_args(V1_OPS.contains(&op.as_str()), funcs, labels),
+                AbstractCode::Instruction(AbstractInstruction::Constant { .. })
+                | AbstractCode::Label { .. } => false,
+            })
+    }
+
+    #[cfg_attr(not(feature = "float"), allow(unused_variables))]
+    fn finish_upgrade(&mut self, version: SchemaVersion) {
+        #[cfg(feature = "attrs")]
+        self.attrs.remove("version");
+        #[cfg(feature = "float")]
+        if version.major < 3 {
+            for code in self.functions.iter_mut().flat_map(|f| &mut f.instrs) {
+                if let AbstractCode::Instruction(AbstractInstruction::Constant {
+                    const_type: Some(AbstractType::Primitive(t)),
+                    value,
+                    ..
+                }) = code
+                {
+                    if t == "float" {
+                        js_float_literal(value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// A float constant read directly from before version 3 may be written like an integer, which [`upgrade`] reads as a float
+#[cfg(feature = "float")]
+#[allow(clippy::cast_precision_loss)]
+// This can only be const when the strings feature, whose literals can't be dropped in a const fn, is disabled
+#[cfg_attr(not(feature = "strings"), allow(clippy::missing_const_for_fn))]
+fn js_float_literal(value: &mut Literal) {
+    if let Literal::Int(i) = *value {
+        *value = Literal::Float(i as f64);
+    }
+}
+
+// Just the `version` field of a program, which is quick to find without building a [`Value`] of the rest
+#[derive(Deserialize)]
+struct VersionField {
+    #[serde(default)]
+    version: Option<Value>,
+}
+
+/// Reads a [`crate::Program`] or [`crate::AbstractProgram`] from ```json``` in any supported version, as if with [`upgrade`].
+///
+/// A program in the current version, or in version 2 without a `version` field, is read directly into ```T```, and only programs in older versions, or which can't be read directly, go through a [`Value`] first
+///
+/// ```
+/// use bril_rs::{version, Program};
+///
+/// let v1 = r#"{"functions": [{"name": "main", "instrs": [{"op": "jmp", "args": ["end"]}, {"label": "end"}]}]}"#;
+/// let current = r#"{"functions": [{"name": "main", "instrs": [{"op": "jmp", "labels": ["end"]}, {"label": "end"}]}]}"#;
+/// let v1: Program = version::from_str(v1).unwrap();
+/// let current: Program = version::from_str(current).unwrap();
+/// assert_eq!(v1.to_string(), current.to_string());
+/// ```
+/// # Errors
+/// Will return an error if ```json``` isn't a Bril program in a supported version
+pub fn from_str<T: Versioned>(json: &str) -> Result<T, VersionError> {
+    from_slice(json.as_bytes())
+}
+
+/// The same as [`from_str`] but for the bytes of ```json```
+/// # Errors
+/// Will return an error if ```json``` isn't a Bril program in a supported version
+pub fn from_slice<T: Versioned>(json: &[u8]) -> Result<T, VersionError> {
+    let version = match serde_json::from_slice::<VersionField>(json)?.version {
+        None => None,
+        Some(Value::String(version)) => Some(version.parse::<SchemaVersion>()?),
+        Some(version) => return Err(VersionError::Invalid(version.to_string())),
+    };
+    if version.is_none_or(|v| v.major == SchemaVersion::CURRENT.major) {
+        if let Ok(mut prog) = serde_json::from_slice::<T>(json) {
+            if version.is_some() || !prog.has_v1_args() {
+                prog.finish_upgrade(version.unwrap_or(SchemaVersion::V2));
+                return Ok(prog);
+            }
+        }
+    }
+    let mut json = serde_json::from_slice(json)?;
+    upgrade(&mut json)?;
+    Ok(serde_json::from_value(json)?)
+}
+
+/// Writes ```prog``` as JSON in ```version```, using [`downgrade`]
+/// # Errors
+/// Will return an error if ```version``` isn't supported
+pub fn to_value<T: Serialize>(prog: &T, version: SchemaVersion) -> Result<Value, VersionError> {
+    let mut json = serde_json::to_value(prog)?;
+    downgrade(&mut json, version)?;
+    Ok(json)
+}
+
+/// Writes ```prog``` to ```out``` as pretty-printed JSON in ```version``` followed by a newline, like [`crate::output_program`] does in the current version
+/// # Errors
+/// Will return an error if ```version``` isn't supported or ```out``` can't be written to
+pub fn write<W: Write, T: Serialize>(
+    mut out: W,
+    prog: &T,
+    version: SchemaVersion,
+) -> Result<(), VersionError> {
+    serde_json::to_writer_pretty(&mut out, &to_value(prog, version)?)?;
+    out.write_all(b"\n").map_err(serde_json::Error::io)?;
+    Ok(())
+}
+
+fn functions(json: &mut Value) -> impl Iterator<Item = &mut Map<String, Value>> {
+    json.get_mut("functions")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+// The labels and instructions of ```func```
+fn code(func: &mut Map<String, Value>) -> impl Iterator<Item = &mut Map<String, Value>> {
+    func.get_mut("instrs")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+// The value of ```instr``` if it is a float constant
+fn float_constant(instr: &mut Map<String, Value>) -> Option<&mut Value> {
+    if instr.get("op").and_then(Value::as_str) == Some("const")
+        && instr.get("type").and_then(Value::as_str) == Some("float")
+    {
+        instr.get_mut("value").filter(|value| value.is_number())
+    } else {
+        None
+    }
+}
+
+// JavaScript writes floats without a fractional part like integers, which is how version 1 and 2 tools expect to read them, and only floats small enough to be exact as an i64 are affected
+#[allow(clippy::cast_possible_truncation)]
+fn js_float(value: &mut Value) {
+    if let Some(x) = value.as_f64() {
+        if x.fract() == 0.0 && x.abs() < 2_f64.powi(53) {
+            *value = Value::from(x as i64);
+        }
+    }
+}
+
+fn old_position(item: &mut Map<String, Value>, version: SchemaVersion) {
+    if version.major < 2 {
+        item.remove("pos");
+    } else if version.major < 3 {
+        if let Some(pos) = item.get_mut("pos").and_then(Value::as_object_mut) {
+            pos.remove("derived");
+            pos.remove("source");
+        }
+    }
+}
+
+// Moves the functions at the start and the labels at the end of the `args` of a version 1 instruction into `funcs` and `labels`, or returns its op if it doesn't have enough `args` for them. A `phi` has a label for each of its variables, which come first
+fn split_args(instr: &mut Map<String, Value>) -> Result<(), String> {
+    let op = instr
+        .get("op")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    let Some(Value::Array(args)) = instr.get_mut("args") else {
+        return Ok(());
+    };
+    let (funcs, labels) = match op.as_str() {
+        "call" | "spawn" => (1, 0),
+        "jmp" => (0, args.len()),
+        "br" => (0, 2),
+        "guard" => (0, 1),
+        "phi" => (0, args.len() / 2),
+        _ => return Ok(()),
+    };
+    if funcs + labels > args.len() {
+        return Err(op);
+    }
+    let labels = args.split_off(args.len() - labels);
+    let funcs: Vec<Value> = args.drain(..funcs).collect();
+    if args.is_empty() {
+        instr.remove("args");
+    }
+    if !funcs.is_empty() {
+        instr.insert("funcs".to_string(), Value::Array(funcs));
+    }
+    if !labels.is_empty() {
+        instr.insert("labels".to_string(), Value::Array(labels));
+    }
+    Ok(())
+}
+
+// The reverse of ```split_args```, putting the functions and labels of ```instr``` around its variables in `args`
+fn join_args(instr: &mut Map<String, Value>) {
+    let mut take = |key| match instr.remove(key) {
+        Some(Value::Array(values)) => values,
+        _ => Vec::new(),
+    };
+    let funcs = take("funcs");
+    let labels = take("labels");
+    let vars = take("args");
+    let args: Vec<Value> = funcs.into_iter().chain(vars).chain(labels).collect();
+    if !args.is_empty() {
+        instr.insert("args".to_string(), Value::Array(args));
+    }
+}
@@ -23,9 +23,15 @@ export type ParamType = {ptr: Type};
 export type Type = PrimType | ParamType;
 
 /**
- * An (always optional) source code position.
+ * An (always optional) source code position. A front end can also say where
+ * the code came from in its own source with `source`.
  */
-export type Position = {row: number, col: number};
+export type Position = {row: number, col: number, source?: SourcePosition};
+
+/**
+ * A position in the source of the front end that generated a program.
+ */
+export type SourcePosition = {file: string, row: number, col: number};
 
 /**
  * Common fields in any operation.
@@ -51,7 +57,7 @@ export interface EffectOperation extends Op {
  * destination variable.
  */
 export interface ValueOperation extends Op {
-  op: "add" | "mul" | "sub" | "div" |
+  op: "add" | "mul" | "sub" | "div" | "rem" |
       "id" | "nop" |
       "eq" | "lt" | "gt" | "ge" | "le" | "not" | "and" | "or" |
       "call" |
@@ -180,6 +180,7 @@ const argCounts: {[key in bril.OpCode]: number | null} = {
   mul: 2,
   sub: 2,
   div: 2,
+  rem: 2,
   id: 1,
   lt: 2,
   le: 2,
@@ -553,6 +554,13 @@ function evalInstr(instr: bril.Instruction, state: State): Action {
     return NEXT;
   }
 
+  case "rem": {
+    let val = getInt(instr, state.env, 0) % getInt(instr, state.env, 1);
+    val = BigInt.asIntN(64, val);
+    state.env.set(instr.dest, val);
+    return NEXT;
+  }
+
   case "le": {
     let val = getInt(instr, state.env, 0) <= getInt(instr, state.env, 1);
     state.env.set(instr.dest, val);
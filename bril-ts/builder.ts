@@ -9,6 +9,12 @@ export class Builder {
    */
   public program: bril.Program = { functions: [] };
 
+  /**
+   * The position given to the functions, instructions, and labels built from
+   * now on, if any.
+   */
+  public pos: bril.Position | undefined = undefined;
+
   private curFunction: bril.Function | null = null;
   private nextFresh: number = 0;
 
@@ -22,6 +28,9 @@ export class Builder {
     } else {
       func = {name: name, instrs: [], args: args, type: type};
     }
+    if (this.pos) {
+      func.pos = this.pos;
+    }
     this.program.functions.push(func);
     this.curFunction = func;
     this.nextFresh = 0;
@@ -114,6 +123,9 @@ export class Builder {
     if (!this.curFunction) {
       throw "cannot build instruction/label without a function";
     }
+    if (this.pos) {
+      instr.pos = this.pos;
+    }
     this.curFunction.instrs.push(instr);
   }
 
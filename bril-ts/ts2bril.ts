@@ -51,7 +51,29 @@ function emitBril(prog: ts.Node, checker: ts.TypeChecker): bril.Program {
   let builder = new Builder();
   builder.buildFunction("main", []);  // Main has no return type.
 
+  /**
+   * Run `build` with the code it builds given the position of `node` in the
+   * TypeScript source.
+   */
+  function at<T>(node: ts.Node, build: () => T): T {
+    let sf = node.getSourceFile();
+    let {line, character} = sf.getLineAndCharacterOfPosition(node.getStart());
+    let row = line + 1;
+    let col = character + 1;
+    let outer = builder.pos;
+    builder.pos = {row, col, source: {file: sf.fileName, row, col}};
+    try {
+      return build();
+    } finally {
+      builder.pos = outer;
+    }
+  }
+
   function emitExpr(expr: ts.Expression): bril.ValueInstruction {
+    return at(expr, () => emitExprAt(expr));
+  }
+
+  function emitExprAt(expr: ts.Expression): bril.ValueInstruction {
     switch (expr.kind) {
     case ts.SyntaxKind.NumericLiteral: {
       let lit = expr as ts.NumericLiteral;
@@ -153,6 +175,10 @@ function emitBril(prog: ts.Node, checker: ts.TypeChecker): bril.Program {
   }
 
   function emit(node: ts.Node) {
+    at(node, () => emitAt(node));
+  }
+
+  function emitAt(node: ts.Node) {
     switch (node.kind) {
       // Descend through containers.
       case ts.SyntaxKind.SourceFile:
@@ -76,6 +76,7 @@ export const OP_SIGS: {[key: string]: Signature | PolySignature} = {
   'mul': {args: ['int', 'int'], dest: 'int'},
   'sub': {args: ['int', 'int'], dest: 'int'},
   'div': {args: ['int', 'int'], dest: 'int'},
+  'rem': {args: ['int', 'int'], dest: 'int'},
   'eq': {args: ['int', 'int'], dest: 'bool'},
   'lt': {args: ['int', 'int'], dest: 'bool'},
   'gt': {args: ['int', 'int'], dest: 'bool'},
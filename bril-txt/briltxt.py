@@ -62,15 +62,38 @@ COMMENT: /#.*/
 """.strip()
 
 
-def _pos(token):
-    """Generate a position dict from a Lark token."""
-    return {'row': token.line, 'col': token.column}
+def _sources(txt):
+    """Find the `#@ file:row:col` comments which say where the code on a line
+    came from in the source of a front end, by line number.
+    """
+    sources = {}
+    for line_num, line in enumerate(txt.splitlines(), 1):
+        _, _, comment = line.partition('#')
+        if not comment.startswith('@'):
+            continue
+        parts = comment[1:].strip().rsplit(':', 2)
+        if len(parts) == 3 and parts[0] and \
+                parts[1].isdigit() and parts[2].isdigit():
+            sources[line_num] = {
+                'file': parts[0],
+                'row': int(parts[1]),
+                'col': int(parts[2]),
+            }
+    return sources
 
 
 class JSONTransformer(lark.Transformer):
-    def __init__(self, include_pos=False):
+    def __init__(self, include_pos=False, sources=None):
         super().__init__()
         self.include_pos = include_pos
+        self.sources = sources or {}
+
+    def _pos(self, token):
+        """Generate a position dict from a Lark token."""
+        pos = {'row': token.line, 'col': token.column}
+        if token.line in self.sources:
+            pos['source'] = self.sources[token.line]
+        return pos
 
     def start(self, items):
         structs = [i for i in items if 'mbrs' in i]
@@ -97,7 +120,7 @@ class JSONTransformer(lark.Transformer):
         if typ:
             func['type'] = typ
         if self.include_pos:
-            func['pos'] = _pos(name)
+            func['pos'] = self._pos(name)
         return func
 
     def arg(self, items):
@@ -137,7 +160,7 @@ class JSONTransformer(lark.Transformer):
         if type:
             out['type'] = type
         if self.include_pos:
-            out['pos'] = _pos(dest)
+            out['pos'] = self._pos(dest)
         return out
 
     def vop(self, items):
@@ -147,7 +170,7 @@ class JSONTransformer(lark.Transformer):
             out['type'] = type
         out.update(op)
         if self.include_pos:
-            out['pos'] = _pos(dest)
+            out['pos'] = self._pos(dest)
         return out
 
     def op(self, items):
@@ -173,7 +196,7 @@ class JSONTransformer(lark.Transformer):
         if labels:
             out['labels'] = labels
         if self.include_pos:
-            out['pos'] = _pos(op_token)
+            out['pos'] = self._pos(op_token)
         return out
 
     def eop(self, items):
@@ -186,7 +209,7 @@ class JSONTransformer(lark.Transformer):
             'label': str(name)[1:]  # Strip `.`.
         }
         if self.include_pos:
-            out['pos'] = _pos(name)
+            out['pos'] = self._pos(name)
         return out
 
     def int(self, items):
@@ -218,7 +241,7 @@ def parse_bril(txt, include_pos=False):
     """
     parser = lark.Lark(GRAMMAR, maybe_placeholders=True)
     tree = parser.parse(txt)
-    data = JSONTransformer(include_pos).transform(tree)
+    data = JSONTransformer(include_pos, _sources(txt)).transform(tree)
     return json.dumps(data, indent=2, sort_keys=True)
 
 
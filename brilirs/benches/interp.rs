@@ -0,0 +1,50 @@
+// Criterion benchmarks of the interpreter on the programs in `benchmarks/` with the arguments from their `# ARGS:` lines. Only the interpretation is timed, not parsing or type checking.
+// Run with `make criterion`, or `cargo bench --profile bench-unwind -- mat-mul` for only the benchmarks whose names contain `mat-mul`. Each run is compared against the one before it, and adding `-- --save-baseline before` on one commit and `-- --baseline before` on another compares the two commits.
+use std::path::Path;
+
+use brilirs::basic_block::BBProgram;
+use brilirs::{check, interp};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+// Every program in `benchmarks/` by the name of its file, converted and type checked, along with its arguments
+fn programs() -> Vec<(String, BBProgram, Vec<String>)> {
+  let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../benchmarks");
+  let mut paths: Vec<_> = std::fs::read_dir(&dir)
+    .unwrap()
+    .map(|entry| entry.unwrap().path())
+    .filter(|path| path.extension().is_some_and(|ext| ext == "bril"))
+    .collect();
+  paths.sort();
+  paths
+    .into_iter()
+    .map(|path| {
+      let source = std::fs::read_to_string(&path).unwrap();
+      let args = source
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("# ARGS:"))
+        .map_or_else(Vec::new, |args| {
+          args.split_whitespace().map(str::to_string).collect()
+        });
+      let prog = bril2json::parse_abstract_program_from_read(source.as_bytes(), true)
+        .try_into()
+        .unwrap();
+      let bbprog = BBProgram::new(prog).unwrap();
+      check::type_check(&bbprog).unwrap();
+      let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+      (name, bbprog, args)
+    })
+    .collect()
+}
+
+fn benchmarks(c: &mut Criterion) {
+  let mut group = c.benchmark_group("benchmarks");
+  for (name, bbprog, args) in programs() {
+    group.bench_function(&name, |b| {
+      b.iter(|| interp::execute_main(&bbprog, std::io::sink(), &args, false).unwrap());
+    });
+  }
+  group.finish();
+}
+
+criterion_group!(benches, benchmarks);
+criterion_main!(benches);
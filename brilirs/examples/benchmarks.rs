@@ -0,0 +1,51 @@
+// Times the interpreter on every program in `benchmarks/` with the arguments from its `# ARGS:` line. Only the interpretation is timed, not parsing or type checking, and each program is run repeatedly to get a steady time per run.
+// Run with `cargo run --release --example benchmarks`, adding `--features tagged-values` to compare against the tagged representation of values that debug builds use. This is a quick look at the release build as it is. `benches/interp.rs` times the same programs with criterion, which is slower but steadier and can compare against an earlier run.
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use brilirs::basic_block::BBProgram;
+use brilirs::{check, interp};
+
+// How long to keep running each program for
+const TARGET: Duration = Duration::from_millis(500);
+
+fn main() {
+  let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../benchmarks");
+  let mut paths: Vec<_> = std::fs::read_dir(&dir)
+    .unwrap()
+    .map(|entry| entry.unwrap().path())
+    .filter(|path| path.extension().is_some_and(|ext| ext == "bril"))
+    .collect();
+  paths.sort();
+
+  let mut total = Duration::ZERO;
+  for path in paths {
+    let source = std::fs::read_to_string(&path).unwrap();
+    let args: Vec<String> = source
+      .lines()
+      .find_map(|line| line.trim().strip_prefix("# ARGS:"))
+      .map_or_else(Vec::new, |args| {
+        args.split_whitespace().map(str::to_string).collect()
+      });
+    let prog = bril2json::parse_abstract_program_from_read(source.as_bytes(), true)
+      .try_into()
+      .unwrap();
+    let bbprog = BBProgram::new(prog).unwrap();
+    check::type_check(&bbprog).unwrap();
+
+    let mut runs = 0;
+    let start = Instant::now();
+    while runs == 0 || start.elapsed() < TARGET {
+      interp::execute_main(&bbprog, std::io::sink(), &args, false).unwrap();
+      runs += 1;
+    }
+    let per_run = start.elapsed() / runs;
+    total += per_run;
+    println!(
+      "{:<32} {:>10.1} us/run ({runs} runs)",
+      path.file_name().unwrap().to_string_lossy(),
+      per_run.as_secs_f64() * 1e6
+    );
+  }
+  println!("{:<32} {:>10.1} us", "total", total.as_secs_f64() * 1e6);
+}
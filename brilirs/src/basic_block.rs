@@ -1,14 +1,52 @@
+use std::sync::Arc;
+
 use bril_rs::{Function, Instruction, Position, Program};
 use error::InterpError;
-use fxhash::FxHashMap;
+use serde::Serialize;
 
 use crate::error;
+use crate::intrinsic::Intrinsic;
+use crate::op::Op;
+use crate::symbol::SymbolTable;
 
-/// A program represented as basic blocks. This is the IR of brilirs
-#[derive(Debug)]
+/// A program represented as basic blocks.
+///
+/// This is the IR of brilirs, whose blocks always end after a `jmp`, `br`, or `ret` and are kept even when empty so that every label starts one. Analyses which need blocks split another way can build them with [`bril_rs::cfg::BlockBuilder`]
+///
+/// A [`BBProgram`] is [`Send`] and [`Sync`], and each function is kept behind an [`Arc`] so that cloning one only copies the symbol table and not the blocks. Threads can share one parsed program by reference, or each take a clone of it when they need to own theirs.
+/// ```
+/// use brilirs::basic_block::BBProgram;
+/// use brilirs::interp::{Interpreter, Options};
+///
+/// let json = r#"{"functions": [{"name": "main", "args": [{"name": "n", "type": "int"}], "instrs": [
+///     {"op": "mul", "dest": "sq", "type": "int", "args": ["n", "n"]},
+///     {"op": "print", "args": ["sq"]}
+/// ]}]}"#;
+/// let prog = BBProgram::new(serde_json::from_str(json).unwrap()).unwrap();
+/// let workers: Vec<_> = (1..=3)
+///     .map(|n| {
+///         let prog = prog.clone();
+///         std::thread::spawn(move || {
+///             let mut out = Vec::new();
+///             let mut interpreter = Interpreter::new(&prog, &mut out, Options::default());
+///             interpreter.start_main(&[n.to_string()]).unwrap();
+///             interpreter.run_to_completion().unwrap();
+///             interpreter.finish().unwrap();
+///             String::from_utf8(out).unwrap()
+///         })
+///     })
+///     .collect();
+/// let outputs: Vec<String> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+/// assert_eq!(outputs, ["1\n", "4\n", "9\n"]);
+/// ```
+#[derive(Debug, Default, Clone)]
 pub struct BBProgram {
-  #[doc(hidden)]
-  pub func_index: FxHashMap<String, BBFunction>,
+  // Every function which is defined or called, so that calls can find what they call by its symbol
+  pub(crate) func_symbols: SymbolTable,
+  // The function with each symbol, which is missing for functions that are called but never defined. Clones of the program share them
+  functions: Vec<Option<Arc<BBFunction>>>,
+  // The intrinsic with each symbol, which calls go to when there is no function with that symbol
+  intrinsics: Vec<Option<Intrinsic>>,
 }
 
 impl TryFrom<Program> for BBProgram {
@@ -22,36 +60,207 @@ impl TryFrom<Program> for BBProgram {
 impl BBProgram {
   /// Converts a [`Program`] into a [`BBProgram`]
   pub fn new(prog: Program) -> Result<Self, InterpError> {
-    let num_funcs = prog.functions.len();
-    let bb = Self {
-      func_index: prog
-        .functions
-        .into_iter()
-        .map(|func| (func.name.clone(), BBFunction::new(func)))
-        .collect(),
-    };
-    if bb.func_index.len() != num_funcs {
-      Err(InterpError::DuplicateFunction)
-    } else {
-      Ok(bb)
+    let mut bbprog = Self::default();
+    for func in prog.functions {
+      bbprog.add_function(func)?;
+    }
+    Ok(bbprog)
+  }
+
+  /// Converts and adds a single function to the program. This builds up a [`BBProgram`] one function at a time without ever holding the whole [`Program`], like when its functions come from [`bril_rs::stream::for_each_function`].
+  pub fn add_function(&mut self, func: Function) -> Result<(), InterpError> {
+    let (name, pos) = (func.name.clone(), func.pos);
+    let symbol = self.func_symbols.intern(&name) as usize;
+    let func = BBFunction::new(func, &mut self.func_symbols)?;
+    self.functions.resize_with(self.func_symbols.len(), || None);
+    if self.functions[symbol].replace(Arc::new(func)).is_some() {
+      return Err(InterpError::DuplicateFunction(name).add_pos(pos).into());
     }
+    Ok(())
+  }
+
+  /// Converts ```func``` and puts it in place of the function with the same name, or adds it if there isn't one yet, giving back the function it replaced. The program is left as it was if ```func``` can't be converted. Clones of the program made before this keep the function it replaced.
+  pub fn replace_function(
+    &mut self,
+    func: Function,
+  ) -> Result<Option<Arc<BBFunction>>, InterpError> {
+    let symbol = self.func_symbols.intern(&func.name) as usize;
+    let func = BBFunction::new(func, &mut self.func_symbols)?;
+    self.functions.resize_with(self.func_symbols.len(), || None);
+    Ok(self.functions[symbol].replace(Arc::new(func)))
+  }
+
+  /// Takes the function named ```func_name``` out of the program, if it has been defined. Calls to it are left as they are, as though it had only ever been called
+  pub fn remove_function(&mut self, func_name: &str) -> Option<Arc<BBFunction>> {
+    let symbol = self.func_symbols.get(func_name)?;
+    self.functions.get_mut(symbol as usize)?.take()
+  }
+
+  /// Adds an [`Intrinsic`] which the program can call as ```name```, in place of any intrinsic already added with that name. Calls only go to it while the program doesn't define a function named ```name``` of its own
+  pub fn add_intrinsic(&mut self, name: &str, intrinsic: Intrinsic) {
+    let symbol = self.func_symbols.intern(name) as usize;
+    self
+      .intrinsics
+      .resize_with(self.func_symbols.len(), || None);
+    self.intrinsics[symbol] = Some(intrinsic);
+  }
+
+  /// The intrinsic added as ```name```, if there is one
+  pub fn get_intrinsic(&self, name: &str) -> Option<&Intrinsic> {
+    self.intrinsic(self.func_symbols.get(name)?)
+  }
+
+  // Every intrinsic which has been added, with the name it was added as
+  pub(crate) fn intrinsics_mut(&mut self) -> impl Iterator<Item = (&str, &mut Intrinsic)> {
+    let symbols = &self.func_symbols;
+    self
+      .intrinsics
+      .iter_mut()
+      .enumerate()
+      .filter_map(move |(symbol, intrinsic)| {
+        Some((symbols.name(symbol as u32), intrinsic.as_mut()?))
+      })
+  }
+
+  // The intrinsic with the symbol ```symbol```, if one has been added
+  #[inline(always)]
+  pub(crate) fn intrinsic(&self, symbol: u32) -> Option<&Intrinsic> {
+    self.intrinsics.get(symbol as usize)?.as_ref()
   }
 
   #[doc(hidden)]
   pub fn get(&self, func_name: &str) -> Option<&BBFunction> {
-    self.func_index.get(func_name)
+    self.function(self.func_symbols.get(func_name)?)
+  }
+
+  // The function with the symbol ```symbol```, if it has been defined
+  #[inline(always)]
+  pub(crate) fn function(&self, symbol: u32) -> Option<&BBFunction> {
+    self.functions.get(symbol as usize)?.as_deref()
+  }
+
+  #[doc(hidden)]
+  // Every function in the order that they were first defined or called
+  pub fn functions(&self) -> impl Iterator<Item = &BBFunction> {
+    self.functions.iter().flatten().map(Arc::as_ref)
+  }
+
+  // The functions sorted by name so that dumps don't depend on the order they were added in
+  fn sorted_functions(&self) -> Vec<&BBFunction> {
+    let mut funcs: Vec<&BBFunction> = self.functions().collect();
+    funcs.sort_by(|a, b| a.name.cmp(&b.name));
+    funcs
+  }
+
+  /// Renders the control flow graph of every function as a [Graphviz](https://graphviz.org) DOT graph. Each function is a cluster of basic blocks listing their instructions, with edges labelled `true` and `false` for branches and dashed for fallthroughs.
+  pub fn to_dot(&self) -> String {
+    let mut dot = String::from("digraph program {\n  node [shape=box, fontname=monospace];\n");
+    for func in self.sorted_functions() {
+      dot.push_str(&format!(
+        "  subgraph \"cluster_{}\" {{\n    label = \"@{}\";\n",
+        escape_dot(&func.name),
+        escape_dot(&func.name)
+      ));
+      for (i, block) in func.blocks.iter().enumerate() {
+        let mut text = format!("{}:\\l", escape_dot(&block.name(i)));
+        for instr in &block.instrs {
+          text.push_str(&format!("  {}\\l", escape_dot(&instr.to_string())));
+        }
+        dot.push_str(&format!(
+          "    \"{}.{i}\" [label=\"{text}\"];\n",
+          escape_dot(&func.name)
+        ));
+      }
+      for (i, block) in func.blocks.iter().enumerate() {
+        for edge in block.edges() {
+          let attrs = match edge.kind {
+            EdgeKind::Fallthrough => " [style=dashed]".to_string(),
+            EdgeKind::Jump => String::new(),
+            EdgeKind::Branch => format!(" [label=\"{}\"]", edge.taken.unwrap_or_default()),
+          };
+          dot.push_str(&format!(
+            "    \"{name}.{i}\" -> \"{name}.{}\"{attrs};\n",
+            edge.target,
+            name = escape_dot(&func.name)
+          ));
+        }
+      }
+      dot.push_str("  }\n");
+    }
+    dot.push_str("}\n");
+    dot
+  }
+
+  /// Describes the control flow graph of every function as JSON. Each function has its `name` and a list of `blocks`, each of which has its `label` if it has one, its `instrs` as Bril text, and its `successors`. Every successor has the index of its `target` block and the `kind` of edge, which is one of `fallthrough`, `jump`, or `branch`, with `taken` giving which way a branch goes.
+  pub fn to_json_cfg(&self) -> serde_json::Value {
+    serde_json::json!({
+      "functions": self
+        .sorted_functions()
+        .into_iter()
+        .map(|func| {
+          serde_json::json!({
+            "name": func.name,
+            "blocks": func
+              .blocks
+              .iter()
+              .map(|block| {
+                serde_json::json!({
+                  "label": block.label,
+                  "instrs": block.instrs.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                  "successors": block.edges(),
+                })
+              })
+              .collect::<Vec<_>>(),
+          })
+        })
+        .collect::<Vec<_>>(),
+    })
   }
 }
 
+fn escape_dot(s: &str) -> String {
+  s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// How control gets from one basic block to the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeKind {
+  /// The block doesn't end in a terminator so it runs straight into the next block
+  Fallthrough,
+  /// The block ends with a `jmp`
+  Jump,
+  /// The block ends with a `br`
+  Branch,
+}
+
+/// An edge of the control flow graph out of a [`BasicBlock`]
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Edge {
+  /// The index of the block control goes to
+  pub target: usize,
+  /// What kind of control flow the edge is
+  pub kind: EdgeKind,
+  /// For a [`EdgeKind::Branch`], whether this is where the branch goes when its condition is true
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub taken: Option<bool>,
+}
+
 #[doc(hidden)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BasicBlock {
   pub label: Option<String>,
-  // These two vecs work in parallel
+  // The symbol of the label among the labels of its function, for phi nodes to compare against
+  pub label_symbol: Option<u32>,
+  // Where the label is in the source, for warnings about it
+  pub label_pos: Option<Position>,
+  // These three vecs work in parallel
   // One is the normal instruction
-  // The other contains the numified version of the destination and arguments
+  // Another contains the numified version of the destination and arguments
+  // The last is what the interpreter runs, which is compiled from the other two once the whole function has been
   pub instrs: Vec<bril_rs::Instruction>,
   pub numified_instrs: Vec<NumifiedInstruction>,
+  pub(crate) ops: Vec<Op>,
   pub exit: Vec<usize>,
 }
 
@@ -59,67 +268,131 @@ impl BasicBlock {
   const fn new() -> Self {
     Self {
       label: None,
+      label_symbol: None,
+      label_pos: None,
       instrs: Vec::new(),
       numified_instrs: Vec::new(),
+      ops: Vec::new(),
       exit: Vec::new(),
     }
   }
+
+  // How the block is shown in a dump, where ```index``` is its index in the function
+  pub(crate) fn name(&self, index: usize) -> String {
+    self
+      .label
+      .as_ref()
+      .map_or_else(|| format!("<block {index}>"), |l| format!(".{l}"))
+  }
+
+  // The blocks control can go to from this one, which includes where its guards fall back to on top of ```exit```
+  pub(crate) fn successors(&self) -> impl Iterator<Item = usize> + '_ {
+    self
+      .exit
+      .iter()
+      .copied()
+      .chain(self.numified_instrs.iter().filter_map(|i| i.target))
+  }
+
+  /// The edges out of this block
+  pub fn edges(&self) -> Vec<Edge> {
+    match self.instrs.last() {
+      Some(Instruction::Effect {
+        op: bril_rs::EffectOps::Branch,
+        ..
+      }) => self
+        .exit
+        .iter()
+        .zip([true, false])
+        .map(|(&target, taken)| Edge {
+          target,
+          kind: EdgeKind::Branch,
+          taken: Some(taken),
+        })
+        .collect(),
+      Some(Instruction::Effect {
+        op: bril_rs::EffectOps::Jump,
+        ..
+      }) => self
+        .exit
+        .iter()
+        .map(|&target| Edge {
+          target,
+          kind: EdgeKind::Jump,
+          taken: None,
+        })
+        .collect(),
+      _ => self
+        .exit
+        .iter()
+        .map(|&target| Edge {
+          target,
+          kind: EdgeKind::Fallthrough,
+          taken: None,
+        })
+        .collect(),
+    }
+  }
 }
 
 #[doc(hidden)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct NumifiedInstruction {
   pub dest: Option<u32>,
   pub args: Vec<u32>,
-}
-
-fn get_num_from_map(
-  var: &str,
-  num_of_vars: &mut u32,
-  num_var_map: &mut FxHashMap<String, u32>,
-) -> u32 {
-  match num_var_map.get(var) {
-    Some(i) => *i,
-    None => {
-      let x = *num_of_vars;
-      num_var_map.insert(var.to_string(), x);
-      *num_of_vars += 1;
-      x
-    }
-  }
+  // The symbols of the labels the instruction names, among the labels of its function
+  pub labels: Vec<u32>,
+  // The symbol of the function a call calls, among the functions of the program
+  pub func: Option<u32>,
+  // The index of the block a guard falls back to when its condition is false
+  pub target: Option<usize>,
+  // The variables after the first which a call of a function returning more than one value assigns
+  pub extra_dests: Vec<u32>,
 }
 
 impl NumifiedInstruction {
   fn create(
     instr: &Instruction,
-    num_of_vars: &mut u32,
-    num_var_map: &mut FxHashMap<String, u32>,
+    vars: &mut SymbolTable,
+    labels: &mut SymbolTable,
+    funcs: &mut SymbolTable,
   ) -> Self {
-    match instr {
-      Instruction::Constant { dest, .. } => Self {
-        dest: Some(get_num_from_map(dest, num_of_vars, num_var_map)),
-        args: Vec::new(),
-      },
-      Instruction::Value { dest, args, .. } => Self {
-        dest: Some(get_num_from_map(dest, num_of_vars, num_var_map)),
-        args: args
-          .iter()
-          .map(|v| get_num_from_map(v, num_of_vars, num_var_map))
-          .collect(),
-      },
-      Instruction::Effect { args, .. } => Self {
-        dest: None,
-        args: args
-          .iter()
-          .map(|v| get_num_from_map(v, num_of_vars, num_var_map))
-          .collect(),
-      },
+    let (dest, args, instr_labels, instr_funcs, extra_dests): (
+      _,
+      &[String],
+      &[String],
+      &[String],
+      &[bril_rs::Argument],
+    ) = match instr {
+      Instruction::Constant { dest, .. } => (Some(dest), &[], &[], &[], &[]),
+      Instruction::Value {
+        dest,
+        args,
+        labels,
+        funcs,
+        extra_dests,
+        ..
+      } => (Some(dest), args, labels, funcs, extra_dests),
+      Instruction::Effect {
+        args,
+        labels,
+        funcs,
+        ..
+      } => (None, args, labels, funcs, &[]),
+    };
+    Self {
+      dest: dest.map(|d| vars.intern(d)),
+      args: args.iter().map(|v| vars.intern(v)).collect(),
+      labels: instr_labels.iter().map(|l| labels.intern(l)).collect(),
+      func: instr_funcs.first().map(|f| funcs.intern(f)),
+      target: None,
+      extra_dests: extra_dests.iter().map(|d| vars.intern(&d.name)).collect(),
     }
   }
 }
 
 #[doc(hidden)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BBFunction {
   pub name: String,
   pub args: Vec<bril_rs::Argument>,
@@ -130,89 +403,92 @@ pub struct BBFunction {
   // These replacements are found for function args and for code in the BasicBlocks
   pub num_of_vars: u32,
   pub args_as_nums: Vec<u32>,
+  // The original name of each numbered variable for when it needs to be displayed
+  pub var_names: Vec<String>,
+  // The labels of the function, whether they start a block or are only named by phi nodes
+  pub labels: SymbolTable,
+  // The type of each numbered variable, for when its value needs to be displayed. This comes from the arguments and the first definition of each variable in a block that can be reached, which the type checker makes sure that every other definition which can run agrees with. Variables that are never defined where they could run have no type.
+  pub var_types: Vec<Option<bril_rs::Type>>,
   pub pos: Option<Position>,
 }
 
 impl BBFunction {
-  fn new(f: Function) -> Self {
-    let (mut func, label_map) = Self::find_basic_blocks(f);
-    func.build_cfg(label_map);
-    func
+  fn new(f: Function, funcs: &mut SymbolTable) -> Result<Self, InterpError> {
+    let (mut func, label_blocks) = Self::find_basic_blocks(f, funcs);
+    func.build_cfg(&label_blocks)?;
+    func.find_var_types();
+    func.compile();
+    Ok(func)
   }
 
-  fn find_basic_blocks(func: bril_rs::Function) -> (Self, FxHashMap<String, usize>) {
+  // Compiles every instruction into the Op that the interpreter runs, which needs the blocks that each block goes to
+  fn compile(&mut self) {
+    for block in &mut self.blocks {
+      block.ops = Op::compile_block(&block.instrs, &block.numified_instrs, &block.exit);
+    }
+  }
+
+  // Splits ```func``` into blocks, also returning the index of the block that starts with each label symbol
+  fn find_basic_blocks(
+    func: bril_rs::Function,
+    funcs: &mut SymbolTable,
+  ) -> (Self, Vec<Option<usize>>) {
     let mut blocks = Vec::new();
-    let mut label_map = FxHashMap::default();
+    let mut vars = SymbolTable::default();
+    let mut labels = SymbolTable::default();
 
-    let mut num_of_vars = 0;
-    let mut num_var_map = FxHashMap::default();
-
-    let args_as_nums = func
-      .args
-      .iter()
-      .map(|a| get_num_from_map(&a.name, &mut num_of_vars, &mut num_var_map))
-      .collect();
+    let args_as_nums = func.args.iter().map(|a| vars.intern(&a.name)).collect();
 
     let mut curr_block = BasicBlock::new();
     for instr in func.instrs {
       match instr {
-        bril_rs::Code::Label { label, pos: _ } => {
+        bril_rs::Code::Label { label, pos } => {
           if !curr_block.instrs.is_empty() || curr_block.label.is_some() {
-            if let Some(old_label) = curr_block.label.as_ref() {
-              label_map.insert(old_label.to_string(), blocks.len());
-            }
             blocks.push(curr_block);
             curr_block = BasicBlock::new();
           }
+          curr_block.label_symbol = Some(labels.intern(&label));
           curr_block.label = Some(label);
-        }
-        bril_rs::Code::Instruction(bril_rs::Instruction::Effect {
-          op,
-          args,
-          funcs,
-          labels,
-          pos,
-        }) if op == bril_rs::EffectOps::Jump
-          || op == bril_rs::EffectOps::Branch
-          || op == bril_rs::EffectOps::Return =>
-        {
-          let i = bril_rs::Instruction::Effect {
-            op,
-            args,
-            funcs,
-            labels,
-            pos,
-          };
-          curr_block.numified_instrs.push(NumifiedInstruction::create(
-            &i,
-            &mut num_of_vars,
-            &mut num_var_map,
-          ));
-          curr_block.instrs.push(i);
-          if let Some(l) = curr_block.label.as_ref() {
-            label_map.insert(l.to_string(), blocks.len());
-          }
-          blocks.push(curr_block);
-          curr_block = BasicBlock::new();
+          curr_block.label_pos = pos;
         }
         bril_rs::Code::Instruction(code) => {
+          let done = matches!(
+            code,
+            bril_rs::Instruction::Effect {
+              op: bril_rs::EffectOps::Jump
+                | bril_rs::EffectOps::Branch
+                | bril_rs::EffectOps::Return,
+              ..
+            }
+          );
           curr_block.numified_instrs.push(NumifiedInstruction::create(
             &code,
-            &mut num_of_vars,
-            &mut num_var_map,
+            &mut vars,
+            &mut labels,
+            funcs,
           ));
           curr_block.instrs.push(code);
+          if done {
+            blocks.push(curr_block);
+            curr_block = BasicBlock::new();
+          }
         }
       }
     }
 
-    if !curr_block.instrs.is_empty() || curr_block.label.is_some() {
-      if let Some(l) = curr_block.label.as_ref() {
-        label_map.insert(l.to_string(), blocks.len());
-      }
+    // Every function has at least one block to start executing from, even if it is empty
+    if !curr_block.instrs.is_empty() || curr_block.label.is_some() || blocks.is_empty() {
       blocks.push(curr_block);
     }
 
+    // A label which starts more than one block goes to the last of them
+    let mut label_blocks = vec![None; labels.len()];
+    for (i, block) in blocks.iter().enumerate() {
+      if let Some(l) = block.label_symbol {
+        label_blocks[l as usize] = Some(i);
+      }
+    }
+
     (
       Self {
         name: func.name,
@@ -220,43 +496,96 @@ impl BBFunction {
         return_type: func.return_type,
         blocks,
         args_as_nums,
-        num_of_vars,
+        num_of_vars: vars.len() as u32,
+        var_names: vars.into_names(),
+        labels,
+        var_types: Vec::new(),
         pos: func.pos,
       },
-      label_map,
+      label_blocks,
     )
   }
 
-  fn build_cfg(&mut self, label_map: FxHashMap<String, usize>) {
+  fn find_var_types(&mut self) {
+    let mut var_types = vec![None; self.num_of_vars as usize];
+    for (a, num) in self.args.iter().zip(&self.args_as_nums) {
+      var_types[*num as usize] = Some(a.arg_type.clone());
+    }
+    let mut reached = vec![false; self.blocks.len()];
+    let mut work_list = vec![0];
+    while let Some(b) = work_list.pop() {
+      if std::mem::replace(&mut reached[b], true) {
+        continue;
+      }
+      let block = &self.blocks[b];
+      for (instr, numified) in block.instrs.iter().zip(&block.numified_instrs) {
+        if let (
+          Instruction::Constant { const_type: ty, .. } | Instruction::Value { op_type: ty, .. },
+          Some(dest),
+        ) = (instr, numified.dest)
+        {
+          var_types[dest as usize].get_or_insert_with(|| ty.clone());
+        }
+        if let Instruction::Value { extra_dests, .. } = instr {
+          for (extra, num) in extra_dests.iter().zip(&numified.extra_dests) {
+            var_types[*num as usize].get_or_insert_with(|| extra.arg_type.clone());
+          }
+        }
+      }
+      work_list.extend(block.successors());
+    }
+    self.var_types = var_types;
+  }
+
+  fn build_cfg(&mut self, label_blocks: &[Option<usize>]) -> Result<(), InterpError> {
     let last_idx = self.blocks.len() - 1;
+    let labels = &self.labels;
+    let find = |l: u32, pos: Option<Position>| {
+      label_blocks[l as usize]
+        .ok_or_else(|| InterpError::MissingLabel(labels.name(l).to_string()).add_pos(pos))
+    };
     for (i, block) in self.blocks.iter_mut().enumerate() {
-      // If we're before the last block
-      if i < last_idx {
-        // Get the last instruction
-        let last_instr = block.instrs.last().cloned();
-        if let Some(bril_rs::Instruction::Effect {
-          op: bril_rs::EffectOps::Jump | bril_rs::EffectOps::Branch,
-          labels,
+      for (instr, numified) in block.instrs.iter().zip(&mut block.numified_instrs) {
+        if let bril_rs::Instruction::Effect {
+          op: bril_rs::EffectOps::Guard,
+          pos,
           ..
-        }) = last_instr
+        } = instr
         {
-          for l in labels {
-            block.exit.push(
-              *label_map
-                .get(&l)
-                .unwrap_or_else(|| panic!("No label {} found.", &l)),
-            );
+          if let Some(l) = numified.labels.first() {
+            numified.target = Some(find(*l, *pos)?);
           }
-        } else if let Some(bril_rs::Instruction::Effect {
-          op: bril_rs::EffectOps::Return,
-          ..
-        }) = last_instr
-        {
+        }
+      }
+      match (block.instrs.last(), block.numified_instrs.last()) {
+        (
+          Some(bril_rs::Instruction::Effect {
+            op: bril_rs::EffectOps::Jump | bril_rs::EffectOps::Branch,
+            pos,
+            ..
+          }),
+          Some(numified),
+        ) => {
+          for l in &numified.labels {
+            block.exit.push(find(*l, *pos)?);
+          }
+        }
+        (
+          Some(bril_rs::Instruction::Effect {
+            op: bril_rs::EffectOps::Return,
+            ..
+          }),
+          _,
+        ) => {
           // We are done, there is no exit from this block
-        } else {
+        }
+        _ if i < last_idx => {
+          // Fall through to the next block
           block.exit.push(i + 1);
         }
+        _ => {}
       }
     }
+    Ok(())
   }
 }
@@ -0,0 +1,42 @@
+use rayon::prelude::*;
+
+use crate::basic_block::BBProgram;
+use crate::error::PositionalInterpError;
+use crate::interp::{Interpreter, Options};
+
+/// What one run of [`run_batch`] printed and how many instructions it took
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchRun {
+  /// Everything the program printed
+  pub output: String,
+  /// The total number of dynamic instructions, the same number reported by `total_dyn_inst`
+  pub total_dyn_inst: u32,
+}
+
+/// Runs the "main" function of ```prog``` once with each of ```inputs``` as its arguments, alongside each other on rayon's thread pool.
+///
+/// Every run has its own [`Interpreter`] with the [`Options`] made by ```options```, so runs share nothing but ```prog``` and one failing doesn't stop the others. The results are in the order of ```inputs``` no matter which run finishes first.
+pub fn run_batch<F>(
+  prog: &BBProgram,
+  inputs: &[Vec<String>],
+  options: F,
+) -> Vec<Result<BatchRun, PositionalInterpError>>
+where
+  F: Fn() -> Options + Sync,
+{
+  inputs
+    .par_iter()
+    .map(|input_args| {
+      let mut out = Vec::new();
+      let mut interpreter = Interpreter::new(prog, &mut out, options());
+      interpreter.start_main(input_args)?;
+      interpreter.run_to_completion()?;
+      let total_dyn_inst = interpreter.instruction_count();
+      interpreter.finish()?;
+      Ok(BatchRun {
+        output: String::from_utf8_lossy(&out).into_owned(),
+        total_dyn_inst,
+      })
+    })
+    .collect()
+}
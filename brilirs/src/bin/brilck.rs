@@ -0,0 +1,63 @@
+use clap::{ArgEnum, Parser};
+use std::fs::File;
+
+#[derive(Clone, Copy, ArgEnum)]
+enum ErrorFormat {
+  Human,
+  Json,
+}
+
+#[derive(Parser)]
+#[clap(
+  about = "Statically checks Bril programs without running them",
+  version,
+  author
+)]
+struct Cli {
+  /// The bril file to check. stdin is assumed if file is not provided
+  #[clap(short, long)]
+  file: Option<String>,
+
+  /// Flag for when the bril program is in text form
+  #[clap(short, long)]
+  text: bool,
+
+  /// How to print each problem that is found. json prints one object per line for editor integration
+  #[clap(long, arg_enum, default_value = "human")]
+  error_format: ErrorFormat,
+
+  /// Exit with a status of 1 when there are warnings, like there are errors
+  #[clap(long)]
+  deny_warnings: bool,
+
+  /// Also report everything which relies on behaviour that Bril leaves unspecified as errors, like using a variable which might not have been assigned yet or `int2char` of a constant which is not a character
+  #[clap(long)]
+  strict: bool,
+}
+
+fn main() {
+  let args = Cli::parse();
+
+  let input: Box<dyn std::io::Read> = match args.file.as_ref() {
+    None => Box::new(std::io::stdin()),
+
+    Some(input_file) => Box::new(File::open(input_file).unwrap()),
+  };
+
+  match brilirs::check_input(
+    input,
+    std::io::stderr(),
+    args.text,
+    args.file.as_deref(),
+    matches!(args.error_format, ErrorFormat::Json),
+    args.deny_warnings,
+    args.strict,
+  ) {
+    Ok(0) => {}
+    Ok(_) => std::process::exit(1),
+    Err(e) => {
+      eprintln!("error: {e}");
+      std::process::exit(2)
+    }
+  }
+}
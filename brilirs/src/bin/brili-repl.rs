@@ -0,0 +1,314 @@
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::Path;
+
+use bril_rs::link::Linker;
+use bril_rs::{
+  AbstractCode, Argument, Attrs, Code, EffectOps, Function, Instruction, Program, Type,
+};
+use brilirs::basic_block::BBProgram;
+use brilirs::check::type_check;
+use brilirs::interp::{Interpreter, Memory, Options, RuntimeValue};
+use clap::Parser;
+
+const HELP: &str = "\
+Type a Bril instruction to run it, or a function to define it. Functions can go over several lines
+and replace any function of the same name.
+
+Commands:
+  :load file    define every function in a Bril text or JSON file
+  :env          print every variable along with its type and value
+  :heap         print every live allocation
+  :funcs        print the signature of every function
+  :help         print this message
+  :quit         leave the REPL";
+
+#[derive(Parser)]
+#[clap(
+  about = "Runs Bril instructions interactively as they are typed",
+  version,
+  author
+)]
+struct Cli {
+  /// Bril files whose functions are defined before the first prompt, as with :load
+  files: Vec<String>,
+}
+
+// Everything which lasts from one input to the next
+#[derive(Default)]
+struct Session {
+  functions: Vec<Function>,
+  // Every variable defined so far along with its type and value, in the order they were first defined
+  vars: Vec<(String, Type, RuntimeValue)>,
+  memory: Memory,
+  // How many inputs there have been, for naming the function each instruction runs in
+  inputs: usize,
+}
+
+impl Session {
+  // Handles one complete input, returning false once the REPL should stop
+  fn eval(&mut self, input: &str) -> Result<bool, String> {
+    self.inputs += 1;
+    if let Some(command) = input.strip_prefix(':') {
+      let (command, arg) = command
+        .split_once(char::is_whitespace)
+        .map_or((command, ""), |(c, a)| (c, a.trim()));
+      match command {
+        "load" if !arg.is_empty() => self.load(Path::new(arg))?,
+        "env" => {
+          for (name, ty, value) in &self.vars {
+            println!("{name}: {ty} = {value}");
+          }
+        }
+        "heap" => {
+          for (base, values) in self.memory.allocations() {
+            let values: Vec<String> = values
+              .iter()
+              .map(|v| {
+                v.as_ref()
+                  .map_or_else(|| "<uninitialized>".to_string(), ToString::to_string)
+              })
+              .collect();
+            println!("{base}: [{}]", values.join(", "));
+          }
+        }
+        "funcs" => {
+          for func in &self.functions {
+            println!("{}", signature(func));
+          }
+        }
+        "help" => println!("{HELP}"),
+        "quit" => return Ok(false),
+        _ => return Err(format!("unknown command :{command}, see :help")),
+      }
+    } else if input.starts_with('@') || is_import(input) {
+      let prog =
+        bril2json::parse_abstract_program_from_str(input, true).map_err(|e| e.to_string())?;
+      self.define(
+        Program::try_from(prog).map_err(|e| e.to_string())?,
+        Path::new("."),
+      )?;
+    } else {
+      match bril2json::parse_abstract_code(input, false).map_err(|e| e.to_string())? {
+        AbstractCode::Label { .. } => {
+          return Err("labels can only be used inside a function".to_string())
+        }
+        AbstractCode::Instruction(instr) => {
+          self.run(Instruction::try_from(instr).map_err(|e| e.to_string())?)?;
+        }
+      }
+    }
+    Ok(true)
+  }
+
+  fn load(&mut self, path: &Path) -> Result<(), String> {
+    let prog = load_file(path)?;
+    self.define(prog, path.parent().unwrap_or_else(|| Path::new(".")))
+  }
+
+  // Adds the functions of ```prog``` and everything they import relative to ```dir```, as long as all of the functions still type check together
+  fn define(&mut self, mut prog: Program, dir: &Path) -> Result<(), String> {
+    Linker::new(Vec::new(), load_file)
+      .link(&mut prog, dir)
+      .map_err(|e| e.to_string())?;
+    let mut functions = self.functions.clone();
+    for func in prog.functions {
+      match functions.iter_mut().find(|f| f.name == func.name) {
+        Some(old) => *old = func,
+        None => functions.push(func),
+      }
+    }
+    check(functions.clone())?;
+    self.functions = functions;
+    Ok(())
+  }
+
+  // Runs ```instr``` in a function of its own which takes the variables it uses as arguments and returns whatever it defines
+  fn run(&mut self, instr: Instruction) -> Result<(), String> {
+    let (args, dest) = match &instr {
+      Instruction::Constant {
+        dest, const_type, ..
+      } => (&[][..], Some((dest.clone(), const_type.clone()))),
+      Instruction::Value {
+        args,
+        dest,
+        op_type,
+        ..
+      } => (&args[..], Some((dest.clone(), op_type.clone()))),
+      Instruction::Effect {
+        op: op @ (EffectOps::Jump | EffectOps::Branch | EffectOps::Return),
+        ..
+      } => return Err(format!("{op} can only be used inside a function")),
+      Instruction::Effect {
+        op: op @ (EffectOps::Speculate | EffectOps::Commit | EffectOps::Guard),
+        ..
+      } => return Err(format!("{op} is not supported")),
+      Instruction::Effect { args, .. } => (&args[..], None),
+    };
+
+    // Variables which haven't been defined are left for the type checker to report
+    let mut params: Vec<Argument> = Vec::new();
+    let mut values = Vec::new();
+    for arg in args {
+      if params.iter().any(|p| &p.name == arg) {
+        continue;
+      }
+      if let Some((name, ty, value)) = self.vars.iter().find(|(name, _, _)| name == arg) {
+        params.push(Argument {
+          name: name.clone(),
+          arg_type: ty.clone(),
+        });
+        values.push(value.clone());
+      }
+    }
+
+    let name = format!("input {}", self.inputs);
+    let mut instrs = vec![Code::Instruction(instr)];
+    if let Some((dest, _)) = &dest {
+      instrs.push(Code::Instruction(Instruction::Effect {
+        args: vec![dest.clone()],
+        funcs: Vec::new(),
+        labels: Vec::new(),
+        op: EffectOps::Return,
+        pos: None,
+        attrs: Attrs::new(),
+      }));
+    }
+    let mut functions = self.functions.clone();
+    functions.push(Function {
+      args: params,
+      instrs,
+      name: name.clone(),
+      pos: None,
+      return_type: dest.as_ref().map(|(_, ty)| ty.clone()),
+      attrs: Attrs::new(),
+    });
+    let bbprog = check(functions)?;
+
+    let mut out = std::io::stdout();
+    let mut interp = Interpreter::with_memory(
+      &bbprog,
+      &mut out,
+      Options::default(),
+      std::mem::take(&mut self.memory),
+    );
+    let result = interp.call_function(&name, &values);
+    self.memory = interp.into_memory();
+    out.flush().map_err(|e| e.to_string())?;
+
+    if let (Some((dest, ty)), Some(value)) = (dest, result.map_err(|e| e.to_string())?) {
+      match self.vars.iter_mut().find(|(name, _, _)| *name == dest) {
+        Some(var) => *var = (dest, ty, value),
+        None => self.vars.push((dest, ty, value)),
+      }
+    }
+    Ok(())
+  }
+}
+
+// Builds and type checks a program out of ```functions```
+fn check(functions: Vec<Function>) -> Result<BBProgram, String> {
+  let bbprog = BBProgram::new(Program {
+    functions,
+    imports: Vec::new(),
+    attrs: Attrs::new(),
+  })
+  .map_err(|e| e.to_string())?;
+  type_check(&bbprog).map_err(|e| e.to_string())?;
+  Ok(bbprog)
+}
+
+// Reads a Bril program from ```path```, which is Bril text unless it ends in `.json`
+fn load_file(path: &Path) -> Result<Program, String> {
+  let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+  if path.extension().is_some_and(|ext| ext == "json") {
+    serde_json::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))
+  } else {
+    let prog = bril2json::parse_abstract_program_from_str(&text, true)
+      .map_err(|e| format!("{}: {e}", path.display()))?;
+    Program::try_from(prog).map_err(|e| format!("{}: {e}", path.display()))
+  }
+}
+
+fn signature(func: &Function) -> String {
+  let args: Vec<String> = func
+    .args
+    .iter()
+    .map(|a| format!("{}: {}", a.name, a.arg_type))
+    .collect();
+  let ret = func
+    .return_type
+    .as_ref()
+    .map_or_else(String::new, |ty| format!(": {ty}"));
+  format!("@{}({}){ret}", func.name, args.join(", "))
+}
+
+// Whether ```input``` starts with an import, since `from` could also be the name of a variable
+fn is_import(input: &str) -> bool {
+  input
+    .strip_prefix("from")
+    .is_some_and(|rest| rest.trim_start().starts_with('"'))
+}
+
+// How many more braces ```text``` opens than it closes, which is more than zero while a function is still being typed
+fn open_braces(text: &str) -> i64 {
+  let mut depth = 0;
+  for line in text.lines() {
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+      match c {
+        // A char literal could be a brace or the start of a comment
+        '\'' => {
+          chars.nth(1);
+        }
+        '#' => break,
+        '{' => depth += 1,
+        '}' => depth -= 1,
+        _ => {}
+      }
+    }
+  }
+  depth
+}
+
+fn main() {
+  let args = Cli::parse();
+  let mut session = Session::default();
+  for file in &args.files {
+    if let Err(e) = session.load(Path::new(file)) {
+      eprintln!("error: {e}");
+      std::process::exit(2)
+    }
+  }
+
+  // Prompts would only get in the way of output which is being piped somewhere
+  let interactive = std::io::stdin().is_terminal();
+  let mut lines = std::io::stdin().lock().lines();
+  let mut pending = String::new();
+  loop {
+    if interactive {
+      print!("{}", if pending.is_empty() { ">> " } else { ".. " });
+      std::io::stdout().flush().unwrap();
+    }
+    let Some(Ok(line)) = lines.next() else {
+      if interactive {
+        println!();
+      }
+      break;
+    };
+    pending.push_str(&line);
+    pending.push('\n');
+    if open_braces(&pending) > 0 {
+      continue;
+    }
+    let input = std::mem::take(&mut pending);
+    let input = input.trim();
+    if input.is_empty() || input.starts_with('#') {
+      continue;
+    }
+    match session.eval(input) {
+      Ok(true) => {}
+      Ok(false) => break,
+      Err(e) => eprintln!("error: {e}"),
+    }
+  }
+}
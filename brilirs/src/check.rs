@@ -1,12 +1,17 @@
 use crate::{
-  basic_block::{BBFunction, BBProgram},
+  basic_block::{BBFunction, BBProgram, BasicBlock},
   error::PositionalInterpError,
 };
-use bril_rs::{ConstOps, EffectOps, Instruction, Type, ValueOps};
+use bril_rs::{
+  ConstOps, EffectOps, Function, Instruction, Literal, Position, SourcePosition, Type, ValueOps,
+};
 
 use crate::error::InterpError;
 
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::fmt::Display;
 
 #[inline(always)]
 const fn check_num_args(expected: usize, args: &[String]) -> Result<(), InterpError> {
@@ -50,10 +55,13 @@ fn update_env<'a>(
   dest: &'a str,
   typ: &'a Type,
 ) -> Result<(), InterpError> {
-  match env.get(dest) {
-    Some(current_typ) => check_asmt_type(current_typ, typ),
-    None => {
-      env.insert(dest, typ);
+  if let Type::Tuple(_) = typ {
+    return Err(InterpError::TupleVariable(dest.to_string(), typ.clone()));
+  }
+  match env.entry(dest) {
+    Entry::Occupied(current_typ) => check_asmt_type(current_typ.get(), typ),
+    Entry::Vacant(e) => {
+      e.insert(typ);
       Ok(())
     }
   }
@@ -82,12 +90,37 @@ fn get_ptr_type(typ: &bril_rs::Type) -> Result<&bril_rs::Type, InterpError> {
   }
 }
 
+// The types of the arguments and result of the function named ```name```, or of the intrinsic by that name if the program doesn't define one
+fn callee<'a>(
+  prog: &'a BBProgram,
+  name: &str,
+) -> Result<(Vec<&'a Type>, Option<&'a Type>), InterpError> {
+  match (prog.get(name), prog.get_intrinsic(name)) {
+    (Some(func), _) => Ok((
+      func.args.iter().map(|a| &a.arg_type).collect(),
+      func.return_type.as_ref(),
+    )),
+    (None, Some(intrinsic)) => Ok((intrinsic.args().iter().collect(), intrinsic.return_type())),
+    (None, None) => Err(InterpError::FuncNotFound(name.to_string())),
+  }
+}
+
+// ```assigned``` holds every variable which is an argument of ```func``` or assigned to anywhere in it
 fn type_check_instruction<'a>(
   instr: &'a Instruction,
   func: &BBFunction,
   prog: &BBProgram,
   env: &mut FxHashMap<&'a str, &'a Type>,
+  assigned: &FxHashSet<&str>,
 ) -> Result<(), InterpError> {
+  if let Instruction::Value {
+    op, extra_dests, ..
+  } = instr
+  {
+    if *op != ValueOps::Call && !extra_dests.is_empty() {
+      return Err(InterpError::ExtraDests(*op));
+    }
+  }
   match instr {
     Instruction::Constant {
       op: ConstOps::Const,
@@ -95,20 +128,26 @@ fn type_check_instruction<'a>(
       const_type,
       value,
       pos: _,
+      attrs: _,
     } => {
-      if !(const_type == &Type::Float && value.get_type() == Type::Int) {
-        check_asmt_type(const_type, &value.get_type())?;
+      // Integer literals can be floats, and JSON can't tell a `str` of one character from a `char`
+      match (const_type, value.get_type()) {
+        (Type::Pointer(_), _) => return Err(InterpError::PointerConst(const_type.clone())),
+        (Type::Float, Type::Int) | (Type::Str, Type::Char) => {}
+        (_, literal_type) => check_asmt_type(const_type, &literal_type)?,
       }
       update_env(env, dest, const_type)
     }
     Instruction::Value {
-      op: ValueOps::Add | ValueOps::Sub | ValueOps::Mul | ValueOps::Div,
+      op: ValueOps::Add | ValueOps::Sub | ValueOps::Mul | ValueOps::Div | ValueOps::Rem,
       dest,
       op_type,
       args,
       funcs,
       labels,
       pos: _,
+      extra_dests: _,
+      attrs: _,
     } => {
       check_num_args(2, args)?;
       check_num_funcs(0, funcs)?;
@@ -126,6 +165,8 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      extra_dests: _,
+      attrs: _,
     } => {
       check_num_args(2, args)?;
       check_num_funcs(0, funcs)?;
@@ -143,6 +184,8 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      extra_dests: _,
+      attrs: _,
     } => {
       check_num_args(1, args)?;
       check_num_funcs(0, funcs)?;
@@ -159,6 +202,8 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      extra_dests: _,
+      attrs: _,
     } => {
       check_num_args(2, args)?;
       check_num_funcs(0, funcs)?;
@@ -176,11 +221,21 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      extra_dests: _,
+      attrs: _,
     } => {
       check_num_args(1, args)?;
       check_num_funcs(0, funcs)?;
       check_num_labels(0, labels)?;
-      check_asmt_type(op_type, get_type(env, 0, args)?)?;
+      // Types are compared whole, so `ptr<ptr<int>>` is never the same as `ptr<int>`
+      let arg_type = get_type(env, 0, args)?;
+      if op_type != *arg_type {
+        return Err(InterpError::BadIdType(
+          args[0].clone(),
+          (*arg_type).clone(),
+          op_type.clone(),
+        ));
+      }
       update_env(env, dest, op_type)
     }
     Instruction::Value {
@@ -191,6 +246,8 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      extra_dests: _,
+      attrs: _,
     } => {
       check_num_args(2, args)?;
       check_num_funcs(0, funcs)?;
@@ -208,6 +265,8 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      extra_dests: _,
+      attrs: _,
     } => {
       check_num_args(2, args)?;
       check_num_funcs(0, funcs)?;
@@ -217,6 +276,153 @@ fn type_check_instruction<'a>(
       check_asmt_type(&Type::Bool, op_type)?;
       update_env(env, dest, op_type)
     }
+    Instruction::Value {
+      op: ValueOps::Float2bits,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+      extra_dests: _,
+      attrs: _,
+    } => {
+      check_num_args(1, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      check_asmt_type(&Type::Float, get_type(env, 0, args)?)?;
+      check_asmt_type(&Type::Int, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Bits2float,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+      extra_dests: _,
+      attrs: _,
+    } => {
+      check_num_args(1, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      check_asmt_type(&Type::Int, get_type(env, 0, args)?)?;
+      check_asmt_type(&Type::Float, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Ceq | ValueOps::Clt | ValueOps::Cgt | ValueOps::Cle | ValueOps::Cge,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+      extra_dests: _,
+      attrs: _,
+    } => {
+      check_num_args(2, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      check_asmt_type(&Type::Char, get_type(env, 0, args)?)?;
+      check_asmt_type(&Type::Char, get_type(env, 1, args)?)?;
+      check_asmt_type(&Type::Bool, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Char2int,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+      extra_dests: _,
+      attrs: _,
+    } => {
+      check_num_args(1, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      check_asmt_type(&Type::Char, get_type(env, 0, args)?)?;
+      check_asmt_type(&Type::Int, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Int2char,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+      extra_dests: _,
+      attrs: _,
+    } => {
+      check_num_args(1, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      check_asmt_type(&Type::Int, get_type(env, 0, args)?)?;
+      check_asmt_type(&Type::Char, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Concat,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+      extra_dests: _,
+      attrs: _,
+    } => {
+      check_num_args(2, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      check_asmt_type(&Type::Str, get_type(env, 0, args)?)?;
+      check_asmt_type(&Type::Str, get_type(env, 1, args)?)?;
+      check_asmt_type(&Type::Str, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Length,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+      extra_dests: _,
+      attrs: _,
+    } => {
+      check_num_args(1, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      check_asmt_type(&Type::Str, get_type(env, 0, args)?)?;
+      check_asmt_type(&Type::Int, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Index,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+      extra_dests: _,
+      attrs: _,
+    } => {
+      check_num_args(2, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      check_asmt_type(&Type::Str, get_type(env, 0, args)?)?;
+      check_asmt_type(&Type::Int, get_type(env, 1, args)?)?;
+      check_asmt_type(&Type::Char, op_type)?;
+      update_env(env, dest, op_type)
+    }
     Instruction::Value {
       op: ValueOps::Call,
       dest,
@@ -225,20 +431,79 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      extra_dests,
+      attrs: _,
     } => {
       check_num_funcs(1, funcs)?;
       check_num_labels(0, labels)?;
-      let callee_func = prog
-        .func_index
+      let (expected_args, return_type) = callee(prog, &funcs[0])?;
+
+      if args.len() != expected_args.len() {
+        return Err(InterpError::BadNumArgs(expected_args.len(), args.len()));
+      }
+      args
+        .iter()
+        .zip(expected_args)
+        .try_for_each(|(arg_name, expected_arg)| {
+          let ty = env
+            .get(arg_name as &str)
+            .ok_or_else(|| InterpError::VarUndefined(arg_name.to_string()))?;
+
+          check_asmt_type(ty, expected_arg)
+        })?;
+
+      match return_type {
+        None => return Err(InterpError::NonEmptyRetForFunc(funcs[0].clone())),
+        // Each variable the call assigns gets the value returned in the same place
+        Some(Type::Tuple(types)) => {
+          if types.len() != extra_dests.len() + 1 {
+            return Err(InterpError::BadNumDests(
+              funcs[0].clone(),
+              types.len(),
+              extra_dests.len() + 1,
+            ));
+          }
+          check_asmt_type(op_type, &types[0])?;
+          for (extra, t) in extra_dests.iter().zip(&types[1..]) {
+            check_asmt_type(&extra.arg_type, t)?;
+            update_env(env, &extra.name, &extra.arg_type)?;
+          }
+        }
+        Some(_) if !extra_dests.is_empty() => {
+          return Err(InterpError::BadNumDests(
+            funcs[0].clone(),
+            1,
+            extra_dests.len() + 1,
+          ));
+        }
+        Some(t) => check_asmt_type(op_type, t)?,
+      }
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Spawn,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+      extra_dests: _,
+      attrs: _,
+    } => {
+      check_num_funcs(1, funcs)?;
+      check_num_labels(0, labels)?;
+      // Intrinsics run in place, so only functions of the program can be run on their own thread
+      let callee = prog
         .get(&funcs[0])
         .ok_or_else(|| InterpError::FuncNotFound(funcs[0].clone()))?;
 
-      if args.len() != callee_func.args.len() {
-        return Err(InterpError::BadNumArgs(callee_func.args.len(), args.len()));
+      if args.len() != callee.args.len() {
+        return Err(InterpError::BadNumArgs(callee.args.len(), args.len()));
       }
       args
         .iter()
-        .zip(callee_func.args.iter())
+        .zip(&callee.args)
         .try_for_each(|(arg_name, expected_arg)| {
           let ty = env
             .get(arg_name as &str)
@@ -247,10 +512,35 @@ fn type_check_instruction<'a>(
           check_asmt_type(ty, &expected_arg.arg_type)
         })?;
 
-      match &callee_func.return_type {
-        None => Err(InterpError::NonEmptyRetForFunc(callee_func.name.clone())),
-        Some(t) => check_asmt_type(op_type, t),
-      }?;
+      // Nothing can be given back by a join, so threads can't return anything
+      if callee.return_type.is_some() {
+        return Err(InterpError::NonEmptyRetForFunc(funcs[0].clone()));
+      }
+      check_asmt_type(&Type::Thread, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Rand,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+      extra_dests: _,
+      attrs: _,
+    } => {
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      // A float needs no range, while an int is drawn from between its two arguments
+      if *op_type == Type::Float {
+        check_num_args(0, args)?;
+      } else {
+        check_num_args(2, args)?;
+        check_asmt_type(&Type::Int, get_type(env, 0, args)?)?;
+        check_asmt_type(&Type::Int, get_type(env, 1, args)?)?;
+        check_asmt_type(&Type::Int, op_type)?;
+      }
       update_env(env, dest, op_type)
     }
     Instruction::Value {
@@ -261,14 +551,26 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      extra_dests: _,
+      attrs: _,
     } => {
       if args.len() != labels.len() {
         return Err(InterpError::UnequalPhiNode);
       }
       check_num_funcs(0, funcs)?;
+      if let Some(label) = labels
+        .iter()
+        .find(|l| !func.blocks.iter().any(|b| b.label.as_ref() == Some(l)))
+      {
+        return Err(InterpError::PhiMissingLabel(label.clone()));
+      }
       // Phi nodes are a little weird with their args and there has been some discussion on an _undefined var name in #108
       // Instead, we are going to assign the type we expect to all of the args and this will trigger an error if any of these args ends up being a different type.
-      args.iter().try_for_each(|a| update_env(env, a, op_type))?;
+      // Args which are never assigned to, like the `__undefined` which SSA construction uses for paths without a definition, leave the destination undefined instead, so they can stand in for any type.
+      args
+        .iter()
+        .filter(|a| assigned.contains(a.as_str()))
+        .try_for_each(|a| update_env(env, a, op_type))?;
 
       update_env(env, dest, op_type)
     }
@@ -280,6 +582,8 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      extra_dests: _,
+      attrs: _,
     } => {
       check_num_args(1, args)?;
       check_num_funcs(0, funcs)?;
@@ -296,6 +600,8 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      extra_dests: _,
+      attrs: _,
     } => {
       check_num_args(1, args)?;
       check_num_funcs(0, funcs)?;
@@ -312,6 +618,8 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      extra_dests: _,
+      attrs: _,
     } => {
       check_num_args(2, args)?;
       check_num_funcs(0, funcs)?;
@@ -328,6 +636,7 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      attrs: _,
     } => {
       check_num_args(0, args)?;
       check_num_funcs(0, funcs)?;
@@ -340,6 +649,7 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      attrs: _,
     } => {
       check_num_args(1, args)?;
       check_asmt_type(&Type::Bool, get_type(env, 0, args)?)?;
@@ -353,14 +663,41 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      attrs: _,
     } => {
       check_num_funcs(0, funcs)?;
       check_num_labels(0, labels)?;
       match &func.return_type {
+        Some(t) if args.is_empty() => {
+          Err(InterpError::EmptyRetForFunc(func.name.clone(), t.clone()))
+        }
+        Some(Type::Tuple(types)) => {
+          check_num_args(types.len(), args)?;
+          let actual = (0..args.len())
+            .map(|i| Ok((*get_type(env, i, args)?).clone()))
+            .collect::<Result<Vec<_>, InterpError>>()?;
+          if *types == actual {
+            Ok(())
+          } else {
+            Err(InterpError::BadRetType(
+              func.name.clone(),
+              Type::Tuple(types.clone()),
+              Type::Tuple(actual),
+            ))
+          }
+        }
         Some(t) => {
           check_num_args(1, args)?;
           let ty0 = get_type(env, 0, args)?;
-          check_asmt_type(t, ty0)
+          if t == *ty0 {
+            Ok(())
+          } else {
+            Err(InterpError::BadRetType(
+              func.name.clone(),
+              t.clone(),
+              (*ty0).clone(),
+            ))
+          }
         }
         None => {
           if args.is_empty() {
@@ -377,9 +714,11 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      attrs: _,
     } => {
       check_num_funcs(0, funcs)?;
       check_num_labels(0, labels)?;
+      // Every type can be printed, so any number of arguments of any types only have to be defined
       args.iter().enumerate().try_for_each(|(i, _)| {
         get_type(env, i, args)?;
         Ok(())
@@ -391,42 +730,55 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      attrs: _,
     } => {
       check_num_args(0, args)?;
       check_num_funcs(0, funcs)?;
       check_num_labels(0, labels)?;
       Ok(())
     }
+    Instruction::Effect {
+      op: EffectOps::Join,
+      args,
+      funcs,
+      labels,
+      pos: _,
+      attrs: _,
+    } => {
+      check_num_args(1, args)?;
+      check_asmt_type(&Type::Thread, get_type(env, 0, args)?)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      Ok(())
+    }
     Instruction::Effect {
       op: EffectOps::Call,
       args,
       funcs,
       labels,
       pos: _,
+      attrs: _,
     } => {
       check_num_funcs(1, funcs)?;
       check_num_labels(0, labels)?;
-      let callee_func = prog
-        .func_index
-        .get(&funcs[0])
-        .ok_or_else(|| InterpError::FuncNotFound(funcs[0].clone()))?;
+      let (expected_args, return_type) = callee(prog, &funcs[0])?;
 
-      if args.len() != callee_func.args.len() {
-        return Err(InterpError::BadNumArgs(callee_func.args.len(), args.len()));
+      if args.len() != expected_args.len() {
+        return Err(InterpError::BadNumArgs(expected_args.len(), args.len()));
       }
       args
         .iter()
-        .zip(callee_func.args.iter())
+        .zip(expected_args)
         .try_for_each(|(arg_name, expected_arg)| {
           let ty = env
             .get(arg_name as &str)
             .ok_or_else(|| InterpError::VarUndefined(arg_name.to_string()))?;
 
-          check_asmt_type(ty, &expected_arg.arg_type)
+          check_asmt_type(ty, expected_arg)
         })?;
 
-      if callee_func.return_type.is_some() {
-        Err(InterpError::NonEmptyRetForFunc(callee_func.name.clone()))
+      if return_type.is_some() {
+        Err(InterpError::NonEmptyRetForFunc(funcs[0].clone()))
       } else {
         Ok(())
       }
@@ -437,6 +789,7 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      attrs: _,
     } => {
       check_num_args(2, args)?;
       check_num_funcs(0, funcs)?;
@@ -451,6 +804,7 @@ fn type_check_instruction<'a>(
       funcs,
       labels,
       pos: _,
+      attrs: _,
     } => {
       check_num_args(1, args)?;
       check_num_funcs(0, funcs)?;
@@ -459,49 +813,900 @@ fn type_check_instruction<'a>(
       Ok(())
     }
     Instruction::Effect {
-      op: EffectOps::Speculate | EffectOps::Guard | EffectOps::Commit,
-      args: _,
-      funcs: _,
-      labels: _,
+      op: EffectOps::Speculate | EffectOps::Commit,
+      args,
+      funcs,
+      labels,
+      pos: _,
+      attrs: _,
+    } => {
+      check_num_args(0, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      Ok(())
+    }
+    Instruction::Effect {
+      op: EffectOps::Guard,
+      args,
+      funcs,
+      labels,
       pos: _,
+      attrs: _,
     } => {
-      unimplemented!()
+      check_num_args(1, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(1, labels)?;
+      check_asmt_type(&Type::Bool, get_type(env, 0, args)?)
     }
   }
 }
 
-fn type_check_func(bbfunc: &BBFunction, bbprog: &BBProgram) -> Result<(), PositionalInterpError> {
+// Checks every reachable instruction of ```bbfunc```, adding any errors to ```errors``` instead of stopping at the first one
+fn type_check_func(
+  bbfunc: &BBFunction,
+  bbprog: &BBProgram,
+  errors: &mut Vec<PositionalInterpError>,
+) {
   let mut env: FxHashMap<&str, &Type> =
     FxHashMap::with_capacity_and_hasher(20, fxhash::FxBuildHasher::default());
   bbfunc.args.iter().for_each(|a| {
+    if let Type::Tuple(_) = a.arg_type {
+      errors
+        .push(InterpError::TupleVariable(a.name.clone(), a.arg_type.clone()).add_pos(bbfunc.pos));
+    }
     env.insert(&a.name, &a.arg_type);
   });
+  let assigned: FxHashSet<&str> = bbfunc
+    .args
+    .iter()
+    .map(|a| a.name.as_str())
+    .chain(bbfunc.blocks.iter().flat_map(|b| &b.instrs).flat_map(|i| {
+      match i {
+        Instruction::Constant { dest, .. } => vec![dest.as_str()],
+        Instruction::Value {
+          dest, extra_dests, ..
+        } => std::iter::once(dest.as_str())
+          .chain(extra_dests.iter().map(|d| d.name.as_str()))
+          .collect(),
+        Instruction::Effect { .. } => Vec::new(),
+      }
+    }))
+    .collect();
 
   let mut work_list = vec![0];
   let mut done_list = Vec::new();
 
   while let Some(b) = work_list.pop() {
     let block = bbfunc.blocks.get(b).unwrap();
-    block.instrs.iter().try_for_each(|i| {
-      type_check_instruction(i, bbfunc, bbprog, &mut env).map_err(|e| e.add_pos(i.get_pos()))
-    })?;
+    block.instrs.iter().for_each(|i| {
+      if let Err(e) = type_check_instruction(i, bbfunc, bbprog, &mut env, &assigned) {
+        errors.push(e.add_pos(i.get_pos()));
+        // Give the destination its declared type anyway so that later uses of it don't also report it as undefined
+        match i {
+          Instruction::Constant {
+            dest, const_type, ..
+          } => {
+            env.entry(dest).or_insert(const_type);
+          }
+          Instruction::Value {
+            dest,
+            op_type,
+            extra_dests,
+            ..
+          } => {
+            env.entry(dest).or_insert(op_type);
+            for extra in extra_dests {
+              env.entry(&extra.name).or_insert(&extra.arg_type);
+            }
+          }
+          Instruction::Effect { .. } => {}
+        }
+      }
+    });
+    if let Some(return_type) = &bbfunc.return_type {
+      if falls_off_end(block) {
+        let pos = block
+          .instrs
+          .last()
+          .and_then(Instruction::get_pos)
+          .or(block.label_pos)
+          .or(bbfunc.pos);
+        errors
+          .push(InterpError::MissingReturn(bbfunc.name.clone(), return_type.clone()).add_pos(pos));
+      }
+    }
     done_list.push(b);
-    block.exit.iter().for_each(|e| {
-      if !done_list.contains(e) && !work_list.contains(e) {
-        work_list.push(*e)
+    block.successors().for_each(|e| {
+      if !done_list.contains(&e) && !work_list.contains(&e) {
+        work_list.push(e)
       }
     })
   }
 
-  Ok(())
+  check_speculation(bbfunc, errors);
+}
+
+// Whether running to the end of ```block``` ends its function without a `ret`
+fn falls_off_end(block: &BasicBlock) -> bool {
+  block.exit.is_empty()
+    && !matches!(
+      block.instrs.last(),
+      Some(Instruction::Effect {
+        op: EffectOps::Return,
+        ..
+      })
+    )
+}
+
+// Reports every guard in ```bbfunc``` which could run without a speculation to fall back out of, by finding the fewest speculations which can have been started and not committed at the start of each block
+fn check_speculation(bbfunc: &BBFunction, errors: &mut Vec<PositionalInterpError>) {
+  // The number of speculations is unknown for blocks which haven't been reached yet
+  let mut depths: Vec<Option<usize>> = vec![None; bbfunc.blocks.len()];
+  depths[0] = Some(0);
+  let mut work_list = vec![0];
+  while let Some(b) = work_list.pop() {
+    let block = &bbfunc.blocks[b];
+    let mut depth = depths[b].unwrap_or_default();
+    let mut successors = Vec::new();
+    for (instr, numified) in block.instrs.iter().zip(&block.numified_instrs) {
+      // Only guards have a target, and failing one goes back to before the innermost speculation
+      if let Some(target) = numified.target {
+        successors.push((target, depth.saturating_sub(1)));
+      }
+      depth = depth_after(instr, depth);
+    }
+    successors.extend(block.exit.iter().map(|e| (*e, depth)));
+    for (succ, depth) in successors {
+      if depths[succ].is_none_or(|d| depth < d) {
+        depths[succ] = Some(depth);
+        work_list.push(succ);
+      }
+    }
+  }
+
+  for (block, depth) in bbfunc.blocks.iter().zip(depths) {
+    let Some(mut depth) = depth else { continue };
+    for instr in &block.instrs {
+      if let Instruction::Effect {
+        op: EffectOps::Guard,
+        pos,
+        ..
+      } = instr
+      {
+        if depth == 0 {
+          errors.push(InterpError::GuardOutsideSpeculation.add_pos(*pos));
+        }
+      }
+      depth = depth_after(instr, depth);
+    }
+  }
+}
+
+// The number of speculations in progress after ```instr``` runs with ```depth``` of them in progress before it
+const fn depth_after(instr: &Instruction, depth: usize) -> usize {
+  match instr {
+    Instruction::Effect {
+      op: EffectOps::Speculate,
+      ..
+    } => depth + 1,
+    Instruction::Effect {
+      op: EffectOps::Commit,
+      ..
+    } => depth.saturating_sub(1),
+    _ => depth,
+  }
 }
 
 /// Provides validation of Bril programs. This involves
 /// statically checking the types and number of arguments to Bril
 /// instructions.
 pub fn type_check(bbprog: &BBProgram) -> Result<(), PositionalInterpError> {
-  bbprog
-    .func_index
+  bbprog.functions().try_for_each(|bbfunc| {
+    let mut errors = Vec::new();
+    type_check_func(bbfunc, bbprog, &mut errors);
+    errors.into_iter().next().map_or(Ok(()), Err)
+  })
+}
+
+/// How serious a [`Diagnostic`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+  /// The program is not valid Bril and can't be run
+  Error,
+  /// The program is valid but probably doesn't do what was meant, like when it has code which never runs
+  Warning,
+}
+
+/// A problem found by [`type_check_all`]
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+  /// The function the problem was found in, if it is specific to one function
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub function: Option<String>,
+  /// The line of the instruction with the problem, if the program has source positions
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub line: Option<u64>,
+  /// The column of the instruction with the problem, if the program has source positions
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub column: Option<u64>,
+  /// Where the instruction with the problem came from in the source of the frontend which generated the program, if it says
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub source: Option<SourcePosition>,
+  /// Whether the problem stops the program from running
+  pub severity: Severity,
+  /// A description of the problem
+  pub message: String,
+}
+
+impl Diagnostic {
+  // A problem in ```function``` at ```pos```
+  fn new(function: &str, pos: Option<Position>, severity: Severity, message: String) -> Self {
+    Self {
+      function: Some(function.to_string()),
+      line: pos.map(|p| p.row),
+      column: pos.map(|p| p.col),
+      source: pos.and_then(|p| p.source),
+      severity,
+      message,
+    }
+  }
+}
+
+impl Display for Diagnostic {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if let Some(source) = self.source {
+      write!(f, "{source}: ")?;
+    } else if let (Some(line), Some(column)) = (self.line, self.column) {
+      write!(f, "{line}:{column}: ")?;
+    }
+    if self.severity == Severity::Warning {
+      write!(f, "warning: ")?;
+    }
+    write!(f, "{}", self.message)
+  }
+}
+
+/// The same checks as [`type_check`] except that every problem in the program is reported instead of just the first one. On top of the errors that [`type_check`] finds, there are warnings for variables which are assigned to but never used, labels which nothing goes to, blocks which can never run, and loops which never end once they are entered, because nothing leaves them or because every way out is a `br` on a condition which the loop never updates. The diagnostics are ordered by function and then by source position.
+pub fn type_check_all(bbprog: &BBProgram) -> Vec<Diagnostic> {
+  in_program_order(bbprog.functions())
+    .into_iter()
+    .flat_map(|bbfunc| check_function(bbfunc, bbprog))
+    .collect()
+}
+
+// ```funcs``` in the order that type_check_all reports them in
+fn in_program_order<'a>(funcs: impl Iterator<Item = &'a BBFunction>) -> Vec<&'a BBFunction> {
+  let mut funcs: Vec<&BBFunction> = funcs.collect();
+  funcs.sort_by_key(|f| (f.pos.map(|p| (p.row, p.col)), &f.name));
+  funcs
+}
+
+// Every problem type_check_all finds in ```bbfunc```, in program order
+fn check_function(bbfunc: &BBFunction, bbprog: &BBProgram) -> Vec<Diagnostic> {
+  let mut errors = Vec::new();
+  type_check_func(bbfunc, bbprog, &mut errors);
+  let mut diagnostics: Vec<Diagnostic> = errors
+    .into_iter()
+    .map(|e| {
+      Diagnostic::new(
+        &bbfunc.name,
+        e.pos(),
+        Severity::Error,
+        e.error().to_string(),
+      )
+    })
+    .collect();
+  find_warnings(bbfunc, &mut diagnostics);
+  // Blocks are visited in control flow order so put them back in program order. Errors without a position stay where they were found.
+  diagnostics.sort_by_key(|d| d.line.zip(d.column).unwrap_or((u64::MAX, u64::MAX)));
+  diagnostics
+}
+
+/// Rejects programs which type check but rely on behaviour that Bril leaves unspecified, which `--strict` turns into errors. Like [`type_check`], only the first one is returned.
+///
+/// These are uses of a variable along a path where it might not have been assigned yet, found by a definite assignment analysis, and `int2char` of a constant which is not a Unicode scalar value. A `phi` is allowed to take a variable which hasn't been assigned, but it then leaves its destination unassigned along that path too. Falling off the end of a function which returns a value is already an error of [`type_check`], as are uses of variables which are never assigned anywhere.
+/// ```
+/// use brilirs::basic_block::BBProgram;
+/// use brilirs::check::{strict_check, type_check};
+///
+/// let text = "@main { c: bool = const true; br c .done .set; .set: x: int = const 1; .done: print x; }";
+/// let prog = bril_rs::Program::try_from(bril2json::parse_abstract_program_from_str(text, false).unwrap()).unwrap();
+/// let bbprog = BBProgram::new(prog).unwrap();
+/// assert!(type_check(&bbprog).is_ok());
+/// assert_eq!(strict_check(&bbprog).unwrap_err().to_string(), "`x` might be used before it has been assigned");
+/// ```
+pub fn strict_check(bbprog: &BBProgram) -> Result<(), PositionalInterpError> {
+  bbprog.functions().try_for_each(|bbfunc| {
+    strict_check_func(bbfunc)
+      .into_iter()
+      .next()
+      .map_or(Ok(()), Err)
+  })
+}
+
+/// The same checks as [`strict_check`] except that every violation in the program is reported, as errors ordered by function and then by source position like [`type_check_all`]
+pub fn strict_check_all(bbprog: &BBProgram) -> Vec<Diagnostic> {
+  in_program_order(bbprog.functions())
+    .into_iter()
+    .flat_map(|bbfunc| {
+      let mut diagnostics: Vec<Diagnostic> = strict_check_func(bbfunc)
+        .into_iter()
+        .map(|e| {
+          Diagnostic::new(
+            &bbfunc.name,
+            e.pos(),
+            Severity::Error,
+            e.error().to_string(),
+          )
+        })
+        .collect();
+      diagnostics.sort_by_key(|d| d.line.zip(d.column).unwrap_or((u64::MAX, u64::MAX)));
+      diagnostics
+    })
+    .collect()
+}
+
+// What strict_check finds in ```bbfunc```. Each variable is only reported the first time it might be used unassigned, in the order of the blocks
+fn strict_check_func(bbfunc: &BBFunction) -> Vec<PositionalInterpError> {
+  let num_vars = bbfunc.num_of_vars as usize;
+  // Variables which are never assigned anywhere are reported by type checking, or stand in for a missing value in a phi like `__undefined`
+  let mut assigned = vec![false; num_vars];
+  // The value of each variable whose only assignment is a `const` of an integer
+  let mut definitions = vec![0_usize; num_vars];
+  let mut consts: Vec<Option<i64>> = vec![None; num_vars];
+  for arg in &bbfunc.args_as_nums {
+    assigned[*arg as usize] = true;
+    definitions[*arg as usize] += 1;
+  }
+  for block in &bbfunc.blocks {
+    for (instr, numified) in block.instrs.iter().zip(&block.numified_instrs) {
+      for dest in numified.dest.iter().chain(&numified.extra_dests) {
+        assigned[*dest as usize] = true;
+        definitions[*dest as usize] += 1;
+      }
+      if let (
+        Instruction::Constant {
+          value: Literal::Int(value),
+          ..
+        },
+        Some(dest),
+      ) = (instr, numified.dest)
+      {
+        consts[dest as usize] = Some(*value);
+      }
+    }
+  }
+  for (value, definitions) in consts.iter_mut().zip(definitions) {
+    if definitions != 1 {
+      *value = None;
+    }
+  }
+
+  // The variables which are assigned along every path to the end of each block, or None for blocks which haven't been reached. Edges out of a block carry these as well, except for guards, which go back to what was assigned at the start of their speculation
+  let mut exits: Vec<Option<Vec<bool>>> = vec![None; bbfunc.blocks.len()];
+  let mut edges: Vec<Vec<(usize, Vec<bool>)>> = vec![Vec::new(); bbfunc.blocks.len()];
+  let mut entry_args = vec![false; num_vars];
+  for arg in &bbfunc.args_as_nums {
+    entry_args[*arg as usize] = true;
+  }
+  let entry = |edges: &[Vec<(usize, Vec<bool>)>], b: usize| -> Option<Vec<bool>> {
+    let mut incoming = edges
+      .iter()
+      .flatten()
+      .filter(|(to, _)| *to == b)
+      .map(|(_, set)| set)
+      .chain((b == 0).then_some(&entry_args));
+    let mut entry = incoming.next()?.clone();
+    for set in incoming {
+      entry.iter_mut().zip(set).for_each(|(a, b)| *a &= b);
+    }
+    Some(entry)
+  };
+
+  // The sets only ever shrink, so going over the blocks until nothing changes finishes
+  let mut changed = true;
+  while changed {
+    changed = false;
+    for b in 0..bbfunc.blocks.len() {
+      let Some(entry) = entry(&edges, b) else {
+        continue;
+      };
+      let (exit, out) = assign_block(bbfunc, b, entry, &exits, &assigned, |_, _| {});
+      if exits[b].as_ref() != Some(&exit) || edges[b] != out {
+        exits[b] = Some(exit);
+        edges[b] = out;
+        changed = true;
+      }
+    }
+  }
+
+  let mut errors = Vec::new();
+  let mut reported = vec![false; num_vars];
+  for b in 0..bbfunc.blocks.len() {
+    let Some(entry) = entry(&edges, b) else {
+      continue;
+    };
+    assign_block(bbfunc, b, entry, &exits, &assigned, |instr, var| {
+      if !std::mem::replace(&mut reported[var as usize], true) {
+        let name = bbfunc.var_names[var as usize].clone();
+        errors.push(InterpError::MaybeUnassigned(name).add_pos(instr.get_pos()));
+      }
+    });
+    for (instr, numified) in bbfunc.blocks[b]
+      .instrs
+      .iter()
+      .zip(&bbfunc.blocks[b].numified_instrs)
+    {
+      if let Instruction::Value {
+        op: ValueOps::Int2char,
+        pos,
+        ..
+      } = instr
+      {
+        if let Some(value) = numified.args.first().and_then(|a| consts[*a as usize]) {
+          if u32::try_from(value).ok().and_then(char::from_u32).is_none() {
+            errors.push(InterpError::InvalidChar(value).add_pos(*pos));
+          }
+        }
+      }
+    }
+  }
+  errors
+}
+
+// Runs block ```b``` of ```bbfunc``` starting with the variables which are definitely assigned in ```entry```, calling ```report``` with every use of a variable which might not be and the number of that variable. Returns what is assigned at the end of the block along with what each edge out of it carries. ```exits``` has what is assigned at the end of the other blocks so far, for phis
+fn assign_block(
+  bbfunc: &BBFunction,
+  b: usize,
+  entry: Vec<bool>,
+  exits: &[Option<Vec<bool>>],
+  assigned: &[bool],
+  mut report: impl FnMut(&Instruction, u32),
+) -> (Vec<bool>, Vec<(usize, Vec<bool>)>) {
+  let block = &bbfunc.blocks[b];
+  let mut assigned_here = entry.clone();
+  let mut edges = Vec::new();
+  // What was assigned at the start of each speculation in progress
+  let mut speculations: Vec<Vec<bool>> = Vec::new();
+  for (instr, numified) in block.instrs.iter().zip(&block.numified_instrs) {
+    let phi = matches!(
+      instr,
+      Instruction::Value {
+        op: ValueOps::Phi,
+        ..
+      }
+    );
+    if phi {
+      // The destination is only assigned when the argument taken from each block which can come here is
+      let Instruction::Value { labels, .. } = instr else {
+        unreachable!()
+      };
+      let takes_unassigned = (0..bbfunc.blocks.len())
+        .filter(|p| bbfunc.blocks[*p].exit.contains(&b))
+        .filter_map(|p| Some((bbfunc.blocks[p].label.as_ref()?, exits[p].as_ref()?)))
+        .any(|(label, exit)| {
+          labels
+            .iter()
+            .position(|l| l == label)
+            .and_then(|i| numified.args.get(i))
+            .is_some_and(|arg| !exit[*arg as usize])
+        });
+      if let Some(dest) = numified.dest {
+        assigned_here[dest as usize] = !takes_unassigned;
+      }
+      continue;
+    }
+    for arg in &numified.args {
+      if assigned[*arg as usize] && !assigned_here[*arg as usize] {
+        report(instr, *arg);
+      }
+    }
+    match instr {
+      Instruction::Effect {
+        op: EffectOps::Speculate,
+        ..
+      } => speculations.push(assigned_here.clone()),
+      Instruction::Effect {
+        op: EffectOps::Commit,
+        ..
+      } => {
+        speculations.pop();
+      }
+      _ => {}
+    }
+    if let Some(target) = numified.target {
+      // A speculation started in an earlier block is taken to have started with what this block does
+      let rolled_back = speculations.last().unwrap_or(&entry).clone();
+      edges.push((target, rolled_back));
+    }
+    for dest in numified.dest.iter().chain(&numified.extra_dests) {
+      assigned_here[*dest as usize] = true;
+    }
+  }
+  edges.extend(block.exit.iter().map(|e| (*e, assigned_here.clone())));
+  (assigned_here, edges)
+}
+
+/// Keeps the diagnostics of a program up to date as its functions are changed one at a time, for editors which check a program after every edit.
+///
+/// Checking a function only looks at itself and the signatures of the functions it calls, so changing one function only has to check it again, along with the functions which call it when its arguments or return type changed. Everything else keeps the diagnostics it had, which is what makes this much faster than [`type_check_all`] for large programs. The diagnostics are always the same as what [`type_check_all`] would give for the program as it is now.
+///
+/// ```
+/// use brilirs::basic_block::BBProgram;
+/// use brilirs::check::IncrementalCheck;
+///
+/// let parse = |text| bril_rs::Program::try_from(bril2json::parse_abstract_program_from_str(text, false).unwrap()).unwrap();
+/// let prog = parse("@main { x: int = call @f; print x; } @f: int { one: int = const 1; ret one; }");
+/// let mut check = IncrementalCheck::new(BBProgram::new(prog).unwrap());
+/// assert!(check.diagnostics().is_empty());
+///
+/// // @f now returns a bool, which the call in @main doesn't expect
+/// let changed = parse("@f: bool { t: bool = const true; ret t; }").functions.remove(0);
+/// let diagnostics = check.update(changed).unwrap();
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(diagnostics[0].function.as_deref(), Some("main"));
+/// ```
+pub struct IncrementalCheck {
+  bbprog: BBProgram,
+  // The diagnostics of each function which is defined, by name
+  diagnostics: FxHashMap<String, Vec<Diagnostic>>,
+  // The names of the functions which call each function, which might not be defined
+  callers: FxHashMap<String, FxHashSet<String>>,
+}
+
+impl IncrementalCheck {
+  /// Checks all of ```bbprog``` like [`type_check_all`], keeping what was found for when it changes
+  #[must_use]
+  pub fn new(bbprog: BBProgram) -> Self {
+    let mut check = Self {
+      bbprog,
+      diagnostics: FxHashMap::default(),
+      callers: FxHashMap::default(),
+    };
+    let names: Vec<String> = check.bbprog.functions().map(|f| f.name.clone()).collect();
+    for name in &names {
+      check.add_calls(name);
+    }
+    check.recheck(names);
+    check
+  }
+
+  /// The program as it is after every change so far
+  #[must_use]
+  pub const fn program(&self) -> &BBProgram {
+    &self.bbprog
+  }
+
+  /// Every problem in the program, in the same order as [`type_check_all`]
+  #[must_use]
+  pub fn diagnostics(&self) -> Vec<Diagnostic> {
+    in_program_order(self.bbprog.functions())
+      .into_iter()
+      .flat_map(|f| self.diagnostics.get(&f.name).into_iter().flatten().cloned())
+      .collect()
+  }
+
+  /// Puts ```func``` in place of the function with the same name, or adds it if it is new, and gives back every problem in the program afterwards
+  /// # Errors
+  /// Will return an error without changing anything if ```func``` can't be made into basic blocks, like when it has two labels with the same name
+  pub fn update(&mut self, func: Function) -> Result<Vec<Diagnostic>, InterpError> {
+    let name = func.name.clone();
+    let old = self.bbprog.replace_function(func)?;
+    if let Some(old) = &old {
+      self.remove_calls(old);
+    }
+    self.add_calls(&name);
+    let signature_changed = old.is_none_or(|old| {
+      let new = self.bbprog.get(&name).unwrap();
+      !same_signature(&old, new)
+    });
+    let mut changed = vec![name.clone()];
+    if signature_changed {
+      changed.extend(self.callers_of(&name));
+    }
+    self.recheck(changed);
+    Ok(self.diagnostics())
+  }
+
+  /// Takes the function named ```func_name``` out of the program, checking the functions which call it again, and gives back every problem in the program afterwards
+  pub fn remove(&mut self, func_name: &str) -> Vec<Diagnostic> {
+    if let Some(old) = self.bbprog.remove_function(func_name) {
+      self.remove_calls(&old);
+      self.diagnostics.remove(func_name);
+      let callers = self.callers_of(func_name);
+      self.recheck(callers);
+    }
+    self.diagnostics()
+  }
+
+  // Checks each of the functions named ```names``` again, forgetting those which are no longer defined
+  fn recheck(&mut self, names: Vec<String>) {
+    for name in names {
+      match self.bbprog.get(&name) {
+        Some(bbfunc) => {
+          let diagnostics = check_function(bbfunc, &self.bbprog);
+          self.diagnostics.insert(name, diagnostics);
+        }
+        None => {
+          self.diagnostics.remove(&name);
+        }
+      }
+    }
+  }
+
+  // The functions which call ```name``` and are still defined
+  fn callers_of(&self, name: &str) -> Vec<String> {
+    self
+      .callers
+      .get(name)
+      .into_iter()
+      .flatten()
+      .filter(|caller| *caller != name)
+      .cloned()
+      .collect()
+  }
+
+  fn add_calls(&mut self, name: &str) {
+    let Some(bbfunc) = self.bbprog.get(name) else {
+      return;
+    };
+    for callee in callees(bbfunc) {
+      self
+        .callers
+        .entry(callee.to_string())
+        .or_default()
+        .insert(name.to_string());
+    }
+  }
+
+  fn remove_calls(&mut self, bbfunc: &BBFunction) {
+    for callee in callees(bbfunc) {
+      if let Some(callers) = self.callers.get_mut(callee) {
+        callers.remove(&bbfunc.name);
+      }
+    }
+  }
+}
+
+// Every function which ```bbfunc``` names in a call
+fn callees(bbfunc: &BBFunction) -> impl Iterator<Item = &str> {
+  bbfunc
+    .blocks
     .iter()
-    .try_for_each(|(_, bbfunc)| type_check_func(bbfunc, bbprog))
+    .flat_map(|b| &b.instrs)
+    .flat_map(|i| match i {
+      Instruction::Value { funcs, .. } | Instruction::Effect { funcs, .. } => funcs.as_slice(),
+      Instruction::Constant { .. } => &[],
+    })
+    .map(String::as_str)
+}
+
+// Whether calls to ```a``` and ```b``` are checked the same way
+fn same_signature(a: &BBFunction, b: &BBFunction) -> bool {
+  a.return_type == b.return_type
+    && a.args.len() == b.args.len()
+    && a
+      .args
+      .iter()
+      .zip(&b.args)
+      .all(|(a, b)| a.arg_type == b.arg_type)
+}
+
+// Adds a warning to ```diagnostics``` for everything in ```bbfunc``` which is allowed but does nothing
+fn find_warnings(bbfunc: &BBFunction, diagnostics: &mut Vec<Diagnostic>) {
+  let warn = |pos, message| Diagnostic::new(&bbfunc.name, pos, Severity::Warning, message);
+
+  let mut reachable = vec![false; bbfunc.blocks.len()];
+  let mut work_list = vec![0];
+  while let Some(b) = work_list.pop() {
+    if !std::mem::replace(&mut reachable[b], true) {
+      work_list.extend(bbfunc.blocks[b].successors());
+    }
+  }
+
+  // Uses in blocks which never run still count, since those blocks are warned about on their own
+  let mut used_vars = FxHashSet::default();
+  let mut used_labels = FxHashSet::default();
+  for instr in bbfunc.blocks.iter().flat_map(|b| &b.instrs) {
+    match instr {
+      Instruction::Constant { .. } => {}
+      Instruction::Value { args, labels, .. } | Instruction::Effect { args, labels, .. } => {
+        used_vars.extend(args.iter().map(String::as_str));
+        used_labels.extend(labels.iter().map(String::as_str));
+      }
+    }
+  }
+
+  for arg in &bbfunc.args {
+    // Anything which wasn't in the set already is never used
+    if used_vars.insert(arg.name.as_str()) {
+      diagnostics.push(warn(bbfunc.pos, format!("unused argument `{}`", arg.name)));
+    }
+  }
+  let reachable_blocks: Vec<usize> = (0..bbfunc.blocks.len()).filter(|b| reachable[*b]).collect();
+  find_infinite_loops(bbfunc, &reachable_blocks, &reachable, diagnostics);
+  for (block, reachable) in bbfunc.blocks.iter().zip(reachable) {
+    if let Some(label) = &block.label {
+      if !used_labels.contains(label.as_str()) {
+        diagnostics.push(warn(block.label_pos, format!("unused label `.{label}`")));
+      }
+    }
+    if !reachable {
+      let pos = block
+        .label_pos
+        .or_else(|| block.instrs.first().and_then(Instruction::get_pos));
+      diagnostics.push(warn(pos, "unreachable code".to_string()));
+      continue;
+    }
+    for instr in &block.instrs {
+      if let Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } = instr {
+        // Only the first assignment to a variable which is never used is warned about
+        if used_vars.insert(dest.as_str()) {
+          diagnostics.push(warn(instr.get_pos(), format!("unused variable `{dest}`")));
+        }
+      }
+    }
+  }
+}
+
+// Warns about the loops made of ```blocks``` which never end once they are entered, either because nothing leaves them or because every way out is a `br` on a condition which the loop never updates. The loops inside each loop which isn't warned about are found by looking again at its blocks without the ones where it is entered
+fn find_infinite_loops(
+  bbfunc: &BBFunction,
+  blocks: &[usize],
+  reachable: &[bool],
+  diagnostics: &mut Vec<Diagnostic>,
+) {
+  let mut in_blocks = vec![false; bbfunc.blocks.len()];
+  for b in blocks {
+    in_blocks[*b] = true;
+  }
+  // Every block which each block can get to without going outside of ```blocks```
+  let reaches: FxHashMap<usize, FxHashSet<usize>> = blocks
+    .iter()
+    .map(|b| {
+      let mut seen = FxHashSet::default();
+      let mut work_list: Vec<usize> = bbfunc.blocks[*b].successors().collect();
+      while let Some(s) = work_list.pop() {
+        if in_blocks[s] && seen.insert(s) {
+          work_list.extend(bbfunc.blocks[s].successors());
+        }
+      }
+      (*b, seen)
+    })
+    .collect();
+
+  let mut done = FxHashSet::default();
+  for b in blocks {
+    // A block is only in a loop if it can get back to itself
+    if done.contains(b) || !reaches[b].contains(b) {
+      continue;
+    }
+    let body: Vec<usize> = blocks
+      .iter()
+      .copied()
+      .filter(|c| reaches[b].contains(c) && reaches[c].contains(b))
+      .collect();
+    done.extend(body.iter().copied());
+
+    let mut in_body = vec![false; bbfunc.blocks.len()];
+    for c in &body {
+      in_body[*c] = true;
+    }
+    // The loop is entered at the start of the function or from anywhere outside of it which can run
+    let mut entries = FxHashSet::default();
+    if in_body[0] {
+      entries.insert(0);
+    }
+    for (p, block) in bbfunc.blocks.iter().enumerate() {
+      if reachable[p] && !in_body[p] {
+        entries.extend(block.successors().filter(|s| in_body[*s]));
+      }
+    }
+    let header = entries.iter().copied().min().unwrap_or(body[0]);
+
+    if !warn_infinite_loop(bbfunc, &body, &in_body, header, diagnostics) {
+      let inner: Vec<usize> = body
+        .iter()
+        .copied()
+        .filter(|c| !entries.contains(c))
+        .collect();
+      find_infinite_loops(bbfunc, &inner, reachable, diagnostics);
+    }
+  }
+}
+
+// Adds a warning about the loop made of ```body``` if it never ends once it is entered at ```header```, returning whether it did
+fn warn_infinite_loop(
+  bbfunc: &BBFunction,
+  body: &[usize],
+  in_body: &[bool],
+  header: usize,
+  diagnostics: &mut Vec<Diagnostic>,
+) -> bool {
+  let warn = |pos, message| Diagnostic::new(&bbfunc.name, pos, Severity::Warning, message);
+  let exits: Vec<&BasicBlock> = body
+    .iter()
+    .map(|b| &bbfunc.blocks[*b])
+    .filter(|block| block.successors().any(|s| !in_body[s]))
+    .collect();
+  if exits.is_empty() {
+    let block = &bbfunc.blocks[header];
+    let pos = block
+      .label_pos
+      .or_else(|| block.instrs.first().and_then(Instruction::get_pos));
+    diagnostics.push(warn(
+      pos,
+      "infinite loop: nothing ever leaves this loop".to_string(),
+    ));
+    return true;
+  }
+
+  let mut assignments: FxHashMap<&str, usize> = FxHashMap::default();
+  for instr in body.iter().flat_map(|b| &bbfunc.blocks[*b].instrs) {
+    if let Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } = instr {
+      *assignments.entry(dest.as_str()).or_default() += 1;
+    }
+  }
+  // Every way out has to be a `br` on a condition which is the same each time around for the loop to never end
+  let mut found = Vec::new();
+  for block in exits {
+    let Some(
+      br @ Instruction::Effect {
+        op: EffectOps::Branch,
+        args,
+        ..
+      },
+    ) = block.instrs.last()
+    else {
+      return false;
+    };
+    if !never_updated(&args[0], block, block.instrs.len() - 1, &assignments) {
+      return false;
+    }
+    found.push(warn(
+      br.get_pos(),
+      format!(
+        "infinite loop: the loop condition `{}` is never updated inside the loop",
+        args[0]
+      ),
+    ));
+  }
+  diagnostics.extend(found);
+  true
+}
+
+// Whether ```var``` holds the same value every time the instruction at ```index``` of ```block``` runs, given how many times each variable is assigned to in the loop around it. That is when the loop never assigns to it, or when it is assigned once, earlier in ```block```, from variables which are the same every time in turn
+fn never_updated(
+  var: &str,
+  block: &BasicBlock,
+  index: usize,
+  assignments: &FxHashMap<&str, usize>,
+) -> bool {
+  match assignments.get(var) {
+    None => true,
+    Some(1) => block.instrs[..index]
+      .iter()
+      .enumerate()
+      .rev()
+      .find_map(|(i, instr)| match instr {
+        Instruction::Constant { dest, .. } if dest == var => Some(true),
+        Instruction::Value { dest, op, args, .. } if dest == var => Some(
+          !matches!(
+            op,
+            ValueOps::Call
+              | ValueOps::Phi
+              | ValueOps::Alloc
+              | ValueOps::Load
+              | ValueOps::Spawn
+              | ValueOps::Rand
+          ) && args
+            .iter()
+            .all(|arg| never_updated(arg, block, i, assignments)),
+        ),
+        _ => None,
+      })
+      .unwrap_or(false),
+    Some(_) => false,
+  }
 }
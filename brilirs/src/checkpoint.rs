@@ -0,0 +1,109 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use bril_rs::Type;
+use fxhash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::error::InterpError;
+use crate::interp::Environment;
+use crate::value::Value;
+
+/// Everything about a program part of the way through running other than the program itself, from [`crate::interp::Interpreter::checkpoint`].
+///
+/// This is the call stack with where each call is up to and its variables, along with the heap and the number of instructions run so far. [`crate::interp::Interpreter::resume`] carries on running from a checkpoint, even in another process, which is how `--checkpoint-every` and `--resume` work. Functions and the instructions that allocations were made at are saved by the name of the function and where the instruction is in it, so a checkpoint can only be resumed with the program it was made from. The debugger, traces, and profiles are not part of a checkpoint.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+  // The outermost call is first
+  pub(crate) frames: Vec<FrameState>,
+  pub(crate) heap: HeapState,
+  pub(crate) instruction_count: u32,
+  // The function that leaks are reported at, if the interpreter was started with start_main
+  pub(crate) main: Option<String>,
+  // Where the numbers `rand` gives were up to, which checkpoints from before `rand` don't have
+  #[serde(default)]
+  pub(crate) rng: Option<u64>,
+}
+
+impl Checkpoint {
+  /// Saves the checkpoint to ```path``` as JSON. It is written to a file next to ```path``` first and then moved over it, so that stopping the program while it is being saved still leaves the last checkpoint behind.
+  pub fn save(&self, path: &Path) -> Result<(), InterpError> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    File::create(&partial)
+      .and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()
+      })
+      .and_then(|()| std::fs::rename(&partial, path))
+      .map_err(|e| InterpError::CheckpointOutput(path.display().to_string(), Box::new(e)))
+  }
+
+  /// Loads a checkpoint which was saved with [`Checkpoint::save`]
+  pub fn load(path: &Path) -> Result<Self, InterpError> {
+    File::open(path)
+      .and_then(|file| Ok(serde_json::from_reader(BufReader::new(file))?))
+      .map_err(|e| InterpError::CheckpointInput(path.display().to_string(), Box::new(e)))
+  }
+}
+
+// A call which hasn't returned yet
+#[derive(Serialize, Deserialize)]
+pub(crate) struct FrameState {
+  pub function: String,
+  pub env: Environment,
+  pub block: usize,
+  pub instr: usize,
+  // The label of the block which ran before the current one, for phi nodes
+  pub last_label: Option<String>,
+  pub ret_dest: Option<u32>,
+  pub speculation: Vec<Environment>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct HeapState {
+  pub arena: Vec<Value>,
+  pub initialized: Vec<bool>,
+  pub allocations: Vec<AllocationState>,
+  pub free_list: FxHashMap<usize, Vec<usize>>,
+  // Checkpoints from before slots were reused don't have these, and never reuse the slots which were already free
+  #[serde(default)]
+  pub free_slots: VecDeque<u32>,
+  #[serde(default)]
+  pub made: Option<usize>,
+  pub num_live: usize,
+  // Checkpoints from before strings were added don't have any
+  #[serde(default)]
+  pub strings: Vec<String>,
+  // The number of pointers to each allocation, for a program run with --rc-heap
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub counts: Option<Vec<u32>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct AllocationState {
+  pub start: usize,
+  pub len: usize,
+  #[serde(default)]
+  pub generation: u32,
+  #[serde(default)]
+  pub number: Option<usize>,
+  pub allocated_at: OriginState,
+  pub freed_at: Option<OriginState>,
+}
+
+// Where an allocation was made or freed
+#[derive(Serialize, Deserialize)]
+pub(crate) enum OriginState {
+  // An instruction of the program which is running
+  Site {
+    function: String,
+    block: usize,
+    instr: usize,
+  },
+  // An instruction of a program which ran before the heap was handed on, as what it said and the type it allocated
+  Detached(String, Option<Type>),
+}
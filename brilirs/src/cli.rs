@@ -8,6 +8,26 @@ pub struct Cli {
   #[clap(short, long)]
   pub profile: bool,
 
+  /// Output a JSON report of the number of times each function, opcode, and basic block was executed to this file
+  #[clap(long)]
+  pub profile_out: Option<String>,
+
+  /// Output a JSON report of how many times each instruction was executed to this file, and the program as Bril text with those counts in comments to the same file with a `.bril` extension, so that instructions which never ran stand out
+  #[clap(long, conflicts_with_all = &["dump-cfg", "checkpoint-every", "resume"])]
+  pub coverage: Option<std::path::PathBuf>,
+
+  /// Output the program as Bril text to this file once it has run, with every line starting with how many times it ran and what percentage of all of the instructions run that was, followed by the 20 blocks which ran the most instructions
+  #[clap(long, conflicts_with_all = &["dump-cfg", "checkpoint-every", "resume"])]
+  pub annotate: Option<std::path::PathBuf>,
+
+  /// Output a JSON report of how much memory the program had allocated over time, the most it had allocated at once, how many allocations of each size it made, and how much each `alloc` allocated to this file, and the bytes allocated by each call stack to the same file with a `.folded` extension for flame graph tools
+  #[clap(long, conflicts_with = "dump-cfg")]
+  pub memory_profile: Option<std::path::PathBuf>,
+
+  /// Flag to run the program without printing anything it outputs and only output the total number of dynamic instructions, for benchmarking
+  #[clap(long, conflicts_with_all = &["debug", "dump-cfg", "check"])]
+  pub count_only: bool,
+
   /// The bril file to run. stdin is assumed if file is not provided
   #[clap(short, long)]
   pub file: Option<String>,
@@ -16,10 +36,312 @@ pub struct Cli {
   #[clap(short, long)]
   pub check: bool,
 
+  /// Also reject programs which rely on behaviour that Bril leaves unspecified, like using a variable which might not have been assigned yet or `int2char` of a constant which is not a character, before running them
+  #[clap(long)]
+  pub strict: bool,
+
   /// Flag for when the bril program is in text form
   #[clap(short, long)]
   pub text: bool,
 
-  /// Arguments for the main function
+  /// The form the bril program is in, where binary is what `bril2bc` writes. Binary programs are read in all at once, which is still much faster than JSON
+  #[clap(long, arg_enum, conflicts_with = "text")]
+  pub format: Option<InputFormat>,
+
+  /// Fill in the types of constants and value operations which leave them out before running the program. JSON programs are read in all at once instead of one function at a time
+  #[clap(long)]
+  pub infer: bool,
+
+  /// Flag to step through the program with an interactive debugger. Commands are read from stdin so the program should be provided with --file
+  #[clap(short, long)]
+  pub debug: bool,
+
+  /// Log every variable and heap write, call, and return so that the debugger can step backwards with reverse-step and reverse-continue, and find when a variable was last written with last. The log grows with every instruction which runs
+  #[clap(long, requires = "debug", conflicts_with = "rc-heap")]
+  pub time_travel: bool,
+
+  /// Record the path taken from every block which is entered this many times and output it to stderr as straight-line Bril using the speculation extension
+  #[clap(long, conflicts_with = "debug")]
+  pub trace_threshold: Option<u32>,
+
+  /// Write a line of JSON to stderr for every instruction that runs, with the values of its arguments and the value it assigns
+  #[clap(long)]
+  pub trace: bool,
+
+  /// Only --trace the instructions in these functions. Can be repeated or given as a comma-separated list
+  #[clap(long, use_value_delimiter = true, requires = "trace")]
+  pub trace_fn: Vec<String>,
+
+  /// Only --trace the instructions with these operations, like alloc,free,store,load. Can be repeated or given as a comma-separated list
+  #[clap(long, use_value_delimiter = true, requires = "trace", validator = is_op)]
+  pub trace_op: Vec<String>,
+
+  /// Write the --trace to this file instead of stderr
+  #[clap(long, requires = "trace")]
+  pub trace_out: Option<std::path::PathBuf>,
+
+  /// The number of values to preallocate on the heap for programs using the memory extension
+  #[clap(long, default_value_t = 0)]
+  pub heap_size: usize,
+
+  /// What to do when integer arithmetic overflows
+  #[clap(long, arg_enum, default_value = "wrap")]
+  pub overflow: Overflow,
+
+  /// How div rounds when an operand is negative, which rem follows so that div and rem always agree
+  #[clap(long, arg_enum, default_value = "trunc")]
+  pub div_semantics: DivSemantics,
+
+  /// Make every guard fail as though its condition were false, so that programs using the speculation extension always fall back to the code for when speculation goes wrong
+  #[clap(long)]
+  pub no_speculation: bool,
+
+  /// When memory is never freed, list every allocation which leaked with its size and where it was made instead of only the first
+  #[clap(long)]
+  pub check_leaks: bool,
+
+  /// Count the pointers to every allocation and free it as soon as the last one is overwritten or its function returns, so that programs can be run without ever calling free. How much memory was collected is output to stderr as `collected_bytes` when the program finishes.
+  #[clap(long)]
+  pub rc_heap: bool,
+
+  /// Report loads from memory which was never stored to with the allocation they were from, pointer arithmetic which goes outside of its allocation, and variables which are used before they are assigned, at the cost of running a few times slower
+  #[clap(long)]
+  pub sanitize: bool,
+
+  /// The most calls which can be running at once, counting main. A call which would go deeper stops the program with an error showing the outermost and innermost calls instead of using up all of the memory
+  #[clap(long, default_value_t = 1_000_000)]
+  pub max_call_depth: usize,
+
+  /// Stop the program with an error once it has run this many instructions, so that one which loops forever can't hang whatever is running it
+  #[clap(long)]
+  pub max_instructions: Option<u64>,
+
+  /// Stop the program with an error once it has run for this many milliseconds
+  #[clap(long, conflicts_with = "debug")]
+  pub timeout_ms: Option<u64>,
+
+  /// Stop the program with an error once the instructions it has run cost more than this much gas, counted with --cost-model
+  #[clap(long, conflicts_with = "resume")]
+  pub gas: Option<u64>,
+
+  /// Count the gas the program uses with the costs in this TOML file, which has a line like `load = 4` for each operation to give a cost and can set the cost of the rest with `default = 1`. Without it, every operation costs 1 except for the slower ones like memory operations and division. The total is output with -p as `total_gas` and to --profile-out
+  #[clap(long, conflicts_with = "resume")]
+  pub cost_model: Option<std::path::PathBuf>,
+
+  /// Run a call which is immediately followed by returning its result in place of the function making it, so that deep recursion in tail position doesn't grow the call stack
+  #[clap(long)]
+  pub tco: bool,
+
+  /// How threads started with `spawn` take turns running. Either way the same program and arguments always interleave the same way, for random with the same --seed
+  #[clap(long, arg_enum, default_value = "round-robin")]
+  pub schedule: Schedule,
+
+  /// The seed for the numbers `rand` gives and for --schedule random, so that a run which goes wrong can be run again the same way
+  #[clap(long, default_value_t = 0)]
+  pub seed: u64,
+
+  /// How many instructions a thread runs before the scheduler gives another thread a turn. Defaults to 100
+  #[clap(long)]
+  pub quantum: Option<std::num::NonZeroU64>,
+
+  /// Report loads, stores, and frees of the same memory by threads which could have run at the same time in either order, because neither had joined the other in between, instead of only the interleaving which happened to be run
+  #[clap(long)]
+  pub detect_races: bool,
+
+  /// An optimization pass from `bril-opt`, like `dce` or `lvn`, to run over the program once it has been type checked. Passes run in the order they are given and can be repeated, and the program they make is type checked again before it runs
+  #[clap(long = "pass")]
+  pub passes: Vec<String>,
+
+  /// Write the program to this file as JSON after running any --pass passes over it, before running it
+  #[clap(long)]
+  pub emit: Option<std::path::PathBuf>,
+
+  /// Print the control flow graph of every function in the given format instead of running the program
+  #[clap(long, arg_enum, conflicts_with_all = &["debug", "trace-threshold", "profile-out"])]
+  pub dump_cfg: Option<CfgFormat>,
+
+  /// Save the state of the program to --checkpoint-file every time this many more instructions have run, so that a run which is stopped can be carried on with --resume
+  #[clap(long, conflicts_with_all = &["debug", "trace-threshold", "profile-out", "check", "dump-cfg"])]
+  pub checkpoint_every: Option<std::num::NonZeroU64>,
+
+  /// Where --checkpoint-every saves the state of the program
+  #[clap(long, default_value = "state.bin")]
+  pub checkpoint_file: std::path::PathBuf,
+
+  /// Carry on running the program from the state saved in this file by --checkpoint-every instead of starting main. The program has to be the one which was running, and its arguments are ignored
+  #[clap(long, conflicts_with_all = &["debug", "trace-threshold", "profile-out", "check", "dump-cfg"])]
+  pub resume: Option<std::path::PathBuf>,
+
+  /// Save the arguments of main and what every call to an intrinsic gave back to this file once the program stops, even with an error, so that a run of a program which doesn't always do the same thing can be made again exactly with --replay
+  #[clap(long, conflicts_with_all = &["batch", "resume", "replay"])]
+  pub record: Option<std::path::PathBuf>,
+
+  /// Run the program the way it ran when this file was saved with --record, with the same arguments and with every call to an intrinsic giving back what it did then. Any arguments given are ignored
+  #[clap(long, conflicts_with_all = &["batch", "resume", "args-file"])]
+  pub replay: Option<std::path::PathBuf>,
+
+  /// A directory to search for the files the program imports from when they aren't next to the file importing them. Can be repeated
+  #[clap(short = 'L', long)]
+  pub lib_path: Vec<std::path::PathBuf>,
+
+  /// Read the arguments for the main function from this JSON file instead, or from stdin for `-` when the program is given with --file. It holds an array of the arguments in order or an object of them by name, where pointer arguments are arrays that are allocated on the heap before main starts
+  #[clap(long, conflicts_with = "args")]
+  pub args_file: Option<std::path::PathBuf>,
+
+  /// Run main once for every line of this JSON lines file, or of stdin for `-` when the program is given with --file, where each line holds the arguments like --args-file does. The runs happen alongside each other and a line of JSON with what each one printed and its total number of dynamic instructions is output for each of them in order
+  #[clap(long, conflicts_with_all = &["args", "args-file", "debug", "check", "count-only", "dump-cfg", "trace", "trace-threshold", "profile", "profile-out", "coverage", "annotate", "memory-profile", "checkpoint-every", "resume"])]
+  pub batch: Option<std::path::PathBuf>,
+
+  /// Run this function instead of main, with the arguments given the same way, and print what it returns after everything it prints. The program doesn't need a main function, so the functions of a library can be tested one at a time
+  #[clap(long, conflicts_with = "batch")]
+  pub entry: Option<String>,
+
+  /// How the values of each `print` are written to stdout: as text, like brili does, or as a line of JSON per `print` with the type and value of everything printed, for comparing outputs without depending on how values are formatted
+  #[clap(long, arg_enum, default_value = "text", conflicts_with_all = &["batch", "dump-cfg"])]
+  pub output_format: OutputFormat,
+
+  /// Whether printed text has its values coloured by their type. `auto` colours it when stdout is a terminal. JSON output is never coloured
+  #[clap(long, arg_enum, default_value = "never")]
+  pub color: Color,
+
+  /// Arguments for the main function. Pointer arguments are written as JSON arrays, like `[1, 2, 3]`, or as JSON strings for `ptr<char>`
   pub args: Vec<String>,
 }
+
+/// What the interpreter does when the result of an integer `add`, `sub`, `mul`, or `div` does not fit in 64 bits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ArgEnum)]
+pub enum Overflow {
+  /// Wrap around in two's complement, which is what brili does
+  #[default]
+  Wrap,
+  /// Stop the program with an integer overflow error
+  Trap,
+  /// Clamp the result to the largest or smallest `int`
+  Saturate,
+}
+
+/// How `div` rounds, and so what `rem` leaves over, when an operand is negative
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ArgEnum)]
+pub enum DivSemantics {
+  /// Round towards zero so that `rem` has the sign of the dividend, which is what brili does
+  #[default]
+  Trunc,
+  /// Round so that `rem` is never negative, which is Euclidean division
+  Euclid,
+}
+
+/// How the interpreter picks which thread runs next for programs using the concurrency extension
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ArgEnum)]
+pub enum Schedule {
+  /// Each thread runs in turn, in the order they were spawned
+  #[default]
+  RoundRobin,
+  /// A thread which can run is picked at random each turn, from the sequence given by `--seed`
+  Random,
+}
+
+/// The forms that `--format` can read a program in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ArgEnum)]
+pub enum InputFormat {
+  /// Bril JSON, as `bril2json` writes it
+  #[default]
+  Json,
+  /// Bril text, as `bril2txt` writes it
+  Text,
+  /// The binary encoding of `bril_rs::binary`, as `bril2bc` writes it
+  Binary,
+}
+
+/// The formats that `--dump-cfg` can print the control flow graph in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum CfgFormat {
+  /// A Graphviz graph for `dot`
+  Dot,
+  /// JSON, for other tools to read
+  Json,
+}
+
+/// The formats that `--output-format` can write what the program prints in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ArgEnum)]
+pub enum OutputFormat {
+  /// A line of text per `print`, the same as brili
+  #[default]
+  Text,
+  /// A line of JSON per `print`, as [`crate::sink::JsonLines`] writes it
+  Json,
+}
+
+/// When `--color` colours the values that the program prints
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ArgEnum)]
+pub enum Color {
+  /// When stdout is a terminal
+  Auto,
+  /// Every time
+  Always,
+  /// Never
+  #[default]
+  Never,
+}
+
+// Every operation that --trace-op can pick out, as it is written in Bril text
+const OPS: &[&str] = &[
+  "const",
+  "add",
+  "sub",
+  "mul",
+  "div",
+  "rem",
+  "eq",
+  "lt",
+  "gt",
+  "le",
+  "ge",
+  "not",
+  "and",
+  "or",
+  "call",
+  "id",
+  "phi",
+  "fadd",
+  "fsub",
+  "fmul",
+  "fdiv",
+  "feq",
+  "flt",
+  "fgt",
+  "fle",
+  "fge",
+  "float2bits",
+  "bits2float",
+  "alloc",
+  "load",
+  "ptradd",
+  "ceq",
+  "clt",
+  "cle",
+  "cgt",
+  "cge",
+  "char2int",
+  "int2char",
+  "spawn",
+  "rand",
+  "jmp",
+  "br",
+  "ret",
+  "print",
+  "nop",
+  "store",
+  "free",
+  "speculate",
+  "commit",
+  "guard",
+  "join",
+];
+
+fn is_op(op: &str) -> Result<(), String> {
+  if OPS.contains(&op) {
+    Ok(())
+  } else {
+    Err(format!("`{op}` is not a Bril operation"))
+  }
+}
@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use bril_rs::{Attrs, Instruction};
+use fxhash::FxHashMap;
+use serde::Serialize;
+
+use crate::basic_block::{BBFunction, BBProgram};
+
+/// Which instructions of a program ran over a single run, and how many times
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Coverage {
+  /// The number of instructions in the program
+  pub instructions: usize,
+  /// The number of instructions which ran at least once
+  pub executed: usize,
+  /// The coverage of every function in the program by name, including those which were never called
+  pub functions: BTreeMap<String, FunctionCoverage>,
+}
+
+/// The part of a [`Coverage`] for a single function
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FunctionCoverage {
+  /// The number of instructions in the function
+  pub instructions: usize,
+  /// The number of instructions in the function which ran at least once
+  pub executed: usize,
+  /// Every instruction of the function, in the order they appear in it
+  pub instrs: Vec<InstructionCoverage>,
+}
+
+/// The part of a [`FunctionCoverage`] for a single instruction
+#[derive(Debug, Clone, Serialize)]
+pub struct InstructionCoverage {
+  /// Where the instruction is in its function, counting from 0 and leaving out labels
+  pub index: usize,
+  /// The line of the source the instruction is on, if the program has positions
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub line: Option<u64>,
+  /// The column of the source the instruction starts at, if the program has positions
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub column: Option<u64>,
+  /// Whether the instruction was added by a transformation like an optimization pass, so that the line and column are of the code it was added for rather than of the instruction itself. Only written when it is set
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub derived: bool,
+  /// The opcode of the instruction
+  pub op: String,
+  /// The number of times the instruction ran
+  pub count: u64,
+}
+
+impl Coverage {
+  /// ```prog``` as Bril text with the number of times each instruction ran in a comment after it, and how many instructions of each function and of the whole program ran in comments above them. ```prog``` has to be the program the coverage is of.
+  #[must_use]
+  pub fn annotate(&self, prog: &BBProgram) -> String {
+    let mut text = String::new();
+    // Writing to a String can't fail
+    let _ = writeln!(text, "# {}", summary(self.executed, self.instructions));
+    for func in prog.functions() {
+      let Some(coverage) = self.functions.get(&func.name) else {
+        continue;
+      };
+      let _ = writeln!(
+        text,
+        "\n# {}",
+        summary(coverage.executed, coverage.instructions)
+      );
+      let _ = writeln!(text, "{}", signature(func));
+      let mut counts = coverage.instrs.iter();
+      for block in &func.blocks {
+        if let Some(label) = &block.label {
+          let _ = writeln!(text, ".{label}:");
+        }
+        for (instr, coverage) in block.instrs.iter().zip(&mut counts) {
+          let _ = writeln!(text, "  {instr}  # {}", coverage.count);
+        }
+      }
+      let _ = writeln!(text, "}}");
+    }
+    text
+  }
+
+  /// ```prog``` as Bril text with every line starting with the number of times it ran and what part of all of the instructions run that was, followed by the ```hottest``` blocks which ran the most instructions. ```prog``` has to be the program the coverage is of, and the counts of each instruction are found by where it is in its function, so it can't have been changed since.
+  /// ```
+  /// use brilirs::basic_block::BBProgram;
+  /// use brilirs::interp::{self, Options};
+  ///
+  /// let prog = bril2json::parse_abstract_program_from_str("@main {
+  ///   n: int = const 2; one: int = const 1; zero: int = const 0;
+  /// .loop:
+  ///   n: int = sub n one; more: bool = gt n zero; br more .loop .done;
+  /// .done:
+  ///   ret;
+  /// }", false).unwrap();
+  /// let bbprog = BBProgram::new(prog.try_into().unwrap()).unwrap();
+  /// let options = Options { collect_coverage: true, ..Options::default() };
+  /// let report = interp::execute_main_with_options(&bbprog, Vec::new(), &[], options).unwrap();
+  /// let heatmap = report.coverage.unwrap().heatmap(&bbprog, 20);
+  /// assert_eq!(format!("\n{heatmap}"), "
+  ///           | @main {
+  ///  1  10.0% |   n: int = const 2;
+  ///  1  10.0% |   one: int = const 1;
+  ///  1  10.0% |   zero: int = const 0;
+  ///           | .loop:
+  ///  2  20.0% |   n: int = sub n one;
+  ///  2  20.0% |   more: bool = gt n zero;
+  ///  2  20.0% |   br more .loop .done;
+  ///           | .done:
+  ///  1  10.0% |   ret;
+  ///           | }
+  ///
+  /// hottest blocks:
+  ///  6  60.0% | @main .loop, entered 2 times
+  ///  3  30.0% | @main <block 0>, entered 1 time
+  ///  1  10.0% | @main .done, entered 1 time
+  /// ");
+  /// ```
+  #[must_use]
+  pub fn heatmap(&self, prog: &BBProgram, hottest: usize) -> String {
+    let total: u64 = self
+      .functions
+      .values()
+      .flat_map(|f| &f.instrs)
+      .map(|i| i.count)
+      .sum();
+    // No count can be more than the total, so they all fit in the width of it
+    let width = total.to_string().len();
+    let heat = |count: u64| {
+      let share = if total == 0 {
+        0.0
+      } else {
+        count as f64 * 100.0 / total as f64
+      };
+      format!("{count:>width$} {share:>5.1}% |")
+    };
+    let blank = format!("{:width$}        |", "");
+
+    let mut text = String::new();
+    // (instructions run, times entered, function, block name) for every block which ran
+    let mut blocks = Vec::new();
+    for func in prog.functions() {
+      let Some(coverage) = self.functions.get(&func.name) else {
+        continue;
+      };
+      let mut counts = coverage.instrs.iter().map(|i| i.count);
+      if !text.is_empty() {
+        text.push('\n');
+      }
+      // Writing to a String can't fail
+      let _ = writeln!(text, "{blank} {}", signature(func));
+      for (index, block) in func.blocks.iter().enumerate() {
+        if let Some(label) = &block.label {
+          let _ = writeln!(text, "{blank} .{label}:");
+        }
+        let mut run = 0;
+        let mut entered = None;
+        for (instr, count) in block.instrs.iter().zip(&mut counts) {
+          let _ = writeln!(text, "{}   {instr}", heat(count));
+          run += count;
+          entered.get_or_insert(count);
+        }
+        if run > 0 {
+          blocks.push((
+            run,
+            entered.unwrap_or_default(),
+            &func.name,
+            block.name(index),
+          ));
+        }
+      }
+      let _ = writeln!(text, "{blank} }}");
+    }
+
+    // Ties are left in program order
+    blocks.sort_by_key(|(run, ..)| std::cmp::Reverse(*run));
+    if !blocks.is_empty() && hottest > 0 {
+      let _ = writeln!(text, "\nhottest blocks:");
+    }
+    for (run, entered, func, block) in blocks.into_iter().take(hottest) {
+      let times = if entered == 1 { "time" } else { "times" };
+      let _ = writeln!(
+        text,
+        "{} @{func} {block}, entered {entered} {times}",
+        heat(run)
+      );
+    }
+    text
+  }
+}
+
+// The first line of ```func``` in Bril text, up to and including the opening brace
+fn signature(func: &BBFunction) -> String {
+  let signature = bril_rs::Function {
+    name: func.name.clone(),
+    args: func.args.clone(),
+    return_type: func.return_type.clone(),
+    instrs: Vec::new(),
+    pos: None,
+    attrs: Attrs::new(),
+  }
+  .to_string();
+  // The signature is written the way a function with no instructions would be, so everything after the opening brace is left off
+  signature.trim_end_matches(['\n', '}']).to_string()
+}
+
+fn summary(executed: usize, instructions: usize) -> String {
+  if instructions == 0 {
+    return "no instructions".to_string();
+  }
+  format!(
+    "{executed} of {instructions} instructions ran ({:.1}%)",
+    executed as f64 * 100.0 / instructions as f64
+  )
+}
+
+fn opcode(instr: &Instruction) -> String {
+  match instr {
+    Instruction::Constant { op, .. } => op.to_string(),
+    Instruction::Value { op, .. } => op.to_string(),
+    Instruction::Effect { op, .. } => op.to_string(),
+  }
+}
+
+// The counts are keyed by the address of each function so that recording them doesn't need to hash function names
+#[derive(Default)]
+pub(crate) struct CoverageRecorder {
+  counts: FxHashMap<*const BBFunction, Vec<Vec<u64>>>,
+}
+
+impl CoverageRecorder {
+  pub(crate) fn record(&mut self, func: &BBFunction, block_idx: usize, instr_idx: usize) {
+    self.counts.entry(func).or_insert_with(|| {
+      func
+        .blocks
+        .iter()
+        .map(|block| vec![0; block.instrs.len()])
+        .collect()
+    })[block_idx][instr_idx] += 1;
+  }
+
+  pub(crate) fn finish(&self, prog: &BBProgram) -> Coverage {
+    let mut coverage = Coverage::default();
+    for func in prog.functions() {
+      let key: *const BBFunction = func;
+      let counts = self.counts.get(&key);
+      let mut func_coverage = FunctionCoverage::default();
+      for (block_idx, block) in func.blocks.iter().enumerate() {
+        for (instr_idx, instr) in block.instrs.iter().enumerate() {
+          let count = counts.map_or(0, |c| c[block_idx][instr_idx]);
+          let pos = instr.get_pos();
+          func_coverage.instrs.push(InstructionCoverage {
+            index: func_coverage.instrs.len(),
+            line: pos.map(|p| p.row),
+            column: pos.map(|p| p.col),
+            derived: pos.is_some_and(|p| p.derived),
+            op: opcode(instr),
+            count,
+          });
+          if count != 0 {
+            func_coverage.executed += 1;
+          }
+        }
+      }
+      func_coverage.instructions = func_coverage.instrs.len();
+
+      coverage.instructions += func_coverage.instructions;
+      coverage.executed += func_coverage.executed;
+      coverage.functions.insert(func.name.clone(), func_coverage);
+    }
+    coverage
+  }
+}
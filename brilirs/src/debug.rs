@@ -0,0 +1,550 @@
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+
+use bril_rs::{Instruction, Type};
+use fxhash::FxHashSet;
+
+use crate::basic_block::BBFunction;
+use crate::error::InterpError;
+use crate::interp::{Environment, Heap};
+use crate::time_travel::{History, LastWrite};
+use crate::value::{Strings, Value};
+
+const HELP: &str = "\
+Commands:
+  s, step                 execute the next instruction
+  c, continue             run until the next breakpoint
+  b, break .label|@func   pause when entering the block or function
+  d, delete .label|@func  remove a breakpoint
+  breakpoints             list all breakpoints
+  p, print var...         print the value of variables
+  env                     print every initialized variable of the current function
+  heap                    print every live allocation
+  bt, backtrace           print the current call stack
+  l, list                 print the current basic block
+  rs, reverse-step        go back to the previous instruction (needs --time-travel)
+  rc, reverse-continue    go back to the previous breakpoint (needs --time-travel)
+  last var...             print when variables were last written (needs --time-travel)
+  q, quit                 stop executing the program
+  h, help                 print this message";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Breakpoint {
+  Label(String),
+  Function(String),
+}
+
+impl Breakpoint {
+  fn parse(s: &str) -> Option<Self> {
+    s.strip_prefix('.')
+      .map(|l| Self::Label(l.to_string()))
+      .or_else(|| s.strip_prefix('@').map(|f| Self::Function(f.to_string())))
+  }
+}
+
+impl std::fmt::Display for Breakpoint {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Label(l) => write!(f, ".{l}"),
+      Self::Function(func) => write!(f, "@{func}"),
+    }
+  }
+}
+
+/// An interactive debugger which reads commands from ```input``` and writes its responses to ```output```. It is driven by the interpreter when passed in [`crate::interp::Options::debugger`], which pauses before the first instruction of `main`, every time a breakpoint is hit, and after each instruction when single-stepping.
+pub struct Debugger {
+  input: Box<dyn BufRead>,
+  output: Box<dyn Write>,
+  breakpoints: FxHashSet<Breakpoint>,
+  stepping: bool,
+  // Set once ```input``` has been exhausted so that the rest of the program runs uninterrupted
+  detached: bool,
+  // The names of the functions currently being executed, innermost last
+  call_stack: Vec<String>,
+  entered_function: bool,
+  // Every change the program has made so far, when the debugger can go back in time
+  history: Option<History>,
+}
+
+// What the commands which inspect the program look at, which is where it is paused unless the debugger has gone back to an earlier step
+struct View<'v> {
+  func: &'v str,
+  label: Option<&'v String>,
+  instrs: &'v [Instruction],
+  instr_idx: usize,
+  var_names: &'v [String],
+  var_types: &'v [Option<Type>],
+  vars: Vec<Option<Value>>,
+  // The functions being executed, innermost last
+  call_stack: Vec<&'v str>,
+}
+
+impl View<'_> {
+  fn show_var(&self, var: usize, strings: &Strings) -> String {
+    show(self.vars[var], self.var_types[var].as_ref(), strings)
+  }
+}
+
+fn io_err(e: std::io::Error) -> InterpError {
+  InterpError::IoError(Box::new(e))
+}
+
+fn show(v: Option<Value>, ty: Option<&bril_rs::Type>, strings: &Strings) -> String {
+  match (v, ty) {
+    // `print` shows every pointer the same way, like brili, but here it helps to see where it points
+    (Some(v), Some(bril_rs::Type::Pointer(_))) => format!("{:?}", v.as_pointer()),
+    (Some(v), Some(ty)) => v.display(ty, strings).to_string(),
+    _ => "<uninitialized>".to_string(),
+  }
+}
+
+fn location(func: &str, label: Option<&String>, instr_idx: usize, instr: &Instruction) -> String {
+  let label = label.map_or_else(String::new, |l| format!(" .{l}"));
+  format!("@{func}{label} [{instr_idx}]: {instr}")
+}
+
+impl Debugger {
+  /// Creates a debugger which will pause at the first instruction of the program
+  pub fn new(input: Box<dyn BufRead>, output: Box<dyn Write>) -> Self {
+    Self {
+      input,
+      output,
+      breakpoints: FxHashSet::default(),
+      stepping: true,
+      detached: false,
+      call_stack: Vec::new(),
+      entered_function: false,
+      history: None,
+    }
+  }
+
+  /// Logs every variable and heap write, call, and return while the program runs, so that the debugger can step backwards with `reverse-step` and `reverse-continue` and find out when a variable was last written with `last`.
+  ///
+  /// The log grows with every instruction which runs until the debugger stops reading commands. Heaps which free allocations on their own with [`crate::interp::Options::rc_heap`] aren't supported, since those frees can't be undone.
+  #[must_use]
+  pub fn with_time_travel(mut self) -> Self {
+    self.history = Some(History::default());
+    self
+  }
+
+  pub(crate) fn enter_function(&mut self, func: &BBFunction) {
+    self.call_stack.push(func.name.clone());
+    self.entered_function = true;
+    if let Some(history) = self.history.as_mut() {
+      history.enter_function(func);
+    }
+  }
+
+  pub(crate) fn exit_function(&mut self) {
+    self.call_stack.pop();
+    if let Some(history) = self.history.as_mut() {
+      history.exit_function();
+    }
+  }
+
+  pub(crate) fn before_instruction(
+    &mut self,
+    func: &BBFunction,
+    block_idx: usize,
+    instr_idx: usize,
+    env: &Environment,
+    heap: &Heap<'_>,
+  ) -> Result<(), InterpError> {
+    if self.detached {
+      return Ok(());
+    }
+    if let Some(history) = self.history.as_mut() {
+      history.record(func, block_idx, instr_idx, env, heap);
+    }
+    self.pause(func, block_idx, instr_idx, env, heap)
+  }
+
+  // The breakpoint which instruction ```instr_idx``` of ```func``` hits, if ```entered``` is whether it is the first of a call and it is in the block labelled ```label```
+  fn hit(
+    &self,
+    func: &str,
+    label: Option<&String>,
+    instr_idx: usize,
+    entered: bool,
+  ) -> Option<Breakpoint> {
+    let function = Breakpoint::Function(func.to_string());
+    if entered && self.breakpoints.contains(&function) {
+      Some(function)
+    } else if instr_idx == 0 {
+      label
+        .map(|l| Breakpoint::Label(l.clone()))
+        .filter(|b| self.breakpoints.contains(b))
+    } else {
+      None
+    }
+  }
+
+  // Stops to prompt for commands if there is a breakpoint here or the debugger is stepping
+  fn pause(
+    &mut self,
+    func: &BBFunction,
+    block_idx: usize,
+    instr_idx: usize,
+    env: &Environment,
+    heap: &Heap<'_>,
+  ) -> Result<(), InterpError> {
+    let entered_function = std::mem::take(&mut self.entered_function);
+    let block = &func.blocks[block_idx];
+    let hit = self.hit(
+      &func.name,
+      block.label.as_ref(),
+      instr_idx,
+      entered_function,
+    );
+    if hit.is_none() && !self.stepping {
+      return Ok(());
+    }
+    if let Some(b) = hit {
+      writeln!(self.output, "Breakpoint {b}").map_err(io_err)?;
+    }
+    let here = location(
+      &func.name,
+      block.label.as_ref(),
+      instr_idx,
+      &block.instrs[instr_idx],
+    );
+    writeln!(self.output, "{here}").map_err(io_err)?;
+    self.prompt(func, block_idx, instr_idx, env, heap)
+  }
+
+  // Where ```step``` of the history is, and the breakpoint it hits
+  fn step_location(history: &History, step: usize, hit: Option<Breakpoint>) -> String {
+    let (func, block, instr_idx, _) = history.place(step);
+    let (label, instrs) = &func.blocks[block];
+    let here = location(&func.name, label.as_ref(), instr_idx, &instrs[instr_idx]);
+    match hit {
+      Some(b) => format!("Breakpoint {b}\n{here}"),
+      None => here,
+    }
+  }
+
+  // The breakpoint which ```step``` of the history hit
+  fn step_hit(&self, history: &History, step: usize) -> Option<Breakpoint> {
+    let (func, block, instr_idx, entered) = history.place(step);
+    self.hit(
+      &func.name,
+      func.blocks[block].0.as_ref(),
+      instr_idx,
+      entered,
+    )
+  }
+
+  // Goes back to the last step before ```step``` which hits a breakpoint, or the first step of the history if none of them do, when ```stop_at_breakpoints```. Otherwise it only goes back one step
+  fn travel_back(
+    &self,
+    history: &History,
+    step: usize,
+    stop_at_breakpoints: bool,
+  ) -> (usize, String) {
+    if step == 0 {
+      return (0, "Already at the first step of the history".to_string());
+    }
+    let mut to = step - 1;
+    if stop_at_breakpoints {
+      while to > 0 && self.step_hit(history, to).is_none() {
+        to -= 1;
+      }
+    }
+    let hit = self.step_hit(history, to).filter(|_| stop_at_breakpoints);
+    (to, Self::step_location(history, to, hit))
+  }
+
+  // Goes forward from ```step``` of the history, which has to be before the step the program is paused at, one step at a time or else to the first step that hits a breakpoint. The step is None once the debugger is back where the program is paused, which doesn't count as hitting a breakpoint if it was never hit there
+  fn travel_forward(
+    &self,
+    history: &History,
+    step: usize,
+    stop_at_breakpoints: bool,
+  ) -> (Option<usize>, Option<String>) {
+    let present = history.len() - 1;
+    let mut to = step + 1;
+    if stop_at_breakpoints {
+      while to < present && self.step_hit(history, to).is_none() {
+        to += 1;
+      }
+    }
+    let hit = self.step_hit(history, to).filter(|_| stop_at_breakpoints);
+    if to == present && stop_at_breakpoints && hit.is_none() {
+      return (None, None);
+    }
+    let shown = Self::step_location(history, to, hit);
+    ((to < present).then_some(to), Some(shown))
+  }
+
+  // Looks at ```past``` of the history, or where the program is paused if it is None
+  fn view<'v>(
+    &'v self,
+    past: Option<usize>,
+    func: &'v BBFunction,
+    block_idx: usize,
+    instr_idx: usize,
+    env: &Environment,
+    heap: &Heap<'_>,
+  ) -> View<'v> {
+    match (past, self.history.as_ref()) {
+      (Some(step), Some(history)) => {
+        let (past_func, block, instr_idx, _) = history.place(step);
+        let (label, instrs) = &past_func.blocks[block];
+        let mut state = history.state_at(step, heap);
+        View {
+          func: &past_func.name,
+          label: label.as_ref(),
+          instrs,
+          instr_idx,
+          var_names: &past_func.var_names,
+          var_types: &past_func.var_types,
+          vars: state
+            .frames
+            .last_mut()
+            .map(|(_, vars)| std::mem::take(vars))
+            .unwrap_or_default(),
+          call_stack: state
+            .frames
+            .iter()
+            .map(|(f, _)| history.function(*f).name.as_str())
+            .collect(),
+        }
+      }
+      _ => {
+        let block = &func.blocks[block_idx];
+        View {
+          func: &func.name,
+          label: block.label.as_ref(),
+          instrs: &block.instrs,
+          instr_idx,
+          var_names: &func.var_names,
+          var_types: &func.var_types,
+          vars: (0..func.var_names.len()).map(|i| env.lookup(i)).collect(),
+          call_stack: self.call_stack.iter().map(String::as_str).collect(),
+        }
+      }
+    }
+  }
+
+  // Every allocation which hadn't been freed at ```past``` of the history, or which hasn't been freed if it is None, with the type of its values
+  fn allocations(
+    &self,
+    past: Option<usize>,
+    heap: &Heap<'_>,
+  ) -> Vec<(usize, Type, Vec<Option<Value>>)> {
+    match (past, self.history.as_ref()) {
+      (Some(step), Some(history)) => {
+        let mut allocations: Vec<_> = history.state_at(step, heap).heap.into_iter().collect();
+        allocations.sort_by_key(|(_, (number, _, _))| *number);
+        allocations
+          .into_iter()
+          .map(|(base, (_, ty, values))| (base, ty, values))
+          .collect()
+      }
+      _ => heap
+        .live_allocations()
+        .map(|(base, ty, values)| (base, ty.clone(), values))
+        .collect(),
+    }
+  }
+
+  // What ```command``` shows about ```view```, for the commands which look at the state of the program
+  fn inspect(view: &View<'_>, command: &str, rest: &[&str], strings: &Strings) -> String {
+    let mut shown = String::new();
+    match command {
+      "p" | "print" => {
+        for var in rest {
+          match view.var_names.iter().position(|v| v == var) {
+            Some(i) => writeln!(shown, "{var} = {}", view.show_var(i, strings)),
+            None => writeln!(shown, "No variable `{var}` in @{}", view.func),
+          }
+          .unwrap();
+        }
+      }
+      "env" => {
+        for (i, name) in view.var_names.iter().enumerate() {
+          if view.vars[i].is_some() {
+            writeln!(shown, "{name} = {}", view.show_var(i, strings)).unwrap();
+          }
+        }
+      }
+      "bt" | "backtrace" => {
+        for (depth, name) in view.call_stack.iter().rev().enumerate() {
+          writeln!(shown, "#{depth} @{name}").unwrap();
+        }
+      }
+      _ => {
+        if let Some(l) = view.label {
+          writeln!(shown, ".{l}:").unwrap();
+        }
+        for (i, instr) in view.instrs.iter().enumerate() {
+          let marker = if i == view.instr_idx { "->" } else { "  " };
+          writeln!(shown, "{marker} {instr}").unwrap();
+        }
+      }
+    }
+    shown
+  }
+
+  // When each of ```vars``` of the call being looked at at ```step``` was last written before it
+  fn last_writes(history: &History, step: usize, vars: &[&str], strings: &Strings) -> String {
+    let mut shown = String::new();
+    let (func, ..) = history.place(step);
+    for var in vars {
+      let Some(i) = func.var_names.iter().position(|v| v == var) else {
+        writeln!(shown, "No variable `{var}` in @{}", func.name).unwrap();
+        continue;
+      };
+      let ty = func.var_types[i].as_ref();
+      match history.last_write(step, i) {
+        LastWrite::Never => writeln!(
+          shown,
+          "`{var}` hasn't been written in this call of @{}",
+          func.name
+        ),
+        LastWrite::Argument(v) => writeln!(
+          shown,
+          "{var} = {} was passed to @{}",
+          show(v, ty, strings),
+          func.name
+        ),
+        LastWrite::Step(at, v) => {
+          let ago = step - at;
+          let (_, block, instr_idx, _) = history.place(at);
+          let (label, instrs) = &func.blocks[block];
+          writeln!(
+            shown,
+            "{var} = {} was written {ago} step{} ago by {}",
+            show(v, ty, strings),
+            if ago == 1 { "" } else { "s" },
+            location(&func.name, label.as_ref(), instr_idx, &instrs[instr_idx])
+          )
+        }
+      }
+      .unwrap();
+    }
+    shown
+  }
+
+  fn prompt(
+    &mut self,
+    func: &BBFunction,
+    block_idx: usize,
+    instr_idx: usize,
+    env: &Environment,
+    heap: &Heap<'_>,
+  ) -> Result<(), InterpError> {
+    // The step of the history being looked at, which is None while looking at where the program is paused
+    let mut past: Option<usize> = None;
+    loop {
+      write!(self.output, "(brildb) ").map_err(io_err)?;
+      self.output.flush().map_err(io_err)?;
+
+      let mut line = String::new();
+      if self.input.read_line(&mut line).map_err(io_err)? == 0 {
+        writeln!(self.output).map_err(io_err)?;
+        self.detached = true;
+        // Nothing can look at the history any more
+        self.history = None;
+        return Ok(());
+      }
+      let mut words = line.split_whitespace();
+      let Some(command) = words.next() else {
+        continue;
+      };
+      let rest: Vec<&str> = words.collect();
+      match command {
+        "s" | "step" | "c" | "continue" => {
+          let stop_at_breakpoints = command.starts_with('c');
+          let (Some(step), Some(history)) = (past, self.history.as_ref()) else {
+            self.stepping = !stop_at_breakpoints;
+            return Ok(());
+          };
+          // Going forward through the history replays it, until the debugger catches up with the program
+          let (to, shown) = self.travel_forward(history, step, stop_at_breakpoints);
+          past = to;
+          match shown {
+            Some(shown) => writeln!(self.output, "{shown}").map_err(io_err)?,
+            None => {
+              self.stepping = false;
+              return Ok(());
+            }
+          }
+        }
+        "rs" | "reverse-step" | "rc" | "reverse-continue" => {
+          let Some(history) = self.history.as_ref() else {
+            writeln!(self.output, "`{command}` needs --time-travel").map_err(io_err)?;
+            continue;
+          };
+          let step = past.unwrap_or(history.len() - 1);
+          let (to, shown) =
+            self.travel_back(history, step, matches!(command, "rc" | "reverse-continue"));
+          past = Some(to);
+          writeln!(self.output, "{shown}").map_err(io_err)?;
+        }
+        "last" => {
+          let Some(history) = self.history.as_ref() else {
+            writeln!(self.output, "`{command}` needs --time-travel").map_err(io_err)?;
+            continue;
+          };
+          let step = past.unwrap_or(history.len() - 1);
+          let shown = Self::last_writes(history, step, &rest, heap.strings());
+          write!(self.output, "{shown}").map_err(io_err)?;
+        }
+        "b" | "break" | "d" | "delete" => {
+          for arg in rest {
+            match Breakpoint::parse(arg) {
+              None => writeln!(self.output, "Expected a .label or @function, found `{arg}`"),
+              Some(b) if command.starts_with('b') => {
+                let msg = format!("Breakpoint set at {b}");
+                self.breakpoints.insert(b);
+                writeln!(self.output, "{msg}")
+              }
+              Some(b) => {
+                if self.breakpoints.remove(&b) {
+                  writeln!(self.output, "Breakpoint {b} deleted")
+                } else {
+                  writeln!(self.output, "No breakpoint at {b}")
+                }
+              }
+            }
+            .map_err(io_err)?;
+          }
+        }
+        "breakpoints" => {
+          let mut breakpoints: Vec<_> = self.breakpoints.iter().collect();
+          breakpoints.sort();
+          for b in breakpoints {
+            writeln!(self.output, "{b}").map_err(io_err)?;
+          }
+        }
+        "heap" => {
+          for (base, ty, values) in self.allocations(past, heap) {
+            writeln!(
+              self.output,
+              "{base}: [{}]",
+              values
+                .into_iter()
+                .map(|v| show(v, Some(&ty), heap.strings()))
+                .collect::<Vec<_>>()
+                .join(", ")
+            )
+            .map_err(io_err)?;
+          }
+        }
+        "p" | "print" | "env" | "bt" | "backtrace" | "l" | "list" => {
+          let view = self.view(past, func, block_idx, instr_idx, env, heap);
+          let shown = Self::inspect(&view, command, &rest, heap.strings());
+          write!(self.output, "{shown}").map_err(io_err)?;
+        }
+        "q" | "quit" => return Err(InterpError::DebuggerQuit),
+        "h" | "help" => writeln!(self.output, "{HELP}").map_err(io_err)?,
+        _ => writeln!(
+          self.output,
+          "Unknown command `{command}`, try `help` for a list of commands"
+        )
+        .map_err(io_err)?,
+      }
+    }
+  }
+}
@@ -3,89 +3,434 @@ use std::fmt::Display;
 use bril_rs::Position;
 use thiserror::Error;
 
+/// Everything that can go wrong while converting, checking, or running a program
 // Having the #[error(...)] for all variants derives the Display trait as well
 #[derive(Error, Debug)]
 pub enum InterpError {
-  #[error("Some memory locations have not been freed by the end of execution")]
-  MemLeak,
+  /// Not every allocation was freed by the time `main` returned
+  #[error("Some memory locations have not been freed by the end of execution. `{0}` allocations were never freed, the first made at {1}")]
+  MemLeak(usize, String), // (number of allocations, first allocation site)
+  /// Loaded from a heap location which was never stored to
   #[error("Trying to load from uninitialized memory")]
   UsingUninitializedMemory,
+  /// A `phi` ran before any label was reached
   #[error("phi node executed with no last label")]
   NoLastLabel,
+  /// The program has no `main` function to start from
   #[error("no main function defined, doing nothing")]
   NoMainFunction,
+  /// A `phi` has a different number of labels and arguments
   #[error("pi node has unequal numbers of labels and args")]
   UnequalPhiNode,
-  #[error("multiple functions of the same name found")]
-  DuplicateFunction,
+  /// Two functions share the same name
+  #[error("multiple functions of name {0} found")]
+  DuplicateFunction(String),
+  /// A function without a return type returns a value, or `main` has a return type
   #[error("Expected empty return for `{0}`, found value")]
   NonEmptyRetForFunc(String),
+  /// `alloc` was given a size less than one
   #[error("cannot allocate `{0}` entries")]
   CannotAllocSize(i64),
-  #[error("Tried to free illegal memory location base: `{0}`, offset: `{1}`. Offset must be 0.")]
-  IllegalFree(usize, i64), // (base, offset)
-  #[error("Uninitialized heap location `{0}` and/or illegal offset `{1}`")]
-  InvalidMemoryAccess(usize, i64), // (base, offset)
+  /// More allocations were live at once than pointers can tell apart, counting those whose place can't be reused any more
+  #[error("cannot have more than `{0}` allocations at once")]
+  TooManyAllocations(usize),
+  /// An integer operation overflowed with [`crate::cli::Overflow::Trap`]
+  #[error("integer overflow in `{0}`")]
+  Overflow(bril_rs::ValueOps),
+  /// An integer `div` by zero
+  #[error("division by zero")]
+  DivisionByZero,
+  /// `int2char` was given an integer which is not a Unicode scalar value
+  #[error("`{0}` is not a valid Unicode scalar value for a char")]
+  InvalidChar(i64),
+  /// `index` was given an index outside of its string
+  #[error("Index `{0}` is out of bounds for a string of `{1}` characters")]
+  StringOutOfBounds(i64, usize), // (index, length)
+  /// `rand` was given a range with nothing in it
+  #[error("cannot pick a random int from `{0}` up to `{1}`")]
+  EmptyRandRange(i64, i64),
+  /// `free` was given a pointer which is not the start of an allocation
+  #[error("Tried to free the allocation made at {1} at offset `{0}`. Offset must be 0.")]
+  IllegalFree(i64, String), // (offset, allocation site)
+  /// `free` was given a pointer to an allocation which was already freed
+  #[error("Tried to free the allocation made at {0} which was already freed at {1}")]
+  DoubleFree(String, String), // (allocation site, free site)
+  /// Loaded from or stored to an allocation which was already freed
+  #[error("Access to freed memory from the allocation made at {0} which was freed at {1}")]
+  UseAfterFree(String, String), // (allocation site, free site)
+  /// Loaded from or stored to an allocation which was freed so long ago that another allocation has been made in its place
+  #[error("Access to freed memory from an allocation whose place has been taken by the allocation made at {0}")]
+  UseAfterReuse(String), // (allocation site of the new allocation)
+  /// `free` was given a pointer to an allocation which was freed so long ago that another allocation has been made in its place
+  #[error("Tried to free an allocation which was already freed, and whose place has been taken by the allocation made at {0}")]
+  DoubleFreeAfterReuse(String), // (allocation site of the new allocation)
+  /// Loaded from or stored to an offset outside of the allocation a pointer points into
+  #[error("Offset `{0}` is out of bounds for the allocation of `{1}` values made at {2}")]
+  OutOfBounds(i64, usize, String), // (offset, size, allocation site)
+  /// With `--sanitize`, loaded from a value of an allocation which was never stored to
+  #[error("Load from offset `{0}` of the allocation of `{1}` values made at {2}, which has not been stored to")]
+  UninitializedLoad(i64, usize, String), // (offset, size, allocation site)
+  /// With `--sanitize`, `ptradd` made a pointer to neither a value of its allocation nor the end of it
+  #[error("Pointer arithmetic moved to offset `{0}`, outside of the allocation of `{1}` values made at {2}")]
+  PointerEscape(i64, usize, String), // (offset, size, allocation site)
+  /// With `--sanitize`, an instruction used a variable which hasn't been assigned along the path that was taken
+  #[error("variable `{0}` is used before it has been assigned")]
+  UnassignedVariable(String),
+  /// With `--strict`, an instruction uses a variable which isn't assigned along every path to it
+  #[error("`{0}` might be used before it has been assigned")]
+  MaybeUnassigned(String),
+  /// A call would have made more calls be running at once than [`crate::interp::Options::max_call_depth`] allows
+  #[error("call stack exceeded the maximum depth of `{0}` calls: {1}")]
+  StackOverflow(usize, String), // (maximum depth, outermost and innermost calls)
+  /// The program ran for longer than [`crate::interp::Options::max_instructions`], [`crate::interp::Options::timeout`], or [`crate::interp::Options::gas`] allow
+  #[error("stopped for going past the {0} after running `{1}` instructions")]
+  ResourceLimit(Limit, u32), // (limit, instructions run)
+  /// A call or `main` was given the wrong number of arguments
   #[error("Expected `{0}` function arguments, found `{1}`")]
   BadNumFuncArgs(usize, usize), // (expected, actual)
+  /// An instruction has the wrong number of arguments
   #[error("Expected `{0}` instruction arguments, found `{1}`")]
   BadNumArgs(usize, usize), // (expected, actual)
+  /// An instruction has the wrong number of labels
   #[error("Expected `{0}` labels, found `{1}`")]
   BadNumLabels(usize, usize), // (expected, actual)
+  /// An instruction has the wrong number of functions
   #[error("Expected `{0}` functions, found `{1}`")]
   BadNumFuncs(usize, usize), // (expected, actual)
+  /// A call to a function which does not exist
   #[error("no function of name `{0}` found")]
   FuncNotFound(String),
+  /// An intrinsic returned an error
+  #[error("the intrinsic `@{0}` failed: {1}")]
+  IntrinsicFailed(String, Box<dyn std::error::Error + Send + Sync>),
+  /// An intrinsic returned a value which doesn't match its return type, or returned nothing when it has one
+  #[error("the intrinsic `@{0}` returned {1}, which doesn't match its return type")]
+  IntrinsicReturn(String, String),
+  /// A use of a variable which is never defined
   #[error("undefined variable `{0}`")]
   VarUndefined(String),
+  /// A jump or branch to a label which does not exist
+  #[error("label `{0}` not found")]
+  MissingLabel(String),
+  /// A `phi` refers to a label which does not exist
   #[error("Label `{0}` for phi node not found")]
   PhiMissingLabel(String),
+  /// A memory operation on something other than a pointer
   #[error("unspecified pointer type `{0:?}`")]
   ExpectedPointerType(bril_rs::Type), // found type
+  /// A value passed to a function or `main` does not have the expected type
   #[error("Expected type `{0:?}` for function argument, found `{1:?}`")]
   BadFuncArgType(bril_rs::Type, String), // (expected, actual)
+  /// The type of an instruction's destination does not match what it produces
   #[error("Expected type `{0:?}` for assignment, found `{1:?}`")]
   BadAsmtType(bril_rs::Type, bril_rs::Type), // (expected, actual). For when the LHS type of an instruction is bad
+  /// A `const` has a pointer type, which no literal can have
+  #[error("`const` can't make a `{0}`, since pointers only come from `alloc` and `ptradd`")]
+  PointerConst(bril_rs::Type),
+  /// The destination of an `id` has a different type than its argument
+  #[error("`{0}` has type `{1}`, so `id` of it can't have type `{2}`")]
+  BadIdType(String, bril_rs::Type, bril_rs::Type), // (arg, actual, expected)
+  /// A `ret` gives back a value of a different type than its function returns
+  #[error("`@{0}` returns `{1}`, found `{2}`")]
+  BadRetType(String, bril_rs::Type, bril_rs::Type), // (function, expected, actual)
+  /// A `ret` without a value in a function which returns one
+  #[error("`@{0}` returns `{1}`, found `ret` without a value")]
+  EmptyRetForFunc(String, bril_rs::Type),
+  /// Control can reach the end of a function which returns a value without going through a `ret`
+  #[error("`@{0}` can reach its end without returning a value of type `{1}`")]
+  MissingReturn(String, bril_rs::Type),
+  /// A call assigned a different number of variables than the function it calls returns values
+  #[error("`@{0}` returns `{1}` values, found a call assigning `{2}` variables")]
+  BadNumDests(String, usize, usize), // (function, expected, actual)
+  /// An instruction other than `call` assigned more than one variable
+  #[error("only `call` can assign more than one variable, found `{0}`")]
+  ExtraDests(bril_rs::ValueOps),
+  /// A variable or argument was given the type of the values returned together by a function
+  #[error("`{0}` can't have type `{1}`, which is only for the values returned by a function")]
+  TupleVariable(String, bril_rs::Type),
+  /// A `commit` ran without a `speculate` to commit
+  #[error("commit in non-speculative state")]
+  CommitOutsideSpeculation,
+  /// A `guard` failed without a `speculate` to fall back out of
+  #[error("abort in non-speculative state")]
+  AbortOutsideSpeculation,
+  /// A `guard` can be reached without having run a `speculate` which hasn't been committed yet
+  #[error("guard outside of speculation")]
+  GuardOutsideSpeculation,
+  /// A call, `ret`, `spawn`, or `join` ran while speculating
+  #[error("{0} not allowed during speculation")]
+  NotAllowedDuringSpeculation(String),
+  /// A function ran off of its end while speculating
+  #[error("implicit return in speculative state")]
+  ImplicitReturnDuringSpeculation,
+  /// A `join` of a thread which can't be joined
+  #[error("cannot join thread `{0}`, which {1}")]
+  InvalidJoin(u32, &'static str), // (thread, why not)
+  /// A function returned while a thread it spawned hadn't been joined yet
+  #[error("`@{0}` returned without joining `{1}` of the threads it spawned")]
+  UnjoinedThread(String, u32), // (function, number of threads)
+  /// A `join` of a thread which is waiting, directly or through other threads, for the thread joining it
+  #[error("deadlock: thread `{0}` is waiting for the thread joining it to finish")]
+  Deadlock(u32),
+  /// With [`crate::interp::Options::detect_races`], two threads accessed the same value of the heap without one having joined the other in between, and at least one of them changed it
+  #[error("data race between {2} and {3}, on offset `{0}` of the allocation made at {1}")]
+  DataRace(i32, String, String, String), // (offset, allocation site, earlier access, later access)
+  /// A `spawn` ran with something turned on which only knows how to follow a single thread
+  #[error("`spawn` is not supported with {0}")]
+  ThreadsUnsupported(&'static str),
+  /// The debugger was told to stop the program
+  #[error("execution was stopped by the debugger")]
+  DebuggerQuit,
+  /// Reading the program or writing its output failed
   #[error("There has been an io error when trying to print: `{0:?}`")]
   IoError(Box<std::io::Error>),
-  #[error("You probably shouldn't see this error, this is here to handle conversions between InterpError and PositionalError")]
+  /// Writing the profile requested with `--profile-out` to the given file failed
+  #[error("could not write the profile to `{0}`: {1}")]
+  ProfileOutput(String, Box<std::io::Error>),
+  /// Writing the coverage requested with `--coverage` to the given file failed
+  #[error("could not write the coverage to `{0}`: {1}")]
+  CoverageOutput(String, Box<std::io::Error>),
+  /// Saving a checkpoint for `--checkpoint-every` to the given file failed
+  #[error("could not write the checkpoint to `{0}`: {1}")]
+  CheckpointOutput(String, Box<std::io::Error>),
+  /// Loading the checkpoint given with `--resume` from the given file failed
+  #[error("could not read the checkpoint from `{0}`: {1}")]
+  CheckpointInput(String, Box<std::io::Error>),
+  /// Loading a profile which `--profile-out` wrote from the given file failed
+  #[error("could not read the profile from `{0}`: {1}")]
+  ProfileInput(String, Box<std::io::Error>),
+  /// A checkpoint refers to something which isn't in the program it is resumed with
+  #[error("the checkpoint was made from a different program: {0}")]
+  CheckpointMismatch(String),
+  /// Saving the recording for `--record` to the given file failed
+  #[error("could not write the recording to `{0}`: {1}")]
+  RecordingOutput(String, Box<std::io::Error>),
+  /// Loading the recording given with `--replay` from the given file failed
+  #[error("could not read the recording from `{0}`: {1}")]
+  RecordingInput(String, Box<std::io::Error>),
+  /// A run being replayed called an intrinsic differently than the recorded run did, so it can't be replayed any further
+  #[error("the run has gone differently than the recording: {0}")]
+  ReplayMismatch(String),
+  /// The cost model given with `--cost-model` could not be read from the given file
+  #[error("could not read the cost model from `{0}`: {1}")]
+  CostModelInput(String, Box<std::io::Error>),
+  /// A cost model had something other than the cost of an operation in it
+  #[error("invalid cost model: {0}")]
+  InvalidCostModel(String),
+  /// The arguments given with `--args-file` could not be read from the given file
+  #[error("could not read the arguments from `{0}`: {1}")]
+  ArgsInput(String, Box<std::io::Error>),
+  /// The arguments given with `--args-file` are not JSON arguments for `main`
+  #[error("invalid arguments for main: {0}")]
+  InvalidArgs(String),
+  /// The batch given with `--batch` could not be read from the given file
+  #[error("could not read the batch from `{0}`: {1}")]
+  BatchInput(String, Box<std::io::Error>),
+  /// Some of the runs of a `--batch` failed, which have already been reported with their output
+  #[error("{0} of the {1} runs in the batch failed")]
+  BatchFailed(usize, usize),
+  /// The program is not well-formed Bril text
+  #[error("invalid Bril text: {0}")]
+  InvalidText(String),
+  /// The program is not well-formed Bril JSON
+  #[error("invalid Bril JSON: {0}")]
+  InvalidJson(serde_json::Error),
+  /// The program is not a well-formed binary Bril program
+  #[error("invalid binary Bril program: {0}")]
+  InvalidBinary(bril_rs::binary::BinaryError),
+  /// The program could not be converted from its JSON or text form
+  #[error(transparent)]
+  InvalidProgram(bril_rs::conversion::ConversionError),
+  /// A `--pass` was given which `bril_opt::Registry` doesn't have, or the pass couldn't handle the program
+  #[error(transparent)]
+  Pass(bril_opt::RunError),
+  /// Writing the program requested with `--emit` to the given file failed
+  #[error("could not write the program to `{0}`: {1}")]
+  EmitOutput(String, Box<std::io::Error>),
+  /// The files the program imports from could not be linked in
+  #[error(transparent)]
+  Link(Box<bril_rs::link::LinkError<PositionalInterpError>>),
+  /// This is here to handle conversions between InterpError and PositionalError
+  #[error(transparent)]
   PositionalInterpErrorConversion(#[from] PositionalInterpError),
 }
 
 impl InterpError {
+  /// Attaches the position the error occurred at, if the program has source positions. Errors which already have one keep it.
   pub fn add_pos(self, pos: Option<Position>) -> PositionalInterpError {
     match self {
       Self::PositionalInterpErrorConversion(e) => e,
       _ => PositionalInterpError {
         e: Box::new(self),
         pos,
+        backtrace: Vec::new(),
+        #[cfg(feature = "miette")]
+        source_code: None,
       },
     }
   }
 }
 
+/// A limit on how long a program can run for, which stops it with [`InterpError::ResourceLimit`] once it is reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+  /// [`crate::interp::Options::max_instructions`]
+  Instructions(u64),
+  /// [`crate::interp::Options::timeout`]
+  Timeout(std::time::Duration),
+  /// [`crate::interp::Options::gas`]
+  Gas(u64),
+}
+
+impl Display for Limit {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Instructions(n) => write!(f, "limit of `{n}` instructions"),
+      Self::Timeout(t) => write!(f, "timeout of `{}`ms", t.as_millis()),
+      Self::Gas(n) => write!(f, "gas limit of `{n}`"),
+    }
+  }
+}
+
+/// Wraps [`InterpError`] with the source position it occurred at, if the program has source positions
 #[derive(Error, Debug)]
 pub struct PositionalInterpError {
   e: Box<InterpError>,
   pos: Option<Position>,
+  backtrace: Vec<BacktraceFrame>,
+  #[cfg(feature = "miette")]
+  source_code: Option<String>,
+}
+
+/// One of the calls which were running when a [`PositionalInterpError`] happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BacktraceFrame {
+  /// How many calls the call is outside of the one the error happened in, which is 0
+  pub depth: usize,
+  /// The name of the function being called
+  pub function: String,
+  /// The label of the block the call was in, unless it was the unlabelled block at the start of the function
+  pub label: Option<String>,
+  /// Where the error happened for the innermost call, and where each of the others made the call inside of it, if the program has source positions
+  pub pos: Option<Position>,
+}
+
+impl Display for BacktraceFrame {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "#{} @{}", self.depth, self.function)?;
+    if let Some(label) = &self.label {
+      write!(f, " in .{label}")?;
+    }
+    if let Some(pos) = self.pos {
+      write!(f, " at {}", location(pos))?;
+    }
+    Ok(())
+  }
+}
+
+// Where ```pos``` is, which is in the source of the frontend that generated the program when it says
+pub(crate) fn location(pos: Position) -> String {
+  pos.source.map_or_else(
+    || format!("Line {}, Column {}", pos.row, pos.col),
+    |source| {
+      format!(
+        "Line {}, Column {} of {}",
+        source.row, source.col, source.file
+      )
+    },
+  )
 }
 
 impl PositionalInterpError {
+  /// An error without a position, for problems with the program as a whole
   pub fn new(e: InterpError) -> Self {
-    Self {
-      e: Box::new(e),
-      pos: None,
+    InterpError::add_pos(e, None)
+  }
+
+  /// Where the error occurred
+  pub const fn pos(&self) -> Option<Position> {
+    self.pos
+  }
+
+  /// The calls which were running when the error happened, from the innermost to the outermost. Only the innermost and outermost few of a deep call stack are kept, so the depths of the frames can skip some calls in the middle. This is empty for errors which didn't happen while the program was running
+  pub fn backtrace(&self) -> &[BacktraceFrame] {
+    &self.backtrace
+  }
+
+  // Errors keep the first backtrace given to them, since more can be added as they are passed out of calls made by the embedder
+  pub(crate) fn with_backtrace(mut self, backtrace: impl FnOnce() -> Vec<BacktraceFrame>) -> Self {
+    if self.backtrace.is_empty() {
+      self.backtrace = backtrace();
     }
+    self
+  }
+
+  /// The underlying error without its position
+  pub fn error(&self) -> &InterpError {
+    &self.e
+  }
+
+  /// Attaches the Bril text of the program so that the error can be rendered with the offending line underlined
+  #[cfg(feature = "miette")]
+  #[must_use]
+  pub fn with_source_code(mut self, source_code: String) -> Self {
+    self.source_code = Some(source_code);
+    self
+  }
+
+  // The byte range of the source code from the error's position to the end of its line
+  #[cfg(feature = "miette")]
+  fn span(&self) -> Option<miette::SourceSpan> {
+    let source_code = self.source_code.as_ref()?;
+    let pos = self.pos?;
+    let mut start = 0;
+    let line = source_code
+      .split_inclusive('\n')
+      .inspect(|line| start += line.len())
+      .nth(usize::try_from(pos.row).ok()?.checked_sub(1)?)?;
+    start -= line.len();
+    let col = usize::try_from(pos.col).ok()?.checked_sub(1)?;
+    let len = line.get(col..)?.trim_end().len();
+    Some((start + col, len).into())
+  }
+}
+
+impl From<bril_rs::conversion::PositionalConversionError> for PositionalInterpError {
+  fn from(e: bril_rs::conversion::PositionalConversionError) -> Self {
+    let (e, pos) = e.into_parts();
+    InterpError::InvalidProgram(e).add_pos(pos)
   }
 }
 
 impl Display for PositionalInterpError {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
-      PositionalInterpError { e, pos: Some(pos) } => {
-        write!(f, "Line {}, Column {}: {e}", pos.row, pos.col)
+      Self {
+        e, pos: Some(pos), ..
+      } => {
+        write!(f, "{}: {e}", location(*pos))
       }
-      PositionalInterpError { e, pos: None } => write!(f, "{e}"),
+      Self { e, pos: None, .. } => write!(f, "{e}"),
     }
   }
 }
+
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for InterpError {}
+
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for PositionalInterpError {
+  fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+    self
+      .source_code
+      .as_ref()
+      .map(|s| s as &dyn miette::SourceCode)
+  }
+
+  fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+    let span = self.span()?;
+    Some(Box::new(std::iter::once(
+      miette::LabeledSpan::new_with_span(Some(self.e.to_string()), span),
+    )))
+  }
+}
@@ -0,0 +1,208 @@
+use std::path::Path;
+
+use bril_rs::{EffectOps, Instruction, ValueOps};
+use serde::de::value::{Error as NameError, StrDeserializer};
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+
+use crate::error::InterpError;
+
+/// How much each operation costs to run, so that a program can be measured in something closer to cycles than the number of instructions it runs. This is what `--gas` and `--cost-model` count with.
+///
+/// The default model has every operation cost 1 except for the ones which are usually slower on real machines: `mul` and `fmul` cost 3, `div`, `rem`, and `fdiv` cost 10, `call` costs 5, `load` and `store` cost 4, and `alloc` and `free` cost 10. Other models can be written as TOML with [`CostModel::from_toml`].
+/// ```
+/// use brilirs::gas::CostModel;
+///
+/// let model = CostModel::from_toml("# Memory is slow on this machine\nload = 20\nstore = 20\n").unwrap();
+/// let prog: bril_rs::Program = bril2json::parse_abstract_program_from_str(
+///   "@main(p: ptr<int>) { x: int = load p; y: int = add x x; }",
+///   false,
+/// ).unwrap().try_into().unwrap();
+/// let costs: Vec<u64> = prog.functions[0].instrs.iter().map(|code| match code {
+///   bril_rs::Code::Instruction(instr) => model.cost(instr),
+///   bril_rs::Code::Label { .. } => 0,
+/// }).collect();
+/// assert_eq!(costs, [20, 1]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostModel {
+  // The cost of every operation which isn't given one of its own
+  default: u64,
+  constant: Option<u64>,
+  // Indexed by the operation, and only as long as the last one which has a cost of its own
+  value: Vec<Option<u64>>,
+  effect: Vec<Option<u64>>,
+}
+
+impl Default for CostModel {
+  fn default() -> Self {
+    let mut model = Self::uniform(1);
+    for (op, cost) in [
+      ("mul", 3),
+      ("fmul", 3),
+      ("div", 10),
+      ("rem", 10),
+      ("fdiv", 10),
+      ("call", 5),
+      ("load", 4),
+      ("store", 4),
+      ("alloc", 10),
+      ("free", 10),
+    ] {
+      // Operations of extensions which aren't enabled can't be run anyway
+      let _ = model.set(op, cost);
+    }
+    model
+  }
+}
+
+// Puts ```cost``` at ```index``` of ```costs```, making room for it if there isn't any
+fn set_at(costs: &mut Vec<Option<u64>>, index: usize, cost: u64) {
+  if costs.len() <= index {
+    costs.resize(index + 1, None);
+  }
+  costs[index] = Some(cost);
+}
+
+fn at_line(line: usize, message: String) -> InterpError {
+  InterpError::InvalidCostModel(format!("line {line}: {message}"))
+}
+
+impl CostModel {
+  /// A model where every operation costs ```cost```
+  #[must_use]
+  pub const fn uniform(cost: u64) -> Self {
+    Self {
+      default: cost,
+      constant: None,
+      value: Vec::new(),
+      effect: Vec::new(),
+    }
+  }
+
+  /// Makes the operation named ```op```, as it is written in Bril text, cost ```cost```. Both kinds of `call` are given the cost. It is an error if there is no such operation, or it is part of an extension which brilirs wasn't built with
+  pub fn set(&mut self, op: &str, cost: u64) -> Result<(), InterpError> {
+    let name = || -> StrDeserializer<'_, NameError> { op.into_deserializer() };
+    let value = ValueOps::deserialize(name()).ok();
+    let effect = EffectOps::deserialize(name()).ok();
+    if op == "const" {
+      self.constant = Some(cost);
+    } else if value.is_none() && effect.is_none() {
+      return Err(InterpError::InvalidCostModel(format!(
+        "there is no operation named `{op}`"
+      )));
+    }
+    if let Some(value) = value {
+      set_at(&mut self.value, value as usize, cost);
+    }
+    if let Some(effect) = effect {
+      set_at(&mut self.effect, effect as usize, cost);
+    }
+    Ok(())
+  }
+
+  /// Reads a model from TOML made of a key for each operation with its cost, like `load = 4`, where operations which aren't given a cost keep their cost from [`CostModel::default`]. A key named `default` instead gives every operation which isn't listed that cost. Tables and values other than integers aren't supported
+  pub fn from_toml(src: &str) -> Result<Self, InterpError> {
+    let mut costs = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+      let line_number = i + 1;
+      let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+      if line.is_empty() {
+        continue;
+      }
+      let (key, cost) = line.split_once('=').ok_or_else(|| {
+        at_line(
+          line_number,
+          format!("expected `operation = cost`, found `{line}`"),
+        )
+      })?;
+      let key = key.trim();
+      let key = key
+        .strip_prefix('"')
+        .and_then(|k| k.strip_suffix('"'))
+        .unwrap_or(key);
+      let cost = cost.trim();
+      let cost = cost.replace('_', "").parse::<u64>().map_err(|_| {
+        at_line(
+          line_number,
+          format!("`{cost}` is not a cost, which has to be a whole number"),
+        )
+      })?;
+      costs.push((line_number, key, cost));
+    }
+
+    let mut model = costs
+      .iter()
+      .rev()
+      .find(|(_, key, _)| *key == "default")
+      .map_or_else(Self::default, |(_, _, cost)| Self::uniform(*cost));
+    for (line_number, key, cost) in costs {
+      if key != "default" {
+        model.set(key, cost).map_err(|e| match e {
+          InterpError::InvalidCostModel(e) => at_line(line_number, e),
+          e => e,
+        })?;
+      }
+    }
+    Ok(model)
+  }
+
+  /// Reads the model in the file at ```path``` with [`CostModel::from_toml`]
+  pub fn load(path: &Path) -> Result<Self, InterpError> {
+    let src = std::fs::read_to_string(path)
+      .map_err(|e| InterpError::CostModelInput(path.display().to_string(), Box::new(e)))?;
+    Self::from_toml(&src).map_err(|e| match e {
+      InterpError::InvalidCostModel(e) => {
+        InterpError::InvalidCostModel(format!("`{}` {e}", path.display()))
+      }
+      e => e,
+    })
+  }
+
+  /// What running ```instr``` costs
+  #[must_use]
+  #[inline(always)]
+  pub fn cost(&self, instr: &Instruction) -> u64 {
+    let cost = match instr {
+      Instruction::Constant { .. } => self.constant,
+      Instruction::Value { op, .. } => self.value.get(*op as usize).copied().flatten(),
+      Instruction::Effect { op, .. } => self.effect.get(*op as usize).copied().flatten(),
+    };
+    cost.unwrap_or(self.default)
+  }
+}
+
+// The gas a run has used so far, and how much it is allowed to use
+pub(crate) struct Gas {
+  model: CostModel,
+  pub(crate) used: u64,
+  limit: Option<u64>,
+  // How many instructions have been paid for, which is every instruction that has run
+  pub(crate) instructions: u32,
+}
+
+impl Gas {
+  pub(crate) const fn new(model: CostModel, limit: Option<u64>) -> Self {
+    Self {
+      model,
+      used: 0,
+      limit,
+      instructions: 0,
+    }
+  }
+
+  // Pays for running ```instr```, unless that would use more gas than the limit allows
+  #[inline(always)]
+  pub(crate) fn charge(&mut self, instr: &Instruction) -> Result<(), InterpError> {
+    let used = self.used.saturating_add(self.model.cost(instr));
+    if let Some(limit) = self.limit.filter(|limit| used > *limit) {
+      return Err(InterpError::ResourceLimit(
+        crate::error::Limit::Gas(limit),
+        self.instructions,
+      ));
+    }
+    self.used = used;
+    self.instructions += 1;
+    Ok(())
+  }
+}
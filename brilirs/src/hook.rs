@@ -0,0 +1,159 @@
+use bril_rs::Instruction;
+
+use crate::basic_block::{BBFunction, NumifiedInstruction};
+use crate::interp::{Environment, RuntimeValue};
+use crate::value::Strings;
+
+/// Code which runs alongside a program, given to the interpreter with [`crate::interp::Options::hook`].
+///
+/// Dynamic analyses like taint tracking or memory profiling can use this to watch every instruction and call without changing the interpreter.
+///
+/// Every method does nothing unless it is implemented, so a hook only has to implement the ones it cares about. The interpreter owns the hook for as long as the program runs, so anything it records which is needed afterwards should be shared with the code which made it, for example through an [`std::rc::Rc`] of a [`std::cell::RefCell`].
+///
+/// ```
+/// use std::cell::RefCell;
+/// use std::collections::HashMap;
+/// use std::rc::Rc;
+///
+/// use brilirs::basic_block::BBProgram;
+/// use brilirs::hook::{InstCtx, InterpHook};
+/// use brilirs::interp::{execute_main_with_options, Options};
+///
+/// // Counts how many times each operation runs
+/// struct OpCounter(Rc<RefCell<HashMap<String, u64>>>);
+///
+/// impl InterpHook for OpCounter {
+///   fn before_inst(&mut self, ctx: &InstCtx<'_>) {
+///     *self.0.borrow_mut().entry(ctx.op()).or_default() += 1;
+///   }
+/// }
+///
+/// let prog = bril2json::parse_abstract_program_from_str(
+///   "@main { x: int = const 2; y: int = add x x; z: int = add y x; print z; }",
+///   false,
+/// )
+/// .unwrap();
+/// let prog = BBProgram::try_from(bril_rs::Program::try_from(prog).unwrap()).unwrap();
+///
+/// let counts = Rc::new(RefCell::new(HashMap::new()));
+/// let mut out = Vec::new();
+/// execute_main_with_options(
+///   &prog,
+///   &mut out,
+///   &[],
+///   Options {
+///     hook: Some(Box::new(OpCounter(Rc::clone(&counts)))),
+///     ..Options::default()
+///   },
+/// )
+/// .unwrap();
+/// assert_eq!(out, b"6\n");
+/// assert_eq!(counts.borrow()["add"], 2);
+/// assert_eq!(counts.borrow()["print"], 1);
+/// ```
+pub trait InterpHook {
+  /// Called just before each instruction runs, when its arguments have the values it will use
+  fn before_inst(&mut self, _ctx: &InstCtx<'_>) {}
+
+  /// Called just after each instruction which carries on to the next one has run, when its destination has the value it assigned. Jumps, branches, returns, calls, and guards which fail leave the instruction they are in, so this isn't called for them; calls are followed by [`InterpHook::after_call`] instead
+  fn after_inst(&mut self, _ctx: &InstCtx<'_>) {}
+
+  /// Called when ```callee``` starts running with ```args``` as its arguments, which includes `main` at the start of the program
+  fn before_call(&mut self, _callee: &str, _args: &[RuntimeValue]) {}
+
+  /// Called when ```callee``` returns, with the value it returned if it has a return type. With [`crate::interp::Options::tco`], a function which is replaced by the call it makes in tail position never returns itself, so this is only called for the function which replaced it
+  fn after_call(&mut self, _callee: &str, _returned: Option<&RuntimeValue>) {}
+}
+
+/// The instruction a [`InterpHook`] is called for, along with the state of the function it is in
+pub struct InstCtx<'a> {
+  func: &'a BBFunction,
+  label: Option<&'a str>,
+  instr: &'a Instruction,
+  numified: &'a NumifiedInstruction,
+  env: &'a Environment,
+  strings: &'a Strings,
+}
+
+impl<'a> InstCtx<'a> {
+  pub(crate) const fn new(
+    func: &'a BBFunction,
+    label: Option<&'a str>,
+    instr: &'a Instruction,
+    numified: &'a NumifiedInstruction,
+    env: &'a Environment,
+    strings: &'a Strings,
+  ) -> Self {
+    Self {
+      func,
+      label,
+      instr,
+      numified,
+      env,
+      strings,
+    }
+  }
+
+  /// The name of the function the instruction is in
+  #[must_use]
+  pub fn function(&self) -> &'a str {
+    &self.func.name
+  }
+
+  /// The label of the block the instruction is in, if it has one
+  #[must_use]
+  pub const fn label(&self) -> Option<&'a str> {
+    self.label
+  }
+
+  /// The instruction itself
+  #[must_use]
+  pub const fn instruction(&self) -> &'a Instruction {
+    self.instr
+  }
+
+  /// The operation of the instruction, like `add` or `print`
+  #[must_use]
+  pub fn op(&self) -> String {
+    match self.instr {
+      Instruction::Constant { op, .. } => op.to_string(),
+      Instruction::Value { op, .. } => op.to_string(),
+      Instruction::Effect { op, .. } => op.to_string(),
+    }
+  }
+
+  /// The value of argument ```index``` of the instruction, or [`None`] if it doesn't have that many arguments or the variable hasn't been assigned to
+  #[must_use]
+  pub fn arg(&self, index: usize) -> Option<RuntimeValue> {
+    self
+      .numified
+      .args
+      .get(index)
+      .and_then(|var| self.variable(*var))
+  }
+
+  /// The values of all of the arguments of the instruction, in order
+  #[must_use]
+  pub fn args(&self) -> Vec<Option<RuntimeValue>> {
+    self
+      .numified
+      .args
+      .iter()
+      .map(|var| self.variable(*var))
+      .collect()
+  }
+
+  /// The value of the destination of the instruction, which is what it assigned in [`InterpHook::after_inst`] and whatever the variable held before in [`InterpHook::before_inst`]. This is [`None`] for instructions without a destination and for variables which haven't been assigned to
+  #[must_use]
+  pub fn dest_value(&self) -> Option<RuntimeValue> {
+    self.numified.dest.and_then(|var| self.variable(var))
+  }
+
+  fn variable(&self, var: u32) -> Option<RuntimeValue> {
+    self
+      .env
+      .lookup(var as usize)
+      .zip(self.func.var_types[var as usize].as_ref())
+      .map(|(value, ty)| RuntimeValue::new(value, ty, self.strings))
+  }
+}
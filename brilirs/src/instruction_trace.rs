@@ -0,0 +1,155 @@
+use std::io::Write;
+
+use bril_rs::{Instruction, Position};
+use serde::Serialize;
+
+use crate::basic_block::{BBFunction, NumifiedInstruction};
+use crate::error::InterpError;
+use crate::interp::{Environment, RuntimeValue};
+use crate::value::Strings;
+
+/// Writes a line of JSON for every instruction the program runs, like `{"func":"main","op":"add","dest":"z","args":[{"name":"x","value":1},{"name":"y","value":2}],"value":3}`.
+///
+/// Each line has the function the instruction is in, its operation, the values of its arguments as they were just before it ran, any functions or labels it names, and where it is in the source if the program was given as text. Instructions with a destination also have the value they assigned to it, except for calls, whose results show up in the `ret` of the function they call. Pointers are written as their `base` allocation and `offset` into it, and floats which JSON can't represent are written as strings the way `print` shows them. Lines are only written for instructions which are in one of the functions and use one of the operations being traced, when there are any of either to pick from.
+pub struct InstructionTrace {
+  out: Box<dyn Write>,
+  funcs: Vec<String>,
+  ops: Vec<String>,
+}
+
+// An instruction which has run, waiting for its result to be filled in if it has one
+#[derive(Serialize)]
+pub(crate) struct Event<'a> {
+  func: &'a str,
+  op: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  dest: Option<&'a str>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  args: Vec<Arg<'a>>,
+  #[serde(skip_serializing_if = "<[_]>::is_empty")]
+  funcs: &'a [String],
+  #[serde(skip_serializing_if = "<[_]>::is_empty")]
+  labels: &'a [String],
+  #[serde(skip_serializing_if = "Option::is_none")]
+  value: Option<serde_json::Value>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pos: Option<Position>,
+}
+
+#[derive(Serialize)]
+struct Arg<'a> {
+  name: &'a str,
+  value: serde_json::Value,
+}
+
+impl InstructionTrace {
+  /// Traces to ```out``` the instructions which are in one of ```funcs``` and use one of ```ops```, where either being empty allows everything
+  #[must_use]
+  pub fn new(out: Box<dyn Write>, funcs: Vec<String>, ops: Vec<String>) -> Self {
+    Self { out, funcs, ops }
+  }
+
+  // Starts tracing ```code``` just before it runs. Instructions which might leave the block or the function are written out straight away, and the rest are given back to be finished with their result once they have run
+  pub(crate) fn start<'a>(
+    &mut self,
+    func: &'a BBFunction,
+    code: &'a Instruction,
+    numified_code: &NumifiedInstruction,
+    env: &Environment,
+    strings: &Strings,
+  ) -> Result<Option<Event<'a>>, InterpError> {
+    let op = match code {
+      Instruction::Constant { op, .. } => op.to_string(),
+      Instruction::Value { op, .. } => op.to_string(),
+      Instruction::Effect { op, .. } => op.to_string(),
+    };
+    if !(self.funcs.is_empty() || self.funcs.contains(&func.name))
+      || !(self.ops.is_empty() || self.ops.contains(&op))
+    {
+      return Ok(None);
+    }
+    let (dest, args, funcs, labels): (_, &[String], &[String], &[String]) = match code {
+      Instruction::Constant { dest, .. } => (Some(dest.as_str()), &[], &[], &[]),
+      Instruction::Value {
+        dest,
+        args,
+        funcs,
+        labels,
+        ..
+      } => (Some(dest.as_str()), args, funcs, labels),
+      Instruction::Effect {
+        args,
+        funcs,
+        labels,
+        ..
+      } => (None, args, funcs, labels),
+    };
+    let event = Event {
+      func: &func.name,
+      dest,
+      args: args
+        .iter()
+        .zip(&numified_code.args)
+        .map(|(name, var)| Arg {
+          name,
+          value: variable_value(func, env, strings, *var),
+        })
+        .collect(),
+      funcs,
+      labels,
+      value: None,
+      pos: code.get_pos(),
+      op,
+    };
+    if dest.is_some() && funcs.is_empty() {
+      Ok(Some(event))
+    } else {
+      self.write(&event)?;
+      Ok(None)
+    }
+  }
+
+  // Writes out ```event``` along with the value it assigned to ```dest```
+  pub(crate) fn finish(
+    &mut self,
+    mut event: Event<'_>,
+    func: &BBFunction,
+    env: &Environment,
+    strings: &Strings,
+    dest: u32,
+  ) -> Result<(), InterpError> {
+    event.value = Some(variable_value(func, env, strings, dest));
+    self.write(&event)
+  }
+
+  fn write(&mut self, event: &Event<'_>) -> Result<(), InterpError> {
+    serde_json::to_writer(&mut self.out, event)
+      .map_err(std::io::Error::from)
+      .and_then(|()| writeln!(self.out))
+      .map_err(|e| InterpError::IoError(Box::new(e)))
+  }
+}
+
+// The value of ```var``` as JSON, or null if it hasn't been assigned to
+fn variable_value(
+  func: &BBFunction,
+  env: &Environment,
+  strings: &Strings,
+  var: u32,
+) -> serde_json::Value {
+  let value = env
+    .lookup(var as usize)
+    .zip(func.var_types[var as usize].as_ref())
+    .map(|(value, ty)| RuntimeValue::new(value, ty, strings));
+  match value {
+    None => serde_json::Value::Null,
+    Some(RuntimeValue::Int(i)) => i.into(),
+    Some(RuntimeValue::Bool(b)) => b.into(),
+    Some(RuntimeValue::Float(f)) if f.is_finite() => f.into(),
+    Some(v @ (RuntimeValue::Float(_) | RuntimeValue::Char(_) | RuntimeValue::Thread(_))) => {
+      v.to_string().into()
+    }
+    Some(RuntimeValue::Str(s)) => s.as_ref().into(),
+    Some(RuntimeValue::Pointer(p)) => serde_json::json!({"base": p.base, "offset": p.offset}),
+  }
+}
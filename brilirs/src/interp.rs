@@ -1,19 +1,43 @@
+use std::collections::VecDeque;
 use std::fmt;
 use std::hint::unreachable_unchecked;
+use std::sync::Arc;
 
-use crate::basic_block::{BBFunction, BBProgram, BasicBlock};
-use crate::error::{InterpError, PositionalInterpError};
-use bril_rs::Instruction;
+use crate::basic_block::{BBFunction, BBProgram, BasicBlock, NumifiedInstruction};
+use crate::checkpoint::{AllocationState, Checkpoint, FrameState, HeapState, OriginState};
+use crate::cli::{DivSemantics, Overflow, Schedule};
+use crate::coverage::{Coverage, CoverageRecorder};
+use crate::debug::Debugger;
+use crate::error::{BacktraceFrame, InterpError, Limit, PositionalInterpError};
+use crate::gas::{CostModel, Gas};
+use crate::hook::{InstCtx, InterpHook};
+use crate::instruction_trace::{Event, InstructionTrace};
+use crate::intrinsic::Intrinsic;
+use crate::op::Op;
+use crate::profile::{ExecutionStats, MemoryProfile, MemoryProfiler, Profile, Profiler};
+use crate::rng::Rng;
+use crate::sink::{Printed, Sink};
+use crate::thread::{Race, RaceDetector, Scheduler, DEFAULT_QUANTUM};
+use crate::trace::{Trace, Tracer};
+pub use crate::value::Pointer;
+use crate::value::{format_float, parse_float, parse_int, Strings, Value};
+use bril_rs::{Instruction, Position, Type};
 
 use fxhash::FxHashMap;
+use serde::{Deserialize, Serialize};
 
+#[cfg(not(target_arch = "wasm32"))]
 use mimalloc::MiMalloc;
 
+#[cfg(not(target_arch = "wasm32"))]
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
-struct Environment {
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub(crate) struct Environment {
   env: Vec<Value>,
+  // Whether each variable has been assigned to yet, since values can't be undefined themselves. This is always as long as env
+  defined: Vec<bool>,
 }
 
 impl Environment {
@@ -21,590 +45,3265 @@ impl Environment {
   pub fn new(size: u32) -> Self {
     Self {
       env: vec![Value::default(); size as usize],
+      defined: vec![false; size as usize],
     }
   }
+  // Clears out the environment of a call which has returned so that it can be reused for a call to a function with ```size``` variables. Values left over from the last call are only marked as undefined, since nothing reads a variable before assigning it
   #[inline(always)]
-  pub fn get(&self, ident: &u32) -> &Value {
+  fn reset(&mut self, size: u32) {
+    self.env.resize(size as usize, Value::default());
+    self.defined.clear();
+    self.defined.resize(size as usize, false);
+  }
+  #[inline(always)]
+  pub fn get(&self, ident: &u32) -> Value {
     // A bril program is well formed when, dynamically, every variable is defined before its use.
-    // If this is violated, this will return Value::Uninitialized and the whole interpreter will come crashing down.
-    self.env.get(*ident as usize).unwrap()
+    // If this is violated, this will return whatever the variable was initialized to and the whole interpreter will come crashing down.
+    debug_assert!((*ident as usize) < self.env.len());
+    // This is safe because variables are numbered below num_of_vars, which every environment of a function is made, reset or resumed with as many values as
+    unsafe { *self.env.get_unchecked(*ident as usize) }
   }
   #[inline(always)]
   pub fn set(&mut self, ident: u32, val: Value) {
-    self.env[ident as usize] = val;
+    debug_assert!((ident as usize) < self.env.len());
+    // This is safe for the same reason as in get, and defined is as long as env
+    unsafe {
+      *self.env.get_unchecked_mut(ident as usize) = val;
+      *self.defined.get_unchecked_mut(ident as usize) = true;
+    }
+  }
+  // Makes ```ident``` undefined again, like a `phi` which takes an argument that was never assigned to
+  #[inline(always)]
+  pub fn unset(&mut self, ident: u32) {
+    self.defined[ident as usize] = false;
+  }
+  // The value of ```ident``` if it has been defined, for looking at the state of the program from outside
+  pub fn lookup(&self, ident: usize) -> Option<Value> {
+    self.defined[ident].then(|| self.env[ident])
   }
 }
 
-// todo: This is basically a copy of the heap implement in brili and we could probably do something smarter. This currently isn't that worth it to optimize because most benchmarks do not use the memory extension nor do they run for very long. You (the reader in the future) may be working with bril programs that you would like to speed up that extensively use the bril memory extension. In that case, it would be worth seeing how to implement Heap without a map based memory. Maybe try to re-implement malloc for a large Vec<Value>?
-struct Heap {
-  memory: FxHashMap<usize, Vec<Value>>,
-  base_num_counter: usize,
+// Where an instruction is in the program, so that memory errors can point at where the memory involved was allocated and freed
+#[derive(Clone, Copy)]
+pub(crate) struct Site<'a> {
+  func: &'a BBFunction,
+  block: usize,
+  instr: usize,
 }
 
-impl Default for Heap {
-  fn default() -> Self {
-    Self {
-      memory: FxHashMap::with_capacity_and_hasher(20, fxhash::FxBuildHasher::default()),
-      base_num_counter: 0,
+impl<'a> Site<'a> {
+  // The type of the values allocated at this site, if it is an `alloc`
+  fn allocated_type(&self) -> Option<&'a Type> {
+    match &self.func.blocks[self.block].instrs[self.instr] {
+      Instruction::Value {
+        op_type: Type::Pointer(ty),
+        ..
+      } => Some(ty),
+      _ => None,
     }
   }
 }
 
-impl Heap {
-  #[inline(always)]
-  fn is_empty(&self) -> bool {
-    self.memory.is_empty()
+impl fmt::Display for Site<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.func.blocks[self.block].instrs[self.instr].get_pos() {
+      Some(pos) if pos.source.is_some() => {
+        write!(f, "{} in @{}", crate::error::location(pos), self.func.name)
+      }
+      Some(pos) => write!(
+        f,
+        "Line {}, Column {} of @{}",
+        pos.row, pos.col, self.func.name
+      ),
+      None => {
+        let index = self.func.blocks[..self.block]
+          .iter()
+          .map(|b| b.instrs.len())
+          .sum::<usize>()
+          + self.instr;
+        write!(f, "instruction {index} of @{}", self.func.name)
+      }
+    }
+  }
+}
+
+// Where an allocation was made or freed. Once the heap is handed on to another program, the site is replaced by what it said and the type it allocated since the function it pointed into may be gone
+enum Origin<'a> {
+  Site(Site<'a>),
+  Detached(Box<(String, Option<Type>)>),
+}
+
+impl Origin<'_> {
+  // The type of the values allocated here, which has to be where an allocation was made
+  fn pointee_type(&self) -> &Type {
+    let ty = match self {
+      Self::Site(site) => site.allocated_type(),
+      Self::Detached(detached) => detached.1.as_ref(),
+    };
+    // This is safe because only the sites that allocations were made at are asked for their type, and alloc always has a pointer type after type checking
+    ty.unwrap_or_else(|| unsafe { unreachable_unchecked() })
   }
 
-  #[inline(always)]
-  fn alloc(&mut self, amount: i64) -> Result<Value, InterpError> {
-    if amount < 0 {
-      return Err(InterpError::CannotAllocSize(amount));
+  fn detach(self) -> Origin<'static> {
+    match self {
+      Self::Site(site) => {
+        Origin::Detached(Box::new((site.to_string(), site.allocated_type().cloned())))
+      }
+      Self::Detached(detached) => Origin::Detached(detached),
     }
-    let base = self.base_num_counter;
-    self.base_num_counter += 1;
-    self
-      .memory
-      .insert(base, vec![Value::default(); amount as usize]);
-    Ok(Value::Pointer(Pointer { base, offset: 0 }))
   }
 
-  #[inline(always)]
-  fn free(&mut self, key: &Pointer) -> Result<(), InterpError> {
-    if self.memory.remove(&key.base).is_some() && key.offset == 0 {
-      Ok(())
-    } else {
-      Err(InterpError::IllegalFree(key.base, key.offset))
+  fn save(&self) -> OriginState {
+    match self {
+      Self::Site(site) => OriginState::Site {
+        function: site.func.name.clone(),
+        block: site.block,
+        instr: site.instr,
+      },
+      Self::Detached(detached) => OriginState::Detached(detached.0.clone(), detached.1.clone()),
     }
   }
+}
 
-  #[inline(always)]
-  fn write(&mut self, key: &Pointer, val: Value) -> Result<(), InterpError> {
-    match self.memory.get_mut(&key.base) {
-      Some(vec) if vec.len() > (key.offset as usize) && key.offset >= 0 => {
-        vec[key.offset as usize] = val;
-        Ok(())
+impl<'a> Origin<'a> {
+  // The origin saved in ```state```, pointing back into ```prog``` if it was part of the program
+  fn restore(state: OriginState, prog: &'a BBProgram) -> Result<Self, InterpError> {
+    match state {
+      OriginState::Site {
+        function,
+        block,
+        instr,
+      } => {
+        let func = checkpointed_function(prog, &function)?;
+        if func
+          .blocks
+          .get(block)
+          .is_some_and(|b| instr < b.instrs.len())
+        {
+          Ok(Self::Site(Site { func, block, instr }))
+        } else {
+          Err(InterpError::CheckpointMismatch(format!(
+            "@{function} has no instruction {instr} in block {block}"
+          )))
+        }
       }
-      Some(_) | None => Err(InterpError::InvalidMemoryAccess(key.base, key.offset)),
+      OriginState::Detached(description, ty) => Ok(Self::Detached(Box::new((description, ty)))),
     }
   }
+}
 
-  #[inline(always)]
-  fn read(&self, key: &Pointer) -> Result<&Value, InterpError> {
-    self
-      .memory
-      .get(&key.base)
-      .and_then(|vec| vec.get(key.offset as usize))
-      .ok_or(InterpError::InvalidMemoryAccess(key.base, key.offset))
-      .and_then(|val| match val {
-        Value::Uninitialized => Err(InterpError::UsingUninitializedMemory),
-        _ => Ok(val),
-      })
+// The function called ```name``` in ```prog```, which a checkpoint being resumed refers to
+fn checkpointed_function<'a>(
+  prog: &'a BBProgram,
+  name: &str,
+) -> Result<&'a BBFunction, InterpError> {
+  prog
+    .get(name)
+    .ok_or_else(|| InterpError::CheckpointMismatch(format!("there is no function @{name}")))
+}
+
+impl fmt::Display for Origin<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Site(site) => site.fmt(f),
+      Self::Detached(detached) => f.write_str(&detached.0),
+    }
   }
 }
 
+// A single allocation which lives in ```Heap::arena[start..start + len]```
+struct Allocation<'a> {
+  start: usize,
+  len: usize,
+  // How many allocations were kept in the same slot before this one
+  generation: u32,
+  // How many allocations were made before this one
+  number: usize,
+  allocated_at: Origin<'a>,
+  // None while the allocation is live
+  freed_at: Option<Origin<'a>>,
+}
+
+// The count of an allocation which is never collected with Options::rc_heap
+const PINNED: u32 = u32::MAX;
+
+// The base of a pointer is the slot its allocation is kept in, in the low SLOT_BITS bits, and the generation of that allocation in the rest
+const SLOT_BITS: u32 = 24;
+const SLOT_MASK: u32 = (1 << SLOT_BITS) - 1;
+// A slot whose generation has run out is never used again, rather than starting over from 0
+const MAX_GENERATION: u32 = u32::MAX >> SLOT_BITS;
+
+// The slot of the allocation that a pointer with ```base``` points into
 #[inline(always)]
-fn get_value<'a>(vars: &'a Environment, index: usize, args: &[u32]) -> &'a Value {
-  vars.get(&args[index])
+const fn slot(base: u32) -> usize {
+  (base & SLOT_MASK) as usize
 }
 
+// The base of the pointers into the allocation in ```slot``` with ```generation```
 #[inline(always)]
-fn get_arg<'a, T>(vars: &'a Environment, index: usize, args: &[u32]) -> T
-where
-  T: From<&'a Value>,
-{
-  T::from(vars.get(&args[index]))
+const fn base(slot: usize, generation: u32) -> u32 {
+  slot as u32 | (generation << SLOT_BITS)
 }
 
-#[derive(Debug, Clone)]
-enum Value {
-  Int(i64),
-  Bool(bool),
-  Float(f64),
-  Pointer(Pointer),
-  Uninitialized,
+// The heap is one large arena of values which allocations are bumped out of. Freed regions are kept on a free list by size so that the next allocation of the same size can reuse them.
+// Each allocation is kept in a slot, which is handed out again once the allocation is freed. Slots are reused in the order they were freed so that a freed allocation keeps its slot for as long as possible, which lets a pointer to it still be reported with where it was allocated and freed. Every reuse of a slot bumps its generation, which is part of the base of the pointers into it, so a pointer into a freed allocation is still caught as an invalid access after its slot and region have been handed out again.
+pub(crate) struct Heap<'a> {
+  arena: Vec<Value>,
+  // Whether each value in the arena has been stored to since it was allocated, since values themselves can't be uninitialized
+  initialized: Vec<bool>,
+  // Indexed by slot. A freed allocation stays in its slot until the slot is reused, to report where it came from if it is used again
+  allocations: Vec<Allocation<'a>>,
+  free_list: FxHashMap<usize, Vec<usize>>,
+  // The slots of freed allocations, in the order they were freed
+  free_slots: VecDeque<u32>,
+  // How many allocations have been made, including those which have been freed
+  made: usize,
+  num_live: usize,
+  // The number of values in live allocations, now and at most so far
+  live_values: usize,
+  peak_values: usize,
+  // Strings are kept with the heap so that they go wherever its pointers go
+  strings: Strings,
+  // With Options::rc_heap, the number of pointers to each allocation, indexed by slot
+  counts: Option<Vec<u32>>,
+  // The number of values which have been freed because nothing pointed to them any more
+  collected_values: usize,
 }
 
-impl Default for Value {
+impl Default for Heap<'_> {
   fn default() -> Self {
-    Self::Uninitialized
+    Self::new(0)
   }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-struct Pointer {
-  base: usize,
-  offset: i64,
-}
-
-impl Pointer {
-  const fn add(&self, offset: i64) -> Self {
+impl<'a> Heap<'a> {
+  // ```size``` is the number of values to reserve room for up front
+  pub(crate) fn new(size: usize) -> Self {
     Self {
-      base: self.base,
-      offset: self.offset + offset,
+      arena: Vec::with_capacity(size),
+      initialized: Vec::with_capacity(size),
+      allocations: Vec::new(),
+      free_list: FxHashMap::default(),
+      free_slots: VecDeque::new(),
+      made: 0,
+      num_live: 0,
+      live_values: 0,
+      peak_values: 0,
+      strings: Strings::default(),
+      counts: None,
+      collected_values: 0,
     }
   }
-}
 
-impl fmt::Display for Value {
-  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    match self {
-      Value::Int(i) => write!(f, "{i}"),
-      Value::Bool(b) => write!(f, "{b}"),
-      Value::Float(v) => write!(f, "{v}"),
-      Value::Pointer(p) => write!(f, "{p:?}"),
-      // This is safe because Uninitialized is only used in relation to memory and immediately errors if this value is returned. Otherwise this value can not appear in the code
-      Value::Uninitialized => unsafe { unreachable_unchecked() },
-    }
+  #[inline(always)]
+  const fn is_empty(&self) -> bool {
+    self.num_live == 0
   }
-}
 
-impl From<&bril_rs::Literal> for Value {
-  #[inline(always)]
-  fn from(l: &bril_rs::Literal) -> Self {
-    match l {
-      bril_rs::Literal::Int(i) => Self::Int(*i),
-      bril_rs::Literal::Bool(b) => Self::Bool(*b),
-      bril_rs::Literal::Float(f) => Self::Float(*f),
-    }
+  pub(crate) const fn strings(&self) -> &Strings {
+    &self.strings
   }
-}
 
-impl From<bril_rs::Literal> for Value {
-  #[inline(always)]
-  fn from(l: bril_rs::Literal) -> Self {
-    match l {
-      bril_rs::Literal::Int(i) => Self::Int(i),
-      bril_rs::Literal::Bool(b) => Self::Bool(b),
-      bril_rs::Literal::Float(f) => Self::Float(f),
-    }
+  // The base of every allocation which hasn't been freed, in the order they were made
+  fn live_bases(&self) -> Vec<usize> {
+    let mut live: Vec<(usize, &Allocation<'a>)> = self
+      .allocations
+      .iter()
+      .enumerate()
+      .filter(|(_, a)| a.freed_at.is_none())
+      .collect();
+    live.sort_unstable_by_key(|(_, a)| a.number);
+    live
+      .into_iter()
+      .map(|(slot, a)| base(slot, a.generation) as usize)
+      .collect()
   }
-}
 
-impl From<&Value> for i64 {
-  #[inline(always)]
-  fn from(value: &Value) -> Self {
-    if let Value::Int(i) = value {
-      *i
-    } else {
-      // This is safe because we type check the program beforehand
-      unsafe { unreachable_unchecked() }
-    }
+  // Every allocation which hasn't been freed in the order they were made along with the type of its values and each of its values that has been initialized, for the debugger
+  pub(crate) fn live_allocations(
+    &self,
+  ) -> impl Iterator<Item = (usize, &Type, Vec<Option<Value>>)> + '_ {
+    self
+      .live_bases()
+      .into_iter()
+      .filter_map(|base| self.allocation(base).map(|(ty, values)| (base, ty, values)))
   }
-}
 
-impl From<&Value> for bool {
-  #[inline(always)]
-  fn from(value: &Value) -> Self {
-    if let Value::Bool(b) = value {
-      *b
-    } else {
-      // This is safe because we type check the program beforehand
-      unsafe { unreachable_unchecked() }
-    }
+  // The allocation which pointers with ```base``` point into, unless it has been freed
+  fn live(&self, base: usize) -> Option<&Allocation<'a>> {
+    let base = u32::try_from(base).ok()?;
+    self
+      .allocations
+      .get(slot(base))
+      .filter(|a| a.generation == base >> SLOT_BITS && a.freed_at.is_none())
   }
-}
 
-impl From<&Value> for f64 {
-  #[inline(always)]
-  fn from(value: &Value) -> Self {
-    if let Value::Float(f) = value {
-      *f
-    } else {
-      // This is safe because we type check the program beforehand
-      unsafe { unreachable_unchecked() }
-    }
+  // How many allocations were made before allocation ```base```, unless it has been freed
+  pub(crate) fn number(&self, base: usize) -> Option<usize> {
+    self.live(base).map(|a| a.number)
   }
-}
 
-impl<'a> From<&'a Value> for &'a Pointer {
-  #[inline(always)]
-  fn from(value: &'a Value) -> Self {
-    if let Value::Pointer(p) = value {
-      p
-    } else {
-      // This is safe because we type check the program beforehand
-      unsafe { unreachable_unchecked() }
+  // The type of the values of allocation ```base``` and each of its values that has been initialized, unless it has been freed
+  pub(crate) fn allocation(&self, base: usize) -> Option<(&Type, Vec<Option<Value>>)> {
+    let a = self.live(base)?;
+    let range = a.start..a.start + a.len;
+    let values = self.arena[range.clone()]
+      .iter()
+      .zip(&self.initialized[range])
+      .map(|(v, init)| init.then_some(*v))
+      .collect();
+    Some((a.allocated_at.pointee_type(), values))
+  }
+
+  // How many allocations have been made, including those which have been freed, which is the number the next one will get
+  pub(crate) const fn allocations_made(&self) -> usize {
+    self.made
+  }
+
+  // What ```key``` points to, without it being an error if that isn't a value which has been initialized
+  pub(crate) fn peek(&self, key: Pointer) -> Option<Value> {
+    let idx = self.index(key).ok()?;
+    self.initialized[idx].then(|| self.arena[idx])
+  }
+
+  // Every allocation which hasn't been freed along with its values, as they are handed out of an Interpreter
+  fn runtime_allocations(&self) -> Vec<(usize, Vec<Option<RuntimeValue>>)> {
+    self
+      .live_allocations()
+      .map(|(base, ty, values)| {
+        let values = values
+          .into_iter()
+          .map(|v| v.map(|v| RuntimeValue::new(v, ty, &self.strings)))
+          .collect();
+        (base, values)
+      })
+      .collect()
+  }
+
+  // The same heap with every site detached from the program, so that it can outlive it
+  fn detach(self) -> Heap<'static> {
+    Heap {
+      arena: self.arena,
+      initialized: self.initialized,
+      allocations: self
+        .allocations
+        .into_iter()
+        .map(|a| Allocation {
+          start: a.start,
+          len: a.len,
+          generation: a.generation,
+          number: a.number,
+          allocated_at: a.allocated_at.detach(),
+          freed_at: a.freed_at.map(Origin::detach),
+        })
+        .collect(),
+      free_list: self.free_list,
+      free_slots: self.free_slots,
+      made: self.made,
+      num_live: self.num_live,
+      live_values: self.live_values,
+      peak_values: self.peak_values,
+      strings: self.strings,
+      counts: self.counts,
+      collected_values: self.collected_values,
     }
   }
-}
 
-// todo do this with less function arguments
-#[inline(always)]
-fn execute_value_op<'a, T: std::io::Write>(
-  prog: &'a BBProgram,
-  op: &bril_rs::ValueOps,
-  dest: u32,
-  args: &[u32],
-  labels: &[String],
-  funcs: &[String],
-  out: &mut T,
-  value_store: &mut Environment,
-  heap: &mut Heap,
-  last_label: Option<&String>,
-  instruction_count: &mut u32,
-) -> Result<(), InterpError> {
-  use bril_rs::ValueOps::*;
-  match *op {
-    Add => {
-      let arg0 = get_arg::<i64>(value_store, 0, args);
-      let arg1 = get_arg::<i64>(value_store, 1, args);
-      value_store.set(dest, Value::Int(arg0.wrapping_add(arg1)));
+  fn save(&self) -> HeapState {
+    HeapState {
+      arena: self.arena.clone(),
+      initialized: self.initialized.clone(),
+      allocations: self
+        .allocations
+        .iter()
+        .map(|a| AllocationState {
+          start: a.start,
+          len: a.len,
+          generation: a.generation,
+          number: Some(a.number),
+          allocated_at: a.allocated_at.save(),
+          freed_at: a.freed_at.as_ref().map(Origin::save),
+        })
+        .collect(),
+      free_list: self.free_list.clone(),
+      free_slots: self.free_slots.clone(),
+      made: Some(self.made),
+      num_live: self.num_live,
+      strings: self.strings.to_vec(),
+      counts: self.counts.clone(),
     }
-    Mul => {
-      let arg0 = get_arg::<i64>(value_store, 0, args);
-      let arg1 = get_arg::<i64>(value_store, 1, args);
-      value_store.set(dest, Value::Int(arg0.wrapping_mul(arg1)));
+  }
+
+  // The heap saved in ```state``` with its sites pointing back into ```prog```
+  fn restore(state: HeapState, prog: &'a BBProgram) -> Result<Self, InterpError> {
+    let mismatch = |slot: usize, problem: &str| {
+      InterpError::CheckpointMismatch(format!("allocation {slot} {problem}"))
+    };
+    if state.allocations.len() > SLOT_MASK as usize + 1 {
+      return Err(InterpError::CheckpointMismatch(
+        "it has more allocations than pointers can tell apart".to_string(),
+      ));
     }
-    Sub => {
-      let arg0 = get_arg::<i64>(value_store, 0, args);
-      let arg1 = get_arg::<i64>(value_store, 1, args);
-      value_store.set(dest, Value::Int(arg0.wrapping_sub(arg1)));
+    let mut allocations = Vec::with_capacity(state.allocations.len());
+    for a in state.allocations {
+      let base = allocations.len();
+      if a.generation > MAX_GENERATION {
+        return Err(mismatch(base, "has been reused too many times"));
+      }
+      if a
+        .start
+        .checked_add(a.len)
+        .is_none_or(|end| end > state.arena.len() || end > state.initialized.len())
+      {
+        return Err(mismatch(base, "is outside of the heap"));
+      }
+      let allocated_at = Origin::restore(a.allocated_at, prog)?;
+      // Every allocation has to know the type of its values to be shown
+      let typed = match &allocated_at {
+        Origin::Site(site) => site.allocated_type().is_some(),
+        Origin::Detached(detached) => detached.1.is_some(),
+      };
+      if !typed {
+        return Err(mismatch(base, "wasn't made by an alloc"));
+      }
+      allocations.push(Allocation {
+        start: a.start,
+        len: a.len,
+        generation: a.generation,
+        // Checkpoints from before slots were reused numbered allocations by their slot
+        number: a.number.unwrap_or(base),
+        allocated_at,
+        freed_at: a.freed_at.map(|o| Origin::restore(o, prog)).transpose()?,
+      });
     }
-    Div => {
-      let arg0 = get_arg::<i64>(value_store, 0, args);
-      let arg1 = get_arg::<i64>(value_store, 1, args);
-      value_store.set(dest, Value::Int(arg0.wrapping_div(arg1)));
+    if state.free_slots.iter().any(|&s| {
+      allocations
+        .get(s as usize)
+        .is_none_or(|a| a.freed_at.is_none())
+    }) {
+      return Err(InterpError::CheckpointMismatch(
+        "it would reuse the slot of an allocation which hasn't been freed".to_string(),
+      ));
     }
-    Eq => {
-      let arg0 = get_arg::<i64>(value_store, 0, args);
-      let arg1 = get_arg::<i64>(value_store, 1, args);
-      value_store.set(dest, Value::Bool(arg0 == arg1));
+    if state
+      .counts
+      .as_ref()
+      .is_some_and(|counts| counts.len() != allocations.len())
+    {
+      return Err(InterpError::CheckpointMismatch(
+        "the pointers to its allocations weren't all counted".to_string(),
+      ));
     }
-    Lt => {
-      let arg0 = get_arg::<i64>(value_store, 0, args);
-      let arg1 = get_arg::<i64>(value_store, 1, args);
-      value_store.set(dest, Value::Bool(arg0 < arg1));
+    // Checkpoints don't keep the peak, so it starts again from what is allocated now
+    let live_values = allocations
+      .iter()
+      .filter(|a| a.freed_at.is_none())
+      .map(|a| a.len)
+      .sum();
+    Ok(Self {
+      initialized: state.initialized,
+      arena: state.arena,
+      made: state.made.unwrap_or(allocations.len()),
+      allocations,
+      free_list: state.free_list,
+      free_slots: state.free_slots,
+      num_live: state.num_live,
+      live_values,
+      peak_values: live_values,
+      strings: Strings::from_vec(state.strings),
+      counts: state.counts,
+      collected_values: 0,
+    })
+  }
+
+  // Every allocation which hasn't been freed, in the order they were made
+  fn leaks(&self) -> Vec<Leak> {
+    self
+      .live_bases()
+      .into_iter()
+      .map(|base| {
+        let a = &self.allocations[slot(base as u32)];
+        Leak {
+          base,
+          size: a.len,
+          ty: a.allocated_at.pointee_type().clone(),
+          allocated_at: a.allocated_at.to_string(),
+        }
+      })
+      .collect()
+  }
+
+  // The error for the allocations which are still live at the end of the program
+  fn leak_error(&self) -> InterpError {
+    let first = self
+      .allocations
+      .iter()
+      .filter(|a| a.freed_at.is_none())
+      .min_by_key(|a| a.number);
+    InterpError::MemLeak(
+      self.num_live,
+      first.map_or_else(String::new, |a| a.allocated_at.to_string()),
+    )
+  }
+
+  #[inline(always)]
+  fn alloc(&mut self, amount: i64, site: Site<'a>) -> Result<Value, InterpError> {
+    self.alloc_from(amount, Origin::Site(site))
+  }
+
+  #[inline(always)]
+  fn alloc_from(&mut self, amount: i64, allocated_at: Origin<'a>) -> Result<Value, InterpError> {
+    // Offsets into an allocation have to fit in a pointer
+    if amount < 0 || amount > i64::from(i32::MAX) {
+      return Err(InterpError::CannotAllocSize(amount));
     }
-    Gt => {
-      let arg0 = get_arg::<i64>(value_store, 0, args);
-      let arg1 = get_arg::<i64>(value_store, 1, args);
-      value_store.set(dest, Value::Bool(arg0 > arg1));
+    let len = amount as usize;
+    let (slot, generation) = match self.free_slots.pop_front() {
+      Some(slot) => (
+        slot as usize,
+        self.allocations[slot as usize].generation + 1,
+      ),
+      None if self.allocations.len() > SLOT_MASK as usize => {
+        return Err(InterpError::TooManyAllocations(SLOT_MASK as usize + 1))
+      }
+      None => (self.allocations.len(), 0),
+    };
+    let start = match self.free_list.get_mut(&len).and_then(Vec::pop) {
+      Some(start) => {
+        self.initialized[start..start + len].fill(false);
+        start
+      }
+      None => {
+        let start = self.arena.len();
+        self.arena.resize(start + len, Value::default());
+        self.initialized.resize(start + len, false);
+        start
+      }
+    };
+    let allocation = Allocation {
+      start,
+      len,
+      generation,
+      number: self.made,
+      allocated_at,
+      freed_at: None,
+    };
+    if slot == self.allocations.len() {
+      self.allocations.push(allocation);
+      if let Some(counts) = self.counts.as_mut() {
+        counts.push(0);
+      }
+    } else {
+      self.allocations[slot] = allocation;
+      if let Some(counts) = self.counts.as_mut() {
+        counts[slot] = 0;
+      }
     }
-    Le => {
-      let arg0 = get_arg::<i64>(value_store, 0, args);
-      let arg1 = get_arg::<i64>(value_store, 1, args);
-      value_store.set(dest, Value::Bool(arg0 <= arg1));
+    self.made += 1;
+    self.num_live += 1;
+    self.live_values += len;
+    self.peak_values = self.peak_values.max(self.live_values);
+    Ok(Value::pointer(Pointer {
+      base: base(slot, generation),
+      offset: 0,
+    }))
+  }
+
+  // Allocates the values of ```json```, which is given to main for an argument pointing to ```ty```, as though the program had allocated and stored them itself. Arrays inside of it are allocated on their own with pointers to them stored in the outer allocation, and every allocation is said to be made by ```origin```
+  fn alloc_argument(
+    &mut self,
+    json: &serde_json::Value,
+    ty: &Type,
+    origin: &str,
+  ) -> Result<Value, InterpError> {
+    let values = match (json, ty) {
+      (serde_json::Value::String(s), Type::Char) => s.chars().map(Value::char).collect(),
+      (serde_json::Value::Array(elements), _) => elements
+        .iter()
+        .map(|element| match ty {
+          Type::Pointer(inner) => {
+            let pointer = self.alloc_argument(element, inner, origin)?;
+            self.retain(pointer);
+            Ok(pointer)
+          }
+          _ => scalar_argument(element, ty, &mut self.strings),
+        })
+        .collect::<Result<Vec<_>, _>>()?,
+      _ => {
+        return Err(InterpError::BadFuncArgType(
+          Type::Pointer(Box::new(ty.clone())),
+          json.to_string(),
+        ))
+      }
+    };
+    let pointer = self.alloc_from(
+      values.len() as i64,
+      Origin::Detached(Box::new((origin.to_string(), Some(ty.clone())))),
+    )?;
+    for (offset, value) in values.into_iter().enumerate() {
+      self.write(
+        Pointer {
+          offset: offset as i32,
+          ..pointer.as_pointer()
+        },
+        value,
+      )?;
     }
-    Ge => {
-      let arg0 = get_arg::<i64>(value_store, 0, args);
-      let arg1 = get_arg::<i64>(value_store, 1, args);
-      value_store.set(dest, Value::Bool(arg0 >= arg1));
-    }
-    Not => {
-      let arg0 = get_arg::<bool>(value_store, 0, args);
-      value_store.set(dest, Value::Bool(!arg0));
-    }
-    And => {
-      let arg0 = get_arg::<bool>(value_store, 0, args);
-      let arg1 = get_arg::<bool>(value_store, 1, args);
-      value_store.set(dest, Value::Bool(arg0 && arg1));
-    }
-    Or => {
-      let arg0 = get_arg::<bool>(value_store, 0, args);
-      let arg1 = get_arg::<bool>(value_store, 1, args);
-      value_store.set(dest, Value::Bool(arg0 || arg1));
-    }
-    Id => {
-      let src = get_value(value_store, 0, args).clone();
-      value_store.set(dest, src);
-    }
-    Fadd => {
-      let arg0 = get_arg::<f64>(value_store, 0, args);
-      let arg1 = get_arg::<f64>(value_store, 1, args);
-      value_store.set(dest, Value::Float(arg0 + arg1));
-    }
-    Fmul => {
-      let arg0 = get_arg::<f64>(value_store, 0, args);
-      let arg1 = get_arg::<f64>(value_store, 1, args);
-      value_store.set(dest, Value::Float(arg0 * arg1));
-    }
-    Fsub => {
-      let arg0 = get_arg::<f64>(value_store, 0, args);
-      let arg1 = get_arg::<f64>(value_store, 1, args);
-      value_store.set(dest, Value::Float(arg0 - arg1));
-    }
-    Fdiv => {
-      let arg0 = get_arg::<f64>(value_store, 0, args);
-      let arg1 = get_arg::<f64>(value_store, 1, args);
-      value_store.set(dest, Value::Float(arg0 / arg1));
-    }
-    Feq => {
-      let arg0 = get_arg::<f64>(value_store, 0, args);
-      let arg1 = get_arg::<f64>(value_store, 1, args);
-      value_store.set(dest, Value::Bool(arg0 == arg1));
-    }
-    Flt => {
-      let arg0 = get_arg::<f64>(value_store, 0, args);
-      let arg1 = get_arg::<f64>(value_store, 1, args);
-      value_store.set(dest, Value::Bool(arg0 < arg1));
-    }
-    Fgt => {
-      let arg0 = get_arg::<f64>(value_store, 0, args);
-      let arg1 = get_arg::<f64>(value_store, 1, args);
-      value_store.set(dest, Value::Bool(arg0 > arg1));
-    }
-    Fle => {
-      let arg0 = get_arg::<f64>(value_store, 0, args);
-      let arg1 = get_arg::<f64>(value_store, 1, args);
-      value_store.set(dest, Value::Bool(arg0 <= arg1));
-    }
-    Fge => {
-      let arg0 = get_arg::<f64>(value_store, 0, args);
-      let arg1 = get_arg::<f64>(value_store, 1, args);
-      value_store.set(dest, Value::Bool(arg0 >= arg1));
-    }
-    Call => {
-      let callee_func = prog
-        .get(&funcs[0])
-        .ok_or_else(|| InterpError::FuncNotFound(funcs[0].clone()))?;
-
-      let next_env = make_func_args(callee_func, args, value_store);
+    Ok(pointer)
+  }
 
-      value_store.set(
-        dest,
-        execute(prog, callee_func, out, next_env, heap, instruction_count)?.unwrap(),
-      )
+  #[inline(always)]
+  fn free(&mut self, key: Pointer, site: Site<'a>) -> Result<(), InterpError> {
+    // Every pointer comes from an allocation so its slot is always in bounds
+    let a = &mut self.allocations[slot(key.base)];
+    if a.generation != key.base >> SLOT_BITS {
+      return Err(InterpError::DoubleFreeAfterReuse(
+        a.allocated_at.to_string(),
+      ));
     }
-    Phi => {
-      if last_label.is_none() {
-        return Err(InterpError::NoLastLabel);
-      } else {
-        let arg = labels
-          .iter()
-          .position(|l| l == last_label.unwrap())
-          .ok_or_else(|| InterpError::PhiMissingLabel(last_label.unwrap().to_string()))
-          .map(|i| value_store.get(args.get(i).unwrap()))?
-          .clone();
-        value_store.set(dest, arg);
-      }
+    if let Some(freed_at) = &a.freed_at {
+      return Err(InterpError::DoubleFree(
+        a.allocated_at.to_string(),
+        freed_at.to_string(),
+      ));
     }
-    Alloc => {
-      let arg0 = get_arg::<i64>(value_store, 0, args);
-      let res = heap.alloc(arg0)?;
-      value_store.set(dest, res)
+    a.freed_at = Some(Origin::Site(site));
+    self.free_list.entry(a.len).or_default().push(a.start);
+    if a.generation < MAX_GENERATION {
+      self.free_slots.push_back(key.base & SLOT_MASK);
     }
-    Load => {
-      let arg0 = get_arg::<&Pointer>(value_store, 0, args);
-      let res = heap.read(arg0)?;
-      value_store.set(dest, res.clone())
+    self.num_live -= 1;
+    self.live_values -= a.len;
+    // What the allocation pointed to isn't pointed to from here any more
+    if self.counts.is_some() && matches!(a.allocated_at.pointee_type(), Type::Pointer(_)) {
+      let range = a.start..a.start + a.len;
+      let contents: Vec<Value> = self.arena[range.clone()]
+        .iter()
+        .zip(&self.initialized[range])
+        .filter(|(_, init)| **init)
+        .map(|(v, _)| *v)
+        .collect();
+      for value in contents {
+        self.release(value);
+      }
     }
-    PtrAdd => {
-      let arg0 = get_arg::<&Pointer>(value_store, 0, args);
-      let arg1 = get_arg::<i64>(value_store, 1, args);
-      let res = Value::Pointer(arg0.add(arg1));
-      value_store.set(dest, res)
+    let a = &self.allocations[slot(key.base)];
+    if key.offset == 0 {
+      Ok(())
+    } else {
+      Err(InterpError::IllegalFree(
+        i64::from(key.offset),
+        a.allocated_at.to_string(),
+      ))
     }
   }
-  Ok(())
-}
 
-// Returns a map from function parameter names to values of the call arguments
-// that are bound to those parameters.
-fn make_func_args<'a>(
-  callee_func: &'a BBFunction,
-  args: &[u32],
-  vars: &Environment,
-) -> Environment {
-  // todo: Having to allocate a new environment on each function call probably makes small function calls very heavy weight. This could be interesting to profile and see if old environments can be reused instead of being deallocated and reallocated. Maybe there is another way to sometimes avoid this allocation.
-  let mut next_env = Environment::new(callee_func.num_of_vars);
+  // Whether the pointers to each allocation are being counted for Options::rc_heap
+  #[inline(always)]
+  const fn counting(&self) -> bool {
+    self.counts.is_some()
+  }
 
-  args
-    .iter()
-    .zip(callee_func.args_as_nums.iter())
-    .for_each(|(arg_name, expected_arg)| {
-      let arg = vars.get(arg_name);
-      next_env.set(*expected_arg, arg.clone());
-    });
+  // Starts or stops counting the pointers to each allocation for Options::rc_heap. The pointers to the allocations which are already on the heap weren't counted, so those are never collected
+  fn count_pointers(&mut self, rc_heap: bool) {
+    match (rc_heap, &self.counts) {
+      (true, None) => self.counts = Some(vec![PINNED; self.allocations.len()]),
+      (false, Some(_)) => self.counts = None,
+      _ => {}
+    }
+  }
 
-  next_env
-}
+  // The count of the pointers to the allocation that a pointer with ```base``` points into, unless that allocation's slot has been reused since
+  #[inline(always)]
+  fn count(&mut self, base: u32) -> Option<&mut u32> {
+    let counts = self.counts.as_mut()?;
+    (self.allocations[slot(base)].generation == base >> SLOT_BITS).then(|| &mut counts[slot(base)])
+  }
 
-// todo do this with less function arguments
-#[inline(always)]
-fn execute_effect_op<'a, T: std::io::Write>(
-  prog: &'a BBProgram,
-  func: &BBFunction,
-  op: &bril_rs::EffectOps,
-  args: &[u32],
-  funcs: &[String],
-  curr_block: &BasicBlock,
-  out: &mut T,
-  value_store: &Environment,
-  heap: &mut Heap,
-  next_block_idx: &mut Option<usize>,
-  instruction_count: &mut u32,
-) -> Result<Option<Value>, InterpError> {
-  use bril_rs::EffectOps::*;
-  match op {
-    Jump => {
-      *next_block_idx = Some(curr_block.exit[0]);
+  // Counts one more pointer to the allocation that the pointer ```value``` points into
+  #[inline(always)]
+  fn retain(&mut self, value: Value) {
+    if let Some(count) = self.count(value.as_pointer().base) {
+      *count = count.saturating_add(1);
     }
-    Branch => {
-      let bool_arg0 = get_arg::<bool>(value_store, 0, args);
-      let exit_idx = if bool_arg0 { 0 } else { 1 };
-      *next_block_idx = Some(curr_block.exit[exit_idx]);
+  }
+
+  // Keeps the allocation that the pointer ```value``` points into from ever being collected, since the pointer is going somewhere that its copies can't be counted
+  fn pin(&mut self, value: Value) {
+    if let Some(count) = self.count(value.as_pointer().base) {
+      *count = PINNED;
     }
-    Return => match &func.return_type {
-      Some(_) => {
-        let arg0 = get_value(value_store, 0, args);
-        return Ok(Some(arg0.clone()));
+  }
+
+  // Counts one less pointer to the allocation that the pointer ```value``` points into, freeing it once there are none left along with whatever only it pointed to. Returns whether anything was freed
+  #[inline(always)]
+  fn release(&mut self, value: Value) -> bool {
+    let mut collected = false;
+    let mut pending = vec![value.as_pointer().base];
+    while let Some(base) = pending.pop() {
+      let Some(count) = self.count(base) else {
+        continue;
+      };
+      if *count != PINNED {
+        *count = count.saturating_sub(1);
+      }
+      let dead = *count == 0;
+      let a = &mut self.allocations[slot(base)];
+      if dead && a.freed_at.is_none() {
+        a.freed_at = Some(Origin::Detached(Box::new((
+          "the point where nothing pointed to it any more".to_string(),
+          None,
+        ))));
+        self.free_list.entry(a.len).or_default().push(a.start);
+        if a.generation < MAX_GENERATION {
+          self.free_slots.push_back(base & SLOT_MASK);
+        }
+        self.num_live -= 1;
+        self.live_values -= a.len;
+        self.collected_values += a.len;
+        collected = true;
+        if matches!(a.allocated_at.pointee_type(), Type::Pointer(_)) {
+          let range = a.start..a.start + a.len;
+          pending.extend(
+            self.arena[range.clone()]
+              .iter()
+              .zip(&self.initialized[range])
+              .filter(|(_, init)| **init)
+              .map(|(v, _)| v.as_pointer().base),
+          );
+        }
       }
-      None => return Ok(None),
-    },
-    Print => {
-      writeln!(
-        out,
-        "{}",
-        args
-          .iter()
-          .map(|a| value_store.get(a).to_string())
-          .collect::<Vec<String>>()
-          .join(" ")
-      )
-      .map_err(|e| InterpError::IoError(Box::new(e)))?;
-      out.flush().map_err(|e| InterpError::IoError(Box::new(e)))?;
     }
-    Nop => {}
-    Call => {
-      let callee_func = prog
-        .get(&funcs[0])
-        .ok_or_else(|| InterpError::FuncNotFound(funcs[0].clone()))?;
-
-      let next_env = make_func_args(callee_func, args, value_store);
+    collected
+  }
 
-      execute(prog, callee_func, out, next_env, heap, instruction_count)?;
-    }
-    Store => {
-      let arg0 = get_arg::<&Pointer>(value_store, 0, args);
-      let arg1 = get_value(value_store, 1, args);
-      heap.write(arg0, arg1.clone())?
+  // The allocation that ```key``` points into, which has to be live to be used
+  #[inline(always)]
+  fn live_allocation(&self, key: Pointer) -> Result<&Allocation<'a>, InterpError> {
+    let a = &self.allocations[slot(key.base)];
+    if a.generation != key.base >> SLOT_BITS {
+      return Err(InterpError::UseAfterReuse(a.allocated_at.to_string()));
     }
-    Free => {
-      let arg0 = get_arg::<&Pointer>(value_store, 0, args);
-      heap.free(arg0)?
+    if let Some(freed_at) = &a.freed_at {
+      return Err(InterpError::UseAfterFree(
+        a.allocated_at.to_string(),
+        freed_at.to_string(),
+      ));
     }
-    Speculate | Commit | Guard => unimplemented!(),
+    Ok(a)
   }
-  Ok(None)
-}
 
-fn execute<'a, T: std::io::Write>(
-  prog: &'a BBProgram,
-  func: &'a BBFunction,
-  out: &mut T,
-  mut value_store: Environment,
-  heap: &mut Heap,
-  instruction_count: &mut u32,
-) -> Result<Option<Value>, PositionalInterpError> {
-  // Map from variable name to value.
-  let mut last_label;
-  let mut current_label = None;
-  let mut curr_block_idx = 0;
-  let mut result = None;
-
-  loop {
-    let curr_block = &func.blocks[curr_block_idx];
-    let curr_instrs = &curr_block.instrs;
-    let curr_numified_instrs = &curr_block.numified_instrs;
-    // WARNING!!! We can add the # of instructions at once because you can only jump to a new block at the end. This may need to be changed if speculation is implemented
-    *instruction_count += curr_instrs.len() as u32;
-    last_label = current_label;
-    current_label = curr_block.label.as_ref();
-
-    let mut next_block_idx = if curr_block.exit.len() == 1 {
-      Some(curr_block.exit[0])
+  // The index into the arena that ```key``` points to
+  #[inline(always)]
+  fn index(&self, key: Pointer) -> Result<usize, InterpError> {
+    let a = self.live_allocation(key)?;
+    if key.offset >= 0 && (key.offset as usize) < a.len {
+      Ok(a.start + key.offset as usize)
     } else {
-      None
-    };
-
-    for (code, numified_code) in curr_instrs.iter().zip(curr_numified_instrs.iter()) {
-      match code {
-        Instruction::Constant {
-          op: bril_rs::ConstOps::Const,
-          dest: _,
-          const_type,
-          value,
-          pos: _,
-        } => {
-          // Integer literals can be promoted to Floating point
-          if const_type == &bril_rs::Type::Float {
-            match value {
-              bril_rs::Literal::Int(i) => {
-                value_store.set(numified_code.dest.unwrap(), Value::Float(*i as f64))
-              }
-              bril_rs::Literal::Float(f) => {
-                value_store.set(numified_code.dest.unwrap(), Value::Float(*f))
-              }
-              // this is safe because we type check this beforehand
-              bril_rs::Literal::Bool(_) => unsafe { unreachable_unchecked() },
-            }
-          } else {
-            value_store.set(numified_code.dest.unwrap(), Value::from(value));
-          };
-        }
-        Instruction::Value {
-          op,
-          dest: _,
-          op_type: _,
-          args: _,
-          labels,
-          funcs,
-          pos,
-        } => {
-          execute_value_op(
-            prog,
-            op,
-            numified_code.dest.unwrap(),
-            &numified_code.args,
-            labels,
-            funcs,
-            out,
-            &mut value_store,
-            heap,
-            last_label,
-            instruction_count,
-          )
-          .map_err(|e| e.add_pos(*pos))?;
-        }
-        Instruction::Effect {
-          op,
-          args: _,
-          labels: _,
-          funcs,
-          pos,
-        } => {
-          result = execute_effect_op(
-            prog,
-            func,
-            op,
-            &numified_code.args,
-            funcs,
-            curr_block,
-            out,
-            &value_store,
-            heap,
-            &mut next_block_idx,
-            instruction_count,
-          )
-          .map_err(|e| e.add_pos(*pos))?;
-        }
-      }
+      Err(InterpError::OutOfBounds(
+        i64::from(key.offset),
+        a.len,
+        a.allocated_at.to_string(),
+      ))
     }
-    if let Some(idx) = next_block_idx {
-      curr_block_idx = idx;
+  }
+
+  #[inline(always)]
+  fn write(&mut self, key: Pointer, val: Value) -> Result<(), InterpError> {
+    let idx = self.index(key)?;
+    self.arena[idx] = val;
+    self.initialized[idx] = true;
+    Ok(())
+  }
+
+  #[inline(always)]
+  fn read(&self, key: Pointer) -> Result<Value, InterpError> {
+    let idx = self.index(key)?;
+    if self.initialized[idx] {
+      Ok(self.arena[idx])
     } else {
-      return Ok(result);
+      Err(InterpError::UsingUninitializedMemory)
     }
   }
-}
 
-fn parse_args(
-  mut env: Environment,
-  args: &[bril_rs::Argument],
-  args_as_nums: &[u32],
-  inputs: &[String],
+  // The error for loading from ```key```, which hasn't been stored to, saying where in which allocation it is
+  #[cold]
+  fn uninitialized_error(&self, key: Pointer) -> InterpError {
+    let a = &self.allocations[slot(key.base)];
+    InterpError::UninitializedLoad(i64::from(key.offset), a.len, a.allocated_at.to_string())
+  }
+
+  // The error for ```race```, which was on a value of allocation ```base```
+  #[cold]
+  fn race_error(&self, base: u32, race: &Race<Site<'a>>) -> InterpError {
+    InterpError::DataRace(
+      race.offset,
+      self.allocations[slot(base)].allocated_at.to_string(),
+      race.earlier.to_string(),
+      race.later.to_string(),
+    )
+  }
+
+  // ```key``` moved along by ```offset``` like ```offset```, as long as it still points into a live allocation or at the end of it
+  #[inline(always)]
+  fn offset_within(&self, key: Pointer, offset: i64) -> Result<Pointer, InterpError> {
+    let a = self.live_allocation(key)?;
+    let moved = i64::from(key.offset).wrapping_add(offset);
+    if moved < 0 || moved > a.len as i64 {
+      return Err(InterpError::PointerEscape(
+        moved,
+        a.len,
+        a.allocated_at.to_string(),
+      ));
+    }
+    self.offset(key, offset)
+  }
+
+  // ```key``` moved along by ```offset```. Pointers only have room for offsets that could be in bounds of some allocation, so moving any further than that is reported as out of bounds straight away instead of when the pointer is used
+  #[inline(always)]
+  fn offset(&self, key: Pointer, offset: i64) -> Result<Pointer, InterpError> {
+    let moved = i64::from(key.offset).wrapping_add(offset);
+    i32::try_from(moved).map_or_else(
+      |_| {
+        let a = &self.allocations[slot(key.base)];
+        Err(InterpError::OutOfBounds(
+          moved,
+          a.len,
+          a.allocated_at.to_string(),
+        ))
+      },
+      |offset| {
+        Ok(Pointer {
+          base: key.base,
+          offset,
+        })
+      },
+    )
+  }
+}
+
+// The parts of the interpreter which live for the whole run of the program instead of a single function call
+struct State<'a, T: Sink> {
+  prog: &'a BBProgram,
+  out: T,
+  heap: Heap<'a>,
+  instruction_count: u32,
+  debugger: Option<Debugger>,
+  tracer: Option<Tracer>,
+  instruction_trace: Option<InstructionTrace>,
+  profiler: Option<Profiler>,
+  memory_profiler: Option<MemoryProfiler<'a>>,
+  coverage: Option<CoverageRecorder>,
+  gas: Option<Gas>,
+  hook: Option<Box<dyn InterpHook>>,
+  overflow: Overflow,
+  div_semantics: DivSemantics,
+  no_speculation: bool,
+  tco: bool,
+  sanitize: bool,
+  // Made by the first spawn with Options::detect_races
+  races: Option<RaceDetector<Site<'a>>>,
+  detect_races: bool,
+  // Where the numbers `rand` gives are up to
+  rng: Rng,
+}
+
+impl<T: Sink> State<'_, T> {
+  // Whether anything looks at each instruction as it runs. When nothing does, an instruction runs with nothing more than a dispatch on its Op
+  #[inline(always)]
+  const fn instrumented(&self) -> bool {
+    self.debugger.is_some()
+      || self.gas.is_some()
+      || self.coverage.is_some()
+      || self.sanitize
+      || self.hook.is_some()
+      || self.instruction_trace.is_some()
+  }
+
+  // Whether a run needs nothing but the program itself, with nothing looking at the instructions, blocks or calls it runs and no pointers counted for Options::rc_heap. A plain run gets an interpreter loop without any of that
+  #[inline(always)]
+  const fn plain(&self) -> bool {
+    !self.instrumented()
+      && !self.heap.counting()
+      && self.tracer.is_none()
+      && self.profiler.is_none()
+      && self.memory_profiler.is_none()
+  }
+
+  // Counts the pointer ```new``` in place of ```old```, which it has overwritten, for Options::rc_heap
+  #[inline(always)]
+  fn reassign(&mut self, new: Option<Value>, old: Option<Value>) {
+    if let Some(new) = new {
+      self.heap.retain(new);
+    }
+    if let Some(old) = old {
+      self.release(old);
+    }
+  }
+
+  // Counts one less pointer to where ```value``` points, which may free memory
+  #[inline(always)]
+  fn release(&mut self, value: Value) {
+    if self.heap.release(value) {
+      if let Some(profiler) = self.memory_profiler.as_mut() {
+        profiler.free(self.heap.live_values * 8);
+      }
+    }
+  }
+
+  // Counts the pointers in the variables of ```env```, which belongs to a call to ```func```, for Options::rc_heap
+  fn retain_vars(&mut self, func: &BBFunction, env: &Environment) {
+    if self.heap.counting() {
+      for value in pointer_vars(func, env) {
+        self.heap.retain(value);
+      }
+    }
+  }
+
+  // Stops counting the pointers in the variables of ```env```, whose call to ```func``` is over, for Options::rc_heap
+  fn release_vars(&mut self, func: &BBFunction, env: &Environment) {
+    if self.heap.counting() {
+      for value in pointer_vars(func, env) {
+        self.release(value);
+      }
+    }
+  }
+}
+
+// Whether ```var``` of ```func``` holds pointers, which are counted for Options::rc_heap
+#[inline(always)]
+fn holds_pointer(func: &BBFunction, var: u32) -> bool {
+  matches!(func.var_types[var as usize], Some(Type::Pointer(_)))
+}
+
+// The values of the pointer variables of ```func``` which have been defined in ```env```
+fn pointer_vars<'e>(
+  func: &'e BBFunction,
+  env: &'e Environment,
+) -> impl Iterator<Item = Value> + 'e {
+  (0..func.num_of_vars)
+    .filter(|var| holds_pointer(func, *var))
+    .filter_map(|var| env.lookup(var as usize))
+}
+
+#[inline(always)]
+fn get_value(vars: &Environment, index: usize, args: &[u32]) -> Value {
+  vars.get(&args[index])
+}
+
+#[inline(always)]
+fn get_arg<T>(vars: &Environment, index: usize, args: &[u32]) -> T
+where
+  T: for<'a> From<&'a Value>,
+{
+  T::from(&vars.get(&args[index]))
+}
+
+// Computes one of the arithmetic ```op```s on ints while handling overflow as ```overflow``` says to, and rounding division as ```div_semantics``` says to
+#[inline(always)]
+fn int_arith(
+  overflow: Overflow,
+  div_semantics: DivSemantics,
+  op: bril_rs::ValueOps,
+  arg0: i64,
+  arg1: i64,
+) -> Result<i64, InterpError> {
+  use bril_rs::ValueOps::{Add, Div, Mul, Rem, Sub};
+  type Arith<T> = fn(i64, i64) -> T;
+  if matches!(op, Div | Rem) && arg1 == 0 {
+    return Err(InterpError::DivisionByZero);
+  }
+  let (wrapping, checked, saturating): (Arith<i64>, Arith<Option<i64>>, Arith<i64>) = match op {
+    Add => (i64::wrapping_add, i64::checked_add, i64::saturating_add),
+    Sub => (i64::wrapping_sub, i64::checked_sub, i64::saturating_sub),
+    Mul => (i64::wrapping_mul, i64::checked_mul, i64::saturating_mul),
+    Div => match div_semantics {
+      DivSemantics::Trunc => (i64::wrapping_div, i64::checked_div, i64::saturating_div),
+      // The only quotient which doesn't fit is that of the smallest int by -1, which saturates to the largest
+      DivSemantics::Euclid => (i64::wrapping_div_euclid, i64::checked_div_euclid, |a, b| {
+        a.checked_div_euclid(b).unwrap_or(i64::MAX)
+      }),
+    },
+    // The remainder always fits, even for the smallest int by -1 where the quotient doesn't, so it never overflows
+    Rem => match div_semantics {
+      DivSemantics::Trunc => (
+        i64::wrapping_rem,
+        |a, b| Some(a.wrapping_rem(b)),
+        i64::wrapping_rem,
+      ),
+      DivSemantics::Euclid => (
+        i64::wrapping_rem_euclid,
+        |a, b| Some(a.wrapping_rem_euclid(b)),
+        i64::wrapping_rem_euclid,
+      ),
+    },
+    // This is safe because int_arith is only called for the above operations
+    _ => unsafe { unreachable_unchecked() },
+  };
+  match overflow {
+    Overflow::Wrap => Ok(wrapping(arg0, arg1)),
+    Overflow::Trap => checked(arg0, arg1).ok_or(InterpError::Overflow(op)),
+    Overflow::Saturate => Ok(saturating(arg0, arg1)),
+  }
+}
+
+#[inline(always)]
+fn execute_value_op<'a, T: Sink>(
+  state: &mut State<'a, T>,
+  op: &bril_rs::ValueOps,
+  dest: u32,
+  args: &[u32],
+  labels: &[u32],
+  value_store: &mut Environment,
+  last_label: Option<u32>,
+  site: Site<'a>,
+) -> Result<(), InterpError> {
+  use bril_rs::ValueOps::*;
+  match *op {
+    // This is safe because calls have an Op of their own and ```execute_other``` handles spawns itself
+    Call | Spawn => unsafe { unreachable_unchecked() },
+    Phi => {
+      let Some(last_label) = last_label else {
+        return Err(InterpError::NoLastLabel);
+      };
+      let arg = labels
+        .iter()
+        .position(|l| *l == last_label)
+        .ok_or_else(|| InterpError::PhiMissingLabel(site.func.labels.name(last_label).to_string()))
+        .map(|i| args[i])?;
+      // Taking a variable which hasn't been assigned along the path that was taken, like `__undefined`, leaves the destination undefined as well
+      match value_store.lookup(arg as usize) {
+        Some(value) => value_store.set(dest, value),
+        None => value_store.unset(dest),
+      }
+    }
+    Alloc => {
+      let arg0 = get_arg::<i64>(value_store, 0, args);
+      let res = state.heap.alloc(arg0, site)?;
+      if let Some(profiler) = state.memory_profiler.as_mut() {
+        profiler.alloc(
+          (site.func, site.block, site.instr),
+          arg0 as usize * 8,
+          state.heap.live_values * 8,
+          || site.to_string(),
+        );
+      }
+      value_store.set(dest, res)
+    }
+    Concat => {
+      let strings = &mut state.heap.strings;
+      let arg0 = strings.get(get_value(value_store, 0, args));
+      let arg1 = strings.get(get_value(value_store, 1, args));
+      let s = strings.intern(&[arg0, arg1].concat());
+      value_store.set(dest, s);
+    }
+    Length => {
+      let arg0 = state.heap.strings.get(get_value(value_store, 0, args));
+      // Strings are measured in characters, like `index` counts them
+      value_store.set(dest, Value::int(arg0.chars().count() as i64));
+    }
+    Index => {
+      let arg0 = state.heap.strings.get(get_value(value_store, 0, args));
+      let arg1 = get_arg::<i64>(value_store, 1, args);
+      let c = usize::try_from(arg1)
+        .ok()
+        .and_then(|i| arg0.chars().nth(i))
+        .ok_or_else(|| InterpError::StringOutOfBounds(arg1, arg0.chars().count()))?;
+      value_store.set(dest, Value::char(c));
+    }
+    // The type checker only lets a `rand` without a range be a float
+    Rand if args.is_empty() => value_store.set(dest, Value::float(state.rng.float())),
+    Rand => {
+      let lo = get_arg::<i64>(value_store, 0, args);
+      let hi = get_arg::<i64>(value_store, 1, args);
+      if hi <= lo {
+        return Err(InterpError::EmptyRandRange(lo, hi));
+      }
+      value_store.set(dest, Value::int(state.rng.int(lo, hi)));
+    }
+    // This is safe because the type checker makes sure that these have as many arguments as they take, so they always have an Op of their own
+    Add | Mul | Sub | Div | Rem | Eq | Lt | Gt | Le | Ge | Not | And | Or | Id | Fadd | Fmul
+    | Fsub | Fdiv | Feq | Flt | Fgt | Fle | Fge | Float2bits | Bits2float | Load | PtrAdd | Ceq
+    | Clt | Cgt | Cle | Cge | Char2int | Int2char => unsafe { unreachable_unchecked() },
+  }
+  Ok(())
+}
+
+// Fills ```next_env``` with the values of the call arguments bound to the parameters of ```callee_func```. ```next_env``` is either empty or left over from a call which has returned, so that calls don't have to allocate a new environment every time.
+fn make_func_args(
+  callee_func: &BBFunction,
+  args: &[u32],
+  vars: &Environment,
+  next_env: &mut Environment,
+  heap: &mut Heap,
+) {
+  next_env.reset(callee_func.num_of_vars);
+
+  args
+    .iter()
+    .zip(callee_func.args_as_nums.iter())
+    .for_each(|(arg_name, expected_arg)| {
+      let arg = vars.get(arg_name);
+      next_env.set(*expected_arg, arg);
+      if heap.counting() && holds_pointer(callee_func, *expected_arg) {
+        heap.retain(arg);
+      }
+    });
+}
+
+// Runs `print` and `free`, the only effect operations which neither have an Op of their own nor are handled by ```execute_other``` itself
+#[inline(always)]
+fn execute_effect_op<'a, T: Sink>(
+  state: &mut State<'a, T>,
+  func: &BBFunction,
+  op: &bril_rs::EffectOps,
+  args: &[u32],
+  value_store: &Environment,
+  site: Site<'a>,
+) -> Result<(), InterpError> {
+  use bril_rs::EffectOps::*;
+  match op {
+    Print => {
+      let line = Printed::new(args, &func.var_types, value_store, &state.heap.strings);
+      state
+        .out
+        .print_line(&line)
+        .map_err(|e| InterpError::IoError(Box::new(e)))?;
+    }
+    Free => {
+      let arg0 = get_arg::<Pointer>(value_store, 0, args);
+      state.heap.free(arg0, site)?;
+      if let Some(races) = state.races.as_mut() {
+        races
+          .free(arg0.base, site)
+          .map_err(|race| state.heap.race_error(arg0.base, &race))?;
+      }
+      if let Some(profiler) = state.memory_profiler.as_mut() {
+        profiler.free(state.heap.live_values * 8);
+      }
+    }
+    // This is safe because these all have an Op of their own or are handled by ```execute_other``` itself, since the type checker makes sure that they have as many arguments as they take
+    Jump | Branch | Return | Call | Speculate | Commit | Guard | Join | Nop | Store => unsafe {
+      unreachable_unchecked()
+    },
+  }
+  Ok(())
+}
+
+// A call to a function which hasn't returned yet
+struct Frame<'a> {
+  func: &'a BBFunction,
+  env: Environment,
+  at: Cursor,
+  // Where the caller stores the value this call returns, if anywhere
+  ret_dest: Option<u32>,
+  // The environment from when each speculation which hasn't been committed started, to go back to if a guard fails. The innermost speculation is last
+  speculation: Vec<Environment>,
+  // How many of the threads this call spawned haven't been joined yet, which it can't return until they have
+  unjoined: u32,
+}
+
+impl<'a> Frame<'a> {
+  // A call to ```func``` with ```env``` as its variables, which has yet to enter its first block
+  const fn new(func: &'a BBFunction, env: Environment, ret_dest: Option<u32>) -> Self {
+    Self {
+      func,
+      env,
+      at: Cursor {
+        block: 0,
+        instr: 0,
+        current_label: None,
+        last_label: None,
+      },
+      ret_dest,
+      speculation: Vec::new(),
+      unjoined: 0,
+    }
+  }
+
+  // The instruction which ran last, which is the call waiting to return in a caller or the `ret` which a call just ran
+  fn last_run(&self) -> &'a NumifiedInstruction {
+    &self.func.blocks[self.at.block].numified_instrs[self.at.instr - 1]
+  }
+
+  // Where the `ret` or call which the call just ran is, or where the function is for falling off of its end
+  fn returned_at(&self) -> Option<Position> {
+    let last = self
+      .at
+      .instr
+      .checked_sub(1)
+      .map(|idx| &self.func.blocks[self.at.block].instrs[idx]);
+    match last {
+      Some(
+        instr @ (Instruction::Effect {
+          op: bril_rs::EffectOps::Return | bril_rs::EffectOps::Call,
+          ..
+        }
+        | Instruction::Value {
+          op: bril_rs::ValueOps::Call,
+          ..
+        }),
+      ) => instr.get_pos(),
+      _ => self.func.pos,
+    }
+  }
+}
+
+// How far along a call is
+#[derive(Clone, Copy)]
+struct Cursor {
+  block: usize,
+  // The index into ```block``` of the next instruction to run
+  instr: usize,
+  // The symbols of the labels of the current block and the one before it, for phi nodes
+  current_label: Option<u32>,
+  last_label: Option<u32>,
+}
+
+impl Cursor {
+  #[inline(always)]
+  fn enter(&mut self, func: &BBFunction, idx: usize) {
+    self.block = idx;
+    self.instr = 0;
+    self.last_label = self.current_label;
+    self.current_label = func.blocks[idx].label_symbol;
+  }
+}
+
+// Runs a call to ```intrinsic```, which is called ```name```, putting what it returns in the destination of the call
+#[inline(never)]
+fn call_intrinsic<T: Sink>(
+  state: &mut State<'_, T>,
+  intrinsic: &Intrinsic,
+  name: &str,
+  numified: &NumifiedInstruction,
+  value_store: &mut Environment,
+) -> Result<(), InterpError> {
+  let args: Vec<RuntimeValue> = numified
+    .args
+    .iter()
+    .zip(intrinsic.args())
+    .map(|(a, ty)| RuntimeValue::new(value_store.get(a), ty, &state.heap.strings))
+    .collect();
+  let result = intrinsic
+    .call(&args)
+    .map_err(|e| InterpError::IntrinsicFailed(name.to_string(), e))?;
+  match (result, numified.dest, intrinsic.return_type()) {
+    (Some(value), Some(dest), Some(ty)) if value.fits(ty) => {
+      value_store.set(dest, value.value(&mut state.heap.strings));
+      Ok(())
+    }
+    (None, None, _) => Ok(()),
+    (Some(value), ..) => Err(InterpError::IntrinsicReturn(
+      name.to_string(),
+      format!("`{value}`"),
+    )),
+    (None, ..) => Err(InterpError::IntrinsicReturn(
+      name.to_string(),
+      "nothing".to_string(),
+    )),
+  }
+}
+
+// Moves ```at``` to the start of the block of ```func``` at ```idx```
+#[inline(always)]
+fn enter_block<'a, T: Sink>(
+  state: &mut State<'a, T>,
+  func: &'a BBFunction,
+  at: &mut Cursor,
+  idx: usize,
+) {
+  if let Some(tracer) = state.tracer.as_mut() {
+    tracer.enter_block(func, idx);
+  }
+  if let Some(profiler) = state.profiler.as_mut() {
+    profiler.enter_block(func, idx);
+  }
+  if let Some(profiler) = state.memory_profiler.as_mut() {
+    profiler.enter_block(func, idx);
+  }
+  at.enter(func, idx);
+}
+
+// Moves ```at``` to the start of the block of ```func``` at ```idx```, without telling anything which watches blocks being entered in a plain run, since there is nothing to tell
+#[inline(always)]
+fn enter<'a, T: Sink, const PLAIN: bool>(
+  state: &mut State<'a, T>,
+  func: &'a BBFunction,
+  at: &mut Cursor,
+  idx: usize,
+) {
+  if PLAIN {
+    at.enter(func, idx);
+  } else {
+    enter_block(state, func, at, idx);
+  }
+}
+
+/// A value in a running program, as it is passed into and out of an [`Interpreter`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeValue {
+  /// An `int`
+  Int(i64),
+  /// A `bool`
+  Bool(bool),
+  /// A `float`
+  Float(f64),
+  /// A `char`
+  Char(char),
+  /// A `str`, shared with the interpreter which made it
+  Str(Arc<str>),
+  /// A `ptr` of any type
+  Pointer(Pointer),
+  /// A `thread`, which is the number of the thread in the order they were spawned after the main thread
+  Thread(u32),
+}
+
+impl RuntimeValue {
+  // ```value``` read as ```ty```, which has to be the type it was created with, looking up strings in ```strings```
+  pub(crate) fn new(value: Value, ty: &Type, strings: &Strings) -> Self {
+    match ty {
+      Type::Int => Self::Int(value.as_int()),
+      Type::Bool => Self::Bool(value.as_bool()),
+      Type::Float => Self::Float(value.as_float()),
+      Type::Char => Self::Char(value.as_char()),
+      Type::Str => Self::Str(strings.shared(value)),
+      Type::Pointer(_) => Self::Pointer(value.as_pointer()),
+      Type::Thread => Self::Thread(value.as_thread()),
+      // Nothing has this type, but a function returning more than one value returns its first one wherever only one value fits
+      Type::Tuple(types) => Self::new(value, &types[0], strings),
+    }
+  }
+
+  fn value(&self, strings: &mut Strings) -> Value {
+    match self {
+      Self::Int(i) => Value::int(*i),
+      Self::Bool(b) => Value::bool(*b),
+      Self::Float(f) => Value::float(*f),
+      Self::Char(c) => Value::char(*c),
+      Self::Str(s) => strings.intern(s),
+      Self::Pointer(p) => Value::pointer(*p),
+      Self::Thread(t) => Value::thread(*t),
+    }
+  }
+
+  /// This value as `print` shows it, which is byte for byte how brili shows it. Floats are written like JavaScript numbers, so `2.5`, `3`, `1e+21`, `NaN` and `-Infinity`, and every pointer is `[object Object]` since brili has nothing else to show for one. A `char` is itself and a `str` is its text, and a `thread`, which brili doesn't have, is `thread` followed by its number.
+  ///
+  /// ```
+  /// use brilirs::interp::RuntimeValue;
+  /// assert_eq!(RuntimeValue::Float(0.1 + 0.2).to_bril_string(), "0.30000000000000004");
+  /// assert_eq!(RuntimeValue::Float(-0.0).to_bril_string(), "0");
+  /// assert_eq!(RuntimeValue::Float(1e21).to_bril_string(), "1e+21");
+  /// assert_eq!(RuntimeValue::Bool(true).to_bril_string(), "true");
+  /// ```
+  pub fn to_bril_string(&self) -> String {
+    self.to_string()
+  }
+
+  /// Parses ```s``` as an argument of type ```ty``` to the "main" function, accepting exactly what brili accepts. An `int` is read with JavaScript's `parseInt` and a `float` with its `parseFloat`, so leading white space and anything after the number are ignored, `0x` starts a hexadecimal `int`, and a `float` can be `Infinity` but not `NaN`. A `bool` is `true` or `false`. brili has no `char` or `str` arguments, so a `char` is any single character and a `str` is ```s``` itself. Pointers are given as JSON and have to be allocated, so they aren't parsed here, and neither are threads.
+  ///
+  /// # Errors
+  /// [`InterpError::BadFuncArgType`] if ```s``` isn't an argument of type ```ty```, including an `int` that doesn't fit in 64 bits, which brili would keep as a bigger integer. An `int` which only goes past 64 bits when it is rounded like JavaScript rounds it, like the largest `int` itself, keeps its exact value instead
+  ///
+  /// ```
+  /// use bril_rs::Type;
+  /// use brilirs::interp::RuntimeValue;
+  /// assert_eq!(RuntimeValue::parse(&Type::Int, " 12abc").unwrap(), RuntimeValue::Int(12));
+  /// assert_eq!(RuntimeValue::parse(&Type::Int, "0x1f").unwrap(), RuntimeValue::Int(31));
+  /// assert_eq!(RuntimeValue::parse(&Type::Float, "1.5e3x").unwrap(), RuntimeValue::Float(1500.0));
+  /// assert!(RuntimeValue::parse(&Type::Float, "NaN").is_err());
+  /// ```
+  pub fn parse(ty: &Type, s: &str) -> Result<Self, InterpError> {
+    let value = match ty {
+      Type::Int => parse_int(s).map(Self::Int),
+      Type::Bool => match s {
+        "true" => Some(Self::Bool(true)),
+        "false" => Some(Self::Bool(false)),
+        _ => None,
+      },
+      Type::Float => parse_float(s).map(Self::Float),
+      Type::Char => {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+          (Some(c), None) => Some(Self::Char(c)),
+          _ => None,
+        }
+      }
+      Type::Str => Some(Self::Str(s.into())),
+      Type::Pointer(_) | Type::Thread | Type::Tuple(_) => None,
+    };
+    value.ok_or_else(|| InterpError::BadFuncArgType(ty.clone(), s.to_string()))
+  }
+
+  // Whether this can be passed as an argument of type ```ty```. The type that a pointer points to isn't known, so any pointer fits any pointer type
+  const fn fits(&self, ty: &Type) -> bool {
+    matches!(
+      (self, ty),
+      (Self::Int(_), Type::Int)
+        | (Self::Bool(_), Type::Bool)
+        | (Self::Float(_), Type::Float)
+        | (Self::Char(_), Type::Char)
+        | (Self::Str(_), Type::Str)
+        | (Self::Pointer(_), Type::Pointer(_))
+        | (Self::Thread(_), Type::Thread)
+    )
+  }
+}
+
+impl fmt::Display for RuntimeValue {
+  // The same as RuntimeValue::to_bril_string
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Int(i) => write!(f, "{i}"),
+      Self::Bool(b) => write!(f, "{b}"),
+      Self::Float(v) => write!(f, "{}", format_float(*v)),
+      Self::Char(c) => write!(f, "{c}"),
+      Self::Str(s) => write!(f, "{s}"),
+      // brili's pointers are JavaScript objects without a string of their own
+      Self::Pointer(_) => write!(f, "[object Object]"),
+      Self::Thread(t) => write!(f, "thread {t}"),
+    }
+  }
+}
+
+/// An allocation which was never freed, from [`Interpreter::leaks`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leak {
+  /// Which allocation it is, as the `base` of the [`Pointer`]s into it
+  pub base: usize,
+  /// The number of values in the allocation
+  pub size: usize,
+  /// The type of the values in the allocation
+  pub ty: Type,
+  /// Where the allocation was made, like `Line 9, Column 3 of @main`
+  pub allocated_at: String,
+}
+
+impl fmt::Display for Leak {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let values = if self.size == 1 { "value" } else { "values" };
+    write!(
+      f,
+      "{} {values} of type `{}` allocated at {}",
+      self.size, self.ty, self.allocated_at
+    )
+  }
+}
+
+/// The heap of an [`Interpreter`] which has been stopped with [`Interpreter::into_memory`]. Giving it to [`Interpreter::with_memory`] lets another interpreter carry on with the same allocations, even one for a different program, which is how `brili-repl` keeps memory around from one line to the next.
+#[derive(Default)]
+pub struct Memory(Heap<'static>);
+
+impl Memory {
+  /// Every allocation which hasn't been freed, like [`Interpreter::allocations`]
+  pub fn allocations(&self) -> Vec<(usize, Vec<Option<RuntimeValue>>)> {
+    self.0.runtime_allocations()
+  }
+}
+
+/// Runs a [`BBProgram`] that has been type checked, while letting whoever is driving it decide how far to run and look at the state of the program along the way.
+///
+/// An interpreter starts out with nothing running. [`Interpreter::start_main`] and [`Interpreter::start_function`] start a function without running any of it, after which [`Interpreter::step`] runs one instruction at a time and [`Interpreter::run_to_completion`] runs the rest. [`Interpreter::call_function`] runs a whole call at once, even while something else is paused part of the way through, and the heap is shared between everything that runs. Once the embedder is done, [`Interpreter::finish`] checks for leaks and hands back what was recorded. [`execute_main_with_options`] does all of this for `main`.
+///
+/// An error leaves the program just after the instruction which caused it. Its state can still be looked at, but running any further carries on from there.
+pub struct Interpreter<'a, T: Sink> {
+  state: State<'a, T>,
+  // The innermost call is last
+  frames: Vec<Frame<'a>>,
+  // What the last call with nowhere to store its result returned, along with its type
+  returned: Option<(Value, &'a Type)>,
+  // The environments of calls which have returned, to be reused by the next calls
+  spare_envs: Vec<Environment>,
+  check_leaks: bool,
+  max_call_depth: Option<usize>,
+  max_instructions: Option<u64>,
+  // When Options::timeout runs out, counted from when the interpreter was made
+  deadline: Option<(std::time::Instant, std::time::Duration)>,
+  // Set by start_main so that leaks can point at main
+  main: Option<&'a BBFunction>,
+  profiling: bool,
+  // The profiler is also used to count opcodes for the stats, in which case its profile is only reported if it was asked for
+  collect_profile: bool,
+  // When the interpreter was made, if Options::collect_stats was set
+  started: Option<std::time::Instant>,
+  // The most calls which have been running at once
+  max_depth: usize,
+  // The threads of a program using the concurrency extension, numbered by their handles with the main thread first. This stays empty until the first spawn
+  threads: Vec<Thread<'a>>,
+  // Which of the threads is running, whose calls are the ones in frames
+  current: usize,
+  scheduler: Scheduler,
+  // How many instructions a thread runs at a time, and how many more the running thread has left
+  quantum: u64,
+  quantum_left: u64,
+}
+
+// A thread of a program using the concurrency extension, which is either the main thread or one started by `spawn`
+struct Thread<'a> {
+  // Its calls while another thread is running, since the calls of the running thread are kept in Interpreter::frames
+  frames: Vec<Frame<'a>>,
+  // The thread which spawned it and how many calls that thread had running, so that the call which spawned it can be found again once it is joined
+  spawner: (usize, usize),
+  // The thread which has joined it, or is waiting to
+  joiner: Option<usize>,
+  // The thread it is waiting to join, which hasn't finished yet, and where the join is
+  waiting: Option<(usize, Option<Position>)>,
+  finished: bool,
+}
+
+impl Thread<'_> {
+  const fn new(spawner: (usize, usize)) -> Self {
+    Self {
+      frames: Vec::new(),
+      spawner,
+      joiner: None,
+      waiting: None,
+      finished: false,
+    }
+  }
+
+  const fn runnable(&self) -> bool {
+    !self.finished && self.waiting.is_none()
+  }
+}
+
+impl<'a, T: Sink> Interpreter<'a, T> {
+  /// An interpreter for ```prog``` which prints to ```out``` and behaves as ```options``` says
+  pub fn new(prog: &'a BBProgram, out: T, options: Options) -> Self {
+    let heap = Heap::new(options.heap_size);
+    Self::with_heap(prog, out, options, heap)
+  }
+
+  /// An interpreter like [`Interpreter::new`] which starts out with the allocations in ```memory``` instead of an empty heap, so that pointers into it can still be used
+  pub fn with_memory(prog: &'a BBProgram, out: T, options: Options, memory: Memory) -> Self {
+    let mut heap = memory.0;
+    // Whatever gave the memory back may still have pointers into it
+    heap.count_pointers(false);
+    Self::with_heap(prog, out, options, heap)
+  }
+
+  fn with_heap(prog: &'a BBProgram, out: T, options: Options, mut heap: Heap<'a>) -> Self {
+    heap.count_pointers(options.rc_heap);
+    let memory_profiler = options
+      .collect_memory_profile
+      .then(|| MemoryProfiler::new(heap.live_values * 8));
+    Self {
+      state: State {
+        prog,
+        out,
+        heap,
+        instruction_count: 0,
+        debugger: options.debugger,
+        tracer: options.trace_threshold.map(Tracer::new),
+        instruction_trace: options.instruction_trace,
+        profiler: (options.collect_profile || options.collect_stats).then(Profiler::default),
+        memory_profiler,
+        coverage: options.collect_coverage.then(CoverageRecorder::default),
+        gas: (options.cost_model.is_some() || options.gas.is_some())
+          .then(|| Gas::new(options.cost_model.unwrap_or_default(), options.gas)),
+        hook: options.hook,
+        overflow: options.overflow,
+        div_semantics: options.div_semantics,
+        no_speculation: options.no_speculation,
+        tco: options.tco,
+        sanitize: options.sanitize,
+        races: None,
+        detect_races: options.detect_races,
+        rng: Rng::new(options.seed),
+      },
+      frames: Vec::new(),
+      returned: None,
+      spare_envs: Vec::new(),
+      main: None,
+      check_leaks: options.check_leaks,
+      max_call_depth: options.max_call_depth,
+      max_instructions: options.max_instructions,
+      deadline: options.timeout.map(|t| (std::time::Instant::now() + t, t)),
+      profiling: options.profiling,
+      collect_profile: options.collect_profile,
+      started: options.collect_stats.then(std::time::Instant::now),
+      max_depth: 0,
+      threads: Vec::new(),
+      current: 0,
+      scheduler: Scheduler::new(options.schedule, options.seed),
+      quantum: options
+        .quantum
+        .map_or(DEFAULT_QUANTUM, std::num::NonZeroU64::get),
+      quantum_left: 0,
+    }
+  }
+
+  /// Starts running the "main" function with ```input_args``` as its arguments, which are parsed the same way as on the command line
+  pub fn start_main(&mut self, input_args: &[String]) -> Result<(), PositionalInterpError> {
+    let main_func = self
+      .state
+      .prog
+      .get("main")
+      .ok_or_else(|| PositionalInterpError::new(InterpError::NoMainFunction))?;
+
+    if main_func.return_type.is_some() {
+      return Err(InterpError::NonEmptyRetForFunc(main_func.name.clone()))
+        .map_err(|e| e.add_pos(main_func.pos));
+    }
+
+    self.start_program(main_func, input_args)
+  }
+
+  /// Starts running the function ```name``` in place of "main", with ```input_args``` parsed the same way as [`Interpreter::start_main`] parses them. Unlike main it can return a value, which [`Interpreter::run_to_completion`] gives back, and the program doesn't need a main function at all
+  pub fn start_entry(
+    &mut self,
+    name: &str,
+    input_args: &[String],
+  ) -> Result<(), PositionalInterpError> {
+    let func = self
+      .state
+      .prog
+      .get(name)
+      .ok_or_else(|| PositionalInterpError::new(InterpError::FuncNotFound(name.to_string())))?;
+    self.start_program(func, input_args)
+  }
+
+  // Starts ```func``` as the outermost call of the program, which leaks are reported at
+  fn start_program(
+    &mut self,
+    func: &'a BBFunction,
+    input_args: &[String],
+  ) -> Result<(), PositionalInterpError> {
+    let env = Environment::new(func.num_of_vars);
+
+    let value_store =
+      parse_args(env, &mut self.state.heap, func, input_args).map_err(|e| e.add_pos(func.pos))?;
+
+    self.state.retain_vars(func, &value_store);
+    self.main = Some(func);
+    self.push_frame(func, value_store, None)
+  }
+
+  /// Starts a call to the function ```name``` with ```args```, as though the innermost function which is running had called it. Whatever was running before stays paused until the call returns.
+  pub fn start_function(
+    &mut self,
+    name: &str,
+    args: &[RuntimeValue],
+  ) -> Result<(), PositionalInterpError> {
+    let func = self
+      .state
+      .prog
+      .get(name)
+      .ok_or_else(|| PositionalInterpError::new(InterpError::FuncNotFound(name.to_string())))?;
+
+    if func.args.len() != args.len() {
+      return Err(InterpError::BadNumFuncArgs(func.args.len(), args.len()))
+        .map_err(|e| e.add_pos(func.pos));
+    }
+
+    let mut env = Environment::new(func.num_of_vars);
+    for ((arg, arg_as_num), value) in func.args.iter().zip(&func.args_as_nums).zip(args) {
+      if !value.fits(&arg.arg_type) {
+        return Err(InterpError::BadFuncArgType(
+          arg.arg_type.clone(),
+          value.to_string(),
+        ))
+        .map_err(|e| e.add_pos(func.pos));
+      }
+      env.set(*arg_as_num, value.value(&mut self.state.heap.strings));
+    }
+
+    self
+      .check_call_depth(func)
+      .map_err(|e| e.add_pos(func.pos))?;
+    self.state.retain_vars(func, &env);
+    self.push_frame(func, env, None)
+  }
+
+  /// Calls the function ```name``` with ```args``` and runs it until it returns, giving back what it returned. Anything that was already running stays paused where it was.
+  pub fn call_function(
+    &mut self,
+    name: &str,
+    args: &[RuntimeValue],
+  ) -> Result<Option<RuntimeValue>, PositionalInterpError> {
+    let depth = self.frames.len();
+    self.returned = None;
+    self.start_function(name, args)?;
+    self.run(depth, u64::MAX)?;
+    Ok(self.take_returned())
+  }
+
+  /// Runs the next instruction, along with the jumps and returns at the ends of blocks which follow it, and returns whether there is anything left to run
+  pub fn step(&mut self) -> Result<bool, PositionalInterpError> {
+    self.run_for(1)
+  }
+
+  /// Runs up to ```steps``` instructions like [`Interpreter::step`], and returns whether there is anything left to run
+  pub fn run_for(&mut self, steps: u64) -> Result<bool, PositionalInterpError> {
+    self.run(0, steps)?;
+    Ok(self.is_running())
+  }
+
+  /// Runs until everything which has been started returns, giving back what the outermost function returned
+  pub fn run_to_completion(&mut self) -> Result<Option<RuntimeValue>, PositionalInterpError> {
+    self.run(0, u64::MAX)?;
+    Ok(self.take_returned())
+  }
+
+  /// Whether any function is part of the way through running
+  pub const fn is_running(&self) -> bool {
+    !self.frames.is_empty()
+  }
+
+  /// The names of the functions which are running, from the outermost to the innermost
+  pub fn call_stack(&self) -> Vec<&'a str> {
+    self.frames.iter().map(|f| f.func.name.as_str()).collect()
+  }
+
+  /// The instruction which will run next, if anything is running
+  pub fn current_instruction(&self) -> Option<&'a Instruction> {
+    self
+      .frames
+      .last()
+      .map(|f| &f.func.blocks[f.at.block].instrs[f.at.instr])
+  }
+
+  /// The value of ```name``` in the innermost function which is running, if it has been defined
+  pub fn variable(&self, name: &str) -> Option<RuntimeValue> {
+    let frame = self.frames.last()?;
+    let var = frame.func.var_names.iter().position(|v| v == name)?;
+    Self::read_var(frame, var, &self.state.heap.strings)
+  }
+
+  /// Every variable that has been defined in the innermost function which is running, along with its value
+  pub fn variables(&self) -> Vec<(&'a str, RuntimeValue)> {
+    self.frames.last().map_or_else(Vec::new, |frame| {
+      frame
+        .func
+        .var_names
+        .iter()
+        .enumerate()
+        .filter_map(|(var, name)| {
+          Some((
+            name.as_str(),
+            Self::read_var(frame, var, &self.state.heap.strings)?,
+          ))
+        })
+        .collect()
+    })
+  }
+
+  fn read_var(frame: &Frame<'a>, var: usize, strings: &Strings) -> Option<RuntimeValue> {
+    let ty = frame.func.var_types[var].as_ref()?;
+    frame
+      .env
+      .lookup(var)
+      .map(|v| RuntimeValue::new(v, ty, strings))
+  }
+
+  /// Every allocation on the heap which hasn't been freed, in the order they were made, along with each of its values that has been stored to. Each allocation is given by the base of the pointers into it
+  pub fn allocations(&self) -> Vec<(usize, Vec<Option<RuntimeValue>>)> {
+    self.state.heap.runtime_allocations()
+  }
+
+  /// The number of instructions which have run so far
+  pub const fn instruction_count(&self) -> u32 {
+    self.state.instruction_count
+  }
+
+  /// The gas used by the instructions which have run so far, if [`Options::cost_model`] or [`Options::gas`] was set
+  pub fn gas_used(&self) -> Option<u64> {
+    self.state.gas.as_ref().map(|g| g.used)
+  }
+
+  /// Where the program prints to
+  pub const fn output(&self) -> &T {
+    &self.state.out
+  }
+
+  /// Every allocation which hasn't been freed yet, in the order they were made
+  pub fn leaks(&self) -> Vec<Leak> {
+    self.state.heap.leaks()
+  }
+
+  /// Stops the interpreter, reporting any memory which is still allocated as a leak and outputting the number of instructions run, and the gas they used if it was counted, to [std::io::stderr] if [`Options::profiling`] was set. With [`Options::check_leaks`], every leaked allocation is listed on [std::io::stderr] first
+  pub fn finish(self) -> Result<Report, PositionalInterpError> {
+    if self.state.heap.counting() {
+      eprintln!("collected_bytes: {}", self.state.heap.collected_values * 8);
+    }
+    if !self.state.heap.is_empty() {
+      if self.check_leaks {
+        for leak in self.state.heap.leaks() {
+          eprintln!("leak: {leak}");
+        }
+      }
+      return Err(self.state.heap.leak_error())
+        .map_err(|e| e.add_pos(self.main.and_then(|f| f.pos)));
+    }
+
+    if self.profiling {
+      eprintln!("total_dyn_inst: {}", self.state.instruction_count);
+      if let Some(gas) = self.gas_used() {
+        eprintln!("total_gas: {gas}");
+      }
+    }
+
+    let total_gas = self.gas_used();
+    let profile = self.state.profiler.map(|p| Profile {
+      total_gas,
+      ..p.finish(self.state.prog)
+    });
+    let stats = self.started.map(|started| ExecutionStats {
+      total_dyn_inst: u64::from(self.state.instruction_count),
+      opcodes: profile
+        .as_ref()
+        .map_or_else(Default::default, |p| p.opcodes.clone()),
+      max_call_depth: self.max_depth,
+      peak_heap_bytes: self.state.heap.peak_values * 8,
+      collected_bytes: self.state.heap.collected_values * 8,
+      wall_time: started.elapsed(),
+    });
+    Ok(Report {
+      traces: self.state.tracer.map_or_else(Vec::new, |t| t.traces),
+      profile: profile.filter(|_| self.collect_profile),
+      coverage: self.state.coverage.map(|c| c.finish(self.state.prog)),
+      stats,
+      memory_profile: self.state.memory_profiler.map(MemoryProfiler::finish),
+      returned: None,
+    })
+  }
+
+  /// Saves the state of everything which is running so that [`Interpreter::resume`] can carry on from here later. Programs which have spawned threads can't be saved
+  pub fn checkpoint(&self) -> Result<Checkpoint, PositionalInterpError> {
+    if !self.threads.is_empty() {
+      return Err(PositionalInterpError::new(InterpError::ThreadsUnsupported(
+        "checkpoints",
+      )));
+    }
+    Ok(Checkpoint {
+      frames: self
+        .frames
+        .iter()
+        .map(|f| FrameState {
+          function: f.func.name.clone(),
+          env: f.env.clone(),
+          block: f.at.block,
+          instr: f.at.instr,
+          last_label: f.at.last_label.map(|l| f.func.labels.name(l).to_string()),
+          ret_dest: f.ret_dest,
+          speculation: f.speculation.clone(),
+        })
+        .collect(),
+      heap: self.state.heap.save(),
+      instruction_count: self.state.instruction_count,
+      main: self.main.map(|f| f.name.clone()),
+      rng: Some(self.state.rng.state),
+    })
+  }
+
+  /// An interpreter like [`Interpreter::new`] which carries on from where ```checkpoint``` was made, which has to have been with ```prog```
+  pub fn resume(
+    prog: &'a BBProgram,
+    out: T,
+    options: Options,
+    checkpoint: Checkpoint,
+  ) -> Result<Self, PositionalInterpError> {
+    let heap = Heap::restore(checkpoint.heap, prog).map_err(PositionalInterpError::new)?;
+    let mut interpreter = Self::with_heap(prog, out, options, heap);
+    interpreter.state.instruction_count = checkpoint.instruction_count;
+    if let Some(rng) = checkpoint.rng {
+      interpreter.state.rng.state = rng;
+    }
+    if let Some(gas) = interpreter.state.gas.as_mut() {
+      gas.instructions = checkpoint.instruction_count;
+    }
+    interpreter.main = checkpoint
+      .main
+      .map(|name| checkpointed_function(prog, &name))
+      .transpose()
+      .map_err(PositionalInterpError::new)?;
+    for frame in checkpoint.frames {
+      let func =
+        checkpointed_function(prog, &frame.function).map_err(PositionalInterpError::new)?;
+      let mismatch = |problem: String| {
+        PositionalInterpError::new(InterpError::CheckpointMismatch(format!(
+          "@{} {problem}",
+          func.name
+        )))
+      };
+      // Callers can be just past a call at the end of their block
+      if func
+        .blocks
+        .get(frame.block)
+        .is_none_or(|b| frame.instr > b.instrs.len())
+      {
+        return Err(mismatch(format!(
+          "has no instruction {} in block {}",
+          frame.instr, frame.block
+        )));
+      }
+      if std::iter::once(&frame.env)
+        .chain(&frame.speculation)
+        .any(|env| {
+          env.env.len() != func.num_of_vars as usize
+            || env.defined.len() != func.num_of_vars as usize
+        })
+      {
+        return Err(mismatch(format!(
+          "doesn't have {} variables",
+          frame.env.env.len()
+        )));
+      }
+      let last_label = match frame.last_label {
+        Some(label) => Some(
+          func
+            .labels
+            .get(&label)
+            .filter(|l| func.blocks.iter().any(|b| b.label_symbol == Some(*l)))
+            .ok_or_else(|| mismatch(format!("has no label .{label}")))?,
+        ),
+        None => None,
+      };
+      interpreter.frames.push(Frame {
+        func,
+        env: frame.env,
+        at: Cursor {
+          block: frame.block,
+          instr: frame.instr,
+          current_label: func.blocks[frame.block].label_symbol,
+          last_label,
+        },
+        ret_dest: frame.ret_dest,
+        speculation: frame.speculation,
+        unjoined: 0,
+      });
+    }
+    Ok(interpreter)
+  }
+
+  /// Stops the interpreter without checking for leaks, handing back its heap so that it can be given to another interpreter with [`Interpreter::with_memory`]
+  pub fn into_memory(self) -> Memory {
+    Memory(self.state.heap.detach())
+  }
+
+  fn take_returned(&mut self) -> Option<RuntimeValue> {
+    let strings = &self.state.heap.strings;
+    self
+      .returned
+      .take()
+      .map(|(v, ty)| RuntimeValue::new(v, ty, strings))
+  }
+
+  // Runs up to ```steps``` instructions for as long as more than ```depth``` calls are running. With threads, those are the calls of the main thread when ```depth``` is 0 and of the running thread otherwise, and the other threads take their turns along the way
+  fn run(&mut self, depth: usize, steps: u64) -> Result<(), PositionalInterpError> {
+    // How many instructions are run between looking at the clock for Options::timeout
+    const TIMEOUT_SLICE: u64 = 1 << 16;
+    let allowed = self.max_instructions.map_or(steps, |max| {
+      steps.min(max.saturating_sub(u64::from(self.state.instruction_count)))
+    });
+    let driver = if depth == 0 { 0 } else { self.current };
+    let mut left = allowed;
+    let mut result = Ok(());
+    while result.is_ok() && left > 0 && self.frames_of(driver) > depth {
+      let mut slice = left;
+      if self.deadline.is_some() {
+        slice = slice.min(TIMEOUT_SLICE);
+      }
+      if !self.threads.is_empty() {
+        slice = slice.min(self.quantum_left);
+      }
+      let mut slice_left = slice;
+      result = self.run_frame(depth, &mut slice_left);
+      left -= slice - slice_left;
+      if result.is_err() || self.frames_of(driver) <= depth {
+        break;
+      }
+      if !self.threads.is_empty() {
+        result = self.schedule(slice - slice_left);
+      }
+      if let Some((deadline, timeout)) = self.deadline {
+        if result.is_ok() && std::time::Instant::now() >= deadline {
+          result = Err(self.resource_limit(Limit::Timeout(timeout), allowed - left));
+        }
+      }
+    }
+    self.state.instruction_count += (allowed - left) as u32;
+    if result.is_ok() && left == 0 && allowed < steps && self.frames_of(driver) > depth {
+      // There were fewer instructions left than were asked for, so the limit must be set
+      result = Err(self.resource_limit(
+        Limit::Instructions(self.max_instructions.unwrap_or_default()),
+        0,
+      ));
+    }
+    result.map_err(|e| {
+      let pos = e.pos();
+      e.with_backtrace(|| self.backtrace(pos))
+    })
+  }
+
+  // The number of calls ```thread``` has running
+  fn frames_of(&self, thread: usize) -> usize {
+    if thread == self.current {
+      self.frames.len()
+    } else {
+      self.threads[thread].frames.len()
+    }
+  }
+
+  // Moves on to another thread once the running one has finished, is waiting to join another, or has used up its turn by running ```ran``` more instructions
+  fn schedule(&mut self, ran: u64) -> Result<(), PositionalInterpError> {
+    self.quantum_left = self.quantum_left.saturating_sub(ran);
+    let finished = self.frames.is_empty();
+    if finished {
+      self.finish_thread();
+    }
+    let blocked = finished || self.threads[self.current].waiting.is_some();
+    if !blocked && self.quantum_left > 0 {
+      return Ok(());
+    }
+    self.quantum_left = self.quantum;
+    match self.scheduler.next(self.current, self.threads.len(), |t| {
+      self.threads[t].runnable()
+    }) {
+      Some(next) => {
+        self.switch_to(next);
+        Ok(())
+      }
+      // Joins which would wait on each other are caught when they are made, so this is only for a thread which is waiting when nothing else can run
+      None if blocked => {
+        let (thread, pos) = self.threads[self.current]
+          .waiting
+          .unwrap_or((self.current, None));
+        Err(InterpError::Deadlock(thread as u32).add_pos(pos))
+      }
+      None => Ok(()),
+    }
+  }
+
+  // Makes ```thread``` the one which is running
+  fn switch_to(&mut self, thread: usize) {
+    std::mem::swap(&mut self.frames, &mut self.threads[self.current].frames);
+    std::mem::swap(&mut self.frames, &mut self.threads[thread].frames);
+    self.current = thread;
+    if let Some(races) = self.state.races.as_mut() {
+      races.switch(thread);
+    }
+  }
+
+  // Starts a thread running ```func``` with ```args``` from the innermost call of the running thread, which stores the thread in ```dest```. The new thread gets its first turn whenever the scheduler comes to it
+  fn spawn(
+    &mut self,
+    func: &'a BBFunction,
+    args: &[u32],
+    dest: u32,
+  ) -> Result<(), PositionalInterpError> {
+    // These follow a single call stack, which threads taking turns would tangle up
+    if self.state.debugger.is_some() {
+      return Err(PositionalInterpError::new(InterpError::ThreadsUnsupported(
+        "the debugger",
+      )));
+    }
+    if self.state.memory_profiler.is_some() {
+      return Err(PositionalInterpError::new(InterpError::ThreadsUnsupported(
+        "the memory profile",
+      )));
+    }
+    if self.threads.is_empty() {
+      self.threads.push(Thread::new((0, 0)));
+      self.quantum_left = self.quantum;
+    }
+    let thread = self.threads.len();
+    let Some(frame) = self.frames.last_mut() else {
+      unsafe { unreachable_unchecked() }
+    };
+    let mut env = self.spare_envs.pop().unwrap_or_default();
+    make_func_args(func, args, &frame.env, &mut env, &mut self.state.heap);
+    frame.env.set(dest, Value::thread(thread as u32));
+    frame.unjoined += 1;
+    self
+      .threads
+      .push(Thread::new((self.current, self.frames.len())));
+    if self.state.detect_races {
+      self
+        .state
+        .races
+        .get_or_insert_with(RaceDetector::new)
+        .spawn(self.current, thread);
+    }
+    let spawner = self.current;
+    self.switch_to(thread);
+    let result = self.push_frame(func, env, None);
+    // A function with nothing in it is over as soon as it starts
+    if result.is_ok() && self.frames.is_empty() {
+      self.finish_thread();
+    }
+    self.switch_to(spawner);
+    result
+  }
+
+  // The running thread joins ```thread```, waiting at ```pos``` for it to finish if it hasn't yet
+  fn join(&mut self, thread: u32, pos: Option<Position>) -> Result<(), InterpError> {
+    let joined = thread as usize;
+    let invalid = if joined == self.current {
+      Some("is the thread joining it")
+    } else if joined == 0 {
+      Some("is the main thread")
+    } else {
+      match self.threads.get(joined) {
+        None => Some("doesn't exist"),
+        Some(t) if t.joiner.is_some() => Some("another thread has already joined"),
+        Some(_) => None,
+      }
+    };
+    if let Some(why) = invalid {
+      return Err(InterpError::InvalidJoin(thread, why));
+    }
+    // Every thread is joined by at most one other, so following what each thread is waiting for only comes back around to the running thread if the join would wait forever
+    let mut waiting_for = joined;
+    while let Some((next, _)) = self.threads[waiting_for].waiting {
+      if next == self.current {
+        return Err(InterpError::Deadlock(thread));
+      }
+      waiting_for = next;
+    }
+    self.threads[joined].joiner = Some(self.current);
+    if self.threads[joined].finished {
+      self.complete_join(joined);
+    } else {
+      self.threads[self.current].waiting = Some((joined, pos));
+    }
+    Ok(())
+  }
+
+  // The running thread has returned from its first call, so whatever is waiting to join it can carry on
+  fn finish_thread(&mut self) {
+    self.threads[self.current].finished = true;
+    self.complete_join(self.current);
+  }
+
+  // ```joined``` has finished, so if it has been joined its joiner can carry on, as can the call which spawned it once all of its other threads have been joined too
+  fn complete_join(&mut self, joined: usize) {
+    let Some(joiner) = self.threads[joined].joiner else {
+      return;
+    };
+    self.threads[joiner].waiting = None;
+    let (spawner, depth) = self.threads[joined].spawner;
+    let frames = if spawner == self.current {
+      &mut self.frames
+    } else {
+      &mut self.threads[spawner].frames
+    };
+    frames[depth - 1].unjoined -= 1;
+    if let Some(races) = self.state.races.as_mut() {
+      races.join(joiner, joined);
+    }
+  }
+
+  // Stops the program at the next instruction for going past ```limit```, counting the ```uncounted``` instructions which have run since instruction_count was last added to
+  fn resource_limit(&self, limit: Limit, uncounted: u64) -> PositionalInterpError {
+    let count = self.state.instruction_count + uncounted as u32;
+    InterpError::ResourceLimit(limit, count)
+      .add_pos(self.current_instruction().and_then(Instruction::get_pos))
+  }
+
+  // The calls which are running, from the innermost, whose next instruction is at ```pos```, outwards. Only the innermost and outermost BACKTRACE_SHOWN are kept
+  fn backtrace(&self, pos: Option<Position>) -> Vec<BacktraceFrame> {
+    const BACKTRACE_SHOWN: usize = 10;
+    let outermost = self.frames.len().saturating_sub(BACKTRACE_SHOWN);
+    self
+      .frames
+      .iter()
+      .rev()
+      .enumerate()
+      .filter(|(depth, _)| *depth < BACKTRACE_SHOWN || *depth >= outermost)
+      .map(|(depth, frame)| {
+        let block = &frame.func.blocks[frame.at.block];
+        BacktraceFrame {
+          depth,
+          function: frame.func.name.clone(),
+          label: block.label.clone(),
+          pos: if depth == 0 {
+            pos
+          } else {
+            // Callers are left just past the call they are waiting on
+            frame
+              .at
+              .instr
+              .checked_sub(1)
+              .and_then(|idx| block.instrs[idx].get_pos())
+          },
+        }
+      })
+      .collect()
+  }
+
+  fn push_frame(
+    &mut self,
+    func: &'a BBFunction,
+    env: Environment,
+    ret_dest: Option<u32>,
+  ) -> Result<(), PositionalInterpError> {
+    if let Some(debugger) = self.state.debugger.as_mut() {
+      debugger.enter_function(func);
+    }
+    if let Some(profiler) = self.state.profiler.as_mut() {
+      profiler.enter_function(func);
+    }
+    if let Some(profiler) = self.state.memory_profiler.as_mut() {
+      profiler.enter_function(func);
+    }
+    if let Some(hook) = self.state.hook.as_mut() {
+      let args: Vec<RuntimeValue> = func
+        .args
+        .iter()
+        .zip(&func.args_as_nums)
+        .map(|(arg, var)| RuntimeValue::new(env.get(var), &arg.arg_type, &self.state.heap.strings))
+        .collect();
+      hook.before_call(&func.name, &args);
+    }
+    let mut frame = Frame::new(func, env, ret_dest);
+    enter_block(&mut self.state, func, &mut frame.at, 0);
+    self.frames.push(frame);
+    self.max_depth = self.max_depth.max(self.frames.len());
+    self.settle()
+  }
+
+  // Calling ```callee``` can't make the call stack deeper than Options::max_call_depth. The error shows the outermost and innermost calls, since the whole stack could be enormous
+  fn check_call_depth(&self, callee: &BBFunction) -> Result<(), InterpError> {
+    const SHOWN: usize = 5;
+    let Some(max) = self.max_call_depth else {
+      return Ok(());
+    };
+    if self.frames.len() < max {
+      return Ok(());
+    }
+    let names: Vec<&str> = self
+      .frames
+      .iter()
+      .map(|f| f.func.name.as_str())
+      .chain(std::iter::once(callee.name.as_str()))
+      .collect();
+    let backtrace = if names.len() > 2 * SHOWN {
+      format!(
+        "{} -> ... {} more ... -> {}",
+        names[..SHOWN].join(" -> "),
+        names.len() - 2 * SHOWN,
+        names[names.len() - SHOWN..].join(" -> ")
+      )
+    } else {
+      names.join(" -> ")
+    };
+    Err(InterpError::StackOverflow(max, backtrace))
+  }
+
+  // Finishes ```frame```, which has already been taken off of the call stack, by giving ```value``` back to whatever called it
+  fn return_from(&mut self, frame: Frame<'a>, value: Option<Value>) {
+    if let Some(debugger) = self.state.debugger.as_mut() {
+      debugger.exit_function();
+    }
+    if let Some(profiler) = self.state.memory_profiler.as_mut() {
+      profiler.exit_function();
+    }
+    if let Some(hook) = self.state.hook.as_mut() {
+      let returned = value
+        .zip(frame.func.return_type.as_ref())
+        .map(|(value, ty)| RuntimeValue::new(value, ty, &self.state.heap.strings));
+      hook.after_call(&frame.func.name, returned.as_ref());
+    }
+    // The values after the first go into the variables after the first of the call, and a function can only return more than one by running `ret`
+    if let (Some(Type::Tuple(_)), Some(caller)) = (&frame.func.return_type, self.frames.last_mut())
+    {
+      let ret = frame.last_run();
+      for (dest, arg) in caller.last_run().extra_dests.iter().zip(&ret.args[1..]) {
+        let old = caller.env.lookup(*dest as usize);
+        let new = frame.env.get(arg);
+        caller.env.set(*dest, new);
+        if self.state.heap.counting() && holds_pointer(caller.func, *dest) {
+          self.state.reassign(Some(new), old);
+        }
+      }
+    }
+    match (frame.ret_dest, self.frames.last_mut()) {
+      (Some(dest), Some(caller)) => {
+        let old = caller.env.lookup(dest as usize);
+        caller.env.set(dest, value.unwrap());
+        if self.state.heap.counting() && holds_pointer(caller.func, dest) {
+          self.state.reassign(value, old);
+        }
+      }
+      // The first call of a spawned thread has nothing to return to
+      (_, None) if self.current != 0 => {}
+      _ => {
+        self.returned = value.zip(frame.func.return_type.as_ref());
+        // Whoever gets the pointer back could keep it for as long as they like
+        if let Some((value, Type::Pointer(_))) = self.returned {
+          self.state.heap.pin(value);
+        }
+      }
+    }
+    self.state.release_vars(frame.func, &frame.env);
+    self.spare_envs.push(frame.env);
+  }
+
+  // Follows the ends of blocks into the blocks after them and out of the functions they end, until the next thing to run is an instruction or nothing is left running
+  #[inline(always)]
+  fn settle(&mut self) -> Result<(), PositionalInterpError> {
+    while let Some(frame) = self.frames.last_mut() {
+      let block = &frame.func.blocks[frame.at.block];
+      if frame.at.instr < block.instrs.len() {
+        return Ok(());
+      }
+      if block.exit.len() == 1 {
+        enter_block(&mut self.state, frame.func, &mut frame.at, block.exit[0]);
+      } else {
+        check_joined(frame).map_err(|e| e.add_pos(frame.func.pos))?;
+        if let Some(frame) = self.frames.pop() {
+          self.return_from(frame, None);
+        }
+      }
+    }
+    Ok(())
+  }
+
+  // Runs the innermost call until it calls another function or returns, or until ```steps``` instructions have run. Calls and returns which don't leave ```depth``` or fewer calls running are made in place in a plain run
+  #[inline(always)]
+  fn run_frame(&mut self, depth: usize, steps: &mut u64) -> Result<(), PositionalInterpError> {
+    // The stats need to see every call
+    let depth = self.started.is_none().then_some(depth);
+    match execute(
+      &mut self.state,
+      &mut self.frames,
+      &mut self.spare_envs,
+      depth,
+      self.max_call_depth.unwrap_or(usize::MAX),
+      steps,
+    )? {
+      Exit::Paused => {}
+      Exit::Call(callee_func, args, dest) => {
+        let Some(frame) = self.frames.last() else {
+          unsafe { unreachable_unchecked() }
+        };
+        self
+          .check_call_depth(callee_func)
+          .map_err(|e| e.add_pos(frame.returned_at()))?;
+        let mut next_env = self.spare_envs.pop().unwrap_or_default();
+        make_func_args(
+          callee_func,
+          args,
+          &frame.env,
+          &mut next_env,
+          &mut self.state.heap,
+        );
+        self.push_frame(callee_func, next_env, dest)?;
+      }
+      Exit::TailCall(callee_func, args) => {
+        // This is safe because the call making the tail call is still running
+        let Some(frame) = self.frames.last() else {
+          unsafe { unreachable_unchecked() }
+        };
+        check_joined(frame).map_err(|e| e.add_pos(frame.returned_at()))?;
+        let mut next_env = self.spare_envs.pop().unwrap_or_default();
+        make_func_args(
+          callee_func,
+          args,
+          &frame.env,
+          &mut next_env,
+          &mut self.state.heap,
+        );
+        let Some(frame) = self.frames.pop() else {
+          unsafe { unreachable_unchecked() }
+        };
+        if let Some(debugger) = self.state.debugger.as_mut() {
+          debugger.exit_function();
+        }
+        if let Some(profiler) = self.state.memory_profiler.as_mut() {
+          profiler.exit_function();
+        }
+        self.state.release_vars(frame.func, &frame.env);
+        self.spare_envs.push(frame.env);
+        // The callee returns straight to whatever called the function it replaces
+        self.push_frame(callee_func, next_env, frame.ret_dest)?;
+      }
+      Exit::Return(result) => {
+        let Some(frame) = self.frames.last() else {
+          unsafe { unreachable_unchecked() }
+        };
+        check_joined(frame).map_err(|e| e.add_pos(frame.returned_at()))?;
+        let Some(frame) = self.frames.pop() else {
+          unsafe { unreachable_unchecked() }
+        };
+        self.return_from(frame, result);
+        self.settle()?;
+      }
+      Exit::Spawn(callee_func, args, dest, pos) => {
+        self
+          .spawn(callee_func, args, dest)
+          .map_err(|e| InterpError::from(e).add_pos(pos))?;
+      }
+      Exit::Join(thread, pos) => self.join(thread, pos).map_err(|e| e.add_pos(pos))?,
+    }
+    Ok(())
+  }
+}
+
+// A call can't return while threads it spawned are still running, so that every thread is over by the time the call which started it is
+#[inline(always)]
+fn check_joined(frame: &Frame) -> Result<(), InterpError> {
+  if frame.unjoined == 0 {
+    Ok(())
+  } else {
+    Err(InterpError::UnjoinedThread(
+      frame.func.name.clone(),
+      frame.unjoined,
+    ))
+  }
+}
+
+// With --sanitize, every argument of ```code``` has to have been assigned along the path that was taken, which the type checker can't make sure of. Phi nodes are the exception, since they are allowed to take a variable which hasn't been assigned like `__undefined`
+#[inline(never)]
+fn check_assigned(
+  func: &BBFunction,
+  code: &Instruction,
+  numified_code: &NumifiedInstruction,
+  value_store: &Environment,
+) -> Result<(), InterpError> {
+  if matches!(
+    code,
+    Instruction::Value {
+      op: bril_rs::ValueOps::Phi,
+      ..
+    }
+  ) {
+    return Ok(());
+  }
+  numified_code
+    .args
+    .iter()
+    .find(|arg| value_store.lookup(**arg as usize).is_none())
+    .map_or(Ok(()), |arg| {
+      Err(InterpError::UnassignedVariable(
+        func.var_names[*arg as usize].clone(),
+      ))
+    })
+}
+
+// Why ```execute``` stopped running a call
+enum Exit<'a> {
+  // It ran out of steps, so it picks up where it left off next time
+  Paused,
+  // The function to call, the arguments to pass it, and where to store what it returns
+  Call(&'a BBFunction, &'a [u32], Option<u32>),
+  // A call whose result is returned straight away, which takes the place of the call making it
+  TailCall(&'a BBFunction, &'a [u32]),
+  Return(Option<Value>),
+  // The function to start a thread running, the arguments to pass it, where to store the thread, and where the spawn is
+  Spawn(&'a BBFunction, &'a [u32], u32, Option<Position>),
+  // The thread to wait for, and where the join is
+  Join(u32, Option<Position>),
+}
+
+// Runs the innermost of ```frames``` until it calls another function or returns, or until ```steps``` instructions have run. A plain run makes calls and returns itself instead, since nothing else needs to see them, as long as ```depth``` is given and more than that many calls are left running. Calls which would have ```max_call_depth``` or more running are still left to the caller, to fail
+#[inline(always)]
+fn execute<'a, T: Sink>(
+  state: &mut State<'a, T>,
+  frames: &mut Vec<Frame<'a>>,
+  spare_envs: &mut Vec<Environment>,
+  depth: Option<usize>,
+  max_call_depth: usize,
+  steps: &mut u64,
+) -> Result<Exit<'a>, PositionalInterpError> {
+  // This is copied out and back so that it can live in a register while the call runs
+  let mut left = *steps;
+  // Looking at every instruction as it runs, like --trace does, or counting pointers for Options::rc_heap would slow down every instruction, so plain runs get a loop without any of it
+  let result = if state.plain() {
+    execute_blocks::<T, true>(state, frames, spare_envs, depth, max_call_depth, &mut left)
+  } else {
+    execute_blocks::<T, false>(state, frames, spare_envs, None, 0, &mut left)
+  };
+  *steps = left;
+  result
+}
+
+// Takes the innermost of ```frames``` off, returning ```result``` from it, if that can be done in place in a plain run. Returning more than one value, from a call with threads left to join, or to ```depth``` or fewer calls running is left to Interpreter::return_from
+#[inline(always)]
+fn return_in_place(
+  frames: &mut Vec<Frame>,
+  spare_envs: &mut Vec<Environment>,
+  depth: Option<usize>,
+  result: Option<Value>,
+) -> bool {
+  let running = frames.len();
+  match frames.last() {
+    Some(frame)
+      if depth.is_some_and(|depth| running > depth + 1)
+        && frame.unjoined == 0
+        && !matches!(frame.func.return_type, Some(Type::Tuple(_))) => {}
+    _ => return false,
+  }
+  let Some(frame) = frames.pop() else {
+    unsafe { unreachable_unchecked() }
+  };
+  if let (Some(dest), Some(caller)) = (frame.ret_dest, frames.last_mut()) {
+    caller.env.set(dest, result.unwrap());
+  }
+  spare_envs.push(frame.env);
+  true
+}
+
+// What running an instruction without an Op of its own leads to
+enum Flow<'a> {
+  // On to the next instruction of the block
+  Next,
+  // To the start of the block at this index
+  Enter(usize),
+  Exit(Exit<'a>),
+}
+
+#[inline(always)]
+fn execute_blocks<'a, T: Sink, const PLAIN: bool>(
+  state: &mut State<'a, T>,
+  frames: &mut Vec<Frame<'a>>,
+  spare_envs: &mut Vec<Environment>,
+  depth: Option<usize>,
+  max_call_depth: usize,
+  steps: &mut u64,
+) -> Result<Exit<'a>, PositionalInterpError> {
+  // These can't change while the program runs, so they are only worked out once, and never in a plain run
+  let instrumented = !PLAIN && state.instrumented();
+  let counted = !PLAIN && state.heap.counting();
+  'calls: loop {
+    let running = frames.len();
+    // This is safe because execute is only called while something is running
+    let Some(frame) = frames.last_mut() else {
+      unsafe { unreachable_unchecked() }
+    };
+    let func = frame.func;
+    let value_store = &mut frame.env;
+    let speculation = &mut frame.speculation;
+    let at = &mut frame.at;
+    'blocks: loop {
+      let curr_block = &func.blocks[at.block];
+      let curr_instrs = &curr_block.instrs;
+      // Steps are taken for the rest of the block up front, or as much of it as there are steps for, so that they aren't counted one instruction at a time. Leaving part of the way through for a call or return gives back the steps that weren't used
+      let end = curr_instrs.len().min(
+        at.instr
+          .saturating_add(usize::try_from(*steps).unwrap_or(usize::MAX)),
+      );
+      *steps -= (end - at.instr) as u64;
+
+      let start = at.instr;
+      let block_idx = at.block;
+
+      for (instr_idx, op) in curr_block.ops[start..end]
+        .iter()
+        .enumerate()
+        .map(|(i, op)| (start + i, op))
+      {
+        // Where the instruction is, which only memory operations, calls and errors need
+        let site = move || Site {
+          func,
+          block: block_idx,
+          instr: instr_idx,
+        };
+        let pos = move || curr_instrs[instr_idx].get_pos();
+        let traced = if instrumented {
+          before_instruction(state, func, block_idx, instr_idx, value_store)?
+        } else {
+          None
+        };
+        at.instr = instr_idx + 1;
+        // What the destination pointed to before the instruction overwrites it, for Options::rc_heap. Calls have what they return counted when they return
+        let overwritten = if counted {
+          curr_block.numified_instrs[instr_idx]
+            .dest
+            .filter(|dest| holds_pointer(func, *dest))
+            .map(|dest| (dest, value_store.lookup(dest as usize)))
+        } else {
+          None
+        };
+
+        match *op {
+          Op::Const { dest, value } => value_store.set(dest, value),
+          Op::Id { dest, arg } => value_store.set(dest, value_store.get(&arg)),
+          Op::Add { dest, lhs, rhs } => {
+            int_op(state, bril_rs::ValueOps::Add, value_store, dest, lhs, rhs)
+              .map_err(|e| e.add_pos(pos()))?;
+          }
+          Op::Sub { dest, lhs, rhs } => {
+            int_op(state, bril_rs::ValueOps::Sub, value_store, dest, lhs, rhs)
+              .map_err(|e| e.add_pos(pos()))?;
+          }
+          Op::Mul { dest, lhs, rhs } => {
+            int_op(state, bril_rs::ValueOps::Mul, value_store, dest, lhs, rhs)
+              .map_err(|e| e.add_pos(pos()))?;
+          }
+          Op::Div { dest, lhs, rhs } => {
+            int_op(state, bril_rs::ValueOps::Div, value_store, dest, lhs, rhs)
+              .map_err(|e| e.add_pos(pos()))?;
+          }
+          Op::Rem { dest, lhs, rhs } => {
+            int_op(state, bril_rs::ValueOps::Rem, value_store, dest, lhs, rhs)
+              .map_err(|e| e.add_pos(pos()))?;
+          }
+          Op::Eq { dest, lhs, rhs } => compare::<i64>(value_store, dest, lhs, rhs, |a, b| a == b),
+          Op::Lt { dest, lhs, rhs } => compare::<i64>(value_store, dest, lhs, rhs, |a, b| a < b),
+          Op::Gt { dest, lhs, rhs } => compare::<i64>(value_store, dest, lhs, rhs, |a, b| a > b),
+          Op::Le { dest, lhs, rhs } => compare::<i64>(value_store, dest, lhs, rhs, |a, b| a <= b),
+          Op::Ge { dest, lhs, rhs } => compare::<i64>(value_store, dest, lhs, rhs, |a, b| a >= b),
+          Op::EqBr { dest, lhs, rhs } => {
+            if let Some(target) =
+              compare_branch::<PLAIN>(curr_block, value_store, at, end, dest, lhs, rhs, |a, b| {
+                a == b
+              })
+            {
+              enter::<T, PLAIN>(state, func, at, target);
+              continue 'blocks;
+            }
+          }
+          Op::LtBr { dest, lhs, rhs } => {
+            if let Some(target) =
+              compare_branch::<PLAIN>(curr_block, value_store, at, end, dest, lhs, rhs, |a, b| {
+                a < b
+              })
+            {
+              enter::<T, PLAIN>(state, func, at, target);
+              continue 'blocks;
+            }
+          }
+          Op::GtBr { dest, lhs, rhs } => {
+            if let Some(target) =
+              compare_branch::<PLAIN>(curr_block, value_store, at, end, dest, lhs, rhs, |a, b| {
+                a > b
+              })
+            {
+              enter::<T, PLAIN>(state, func, at, target);
+              continue 'blocks;
+            }
+          }
+          Op::LeBr { dest, lhs, rhs } => {
+            if let Some(target) =
+              compare_branch::<PLAIN>(curr_block, value_store, at, end, dest, lhs, rhs, |a, b| {
+                a <= b
+              })
+            {
+              enter::<T, PLAIN>(state, func, at, target);
+              continue 'blocks;
+            }
+          }
+          Op::GeBr { dest, lhs, rhs } => {
+            if let Some(target) =
+              compare_branch::<PLAIN>(curr_block, value_store, at, end, dest, lhs, rhs, |a, b| {
+                a >= b
+              })
+            {
+              enter::<T, PLAIN>(state, func, at, target);
+              continue 'blocks;
+            }
+          }
+          Op::Not { dest, arg } => {
+            let arg = value_store.get(&arg).as_bool();
+            value_store.set(dest, Value::bool(!arg));
+          }
+          Op::And { dest, lhs, rhs } => compare::<bool>(value_store, dest, lhs, rhs, |a, b| a && b),
+          Op::Or { dest, lhs, rhs } => compare::<bool>(value_store, dest, lhs, rhs, |a, b| a || b),
+          Op::Fadd { dest, lhs, rhs } => float_op(value_store, dest, lhs, rhs, |a, b| a + b),
+          Op::Fsub { dest, lhs, rhs } => float_op(value_store, dest, lhs, rhs, |a, b| a - b),
+          Op::Fmul { dest, lhs, rhs } => float_op(value_store, dest, lhs, rhs, |a, b| a * b),
+          Op::Fdiv { dest, lhs, rhs } => float_op(value_store, dest, lhs, rhs, |a, b| a / b),
+          Op::Feq { dest, lhs, rhs } => compare::<f64>(value_store, dest, lhs, rhs, |a, b| a == b),
+          Op::Flt { dest, lhs, rhs } => compare::<f64>(value_store, dest, lhs, rhs, |a, b| a < b),
+          Op::Fgt { dest, lhs, rhs } => compare::<f64>(value_store, dest, lhs, rhs, |a, b| a > b),
+          Op::Fle { dest, lhs, rhs } => compare::<f64>(value_store, dest, lhs, rhs, |a, b| a <= b),
+          Op::Fge { dest, lhs, rhs } => compare::<f64>(value_store, dest, lhs, rhs, |a, b| a >= b),
+          Op::Ceq { dest, lhs, rhs } => compare::<char>(value_store, dest, lhs, rhs, |a, b| a == b),
+          Op::Clt { dest, lhs, rhs } => compare::<char>(value_store, dest, lhs, rhs, |a, b| a < b),
+          Op::Cgt { dest, lhs, rhs } => compare::<char>(value_store, dest, lhs, rhs, |a, b| a > b),
+          Op::Cle { dest, lhs, rhs } => compare::<char>(value_store, dest, lhs, rhs, |a, b| a <= b),
+          Op::Cge { dest, lhs, rhs } => compare::<char>(value_store, dest, lhs, rhs, |a, b| a >= b),
+          Op::Char2int { dest, arg } => {
+            let arg = value_store.get(&arg).as_char();
+            value_store.set(dest, Value::int(i64::from(u32::from(arg))));
+          }
+          Op::Int2char { dest, arg } => {
+            let arg = value_store.get(&arg).as_int();
+            let c = u32::try_from(arg)
+              .ok()
+              .and_then(char::from_u32)
+              .ok_or_else(|| InterpError::InvalidChar(arg).add_pos(pos()))?;
+            value_store.set(dest, Value::char(c));
+          }
+          // Both keep every bit as it is, so NaNs come out with the same payload they went in with
+          Op::Float2bits { dest, arg } => {
+            let arg = value_store.get(&arg).as_float();
+            value_store.set(dest, Value::int(arg.to_bits() as i64));
+          }
+          Op::Bits2float { dest, arg } => {
+            let arg = value_store.get(&arg).as_int();
+            value_store.set(dest, Value::float(f64::from_bits(arg as u64)));
+          }
+          Op::Load { dest, ptr } => {
+            let res = load(state, value_store.get(&ptr).as_pointer(), site());
+            value_store.set(dest, res.map_err(|e| e.add_pos(pos()))?);
+          }
+          Op::Store { ptr, arg } => {
+            store(state, func, value_store, ptr, arg, site(), counted)
+              .map_err(|e| e.add_pos(pos()))?;
+          }
+          Op::PtrAdd { dest, ptr, offset } => {
+            let ptr = value_store.get(&ptr).as_pointer();
+            let offset = value_store.get(&offset).as_int();
+            let res = if state.sanitize {
+              state.heap.offset_within(ptr, offset)
+            } else {
+              state.heap.offset(ptr, offset)
+            };
+            value_store.set(dest, Value::pointer(res.map_err(|e| e.add_pos(pos()))?));
+          }
+          Op::Jmp { target } => {
+            enter::<T, PLAIN>(state, func, at, target as usize);
+            continue 'blocks;
+          }
+          Op::Br {
+            cond,
+            then,
+            otherwise,
+          } => {
+            let target = if value_store.get(&cond).as_bool() {
+              then
+            } else {
+              otherwise
+            };
+            enter::<T, PLAIN>(state, func, at, target as usize);
+            continue 'blocks;
+          }
+          Op::Call => match call(
+            state,
+            func,
+            value_store,
+            speculation,
+            at,
+            steps,
+            end,
+            site(),
+          )? {
+            None => {}
+            Some(Exit::Call(callee_func, args, dest))
+              if PLAIN && depth.is_some() && running < max_call_depth =>
+            {
+              // The frame is pushed already in the block it starts at, and its arguments are put straight into it, so that neither is copied in after
+              frames.push(Frame {
+                at: Cursor {
+                  block: 0,
+                  instr: 0,
+                  current_label: callee_func.blocks[0].label_symbol,
+                  last_label: None,
+                },
+                ..Frame::new(callee_func, spare_envs.pop().unwrap_or_default(), dest)
+              });
+              let [.., caller, callee] = frames.as_mut_slice() else {
+                // This is safe because the caller is still running under the callee
+                unsafe { unreachable_unchecked() }
+              };
+              make_func_args(
+                callee_func,
+                args,
+                &caller.env,
+                &mut callee.env,
+                &mut state.heap,
+              );
+              continue 'calls;
+            }
+            Some(exit) => return Ok(exit),
+          },
+          Op::Ret => {
+            if !speculation.is_empty() {
+              return Err(
+                InterpError::NotAllowedDuringSpeculation(bril_rs::EffectOps::Return.to_string())
+                  .add_pos(pos()),
+              );
+            }
+            let result = func
+              .return_type
+              .as_ref()
+              .map(|_| get_value(value_store, 0, &curr_block.numified_instrs[instr_idx].args));
+            *steps += (end - at.instr) as u64;
+            if PLAIN && return_in_place(frames, spare_envs, depth, result) {
+              continue 'calls;
+            }
+            return Ok(Exit::Return(result));
+          }
+          Op::Nop => {}
+          Op::Other => {
+            match execute_other(
+              state,
+              func,
+              value_store,
+              speculation,
+              at,
+              steps,
+              end,
+              site(),
+            )? {
+              Flow::Next => {}
+              Flow::Enter(target) => {
+                enter::<T, PLAIN>(state, func, at, target);
+                continue 'blocks;
+              }
+              Flow::Exit(exit) => return Ok(exit),
+            }
+          }
+        }
+
+        if instrumented {
+          after_instruction(state, func, block_idx, instr_idx, value_store, traced)?;
+        }
+        if let Some((dest, old)) = overwritten {
+          state.reassign(value_store.lookup(dest as usize), old);
+        }
+      }
+
+      if end < curr_instrs.len() {
+        return Ok(Exit::Paused);
+      }
+      // Falling off the end of a block goes on to the next one, unless it ends the function
+      if curr_block.exit.len() == 1 {
+        enter::<T, PLAIN>(state, func, at, curr_block.exit[0]);
+      } else if !speculation.is_empty() {
+        return Err(InterpError::ImplicitReturnDuringSpeculation.add_pos(func.pos));
+      } else if PLAIN && return_in_place(frames, spare_envs, depth, None) {
+        continue 'calls;
+      } else {
+        return Ok(Exit::Return(None));
+      }
+    }
+  }
+}
+
+// Puts ```op``` of the ints in ```lhs``` and ```rhs``` into ```dest```, handling overflow and rounding division as the options say to
+#[inline(always)]
+fn int_op<T: Sink>(
+  state: &State<'_, T>,
+  op: bril_rs::ValueOps,
+  value_store: &mut Environment,
+  dest: u32,
+  lhs: u32,
+  rhs: u32,
+) -> Result<(), InterpError> {
+  let arg0 = value_store.get(&lhs).as_int();
+  let arg1 = value_store.get(&rhs).as_int();
+  let res = int_arith(state.overflow, state.div_semantics, op, arg0, arg1)?;
+  value_store.set(dest, Value::int(res));
+  Ok(())
+}
+
+#[inline(always)]
+fn float_op(
+  value_store: &mut Environment,
+  dest: u32,
+  lhs: u32,
+  rhs: u32,
+  op: impl Fn(f64, f64) -> f64,
+) {
+  let res = op(
+    value_store.get(&lhs).as_float(),
+    value_store.get(&rhs).as_float(),
+  );
+  value_store.set(dest, Value::float(res));
+}
+
+// Runs a comparison of ints which the `br` ending ```block``` goes by, giving back the block to go to if a plain run can take the branch straight away. That is when the `br` is one of the steps taken, which ```end``` says. Otherwise it is left to run on its own, so that the run can pause before it and anything looking at each instruction sees it
+#[inline(always)]
+fn compare_branch<const PLAIN: bool>(
+  block: &BasicBlock,
+  value_store: &mut Environment,
+  at: &Cursor,
+  end: usize,
+  dest: u32,
+  lhs: u32,
+  rhs: u32,
+  op: impl Fn(i64, i64) -> bool,
+) -> Option<usize> {
+  let res = op(
+    value_store.get(&lhs).as_int(),
+    value_store.get(&rhs).as_int(),
+  );
+  value_store.set(dest, Value::bool(res));
+  if !PLAIN || at.instr >= end {
+    return None;
+  }
+  // This is safe because the op is only compiled for a comparison just before a `br`
+  let Op::Br {
+    then, otherwise, ..
+  } = block.ops[at.instr]
+  else {
+    unsafe { unreachable_unchecked() }
+  };
+  Some(if res { then } else { otherwise } as usize)
+}
+
+// Puts whether ```lhs``` and ```rhs```, read as ```A```, satisfy ```op``` into ```dest```
+#[inline(always)]
+fn compare<A>(
+  value_store: &mut Environment,
+  dest: u32,
+  lhs: u32,
+  rhs: u32,
+  op: impl Fn(A, A) -> bool,
+) where
+  A: for<'v> From<&'v Value>,
+{
+  let res = op(
+    A::from(&value_store.get(&lhs)),
+    A::from(&value_store.get(&rhs)),
+  );
+  value_store.set(dest, Value::bool(res));
+}
+
+#[inline(always)]
+fn load<'a, T: Sink>(
+  state: &mut State<'a, T>,
+  ptr: Pointer,
+  site: Site<'a>,
+) -> Result<Value, InterpError> {
+  let res = state.heap.read(ptr).map_err(|e| match e {
+    InterpError::UsingUninitializedMemory if state.sanitize => state.heap.uninitialized_error(ptr),
+    e => e,
+  })?;
+  if let Some(races) = state.races.as_mut() {
+    races
+      .read(ptr.base, ptr.offset, site)
+      .map_err(|race| state.heap.race_error(ptr.base, &race))?;
+  }
+  Ok(res)
+}
+
+// Stores ```arg``` where ```ptr``` points, counting the pointers it puts there and overwrites when ```counting```
+#[inline(always)]
+fn store<'a, T: Sink>(
+  state: &mut State<'a, T>,
+  func: &BBFunction,
+  value_store: &Environment,
+  ptr: u32,
+  arg: u32,
+  site: Site<'a>,
+  counting: bool,
+) -> Result<(), InterpError> {
+  let key = value_store.get(&ptr).as_pointer();
+  let value = value_store.get(&arg);
+  let counted = counting && holds_pointer(func, arg);
+  let overwritten = if counted {
+    state.heap.read(key).ok()
+  } else {
+    None
+  };
+  state.heap.write(key, value)?;
+  if counted {
+    state.reassign(Some(value), overwritten);
+  }
+  if let Some(races) = state.races.as_mut() {
+    races
+      .write(key.base, key.offset, site)
+      .map_err(|race| state.heap.race_error(key.base, &race))?;
+  }
+  Ok(())
+}
+
+// Everything which looks at the instruction at ```instr_idx``` of block ```block_idx``` before it runs. An instruction which is being traced is only written out once it has run, so its event is given back to finish then
+#[inline(never)]
+fn before_instruction<'a, T: Sink>(
+  state: &mut State<'a, T>,
+  func: &'a BBFunction,
+  block_idx: usize,
+  instr_idx: usize,
+  value_store: &Environment,
+) -> Result<Option<Event<'a>>, PositionalInterpError> {
+  let block = &func.blocks[block_idx];
+  let code = &block.instrs[instr_idx];
+  let numified_code = &block.numified_instrs[instr_idx];
+  if let Some(debugger) = state.debugger.as_mut() {
+    debugger
+      .before_instruction(func, block_idx, instr_idx, value_store, &state.heap)
+      .map_err(|e| e.add_pos(code.get_pos()))?;
+  }
+  if let Some(gas) = state.gas.as_mut() {
+    gas.charge(code).map_err(|e| e.add_pos(code.get_pos()))?;
+  }
+  if let Some(coverage) = state.coverage.as_mut() {
+    coverage.record(func, block_idx, instr_idx);
+  }
+  if state.sanitize {
+    check_assigned(func, code, numified_code, value_store)
+      .map_err(|e| e.add_pos(code.get_pos()))?;
+  }
+  if let Some(hook) = state.hook.as_mut() {
+    hook.before_inst(&InstCtx::new(
+      func,
+      block.label.as_deref(),
+      code,
+      numified_code,
+      value_store,
+      &state.heap.strings,
+    ));
+  }
+  // Instructions which leave the block are traced before they run and the rest once their result is known
+  match state.instruction_trace.as_mut() {
+    Some(trace) => trace
+      .start(func, code, numified_code, value_store, &state.heap.strings)
+      .map_err(|e| e.add_pos(code.get_pos())),
+    None => Ok(None),
+  }
+}
+
+// Everything which looks at the instruction at ```instr_idx``` of block ```block_idx``` once it has run, other than for instructions which left the block
+#[inline(never)]
+fn after_instruction<T: Sink>(
+  state: &mut State<'_, T>,
+  func: &BBFunction,
+  block_idx: usize,
+  instr_idx: usize,
+  value_store: &Environment,
+  traced: Option<Event<'_>>,
+) -> Result<(), PositionalInterpError> {
+  let block = &func.blocks[block_idx];
+  let code = &block.instrs[instr_idx];
+  let numified_code = &block.numified_instrs[instr_idx];
+  if let (Some(event), Some(trace)) = (traced, state.instruction_trace.as_mut()) {
+    trace
+      .finish(
+        event,
+        func,
+        value_store,
+        &state.heap.strings,
+        numified_code.dest.unwrap(),
+      )
+      .map_err(|e| e.add_pos(code.get_pos()))?;
+  }
+  if let Some(hook) = state.hook.as_mut() {
+    hook.after_inst(&InstCtx::new(
+      func,
+      block.label.as_deref(),
+      code,
+      numified_code,
+      value_store,
+      &state.heap.strings,
+    ));
+  }
+  Ok(())
+}
+
+// Runs the call before ```at```, giving back how to leave the block for a call to a function of the program. Calls to intrinsics run in place
+#[inline(always)]
+fn call<'a, T: Sink>(
+  state: &mut State<'a, T>,
+  func: &'a BBFunction,
+  value_store: &mut Environment,
+  speculation: &[Environment],
+  at: &Cursor,
+  steps: &mut u64,
+  end: usize,
+  site: Site<'a>,
+) -> Result<Option<Exit<'a>>, PositionalInterpError> {
+  let curr_block = &func.blocks[at.block];
+  let curr_instrs = &curr_block.instrs;
+  let curr_numified_instrs = &curr_block.numified_instrs;
+  let numified_code = &curr_numified_instrs[site.instr];
+  // This is safe because only calls have an Op::Call
+  let (Instruction::Value { funcs, pos, .. } | Instruction::Effect { funcs, pos, .. }) =
+    &curr_instrs[site.instr]
+  else {
+    unsafe { unreachable_unchecked() }
+  };
+  if !speculation.is_empty() {
+    return Err(
+      InterpError::NotAllowedDuringSpeculation(bril_rs::EffectOps::Call.to_string()).add_pos(*pos),
+    );
+  }
+  match numified_code.func.and_then(|f| state.prog.function(f)) {
+    Some(callee_func) => {
+      // The `ret` after a tail call is counted as having run, so it has to be one of the steps already taken
+      if state.tco
+        && at.instr < end
+        && matches!(curr_block.ops[at.instr], Op::Ret)
+        && curr_numified_instrs[at.instr].args == numified_code.dest.as_slice()
+        && (numified_code.dest.is_some() || callee_func.return_type.is_none())
+      {
+        *steps += (end - at.instr - 1) as u64;
+        if let Some(gas) = state.gas.as_mut() {
+          let ret = &curr_instrs[at.instr];
+          gas.charge(ret).map_err(|e| e.add_pos(ret.get_pos()))?;
+        }
+        if let Some(coverage) = state.coverage.as_mut() {
+          coverage.record(func, site.block, at.instr);
+        }
+        return Ok(Some(Exit::TailCall(callee_func, &numified_code.args)));
+      }
+      *steps += (end - at.instr) as u64;
+      return Ok(Some(Exit::Call(
+        callee_func,
+        &numified_code.args,
+        numified_code.dest,
+      )));
+    }
+    // Calls to a function which the program doesn't define go to the intrinsic of that name, which runs in place like any other instruction
+    None => {
+      let intrinsic = numified_code
+        .func
+        .and_then(|f| state.prog.intrinsic(f))
+        .ok_or_else(|| InterpError::FuncNotFound(funcs[0].clone()).add_pos(*pos))?;
+      call_intrinsic(state, intrinsic, &funcs[0], numified_code, value_store)
+        .map_err(|e| e.add_pos(*pos))?;
+    }
+  }
+  Ok(None)
+}
+
+// Runs the instruction before ```at```, which has no Op of its own. ```end``` is where the steps taken for its block run out
+#[inline(never)]
+fn execute_other<'a, T: Sink>(
+  state: &mut State<'a, T>,
+  func: &'a BBFunction,
+  value_store: &mut Environment,
+  speculation: &mut Vec<Environment>,
+  at: &Cursor,
+  steps: &mut u64,
+  end: usize,
+  site: Site<'a>,
+) -> Result<Flow<'a>, PositionalInterpError> {
+  let curr_block = &func.blocks[at.block];
+  let curr_instrs = &curr_block.instrs;
+  let curr_numified_instrs = &curr_block.numified_instrs;
+  let code = &curr_instrs[site.instr];
+  let numified_code = &curr_numified_instrs[site.instr];
+  match code {
+    Instruction::Constant {
+      op: bril_rs::ConstOps::Const,
+      dest: _,
+      const_type,
+      value,
+      pos: _,
+      attrs: _,
+    } => {
+      value_store.set(
+        numified_code.dest.unwrap(),
+        Value::constant(value, const_type, &mut state.heap.strings),
+      );
+    }
+    Instruction::Value {
+      op: bril_rs::ValueOps::Spawn,
+      funcs,
+      pos,
+      ..
+    } => {
+      if !speculation.is_empty() {
+        return Err(
+          InterpError::NotAllowedDuringSpeculation(bril_rs::ValueOps::Spawn.to_string())
+            .add_pos(*pos),
+        );
+      }
+      let callee_func = numified_code
+        .func
+        .and_then(|f| state.prog.function(f))
+        .ok_or_else(|| InterpError::FuncNotFound(funcs[0].clone()).add_pos(*pos))?;
+      *steps += (end - at.instr) as u64;
+      return Ok(Flow::Exit(Exit::Spawn(
+        callee_func,
+        &numified_code.args,
+        numified_code.dest.unwrap(),
+        *pos,
+      )));
+    }
+    Instruction::Value {
+      op,
+      dest: _,
+      op_type: _,
+      args: _,
+      labels: _,
+      funcs: _,
+      pos,
+      extra_dests: _,
+      attrs: _,
+    } => {
+      execute_value_op(
+        state,
+        op,
+        numified_code.dest.unwrap(),
+        &numified_code.args,
+        &numified_code.labels,
+        value_store,
+        at.last_label,
+        site,
+      )
+      .map_err(|e| e.add_pos(*pos))?;
+    }
+    Instruction::Effect {
+      op: bril_rs::EffectOps::Join,
+      pos,
+      ..
+    } => {
+      if !speculation.is_empty() {
+        return Err(
+          InterpError::NotAllowedDuringSpeculation(bril_rs::EffectOps::Join.to_string())
+            .add_pos(*pos),
+        );
+      }
+      let thread = get_value(value_store, 0, &numified_code.args).as_thread();
+      *steps += (end - at.instr) as u64;
+      return Ok(Flow::Exit(Exit::Join(thread, *pos)));
+    }
+    Instruction::Effect {
+      op: bril_rs::EffectOps::Speculate,
+      ..
+    } => {
+      state.retain_vars(func, value_store);
+      speculation.push(value_store.clone());
+    }
+    Instruction::Effect {
+      op: bril_rs::EffectOps::Commit,
+      pos,
+      ..
+    } => {
+      let Some(started) = speculation.pop() else {
+        return Err(InterpError::CommitOutsideSpeculation.add_pos(*pos));
+      };
+      state.release_vars(func, &started);
+    }
+    Instruction::Effect {
+      op: bril_rs::EffectOps::Guard,
+      pos,
+      ..
+    } => {
+      if state.no_speculation || !get_arg::<bool>(value_store, 0, &numified_code.args) {
+        // Everything but the variables stays the way the speculation left it
+        let started = speculation
+          .pop()
+          .ok_or_else(|| InterpError::AbortOutsideSpeculation.add_pos(*pos))?;
+        state.release_vars(func, value_store);
+        *value_store = started;
+        // This is safe because the type checker makes sure that every guard has a label, which was found when the program was converted
+        let target = numified_code
+          .target
+          .unwrap_or_else(|| unsafe { unreachable_unchecked() });
+        *steps += (end - at.instr) as u64;
+        return Ok(Flow::Enter(target));
+      }
+    }
+    Instruction::Effect {
+      op,
+      args: _,
+      labels: _,
+      funcs: _,
+      pos,
+      attrs: _,
+    } => {
+      execute_effect_op(state, func, op, &numified_code.args, value_store, site)
+        .map_err(|e| e.add_pos(*pos))?;
+    }
+  }
+  Ok(Flow::Next)
+}
+
+fn parse_args(
+  mut env: Environment,
+  heap: &mut Heap,
+  func: &BBFunction,
+  inputs: &[String],
 ) -> Result<Environment, InterpError> {
+  let (args, args_as_nums) = (&func.args, &func.args_as_nums);
   if args.is_empty() && inputs.is_empty() {
     Ok(env)
   } else if inputs.len() != args.len() {
@@ -614,91 +3313,302 @@ fn parse_args(
       .iter()
       .zip(args_as_nums.iter())
       .enumerate()
-      .try_for_each(|(index, (arg, arg_as_num))| match arg.arg_type {
-        bril_rs::Type::Bool => {
-          match inputs.get(index).unwrap().parse::<bool>() {
-            Err(_) => {
-              return Err(InterpError::BadFuncArgType(
-                bril_rs::Type::Bool,
-                (*inputs.get(index).unwrap()).to_string(),
-              ))
-            }
-            Ok(b) => env.set(*arg_as_num, Value::Bool(b)),
-          };
-          Ok(())
-        }
-        bril_rs::Type::Int => {
-          match inputs.get(index).unwrap().parse::<i64>() {
-            Err(_) => {
-              return Err(InterpError::BadFuncArgType(
-                bril_rs::Type::Int,
-                (*inputs.get(index).unwrap()).to_string(),
-              ))
-            }
-            Ok(i) => env.set(*arg_as_num, Value::Int(i)),
-          };
+      .try_for_each(|(index, (arg, arg_as_num))| match &arg.arg_type {
+        // Pointers are given as JSON, which is allocated on the heap before the function starts
+        bril_rs::Type::Pointer(ty) => {
+          let input = inputs.get(index).unwrap();
+          let json = serde_json::from_str(input)
+            .map_err(|_| InterpError::BadFuncArgType(arg.arg_type.clone(), input.clone()))?;
+          let origin = format!("argument `{}` of @{}", arg.name, func.name);
+          env.set(*arg_as_num, heap.alloc_argument(&json, ty, &origin)?);
           Ok(())
         }
-        bril_rs::Type::Float => {
-          match inputs.get(index).unwrap().parse::<f64>() {
-            Err(_) => {
-              return Err(InterpError::BadFuncArgType(
-                bril_rs::Type::Float,
-                (*inputs.get(index).unwrap()).to_string(),
-              ))
-            }
-            Ok(f) => env.set(*arg_as_num, Value::Float(f)),
-          };
+        bril_rs::Type::Tuple(_) => Err(InterpError::TupleVariable(
+          arg.name.clone(),
+          arg.arg_type.clone(),
+        )),
+        // Everything else is parsed the way brili parses it, except threads, which only come from `spawn` and so never parse
+        ty => {
+          let value = RuntimeValue::parse(ty, inputs.get(index).unwrap())?;
+          env.set(*arg_as_num, value.value(&mut heap.strings));
           Ok(())
         }
-        // this is safe because there is no possible way to pass a pointer as an argument
-        bril_rs::Type::Pointer(..) => unsafe { unreachable_unchecked() },
       })?;
     Ok(env)
   }
 }
 
-/// The entrance point to the interpreter. It runs over a ```prog```:[`BBProgram`] starting at the "main" function with ```input_args``` as input. Print statements output to ```out``` which implements [std::io::Write]. You also need to include whether you want the interpreter to count the number of instructions run with ```profiling```. This information is outputted to [std::io::stderr]
+// The value of ```json```, which is an element of an argument given to main as JSON, as a value of ```ty``` with any string interned in ```strings```
+fn scalar_argument(
+  json: &serde_json::Value,
+  ty: &Type,
+  strings: &mut Strings,
+) -> Result<Value, InterpError> {
+  let value = match (ty, json) {
+    (Type::Int, serde_json::Value::Number(n)) => n.as_i64().map(Value::int),
+    (Type::Bool, serde_json::Value::Bool(b)) => Some(Value::bool(*b)),
+    (Type::Float, serde_json::Value::Number(n)) => n.as_f64().map(Value::float),
+    (Type::Char, serde_json::Value::String(s)) => {
+      let mut chars = s.chars();
+      match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(Value::char(c)),
+        _ => None,
+      }
+    }
+    (Type::Str, serde_json::Value::String(s)) => Some(strings.intern(s)),
+    _ => None,
+  };
+  value.ok_or_else(|| InterpError::BadFuncArgType(ty.clone(), json.to_string()))
+}
+
+/// The arguments for the "main" function of ```prog``` given by ```json```, in the form that [`Interpreter::start_main`] takes them. This is either an array of the arguments in order or an object of them by name, where `int`, `bool`, and `float` arguments are JSON numbers and booleans, `char` arguments are strings of one character, `str` arguments are strings, and pointer arguments are arrays of their values. A `ptr<char>` can also be a string, and arrays of pointers are arrays of arrays.
+pub fn main_args_from_json(prog: &BBProgram, json: &str) -> Result<Vec<String>, InterpError> {
+  let main_func = prog.get("main").ok_or(InterpError::NoMainFunction)?;
+  args_from_json(main_func, json)
+}
+
+/// The arguments for the function ```name``` of ```prog``` given by ```json```, in the form that [`Interpreter::start_entry`] takes them, which are written the same way as for [`main_args_from_json`]
+pub fn entry_args_from_json(
+  prog: &BBProgram,
+  name: &str,
+  json: &str,
+) -> Result<Vec<String>, InterpError> {
+  let func = prog
+    .get(name)
+    .ok_or_else(|| InterpError::FuncNotFound(name.to_string()))?;
+  args_from_json(func, json)
+}
+
+fn args_from_json(main_func: &BBFunction, json: &str) -> Result<Vec<String>, InterpError> {
+  let json: serde_json::Value =
+    serde_json::from_str(json).map_err(|e| InterpError::InvalidArgs(e.to_string()))?;
+  let values: Vec<_> = match json {
+    serde_json::Value::Array(values) => values,
+    serde_json::Value::Object(mut values) => {
+      let ordered = main_func
+        .args
+        .iter()
+        .map(|arg| {
+          values
+            .remove(&arg.name)
+            .ok_or_else(|| InterpError::InvalidArgs(format!("missing argument `{}`", arg.name)))
+        })
+        .collect::<Result<_, _>>()?;
+      if let Some(name) = values.keys().next() {
+        return Err(InterpError::InvalidArgs(format!(
+          "@{} has no argument `{name}`",
+          main_func.name
+        )));
+      }
+      ordered
+    }
+    _ => {
+      return Err(InterpError::InvalidArgs(
+        "expected an array or an object".to_string(),
+      ))
+    }
+  };
+  // Scalars are written the way they would be on the command line, and pointers stay JSON
+  Ok(
+    values
+      .into_iter()
+      .enumerate()
+      .map(|(index, value)| match (value, main_func.args.get(index)) {
+        (serde_json::Value::String(s), Some(arg))
+          if matches!(arg.arg_type, Type::Char | Type::Str) =>
+        {
+          s
+        }
+        (value, _) => value.to_string(),
+      })
+      .collect(),
+  )
+}
+
+/// The optional behaviours of the interpreter for [`execute_main_with_options`]
+#[derive(Default)]
+pub struct Options {
+  /// Count the number of instructions run and output it to [std::io::stderr]
+  pub profiling: bool,
+  /// The number of values to reserve room for on the heap before the program starts
+  pub heap_size: usize,
+  /// Pause at the first instruction and then at every breakpoint so that the debugger can inspect the state of the program
+  pub debugger: Option<Debugger>,
+  /// Record a [`Trace`] from every labelled block the first time it is entered this many times. Recording stops when the path loops back around, reaches a call, return, or side-effecting instruction, or gets too long.
+  pub trace_threshold: Option<u32>,
+  /// Write out every instruction that runs, along with the values it used and produced
+  pub instruction_trace: Option<InstructionTrace>,
+  /// Count how often every basic block is executed and summarize it as a [`Profile`]
+  pub collect_profile: bool,
+  /// Count how often every instruction is executed and summarize it as a [`Coverage`]
+  pub collect_coverage: bool,
+  /// Count the instructions run, the deepest the call stack gets, the most memory allocated at once, and how long the program takes, and summarize them as [`ExecutionStats`]
+  pub collect_stats: bool,
+  /// Track how much memory is allocated over the run, the most allocated at once, and how much each `alloc` and call stack allocates, and summarize it as a [`MemoryProfile`]
+  pub collect_memory_profile: bool,
+  /// How integer arithmetic handles results which don't fit in an `int`
+  pub overflow: Overflow,
+  /// How `div` rounds, and so what `rem` leaves over, when an operand is negative
+  pub div_semantics: DivSemantics,
+  /// Make every `guard` fail as though its condition were false, so that the program always falls back to the code for when speculation goes wrong
+  pub no_speculation: bool,
+  /// List every allocation which is never freed on [std::io::stderr] when the program finishes, instead of only reporting how many there are and where the first was made
+  pub check_leaks: bool,
+  /// Count the pointers to every allocation, in variables and on the heap, and free it as soon as the last one is overwritten or the call whose variable held it returns. How much was freed this way is output to [std::io::stderr] as `collected_bytes` when the program finishes and is in [`ExecutionStats::collected_bytes`]. Bril's types can't describe an allocation which points back to itself, so there are no cycles to keep memory alive. Pointers handed out of the interpreter, like one returned by [`Interpreter::call_function`], keep their allocations from ever being collected, as do the allocations of a [`Memory`] given to [`Interpreter::with_memory`]
+  pub rc_heap: bool,
+  /// Run a call which is immediately followed by returning its result in place of the function making it, so that recursion in tail position doesn't grow the call stack. The debugger's backtrace leaves out the functions which were replaced
+  pub tco: bool,
+  /// Check every load, `ptradd`, and use of a variable for the mistakes that the interpreter otherwise lets through or only reports vaguely: loads from memory which was never stored to say which allocation and offset they were from, `ptradd` can't move a pointer outside of its allocation (except to just past the end) or use one into memory which has been freed, and no variable can be used before it has been assigned. This makes programs run a few times slower
+  pub sanitize: bool,
+  /// The most calls which can be running at once, counting `main`. A call which would go past this fails with [`InterpError::StackOverflow`] instead of growing the call stack until memory runs out. [`None`] means there is no limit
+  pub max_call_depth: Option<usize>,
+  /// The most instructions which can run, so that a program which loops forever fails with [`InterpError::ResourceLimit`] instead of hanging. [`None`] means there is no limit
+  pub max_instructions: Option<u64>,
+  /// How long the program can run for, counted from when the [`Interpreter`] is made, after which it fails with [`InterpError::ResourceLimit`]. The clock is only looked at every so many instructions, so the program can run for a little longer than this. [`None`] means there is no limit
+  pub timeout: Option<std::time::Duration>,
+  /// What each operation costs for counting the gas the program uses, which is reported by [`Interpreter::gas_used`], with `total_gas` when [`Options::profiling`] is set, and in the [`Profile`]. Gas is counted when this or [`Options::gas`] is set, with [`CostModel::default`] if only the limit is
+  pub cost_model: Option<CostModel>,
+  /// The most gas the program can use, after which it fails with [`InterpError::ResourceLimit`] instead of running the instruction which would go past it. [`None`] means there is no limit
+  pub gas: Option<u64>,
+  /// How threads take turns running for programs using the concurrency extension
+  pub schedule: Schedule,
+  /// Where the numbers `rand` gives and the picks of [`Schedule::Random`] start
+  pub seed: u64,
+  /// How many instructions a thread runs before another gets a turn, which is 100 if this isn't set
+  pub quantum: Option<std::num::NonZeroU64>,
+  /// Stop the program with [`InterpError::DataRace`] when two threads access the same value of the heap, and at least one of them changes it, without either having joined the other in between
+  pub detect_races: bool,
+  /// Call the methods of an [`InterpHook`] before and after every instruction and call, for analyses which need to watch the program as it runs
+  pub hook: Option<Box<dyn InterpHook>>,
+}
+
+/// What was recorded while running a program with [`execute_main_with_options`]
+#[derive(Debug, Default)]
+pub struct Report {
+  /// The traces recorded if [`Options::trace_threshold`] was set
+  pub traces: Vec<Trace>,
+  /// The profile of the run if [`Options::collect_profile`] was set
+  pub profile: Option<Profile>,
+  /// The coverage of the run if [`Options::collect_coverage`] was set
+  pub coverage: Option<Coverage>,
+  /// The summary of the run if [`Options::collect_stats`] was set
+  pub stats: Option<ExecutionStats>,
+  /// How the run used the heap if [`Options::collect_memory_profile`] was set
+  pub memory_profile: Option<MemoryProfile>,
+  /// What the function run with [`execute_function_with_options`] returned, if it returns anything
+  pub returned: Option<RuntimeValue>,
+}
+
+/// The entrance point to the interpreter. It runs over a ```prog```:[`BBProgram`] starting at the "main" function with ```input_args``` as input. Print statements output to ```out```, a [`Sink`] such as anything which implements [std::io::Write]. You also need to include whether you want the interpreter to count the number of instructions run with ```profiling```. This information is outputted to [std::io::stderr]
 // todo we could probably output the profiling thing to a user defined location. If the program can output to a file, you should probably also be allowed to output this debug info to a file as well.
-pub fn execute_main<T: std::io::Write>(
+pub fn execute_main<T: Sink>(
   prog: &BBProgram,
-  mut out: T,
+  out: T,
   input_args: &[String],
   profiling: bool,
 ) -> Result<(), PositionalInterpError> {
-  let main_func = prog
-    .get("main")
-    .ok_or_else(|| PositionalInterpError::new(InterpError::NoMainFunction))?;
-
-  if main_func.return_type.is_some() {
-    return Err(InterpError::NonEmptyRetForFunc(main_func.name.clone()))
-      .map_err(|e| e.add_pos(main_func.pos));
-  }
-
-  let env = Environment::new(main_func.num_of_vars);
-  let mut heap = Heap::default();
+  execute_main_with_options(
+    prog,
+    out,
+    input_args,
+    Options {
+      profiling,
+      ..Options::default()
+    },
+  )
+  .map(|_| ())
+}
 
-  let value_store = parse_args(env, &main_func.args, &main_func.args_as_nums, input_args)
-    .map_err(|e| e.add_pos(main_func.pos))?;
+/// The same as [`execute_main`] but configured with ```options```
+pub fn execute_main_with_options<T: Sink>(
+  prog: &BBProgram,
+  out: T,
+  input_args: &[String],
+  options: Options,
+) -> Result<Report, PositionalInterpError> {
+  let mut interpreter = Interpreter::new(prog, out, options);
+  interpreter.start_main(input_args)?;
+  interpreter.run_to_completion()?;
+  interpreter.finish()
+}
 
-  let mut instruction_count = 0;
+/// The same as [`execute_main_with_options`] but starting at the function ```name``` instead of "main", with what it returns in [`Report::returned`]
+///
+/// This is how a single function of a program, or of a library without a main function, is tested on its own.
+///
+/// ```
+/// use brilirs::basic_block::BBProgram;
+/// use brilirs::interp::{self, RuntimeValue};
+///
+/// let prog = bril2json::parse_abstract_program_from_str(
+///   "@gcd(a: int, b: int): int {
+///      zero: int = const 0; done: bool = eq b zero; br done .base .step;
+///    .base: ret a;
+///    .step: r: int = call @mod a b; g: int = call @gcd b r; ret g; }
+///    @mod(a: int, b: int): int { q: int = div a b; m: int = mul q b; r: int = sub a m; ret r; }",
+///   false,
+/// )
+/// .unwrap();
+/// let prog = BBProgram::try_from(bril_rs::Program::try_from(prog).unwrap()).unwrap();
+///
+/// let args = ["12".to_string(), "18".to_string()];
+/// let report = interp::execute_function_with_options(&prog, std::io::sink(), "gcd", &args, interp::Options::default()).unwrap();
+/// assert_eq!(report.returned, Some(RuntimeValue::Int(6)));
+/// ```
+pub fn execute_function_with_options<T: Sink>(
+  prog: &BBProgram,
+  out: T,
+  name: &str,
+  input_args: &[String],
+  options: Options,
+) -> Result<Report, PositionalInterpError> {
+  let mut interpreter = Interpreter::new(prog, out, options);
+  interpreter.start_entry(name, input_args)?;
+  let returned = interpreter.run_to_completion()?;
+  Ok(Report {
+    returned,
+    ..interpreter.finish()?
+  })
+}
 
-  execute(
+/// The same as [`execute_main`] but returns how the run went as [`ExecutionStats`] instead of printing the number of instructions to [std::io::stderr].
+///
+/// Set [`Options::collect_stats`] with [`execute_main_with_options`] to get them along with anything else it can record
+///
+/// ```
+/// use brilirs::basic_block::BBProgram;
+/// use brilirs::interp::execute_main_with_stats;
+///
+/// let prog = bril2json::parse_abstract_program_from_str(
+///   "@main { n: int = const 4; p: ptr<int> = alloc n; x: int = call @double n; print x; free p; }
+///    @double(n: int): int { y: int = add n n; ret y; }",
+///   false,
+/// )
+/// .unwrap();
+/// let prog = BBProgram::try_from(bril_rs::Program::try_from(prog).unwrap()).unwrap();
+///
+/// let mut out = Vec::new();
+/// let stats = execute_main_with_stats(&prog, &mut out, &[]).unwrap();
+/// assert_eq!(out, b"8\n");
+/// assert_eq!(stats.total_dyn_inst, 7);
+/// assert_eq!(stats.opcodes["add"], 1);
+/// assert_eq!(stats.max_call_depth, 2);
+/// assert_eq!(stats.peak_heap_bytes, 32);
+/// ```
+pub fn execute_main_with_stats<T: Sink>(
+  prog: &BBProgram,
+  out: T,
+  input_args: &[String],
+) -> Result<ExecutionStats, PositionalInterpError> {
+  let report = execute_main_with_options(
     prog,
-    main_func,
-    &mut out,
-    value_store,
-    &mut heap,
-    &mut instruction_count,
+    out,
+    input_args,
+    Options {
+      collect_stats: true,
+      ..Options::default()
+    },
   )?;
-
-  if !heap.is_empty() {
-    return Err(InterpError::MemLeak).map_err(|e| e.add_pos(main_func.pos));
-  }
-
-  if profiling {
-    eprintln!("total_dyn_inst: {instruction_count}");
-  }
-
-  Ok(())
+  // There are always stats since collect_stats was set
+  Ok(report.stats.unwrap_or_default())
 }
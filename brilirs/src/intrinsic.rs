@@ -0,0 +1,78 @@
+use std::fmt;
+use std::sync::Arc;
+
+use bril_rs::Type;
+
+use crate::interp::RuntimeValue;
+
+/// What an [`Intrinsic`] gives back: the value of the call if it has a return type, or an error which stops the program
+pub type IntrinsicResult = Result<Option<RuntimeValue>, Box<dyn std::error::Error + Send + Sync>>;
+
+// What an intrinsic runs when it is called
+type IntrinsicFn = dyn Fn(&[RuntimeValue]) -> IntrinsicResult + Send + Sync;
+
+/// A function written in Rust which a program can call like one of its own, for things that Bril can't do by itself like `sqrt` or `rand`.
+///
+/// Intrinsics are added to a program by name with [`crate::basic_block::BBProgram::add_intrinsic`], and are only called when the program doesn't define a function of the same name. The type checker checks calls to them against the types they are declared with, and they are called with the values of their arguments in order. An intrinsic can be called from more than one thread at once when runs are batched, so any state it keeps has to be behind something like a [`std::sync::Mutex`].
+/// ```
+/// use brilirs::basic_block::BBProgram;
+/// use brilirs::interp::{self, RuntimeValue};
+/// use brilirs::intrinsic::Intrinsic;
+/// use bril_rs::Type;
+///
+/// let prog = bril2json::parse_abstract_program_from_str("@main { x: float = const 2; r: float = call @sqrt x; print r; }", false).unwrap();
+/// let mut bbprog = BBProgram::new(prog.try_into().unwrap()).unwrap();
+/// bbprog.add_intrinsic("sqrt", Intrinsic::new(vec![Type::Float], Some(Type::Float), |args| match args {
+///   [RuntimeValue::Float(x)] => Ok(Some(RuntimeValue::Float(x.sqrt()))),
+///   _ => unreachable!("calls are type checked"),
+/// }));
+/// brilirs::check::type_check(&bbprog).unwrap();
+/// let mut out = Vec::new();
+/// interp::execute_main(&bbprog, &mut out, &[], false).unwrap();
+/// assert_eq!(String::from_utf8(out).unwrap(), "1.4142135623730951\n");
+/// ```
+#[derive(Clone)]
+pub struct Intrinsic {
+  args: Vec<Type>,
+  return_type: Option<Type>,
+  func: Arc<IntrinsicFn>,
+}
+
+impl Intrinsic {
+  /// An intrinsic taking arguments of the types ```args``` and returning a value of ```return_type```, if any, which runs ```func``` when it is called
+  pub fn new(
+    args: Vec<Type>,
+    return_type: Option<Type>,
+    func: impl Fn(&[RuntimeValue]) -> IntrinsicResult + Send + Sync + 'static,
+  ) -> Self {
+    Self {
+      args,
+      return_type,
+      func: Arc::new(func),
+    }
+  }
+
+  /// The types of the arguments the intrinsic takes
+  pub fn args(&self) -> &[Type] {
+    &self.args
+  }
+
+  /// The type of the value the intrinsic returns, if it returns one
+  pub const fn return_type(&self) -> Option<&Type> {
+    self.return_type.as_ref()
+  }
+
+  // Runs the intrinsic on ```args```, which have the types it was declared with
+  pub(crate) fn call(&self, args: &[RuntimeValue]) -> IntrinsicResult {
+    (self.func)(args)
+  }
+}
+
+impl fmt::Debug for Intrinsic {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Intrinsic")
+      .field("args", &self.args)
+      .field("return_type", &self.return_type)
+      .finish_non_exhaustive()
+  }
+}
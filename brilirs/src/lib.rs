@@ -3,45 +3,775 @@
 #![warn(missing_docs)]
 #![doc = include_str!("../README.md")]
 #![allow(clippy::too_many_arguments)]
+// Duplicate transitive dependencies (e.g. `syn`) are outside of this crate's control
+#![allow(clippy::multiple_crate_versions)]
 
 use std::error::Error;
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use basic_block::BBProgram;
+use bril_rs::link::Linker;
+use bril_rs::stream::StreamError;
 use bril_rs::Program;
+use error::{InterpError, PositionalInterpError};
+use sink::Sink;
 
 /// The internal representation of brilirs, provided a ```TryFrom<Program>``` conversion
 pub mod basic_block;
+/// Provides ```batch::run_batch``` to run the main function of a [BBProgram] with many sets of arguments at once
+pub mod batch;
 /// Provides ```check::type_check``` to validate [Program]
 pub mod check;
+/// Provides ```checkpoint::Checkpoint```, the state of a program part of the way through running which ```interp::Interpreter::resume``` can carry on from
+pub mod checkpoint;
 #[doc(hidden)]
 pub mod cli;
-mod error;
-/// Provides ```interp::execute_main``` to execute [Program] that have been converted into [BBProgram]
+/// Provides ```coverage::Coverage```, which instructions were executed by ```interp::execute_main_with_options``` and how many times
+pub mod coverage;
+/// Provides ```debug::Debugger``` for interactively stepping through a [Program] with ```interp::execute_main_with_options```
+pub mod debug;
+/// Provides ```error::PositionalInterpError```, the error returned by ```run_input``` along with where in the program it occurred
+pub mod error;
+/// Provides ```gas::CostModel```, what each operation costs for counting the gas a program uses with ```interp::execute_main_with_options```
+pub mod gas;
+/// Provides ```hook::InterpHook``` for running code of your own before and after every instruction and call of ```interp::execute_main_with_options```
+pub mod hook;
+/// Provides ```instruction_trace::InstructionTrace```, which writes out every instruction run by ```interp::execute_main_with_options``` as JSON
+pub mod instruction_trace;
+/// Provides ```interp::execute_main``` to execute [Program] that have been converted into [BBProgram], and ```interp::Interpreter``` to run them step by step
 pub mod interp;
+/// Provides ```intrinsic::Intrinsic```, a function written in Rust which a [BBProgram] can call as though it were one of its own
+pub mod intrinsic;
+// Instructions compiled ahead of time into what the interpreter runs
+mod op;
+/// Provides the stages which ```run_input``` puts a program through, each of which can be called on its own so that stages of your own, like instrumentation or a transform, can go between them
+///
+/// A program is ```pipeline::load```ed into a [Program], has its imports ```pipeline::link```ed, is ```pipeline::lower```ed into a [BBProgram], is ```pipeline::check```ed, and is ```pipeline::execute```d
+/// ```
+/// use brilirs::interp::Options;
+/// use brilirs::pipeline::{self, Format};
+///
+/// let text = "@main { a: int = const 2; b: int = const 3; c: int = mul a b; print c; }";
+/// let mut prog = pipeline::load(text.as_bytes(), Format::Text, false).unwrap();
+///
+/// // A stage of our own, which folds the constants before the program ever runs
+/// bril_opt::optimize(&mut prog, &[bril_opt::Pass::Fold, bril_opt::Pass::Dce]).unwrap();
+///
+/// let bbprog = pipeline::lower(prog).unwrap();
+/// assert!(pipeline::check(&bbprog).is_empty());
+/// let mut out = Vec::new();
+/// let options = Options {
+///   collect_stats: true,
+///   ..Options::default()
+/// };
+/// let report = pipeline::execute(&bbprog, &mut out, &[], options).unwrap();
+/// assert_eq!(String::from_utf8(out).unwrap(), "6\n");
+/// assert_eq!(report.stats.unwrap().total_dyn_inst, 2);
+/// ```
+pub mod pipeline;
+/// Provides ```profile::Profile```, ```profile::ExecutionStats```, and ```profile::MemoryProfile```, the execution counts and heap usage collected by ```interp::execute_main_with_options```
+pub mod profile;
+/// Provides ```recording::Recording```, the arguments and intrinsic results of a run which ```recording::Recording::replay``` can make the same run again from
+pub mod recording;
+// The random numbers behind `rand` and --schedule random
+mod rng;
+/// Provides ```sink::Sink```, where the interpreter sends the values of each `print`, either as text or to a callback
+pub mod sink;
+/// Provides ```symbol::SymbolTable```, which numbers the names of functions, variables, and labels in a [BBProgram]
+pub mod symbol;
+/// Provides ```trace::Trace```, the hot paths recorded by ```interp::execute_main_with_options```
+pub mod trace;
+// How threads take turns running and how races between them are found
+mod thread;
+// The log of every change a program makes which lets the debugger go back to earlier steps with `--time-travel`
+mod time_travel;
+// How values are represented while a program runs
+mod value;
+/// Provides ```wasm::run_program``` for running [Program] from JavaScript when compiled to WebAssembly
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 #[doc(hidden)]
 pub fn run_input<T: std::io::Write>(
-  input: Box<dyn std::io::Read>,
+  mut input: Box<dyn std::io::Read>,
   out: T,
   input_args: Vec<String>,
+  args_file: Option<&Path>,
+  batch: Option<&Path>,
+  entry: Option<&str>,
   profiling: bool,
   check: bool,
-  text: bool,
-) -> Result<(), Box<dyn Error>> {
+  strict: bool,
+  format: cli::InputFormat,
+  infer: bool,
+  debug: bool,
+  time_travel: bool,
+  trace_threshold: Option<u32>,
+  instruction_trace: Option<instruction_trace::InstructionTrace>,
+  heap_size: usize,
+  profile_out: Option<String>,
+  coverage_out: Option<&Path>,
+  annotate_out: Option<&Path>,
+  memory_profile_out: Option<&Path>,
+  overflow: cli::Overflow,
+  div_semantics: cli::DivSemantics,
+  no_speculation: bool,
+  check_leaks: bool,
+  rc_heap: bool,
+  tco: bool,
+  sanitize: bool,
+  max_call_depth: usize,
+  max_instructions: Option<u64>,
+  timeout: Option<Duration>,
+  gas: Option<u64>,
+  cost_model: Option<&Path>,
+  schedule: cli::Schedule,
+  seed: u64,
+  quantum: Option<NonZeroU64>,
+  detect_races: bool,
+  passes: &[String],
+  emit: Option<&Path>,
+  dump_cfg: Option<cli::CfgFormat>,
+  output_format: cli::OutputFormat,
+  color: bool,
+  checkpoint_every: Option<NonZeroU64>,
+  checkpoint_file: &Path,
+  resume: Option<&Path>,
+  record: Option<&Path>,
+  replay: Option<&Path>,
+  lib_paths: Vec<PathBuf>,
+  source_dir: &Path,
+) -> Result<(), PositionalInterpError> {
+  let debugger = debug.then(|| {
+    let debugger = debug::Debugger::new(
+      Box::new(std::io::BufReader::new(std::io::stdin())),
+      Box::new(std::io::stderr()),
+    );
+    if time_travel {
+      debugger.with_time_travel()
+    } else {
+      debugger
+    }
+  });
+  let cost_model = cost_model
+    .map(gas::CostModel::load)
+    .transpose()
+    .map_err(|e| e.add_pos(None))?;
+  let options = interp::Options {
+    profiling,
+    heap_size,
+    debugger,
+    trace_threshold,
+    instruction_trace,
+    collect_profile: profile_out.is_some(),
+    collect_coverage: coverage_out.is_some() || annotate_out.is_some(),
+    collect_stats: false,
+    collect_memory_profile: memory_profile_out.is_some(),
+    overflow,
+    div_semantics,
+    no_speculation,
+    check_leaks,
+    rc_heap,
+    tco,
+    sanitize,
+    max_call_depth: Some(max_call_depth),
+    max_instructions,
+    timeout,
+    cost_model,
+    gas,
+    schedule,
+    seed,
+    quantum,
+    detect_races,
+    hook: None,
+  };
+  let checkpoints = Checkpoints {
+    every: checkpoint_every,
+    file: checkpoint_file,
+    resume,
+  };
+  let recordings = Recordings { record, replay };
+
   // It's a little confusing because of the naming conventions.
   //      - bril_rs takes file.json as input
   //      - bril2json takes file.bril as input
-  let prog: Program = if text {
-    bril2json::parse_abstract_program_from_read(input, true).try_into()?
+  let text = format == cli::InputFormat::Text;
+  // Passes work on whole programs, so JSON can't be converted one function at a time for them
+  let optimizing = !passes.is_empty() || emit.is_some();
+  if text || infer || optimizing || format == cli::InputFormat::Binary {
+    let mut source_code = Vec::new();
+    input
+      .read_to_end(&mut source_code)
+      .map_err(|e| InterpError::IoError(Box::new(e)).add_pos(None))?;
+    let format = match format {
+      cli::InputFormat::Json => pipeline::Format::Json,
+      cli::InputFormat::Text => pipeline::Format::Text,
+      cli::InputFormat::Binary => pipeline::Format::Binary,
+    };
+    // Binary programs always have their types, so there is nothing for --infer to do
+    let prog = pipeline::load(&source_code, format, infer);
+    let result = prog
+      .and_then(|mut prog| {
+        pipeline::link(&mut prog, lib_paths, source_dir)?;
+        if optimizing {
+          optimize(&mut prog, passes, emit)?;
+        }
+        pipeline::lower(prog)
+      })
+      .and_then(|mut bbprog| {
+        run_program(
+          &mut bbprog,
+          out,
+          &input_args,
+          args_file,
+          batch,
+          entry,
+          check,
+          strict,
+          options,
+          profile_out,
+          coverage_out,
+          annotate_out,
+          memory_profile_out,
+          dump_cfg,
+          output_format,
+          color,
+          &checkpoints,
+          &recordings,
+        )
+      });
+    // The positions in JSON programs are from the text they were made from, not the JSON
+    #[cfg(feature = "miette")]
+    let result = result.map_err(|e| {
+      if text {
+        e.with_source_code(String::from_utf8(source_code).unwrap_or_default())
+      } else {
+        e
+      }
+    });
+    result
   } else {
-    bril_rs::load_abstract_program_from_read(input).try_into()?
-  };
-  let bbprog: BBProgram = prog.try_into()?;
-  check::type_check(&bbprog)?;
+    // JSON programs are converted one function at a time so that large programs are never held in memory twice
+    let mut bbprog = BBProgram::default();
+    let imports = bril_rs::stream::for_each_function_with_imports(input, |func| {
+      bbprog.add_function(func).map_err(|e| e.add_pos(None))
+    })
+    .map_err(|e| match e {
+      StreamError::Json(e) => InterpError::InvalidJson(e).add_pos(None),
+      StreamError::Function(e) => e,
+    })?;
+    let defined = bbprog.functions().map(|f| f.name.clone()).collect();
+    for func in Linker::new(lib_paths, pipeline::load_import)
+      .link_imports(&imports, source_dir, &defined)
+      .map_err(|e| InterpError::Link(Box::new(e)).add_pos(None))?
+    {
+      bbprog.add_function(func).map_err(|e| e.add_pos(None))?;
+    }
+    run_program(
+      &mut bbprog,
+      out,
+      &input_args,
+      args_file,
+      batch,
+      entry,
+      check,
+      strict,
+      options,
+      profile_out,
+      coverage_out,
+      annotate_out,
+      memory_profile_out,
+      dump_cfg,
+      output_format,
+      color,
+      &checkpoints,
+      &recordings,
+    )
+  }
+}
+
+// Runs each of ```passes``` over ```prog``` in order and writes the result to ```emit```, if given. The program is type checked first so that its problems are reported where they are in the source rather than in whatever the passes would make of them
+fn optimize(
+  prog: &mut Program,
+  passes: &[String],
+  emit: Option<&Path>,
+) -> Result<(), PositionalInterpError> {
+  if !passes.is_empty() {
+    check::type_check(&BBProgram::new(prog.clone()).map_err(|e| e.add_pos(None))?)?;
+  }
+  let registry = bril_opt::Registry::default();
+  let options = bril_opt::Options::default();
+  for pass in passes {
+    registry
+      .run(prog, pass, &options)
+      .map_err(|e| InterpError::Pass(e).add_pos(None))?;
+  }
+  if let Some(path) = emit {
+    std::fs::File::create(path)
+      .and_then(|file| Ok(serde_json::to_writer_pretty(file, prog)?))
+      .map_err(|e| {
+        InterpError::EmitOutput(path.display().to_string(), Box::new(e)).add_pos(None)
+      })?;
+  }
+  Ok(())
+}
+
+fn run_program<T: std::io::Write>(
+  bbprog: &mut BBProgram,
+  mut out: T,
+  input_args: &[String],
+  args_file: Option<&Path>,
+  batch: Option<&Path>,
+  entry: Option<&str>,
+  check: bool,
+  strict: bool,
+  options: interp::Options,
+  profile_out: Option<String>,
+  coverage_out: Option<&Path>,
+  annotate_out: Option<&Path>,
+  memory_profile_out: Option<&Path>,
+  dump_cfg: Option<cli::CfgFormat>,
+  output_format: cli::OutputFormat,
+  color: bool,
+  checkpoints: &Checkpoints,
+  recordings: &Recordings,
+) -> Result<(), PositionalInterpError> {
+  check::type_check(bbprog)?;
+  if strict {
+    check::strict_check(bbprog)?;
+  }
+
+  if let Some(format) = dump_cfg {
+    let dump = match format {
+      cli::CfgFormat::Dot => bbprog.to_dot(),
+      cli::CfgFormat::Json => format!("{:#}\n", bbprog.to_json_cfg()),
+    };
+    return out
+      .write_all(dump.as_bytes())
+      .map_err(|e| InterpError::IoError(Box::new(e)).add_pos(None));
+  }
+
+  if let (false, Some(path)) = (check, batch) {
+    return run_batch_file(bbprog, out, path, &options);
+  }
 
   if !check {
-    interp::execute_main(&bbprog, out, &input_args, profiling)?;
+    let file_args;
+    let replayed;
+    let input_args = match (args_file, recordings.replay) {
+      (_, Some(path)) => {
+        replayed = recording::Recording::load(path).map_err(|e| e.add_pos(None))?;
+        replayed.replay(bbprog);
+        &replayed.args
+      }
+      (Some(path), None) => {
+        file_args = read_args_file(bbprog, path, entry).map_err(|e| e.add_pos(None))?;
+        &file_args
+      }
+      (None, None) => input_args,
+    };
+    let recorder = recordings
+      .record
+      .map(|path| (path, recording::Recorder::new(bbprog)));
+    let bbprog = &*bbprog;
+    let report = match (output_format, color) {
+      (cli::OutputFormat::Json, _) => run_main(
+        bbprog,
+        sink::JsonLines(&mut out),
+        input_args,
+        entry,
+        options,
+        checkpoints,
+      ),
+      (cli::OutputFormat::Text, true) => run_main(
+        bbprog,
+        sink::Colored(&mut out),
+        input_args,
+        entry,
+        options,
+        checkpoints,
+      ),
+      (cli::OutputFormat::Text, false) => {
+        run_main(bbprog, &mut out, input_args, entry, options, checkpoints)
+      }
+    };
+    // A run which failed is what most needs to be made again, so it is recorded either way
+    if let Some((path, recorder)) = recorder {
+      recorder
+        .finish(input_args.to_vec())
+        .save(path)
+        .map_err(|e| e.add_pos(None))?;
+    }
+    let report = report?;
+    // What the entry function returned comes after everything it printed, written the same way as a `print` of it
+    if let Some(value) = &report.returned {
+      let values = std::slice::from_ref(value);
+      match (output_format, color) {
+        (cli::OutputFormat::Json, _) => sink::JsonLines(&mut out).print_values(values),
+        (cli::OutputFormat::Text, true) => sink::Colored(&mut out).print_values(values),
+        (cli::OutputFormat::Text, false) => out.print_values(values),
+      }
+      .map_err(|e| InterpError::IoError(Box::new(e)).add_pos(None))?;
+    }
+    for trace in report.traces {
+      eprint!("{trace}");
+    }
+    if let (Some(path), Some(profile)) = (profile_out, report.profile) {
+      std::fs::File::create(&path)
+        .and_then(|file| Ok(serde_json::to_writer_pretty(file, &profile)?))
+        .map_err(|e| InterpError::ProfileOutput(path, Box::new(e)).add_pos(None))?;
+    }
+    if let (Some(path), Some(coverage)) = (coverage_out, &report.coverage) {
+      write_coverage(bbprog, path, coverage).map_err(|e| e.add_pos(None))?;
+    }
+    if let (Some(path), Some(coverage)) = (annotate_out, &report.coverage) {
+      // How many of the hottest blocks are listed after the program
+      const HOTTEST: usize = 20;
+      std::fs::write(path, coverage.heatmap(bbprog, HOTTEST)).map_err(|e| {
+        InterpError::ProfileOutput(path.display().to_string(), Box::new(e)).add_pos(None)
+      })?;
+    }
+    if let (Some(path), Some(profile)) = (memory_profile_out, report.memory_profile) {
+      write_memory_profile(path, &profile).map_err(|e| e.add_pos(None))?;
+    }
   }
 
   Ok(())
 }
+
+// Writes ```coverage``` as JSON to ```path```, and the program annotated with it to the same path with a `.bril` extension
+fn write_coverage(
+  bbprog: &BBProgram,
+  path: &Path,
+  coverage: &coverage::Coverage,
+) -> Result<(), InterpError> {
+  let mut annotated_path = path.with_extension("bril");
+  if annotated_path == path {
+    annotated_path = path.with_extension("cov.bril");
+  }
+  std::fs::File::create(path)
+    .and_then(|file| Ok(serde_json::to_writer_pretty(file, coverage)?))
+    .map_err(|e| InterpError::CoverageOutput(path.display().to_string(), Box::new(e)))?;
+  std::fs::write(&annotated_path, coverage.annotate(bbprog))
+    .map_err(|e| InterpError::CoverageOutput(annotated_path.display().to_string(), Box::new(e)))
+}
+
+// Writes ```profile``` as JSON to ```path```, and its call stacks folded for flame graphs to the same path with a `.folded` extension
+fn write_memory_profile(path: &Path, profile: &profile::MemoryProfile) -> Result<(), InterpError> {
+  let mut folded_path = path.with_extension("folded");
+  if folded_path == path {
+    folded_path = path.with_extension("stacks.folded");
+  }
+  std::fs::File::create(path)
+    .and_then(|file| Ok(serde_json::to_writer_pretty(file, profile)?))
+    .map_err(|e| InterpError::ProfileOutput(path.display().to_string(), Box::new(e)))?;
+  std::fs::write(&folded_path, profile.folded())
+    .map_err(|e| InterpError::ProfileOutput(folded_path.display().to_string(), Box::new(e)))
+}
+
+// What is output for each run of a batch, which either printed something or failed
+#[derive(serde::Serialize)]
+struct BatchReport {
+  line: usize,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  output: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  total_dyn_inst: Option<u32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  error: Option<String>,
+}
+
+// Runs main with the arguments on each line of the file at ```path```, or of stdin if it is `-`, writing a line of JSON to ```out``` for each run
+fn run_batch_file<T: std::io::Write>(
+  bbprog: &BBProgram,
+  mut out: T,
+  path: &Path,
+  options: &interp::Options,
+) -> Result<(), PositionalInterpError> {
+  let text = read_input_file(path)
+    .map_err(|e| InterpError::BatchInput(path.display().to_string(), Box::new(e)).add_pos(None))?;
+  // Blank lines are skipped but still counted, so that every run is reported with the line it came from
+  let lines: Vec<(usize, Result<Vec<String>, InterpError>)> = text
+    .lines()
+    .enumerate()
+    .filter(|(_, line)| !line.trim().is_empty())
+    .map(|(i, line)| (i + 1, interp::main_args_from_json(bbprog, line)))
+    .collect();
+  let inputs: Vec<Vec<String>> = lines
+    .iter()
+    .filter_map(|(_, args)| args.as_ref().ok().cloned())
+    .collect();
+
+  let (
+    heap_size,
+    overflow,
+    div_semantics,
+    no_speculation,
+    check_leaks,
+    rc_heap,
+    tco,
+    sanitize,
+    max_call_depth,
+    max_instructions,
+    timeout,
+    cost_model,
+    gas,
+    schedule,
+    seed,
+    quantum,
+    detect_races,
+  ) = (
+    options.heap_size,
+    options.overflow,
+    options.div_semantics,
+    options.no_speculation,
+    options.check_leaks,
+    options.rc_heap,
+    options.tco,
+    options.sanitize,
+    options.max_call_depth,
+    options.max_instructions,
+    options.timeout,
+    options.cost_model.clone(),
+    options.gas,
+    options.schedule,
+    options.seed,
+    options.quantum,
+    options.detect_races,
+  );
+  let mut runs = batch::run_batch(bbprog, &inputs, || interp::Options {
+    heap_size,
+    overflow,
+    div_semantics,
+    no_speculation,
+    check_leaks,
+    rc_heap,
+    tco,
+    sanitize,
+    max_call_depth,
+    max_instructions,
+    timeout,
+    cost_model: cost_model.clone(),
+    gas,
+    schedule,
+    seed,
+    quantum,
+    detect_races,
+    ..interp::Options::default()
+  })
+  .into_iter();
+
+  let total = lines.len();
+  let mut failed = 0;
+  for (line, args) in lines {
+    let result = match args {
+      Ok(_) => runs.next().unwrap(),
+      Err(e) => Err(e.add_pos(None)),
+    };
+    let report = match result {
+      Ok(run) => BatchReport {
+        line,
+        output: Some(run.output),
+        total_dyn_inst: Some(run.total_dyn_inst),
+        error: None,
+      },
+      Err(e) => {
+        failed += 1;
+        BatchReport {
+          line,
+          output: None,
+          total_dyn_inst: None,
+          error: Some(e.to_string()),
+        }
+      }
+    };
+    serde_json::to_writer(&mut out, &report)
+      .map_err(std::io::Error::from)
+      .and_then(|()| writeln!(out))
+      .map_err(|e| InterpError::IoError(Box::new(e)).add_pos(None))?;
+  }
+  if failed == 0 {
+    Ok(())
+  } else {
+    Err(InterpError::BatchFailed(failed, total).add_pos(None))
+  }
+}
+
+// The contents of the file at ```path```, or of stdin if it is `-`
+fn read_input_file(path: &Path) -> std::io::Result<String> {
+  if path == Path::new("-") {
+    std::io::read_to_string(std::io::stdin())
+  } else {
+    std::fs::read_to_string(path)
+  }
+}
+
+// The arguments for main in the JSON file at ```path```, or on stdin if it is `-`
+fn read_args_file(
+  bbprog: &BBProgram,
+  path: &Path,
+  entry: Option<&str>,
+) -> Result<Vec<String>, InterpError> {
+  let json = read_input_file(path)
+    .map_err(|e| InterpError::ArgsInput(path.display().to_string(), Box::new(e)))?;
+  entry.map_or_else(
+    || interp::main_args_from_json(bbprog, &json),
+    |name| interp::entry_args_from_json(bbprog, name, &json),
+  )
+}
+
+// Where to save checkpoints while the program runs and where to resume it from
+struct Checkpoints<'a> {
+  every: Option<NonZeroU64>,
+  file: &'a Path,
+  resume: Option<&'a Path>,
+}
+
+// Where to save a recording of the run and where to replay one from
+struct Recordings<'a> {
+  record: Option<&'a Path>,
+  replay: Option<&'a Path>,
+}
+
+// Runs main with its prints going to ```out```, with checkpoints if any were asked for
+fn run_main<T: sink::Sink>(
+  bbprog: &BBProgram,
+  out: T,
+  input_args: &[String],
+  entry: Option<&str>,
+  options: interp::Options,
+  checkpoints: &Checkpoints,
+) -> Result<interp::Report, PositionalInterpError> {
+  if checkpoints.every.is_some() || checkpoints.resume.is_some() {
+    run_with_checkpoints(bbprog, out, input_args, entry, options, checkpoints)
+  } else if let Some(name) = entry {
+    interp::execute_function_with_options(bbprog, out, name, input_args, options)
+  } else {
+    interp::execute_main_with_options(bbprog, out, input_args, options)
+  }
+}
+
+// Runs main or ```entry```, or carries on from the checkpoint at ```checkpoints.resume```, saving a checkpoint every so many instructions along the way
+fn run_with_checkpoints<T: sink::Sink>(
+  bbprog: &BBProgram,
+  out: T,
+  input_args: &[String],
+  entry: Option<&str>,
+  options: interp::Options,
+  checkpoints: &Checkpoints,
+) -> Result<interp::Report, PositionalInterpError> {
+  let mut interpreter = match checkpoints.resume {
+    Some(path) => {
+      let checkpoint = checkpoint::Checkpoint::load(path).map_err(|e| e.add_pos(None))?;
+      interp::Interpreter::resume(bbprog, out, options, checkpoint)?
+    }
+    None => {
+      let mut interpreter = interp::Interpreter::new(bbprog, out, options);
+      match entry {
+        Some(name) => interpreter.start_entry(name, input_args)?,
+        None => interpreter.start_main(input_args)?,
+      }
+      interpreter
+    }
+  };
+  if let Some(steps) = checkpoints.every {
+    while interpreter.run_for(steps.get())? {
+      interpreter
+        .checkpoint()?
+        .save(checkpoints.file)
+        .map_err(|e| e.add_pos(None))?;
+    }
+  }
+  // After checkpointing there is nothing left to run, so this only picks up what was returned
+  let returned = interpreter.run_to_completion()?;
+  Ok(interp::Report {
+    returned,
+    ..interpreter.finish()?
+  })
+}
+
+#[derive(serde::Serialize)]
+struct FileDiagnostic<'a> {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  file: Option<&'a str>,
+  #[serde(flatten)]
+  diagnostic: &'a check::Diagnostic,
+}
+
+// Problems which stop the program from being checked at all are reported on their own
+fn find_diagnostics(
+  input: Box<dyn std::io::Read>,
+  text: bool,
+  strict: bool,
+) -> Vec<check::Diagnostic> {
+  let abstract_prog = if text {
+    bril2json::parse_abstract_program_from_read(input, true)
+  } else {
+    bril_rs::load_abstract_program_from_read(input)
+  };
+  let prog: Program = match abstract_prog.try_into() {
+    Ok(prog) => prog,
+    Err(e) => {
+      return vec![check::Diagnostic {
+        function: None,
+        line: e.pos().map(|p| p.row),
+        column: e.pos().map(|p| p.col),
+        source: e.pos().and_then(|p| p.source),
+        severity: check::Severity::Error,
+        message: e.error().to_string(),
+      }];
+    }
+  };
+  match BBProgram::new(prog) {
+    Ok(bbprog) => {
+      let mut diagnostics = check::type_check_all(&bbprog);
+      if strict {
+        diagnostics.extend(check::strict_check_all(&bbprog));
+      }
+      diagnostics
+    }
+    Err(e) => {
+      let e = e.add_pos(None);
+      vec![check::Diagnostic {
+        function: None,
+        line: e.pos().map(|p| p.row),
+        column: e.pos().map(|p| p.col),
+        source: e.pos().and_then(|p| p.source),
+        severity: check::Severity::Error,
+        message: e.error().to_string(),
+      }]
+    }
+  }
+}
+
+#[doc(hidden)]
+pub fn check_input<T: std::io::Write>(
+  input: Box<dyn std::io::Read>,
+  mut out: T,
+  text: bool,
+  file: Option<&str>,
+  json: bool,
+  deny_warnings: bool,
+  strict: bool,
+) -> Result<usize, Box<dyn Error>> {
+  let diagnostics = find_diagnostics(input, text, strict);
+  for diagnostic in &diagnostics {
+    if json {
+      serde_json::to_writer(&mut out, &FileDiagnostic { file, diagnostic })?;
+      writeln!(out)?;
+    } else if let Some(file) = file {
+      writeln!(out, "{file}:{diagnostic}")?;
+    } else {
+      writeln!(out, "{diagnostic}")?;
+    }
+  }
+  // The number of problems which fail the check
+  Ok(
+    diagnostics
+      .iter()
+      .filter(|d| deny_warnings || d.severity == check::Severity::Error)
+      .count(),
+  )
+}
@@ -1,25 +1,165 @@
-use brilirs::cli::Cli;
+use brilirs::cli::{Cli, Color, InputFormat};
+use brilirs::error::{InterpError, PositionalInterpError};
+use brilirs::instruction_trace::InstructionTrace;
+use brilirs::profile::Profile;
 use clap::Parser;
 use std::fs::File;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// Compares two reports written by --profile-out, like from before and after optimizing a program, and prints how the number of instructions run by each function and of each opcode changed
+#[derive(Parser)]
+#[clap(
+  name = "brilirs profile-diff",
+  bin_name = "brilirs profile-diff",
+  version,
+  author
+)]
+struct ProfileDiffCli {
+  /// The profile to compare against
+  before: PathBuf,
+
+  /// The profile to compare with it
+  after: PathBuf,
+
+  /// How many percent a count has to go up by to be marked as a regression
+  #[clap(long, default_value_t = 5.0)]
+  threshold: f64,
+
+  /// Exit with a status of 1 when anything regressed, so that a script can check that a change didn't make a program run more instructions
+  #[clap(long)]
+  deny_regressions: bool,
+}
 
 fn main() {
+  // No argument of main can be `profile-diff`, so it is safe to take it as the name of the subcommand
+  if std::env::args().nth(1).as_deref() == Some("profile-diff") {
+    profile_diff(ProfileDiffCli::parse_from(std::env::args_os().skip(1)));
+    return;
+  }
+
   let args = Cli::parse();
 
-  let input: Box<dyn std::io::Read> = match args.file {
+  // With --count-only the interpreter is built around a writer which throws everything away, instead of checking whether to print every time
+  let result = if args.count_only {
+    run(args, std::io::sink())
+  } else {
+    run(args, std::io::stdout())
+  };
+  if let Err(e) = result {
+    eprintln!("error: {e}");
+    print_backtrace(&e);
+    std::process::exit(2)
+  }
+}
+
+fn profile_diff(args: ProfileDiffCli) {
+  let load = |path: &Path| {
+    Profile::load(path).unwrap_or_else(|e| {
+      eprintln!("error: {e}");
+      std::process::exit(2)
+    })
+  };
+  let diff = load(&args.before).diff(&load(&args.after));
+  print!("{}", diff.report(args.threshold));
+  if args.deny_regressions && !diff.regressions(args.threshold).is_empty() {
+    std::process::exit(1)
+  }
+}
+
+// Only the innermost and outermost calls of a deep call stack are kept, so the ones in between are counted instead
+fn print_backtrace(e: &PositionalInterpError) {
+  let mut next = 0;
+  for frame in e.backtrace() {
+    if frame.depth != next {
+      eprintln!("  ... {} more calls ...", frame.depth - next);
+    }
+    eprintln!("  {frame}");
+    next = frame.depth + 1;
+  }
+}
+
+fn run<T: Write>(args: Cli, out: T) -> Result<(), PositionalInterpError> {
+  let input: Box<dyn std::io::Read> = match &args.file {
     None => Box::new(std::io::stdin()),
 
     Some(input_file) => Box::new(File::open(input_file).unwrap()),
   };
+  // Imports are looked up next to the program, or in the current directory for stdin
+  let source_dir = args
+    .file
+    .as_deref()
+    .and_then(|f| Path::new(f).parent())
+    .unwrap_or_else(|| Path::new(""));
+
+  let instruction_trace = if args.trace {
+    let out: Box<dyn Write> = match &args.trace_out {
+      Some(path) => Box::new(std::io::BufWriter::new(
+        File::create(path).map_err(|e| InterpError::IoError(Box::new(e)).add_pos(None))?,
+      )),
+      None => Box::new(std::io::stderr()),
+    };
+    Some(InstructionTrace::new(out, args.trace_fn, args.trace_op))
+  } else {
+    None
+  };
 
-  if let Err(e) = brilirs::run_input(
+  brilirs::run_input(
     input,
-    std::io::stdout(),
+    out,
     args.args,
-    args.profile,
+    args.args_file.as_deref(),
+    args.batch.as_deref(),
+    args.entry.as_deref(),
+    args.profile || args.count_only,
     args.check,
-    args.text,
-  ) {
-    eprintln!("error: {e}");
-    std::process::exit(2)
-  }
+    args.strict,
+    if args.text {
+      InputFormat::Text
+    } else {
+      args.format.unwrap_or_default()
+    },
+    args.infer,
+    args.debug,
+    args.time_travel,
+    args.trace_threshold,
+    instruction_trace,
+    args.heap_size,
+    args.profile_out,
+    args.coverage.as_deref(),
+    args.annotate.as_deref(),
+    args.memory_profile.as_deref(),
+    args.overflow,
+    args.div_semantics,
+    args.no_speculation,
+    args.check_leaks,
+    args.rc_heap,
+    args.tco,
+    args.sanitize,
+    args.max_call_depth,
+    args.max_instructions,
+    args.timeout_ms.map(std::time::Duration::from_millis),
+    args.gas,
+    args.cost_model.as_deref(),
+    args.schedule,
+    args.seed,
+    args.quantum,
+    args.detect_races,
+    &args.passes,
+    args.emit.as_deref(),
+    args.dump_cfg,
+    args.output_format,
+    match args.color {
+      Color::Auto => std::io::stdout().is_terminal(),
+      Color::Always => true,
+      Color::Never => false,
+    },
+    args.checkpoint_every,
+    &args.checkpoint_file,
+    args.resume.as_deref(),
+    args.record.as_deref(),
+    args.replay.as_deref(),
+    args.lib_path,
+    source_dir,
+  )
 }
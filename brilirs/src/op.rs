@@ -0,0 +1,359 @@
+use bril_rs::{EffectOps, Instruction, ValueOps};
+
+use crate::basic_block::NumifiedInstruction;
+use crate::value::Value;
+
+// An instruction compiled ahead of time into what the interpreter dispatches on. Each one holds the numbers of the variables it reads and writes, along with anything else that is the same every time it runs, like the value of a constant or the blocks a branch goes to, so running it never has to look through the instruction it came from. They are kept small so that a block of them stays in as few cache lines as possible
+//
+// Instructions which run rarely or need more than this, like `print`, `alloc`, and anything to do with threads or speculation, are `Other` and run from the instruction itself
+#[derive(Debug, Clone, Copy)]
+pub enum Op {
+  Const {
+    dest: u32,
+    value: Value,
+  },
+  Id {
+    dest: u32,
+    arg: u32,
+  },
+  Add {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Sub {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Mul {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Div {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Rem {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Eq {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Lt {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Gt {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Le {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Ge {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Not {
+    dest: u32,
+    arg: u32,
+  },
+  And {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Or {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Fadd {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Fsub {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Fmul {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Fdiv {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Feq {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Flt {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Fgt {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Fle {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Fge {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Ceq {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Clt {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Cgt {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Cle {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Cge {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  Char2int {
+    dest: u32,
+    arg: u32,
+  },
+  Int2char {
+    dest: u32,
+    arg: u32,
+  },
+  Float2bits {
+    dest: u32,
+    arg: u32,
+  },
+  Bits2float {
+    dest: u32,
+    arg: u32,
+  },
+  Load {
+    dest: u32,
+    ptr: u32,
+  },
+  Store {
+    ptr: u32,
+    arg: u32,
+  },
+  PtrAdd {
+    dest: u32,
+    ptr: u32,
+    offset: u32,
+  },
+  // The indices of the blocks to go to
+  Jmp {
+    target: u32,
+  },
+  Br {
+    cond: u32,
+    then: u32,
+    otherwise: u32,
+  },
+  // A comparison of ints whose result the `br` which ends the block goes by, so that a plain run can take the branch without dispatching on it
+  EqBr {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  LtBr {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  GtBr {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  LeBr {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  GeBr {
+    dest: u32,
+    lhs: u32,
+    rhs: u32,
+  },
+  // These leave the block, which is worth doing without going through Other since functions are called so often. They look up the rest of what they need in the instruction
+  Call,
+  Ret,
+  Nop,
+  Other,
+}
+
+impl Op {
+  // Compiles ```instr```, whose variables and labels have been numbered as ```numified```, at the end of a block whose successors are ```exit```
+  pub fn compile(instr: &Instruction, numified: &NumifiedInstruction, exit: &[usize]) -> Self {
+    let dest = numified.dest.unwrap_or_default();
+    let args = numified.args.as_slice();
+    match (instr, args) {
+      (
+        Instruction::Constant {
+          value, const_type, ..
+        },
+        _,
+      ) => {
+        Value::scalar(value, const_type).map_or(Self::Other, |value| Self::Const { dest, value })
+      }
+      (
+        Instruction::Value {
+          op: ValueOps::Call, ..
+        }
+        | Instruction::Effect {
+          op: EffectOps::Call,
+          ..
+        },
+        _,
+      ) => Self::Call,
+      (Instruction::Value { op, .. }, &[arg]) => match op {
+        ValueOps::Id => Self::Id { dest, arg },
+        ValueOps::Not => Self::Not { dest, arg },
+        ValueOps::Char2int => Self::Char2int { dest, arg },
+        ValueOps::Int2char => Self::Int2char { dest, arg },
+        ValueOps::Float2bits => Self::Float2bits { dest, arg },
+        ValueOps::Bits2float => Self::Bits2float { dest, arg },
+        ValueOps::Load => Self::Load { dest, ptr: arg },
+        _ => Self::Other,
+      },
+      (Instruction::Value { op, .. }, &[lhs, rhs]) => match op {
+        ValueOps::Add => Self::Add { dest, lhs, rhs },
+        ValueOps::Sub => Self::Sub { dest, lhs, rhs },
+        ValueOps::Mul => Self::Mul { dest, lhs, rhs },
+        ValueOps::Div => Self::Div { dest, lhs, rhs },
+        ValueOps::Rem => Self::Rem { dest, lhs, rhs },
+        ValueOps::Eq => Self::Eq { dest, lhs, rhs },
+        ValueOps::Lt => Self::Lt { dest, lhs, rhs },
+        ValueOps::Gt => Self::Gt { dest, lhs, rhs },
+        ValueOps::Le => Self::Le { dest, lhs, rhs },
+        ValueOps::Ge => Self::Ge { dest, lhs, rhs },
+        ValueOps::And => Self::And { dest, lhs, rhs },
+        ValueOps::Or => Self::Or { dest, lhs, rhs },
+        ValueOps::Fadd => Self::Fadd { dest, lhs, rhs },
+        ValueOps::Fsub => Self::Fsub { dest, lhs, rhs },
+        ValueOps::Fmul => Self::Fmul { dest, lhs, rhs },
+        ValueOps::Fdiv => Self::Fdiv { dest, lhs, rhs },
+        ValueOps::Feq => Self::Feq { dest, lhs, rhs },
+        ValueOps::Flt => Self::Flt { dest, lhs, rhs },
+        ValueOps::Fgt => Self::Fgt { dest, lhs, rhs },
+        ValueOps::Fle => Self::Fle { dest, lhs, rhs },
+        ValueOps::Fge => Self::Fge { dest, lhs, rhs },
+        ValueOps::Ceq => Self::Ceq { dest, lhs, rhs },
+        ValueOps::Clt => Self::Clt { dest, lhs, rhs },
+        ValueOps::Cgt => Self::Cgt { dest, lhs, rhs },
+        ValueOps::Cle => Self::Cle { dest, lhs, rhs },
+        ValueOps::Cge => Self::Cge { dest, lhs, rhs },
+        ValueOps::PtrAdd => Self::PtrAdd {
+          dest,
+          ptr: lhs,
+          offset: rhs,
+        },
+        _ => Self::Other,
+      },
+      (
+        Instruction::Effect {
+          op: EffectOps::Store,
+          ..
+        },
+        &[ptr, arg],
+      ) => Self::Store { ptr, arg },
+      (
+        Instruction::Effect {
+          op: EffectOps::Jump,
+          ..
+        },
+        _,
+      ) => Self::Jmp {
+        target: exit[0] as u32,
+      },
+      (
+        Instruction::Effect {
+          op: EffectOps::Branch,
+          ..
+        },
+        &[cond],
+      ) => Self::Br {
+        cond,
+        then: exit[0] as u32,
+        otherwise: exit[1] as u32,
+      },
+      (
+        Instruction::Effect {
+          op: EffectOps::Return,
+          ..
+        },
+        _,
+      ) => Self::Ret,
+      (
+        Instruction::Effect {
+          op: EffectOps::Nop, ..
+        },
+        _,
+      ) => Self::Nop,
+      _ => Self::Other,
+    }
+  }
+
+  // Compiles the instructions of a block, which goes on to the blocks in ```exit```
+  pub fn compile_block(
+    instrs: &[Instruction],
+    numified: &[NumifiedInstruction],
+    exit: &[usize],
+  ) -> Vec<Self> {
+    let mut ops: Vec<Self> = instrs
+      .iter()
+      .zip(numified)
+      .map(|(instr, numified)| Self::compile(instr, numified, exit))
+      .collect();
+    if let [.., compare, Self::Br { cond, .. }] = ops.as_mut_slice() {
+      *compare = match *compare {
+        Self::Eq { dest, lhs, rhs } if dest == *cond => Self::EqBr { dest, lhs, rhs },
+        Self::Lt { dest, lhs, rhs } if dest == *cond => Self::LtBr { dest, lhs, rhs },
+        Self::Gt { dest, lhs, rhs } if dest == *cond => Self::GtBr { dest, lhs, rhs },
+        Self::Le { dest, lhs, rhs } if dest == *cond => Self::LeBr { dest, lhs, rhs },
+        Self::Ge { dest, lhs, rhs } if dest == *cond => Self::GeBr { dest, lhs, rhs },
+        op => op,
+      };
+    }
+    ops
+  }
+}
@@ -0,0 +1,95 @@
+use std::path::{Path, PathBuf};
+
+use bril_rs::link::Linker;
+use bril_rs::version::VersionError;
+use bril_rs::{AbstractProgram, Program};
+
+use crate::basic_block::BBProgram;
+use crate::check::{self, Diagnostic};
+use crate::error::{InterpError, PositionalInterpError};
+use crate::interp::{self, Options, Report};
+use crate::sink::Sink;
+
+/// How the program given to [`load`] is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+  /// Bril JSON, as `bril2json` writes it
+  Json,
+  /// Bril text, as `bril2txt` writes it
+  Text,
+  /// The binary encoding of `bril_rs::binary`, as `bril2bc` writes it
+  Binary,
+}
+
+/// Reads the program in ```input```, which is written in ```format```. With ```infer```, the types of constants and value operations which leave them out are filled in, which binary programs never do. A syntax error in Bril text is returned with where it is rather than panicking like `bril2json` does
+pub fn load(input: &[u8], format: Format, infer: bool) -> Result<Program, PositionalInterpError> {
+  let mut abstract_prog: AbstractProgram = match format {
+    Format::Binary => {
+      return bril_rs::binary::from_bytes(input)
+        .map_err(|e| InterpError::InvalidBinary(e).add_pos(None));
+    }
+    // Older versions of Bril JSON are upgraded, like when the program is streamed in, and a version which can't be read is reported the same way
+    Format::Json => bril_rs::version::from_slice(input).map_err(|e| {
+      let e = match e {
+        VersionError::Json(e) => e,
+        e => serde::de::Error::custom(e),
+      };
+      InterpError::InvalidJson(e).add_pos(None)
+    })?,
+    Format::Text => {
+      let text = std::str::from_utf8(input).map_err(|e| {
+        InterpError::InvalidText(format!("the program is not UTF-8: {e}")).add_pos(None)
+      })?;
+      bril2json::parse_abstract_program_from_str(text, true)
+        .map_err(|e| InterpError::InvalidText(e.message).add_pos(e.pos))?
+    }
+  };
+  if infer {
+    bril_rs::infer::infer_types(&mut abstract_prog);
+  }
+  Ok(Program::try_from(abstract_prog)?)
+}
+
+/// Adds the functions which ```prog``` imports to it, looking for the imported files relative to ```source_dir``` and then in each of ```lib_paths```, like `brilirs --lib` does. Imported files are Bril text unless they end in `.json`
+pub fn link(
+  prog: &mut Program,
+  lib_paths: Vec<PathBuf>,
+  source_dir: &Path,
+) -> Result<(), PositionalInterpError> {
+  Linker::new(lib_paths, load_import)
+    .link(prog, source_dir)
+    .map_err(|e| InterpError::Link(Box::new(e)).add_pos(None))
+}
+
+// Reads a file that the program imports from, which is Bril text unless it ends in `.json`
+pub(crate) fn load_import(path: &Path) -> Result<Program, PositionalInterpError> {
+  let input = std::fs::read(path).map_err(|e| InterpError::IoError(Box::new(e)).add_pos(None))?;
+  let format = if path.extension().is_some_and(|ext| ext == "json") {
+    Format::Json
+  } else {
+    Format::Text
+  };
+  load(&input, format, false)
+}
+
+/// Converts ```prog``` into the [`BBProgram`] which the interpreter runs, which is where the functions it calls and the labels it jumps to are looked up
+pub fn lower(prog: Program) -> Result<BBProgram, PositionalInterpError> {
+  BBProgram::new(prog).map_err(|e| e.add_pos(None))
+}
+
+/// Every problem which type checking finds in ```bbprog```, which is empty if there are none. Only those with [`check::Severity::Error`] stop [`execute`] from running it
+#[must_use]
+pub fn check(bbprog: &BBProgram) -> Vec<Diagnostic> {
+  check::type_check_all(bbprog)
+}
+
+/// Runs the main function of ```bbprog``` with ```args```, printing to ```out```. The program is type checked first and the first error is returned if it doesn't, since the interpreter relies on programs being well typed and a stage between [`check()`] and here may have changed it. The [`Report`] has whatever ```options``` asked to be collected, like the [`crate::profile::ExecutionStats`] of `collect_stats`
+pub fn execute<T: Sink>(
+  bbprog: &BBProgram,
+  out: T,
+  args: &[String],
+  options: Options,
+) -> Result<Report, PositionalInterpError> {
+  check::type_check(bbprog)?;
+  interp::execute_main_with_options(bbprog, out, args, options)
+}
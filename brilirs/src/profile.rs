@@ -0,0 +1,532 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+
+use bril_rs::{Instruction, SourcePosition};
+use fxhash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::basic_block::{BBFunction, BBProgram};
+use crate::error::InterpError;
+
+/// How often each part of a program was executed over a single run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+  /// The total number of dynamic instructions, which is the same number reported by `total_dyn_inst`
+  pub total_dyn_inst: u64,
+  /// The total cost of those instructions, which is the same number reported by `total_gas`, if [`crate::interp::Options::cost_model`] or [`crate::interp::Options::gas`] was set
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub total_gas: Option<u64>,
+  /// The number of dynamic instructions of each opcode across the whole program
+  pub opcodes: BTreeMap<String, u64>,
+  /// The profile of every function in the program by name, including those which were never called
+  pub functions: BTreeMap<String, FunctionProfile>,
+}
+
+impl Profile {
+  /// Loads a profile which was written with `--profile-out`
+  pub fn load(path: &Path) -> Result<Self, InterpError> {
+    File::open(path)
+      .and_then(|file| Ok(serde_json::from_reader(BufReader::new(file))?))
+      .map_err(|e| InterpError::ProfileInput(path.display().to_string(), Box::new(e)))
+  }
+
+  /// How ```after``` differs from this profile, which are usually of the same program from before and after some change to it like an optimization
+  #[must_use]
+  pub fn diff(&self, after: &Self) -> ProfileDiff {
+    ProfileDiff {
+      total_dyn_inst: Delta {
+        before: self.total_dyn_inst,
+        after: after.total_dyn_inst,
+      },
+      total_gas: self
+        .total_gas
+        .zip(after.total_gas)
+        .map(|(before, after)| Delta { before, after }),
+      functions: deltas(
+        &self
+          .functions
+          .iter()
+          .map(|(name, f)| (name.clone(), f.dyn_inst))
+          .collect(),
+        &after
+          .functions
+          .iter()
+          .map(|(name, f)| (name.clone(), f.dyn_inst))
+          .collect(),
+      ),
+      opcodes: deltas(&self.opcodes, &after.opcodes),
+    }
+  }
+}
+
+// The change in the count of everything in either ```before``` or ```after```, with the biggest changes first
+fn deltas(before: &BTreeMap<String, u64>, after: &BTreeMap<String, u64>) -> Vec<(String, Delta)> {
+  let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+  let mut deltas: Vec<(String, Delta)> = names
+    .into_iter()
+    .map(|name| {
+      let count = |counts: &BTreeMap<String, u64>| counts.get(name).copied().unwrap_or(0);
+      (
+        name.clone(),
+        Delta {
+          before: count(before),
+          after: count(after),
+        },
+      )
+    })
+    .collect();
+  // Sorting is stable, so changes of the same size stay in order of their names
+  deltas.sort_by_key(|(_, d)| std::cmp::Reverse(d.before.abs_diff(d.after)));
+  deltas
+}
+
+/// How one [`Profile`] differs from another, from [`Profile::diff`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileDiff {
+  /// The change in [`Profile::total_dyn_inst`]
+  pub total_dyn_inst: Delta,
+  /// The change in [`Profile::total_gas`], if both profiles counted gas
+  pub total_gas: Option<Delta>,
+  /// The change in the [`FunctionProfile::dyn_inst`] of every function in either profile, with the biggest changes first. A function missing from a profile counts as having executed nothing
+  pub functions: Vec<(String, Delta)>,
+  /// The change in [`Profile::opcodes`] for every opcode in either profile, with the biggest changes first
+  pub opcodes: Vec<(String, Delta)>,
+}
+
+/// A count from two profiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delta {
+  /// The count in the first profile
+  pub before: u64,
+  /// The count in the second profile
+  pub after: u64,
+}
+
+impl Delta {
+  /// How much the count went up by, which is negative if it went down
+  #[must_use]
+  pub const fn change(&self) -> i128 {
+    self.after as i128 - self.before as i128
+  }
+
+  /// The change as a percentage of the count before, or [`None`] if the count before was 0
+  #[must_use]
+  pub fn percent(&self) -> Option<f64> {
+    (self.before != 0).then(|| self.change() as f64 * 100.0 / self.before as f64)
+  }
+
+  /// Whether the count went up by more than ```threshold``` percent, which anything going up from 0 does
+  #[must_use]
+  pub fn regressed(&self, threshold: f64) -> bool {
+    self.after > self.before && self.percent().is_none_or(|p| p > threshold)
+  }
+}
+
+impl Delta {
+  // The change and how it compares to the count before, like `+5 (+10.0%)`
+  fn describe_change(&self) -> String {
+    match self.percent() {
+      _ if self.change() == 0 => "0".to_string(),
+      Some(percent) => format!("{:+} ({percent:+.1}%)", self.change()),
+      None => format!("{:+} (new)", self.change()),
+    }
+  }
+}
+
+impl fmt::Display for Delta {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{} -> {}, {}",
+      self.before,
+      self.after,
+      self.describe_change()
+    )
+  }
+}
+
+impl ProfileDiff {
+  // The totals, functions, and opcodes, each under the heading of their part of the report and named the way they are written there
+  fn sections(&self) -> [(&'static str, Vec<(String, Delta)>); 3] {
+    let totals = std::iter::once(("total_dyn_inst".to_string(), self.total_dyn_inst))
+      .chain(self.total_gas.map(|d| ("total_gas".to_string(), d)))
+      .collect();
+    let functions = self
+      .functions
+      .iter()
+      .map(|(name, d)| (format!("@{name}"), *d))
+      .collect();
+    [
+      ("totals", totals),
+      ("functions", functions),
+      ("opcodes", self.opcodes.clone()),
+    ]
+  }
+
+  /// The names of the functions, written like `@main`, and of the opcodes whose counts went up by more than ```threshold``` percent, along with `total_dyn_inst` and `total_gas` if they did
+  #[must_use]
+  pub fn regressions(&self, threshold: f64) -> Vec<String> {
+    self
+      .sections()
+      .into_iter()
+      .flat_map(|(_, rows)| rows)
+      .filter(|(_, d)| d.regressed(threshold))
+      .map(|(name, _)| name)
+      .collect()
+  }
+
+  /// The changes as a table for people to read, with the totals and then every function and opcode whose count changed, marking those which went up by more than ```threshold``` percent as regressions
+  #[must_use]
+  pub fn report(&self, threshold: f64) -> String {
+    let mut out = String::new();
+    for (heading, rows) in self.sections() {
+      let rows: Vec<(String, Delta)> = rows
+        .into_iter()
+        .filter(|(_, d)| heading == "totals" || d.change() != 0)
+        .collect();
+      if rows.is_empty() {
+        continue;
+      }
+      if !out.is_empty() {
+        out.push('\n');
+      }
+      writeln!(out, "{heading}").unwrap();
+      let width = |column: &dyn Fn(&Delta) -> String| {
+        rows.iter().map(|(_, d)| column(d).len()).max().unwrap_or(0)
+      };
+      let name_width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+      let before_width = width(&|d| d.before.to_string());
+      let after_width = width(&|d| d.after.to_string());
+      let change_width = width(&Delta::describe_change);
+      for (name, delta) in &rows {
+        let Delta { before, after } = delta;
+        let change = delta.describe_change();
+        let line = format!(
+          "  {name:name_width$}  {before:>before_width$} -> {after:>after_width$}  {change:change_width$}"
+        );
+        if delta.regressed(threshold) {
+          writeln!(out, "{line}  regression").unwrap();
+        } else {
+          writeln!(out, "{}", line.trim_end()).unwrap();
+        }
+      }
+    }
+    out
+  }
+}
+
+/// The part of a [`Profile`] for a single function
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FunctionProfile {
+  /// The number of times the function was called
+  pub calls: u64,
+  /// The number of dynamic instructions executed within the function, not counting the functions it calls
+  pub dyn_inst: u64,
+  /// The number of dynamic instructions of each opcode within the function
+  pub opcodes: BTreeMap<String, u64>,
+  /// How often each basic block was executed, in the order the blocks appear in the function
+  pub blocks: Vec<BlockProfile>,
+  /// Where the function came from in the source of the frontend which generated the program, if it says
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub source: Option<SourcePosition>,
+}
+
+/// The part of a [`FunctionProfile`] for a single basic block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockProfile {
+  /// The label the block starts with, if any
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub label: Option<String>,
+  /// Where the block starts in the source of the frontend which generated the program, which is the first place its label or one of its instructions says it came from
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub source: Option<SourcePosition>,
+  /// The number of instructions in the block
+  pub size: usize,
+  /// The number of times the block was executed
+  pub count: u64,
+}
+
+/// The summary of a run collected with [`crate::interp::Options::collect_stats`], for harnesses which embed the interpreter and would otherwise have to read `total_dyn_inst` from stderr
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ExecutionStats {
+  /// The total number of dynamic instructions, which is the same number reported by `total_dyn_inst`
+  pub total_dyn_inst: u64,
+  /// The number of dynamic instructions of each opcode, counted the same way as [`Profile::opcodes`]
+  pub opcodes: BTreeMap<String, u64>,
+  /// The most calls which were running at once, counting `main`
+  pub max_call_depth: usize,
+  /// The most memory which was allocated at once, counting each value on the heap as the 8 bytes it takes in release builds
+  pub peak_heap_bytes: usize,
+  /// The memory which was freed because nothing pointed to it any more with [`crate::interp::Options::rc_heap`], counted the same way as `peak_heap_bytes`
+  pub collected_bytes: usize,
+  /// How long the program ran for, from when the interpreter was made until it finished
+  pub wall_time: Duration,
+}
+
+fn opcode(instr: &Instruction) -> String {
+  match instr {
+    Instruction::Constant { op, .. } => op.to_string(),
+    Instruction::Value { op, .. } => op.to_string(),
+    Instruction::Effect { op, .. } => op.to_string(),
+  }
+}
+
+// The counts are keyed by the address of each function so that recording them doesn't need to hash function names
+#[derive(Default)]
+pub(crate) struct Profiler {
+  calls: FxHashMap<*const BBFunction, u64>,
+  blocks: FxHashMap<*const BBFunction, Vec<u64>>,
+}
+
+impl Profiler {
+  pub(crate) fn enter_function(&mut self, func: &BBFunction) {
+    *self.calls.entry(func).or_insert(0) += 1;
+  }
+
+  pub(crate) fn enter_block(&mut self, func: &BBFunction, block_idx: usize) {
+    self
+      .blocks
+      .entry(func)
+      .or_insert_with(|| vec![0; func.blocks.len()])[block_idx] += 1;
+  }
+
+  pub(crate) fn finish(&self, prog: &BBProgram) -> Profile {
+    let mut profile = Profile::default();
+    for func in prog.functions() {
+      let key: *const BBFunction = func;
+      let counts = self.blocks.get(&key);
+      let mut func_profile = FunctionProfile {
+        calls: self.calls.get(&key).copied().unwrap_or(0),
+        source: func.pos.and_then(|p| p.source),
+        ..FunctionProfile::default()
+      };
+      for (idx, block) in func.blocks.iter().enumerate() {
+        let count = counts.map_or(0, |c| c[idx]);
+        func_profile.dyn_inst += count * block.instrs.len() as u64;
+        for instr in &block.instrs {
+          *func_profile.opcodes.entry(opcode(instr)).or_insert(0) += count;
+        }
+        func_profile.blocks.push(BlockProfile {
+          label: block.label.clone(),
+          source: block
+            .label_pos
+            .into_iter()
+            .chain(block.instrs.iter().filter_map(Instruction::get_pos))
+            .find_map(|p| p.source),
+          size: block.instrs.len(),
+          count,
+        });
+      }
+      // Opcodes in blocks that never ran aren't interesting
+      func_profile.opcodes.retain(|_, count| *count != 0);
+
+      profile.total_dyn_inst += func_profile.dyn_inst;
+      for (op, count) in &func_profile.opcodes {
+        *profile.opcodes.entry(op.clone()).or_insert(0) += count;
+      }
+      profile.functions.insert(func.name.clone(), func_profile);
+    }
+    profile
+  }
+}
+
+/// How a program used the heap over a single run, collected with [`crate::interp::Options::collect_memory_profile`]. Every value on the heap counts as the 8 bytes it takes in release builds.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MemoryProfile {
+  /// The most bytes which were allocated at once
+  pub peak_bytes: usize,
+  /// The number of instructions which had run when [`MemoryProfile::peak_bytes`] was first reached, counted to the end of the basic block it was reached in
+  pub peak_at: u64,
+  /// The number of allocations made
+  pub allocations: u64,
+  /// The number of bytes allocated over the whole run, counting memory again each time it is reused
+  pub total_bytes: u64,
+  /// The number of allocations made of each size in bytes
+  pub sizes: BTreeMap<usize, u64>,
+  /// The bytes allocated over the course of the run, in order
+  pub timeline: Vec<TimelinePoint>,
+  /// Every instruction which allocated, with the most bytes first
+  pub sites: Vec<SiteProfile>,
+  // The bytes allocated by each call stack, for MemoryProfile::folded
+  #[serde(skip)]
+  stacks: BTreeMap<String, u64>,
+}
+
+/// A point of [`MemoryProfile::timeline`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TimelinePoint {
+  /// The number of instructions which had run, counted to the end of the basic block they were in. Each point covers a stretch of the run starting here, and the stretches get longer as the run does so that there are never more than about a thousand points
+  pub instructions: u64,
+  /// The bytes which were allocated after the last allocation or free since the point before this one
+  pub live_bytes: usize,
+  /// The most bytes which were allocated at once since the point before this one
+  pub peak_bytes: usize,
+}
+
+/// The part of a [`MemoryProfile`] for a single `alloc`
+#[derive(Debug, Clone, Serialize)]
+pub struct SiteProfile {
+  /// The function the `alloc` is in
+  pub function: String,
+  /// Where the `alloc` is, like `Line 9, Column 3 of @main`
+  pub allocated_at: String,
+  /// The number of allocations it made
+  pub allocations: u64,
+  /// The number of bytes it allocated over the whole run
+  pub bytes: u64,
+}
+
+impl MemoryProfile {
+  /// The bytes allocated by each call stack in the folded format that flame graph tools like `flamegraph.pl` and `inferno-flamegraph` read. Each line is the functions which were running from the outermost in, then the `alloc` the bytes came from, then the number of bytes, like `main;make_list;alloc at Line 4, Column 3 72`
+  #[must_use]
+  pub fn folded(&self) -> String {
+    self
+      .stacks
+      .iter()
+      .map(|(stack, bytes)| format!("{stack} {bytes}\n"))
+      .collect()
+  }
+}
+
+// Where an allocation was made, as the function it is in and the block and instruction within that function
+type SiteKey = (*const BBFunction, usize, usize);
+
+// The timeline is kept to about this many points by merging neighbouring points as it grows, so that long runs don't make huge profiles
+const TIMELINE_POINTS: usize = 1024;
+
+pub(crate) struct MemoryProfiler<'a> {
+  // The number of instructions in every block which has been entered
+  clock: u64,
+  // The functions which are running, from the outermost
+  stack: Vec<&'a BBFunction>,
+  // How many instructions each point of the timeline covers
+  stride: u64,
+  profile: MemoryProfile,
+  sites: FxHashMap<SiteKey, SiteProfile>,
+  stacks: FxHashMap<(Vec<*const BBFunction>, SiteKey), u64>,
+  // The name of every function which has been entered
+  names: FxHashMap<*const BBFunction, &'a str>,
+}
+
+impl<'a> MemoryProfiler<'a> {
+  // A profiler for a heap which already has ```live_bytes``` allocated
+  pub(crate) fn new(live_bytes: usize) -> Self {
+    Self {
+      clock: 0,
+      stack: Vec::new(),
+      stride: 1,
+      profile: MemoryProfile {
+        peak_bytes: live_bytes,
+        ..MemoryProfile::default()
+      },
+      sites: FxHashMap::default(),
+      stacks: FxHashMap::default(),
+      names: FxHashMap::default(),
+    }
+  }
+
+  pub(crate) fn enter_function(&mut self, func: &'a BBFunction) {
+    self.names.entry(func).or_insert(&func.name);
+    self.stack.push(func);
+  }
+
+  pub(crate) fn exit_function(&mut self) {
+    self.stack.pop();
+  }
+
+  pub(crate) fn enter_block(&mut self, func: &BBFunction, block_idx: usize) {
+    self.clock += func.blocks[block_idx].instrs.len() as u64;
+  }
+
+  // Records an allocation of ```bytes``` by instruction ```instr``` of block ```block``` of ```func```, which ```describe``` says where it is, after which ```live_bytes``` are allocated
+  pub(crate) fn alloc(
+    &mut self,
+    (func, block, instr): (&BBFunction, usize, usize),
+    bytes: usize,
+    live_bytes: usize,
+    describe: impl FnOnce() -> String,
+  ) {
+    self.profile.allocations += 1;
+    self.profile.total_bytes += bytes as u64;
+    *self.profile.sizes.entry(bytes).or_insert(0) += 1;
+
+    let key: SiteKey = (func, block, instr);
+    let site = self.sites.entry(key).or_insert_with(|| SiteProfile {
+      function: func.name.clone(),
+      allocated_at: describe(),
+      allocations: 0,
+      bytes: 0,
+    });
+    site.allocations += 1;
+    site.bytes += bytes as u64;
+    let stack = self.stack.iter().map(|f| *f as *const BBFunction).collect();
+    *self.stacks.entry((stack, key)).or_insert(0) += bytes as u64;
+
+    self.record(live_bytes);
+  }
+
+  // Records a free, after which ```live_bytes``` are allocated
+  pub(crate) fn free(&mut self, live_bytes: usize) {
+    self.record(live_bytes);
+  }
+
+  fn record(&mut self, live_bytes: usize) {
+    if live_bytes > self.profile.peak_bytes {
+      self.profile.peak_bytes = live_bytes;
+      self.profile.peak_at = self.clock;
+    }
+    let timeline = &mut self.profile.timeline;
+    let bucket = self.clock / self.stride * self.stride;
+    match timeline.last_mut() {
+      Some(last) if last.instructions == bucket => {
+        last.live_bytes = live_bytes;
+        last.peak_bytes = last.peak_bytes.max(live_bytes);
+      }
+      _ => timeline.push(TimelinePoint {
+        instructions: bucket,
+        live_bytes,
+        peak_bytes: live_bytes,
+      }),
+    }
+    if timeline.len() > TIMELINE_POINTS {
+      // Twice as many instructions go into each point from now on, so the points so far are merged to match
+      self.stride *= 2;
+      let mut merged: Vec<TimelinePoint> = Vec::with_capacity(TIMELINE_POINTS / 2 + 1);
+      for point in timeline.drain(..) {
+        let instructions = point.instructions / self.stride * self.stride;
+        match merged.last_mut() {
+          Some(last) if last.instructions == instructions => {
+            last.live_bytes = point.live_bytes;
+            last.peak_bytes = last.peak_bytes.max(point.peak_bytes);
+          }
+          _ => merged.push(TimelinePoint {
+            instructions,
+            ..point
+          }),
+        }
+      }
+      *timeline = merged;
+    }
+  }
+
+  pub(crate) fn finish(self) -> MemoryProfile {
+    let mut profile = self.profile;
+    for ((stack, key), bytes) in self.stacks {
+      let mut frames: Vec<&str> = stack.iter().map(|f| self.names[f]).collect();
+      let site = format!("alloc at {}", self.sites[&key].allocated_at);
+      frames.push(&site);
+      profile.stacks.insert(frames.join(";"), bytes);
+    }
+    profile.sites = self.sites.into_values().collect();
+    profile.sites.sort_by(|a, b| {
+      b.bytes
+        .cmp(&a.bytes)
+        .then_with(|| a.allocated_at.cmp(&b.allocated_at))
+    });
+    profile
+  }
+}
@@ -0,0 +1,226 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use serde::{Deserialize, Serialize};
+
+use crate::basic_block::BBProgram;
+use crate::error::InterpError;
+use crate::interp::{Pointer, RuntimeValue};
+use crate::intrinsic::{Intrinsic, IntrinsicResult};
+
+/// Everything a run of a program got from outside of it, so that the run can be made again exactly with [`Recording::replay`].
+///
+/// This is the arguments of main along with every call to an intrinsic in the order they were made, with the values the intrinsic was given and what it gave back. Intrinsics like `rand` or ones which read input are what make a program do something different from one run to the next, so replaying what they gave back makes the run go the same way as long as the program and the options it is run with are the same. This is how `--record` and `--replay` work. Recordings are JSON, with floats saved by their bits so that every value comes back exactly.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recording {
+  /// The arguments main was run with
+  pub args: Vec<String>,
+  // The calls to intrinsics in the order they were made
+  calls: Vec<Call>,
+}
+
+// A call to an intrinsic and what it gave back, which is the message of its error if it failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Call {
+  name: String,
+  args: Vec<Saved>,
+  result: Result<Option<Saved>, String>,
+}
+
+// A RuntimeValue in a form which can be saved and compared exactly, which floats and strings can't be as they are
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum Saved {
+  Int(i64),
+  Bool(bool),
+  Float(u64),
+  Char(char),
+  Str(String),
+  Pointer(Pointer),
+  Thread(u32),
+}
+
+impl From<&RuntimeValue> for Saved {
+  fn from(value: &RuntimeValue) -> Self {
+    match value {
+      RuntimeValue::Int(i) => Self::Int(*i),
+      RuntimeValue::Bool(b) => Self::Bool(*b),
+      RuntimeValue::Float(f) => Self::Float(f.to_bits()),
+      RuntimeValue::Char(c) => Self::Char(*c),
+      RuntimeValue::Str(s) => Self::Str(s.to_string()),
+      RuntimeValue::Pointer(p) => Self::Pointer(*p),
+      RuntimeValue::Thread(t) => Self::Thread(*t),
+    }
+  }
+}
+
+impl From<&Saved> for RuntimeValue {
+  fn from(value: &Saved) -> Self {
+    match value {
+      Saved::Int(i) => Self::Int(*i),
+      Saved::Bool(b) => Self::Bool(*b),
+      Saved::Float(bits) => Self::Float(f64::from_bits(*bits)),
+      Saved::Char(c) => Self::Char(*c),
+      Saved::Str(s) => Self::Str(s.as_str().into()),
+      Saved::Pointer(p) => Self::Pointer(*p),
+      Saved::Thread(t) => Self::Thread(*t),
+    }
+  }
+}
+
+fn save_all(values: &[RuntimeValue]) -> Vec<Saved> {
+  values.iter().map(Saved::from).collect()
+}
+
+// Puts an intrinsic in place of each one in ```bbprog``` which runs ```wrap``` with its name, the intrinsic it replaced, and the arguments of each call
+fn wrap_intrinsics(
+  bbprog: &mut BBProgram,
+  wrap: impl Fn(&str, &Intrinsic, &[RuntimeValue]) -> IntrinsicResult + Send + Sync + Clone + 'static,
+) {
+  for (name, intrinsic) in bbprog.intrinsics_mut() {
+    let (inner, name, wrap) = (intrinsic.clone(), name.to_string(), wrap.clone());
+    *intrinsic = Intrinsic::new(
+      intrinsic.args().to_vec(),
+      intrinsic.return_type().cloned(),
+      move |args| wrap(&name, &inner, args),
+    );
+  }
+}
+
+impl Recording {
+  /// Replaces every intrinsic of ```bbprog``` with one which gives back what the next call in the recording did instead of running, so that running main with [`Recording::args`] goes the same way as the recorded run. A call to a different intrinsic or with different arguments than the recording has next means that the run has gone another way, which stops the program with an error.
+  pub fn replay(&self, bbprog: &mut BBProgram) {
+    let calls = Arc::new(Mutex::new(VecDeque::from(self.calls.clone())));
+    wrap_intrinsics(bbprog, move |name, _, args| {
+      let next = calls
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .pop_front();
+      let mismatch = |recorded: &str| {
+        Box::new(InterpError::ReplayMismatch(format!(
+          "it called @{name} with {} where the recording {recorded}",
+          show(&save_all(args))
+        )))
+      };
+      match next {
+        None => Err(mismatch("had no more calls")),
+        Some(call) if call.name != name || call.args != save_all(args) => Err(mismatch(&format!(
+          "called @{} with {}",
+          call.name,
+          show(&call.args)
+        ))),
+        Some(call) => match call.result {
+          Ok(value) => Ok(value.as_ref().map(RuntimeValue::from)),
+          Err(message) => Err(message.into()),
+        },
+      }
+    });
+  }
+
+  /// Saves the recording to ```path``` as JSON
+  pub fn save(&self, path: &Path) -> Result<(), InterpError> {
+    File::create(path)
+      .and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()
+      })
+      .map_err(|e| InterpError::RecordingOutput(path.display().to_string(), Box::new(e)))
+  }
+
+  /// Loads a recording which was saved with [`Recording::save`]
+  pub fn load(path: &Path) -> Result<Self, InterpError> {
+    File::open(path)
+      .and_then(|file| Ok(serde_json::from_reader(BufReader::new(file))?))
+      .map_err(|e| InterpError::RecordingInput(path.display().to_string(), Box::new(e)))
+  }
+}
+
+// The arguments of a call as they would be printed
+fn show(args: &[Saved]) -> String {
+  if args.is_empty() {
+    return "no arguments".to_string();
+  }
+  let args: Vec<String> = args
+    .iter()
+    .map(|a| format!("`{}`", RuntimeValue::from(a)))
+    .collect();
+  args.join(", ")
+}
+
+/// Keeps track of the calls to the intrinsics of a program as it runs, to make a [`Recording`] of the run.
+///
+/// ```
+/// use std::sync::atomic::{AtomicI64, Ordering};
+/// use brilirs::basic_block::BBProgram;
+/// use brilirs::interp::{self, RuntimeValue};
+/// use brilirs::intrinsic::Intrinsic;
+/// use brilirs::recording::Recorder;
+/// use bril_rs::Type;
+///
+/// let prog = bril2json::parse_abstract_program_from_str("@main { x: int = call @next; y: int = call @next; print x y; }", false).unwrap();
+/// let program = || {
+///   // Gives back something different every time the program is run
+///   static COUNTER: AtomicI64 = AtomicI64::new(0);
+///   let mut bbprog = BBProgram::new(prog.clone().try_into().unwrap()).unwrap();
+///   bbprog.add_intrinsic("next", Intrinsic::new(vec![], Some(Type::Int), |_| {
+///     Ok(Some(RuntimeValue::Int(COUNTER.fetch_add(1, Ordering::Relaxed))))
+///   }));
+///   bbprog
+/// };
+///
+/// let mut bbprog = program();
+/// let recorder = Recorder::new(&mut bbprog);
+/// let mut out = Vec::new();
+/// interp::execute_main(&bbprog, &mut out, &[], false).unwrap();
+/// assert_eq!(String::from_utf8(out).unwrap(), "0 1\n");
+///
+/// let recording = recorder.finish(Vec::new());
+/// let mut bbprog = program();
+/// recording.replay(&mut bbprog);
+/// let mut out = Vec::new();
+/// interp::execute_main(&bbprog, &mut out, &recording.args, false).unwrap();
+/// assert_eq!(String::from_utf8(out).unwrap(), "0 1\n");
+/// ```
+#[derive(Debug)]
+pub struct Recorder {
+  calls: Arc<Mutex<Vec<Call>>>,
+}
+
+impl Recorder {
+  /// Starts recording every call to the intrinsics of ```bbprog```, which are wrapped so that what they are given and give back is kept. Intrinsics added after this aren't recorded
+  pub fn new(bbprog: &mut BBProgram) -> Self {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let recorded = calls.clone();
+    wrap_intrinsics(bbprog, move |name, intrinsic, args| {
+      let result = intrinsic.call(args);
+      recorded
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(Call {
+          name: name.to_string(),
+          args: save_all(args),
+          result: match &result {
+            Ok(value) => Ok(value.as_ref().map(Saved::from)),
+            Err(e) => Err(e.to_string()),
+          },
+        });
+      result
+    });
+    Self { calls }
+  }
+
+  /// The recording of a run of main with ```args```, with every call made so far
+  pub fn finish(&self, args: Vec<String>) -> Recording {
+    Recording {
+      args,
+      calls: self
+        .calls
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone(),
+    }
+  }
+}
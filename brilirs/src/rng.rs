@@ -0,0 +1,31 @@
+// splitmix64, which is fine starting from any seed including zero. Everything random in a run comes from one of these starting at Options::seed, so that every run with the same seed goes the same way
+#[derive(Clone, Copy)]
+pub struct Rng {
+  pub state: u64,
+}
+
+impl Rng {
+  pub const fn new(seed: u64) -> Self {
+    Self { state: seed }
+  }
+
+  pub const fn next(&mut self) -> u64 {
+    self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = self.state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+  }
+
+  // An int from ```lo``` up to but not including ```hi```, which has to be more than ```lo```. Scaling by the size of the range instead of taking the remainder keeps every int about as likely as any other, even when the range is most of an i64
+  pub fn int(&mut self, lo: i64, hi: i64) -> i64 {
+    let size = hi.wrapping_sub(lo) as u64;
+    let offset = ((u128::from(self.next()) * u128::from(size)) >> 64) as u64;
+    lo.wrapping_add(offset as i64)
+  }
+
+  // A float from 0 up to but not including 1, made from the top 53 bits so that it is one of 2^53 evenly spaced floats
+  pub fn float(&mut self) -> f64 {
+    (self.next() >> 11) as f64 / (1_u64 << 53) as f64
+  }
+}
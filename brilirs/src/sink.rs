@@ -0,0 +1,241 @@
+use std::fmt;
+use std::io::Write;
+
+use bril_rs::Type;
+
+use crate::interp::{Environment, RuntimeValue};
+use crate::value::Strings;
+
+/// Where the values of every `print` of a program go.
+///
+/// Anything which implements [`std::io::Write`] is a [`Sink`] which writes each `print` as a line of text, the same as `brili` does, so the interpreter can be pointed at [std::io::stdout] or a `Vec<u8>` as it always could. Hosts which would sooner have the values themselves, like a playground showing them or a test harness checking them, can implement [`Sink`] on their own or wrap a closure in a [`Callback`].
+pub trait Sink {
+  /// Takes the values of a single `print`, in the order they were printed. An error stops the program with [`crate::error::InterpError::IoError`]
+  ///
+  /// # Errors
+  /// Whatever error the sink runs into
+  fn print_values(&mut self, values: &[RuntimeValue]) -> std::io::Result<()>;
+
+  /// Takes a single `print` before its values have been read out of the program. By default this reads them out and passes them to [`Sink::print_values`], which sinks that write text skip to avoid allocating for every `print`
+  ///
+  /// # Errors
+  /// Whatever error the sink runs into
+  fn print_line(&mut self, line: &Printed<'_>) -> std::io::Result<()> {
+    let values: Vec<RuntimeValue> = line.values().collect();
+    self.print_values(&values)
+  }
+}
+
+impl<W: Write> Sink for W {
+  fn print_values(&mut self, values: &[RuntimeValue]) -> std::io::Result<()> {
+    for (i, value) in values.iter().enumerate() {
+      let sep = if i == 0 { "" } else { " " };
+      write!(self, "{sep}{value}")?;
+    }
+    writeln!(self)?;
+    self.flush()
+  }
+
+  fn print_line(&mut self, line: &Printed<'_>) -> std::io::Result<()> {
+    writeln!(self, "{line}")?;
+    self.flush()
+  }
+}
+
+/// A [`Sink`] which calls a closure with the values of each `print`
+///
+/// ```
+/// use brilirs::basic_block::BBProgram;
+/// use brilirs::interp::{self, RuntimeValue};
+/// use brilirs::sink::Callback;
+///
+/// let prog = bril2json::parse_abstract_program_from_str("@main { x: int = const 4; b: bool = const true; print x b; }", false).unwrap();
+/// let bbprog = BBProgram::new(prog.try_into().unwrap()).unwrap();
+/// let mut printed = Vec::new();
+/// let sink = Callback(|values: &[RuntimeValue]| printed.push(values.to_vec()));
+/// interp::execute_main(&bbprog, sink, &[], false).unwrap();
+/// assert_eq!(printed, [[RuntimeValue::Int(4), RuntimeValue::Bool(true)]]);
+/// ```
+pub struct Callback<F: FnMut(&[RuntimeValue])>(pub F);
+
+impl<F: FnMut(&[RuntimeValue])> Sink for Callback<F> {
+  fn print_values(&mut self, values: &[RuntimeValue]) -> std::io::Result<()> {
+    (self.0)(values);
+    Ok(())
+  }
+}
+
+/// A [`Sink`] which writes each `print` as a line of JSON, like `{"op":"print","values":[{"type":"int","value":3}]}`
+///
+/// This is for harnesses which compare the output of interpreters without depending on how each of them formats values as text. Every value has its `type` as it is written in Bril and its `value` as the JSON closest to it: numbers for `int`s, `float`s, and threads, booleans for `bool`s, strings for `char`s and `str`s, and an object with the `base` and `offset` of a pointer. Floats which JSON has no number for are the strings `"Infinity"`, `"-Infinity"`, and `"NaN"`.
+///
+/// ```
+/// use brilirs::basic_block::BBProgram;
+/// use brilirs::interp;
+/// use brilirs::sink::JsonLines;
+///
+/// let prog = bril2json::parse_abstract_program_from_str("@main { x: float = const 0.1; c: char = const 'a'; print x c; }", false).unwrap();
+/// let bbprog = BBProgram::new(prog.try_into().unwrap()).unwrap();
+/// let mut out = Vec::new();
+/// interp::execute_main(&bbprog, JsonLines(&mut out), &[], false).unwrap();
+/// assert_eq!(
+///   String::from_utf8(out).unwrap(),
+///   "{\"op\":\"print\",\"values\":[{\"type\":\"float\",\"value\":0.1},{\"type\":\"char\",\"value\":\"a\"}]}\n"
+/// );
+/// ```
+pub struct JsonLines<W: Write>(pub W);
+
+impl<W: Write> JsonLines<W> {
+  // Writes the event for a `print` of ```values``` along with the name of the type of each of them
+  fn write_event<'v>(
+    &mut self,
+    values: impl Iterator<Item = (String, &'v RuntimeValue)>,
+  ) -> std::io::Result<()> {
+    let values: Vec<serde_json::Value> = values
+      .map(|(ty, value)| serde_json::json!({ "type": ty, "value": json_value(value) }))
+      .collect();
+    serde_json::to_writer(
+      &mut self.0,
+      &serde_json::json!({ "op": "print", "values": values }),
+    )?;
+    writeln!(self.0)?;
+    self.0.flush()
+  }
+}
+
+impl<W: Write> Sink for JsonLines<W> {
+  fn print_values(&mut self, values: &[RuntimeValue]) -> std::io::Result<()> {
+    self.write_event(values.iter().map(|v| (type_name(v).to_string(), v)))
+  }
+
+  // The types of the variables are used over those of the values so that pointers say what they point to
+  fn print_line(&mut self, line: &Printed<'_>) -> std::io::Result<()> {
+    let values: Vec<RuntimeValue> = line.values().collect();
+    self.write_event(line.types().map(ToString::to_string).zip(&values))
+  }
+}
+
+// The name of the type of ```value```, which for a pointer leaves out what it points to since a value doesn't know that
+const fn type_name(value: &RuntimeValue) -> &'static str {
+  match value {
+    RuntimeValue::Int(_) => "int",
+    RuntimeValue::Bool(_) => "bool",
+    RuntimeValue::Float(_) => "float",
+    RuntimeValue::Char(_) => "char",
+    RuntimeValue::Str(_) => "str",
+    RuntimeValue::Pointer(_) => "ptr",
+    RuntimeValue::Thread(_) => "thread",
+  }
+}
+
+fn json_value(value: &RuntimeValue) -> serde_json::Value {
+  match value {
+    RuntimeValue::Int(i) => (*i).into(),
+    RuntimeValue::Bool(b) => (*b).into(),
+    RuntimeValue::Float(f) => serde_json::Number::from_f64(*f).map_or_else(
+      || {
+        let name = if f.is_nan() {
+          "NaN"
+        } else if f.is_sign_positive() {
+          "Infinity"
+        } else {
+          "-Infinity"
+        };
+        name.into()
+      },
+      serde_json::Value::Number,
+    ),
+    RuntimeValue::Char(c) => c.to_string().into(),
+    RuntimeValue::Str(s) => s.as_ref().into(),
+    RuntimeValue::Pointer(p) => serde_json::json!(p),
+    RuntimeValue::Thread(t) => (*t).into(),
+  }
+}
+
+/// A [`Sink`] which writes each `print` as the same line of text as any [`std::io::Write`] does, with each value coloured by its type using ANSI escape codes for reading in a terminal: numbers are cyan, `bool`s yellow, `char`s and `str`s green, and pointers and threads magenta
+pub struct Colored<W: Write>(pub W);
+
+impl<W: Write> Sink for Colored<W> {
+  fn print_values(&mut self, values: &[RuntimeValue]) -> std::io::Result<()> {
+    for (i, value) in values.iter().enumerate() {
+      let sep = if i == 0 { "" } else { " " };
+      let color = match value {
+        RuntimeValue::Int(_) | RuntimeValue::Float(_) => 36,
+        RuntimeValue::Bool(_) => 33,
+        RuntimeValue::Char(_) | RuntimeValue::Str(_) => 32,
+        RuntimeValue::Pointer(_) | RuntimeValue::Thread(_) => 35,
+      };
+      write!(self.0, "{sep}\x1b[{color}m{value}\x1b[0m")?;
+    }
+    writeln!(self.0)?;
+    self.0.flush()
+  }
+}
+
+/// The arguments of a `print` which is being run, passed to [`Sink::print_line`]. It shows as the line of text that `print` writes
+pub struct Printed<'a> {
+  args: &'a [u32],
+  var_types: &'a [Option<Type>],
+  env: &'a Environment,
+  strings: &'a Strings,
+}
+
+impl<'a> Printed<'a> {
+  pub(crate) const fn new(
+    args: &'a [u32],
+    var_types: &'a [Option<Type>],
+    env: &'a Environment,
+    strings: &'a Strings,
+  ) -> Self {
+    Self {
+      args,
+      var_types,
+      env,
+      strings,
+    }
+  }
+
+  // The type of the variable ```arg```. Every variable that can be printed has a type once the program has been type checked
+  const fn ty(&self, arg: u32) -> &'a Type {
+    self.var_types[arg as usize].as_ref().unwrap()
+  }
+
+  /// The number of values being printed
+  #[must_use]
+  pub const fn len(&self) -> usize {
+    self.args.len()
+  }
+
+  /// Whether this is a `print` with no arguments, which prints an empty line
+  #[must_use]
+  pub const fn is_empty(&self) -> bool {
+    self.args.is_empty()
+  }
+
+  /// The types of the values being printed, in order
+  pub fn types(&self) -> impl Iterator<Item = &'a Type> + '_ {
+    self.args.iter().map(|a| self.ty(*a))
+  }
+
+  /// The values being printed, in order
+  pub fn values(&self) -> impl Iterator<Item = RuntimeValue> + '_ {
+    self
+      .args
+      .iter()
+      .map(|a| RuntimeValue::new(self.env.get(a), self.ty(*a), self.strings))
+  }
+}
+
+impl fmt::Display for Printed<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (i, a) in self.args.iter().enumerate() {
+      let sep = if i == 0 { "" } else { " " };
+      write!(
+        f,
+        "{sep}{}",
+        self.env.get(a).display(self.ty(*a), self.strings)
+      )?;
+    }
+    Ok(())
+  }
+}
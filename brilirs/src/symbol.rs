@@ -0,0 +1,53 @@
+use fxhash::FxHashMap;
+
+/// Numbers names so that they can be compared and looked up without hashing strings.
+///
+/// Each distinct name gets the next number, starting from 0, the first time it is interned, so symbols can index straight into a [`Vec`] with one entry per name. [`crate::basic_block::BBProgram`] numbers functions with one of these, and each function numbers its variables and labels with its own, keeping the names around only for error messages, printing, and debugging.
+#[derive(Debug, Default, Clone)]
+pub struct SymbolTable {
+  symbols: FxHashMap<String, u32>,
+  names: Vec<String>,
+}
+
+impl SymbolTable {
+  /// The symbol of ```name```, which is given the next unused symbol if it doesn't have one yet
+  pub fn intern(&mut self, name: &str) -> u32 {
+    if let Some(symbol) = self.symbols.get(name) {
+      return *symbol;
+    }
+    let symbol = self.names.len() as u32;
+    self.symbols.insert(name.to_string(), symbol);
+    self.names.push(name.to_string());
+    symbol
+  }
+
+  /// The symbol of ```name``` if it has been interned
+  pub fn get(&self, name: &str) -> Option<u32> {
+    self.symbols.get(name).copied()
+  }
+
+  /// The name that was interned as ```symbol```
+  pub fn name(&self, symbol: u32) -> &str {
+    &self.names[symbol as usize]
+  }
+
+  /// The number of names which have been interned, which is one more than the largest symbol
+  pub const fn len(&self) -> usize {
+    self.names.len()
+  }
+
+  /// Whether no names have been interned yet
+  pub const fn is_empty(&self) -> bool {
+    self.names.is_empty()
+  }
+
+  /// Every name which has been interned, in the order of their symbols
+  pub fn names(&self) -> &[String] {
+    &self.names
+  }
+
+  /// Every name which has been interned, in the order of their symbols
+  pub fn into_names(self) -> Vec<String> {
+    self.names
+  }
+}
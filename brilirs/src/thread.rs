@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::cli::Schedule;
+use crate::rng::Rng;
+
+// How many instructions a thread runs before the scheduler moves on to another, unless Options::quantum says otherwise
+pub const DEFAULT_QUANTUM: u64 = 100;
+
+// Picks which thread runs next whenever the one running blocks, finishes, or uses up its quantum
+pub struct Scheduler {
+  schedule: Schedule,
+  // The random numbers for Schedule::Random, which only depend on Options::seed so that every run with the same seed interleaves the same way
+  rng: Rng,
+}
+
+impl Scheduler {
+  pub const fn new(schedule: Schedule, seed: u64) -> Self {
+    Self {
+      schedule,
+      rng: Rng::new(seed),
+    }
+  }
+
+  // The next of the ```count``` threads to run after ```current```, out of those which ```runnable``` says can, or None if none of them can. Round robin goes on to the next thread which can run and only comes back to ```current``` if nothing else can, while random picks any of them
+  pub fn next(
+    &mut self,
+    current: usize,
+    count: usize,
+    runnable: impl Fn(usize) -> bool,
+  ) -> Option<usize> {
+    match self.schedule {
+      Schedule::RoundRobin => (1..=count)
+        .map(|i| (current + i) % count)
+        .find(|t| runnable(*t)),
+      Schedule::Random => {
+        let ready: Vec<usize> = (0..count).filter(|t| runnable(*t)).collect();
+        if ready.is_empty() {
+          None
+        } else {
+          let pick = self.rng.next() % ready.len() as u64;
+          Some(ready[pick as usize])
+        }
+      }
+    }
+  }
+}
+
+// A load, store, or free of a value on the heap by a thread, at ```site```
+#[derive(Clone, Copy)]
+pub struct Access<S> {
+  thread: usize,
+  // The thread's own entry in its vector clock when it made the access
+  time: u32,
+  kind: &'static str,
+  site: S,
+}
+
+impl<S: fmt::Display> fmt::Display for Access<S> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "the {} at {} in thread {}",
+      self.kind, self.site, self.thread
+    )
+  }
+}
+
+// Two accesses to the same value where neither thread had joined with the other in between, which could have happened in either order if the threads ran in parallel
+pub struct Race<S> {
+  // Of the value in its allocation
+  pub offset: i32,
+  pub earlier: Access<S>,
+  pub later: Access<S>,
+}
+
+// The last store to a value of the heap, and the loads from it since then which haven't been ordered before each other
+struct Shadow<S> {
+  write: Option<Access<S>>,
+  reads: Vec<Access<S>>,
+}
+
+// Finds data races between threads with vector clocks. Every thread has a clock with an entry for each thread saying how much of that thread it is known to come after, through `spawn` and `join`. Each value of the heap remembers the accesses made to it, and an access races with an earlier one which conflicts with it unless the earlier one came before it by the clock of the thread making it. Sites are whatever the interpreter uses to point at instructions
+pub struct RaceDetector<S> {
+  clocks: Vec<Vec<u32>>,
+  current: usize,
+  // By the base and offset of each value, since bases are never reused. They are kept in order so that the values of an allocation can be found when it is freed
+  shadows: BTreeMap<(u32, i32), Shadow<S>>,
+}
+
+impl<S: Copy> RaceDetector<S> {
+  // A detector for a program with only the main thread, which is running
+  pub fn new() -> Self {
+    Self {
+      clocks: vec![vec![1]],
+      current: 0,
+      shadows: BTreeMap::new(),
+    }
+  }
+
+  // The thread running from now on
+  pub const fn switch(&mut self, thread: usize) {
+    self.current = thread;
+  }
+
+  // Thread ```parent``` spawned ```child```, which is the next thread. Everything the parent did so far comes before everything the child does, but nothing the parent does from now on does
+  pub fn spawn(&mut self, parent: usize, child: usize) {
+    let mut clock = self.clocks[parent].clone();
+    clock.resize(child + 1, 0);
+    clock[child] = 1;
+    self.clocks.push(clock);
+    self.clocks[parent][parent] += 1;
+  }
+
+  // Thread ```joiner``` joined ```joined```, which has finished, so everything that came before the end of ```joined``` comes before what ```joiner``` does next
+  pub fn join(&mut self, joiner: usize, joined: usize) {
+    let (joiner_clock, joined_clock) = if joiner < joined {
+      let (left, right) = self.clocks.split_at_mut(joined);
+      (&mut left[joiner], &right[0])
+    } else {
+      let (left, right) = self.clocks.split_at_mut(joiner);
+      (&mut right[0], &left[joined])
+    };
+    if joiner_clock.len() < joined_clock.len() {
+      joiner_clock.resize(joined_clock.len(), 0);
+    }
+    for (mine, theirs) in joiner_clock.iter_mut().zip(joined_clock) {
+      *mine = (*mine).max(*theirs);
+    }
+  }
+
+  fn access(&self, kind: &'static str, site: S) -> Access<S> {
+    Access {
+      thread: self.current,
+      time: self.clocks[self.current][self.current],
+      kind,
+      site,
+    }
+  }
+
+  // Whether ```access``` comes before whatever the running thread does now
+  fn ordered(&self, access: &Access<S>) -> bool {
+    access.thread == self.current
+      || self.clocks[self.current]
+        .get(access.thread)
+        .is_some_and(|time| access.time <= *time)
+  }
+
+  // A load of the value at ```offset``` of allocation ```base``` by the running thread
+  pub fn read(&mut self, base: u32, offset: i32, site: S) -> Result<(), Race<S>> {
+    let access = self.access("load", site);
+    let shadow = self.shadows.get(&(base, offset));
+    if let Some(write) = shadow.and_then(|s| s.write).filter(|w| !self.ordered(w)) {
+      return Err(Race {
+        offset,
+        earlier: write,
+        later: access,
+      });
+    }
+    let current = self.current;
+    let shadow = self.shadows.entry((base, offset)).or_insert(Shadow {
+      write: None,
+      reads: Vec::new(),
+    });
+    // Only the latest load by each thread is kept, since a store which races with an earlier one races with it as well
+    shadow.reads.retain(|r| r.thread != current);
+    shadow.reads.push(access);
+    Ok(())
+  }
+
+  // A store to the value at ```offset``` of allocation ```base``` by the running thread
+  pub fn write(&mut self, base: u32, offset: i32, site: S) -> Result<(), Race<S>> {
+    let access = self.access("store", site);
+    if let Some(shadow) = self.shadows.get(&(base, offset)) {
+      self.check_conflicts(shadow, offset, access)?;
+    }
+    self.shadows.insert(
+      (base, offset),
+      Shadow {
+        write: Some(access),
+        reads: Vec::new(),
+      },
+    );
+    Ok(())
+  }
+
+  // A free of allocation ```base``` by the running thread, which conflicts with every access to any of its values. Nothing can use them afterwards, so they are forgotten
+  pub fn free(&mut self, base: u32, site: S) -> Result<(), Race<S>> {
+    let access = self.access("free", site);
+    let values = (base, i32::MIN)..=(base, i32::MAX);
+    for (&(_, offset), shadow) in self.shadows.range(values.clone()) {
+      self.check_conflicts(shadow, offset, access)?;
+    }
+    let keys: Vec<(u32, i32)> = self.shadows.range(values).map(|(k, _)| *k).collect();
+    for key in keys {
+      self.shadows.remove(&key);
+    }
+    Ok(())
+  }
+
+  // Every access to the value at ```offset``` conflicts with ```access```, which writes to it, so they all have to come before it
+  fn check_conflicts(
+    &self,
+    shadow: &Shadow<S>,
+    offset: i32,
+    access: Access<S>,
+  ) -> Result<(), Race<S>> {
+    shadow
+      .write
+      .iter()
+      .chain(&shadow.reads)
+      .find(|a| !self.ordered(a))
+      .map_or(Ok(()), |earlier| {
+        Err(Race {
+          offset,
+          earlier: *earlier,
+          later: access,
+        })
+      })
+  }
+}
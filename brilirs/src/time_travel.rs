@@ -0,0 +1,350 @@
+use std::collections::BTreeMap;
+
+use bril_rs::{EffectOps, Instruction, Type};
+use fxhash::FxHashMap;
+
+use crate::basic_block::BBFunction;
+use crate::interp::{Environment, Heap};
+use crate::value::{Pointer, Value};
+
+// A function as the history remembers it, so that places in it can still be shown once it has returned
+pub struct FunctionView {
+  pub name: String,
+  // The label and instructions of each block
+  pub blocks: Vec<(Option<String>, Vec<Instruction>)>,
+  pub var_names: Vec<String>,
+  pub var_types: Vec<Option<Type>>,
+}
+
+impl FunctionView {
+  fn new(func: &BBFunction) -> Self {
+    Self {
+      name: func.name.clone(),
+      blocks: func
+        .blocks
+        .iter()
+        .map(|b| (b.label.clone(), b.instrs.clone()))
+        .collect(),
+      var_names: func.var_names.clone(),
+      var_types: func.var_types.clone(),
+    }
+  }
+}
+
+// Something which changed the state of the program, or the instruction about to run which marks where the changes of one step end and the next begin
+enum Event {
+  // Instruction ```instr``` of block ```block``` of the innermost call is about to run, after ```allocations``` allocations had been made. ```entered``` is whether it is the first instruction of the call
+  Step {
+    func: u32,
+    block: u32,
+    instr: u32,
+    allocations: usize,
+    entered: bool,
+  },
+  // Variable ```var``` of the innermost call went from ```old``` to ```new```, where None is a variable which isn't defined
+  Write {
+    var: u32,
+    old: Option<Value>,
+    new: Option<Value>,
+  },
+  Store {
+    key: Pointer,
+    old: Option<Value>,
+  },
+  // Allocation ```base```, which ```number``` allocations were made before, was freed, holding ```values``` of ```ty```
+  Free {
+    base: usize,
+    number: usize,
+    ty: Type,
+    values: Vec<Option<Value>>,
+  },
+  Call,
+  // The innermost call returned, with ```vars``` as the values of its variables
+  Return {
+    func: u32,
+    vars: Vec<Option<Value>>,
+  },
+}
+
+// A call which hasn't returned, as of the last event
+struct Frame {
+  func: u32,
+  vars: Vec<Option<Value>>,
+  // The block and instruction which this call ran last, whose writes haven't been logged yet
+  last: Option<(usize, usize)>,
+}
+
+/// The state of the program at a step of its history, which [`History::state_at`] works out
+pub struct PastState {
+  // The function and variables of each call which hadn't returned, innermost last
+  pub frames: Vec<(u32, Vec<Option<Value>>)>,
+  // Every allocation which hadn't been freed by its base, with how many allocations were made before it and the type of its values
+  pub heap: BTreeMap<usize, (usize, Type, Vec<Option<Value>>)>,
+}
+
+/// The last write to a variable before some step, which [`History::last_write`] finds
+pub enum LastWrite {
+  // The variable was never written in the call
+  Never,
+  // The variable was passed in as an argument of the call
+  Argument(Option<Value>),
+  // The variable was written by the instruction at ```step```
+  Step(usize, Option<Value>),
+}
+
+/// An append-only log of every change the program has made to its variables and heap, along with every call and return, which lets the debugger look at any earlier step of the program.
+///
+/// Only the changes are logged, so the state at an earlier step is worked out by undoing them one at a time from the state the program is in now. A store logs the value it overwrote and a free logs the allocation it freed for that reason. The log takes up memory in proportion to the number of instructions which have run, which is why it is only kept with `--time-travel`.
+#[derive(Default)]
+pub struct History {
+  events: Vec<Event>,
+  // Where each step is in ```events```, in the order they ran
+  steps: Vec<usize>,
+  functions: Vec<FunctionView>,
+  function_numbers: FxHashMap<String, u32>,
+  // The calls which haven't returned, innermost last
+  frames: Vec<Frame>,
+  entered_function: bool,
+}
+
+impl History {
+  pub fn enter_function(&mut self, func: &BBFunction) {
+    let next = self.functions.len() as u32;
+    let number = *self
+      .function_numbers
+      .entry(func.name.clone())
+      .or_insert(next);
+    if number == next {
+      self.functions.push(FunctionView::new(func));
+    }
+    self.events.push(Event::Call);
+    self.frames.push(Frame {
+      func: number,
+      vars: vec![None; func.num_of_vars as usize],
+      last: None,
+    });
+    self.entered_function = true;
+  }
+
+  pub fn exit_function(&mut self) {
+    if let Some(frame) = self.frames.pop() {
+      self.events.push(Event::Return {
+        func: frame.func,
+        vars: frame.vars,
+      });
+    }
+  }
+
+  // Logs the writes of the instruction the innermost call ran last, which have happened by now, and then instruction ```instr_idx``` of block ```block_idx```, which is about to run
+  pub fn record(
+    &mut self,
+    func: &BBFunction,
+    block_idx: usize,
+    instr_idx: usize,
+    env: &Environment,
+    heap: &Heap<'_>,
+  ) {
+    let Some(frame) = self.frames.last_mut() else {
+      return;
+    };
+    match frame.last.take() {
+      // A guard which fails goes back to the variables from before the speculation, so anything could have changed
+      Some((b, i))
+        if matches!(
+          func.blocks[b].instrs[i],
+          Instruction::Effect {
+            op: EffectOps::Guard,
+            ..
+          }
+        ) =>
+      {
+        for var in 0..frame.vars.len() {
+          let changed = match (frame.vars[var], env.lookup(var)) {
+            (Some(old), Some(new)) => !old.same(new),
+            (old, new) => old.is_some() != new.is_some(),
+          };
+          if changed {
+            log_write(&mut frame.vars, var, env, &mut self.events);
+          }
+        }
+      }
+      Some((b, i)) => {
+        let numified = &func.blocks[b].numified_instrs[i];
+        for dest in numified.dest.iter().chain(&numified.extra_dests) {
+          log_write(&mut frame.vars, *dest as usize, env, &mut self.events);
+        }
+      }
+      // Nothing has run in this call yet, so the variables which are defined are its arguments
+      None => {
+        for var in 0..frame.vars.len() {
+          if env.lookup(var).is_some() {
+            log_write(&mut frame.vars, var, env, &mut self.events);
+          }
+        }
+      }
+    }
+    frame.last = Some((block_idx, instr_idx));
+
+    self.steps.push(self.events.len());
+    self.events.push(Event::Step {
+      func: frame.func,
+      block: block_idx as u32,
+      instr: instr_idx as u32,
+      allocations: heap.allocations_made(),
+      entered: std::mem::take(&mut self.entered_function),
+    });
+    let block = &func.blocks[block_idx];
+    let args = &block.numified_instrs[instr_idx].args;
+    let pointer = || env.lookup(args[0] as usize).map(Value::as_pointer);
+    match &block.instrs[instr_idx] {
+      Instruction::Effect {
+        op: EffectOps::Store,
+        ..
+      } => {
+        if let Some(key) = pointer() {
+          self.events.push(Event::Store {
+            key,
+            old: heap.peek(key),
+          });
+        }
+      }
+      Instruction::Effect {
+        op: EffectOps::Free,
+        ..
+      } => {
+        if let Some(key) = pointer() {
+          let base = key.base as usize;
+          if let (Some(number), Some((ty, values))) = (heap.number(base), heap.allocation(base)) {
+            self.events.push(Event::Free {
+              base,
+              number,
+              ty: ty.clone(),
+              values,
+            });
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+
+  // The number of steps which have been logged, the last of which is the instruction the program is paused at
+  pub const fn len(&self) -> usize {
+    self.steps.len()
+  }
+
+  // The function, block, and instruction of ```step```, and whether it is the first instruction of a call
+  pub fn place(&self, step: usize) -> (&FunctionView, usize, usize, bool) {
+    match self.events[self.steps[step]] {
+      Event::Step {
+        func,
+        block,
+        instr,
+        entered,
+        ..
+      } => (
+        &self.functions[func as usize],
+        block as usize,
+        instr as usize,
+        entered,
+      ),
+      _ => unreachable!("steps only point at steps"),
+    }
+  }
+
+  pub fn function(&self, number: u32) -> &FunctionView {
+    &self.functions[number as usize]
+  }
+
+  // The state right before ```step``` ran, undoing every event since then from the state of the program now, whose heap is ```heap```
+  pub fn state_at(&self, step: usize, heap: &Heap<'_>) -> PastState {
+    let mut state = PastState {
+      frames: self
+        .frames
+        .iter()
+        .map(|f| (f.func, f.vars.clone()))
+        .collect(),
+      heap: heap
+        .live_allocations()
+        .filter_map(|(base, ty, values)| Some((base, (heap.number(base)?, ty.clone(), values))))
+        .collect(),
+    };
+    let start = self.steps[step];
+    for event in self.events[start..].iter().rev() {
+      match event {
+        Event::Step { .. } => {}
+        Event::Write { var, old, .. } => {
+          if let Some((_, vars)) = state.frames.last_mut() {
+            vars[*var as usize] = *old;
+          }
+        }
+        Event::Store { key, old } => {
+          if let Some((_, _, values)) = state.heap.get_mut(&(key.base as usize)) {
+            if let Some(value) = values.get_mut(key.offset as usize) {
+              *value = *old;
+            }
+          }
+        }
+        Event::Free {
+          base,
+          number,
+          ty,
+          values,
+        } => {
+          state
+            .heap
+            .insert(*base, (*number, ty.clone(), values.clone()));
+        }
+        Event::Call => {
+          state.frames.pop();
+        }
+        Event::Return { func, vars } => state.frames.push((*func, vars.clone())),
+      }
+    }
+    if let Event::Step { allocations, .. } = self.events[start] {
+      state.heap.retain(|_, (number, _, _)| *number < allocations);
+    }
+    state
+  }
+
+  // The last write to ```var``` of the call which is innermost at ```step``` before it ran
+  pub fn last_write(&self, step: usize, var: usize) -> LastWrite {
+    let mut found = None;
+    // How many calls deeper than the one ```step``` is in each event is
+    let mut depth = 0_usize;
+    let mut step_idx = step;
+    for event in self.events[..self.steps[step]].iter().rev() {
+      match event {
+        Event::Step { .. } => {
+          step_idx -= 1;
+          if depth == 0 {
+            if let Some(new) = found {
+              return LastWrite::Step(step_idx, new);
+            }
+          }
+        }
+        Event::Write { var: v, new, .. } if depth == 0 && found.is_none() && *v as usize == var => {
+          found = Some(*new);
+        }
+        Event::Return { .. } => depth += 1,
+        Event::Call if depth == 0 => {
+          return found.map_or(LastWrite::Never, LastWrite::Argument);
+        }
+        Event::Call => depth -= 1,
+        _ => {}
+      }
+    }
+    found.map_or(LastWrite::Never, LastWrite::Argument)
+  }
+}
+
+// Logs that variable ```var``` of a call whose variables were ```vars``` now has the value it has in ```env```
+fn log_write(vars: &mut [Option<Value>], var: usize, env: &Environment, events: &mut Vec<Event>) {
+  let new = env.lookup(var);
+  let old = std::mem::replace(&mut vars[var], new);
+  events.push(Event::Write {
+    var: var as u32,
+    old,
+    new,
+  });
+}
@@ -0,0 +1,172 @@
+use std::fmt::Display;
+
+use bril_rs::{Attrs, Code, EffectOps, Instruction, Type, ValueOps};
+use fxhash::{FxHashMap, FxHashSet};
+
+use crate::basic_block::{BBFunction, BasicBlock};
+
+// Traces are cut off after this many blocks so that a hot loop with a long body doesn't produce an unbounded trace
+const MAX_TRACE_BLOCKS: usize = 128;
+
+/// A straight-line recording of a hot path through a function, written with the instructions of the [speculation extension](https://capra.cs.cornell.edu/bril/lang/spec.html)
+///
+/// The trace starts with `speculate` and every branch along the recorded path is replaced with a `guard` that falls back to ```head``` if the program would have gone the other way. If every guard holds, the trace ends with `commit` and a `jmp` to the block where the recording stopped.
+#[derive(Debug, Clone)]
+pub struct Trace {
+  /// The function the trace was recorded in
+  pub func: String,
+  /// The label of the block the trace starts at
+  pub head: String,
+  /// The instructions of the trace
+  pub instrs: Vec<Code>,
+}
+
+impl Display for Trace {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "# trace of @{} starting at .{}", self.func, self.head)?;
+    for code in &self.instrs {
+      writeln!(f, "{code}")?;
+    }
+    Ok(())
+  }
+}
+
+struct Recording {
+  head: usize,
+  blocks: Vec<usize>,
+}
+
+// A block can be part of a trace as long as it stays within the function and has no side effects which would survive a failed guard
+fn is_traceable(block: &BasicBlock) -> bool {
+  !block.exit.is_empty()
+    && block.instrs.iter().all(|i| match i {
+      Instruction::Constant { .. } => true,
+      Instruction::Value { op, .. } => {
+        !matches!(op, ValueOps::Call | ValueOps::Alloc | ValueOps::Phi)
+      }
+      Instruction::Effect { op, .. } => {
+        matches!(op, EffectOps::Jump | EffectOps::Branch | EffectOps::Nop)
+      }
+    })
+}
+
+/// Counts how often each labelled block is entered and records a [`Trace`] starting at each block the first time it is entered ```threshold``` times
+pub(crate) struct Tracer {
+  threshold: u32,
+  counts: FxHashMap<(String, usize), u32>,
+  recording: Option<Recording>,
+  // Blocks which already appear in a trace of their function don't start new ones
+  covered: FxHashSet<(String, usize)>,
+  num_fresh: usize,
+  pub(crate) traces: Vec<Trace>,
+}
+
+impl Tracer {
+  pub(crate) fn new(threshold: u32) -> Self {
+    Self {
+      threshold,
+      counts: FxHashMap::default(),
+      recording: None,
+      covered: FxHashSet::default(),
+      num_fresh: 0,
+      traces: Vec::new(),
+    }
+  }
+
+  // Since traceable blocks contain no calls or returns, the recording always stays within ```func```
+  pub(crate) fn enter_block(&mut self, func: &BBFunction, block_idx: usize) {
+    let block = &func.blocks[block_idx];
+    if let Some(recording) = self.recording.as_mut() {
+      if block_idx != recording.head
+        && recording.blocks.len() < MAX_TRACE_BLOCKS
+        && is_traceable(block)
+      {
+        recording.blocks.push(block_idx);
+        return;
+      }
+      let recording = self.recording.take().unwrap();
+      self.finish(func, recording, block_idx);
+    }
+
+    let key = (func.name.clone(), block_idx);
+    if block.label.is_none() || !is_traceable(block) || self.covered.contains(&key) {
+      return;
+    }
+    let count = self.counts.entry(key).or_insert(0);
+    *count += 1;
+    if *count == self.threshold {
+      self.recording = Some(Recording {
+        head: block_idx,
+        blocks: vec![block_idx],
+      });
+    }
+  }
+
+  fn finish(&mut self, func: &BBFunction, recording: Recording, end: usize) {
+    // Blocks which are reached from another block always start with a label
+    let label_of = |idx: usize| func.blocks[idx].label.clone().unwrap();
+    let head = label_of(recording.head);
+    self
+      .covered
+      .extend(recording.blocks.iter().map(|idx| (func.name.clone(), *idx)));
+
+    let mut instrs = vec![effect(EffectOps::Speculate, Vec::new(), Vec::new())];
+    for (i, idx) in recording.blocks.iter().enumerate() {
+      let next = recording.blocks.get(i + 1).copied().unwrap_or(end);
+      let block = &func.blocks[*idx];
+      for instr in &block.instrs {
+        match instr {
+          Instruction::Effect {
+            op: EffectOps::Jump,
+            ..
+          } => {}
+          Instruction::Effect {
+            op: EffectOps::Branch,
+            args,
+            ..
+          } => {
+            let cond = if block.exit[0] == next {
+              args[0].clone()
+            } else {
+              let negated = format!("__trace_not_{}", self.num_fresh);
+              self.num_fresh += 1;
+              instrs.push(Code::Instruction(Instruction::Value {
+                args: vec![args[0].clone()],
+                dest: negated.clone(),
+                funcs: Vec::new(),
+                labels: Vec::new(),
+                op: ValueOps::Not,
+                pos: None,
+                op_type: Type::Bool,
+                extra_dests: Vec::new(),
+                attrs: Attrs::new(),
+              }));
+              negated
+            };
+            instrs.push(effect(EffectOps::Guard, vec![cond], vec![head.clone()]));
+          }
+          _ => instrs.push(Code::Instruction(instr.clone())),
+        }
+      }
+    }
+    instrs.push(effect(EffectOps::Commit, Vec::new(), Vec::new()));
+    instrs.push(effect(EffectOps::Jump, Vec::new(), vec![label_of(end)]));
+
+    self.traces.push(Trace {
+      func: func.name.clone(),
+      head,
+      instrs,
+    });
+  }
+}
+
+fn effect(op: EffectOps, args: Vec<String>, labels: Vec<String>) -> Code {
+  Code::Instruction(Instruction::Effect {
+    args,
+    funcs: Vec::new(),
+    labels,
+    op,
+    pos: None,
+    attrs: Attrs::new(),
+  })
+}
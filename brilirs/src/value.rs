@@ -0,0 +1,517 @@
+use std::fmt;
+use std::sync::Arc;
+
+use bril_rs::{Literal, Type};
+use fxhash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::interp::RuntimeValue;
+
+/// A pointer into the heap, which is the allocation it points into along with how far into that allocation it points.
+///
+/// No allocation is larger than [`i32::MAX`] values so that a pointer fits in a `Value` alongside nothing else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pointer {
+  /// Which allocation this points into. The low 24 bits are the slot the heap keeps the allocation in, which is handed out again once it is freed, and the high 8 bits count how many allocations were in that slot before it, so that a pointer into a freed allocation never points into the one which took its place
+  pub base: u32,
+  /// How many values into the allocation this points
+  pub offset: i32,
+}
+
+/// A single Bril value, which is always 64 bits in release builds.
+///
+/// The type checker has already worked out the type of every variable before a program runs, so unlike `brili` a value doesn't need to carry its type around with it. Ints and floats use all 64 bits, so there is no room left over for NaN-boxing tags anyway. Each value is instead just the bits of whatever it holds: ints and floats as themselves, bools as 0 or 1, chars as their code point, strings as their number in the [`Strings`] of the interpreter, threads as their number in the order they were spawned, and pointers with their base in the upper half and their offset in the lower half. Values are read back out with the accessor for the type the program says they have, and printed with [`Value::display`] given that same type.
+///
+/// Debug builds, and release builds with the `tagged-values` feature, use a tagged enum instead. It has the same interface but checks every access against the tag, which catches a value being read as the wrong type and variables being read before they are defined.
+#[cfg(not(any(debug_assertions, feature = "tagged-values")))]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Value(u64);
+
+#[cfg(not(any(debug_assertions, feature = "tagged-values")))]
+impl Value {
+  #[inline(always)]
+  pub const fn int(i: i64) -> Self {
+    Self(i as u64)
+  }
+
+  #[inline(always)]
+  pub const fn bool(b: bool) -> Self {
+    Self(b as u64)
+  }
+
+  #[inline(always)]
+  pub const fn float(f: f64) -> Self {
+    Self(f.to_bits())
+  }
+
+  #[inline(always)]
+  pub const fn char(c: char) -> Self {
+    Self(c as u64)
+  }
+
+  #[inline(always)]
+  pub const fn pointer(p: Pointer) -> Self {
+    Self(((p.base as u64) << 32) | p.offset as u32 as u64)
+  }
+
+  #[inline(always)]
+  const fn string(id: u32) -> Self {
+    Self(id as u64)
+  }
+
+  #[inline(always)]
+  pub const fn thread(id: u32) -> Self {
+    Self(id as u64)
+  }
+
+  #[inline(always)]
+  pub const fn as_int(self) -> i64 {
+    self.0 as i64
+  }
+
+  #[inline(always)]
+  pub const fn as_bool(self) -> bool {
+    self.0 != 0
+  }
+
+  #[inline(always)]
+  pub const fn as_float(self) -> f64 {
+    f64::from_bits(self.0)
+  }
+
+  #[inline(always)]
+  pub const fn as_char(self) -> char {
+    // This is safe because only chars are ever read back out as chars after type checking
+    unsafe { char::from_u32_unchecked(self.0 as u32) }
+  }
+
+  #[inline(always)]
+  pub const fn as_pointer(self) -> Pointer {
+    Pointer {
+      base: (self.0 >> 32) as u32,
+      offset: self.0 as u32 as i32,
+    }
+  }
+
+  #[inline(always)]
+  const fn as_string(self) -> u32 {
+    self.0 as u32
+  }
+
+  // Whether the two values have the same bits, for noticing which variables have changed
+  pub(crate) const fn same(self, other: Self) -> bool {
+    self.0 == other.0
+  }
+
+  #[inline(always)]
+  pub const fn as_thread(self) -> u32 {
+    self.0 as u32
+  }
+}
+
+#[cfg(any(debug_assertions, feature = "tagged-values"))]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum Value {
+  Int(i64),
+  Bool(bool),
+  // Floats are saved as their bits since JSON has no NaN or infinity
+  Float(
+    #[serde(
+      serialize_with = "serialize_float_bits",
+      deserialize_with = "deserialize_float_bits"
+    )]
+    f64,
+  ),
+  Char(char),
+  Pointer(Pointer),
+  Str(u32),
+  Thread(u32),
+  #[default]
+  Undefined,
+}
+
+#[cfg(any(debug_assertions, feature = "tagged-values"))]
+fn serialize_float_bits<S: serde::Serializer>(f: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+  serializer.serialize_u64(f.to_bits())
+}
+
+#[cfg(any(debug_assertions, feature = "tagged-values"))]
+fn deserialize_float_bits<'de, D: serde::Deserializer<'de>>(
+  deserializer: D,
+) -> Result<f64, D::Error> {
+  u64::deserialize(deserializer).map(f64::from_bits)
+}
+
+#[cfg(any(debug_assertions, feature = "tagged-values"))]
+impl Value {
+  #[inline(always)]
+  pub const fn int(i: i64) -> Self {
+    Self::Int(i)
+  }
+
+  #[inline(always)]
+  pub const fn bool(b: bool) -> Self {
+    Self::Bool(b)
+  }
+
+  #[inline(always)]
+  pub const fn float(f: f64) -> Self {
+    Self::Float(f)
+  }
+
+  #[inline(always)]
+  pub const fn char(c: char) -> Self {
+    Self::Char(c)
+  }
+
+  #[inline(always)]
+  pub const fn pointer(p: Pointer) -> Self {
+    Self::Pointer(p)
+  }
+
+  #[inline(always)]
+  const fn string(id: u32) -> Self {
+    Self::Str(id)
+  }
+
+  #[inline(always)]
+  pub const fn thread(id: u32) -> Self {
+    Self::Thread(id)
+  }
+
+  #[inline(always)]
+  pub fn as_int(self) -> i64 {
+    match self {
+      Self::Int(i) => i,
+      _ => self.mismatch("an int"),
+    }
+  }
+
+  #[inline(always)]
+  pub fn as_bool(self) -> bool {
+    match self {
+      Self::Bool(b) => b,
+      _ => self.mismatch("a bool"),
+    }
+  }
+
+  #[inline(always)]
+  pub fn as_float(self) -> f64 {
+    match self {
+      Self::Float(f) => f,
+      _ => self.mismatch("a float"),
+    }
+  }
+
+  #[inline(always)]
+  pub fn as_char(self) -> char {
+    match self {
+      Self::Char(c) => c,
+      _ => self.mismatch("a char"),
+    }
+  }
+
+  #[inline(always)]
+  pub fn as_pointer(self) -> Pointer {
+    match self {
+      Self::Pointer(p) => p,
+      _ => self.mismatch("a pointer"),
+    }
+  }
+
+  #[inline(always)]
+  fn as_string(self) -> u32 {
+    match self {
+      Self::Str(id) => id,
+      _ => self.mismatch("a str"),
+    }
+  }
+
+  #[inline(always)]
+  pub fn as_thread(self) -> u32 {
+    match self {
+      Self::Thread(id) => id,
+      _ => self.mismatch("a thread"),
+    }
+  }
+
+  // Whether the two values have the same bits, for noticing which variables have changed
+  pub(crate) fn same(self, other: Self) -> bool {
+    match (self, other) {
+      (Self::Int(a), Self::Int(b)) => a == b,
+      (Self::Bool(a), Self::Bool(b)) => a == b,
+      (Self::Float(a), Self::Float(b)) => a.to_bits() == b.to_bits(),
+      (Self::Char(a), Self::Char(b)) => a == b,
+      (Self::Pointer(a), Self::Pointer(b)) => a == b,
+      (Self::Str(a), Self::Str(b)) | (Self::Thread(a), Self::Thread(b)) => a == b,
+      (Self::Undefined, Self::Undefined) => true,
+      _ => false,
+    }
+  }
+
+  #[cold]
+  fn mismatch(self, expected: &str) -> ! {
+    panic!("expected {expected} but found {self:?}, which the type checker should have ruled out")
+  }
+}
+
+impl Value {
+  /// Shows the value as ```ty```, which has to be the type it was created with, looking up strings in ```strings```
+  #[inline(always)]
+  pub const fn display<'a>(self, ty: &'a Type, strings: &'a Strings) -> Typed<'a> {
+    Typed {
+      value: self,
+      ty,
+      strings,
+    }
+  }
+
+  /// The value of the `const` instruction with ```value``` and ```const_type```, which promotes integer literals of floats and interns strings in ```strings```
+  #[inline(always)]
+  pub fn constant(value: &Literal, const_type: &Type, strings: &mut Strings) -> Self {
+    match (value, const_type) {
+      // JSON can't tell a `str` of one character from a `char`
+      (Literal::Char(c), Type::Str) => strings.intern(c.encode_utf8(&mut [0; 4])),
+      (Literal::Str(s), _) => strings.intern(s),
+      // Every other constant is a scalar
+      _ => Self::scalar(value, const_type).unwrap_or_default(),
+    }
+  }
+
+  // The value of the `const` instruction with ```value``` and ```const_type``` unless it is a string, which doesn't have a value until it is interned. This is the same for every run of a program, so it can be worked out before the program runs
+  pub const fn scalar(value: &Literal, const_type: &Type) -> Option<Self> {
+    match (value, const_type) {
+      (Literal::Int(i), Type::Float) => Some(Self::float(*i as f64)),
+      (Literal::Int(i), _) => Some(Self::int(*i)),
+      (Literal::Bool(b), _) => Some(Self::bool(*b)),
+      (Literal::Float(f), _) => Some(Self::float(*f)),
+      (Literal::Char(_), Type::Str) | (Literal::Str(_), _) => None,
+      (Literal::Char(c), _) => Some(Self::char(*c)),
+    }
+  }
+}
+
+/// A [`Value`] along with its type so that it can be printed, from [`Value::display`]
+pub struct Typed<'a> {
+  value: Value,
+  ty: &'a Type,
+  strings: &'a Strings,
+}
+
+impl fmt::Display for Typed<'_> {
+  // The same as RuntimeValue, so that `print` shows values exactly like brili
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    RuntimeValue::new(self.value, self.ty, self.strings).fmt(f)
+  }
+}
+
+/// Every `str` which a program has made, each kept once no matter how many times it is made.
+///
+/// A string value is its number here, so strings are as cheap to copy and compare as any other value. Nothing is ever removed, so every string a program makes stays around until the interpreter is done with it.
+#[derive(Debug, Clone, Default)]
+pub struct Strings {
+  table: Vec<Arc<str>>,
+  ids: FxHashMap<Arc<str>, u32>,
+}
+
+impl Strings {
+  // The table holding ```table``` in order, as saved by Strings::to_vec
+  pub(crate) fn from_vec(table: Vec<String>) -> Self {
+    let mut strings = Self::default();
+    for s in table {
+      let s: Arc<str> = s.into();
+      strings
+        .ids
+        .entry(Arc::clone(&s))
+        .or_insert(strings.table.len() as u32);
+      strings.table.push(s);
+    }
+    strings
+  }
+
+  pub(crate) fn to_vec(&self) -> Vec<String> {
+    self.table.iter().map(ToString::to_string).collect()
+  }
+
+  // The value of ```s```, which is the same as that of any equal string made before
+  pub(crate) fn intern(&mut self, s: &str) -> Value {
+    if let Some(id) = self.ids.get(s) {
+      return Value::string(*id);
+    }
+    // Running out of numbers would take hundreds of gigabytes of strings first
+    let id = self.table.len() as u32;
+    let s: Arc<str> = s.into();
+    self.table.push(Arc::clone(&s));
+    self.ids.insert(s, id);
+    Value::string(id)
+  }
+
+  // The text of the string ```value```, which has to be a `str`
+  #[inline(always)]
+  pub(crate) fn get(&self, value: Value) -> &str {
+    &self.table[value.as_string() as usize]
+  }
+
+  // The same as Strings::get, but shared instead of borrowed
+  pub(crate) fn shared(&self, value: Value) -> Arc<str> {
+    Arc::clone(&self.table[value.as_string() as usize])
+  }
+}
+
+impl From<&Value> for i64 {
+  #[inline(always)]
+  fn from(value: &Value) -> Self {
+    value.as_int()
+  }
+}
+
+impl From<&Value> for bool {
+  #[inline(always)]
+  fn from(value: &Value) -> Self {
+    value.as_bool()
+  }
+}
+
+impl From<&Value> for f64 {
+  #[inline(always)]
+  fn from(value: &Value) -> Self {
+    value.as_float()
+  }
+}
+
+impl From<&Value> for char {
+  #[inline(always)]
+  fn from(value: &Value) -> Self {
+    value.as_char()
+  }
+}
+
+impl From<&Value> for Pointer {
+  #[inline(always)]
+  fn from(value: &Value) -> Self {
+    value.as_pointer()
+  }
+}
+
+// Formats ```v``` the same way as JavaScript's `Number.prototype.toString` so that the output matches brili
+pub fn format_float(v: f64) -> String {
+  if v.is_nan() {
+    return "NaN".to_string();
+  } else if v == 0.0 {
+    // Including negative zero
+    return "0".to_string();
+  } else if v.is_infinite() {
+    return if v > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+  }
+
+  // LowerExp gives the shortest digits which round trip, as in "1.2345e-7"
+  let sign = if v < 0.0 { "-" } else { "" };
+  let shortest = format!("{:e}", v.abs());
+  // There can be more than one number with that many digits which rounds trip, and JavaScript picks the one closest to ```v```, which is ```v``` rounded to that many digits as long as it still round trips
+  let k = shortest.split_once('e').unwrap().0.replace('.', "").len();
+  let closest = format!("{:.*e}", k - 1, v.abs());
+  let exp_form = if closest.parse() == Ok(v.abs()) {
+    closest
+  } else {
+    shortest
+  };
+  let (mantissa, exp) = exp_form.split_once('e').unwrap();
+  let digits = mantissa.replace('.', "");
+  let k = digits.len() as i64;
+  // The decimal point goes after the first n digits
+  let n = exp.parse::<i64>().unwrap() + 1;
+
+  let body = if k <= n && n <= 21 {
+    format!("{digits}{}", "0".repeat((n - k) as usize))
+  } else if 0 < n && n <= 21 {
+    let (int, frac) = digits.split_at(n as usize);
+    format!("{int}.{frac}")
+  } else if -6 < n && n <= 0 {
+    format!("0.{}{digits}", "0".repeat(-n as usize))
+  } else {
+    let exp_sign = if n > 0 { "+" } else { "-" };
+    let (first, rest) = digits.split_at(1);
+    let rest = if rest.is_empty() {
+      String::new()
+    } else {
+      format!(".{rest}")
+    };
+    format!("{first}{rest}e{exp_sign}{}", (n - 1).abs())
+  };
+  format!("{sign}{body}")
+}
+
+// Whether JavaScript counts ```c``` as white space that `parseInt` and `parseFloat` skip, which is almost the same as Unicode's White_Space
+const fn is_js_space(c: char) -> bool {
+  (c.is_whitespace() && c != '\u{85}') || c == '\u{feff}'
+}
+
+// Parses ```s``` the same way as brili parses an `int` argument, which is JavaScript's `parseInt` turned into a BigInt. So leading white space and anything after the digits are ignored, `0x` starts hexadecimal, and ints too long to be exact as a double are rounded like one. The only exception is an int which rounds to just past the largest `int`, like that largest `int` itself, which keeps its exact value instead of being refused. None if there are no digits or the int doesn't fit in 64 bits
+pub fn parse_int(s: &str) -> Option<i64> {
+  let s = s.trim_start_matches(is_js_space);
+  let (negative, s) = match s.as_bytes().first() {
+    Some(b'-') => (true, &s[1..]),
+    Some(b'+') => (false, &s[1..]),
+    _ => (false, s),
+  };
+  let (radix, s) = s
+    .strip_prefix("0x")
+    .or_else(|| s.strip_prefix("0X"))
+    .map_or((10, s), |hex| (16, hex));
+  let digits = s.find(|c: char| !c.is_digit(radix)).unwrap_or(s.len());
+  if digits == 0 {
+    return None;
+  }
+  // Anything that doesn't fit in 128 bits is far out of range anyway
+  let exact = s[..digits].chars().try_fold(0u128, |n, c| {
+    n.checked_mul(radix.into())?
+      .checked_add(c.to_digit(radix)?.into())
+  })?;
+  let rounded = if negative {
+    -(exact as f64)
+  } else {
+    exact as f64
+  };
+  // -2^63 is the only double of at least 2^63 in magnitude which fits
+  if (-(2f64.powi(63))..2f64.powi(63)).contains(&rounded) {
+    return Some(rounded as i64);
+  }
+  let exact = i128::try_from(exact).ok()?;
+  i64::try_from(if negative { -exact } else { exact }).ok()
+}
+
+// Parses ```s``` the same way as brili parses a `float` argument, which is JavaScript's `parseFloat`. So leading white space and anything after the number are ignored, `Infinity` is infinite, and there is no NaN. None if it doesn't start with a number
+pub fn parse_float(s: &str) -> Option<f64> {
+  let s = s.trim_start_matches(is_js_space);
+  let bytes = s.as_bytes();
+  let mut end = usize::from(matches!(bytes.first(), Some(b'-' | b'+')));
+  if s[end..].starts_with("Infinity") {
+    return Some(if s.starts_with('-') {
+      f64::NEG_INFINITY
+    } else {
+      f64::INFINITY
+    });
+  }
+  let digits_from = |i: usize| bytes[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+  let whole = digits_from(end);
+  end += whole;
+  let mut fraction = 0;
+  if bytes.get(end) == Some(&b'.') {
+    fraction = digits_from(end + 1);
+    if whole + fraction > 0 {
+      end += 1 + fraction;
+    }
+  }
+  if whole + fraction == 0 {
+    return None;
+  }
+  // An exponent only counts if it has digits
+  if matches!(bytes.get(end), Some(b'e' | b'E')) {
+    let sign = usize::from(matches!(bytes.get(end + 1), Some(b'-' | b'+')));
+    let exponent = digits_from(end + 1 + sign);
+    if exponent > 0 {
+      end += 1 + sign + exponent;
+    }
+  }
+  s[..end].parse().ok()
+}
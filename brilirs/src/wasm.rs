@@ -0,0 +1,36 @@
+use std::error::Error;
+
+use bril_rs::{AbstractProgram, Program};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::basic_block::BBProgram;
+use crate::{check, interp};
+
+fn run(json: &str, args: &[String], out: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+  // load_abstract_program_from_read panics on malformed JSON, which would abort the whole module
+  let prog: Program = serde_json::from_str::<AbstractProgram>(json)?.try_into()?;
+  let bbprog = BBProgram::new(prog)?;
+  check::type_check(&bbprog)?;
+  interp::execute_main(&bbprog, out, args, false)?;
+  Ok(())
+}
+
+/// Runs the Bril program given as JSON with ```args``` as the arguments to `main` and returns everything it printed.
+///
+/// Since there is no stderr to report to, a program which fails to parse, type check, or run has the error appended as a final `error: ...` line, after whatever it printed before failing.
+#[must_use]
+pub fn run_program(json: &str, args: &[String]) -> String {
+  let mut out = Vec::new();
+  if let Err(e) = run(json, args, &mut out) {
+    out.extend_from_slice(format!("error: {e}\n").as_bytes());
+  }
+  String::from_utf8_lossy(&out).into_owned()
+}
+
+// wasm-bindgen can pass an array of strings from JavaScript as a Vec but not as a slice
+#[doc(hidden)]
+#[wasm_bindgen(js_name = run_program)]
+#[must_use]
+pub fn run_program_js(json: &str, args: Vec<String>) -> String {
+  run_program(json, &args)
+}
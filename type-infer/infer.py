@@ -4,7 +4,7 @@ import json
 import sys
 import copy
 
-ARITHMETIC_OPS = ["add", "mul", "sub", "div"]
+ARITHMETIC_OPS = ["add", "mul", "sub", "div", "rem"]
 COMPARISON_OPS = ["eq", "lt", "gt", "le", "ge"]
 LOGIC_OPS = ["not", "and", "or"]
 